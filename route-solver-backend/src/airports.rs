@@ -0,0 +1,70 @@
+//! Airport locations and time zones, so the frontend can draw solved routes on a map and the router can work out which
+//! day flights land.
+//!
+//! The directory is a CSV of major airports compiled into the binary. Codes it doesn't know about are left out of
+//! lookups rather than treated as errors, the map just won't show them.
+
+use std::{collections::HashMap, io::Read};
+
+pub use route_solver_shared::queries::great_circle_km;
+use route_solver_shared::queries::Airport;
+
+const EMBEDDED_CSV: &str = include_str!("../data/airports.csv");
+
+pub struct AirportDirectory {
+    by_iata: HashMap<String, Airport>,
+}
+
+impl AirportDirectory {
+    /// Directory of the airports bundled with the server
+    pub fn embedded() -> Result<AirportDirectory, csv::Error> {
+        Self::from_csv(EMBEDDED_CSV.as_bytes())
+    }
+
+    /// Read a directory from CSV with a `iata,name,city,country,lat,lon,tz` header, the time zone column is optional
+    pub fn from_csv(reader: impl Read) -> Result<AirportDirectory, csv::Error> {
+        let by_iata = csv::Reader::from_reader(reader)
+            .deserialize::<Airport>()
+            .map(|airport| airport.map(|a| (a.iata.clone(), a)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(AirportDirectory { by_iata })
+    }
+
+    pub fn get(&self, iata: &str) -> Option<&Airport> {
+        self.by_iata.get(&iata.to_uppercase())
+    }
+
+    /// The known airports out of `codes`, in the order asked for
+    pub fn lookup<'a>(&self, codes: impl IntoIterator<Item = &'a str>) -> Vec<Airport> {
+        codes.into_iter().filter_map(|code| self.get(code.trim())).cloned().collect()
+    }
+
+    /// Every airport, sorted by code
+    pub fn all(&self) -> Vec<Airport> {
+        let mut airports: Vec<Airport> = self.by_iata.values().cloned().collect();
+        airports.sort_by(|a, b| a.iata.cmp(&b.iata));
+        airports
+    }
+}
+
+#[cfg(test)]
+mod airports_tests {
+    use super::AirportDirectory;
+
+    #[test]
+    fn test_embedded_lookup() {
+        let airports = AirportDirectory::embedded().unwrap();
+
+        let found = airports.lookup(["yvr", "NOPE", " YYZ"]);
+        let codes: Vec<&str> = found.iter().map(|a| a.iata.as_str()).collect();
+        assert_eq!(codes, ["YVR", "YYZ"]);
+        assert!((found[1].lat - 43.68).abs() < 0.01);
+        assert_eq!(found[1].tz.as_deref(), Some("America/Toronto"));
+
+        // Every airport the mock pricing data knows about can be drawn
+        for code in ["YYZ", "YVR", "YYC", "SEA"] {
+            assert!(airports.get(code).is_some(), "{} missing", code);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Which day flights land, in the destination's local time, so a stay's length counts the nights actually spent there.
+//!
+//! Providers with schedules say when a flight lands, see [Quote::arrives]. Indicative prices don't, so otherwise the
+//! flight is assumed to leave at [ASSUMED_DEPARTURE_HOUR] local time and its length is estimated from the great circle
+//! distance between the airports. It's rough, but enough to catch a long haul flight east landing the next morning.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use route_solver_shared::queries::{AirportCode, Date, Flight};
+
+use crate::{
+    airports::{great_circle_km, AirportDirectory},
+    flight_api::Quote,
+};
+
+/// Local hour unscheduled flights are assumed to leave at, long haul flights mostly leave in the evening
+pub const ASSUMED_DEPARTURE_HOUR: u32 = 18;
+
+/// Average speed gate to gate, with [OVERHEAD_MINUTES] covering taxiing, the climb and the descent
+const CRUISE_KMH: f64 = 800.0;
+const OVERHEAD_MINUTES: i64 = 30;
+
+/// Gate to gate minutes a flight of `distance_km` is estimated to take
+pub fn flight_minutes(distance_km: f64) -> i64 {
+    (distance_km / CRUISE_KMH * 60.0) as i64 + OVERHEAD_MINUTES
+}
+
+struct Located {
+    tz: Tz,
+    lat: f64,
+    lon: f64,
+}
+
+pub struct ArrivalClock {
+    airports: HashMap<AirportCode, Located>,
+}
+
+impl ArrivalClock {
+    /// Clock for the airports in `directory` which have a valid code and time zone, flights to or from any others are
+    /// assumed to land the day they leave
+    pub fn new(directory: &AirportDirectory) -> ArrivalClock {
+        let airports = directory
+            .all()
+            .into_iter()
+            .filter_map(|airport| {
+                let code = airport.iata.parse().ok()?;
+                let tz = airport.tz.as_deref()?.parse().ok()?;
+                Some((code, Located { tz, lat: airport.lat, lon: airport.lon }))
+            })
+            .collect();
+
+        ArrivalClock { airports }
+    }
+
+    /// Day `flight` lands at its destination, never before the day it leaves
+    pub fn arrival_date(&self, flight: &Flight, quote: &Quote) -> Date {
+        let arrives = match quote.arrives {
+            Some(arrives) => Some(arrives.date()),
+            None => self.estimate(flight),
+        };
+        arrives.map_or(flight.date, |arrives| arrives.max(flight.date))
+    }
+
+    fn estimate(&self, flight: &Flight) -> Option<Date> {
+        let (src, dest) = (self.airports.get(&flight.src)?, self.airports.get(&flight.dest)?);
+        let departs = flight.date.and_time(NaiveTime::from_hms_opt(ASSUMED_DEPARTURE_HOUR, 0, 0)?);
+        // Clocks going forward can skip the hour, going back repeats it
+        let departs = src.tz.from_local_datetime(&departs).earliest()?;
+
+        let distance = great_circle_km((src.lat, src.lon), (dest.lat, dest.lon));
+        let lands = departs + chrono::Duration::minutes(flight_minutes(distance));
+        Some(lands.with_timezone(&dest.tz).date_naive())
+    }
+}
+
+#[cfg(test)]
+mod arrivals_tests {
+    use chrono::NaiveDate;
+    use route_solver_shared::queries::{Date, Flight};
+
+    use super::ArrivalClock;
+    use crate::{airports::AirportDirectory, flight_api::Quote};
+
+    fn flight(src: &str, dest: &str) -> Flight {
+        Flight {
+            src: src.parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_arrival_date() {
+        let clock = ArrivalClock::new(&AirportDirectory::embedded().unwrap());
+        let unscheduled = Quote {
+            min_price: 500.0,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.0,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        let next_day = Date::from_ymd_opt(2023, 2, 2).unwrap();
+
+        // An evening across the Atlantic lands the next morning, across the country the same night
+        assert_eq!(clock.arrival_date(&flight("YYZ", "LHR"), &unscheduled), next_day);
+        assert_eq!(clock.arrival_date(&flight("YYZ", "YVR"), &unscheduled), flight("YYZ", "YVR").date);
+        // Westward across the date line the flight lands the day it left, on the clock
+        assert_eq!(clock.arrival_date(&flight("SYD", "LAX"), &unscheduled), flight("SYD", "LAX").date);
+
+        // Schedules win over the estimate, and airports without a time zone land the day they leave
+        let scheduled = Quote {
+            arrives: NaiveDate::from_ymd_opt(2023, 2, 2).unwrap().and_hms_opt(1, 15, 0),
+            ..unscheduled
+        };
+        assert_eq!(clock.arrival_date(&flight("YYZ", "YVR"), &scheduled), next_day);
+        assert_eq!(clock.arrival_date(&flight("YYZ", "XXX"), &unscheduled), flight("YYZ", "XXX").date);
+    }
+}
@@ -0,0 +1,283 @@
+//! Audit log of who solved what and when, the provider calls it took and what was exported or sent out, for shared
+//! deployments and for settling billing disputes with providers.
+//!
+//! Entries are only ever appended. With `[audit] path` set they're written to that file as JSON lines and survive
+//! restarts, otherwise the latest [MAX_MEMORY_ENTRIES] are kept in memory. The admin endpoint reads them back through an
+//! [AuditFilter].
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::NaiveDateTime;
+use route_solver_shared::queries::{Date, Flight, JobId, RouteQuery};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{config::AuditConfig, log, request_id};
+
+/// Entries kept without an audit file, the oldest are forgotten first
+pub const MAX_MEMORY_ENTRIES: usize = 100_000;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Could not access the audit log.")]
+    Io(std::io::Error),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// When it happened, in UTC
+    pub at: NaiveDateTime,
+    /// Tenant it happened for, `None` for the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// User it was charged to or admin who did it, see [user_id], `None` for solves and tasks without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Request it happened in, to find it in the server logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A solve was queued
+    Solve { job: JobId, query: Box<RouteQuery> },
+    /// A call went out to the pricing provider, which the provider bills for whatever it answered
+    ProviderCall {
+        /// Solve it was made for, `None` outside of one, e.g. a price calendar
+        job: Option<JobId>,
+        flight: Flight,
+        outcome: CallOutcome,
+    },
+    /// An admin exported data, `what` being the table or `snapshot`
+    Export { what: String },
+    /// A scheduled task's alert went out, `flight` is `None` for a re-solved itinerary's total
+    Alert {
+        task: String,
+        price: f32,
+        currency: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        flight: Option<Flight>,
+    },
+}
+
+/// How a user is written to the log: IP addresses and admins as they are, API keys as `key:` and the start of their
+/// SHA-256 so the log never holds a key someone could use
+pub fn user_id(user: &str) -> String {
+    if user.starts_with("ip:") || user.starts_with("admin:") {
+        return user.to_string();
+    }
+    format!("key:{}", &hex::encode(Sha256::digest(user))[..16])
+}
+
+impl AuditEvent {
+    /// Name of the event's kind, as it's tagged in the log
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuditEvent::Solve { .. } => "solve",
+            AuditEvent::ProviderCall { .. } => "provider_call",
+            AuditEvent::Export { .. } => "export",
+            AuditEvent::Alert { .. } => "alert",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CallOutcome {
+    Quoted,
+    NoFare,
+    Failed,
+}
+
+/// Which entries [AuditLog::entries] reads back, everything left unset matches
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuditFilter {
+    /// First and last days, inclusive
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+    pub tenant: Option<String>,
+    pub user: Option<String>,
+    /// See [AuditEvent::kind]
+    pub kind: Option<String>,
+    /// Most entries read back, the latest ones
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let day = entry.at.date();
+        self.from.is_none_or(|from| day >= from)
+            && self.to.is_none_or(|to| day <= to)
+            && self.tenant.as_ref().is_none_or(|tenant| entry.tenant.as_ref() == Some(tenant))
+            // Either the user as recorded or their API key
+            && self.user.as_deref().is_none_or(|user| {
+                entry.user.as_deref() == Some(user) || entry.user.as_deref() == Some(&user_id(user))
+            })
+            && self.kind.as_deref().is_none_or(|kind| entry.event.kind() == kind)
+    }
+}
+
+enum Backend {
+    File { path: PathBuf, file: Mutex<File> },
+    Memory(Mutex<VecDeque<AuditEntry>>),
+}
+
+pub struct AuditLog {
+    backend: Backend,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog {
+            backend: Backend::Memory(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl AuditLog {
+    /// Log appending to the file at `path`, created if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<AuditLog, AuditError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(AuditError::Io)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(AuditError::Io)?;
+        Ok(AuditLog {
+            backend: Backend::File {
+                path: path.to_path_buf(),
+                file: Mutex::new(file),
+            },
+        })
+    }
+
+    pub fn from_config(config: &AuditConfig) -> Result<AuditLog, AuditError> {
+        match &config.path {
+            Some(path) => AuditLog::open(path),
+            None => Ok(AuditLog::default()),
+        }
+    }
+
+    /// Append `event`, which happened just now in the current request, with `user` recorded as its [user_id]. Failing
+    /// to write it is logged, whatever it records has already happened.
+    pub fn record(&self, tenant: Option<&str>, user: Option<&str>, event: AuditEvent) {
+        let entry = AuditEntry {
+            at: chrono::Utc::now().naive_utc(),
+            tenant: tenant.map(str::to_string),
+            user: user.map(user_id),
+            request_id: request_id::current(),
+            event,
+        };
+
+        match &self.backend {
+            Backend::File { file, .. } => {
+                let mut line = serde_json::to_vec(&entry).expect("Audit entries always serialize");
+                line.push(b'\n');
+                // One write per line so entries from different threads never interleave
+                if let Err(e) = file.lock().unwrap().write_all(&line) {
+                    log!("Failed to write {} to the audit log: {}", entry.event.kind(), e);
+                }
+            }
+            Backend::Memory(entries) => {
+                let mut entries = entries.lock().unwrap();
+                entries.push_back(entry);
+                if entries.len() > MAX_MEMORY_ENTRIES {
+                    entries.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Entries `filter` matches, oldest first
+    pub fn entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, AuditError> {
+        let mut matching: Vec<AuditEntry> = match &self.backend {
+            Backend::File { path, .. } => {
+                let mut matching = Vec::new();
+                for line in BufReader::new(File::open(path).map_err(AuditError::Io)?).lines() {
+                    let line = line.map_err(AuditError::Io)?;
+                    // A line cut short by a crash is skipped, the ones after it are whole
+                    match serde_json::from_str::<AuditEntry>(&line) {
+                        Ok(entry) if filter.matches(&entry) => matching.push(entry),
+                        Ok(_) => {}
+                        Err(e) => log!("Skipping unreadable audit log line: {}", e),
+                    }
+                }
+                matching
+            }
+            Backend::Memory(entries) => entries.lock().unwrap().iter().filter(|e| filter.matches(e)).cloned().collect(),
+        };
+
+        if let Some(limit) = filter.limit {
+            matching.drain(..matching.len().saturating_sub(limit));
+        }
+        Ok(matching)
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use route_solver_shared::queries::{Date, Flight};
+
+    use super::{user_id, AuditEvent, AuditFilter, AuditLog, CallOutcome};
+
+    #[test]
+    fn test_audit_log_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("log.jsonl");
+        let call = |job| AuditEvent::ProviderCall {
+            job,
+            flight: Flight {
+                src: "YYZ".parse().unwrap(),
+                dest: "YVR".parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 6, 1).unwrap(),
+            },
+            outcome: CallOutcome::Quoted,
+        };
+        {
+            let log = AuditLog::open(&path).unwrap();
+            log.record(Some("acme"), Some("key"), call(Some(1)));
+            log.record(None, None, AuditEvent::Export { what: "quotes".to_string() });
+        }
+
+        // Appended to after a restart, not overwritten
+        let log = AuditLog::open(&path).unwrap();
+        log.record(Some("acme"), Some("key"), call(Some(2)));
+        let all = log.entries(&AuditFilter::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert!(matches!(&all[1].event, AuditEvent::Export { what } if what == "quotes"));
+
+        let acme_calls = AuditFilter {
+            tenant: Some("acme".to_string()),
+            kind: Some("provider_call".to_string()),
+            limit: Some(1),
+            ..AuditFilter::default()
+        };
+        let latest = log.entries(&acme_calls).unwrap();
+        assert_eq!(latest.len(), 1);
+        assert!(matches!(latest[0].event, AuditEvent::ProviderCall { job: Some(2), .. }));
+        // The key isn't written down, but it still finds its entries
+        assert_eq!(latest[0].user, Some(user_id("key")));
+        let by_key = AuditFilter {
+            user: Some("key".to_string()),
+            ..AuditFilter::default()
+        };
+        assert_eq!(log.entries(&by_key).unwrap().len(), 2);
+        assert_eq!(user_id("ip:127.0.0.1"), "ip:127.0.0.1");
+
+        let in_memory = AuditLog::default();
+        in_memory.record(None, None, call(None));
+        assert!(in_memory.entries(&acme_calls).unwrap().is_empty());
+        assert_eq!(in_memory.entries(&AuditFilter::default()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+//! Checked bag fees, so a trip asking for a bag is priced on what flying with one costs rather than on headline fares
+//! budget carriers leave bags out of.
+//!
+//! Providers with fare families quote what a bag adds themselves, see [Quote::bag_fee]. For the rest it's estimated
+//! from the carrier's fee in the config, or a default for carriers it doesn't list and quotes which don't say who flies
+//! them. Fees are per passenger and flight in [BASE_CURRENCY](route_solver_shared::queries::BASE_CURRENCY), like quotes.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    config::BaggageConfig,
+    flight_api::{CarrierCode, Quote},
+};
+
+#[derive(Debug, Error)]
+pub enum BaggageError {
+    #[error("Bag fee for {0} isn't a price.")]
+    InvalidFee(String),
+}
+
+pub struct BagFees {
+    default_fee: f32,
+    carriers: HashMap<CarrierCode, f32>,
+}
+
+impl Default for BagFees {
+    fn default() -> Self {
+        BagFees::from_config(&BaggageConfig::default()).expect("Default bag fees are prices")
+    }
+}
+
+impl BagFees {
+    pub fn from_config(config: &BaggageConfig) -> Result<BagFees, BaggageError> {
+        let is_price = |fee: f32| fee.is_finite() && fee >= 0.0;
+        if !is_price(config.default_fee) {
+            return Err(BaggageError::InvalidFee("carriers not listed".to_string()));
+        }
+        if let Some((carrier, _)) = config.carriers.iter().find(|(_, fee)| !is_price(**fee)) {
+            return Err(BaggageError::InvalidFee(carrier.to_string()));
+        }
+
+        Ok(BagFees {
+            default_fee: config.default_fee,
+            carriers: config.carriers.clone(),
+        })
+    }
+
+    /// What a checked bag adds to `quote`, the provider's own fee if it quoted one
+    pub fn fee(&self, quote: &Quote) -> f32 {
+        quote.bag_fee.unwrap_or_else(|| {
+            quote
+                .carrier
+                .and_then(|carrier| self.carriers.get(&carrier).copied())
+                .unwrap_or(self.default_fee)
+        })
+    }
+}
+
+#[cfg(test)]
+mod baggage_tests {
+    use std::collections::HashMap;
+
+    use super::BagFees;
+    use crate::{config::BaggageConfig, flight_api::Quote};
+
+    #[test]
+    fn test_bag_fees() {
+        let config = BaggageConfig {
+            default_fee: 30.0,
+            carriers: HashMap::from([("nk".parse().unwrap(), 55.0)]),
+        };
+        let fees = BagFees::from_config(&config).unwrap();
+        let quote = |carrier: Option<&str>, bag_fee| Quote {
+            min_price: 49.0,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.0,
+            currency: None,
+            carrier: carrier.map(|c| c.parse().unwrap()),
+            bag_fee,
+            fallback: false,
+        };
+
+        assert_eq!(fees.fee(&quote(Some("NK"), None)), 55.0);
+        assert_eq!(fees.fee(&quote(Some("AC"), None)), 30.0);
+        assert_eq!(fees.fee(&quote(None, None)), 30.0);
+        // The provider knows better than the estimate
+        assert_eq!(fees.fee(&quote(Some("NK"), Some(42.5))), 42.5);
+
+        let config = BaggageConfig {
+            carriers: HashMap::from([("FR".parse().unwrap(), -1.0)]),
+            ..config
+        };
+        assert!(BagFees::from_config(&config).is_err());
+    }
+}
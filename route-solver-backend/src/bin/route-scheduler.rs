@@ -0,0 +1,32 @@
+//! Runs the scheduled re-pricing tasks on their own, for when they shouldn't share a process with the web server.
+//!
+//! Reads the same config as the server. Jobs it solves are kept in memory rather than the server's state directory.
+
+use std::{sync::Arc, time::Duration};
+
+use route_solver::{
+    airports::AirportDirectory,
+    config::Config,
+    jobs::JobManager,
+    providers::ProviderRegistry,
+    scheduler::Scheduler,
+};
+
+#[tokio::main]
+async fn main() {
+    let mut config = Config::load().expect("Failed to load server config");
+    // The server resumes whatever it finds in its state directory, which would run these jobs twice
+    config.jobs.state_dir = None;
+
+    let airports = AirportDirectory::embedded().expect("Bundled airport data is malformed");
+    let jobs = Arc::new(JobManager::from_config(&config, &airports, &ProviderRegistry::builtin()));
+    let scheduler = Scheduler::from_config(&config.scheduler, Arc::clone(&jobs)).expect("Invalid scheduler config");
+    println!("Scheduling {} tasks", scheduler.tasks().len());
+
+    let provider = jobs.price_provider();
+    tokio::select! {
+        _ = provider.run_scheduler(&scheduler) => {}
+        _ = tokio::signal::ctrl_c() => println!("Shutting down, draining running solves"),
+    }
+    jobs.shutdown(Duration::from_secs(config.workers.drain_timeout_secs)).await;
+}
@@ -0,0 +1,1022 @@
+//! Price and result caching, shared between solves.
+//!
+//! Storage is abstracted behind the [CacheBackend] key/value trait with in-memory, SQLite and Redis implementations.
+//! The SQLite and Redis backends let several backend instances (or restarts of one) share quotes instead of each
+//! going back to the pricing APIs. [PriceCache] puts the typed quote/result API on top of whichever backend is
+//! configured, and [CachedPriceQuery] wraps any [PriceQuery] so every quote goes through the cache.
+//!
+//! Solves running at the same time often want the same legs, e.g. a batch of re-solves of similar itineraries. A leg
+//! one of them is already fetching isn't fetched again by the others, they wait for its answer instead, so each leg goes
+//! to the provider once and is then cached for its TTL.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use route_solver_shared::queries::{CabinClass, Flight, RouteQuery, RouteSolution};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::{
+    config::{CacheBackendKind, CacheConfig, SimulationConfig},
+    currency::ExchangeRates,
+    flight_api::{PointOfSale, PriceQuery, QueryError, Quote, DEFAULT_MARKET},
+    log,
+    snapshot::{PriceSnapshot, SnapshotFilter, SnapshotLeg, SnapshotQuote, SNAPSHOT_VERSION},
+};
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("Cached value could not be (de)serialized.")]
+    Serde(serde_json::Error),
+    #[cfg(feature = "sqlite-cache")]
+    #[error("SQLite cache error.")]
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "redis-cache")]
+    #[error("Redis cache error.")]
+    Redis(redis::RedisError),
+    #[error("Cache backend {0:?} was not compiled in.")]
+    BackendUnavailable(CacheBackendKind),
+}
+
+/// Plain key/value storage with optional expiry
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<String>, CacheError>;
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), CacheError>;
+    /// Number of live entries
+    fn entry_count(&self) -> Result<u64, CacheError>;
+    /// Every live entry whose key starts with `prefix`, in no particular order
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>, CacheError>;
+}
+
+/// In process storage, optionally bounded. Once full, the least recently used entry makes way for each new one.
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<LruEntries>,
+    /// Most entries kept, unbounded if `None`
+    capacity: Option<usize>,
+}
+
+#[derive(Default)]
+struct LruEntries {
+    /// Value, expiry and when it was last used of each key
+    values: HashMap<String, (String, Option<Instant>, u64)>,
+    /// Keys by when they were last used, least recently first
+    by_use: BTreeMap<u64, String>,
+    /// Bumped on every use
+    clock: u64,
+}
+
+impl LruEntries {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        if let Some((_, _, used)) = self.values.get_mut(key) {
+            self.by_use.remove(used);
+            *used = self.clock;
+            self.by_use.insert(self.clock, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((_, _, used)) = self.values.remove(key) {
+            self.by_use.remove(&used);
+        }
+    }
+}
+
+impl MemoryCache {
+    /// Cache holding at most `capacity` entries
+    pub fn with_capacity(capacity: usize) -> MemoryCache {
+        MemoryCache {
+            entries: Mutex::default(),
+            capacity: Some(capacity.max(1)),
+        }
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Result<Option<String>, CacheError> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.values.get(key) {
+            Some((_, Some(expires), _)) if *expires <= Instant::now() => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Some((value, _, _)) => {
+                let value = value.clone();
+                entries.touch(key);
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), CacheError> {
+        let expires = ttl.map(|t| Instant::now() + t);
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(key);
+        if let Some(capacity) = self.capacity {
+            while entries.values.len() >= capacity {
+                let Some((_, oldest)) = entries.by_use.pop_first() else {
+                    break;
+                };
+                entries.values.remove(&oldest);
+            }
+        }
+
+        entries.values.insert(key.to_string(), (value.to_string(), expires, 0));
+        entries.touch(key);
+        Ok(())
+    }
+
+    fn entry_count(&self) -> Result<u64, CacheError> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.values.values().filter(|(_, expires, _)| expires.is_none_or(|e| e > now)).count() as u64)
+    }
+
+    /// Doesn't count as using the entries, a snapshot shouldn't keep stale quotes from being evicted
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>, CacheError> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .values
+            .iter()
+            .filter(|(key, (_, expires, _))| key.starts_with(prefix) && expires.is_none_or(|e| e > now))
+            .map(|(key, (value, _, _))| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+#[cfg(feature = "sqlite-cache")]
+pub struct SqliteCache {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite-cache")]
+impl SqliteCache {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<SqliteCache, CacheError> {
+        let conn = rusqlite::Connection::open(path).map_err(CacheError::Sqlite)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cache (key TEXT PRIMARY KEY, value TEXT NOT NULL, expires_at INTEGER)",
+            (),
+        )
+        .map_err(CacheError::Sqlite)?;
+
+        Ok(SqliteCache { conn: Mutex::new(conn) })
+    }
+
+    fn unix_now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+}
+
+#[cfg(feature = "sqlite-cache")]
+impl CacheBackend for SqliteCache {
+    fn get(&self, key: &str) -> Result<Option<String>, CacheError> {
+        use rusqlite::OptionalExtension;
+
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM cache WHERE key = ?1 AND (expires_at IS NULL OR expires_at > ?2)",
+                (key, Self::unix_now()),
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(CacheError::Sqlite)
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), CacheError> {
+        let expires_at = ttl.map(|t| Self::unix_now() + t.as_secs() as i64);
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO cache (key, value, expires_at) VALUES (?1, ?2, ?3)",
+                (key, value, expires_at),
+            )
+            .map_err(CacheError::Sqlite)?;
+        Ok(())
+    }
+
+    fn entry_count(&self) -> Result<u64, CacheError> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM cache WHERE expires_at IS NULL OR expires_at > ?1",
+                (Self::unix_now(),),
+                |row| row.get(0),
+            )
+            .map_err(CacheError::Sqlite)
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>, CacheError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT key, value FROM cache WHERE substr(key, 1, length(?1)) = ?1 \
+                 AND (expires_at IS NULL OR expires_at > ?2)",
+            )
+            .map_err(CacheError::Sqlite)?;
+        let rows = stmt
+            .query_map((prefix, Self::unix_now()), |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(CacheError::Sqlite)?;
+        rows.collect::<Result<_, _>>().map_err(CacheError::Sqlite)
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+pub struct RedisCache {
+    client: redis::Client,
+    /// Connected lazily and dropped on error so the next call reconnects
+    conn: Mutex<Option<redis::Connection>>,
+}
+
+#[cfg(feature = "redis-cache")]
+impl RedisCache {
+    pub fn open(url: &str) -> Result<RedisCache, CacheError> {
+        Ok(RedisCache {
+            client: redis::Client::open(url).map_err(CacheError::Redis)?,
+            conn: Mutex::new(None),
+        })
+    }
+
+    fn with_conn<T>(&self, f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>) -> Result<T, CacheError> {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = Some(self.client.get_connection().map_err(CacheError::Redis)?);
+        }
+
+        let res = f(conn.as_mut().unwrap());
+        if res.is_err() {
+            *conn = None;
+        }
+        res.map_err(CacheError::Redis)
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+impl CacheBackend for RedisCache {
+    fn get(&self, key: &str) -> Result<Option<String>, CacheError> {
+        use redis::Commands;
+
+        self.with_conn(|c| c.get(key))
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), CacheError> {
+        use redis::Commands;
+
+        self.with_conn(|c| match ttl {
+            Some(ttl) => c.set_ex(key, value, ttl.as_secs().max(1)),
+            None => c.set(key, value),
+        })
+    }
+
+    /// Size of the whole Redis database, which includes anything else sharing it
+    fn entry_count(&self) -> Result<u64, CacheError> {
+        self.with_conn(|c| redis::cmd("DBSIZE").query(c))
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>, CacheError> {
+        use redis::Commands;
+
+        self.with_conn(|c| {
+            let keys: Vec<String> = c.scan_match(format!("{}*", prefix))?.collect();
+            let mut entries = Vec::with_capacity(keys.len());
+            for key in keys {
+                // Gone if it expired since the scan
+                if let Some(value) = c.get::<_, Option<String>>(&key)? {
+                    entries.push((key, value));
+                }
+            }
+            Ok(entries)
+        })
+    }
+}
+
+const QUOTE_PREFIX: &str = "quote:";
+const NO_FARE_PREFIX: &str = "no-fare:";
+
+/// Typed quote and result cache over a [CacheBackend]
+#[derive(Clone)]
+pub struct PriceCache {
+    backend: Arc<dyn CacheBackend>,
+    quote_ttl: Option<Duration>,
+    result_ttl: Option<Duration>,
+    /// How long a flight with no fare is remembered, shorter than quotes as routes start flying
+    no_fare_ttl: Option<Duration>,
+    /// Prefix of every key, so caches with other namespaces can share the backend without seeing each other's entries
+    namespace: Option<String>,
+    fetches: Arc<InFlight>,
+}
+
+/// What a fetch of a leg came to, for the solves waiting on it
+#[derive(Clone, Copy)]
+enum Fetched {
+    Quote(Quote),
+    NoFare,
+    /// The provider failed, or the fetch was dropped part way, the waiting solves try for themselves
+    Failed,
+}
+
+/// Legs being fetched from the provider right now by their quote key, each with the answer once there is one
+#[derive(Default)]
+struct InFlight(Mutex<HashMap<String, watch::Receiver<Option<Fetched>>>>);
+
+/// See [PriceCache::claim_fetch]
+enum LegFetch {
+    /// Nobody else is fetching the leg, whoever claimed it should and then [FetchGuard::finish]
+    Fetch(FetchGuard),
+    /// Somebody is, their answer comes through here
+    Wait(watch::Receiver<Option<Fetched>>),
+}
+
+/// A claim on fetching a leg, given up when dropped
+struct FetchGuard {
+    key: String,
+    fetches: Arc<InFlight>,
+    answer: watch::Sender<Option<Fetched>>,
+}
+
+impl FetchGuard {
+    fn finish(self, fetched: Fetched) {
+        self.answer.send_replace(Some(fetched));
+    }
+}
+
+impl Drop for FetchGuard {
+    fn drop(&mut self) {
+        // Whoever starts waiting from here on finds the leg cached instead, or fetches it again if it failed
+        self.fetches.0.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl PriceCache {
+    pub fn new(backend: Arc<dyn CacheBackend>, quote_ttl: Option<Duration>, result_ttl: Option<Duration>) -> PriceCache {
+        PriceCache {
+            backend,
+            quote_ttl,
+            result_ttl,
+            no_fare_ttl: quote_ttl,
+            namespace: None,
+            fetches: Arc::default(),
+        }
+    }
+
+    /// Remember flights with no fare for `ttl` rather than as long as quotes
+    pub fn with_no_fare_ttl(mut self, ttl: Option<Duration>) -> PriceCache {
+        self.no_fare_ttl = ttl;
+        self
+    }
+
+    /// Keep entries under `namespace`, apart from those of caches with none or another, e.g. simulated fares from real
+    /// ones
+    pub fn with_namespace(mut self, namespace: Option<&str>) -> PriceCache {
+        self.namespace = namespace.map(str::to_string);
+        self
+    }
+
+    pub fn in_memory() -> PriceCache {
+        PriceCache::new(Arc::new(MemoryCache::default()), None, None)
+    }
+
+    /// Open the backend chosen in the config
+    pub fn from_config(config: &CacheConfig) -> Result<PriceCache, CacheError> {
+        let backend: Arc<dyn CacheBackend> = match config.backend {
+            CacheBackendKind::Memory if config.memory_max_entries > 0 => {
+                Arc::new(MemoryCache::with_capacity(config.memory_max_entries))
+            }
+            CacheBackendKind::Memory => Arc::new(MemoryCache::default()),
+            #[cfg(feature = "sqlite-cache")]
+            CacheBackendKind::Sqlite => Arc::new(SqliteCache::open(&config.sqlite_path)?),
+            #[cfg(feature = "redis-cache")]
+            CacheBackendKind::Redis => Arc::new(RedisCache::open(&config.redis_url)?),
+            #[allow(unreachable_patterns)]
+            kind => return Err(CacheError::BackendUnavailable(kind)),
+        };
+
+        let ttl = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        Ok(PriceCache::new(
+            backend,
+            ttl(config.quote_ttl_secs),
+            ttl(config.result_ttl_secs),
+        )
+        .with_no_fare_ttl(ttl(config.no_fare_ttl_secs)))
+    }
+
+    /// Number of entries in the backend, `None` if it couldn't be asked
+    pub fn entry_count(&self) -> Option<u64> {
+        self.backend
+            .entry_count()
+            .map_err(|e| log!("Cache size lookup failed: {}", e))
+            .ok()
+    }
+
+    /// Quotes are cached per cabin and per market, see [PointOfSale::market]
+    pub fn get_quote(&self, flight: &Flight, cabin: CabinClass, market: &str) -> Option<Quote> {
+        self.get(&Self::quote_key(flight, cabin, market))
+    }
+
+    pub fn put_quote(&self, flight: &Flight, cabin: CabinClass, market: &str, quote: Quote) {
+        self.put(&Self::quote_key(flight, cabin, market), &quote, self.quote_ttl);
+    }
+
+    /// Whether the provider recently had no fare for `flight`
+    pub fn is_no_fare(&self, flight: &Flight, cabin: CabinClass, market: &str) -> bool {
+        self.get::<bool>(&Self::no_fare_key(flight, cabin, market)).unwrap_or(false)
+    }
+
+    pub fn put_no_fare(&self, flight: &Flight, cabin: CabinClass, market: &str) {
+        self.put(&Self::no_fare_key(flight, cabin, market), &true, self.no_fare_ttl);
+    }
+
+    /// Claim fetching `flight` from the provider, unless a solve of the same tenant, `None` for the default, sharing
+    /// the cache is already fetching it. Tenants don't wait on each other's fetches, which are made with other
+    /// credentials and charged to other quotas.
+    fn claim_fetch(&self, flight: &Flight, cabin: CabinClass, market: &str, tenant: Option<&str>) -> LegFetch {
+        let key = format!("{}@{}", Self::quote_key(flight, cabin, market), tenant.unwrap_or_default());
+        let mut fetches = self.fetches.0.lock().unwrap();
+        if let Some(answer) = fetches.get(&key) {
+            return LegFetch::Wait(answer.clone());
+        }
+        let (answer, receiver) = watch::channel(None);
+        fetches.insert(key.clone(), receiver);
+        LegFetch::Fetch(FetchGuard {
+            key,
+            fetches: Arc::clone(&self.fetches),
+            answer,
+        })
+    }
+
+    /// Solution `tenant`, `None` for the default, had for `query`. Tenants only see their own.
+    pub fn get_result(&self, query: &RouteQuery, tenant: Option<&str>) -> Option<RouteSolution> {
+        self.get(&Self::result_key(query, tenant)?)
+    }
+
+    pub fn put_result(&self, query: &RouteQuery, tenant: Option<&str>, solution: &RouteSolution) {
+        if let Some(key) = Self::result_key(query, tenant) {
+            self.put(&key, solution, self.result_ttl);
+        }
+    }
+
+    /// Every quote and no-fare flight cached for the flights `filter` matches
+    pub fn export(&self, filter: &SnapshotFilter) -> Result<PriceSnapshot, CacheError> {
+        let mut quotes = Vec::new();
+        for (key, value) in self.backend.scan(&self.namespaced(QUOTE_PREFIX))? {
+            let leg = self.strip_namespace(&key).and_then(Self::parse_quote_key);
+            let Some(leg) = leg.filter(|leg| filter.matches(&leg.flight)) else {
+                continue;
+            };
+            quotes.push(SnapshotQuote {
+                leg,
+                quote: serde_json::from_str(&value).map_err(CacheError::Serde)?,
+            });
+        }
+        let no_fares = self
+            .backend
+            .scan(&self.namespaced(NO_FARE_PREFIX))?
+            .into_iter()
+            .filter_map(|(key, _)| Self::parse_quote_key(self.strip_namespace(&key)?.strip_prefix(NO_FARE_PREFIX)?))
+            .filter(|leg| filter.matches(&leg.flight))
+            .collect();
+
+        Ok(PriceSnapshot {
+            version: SNAPSHOT_VERSION,
+            exported_at: chrono::Utc::now().naive_utc(),
+            quotes,
+            no_fares,
+        })
+    }
+
+    /// Cache everything in `snapshot`, over whatever is cached for the same flights
+    pub fn import(&self, snapshot: &PriceSnapshot) {
+        for SnapshotQuote { leg, quote } in &snapshot.quotes {
+            self.put_quote(&leg.flight, leg.cabin, &leg.market, *quote);
+        }
+        for leg in &snapshot.no_fares {
+            self.put_no_fare(&leg.flight, leg.cabin, &leg.market);
+        }
+    }
+
+    /// Economy keeps the key it had before cabin classes and the default market the one it had before markets, so
+    /// quotes already cached stay usable
+    fn quote_key(flight: &Flight, cabin: CabinClass, market: &str) -> String {
+        let key = match cabin {
+            CabinClass::Economy => format!("{}{}:{}:{}", QUOTE_PREFIX, flight.src, flight.dest, flight.date),
+            cabin => format!("{}{}:{}:{}:{:?}", QUOTE_PREFIX, flight.src, flight.dest, flight.date, cabin),
+        };
+        match market {
+            DEFAULT_MARKET => key,
+            market => format!("{}@{}", key, market),
+        }
+    }
+
+    fn no_fare_key(flight: &Flight, cabin: CabinClass, market: &str) -> String {
+        format!("{}{}", NO_FARE_PREFIX, Self::quote_key(flight, cabin, market))
+    }
+
+    /// What [PriceCache::quote_key] made `key` from, `None` if it didn't
+    fn parse_quote_key(key: &str) -> Option<SnapshotLeg> {
+        let (key, market) = key.split_once('@').unwrap_or((key, DEFAULT_MARKET));
+        let mut parts = key.strip_prefix(QUOTE_PREFIX)?.split(':');
+        let flight = Flight {
+            src: parts.next()?.parse().ok()?,
+            dest: parts.next()?.parse().ok()?,
+            date: parts.next()?.parse().ok()?,
+        };
+        let cabin = match parts.next() {
+            Some(name) => CabinClass::ALL.into_iter().find(|c| format!("{:?}", c) == name)?,
+            None => CabinClass::Economy,
+        };
+
+        Some(SnapshotLeg {
+            flight,
+            cabin,
+            market: market.to_string(),
+        })
+    }
+
+    /// Queries are keyed on a hash of their JSON so the key is stable across instances, under the tenant's name for
+    /// all but the default tenant
+    fn result_key(query: &RouteQuery, tenant: Option<&str>) -> Option<String> {
+        let json = serde_json::to_vec(query).ok()?;
+        let hash = hex::encode(Sha256::digest(json));
+        Some(match tenant {
+            Some(tenant) => format!("result:{}:{}", tenant, hash),
+            None => format!("result:{}", hash),
+        })
+    }
+
+    /// `key` as stored in the backend, under the namespace if there is one
+    fn namespaced(&self, key: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, key),
+            None => key.to_string(),
+        }
+    }
+
+    /// What [PriceCache::namespaced] made `key` from, `None` if it's in another namespace
+    fn strip_namespace<'a>(&self, key: &'a str) -> Option<&'a str> {
+        match &self.namespace {
+            Some(namespace) => key.strip_prefix(namespace.as_str())?.strip_prefix('/'),
+            None => Some(key),
+        }
+    }
+
+    // Cache failures are logged and treated as a miss, a broken cache shouldn't fail the solve
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let res = self
+            .backend
+            .get(&self.namespaced(key))
+            .and_then(|v| v.map(|v| serde_json::from_str(&v).map_err(CacheError::Serde)).transpose());
+
+        res.unwrap_or_else(|e| {
+            log!("Cache read of {} failed: {}", key, e);
+            None
+        })
+    }
+
+    fn put<T: Serialize>(&self, key: &str, value: &T, ttl: Option<Duration>) {
+        let res = serde_json::to_string(value)
+            .map_err(CacheError::Serde)
+            .and_then(|v| self.backend.set(&self.namespaced(key), &v, ttl));
+
+        if let Err(e) = res {
+            log!("Cache write of {} failed: {}", key, e);
+        }
+    }
+}
+
+/// Quote lookups answered by a cache and ones which had to go on to the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Wraps a provider so quotes are looked up in, and written to, a [PriceCache] first
+pub struct CachedPriceQuery<Api> {
+    inner: Api,
+    cache: PriceCache,
+    cabin: CabinClass,
+    market: String,
+    /// Tenant the fetches are made for, see [PriceCache::claim_fetch]
+    tenant: Option<String>,
+    stats: CacheStats,
+}
+
+impl<Api: PriceQuery> CachedPriceQuery<Api> {
+    pub fn with_cache(inner: Api, cache: PriceCache) -> Self {
+        CachedPriceQuery {
+            inner,
+            cache,
+            cabin: CabinClass::Economy,
+            market: DEFAULT_MARKET.to_string(),
+            tenant: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Fetch for the tenant named `tenant`, the default if `None`, only sharing fetches with its other solves
+    pub fn with_tenant(mut self, tenant: Option<&str>) -> Self {
+        self.tenant = tenant.map(str::to_string);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<Api: PriceQuery> PriceQuery for CachedPriceQuery<Api> {
+    fn new() -> Self {
+        Self::with_cache(Api::new(), PriceCache::in_memory())
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        loop {
+            if let Some(quote) = self.cache.get_quote(&flight, self.cabin, &self.market) {
+                self.stats.hits += 1;
+                return Ok(quote);
+            }
+            if self.cache.is_no_fare(&flight, self.cabin, &self.market) {
+                self.stats.hits += 1;
+                return Err(QueryError::NoFare);
+            }
+
+            let guard = match self.cache.claim_fetch(&flight, self.cabin, &self.market, self.tenant.as_deref()) {
+                LegFetch::Fetch(guard) => guard,
+                LegFetch::Wait(mut answer) => {
+                    // Dropped without an answer counts as failed
+                    let fetched = answer.wait_for(Option::is_some).await.ok().and_then(|fetched| *fetched);
+                    match fetched {
+                        Some(Fetched::Quote(quote)) => {
+                            self.stats.hits += 1;
+                            return Ok(quote);
+                        }
+                        Some(Fetched::NoFare) => {
+                            self.stats.hits += 1;
+                            return Err(QueryError::NoFare);
+                        }
+                        Some(Fetched::Failed) | None => continue,
+                    }
+                }
+            };
+            self.stats.misses += 1;
+
+            let quote = self.inner.get_price(flight.clone()).await;
+            match &quote {
+                Ok(quote) => {
+                    self.cache.put_quote(&flight, self.cabin, &self.market, *quote);
+                    guard.finish(Fetched::Quote(*quote));
+                }
+                Err(QueryError::NoFare) => {
+                    self.cache.put_no_fare(&flight, self.cabin, &self.market);
+                    guard.finish(Fetched::NoFare);
+                }
+                Err(_) => guard.finish(Fetched::Failed),
+            }
+            return quote;
+        }
+    }
+
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        self.inner.cached_quotes()
+    }
+
+    fn restore_quotes(&mut self, quotes: Vec<(Flight, Quote)>) {
+        for (flight, quote) in &quotes {
+            self.cache.put_quote(flight, self.cabin, &self.market, *quote);
+        }
+        self.inner.restore_quotes(quotes);
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        self.cabin = cabin;
+        self.inner.set_cabin_class(cabin);
+    }
+
+    fn set_point_of_sale(&mut self, pos: &PointOfSale) {
+        self.market = pos.market.clone();
+        self.inner.set_point_of_sale(pos);
+    }
+
+    fn set_http_client(&mut self, client: &reqwest::Client) {
+        self.inner.set_http_client(client);
+    }
+
+    fn set_api_key(&mut self, key: &str) {
+        self.inner.set_api_key(key);
+    }
+
+    fn set_response_capture(&mut self, dir: Option<Arc<PathBuf>>) {
+        self.inner.set_response_capture(dir);
+    }
+
+    fn set_exchange_rates(&mut self, rates: Arc<ExchangeRates>) {
+        self.inner.set_exchange_rates(rates);
+    }
+
+    fn set_simulation(&mut self, config: &SimulationConfig) {
+        self.inner.set_simulation(config);
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.stats)
+    }
+
+    fn remaining_calls(&self) -> Option<u64> {
+        self.inner.remaining_calls()
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use route_solver_shared::queries::{CabinClass, Date, Flight};
+
+    use crate::flight_api::{PointOfSale, PriceQuery, QueryError, Quote, TestPriceApiQuery, DEFAULT_MARKET};
+
+    use super::{CacheBackend, CacheStats, CachedPriceQuery, MemoryCache, PriceCache};
+    use crate::snapshot::{PriceSnapshot, SnapshotFilter};
+
+    fn flight() -> Flight {
+        Flight {
+            src: "YYZ".parse().unwrap(),
+            dest: "YYC".parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_memory_cache_expiry() {
+        let cache = MemoryCache::default();
+        cache.set("a", "1", None).unwrap();
+        cache.set("b", "2", Some(Duration::ZERO)).unwrap();
+
+        assert_eq!(cache.get("a").unwrap(), Some("1".to_string()));
+        assert_eq!(cache.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::with_capacity(2);
+        cache.set("a", "1", None).unwrap();
+        cache.set("b", "2", None).unwrap();
+        // Reading `a` makes `b` the one to go
+        cache.get("a").unwrap();
+        cache.set("c", "3", None).unwrap();
+
+        assert_eq!(cache.get("a").unwrap(), Some("1".to_string()));
+        assert_eq!(cache.get("b").unwrap(), None);
+        assert_eq!(cache.get("c").unwrap(), Some("3".to_string()));
+        assert_eq!(cache.entry_count().unwrap(), 2);
+
+        // Overwriting a key doesn't evict anything else
+        cache.set("c", "4", None).unwrap();
+        assert_eq!(cache.get("a").unwrap(), Some("1".to_string()));
+        assert_eq!(cache.get("c").unwrap(), Some("4".to_string()));
+    }
+
+    #[cfg(feature = "sqlite-cache")]
+    #[test]
+    fn test_sqlite_cache_shared_between_handles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.sqlite");
+
+        let quote = Quote {
+            min_price: 120.0,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.0,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        let first = PriceCache::new(std::sync::Arc::new(super::SqliteCache::open(&path).unwrap()), None, None);
+        first.put_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET, quote);
+
+        // A second instance pointed at the same file sees the quote
+        let second = PriceCache::new(std::sync::Arc::new(super::SqliteCache::open(&path).unwrap()), None, None);
+        assert_eq!(second.get_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET).map(|q| q.min_price), Some(120.0));
+    }
+
+    #[tokio::test]
+    async fn test_cached_query_serves_from_cache() {
+        let cache = PriceCache::in_memory();
+        cache.put_quote(
+            &flight(),
+            CabinClass::Economy,
+            DEFAULT_MARKET,
+            Quote {
+                min_price: 1.0,
+                direct: true,
+                arrives: None,
+                quoted_at: None,
+                spread: 0.0,
+                currency: None,
+                carrier: None,
+                bag_fee: None,
+                fallback: false,
+            },
+        );
+
+        // The test API would quote 300, so getting 1 back means the cache answered
+        let mut api = CachedPriceQuery::with_cache(TestPriceApiQuery::new(), cache.clone());
+        assert_eq!(api.get_price(flight()).await.unwrap().min_price, 1.0);
+
+        let other = Flight {
+            dest: "YVR".parse().unwrap(),
+            ..flight()
+        };
+        let quote = api.get_price(other.clone()).await.unwrap();
+        assert_eq!(cache.get_quote(&other, CabinClass::Economy, DEFAULT_MARKET).map(|q| q.min_price), Some(quote.min_price));
+
+        // Other cabins and markets are cached separately
+        api.set_cabin_class(CabinClass::Business);
+        assert_eq!(api.get_price(flight()).await.unwrap().min_price, 300.0);
+        api.set_cabin_class(CabinClass::Economy);
+        api.set_point_of_sale(&PointOfSale {
+            market: "GB".to_string(),
+            locale: "en-GB".to_string(),
+        });
+        assert_eq!(api.get_price(flight()).await.unwrap().min_price, 300.0);
+        assert_eq!(api.cache_stats(), Some(CacheStats { hits: 1, misses: 3 }));
+    }
+
+    /// Quotes every flight at 100 after a moment, counting the calls made to it
+    struct SlowQuery(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl PriceQuery for SlowQuery {
+        fn new() -> Self {
+            SlowQuery(Arc::default())
+        }
+
+        async fn get_price(&mut self, _flight: Flight) -> Result<Quote, QueryError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(Quote {
+                min_price: 100.0,
+                direct: true,
+                arrives: None,
+                quoted_at: None,
+                spread: 0.0,
+                currency: None,
+                carrier: None,
+                bag_fee: None,
+                fallback: false,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_solves_share_fetches() {
+        let cache = PriceCache::in_memory();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let solve = || CachedPriceQuery::with_cache(SlowQuery(Arc::clone(&calls)), cache.clone());
+        let (mut first, mut second) = (solve(), solve());
+
+        let (a, b) = tokio::join!(first.get_price(flight()), second.get_price(flight()));
+        assert_eq!((a.unwrap().min_price, b.unwrap().min_price), (100.0, 100.0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second.cache_stats(), Some(CacheStats { hits: 1, misses: 0 }));
+
+        // Other markets are other fares
+        let mut abroad = solve();
+        abroad.set_point_of_sale(&PointOfSale {
+            market: "GB".to_string(),
+            locale: "en-GB".to_string(),
+        });
+        let _ = tokio::join!(first.get_price(flight()), abroad.get_price(flight()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Nor does another tenant's solve wait on this one's fetch
+        let other = |to: &str| Flight {
+            dest: to.parse().unwrap(),
+            ..flight()
+        };
+        let mut tenant = solve().with_tenant(Some("acme"));
+        let _ = tokio::join!(first.get_price(other("LAX")), tenant.get_price(other("LAX")));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cached_query_remembers_missing_fares() {
+        let cache = PriceCache::in_memory();
+        let mut api = CachedPriceQuery::with_cache(TestPriceApiQuery::new(), cache.clone());
+        let missing = Flight {
+            dest: "LHR".parse().unwrap(),
+            ..flight()
+        };
+
+        assert!(matches!(api.get_price(missing.clone()).await, Err(QueryError::NoFare)));
+        assert!(cache.is_no_fare(&missing, CabinClass::Economy, DEFAULT_MARKET));
+        assert!(matches!(api.get_price(missing.clone()).await, Err(QueryError::NoFare)));
+        assert_eq!(api.cache_stats(), Some(CacheStats { hits: 1, misses: 1 }));
+
+        // Missing fares expire on their own clock, quotes are still kept
+        let cache = PriceCache::new(std::sync::Arc::new(MemoryCache::default()), None, None)
+            .with_no_fare_ttl(Some(Duration::ZERO));
+        cache.put_no_fare(&missing, CabinClass::Economy, DEFAULT_MARKET);
+        assert!(!cache.is_no_fare(&missing, CabinClass::Economy, DEFAULT_MARKET));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let quote = |min_price| Quote {
+            min_price,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.1,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        let other = |dest: &str, day| Flight {
+            dest: dest.parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+            ..flight()
+        };
+        let source = PriceCache::in_memory();
+        source.put_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET, quote(100.0));
+        source.put_quote(&flight(), CabinClass::PremiumEconomy, "GB", quote(250.0));
+        source.put_no_fare(&other("YVR", 1), CabinClass::Economy, DEFAULT_MARKET);
+        // Outside the airports and dates asked for
+        source.put_quote(&other("LHR", 1), CabinClass::Economy, DEFAULT_MARKET, quote(600.0));
+        source.put_quote(&other("YVR", 9), CabinClass::Economy, DEFAULT_MARKET, quote(200.0));
+
+        let filter = SnapshotFilter {
+            airports: Some(["YYZ", "YYC", "YVR"].iter().map(|c| c.parse().unwrap()).collect()),
+            from: None,
+            to: Date::from_ymd_opt(2023, 2, 8),
+        };
+        let snapshot = source.export(&filter).unwrap();
+        assert_eq!((snapshot.quotes.len(), snapshot.no_fares.len()), (2, 1));
+
+        // Through a file, as it would go between machines
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        snapshot.write(&path).unwrap();
+        let target = PriceCache::in_memory();
+        target.import(&PriceSnapshot::read(&path).unwrap());
+
+        let price = |cabin, market| target.get_quote(&flight(), cabin, market).map(|q| q.min_price);
+        assert_eq!(price(CabinClass::Economy, DEFAULT_MARKET), Some(100.0));
+        assert_eq!(price(CabinClass::PremiumEconomy, "GB"), Some(250.0));
+        assert_eq!(price(CabinClass::PremiumEconomy, DEFAULT_MARKET), None);
+        assert!(target.is_no_fare(&other("YVR", 1), CabinClass::Economy, DEFAULT_MARKET));
+        assert!(target.get_quote(&other("LHR", 1), CabinClass::Economy, DEFAULT_MARKET).is_none());
+        assert_eq!(target.entry_count(), Some(3));
+    }
+
+    #[test]
+    fn test_namespaces_kept_apart() {
+        let quote = Quote {
+            min_price: 100.0,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.1,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        let real = PriceCache::in_memory();
+        let simulated = real.clone().with_namespace(Some("simulated"));
+        simulated.put_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET, quote);
+        let yvr = Flight {
+            dest: "YVR".parse().unwrap(),
+            ..flight()
+        };
+        simulated.put_no_fare(&yvr, CabinClass::Economy, DEFAULT_MARKET);
+
+        assert!(real.get_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET).is_none());
+        let price = simulated.get_quote(&flight(), CabinClass::Economy, DEFAULT_MARKET).map(|q| q.min_price);
+        assert_eq!(price, Some(100.0));
+
+        let filter = SnapshotFilter::default();
+        let snapshot = real.export(&filter).unwrap();
+        assert_eq!((snapshot.quotes.len(), snapshot.no_fares.len()), (0, 0));
+        let snapshot = simulated.export(&filter).unwrap();
+        assert_eq!((snapshot.quotes.len(), snapshot.no_fares.len()), (1, 1));
+        assert_eq!(snapshot.quotes[0].leg.flight, flight());
+    }
+}
@@ -0,0 +1,51 @@
+//! Price calendars, quotes for one leg across the days around a date so users can see whether moving it would help.
+
+use route_solver_shared::queries::{AirportCode, CalendarDay, Date, DateMath, Flight};
+
+use crate::flight_api::PriceQuery;
+
+/// Most days either side of the date a calendar may cover, every day is a potential API call
+pub const MAX_CALENDAR_DAYS: u64 = 14;
+
+/// Quote the leg from `src` to `dest` on each day from `days` before `around` to `days` after it.
+///
+/// Days which fail to price (no flights, provider errors, the user's quota running out) are left without a price
+/// rather than failing the whole calendar.
+pub async fn price_calendar(api: &mut impl PriceQuery, src: AirportCode, dest: AirportCode, around: Date, days: u64) -> Vec<CalendarDay> {
+    let days = days.min(MAX_CALENDAR_DAYS);
+    let first = around.add_days_saturating(-(days as i64));
+
+    let mut calendar = Vec::new();
+    for date in first.iter_days().take((2 * days + 1) as usize) {
+        let flight = Flight {
+            src,
+            dest,
+            date,
+        };
+        let price = api.get_price(flight).await.ok().map(|quote| quote.min_price);
+        calendar.push(CalendarDay { date, price });
+    }
+    calendar
+}
+
+#[cfg(test)]
+mod calendar_tests {
+    use route_solver_shared::queries::Date;
+
+    use crate::flight_api::{PriceQuery, TestPriceApiQuery};
+
+    use super::price_calendar;
+
+    #[tokio::test]
+    async fn test_calendar_covers_window() {
+        let mut api = TestPriceApiQuery::new();
+        let calendar = price_calendar(&mut api, "YYZ".parse().unwrap(), "YVR".parse().unwrap(), Date::from_ymd_opt(2023, 2, 1).unwrap(), 2).await;
+
+        let dates: Vec<String> = calendar.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2023-01-30", "2023-01-31", "2023-02-01", "2023-02-02", "2023-02-03"]);
+
+        // The mock data starts on the 1st, earlier days can't be priced but still show up
+        assert_eq!(calendar[0].price, None);
+        assert_eq!(calendar[2].price, Some(400.0));
+    }
+}
@@ -0,0 +1,96 @@
+//! Response compression.
+//!
+//! actix's [Compress](actix_web::middleware::Compress) negotiates gzip/brotli with the client and already leaves
+//! images and video alone. [skip_incompressible] runs inside it and opts out the responses it shouldn't touch: formats
+//! which are compressed already, event streams (which would be buffered by the encoder), and bodies too small to be
+//! worth it.
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{ContentEncoding, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE},
+    middleware::Next,
+    Error,
+};
+
+/// Bodies smaller than this are sent as is
+const MIN_COMPRESS_SIZE: u64 = 1024;
+
+const INCOMPRESSIBLE_TYPES: &[&str] = &[
+    "text/event-stream",
+    "font/woff",
+    "font/woff2",
+    "application/gzip",
+    "application/zip",
+    "application/octet-stream",
+];
+
+/// Register with `wrap` before `Compress` so it runs inside of it
+pub async fn skip_incompressible(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+
+    let too_small = matches!(res.response().body().size(), BodySize::Sized(n) if n < MIN_COMPRESS_SIZE);
+    if too_small || is_incompressible(res.headers().get(CONTENT_TYPE)) {
+        // Compress leaves responses which already declare an encoding alone
+        res.headers_mut()
+            .insert(CONTENT_ENCODING, ContentEncoding::Identity.to_header_value());
+    }
+
+    Ok(res)
+}
+
+fn is_incompressible(content_type: Option<&HeaderValue>) -> bool {
+    let Some(mime) = content_type.and_then(|ct| ct.to_str().ok()) else {
+        return false;
+    };
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+
+    INCOMPRESSIBLE_TYPES.iter().any(|t| essence.eq_ignore_ascii_case(t))
+}
+
+#[cfg(test)]
+mod compression_tests {
+    use actix_web::{
+        http::header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+        middleware::{from_fn, Compress},
+        test::{call_service, init_service, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    use super::skip_incompressible;
+
+    #[actix_web::test]
+    async fn test_only_large_compressible_bodies_are_encoded() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(skip_incompressible))
+                .wrap(Compress::default())
+                .route(
+                    "/json",
+                    web::get().to(|| async { HttpResponse::Ok().content_type("application/json").body("[0]".repeat(1000)) }),
+                )
+                .route(
+                    "/small",
+                    web::get().to(|| async { HttpResponse::Ok().content_type("application/json").body("[0]") }),
+                )
+                .route(
+                    "/font",
+                    web::get().to(|| async { HttpResponse::Ok().content_type("font/woff2").body("a".repeat(4096)) }),
+                ),
+        )
+        .await;
+
+        let encoding = |uri: &'static str| TestRequest::get().uri(uri).insert_header((ACCEPT_ENCODING, "gzip")).to_request();
+
+        let res = call_service(&app, encoding("/json")).await;
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+
+        for uri in ["/small", "/font"] {
+            let res = call_service(&app, encoding(uri)).await;
+            assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "identity", "{} was compressed", uri);
+        }
+    }
+}
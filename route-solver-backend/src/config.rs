@@ -0,0 +1,508 @@
+//! Server configuration.
+//!
+//! Loaded from the TOML file pointed to by `ROUTE_SOLVER_CONFIG`, falling back to `route-solver.toml` in the working
+//! directory. Every field has a default so a missing file or section just gives the defaults.
+
+use route_solver_shared::queries::{AirportCode, CabinClass, Date};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    flight_api::CarrierCode,
+    simulated::{Scenario, DEFAULT_SEED},
+};
+
+const CONFIG_ENV_VAR: &str = "ROUTE_SOLVER_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "route-solver.toml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Could not read config file.")]
+    Io(std::io::Error),
+    #[error("Could not parse config file.")]
+    Parse(toml::de::Error),
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub workers: WorkerConfig,
+    pub jobs: JobConfig,
+    pub cache: CacheConfig,
+    pub quota: QuotaConfig,
+    pub admin: AdminConfig,
+    pub currency: CurrencyConfig,
+    pub templates: TemplateConfig,
+    pub stays: StayConfig,
+    pub ground: GroundConfig,
+    pub estimates: EstimateConfig,
+    pub throttle: ThrottleConfig,
+    pub baggage: BaggageConfig,
+    pub provider: ProviderConfig,
+    pub http: HttpConfig,
+    pub scheduler: SchedulerConfig,
+    pub audit: AuditConfig,
+    /// Customers sharing the server by name, see [crate::tenants]. Requests which aren't any of theirs go by the rest of
+    /// the config.
+    pub tenants: HashMap<String, TenantConfig>,
+}
+
+/// Settings for the background pool solves are dispatched to
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WorkerConfig {
+    /// Number of solves allowed to run at once
+    pub concurrency: usize,
+    /// Number of solves allowed to wait for a free worker before new ones are rejected
+    pub queue_capacity: usize,
+    /// Most batch solves, e.g. scheduled re-solves, running at once so the other workers are kept for solves someone is
+    /// waiting on. Half the workers if unset, at least one and at most all but one, except a pool of one worker runs
+    /// batch solves on it too.
+    pub batch_concurrency: Option<usize>,
+    /// Number of batch solves allowed to wait, apart from `queue_capacity` so they never take an interactive solve's place
+    pub batch_queue_capacity: usize,
+    /// How long shutdown waits for cancelled solves to wind down before exiting anyway
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            concurrency: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2),
+            queue_capacity: 64,
+            batch_concurrency: None,
+            batch_queue_capacity: 64,
+            drain_timeout_secs: 20,
+        }
+    }
+}
+
+/// Settings for job persistence
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct JobConfig {
+    /// Directory job records are persisted to so they survive restarts, persistence is off if unset
+    pub state_dir: Option<PathBuf>,
+    /// Routes each solve looks for, anything over 1 returns the runners up for comparison at the cost of more API calls
+    pub candidates: usize,
+    /// Directory each solve's explored graph is written to, as GraphViz DOT and JSON, for debugging odd routes. Off if
+    /// unset.
+    pub graph_dir: Option<PathBuf>,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        JobConfig {
+            state_dir: Some(Path::new("job-state").to_path_buf()),
+            candidates: 1,
+            graph_dir: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackendKind {
+    Memory,
+    Sqlite,
+    Redis,
+}
+
+/// Settings for the quote and result cache
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub backend: CacheBackendKind,
+    /// Database file used by the SQLite backend
+    pub sqlite_path: PathBuf,
+    /// Connection URL used by the Redis backend
+    pub redis_url: String,
+    /// How long a quote stays cached, 0 to never expire
+    pub quote_ttl_secs: u64,
+    /// How long a solved route stays cached, 0 to never expire
+    pub result_ttl_secs: u64,
+    /// How long a flight the provider had no fare for is remembered, 0 to never expire
+    pub no_fare_ttl_secs: u64,
+    /// Most entries the memory backend holds before evicting the least recently used, 0 for no limit
+    pub memory_max_entries: usize,
+    /// Price snapshot imported into the cache at startup, see [crate::snapshot]
+    pub snapshot_path: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            backend: CacheBackendKind::Memory,
+            sqlite_path: Path::new("price-cache.sqlite").to_path_buf(),
+            redis_url: "redis://127.0.0.1/".to_string(),
+            quote_ttl_secs: 6 * 60 * 60,
+            result_ttl_secs: 60 * 60,
+            no_fare_ttl_secs: 60 * 60,
+            memory_max_entries: 100_000,
+            snapshot_path: None,
+        }
+    }
+}
+
+/// Daily limits on the pricing API calls each user's solves may make
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Calls allowed per user per day, unlimited if unset
+    pub daily_limit: Option<u64>,
+    /// Per API key overrides of `daily_limit`
+    pub keys: HashMap<String, u64>,
+    /// Solves estimated to make more calls than this are only run once the user confirms them, never asked if unset.
+    /// Off by default: only the itinerary builder and `solve --confirm-calls` can confirm, so the other pages, "what
+    /// if" changes and the CLI without the flag are turned down over it.
+    pub confirm_calls_above: Option<u64>,
+}
+
+/// A customer of the server with provider credentials, quotas and jobs of their own, e.g. one of an agency's clients
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TenantConfig {
+    /// `X-Api-Key`s the tenant's requests are made with
+    pub api_keys: Vec<String>,
+    /// First label of the host the tenant's requests are made on, e.g. `acme` for `acme.routes.example.com`
+    pub subdomain: Option<String>,
+    /// Key providers are called with for the tenant, the server's own if unset
+    pub provider_key: Option<String>,
+    pub quota: QuotaConfig,
+}
+
+/// Access to the admin endpoints
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Bearer token admin requests must carry, the admin endpoints are disabled if unset
+    pub token: Option<String>,
+}
+
+/// Exchange rates used to price solutions in currencies other than the one providers quote in
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CurrencyConfig {
+    /// Units of each currency one US dollar buys, on top of the built in rates. Each must be positive.
+    pub rates: HashMap<String, f32>,
+}
+
+/// Where the builder's trip templates come from
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TemplateConfig {
+    /// JSON file of templates to offer instead of the bundled ones
+    pub path: Option<PathBuf>,
+}
+
+/// Where accommodation prices come from
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct StayConfig {
+    /// CSV of `iata,nightly_rate` in US dollars per passenger, routes are priced on flights alone if unset
+    pub rates_path: Option<PathBuf>,
+}
+
+/// Legs which may go overland instead of flying
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GroundConfig {
+    /// CSV of `src,dest,price` train and bus fares in US dollars, every leg is flown if unset
+    pub routes_path: Option<PathBuf>,
+    /// Furthest apart two stops can be for the trip between them to be offered overland
+    pub max_km: f64,
+}
+
+impl Default for GroundConfig {
+    fn default() -> Self {
+        GroundConfig {
+            routes_path: None,
+            max_km: 500.0,
+        }
+    }
+}
+
+/// Fares estimated for days the provider has no quote for
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EstimateConfig {
+    /// Furthest from a missing day to look for quotes to estimate it from, 0 rules days without a fare out instead
+    pub max_gap_days: u32,
+    /// Fraction of an estimate added to it when comparing routes, so quoted fares win ties
+    pub uncertainty: f32,
+}
+
+impl Default for EstimateConfig {
+    fn default() -> Self {
+        EstimateConfig {
+            max_gap_days: 0,
+            uncertainty: 0.2,
+        }
+    }
+}
+
+/// Coarser searches for users running low on their daily quota, see [crate::throttle]. Users without a limit are never
+/// throttled.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// Calls left in the user's quota below which only every `sample_every` days are priced, never if unset
+    pub sample_below: Option<u64>,
+    /// Calls left below which only the `beam_width` cheapest routes at each number of stops are carried on from, never
+    /// if unset
+    pub narrow_below: Option<u64>,
+    pub sample_every: u32,
+    pub beam_width: usize,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            sample_below: Some(200),
+            narrow_below: Some(50),
+            sample_every: 3,
+            beam_width: 20,
+        }
+    }
+}
+
+/// Checked bag fees estimated for queries asking for a bag, on fares the provider didn't quote one for
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BaggageConfig {
+    /// Fee for a checked bag on carriers not in `carriers`, in US dollars per passenger and flight
+    pub default_fee: f32,
+    /// Fees of particular carriers by IATA code, e.g. `{ NK = 55.0, FR = 40.0 }`
+    pub carriers: HashMap<CarrierCode, f32>,
+}
+
+impl Default for BaggageConfig {
+    fn default() -> Self {
+        BaggageConfig {
+            default_fee: 35.0,
+            carriers: HashMap::new(),
+        }
+    }
+}
+
+/// Where solves get their fares
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// Name of the provider solves price every leg they consider with, one of those in the
+    /// [ProviderRegistry](crate::providers::ProviderRegistry) the server runs with
+    pub source: String,
+    /// Re-price the legs of each solved route with the live search before answering, so the fares returned are
+    /// bookable while the search itself stays on cheaper indicative prices. Only applies to the indicative `source`s.
+    pub verify_live: bool,
+    /// Directory responses the provider had nothing usable in are saved to, for working out how its format changed. Off
+    /// if unset.
+    pub capture_dir: Option<PathBuf>,
+    /// Model the `simulated` source makes fares up with
+    pub simulation: SimulationConfig,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        ProviderConfig {
+            source: crate::providers::INDICATIVE.to_string(),
+            verify_live: false,
+            capture_dir: None,
+            simulation: SimulationConfig::default(),
+        }
+    }
+}
+
+/// The seeded model of [SimulatedApiQuery](crate::simulated::SimulatedApiQuery), e.g.
+///
+/// ```toml
+/// [provider.simulation]
+/// seed = 7
+/// scenarios = [{ kind = "unavailable", src = "YYZ", dest = "YVR", from = "2023-03-01" }]
+/// ```
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// The same seed always quotes a flight the same fare
+    pub seed: u64,
+    /// Changes scripted on top of the model's fares, later ones on top of earlier
+    pub scenarios: Vec<Scenario>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            seed: DEFAULT_SEED,
+            scenarios: Vec::new(),
+        }
+    }
+}
+
+/// The HTTP client every provider's outbound calls share
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Proxy every outbound call goes through, e.g. `http://proxy.corp:3128`. The system's proxy settings apply if unset.
+    pub proxy: Option<String>,
+    /// How long to wait for a provider to accept a connection
+    pub connect_timeout_secs: u64,
+    /// How long a whole request may take, 0 for no limit
+    pub timeout_secs: u64,
+    pub user_agent: String,
+    /// Idle connections kept open to each provider host for reuse
+    pub pool_max_idle_per_host: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            proxy: None,
+            connect_timeout_secs: 10,
+            timeout_secs: 30,
+            user_agent: concat!("route-solver/", env!("CARGO_PKG_VERSION")).to_string(),
+            pool_max_idle_per_host: 16,
+        }
+    }
+}
+
+/// Re-pricing on a schedule, see [crate::scheduler]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Run the tasks inside the web server. Leave off when they're run by `route-scheduler` instead.
+    pub enabled: bool,
+    /// URL alerts are POSTed to as JSON, they're only logged if unset
+    pub alert_webhook: Option<String>,
+    /// Directory re-pricing runs record the dates they've got through in, so a run cut short picks up where it
+    /// stopped. Runs always start over if unset.
+    pub progress_dir: Option<PathBuf>,
+    pub tasks: Vec<TaskConfig>,
+}
+
+/// Record of solves, provider calls and exports, see [crate::audit]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// File the log is appended to, it's only kept in memory if unset
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TaskConfig {
+    /// Names the task in logs and alerts
+    pub name: String,
+    /// Five field cron expression, in UTC, e.g. `30 3 * * *` for 03:30 every night
+    pub schedule: String,
+    #[serde(flatten)]
+    pub kind: TaskKind,
+    /// Alert when a run finds a fare, or a trip total, at or below this
+    pub alert_below: Option<f32>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskKind {
+    /// Re-solve the itinerary saved at `itinerary`, a route query as JSON like the frontend submits. Its total is
+    /// compared to `alert_below` in the currency it's priced in.
+    Resolve { itinerary: PathBuf },
+    /// Re-price the flights from `src` to `dest` every day from `from` to `to`, dates given as `"YYYY-MM-DD"` strings.
+    /// Fares are compared to `alert_below` in the base currency.
+    Reprice {
+        src: AirportCode,
+        dest: AirportCode,
+        from: Date,
+        to: Date,
+        #[serde(default)]
+        cabin: CabinClass,
+    },
+}
+
+impl Config {
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = match env::var(CONFIG_ENV_VAR) {
+            Ok(p) => PathBuf::from(p),
+            Err(_) => {
+                let default_path = PathBuf::from(DEFAULT_CONFIG_PATH);
+                if !default_path.exists() {
+                    return Ok(Config::default());
+                }
+                default_path
+            }
+        };
+
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Config::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, ConfigError> {
+        toml::from_str(contents).map_err(ConfigError::Parse)
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use route_solver_shared::queries::CabinClass;
+
+    use super::{CacheBackendKind, Config, TaskKind};
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config = Config::from_toml("[workers]\nconcurrency = 3\n").unwrap();
+
+        assert_eq!(config.workers.concurrency, 3);
+        assert_eq!(config.workers.queue_capacity, 64);
+    }
+
+    #[test]
+    fn test_cache_backend_parse() {
+        let config = Config::from_toml("[cache]\nbackend = \"redis\"\nredis_url = \"redis://cache:6379/\"\n").unwrap();
+
+        assert_eq!(config.cache.backend, CacheBackendKind::Redis);
+        assert_eq!(config.cache.redis_url, "redis://cache:6379/");
+    }
+
+    #[test]
+    fn test_provider_parse() {
+        assert_eq!(Config::default().provider.source, "indicative");
+
+        let config = Config::from_toml("[provider]\nsource = \"live\"\n").unwrap();
+        assert_eq!(config.provider.source, "live");
+        assert!(!config.provider.verify_live);
+    }
+
+    #[test]
+    fn test_scheduler_parse() {
+        let config = Config::from_toml(
+            r#"
+            [[scheduler.tasks]]
+            name = "nightly"
+            schedule = "30 3 * * *"
+            kind = "reprice"
+            src = "YYZ"
+            dest = "YVR"
+            from = "2023-02-01"
+            to = "2023-02-28"
+            alert_below = 250.0
+
+            [[scheduler.tasks]]
+            name = "europe"
+            schedule = "0 4 * * 1"
+            kind = "resolve"
+            itinerary = "itineraries/europe.json"
+            "#,
+        )
+        .unwrap();
+
+        assert!(!config.scheduler.enabled);
+        let tasks = &config.scheduler.tasks;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].alert_below, Some(250.0));
+        assert!(matches!(tasks[0].kind, TaskKind::Reprice { cabin: CabinClass::Economy, .. }));
+        assert_eq!(tasks[1].kind, TaskKind::Resolve { itinerary: "itineraries/europe.json".into() });
+    }
+}
@@ -0,0 +1,260 @@
+//! Currency conversion.
+//!
+//! Fares are converted to [BASE_CURRENCY] as providers quote them, whatever currency they priced in, so the router and
+//! the quote cache only ever see the base. Solutions asked for in another currency are converted once solved. Both use
+//! a fixed table of exchange rates that can be overridden in the config.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use route_solver_shared::queries::{ApiError, RouteSolution, BASE_CURRENCY};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+/// Units of each currency one unit of [BASE_CURRENCY] buys, used when the config doesn't give a rate
+const DEFAULT_RATES: &[(&str, f32)] = &[
+    ("USD", 1.0),
+    ("CAD", 1.36),
+    ("EUR", 0.92),
+    ("GBP", 0.79),
+    ("AUD", 1.52),
+    ("JPY", 150.0),
+    ("MXN", 17.0),
+    ("INR", 83.0),
+];
+
+#[derive(Debug, Error)]
+pub enum CurrencyError {
+    #[error("Currency {0} is not supported.")]
+    Unsupported(String),
+    #[error("Exchange rate for {0} isn't a positive number.")]
+    InvalidRate(String),
+}
+
+impl From<CurrencyError> for ApiError {
+    fn from(e: CurrencyError) -> ApiError {
+        ApiError::Validation(e.to_string())
+    }
+}
+
+/// An ISO 4217 currency code, e.g. "EUR". Kept inline rather than as a `String` so quotes carrying one stay `Copy`.
+/// Goes over the wire as a plain string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CurrencyCode([u8; 3]);
+
+impl CurrencyCode {
+    pub fn as_str(&self) -> &str {
+        // Only built by from_str, which only lets ASCII through
+        std::str::from_utf8(&self.0).expect("currency codes are ASCII")
+    }
+
+    pub fn is_base(&self) -> bool {
+        self.as_str() == BASE_CURRENCY
+    }
+}
+
+impl FromStr for CurrencyCode {
+    type Err = CurrencyError;
+
+    /// Parses three ASCII letters, in either case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[a, b, c] if s.bytes().all(|b| b.is_ascii_alphabetic()) => {
+                Ok(CurrencyCode([a, b, c].map(|b| b.to_ascii_uppercase())))
+            }
+            _ => Err(CurrencyError::Unsupported(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CurrencyCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl fmt::Debug for CurrencyCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for CurrencyCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+pub struct ExchangeRates {
+    per_base: HashMap<String, f32>,
+}
+
+impl Default for ExchangeRates {
+    fn default() -> Self {
+        ExchangeRates::new(&HashMap::new()).expect("Default exchange rates are positive")
+    }
+}
+
+impl ExchangeRates {
+    /// The default rates with `overrides` on top, which must be positive. Rates are units of the currency per unit of
+    /// [BASE_CURRENCY].
+    pub fn new(overrides: &HashMap<String, f32>) -> Result<ExchangeRates, CurrencyError> {
+        if let Some((currency, _)) = overrides.iter().find(|(_, rate)| !(rate.is_finite() && **rate > 0.0)) {
+            return Err(CurrencyError::InvalidRate(currency.to_uppercase()));
+        }
+
+        let mut per_base: HashMap<String, f32> = DEFAULT_RATES.iter().map(|(c, r)| (c.to_string(), *r)).collect();
+        per_base.extend(overrides.iter().map(|(c, r)| (c.to_uppercase(), *r)));
+        // The base always converts to itself, whatever the config says
+        per_base.insert(BASE_CURRENCY.to_string(), 1.0);
+        Ok(ExchangeRates { per_base })
+    }
+
+    /// Currency codes prices can be converted to, alphabetically
+    pub fn supported(&self) -> Vec<String> {
+        let mut codes: Vec<String> = self.per_base.keys().cloned().collect();
+        codes.sort();
+        codes
+    }
+
+    pub fn is_supported(&self, currency: &str) -> bool {
+        self.per_base.contains_key(&currency.to_uppercase())
+    }
+
+    /// Convert an amount in [BASE_CURRENCY] to `currency`
+    pub fn convert_price(&self, amount: f32, currency: &str) -> Result<f32, CurrencyError> {
+        Ok(amount * self.rate(currency)?)
+    }
+
+    /// Convert an amount in `currency` to [BASE_CURRENCY]
+    pub fn to_base(&self, amount: f32, currency: &str) -> Result<f32, CurrencyError> {
+        Ok(amount / self.rate(currency)?)
+    }
+
+    /// Convert every price in `solution`, including its alternatives, to `currency`
+    pub fn convert(&self, solution: &mut RouteSolution, currency: &str) -> Result<(), CurrencyError> {
+        let factor = self.rate(currency)? / self.rate(&solution.currency)?;
+        scale(solution, factor, &currency.to_uppercase());
+        Ok(())
+    }
+
+    fn rate(&self, currency: &str) -> Result<f32, CurrencyError> {
+        self.per_base
+            .get(&currency.to_uppercase())
+            .copied()
+            .ok_or_else(|| CurrencyError::Unsupported(currency.to_string()))
+    }
+}
+
+/// Multiply every price in `solution` and its alternatives by `factor`, now that they're in `currency`
+fn scale(solution: &mut RouteSolution, factor: f32, currency: &str) {
+    for flight in &mut solution.flights {
+        flight.price *= factor;
+        flight.stay_price *= factor;
+        flight.bag_fee *= factor;
+        flight.std_dev *= factor;
+        if let Some(alternative) = &mut flight.alternative {
+            alternative.price *= factor;
+            alternative.saving = alternative.saving.map(|saving| saving * factor);
+        }
+    }
+    solution.total_price *= factor;
+    solution.currency = currency.to_string();
+
+    for alternative in &mut solution.alternatives {
+        scale(alternative, factor, currency);
+    }
+}
+
+#[cfg(test)]
+mod currency_tests {
+    use std::collections::HashMap;
+
+    use route_solver_shared::queries::{
+        Date, Flight, FlightPrice, LegAlternative, PriceSource, RouteSolution, TransportMode, BASE_CURRENCY,
+    };
+
+    use super::ExchangeRates;
+
+    #[test]
+    fn test_convert_solution() {
+        let rates = ExchangeRates::new(&HashMap::from([("eur".to_string(), 0.5)])).unwrap();
+        let leg = FlightPrice {
+            flight: Flight {
+                src: "YYZ".parse().unwrap(),
+                dest: "YVR".parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+            },
+            price: 350.0,
+            stay_price: 50.0,
+            mode: TransportMode::Flight,
+            source: PriceSource::Quoted,
+            std_dev: 10.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 30.0,
+            alternative: None,
+        };
+        let explained = FlightPrice {
+            alternative: Some(LegAlternative {
+                flight: leg.flight.clone(),
+                price: 300.0,
+                saving: Some(20.0),
+            }),
+            ..leg.clone()
+        };
+        let mut solution = RouteSolution {
+            flights: vec![explained],
+            total_price: 400.0,
+            currency: BASE_CURRENCY.to_string(),
+            passengers: 1,
+            alternatives: vec![RouteSolution {
+                flights: vec![leg],
+                total_price: 500.0,
+                currency: BASE_CURRENCY.to_string(),
+                passengers: 1,
+                alternatives: vec![],
+                request_id: None,
+                origin: None,
+                stats: None,
+            }],
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+
+        rates.convert(&mut solution, "eur").unwrap();
+        assert_eq!(solution.currency, "EUR");
+        assert_eq!(solution.total_price, 200.0);
+        assert_eq!(solution.flights[0].price, 175.0);
+        assert_eq!(solution.flights[0].stay_price, 25.0);
+        assert_eq!(solution.flights[0].bag_fee, 15.0);
+        assert_eq!(solution.flights[0].std_dev, 5.0);
+        let alternative = solution.flights[0].alternative.as_ref().unwrap();
+        assert_eq!((alternative.price, alternative.saving), (150.0, Some(10.0)));
+        assert_eq!(solution.alternatives[0].total_price, 250.0);
+        assert_eq!(solution.alternatives[0].flights[0].bag_fee, 15.0);
+
+        // Nothing is converted when either currency isn't supported
+        assert!(rates.convert(&mut solution, "XYZ").is_err());
+        solution.currency = "XYZ".to_string();
+        assert!(rates.convert(&mut solution, "USD").is_err());
+        assert_eq!((solution.total_price, solution.alternatives[0].total_price), (200.0, 250.0));
+        assert_eq!(rates.to_base(100.0, "EUR").unwrap(), 200.0);
+    }
+
+    #[test]
+    fn test_invalid_rates() {
+        for rate in [0.0, -1.36, f32::NAN, f32::INFINITY] {
+            assert!(ExchangeRates::new(&HashMap::from([("CAD".to_string(), rate)])).is_err());
+        }
+        let rates = ExchangeRates::new(&HashMap::from([("cad".to_string(), 1.5)])).unwrap();
+        assert_eq!(rates.convert_price(100.0, "CAD").unwrap(), 150.0);
+    }
+}
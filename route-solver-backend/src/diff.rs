@@ -0,0 +1,129 @@
+//! Differences between two solves of the same itinerary, for showing what re-pricing a trip changed.
+//!
+//! Legs are matched up by the airports they fly between, so a leg whose date moved is still the same leg. Routes which
+//! visit the stops in another order have legs only one of them flies, those are listed without a change.
+
+use route_solver_shared::queries::{DateMath, FlightPrice, LegChange, RouteSolution, SolutionDiff};
+
+use crate::currency::{CurrencyError, ExchangeRates};
+
+/// What changed from `was` to `now`, with `now` converted to the currency `was` is in
+pub fn diff_solutions(was: &RouteSolution, now: &RouteSolution, rates: &ExchangeRates) -> Result<SolutionDiff, CurrencyError> {
+    let mut now = now.clone();
+    rates.convert(&mut now, &was.currency)?;
+
+    let mut was_legs: Vec<&FlightPrice> = was.flights.iter().collect();
+    was_legs.sort_by_key(|f| f.flight.date);
+    let same_leg = |a: &FlightPrice, b: &FlightPrice| a.flight.src == b.flight.src && a.flight.dest == b.flight.dest;
+
+    let mut legs: Vec<LegChange> = was_legs
+        .iter()
+        .map(|&leg| change(Some(leg), now.flights.iter().find(|other| same_leg(leg, other))))
+        .collect();
+    legs.extend(
+        now.flights
+            .iter()
+            .filter(|leg| !was.flights.iter().any(|other| same_leg(leg, other)))
+            .map(|leg| change(None, Some(leg))),
+    );
+
+    Ok(SolutionDiff {
+        legs,
+        was_total: was.total_price,
+        now_total: now.total_price,
+        currency: was.currency.clone(),
+    })
+}
+
+/// A leg as one or both of the solves fly it, at least one of `was` and `now` is set
+fn change(was: Option<&FlightPrice>, now: Option<&FlightPrice>) -> LegChange {
+    let flight = &was.or(now).expect("A leg flown by neither solve").flight;
+    let both = was.zip(now);
+
+    LegChange {
+        src: flight.src,
+        dest: flight.dest,
+        price_change: both.map(|(was, now)| now.price - was.price),
+        days_moved: both.map(|(was, now)| now.flight.date.days_since(was.flight.date)),
+        was: was.cloned(),
+        now: now.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use std::collections::HashMap;
+
+    use route_solver_shared::queries::{Date, Flight, FlightPrice, PriceSource, RouteSolution, TransportMode, BASE_CURRENCY};
+
+    use super::diff_solutions;
+    use crate::currency::ExchangeRates;
+
+    fn leg(src: &str, dest: &str, day: u32, price: f32) -> FlightPrice {
+        FlightPrice {
+            flight: Flight {
+                src: src.parse().unwrap(),
+                dest: dest.parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+            },
+            price,
+            stay_price: 0.0,
+            mode: TransportMode::Flight,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 0.0,
+            alternative: None,
+        }
+    }
+
+    fn solution(flights: Vec<FlightPrice>, currency: &str) -> RouteSolution {
+        RouteSolution {
+            total_price: flights.iter().map(|f| f.price).sum(),
+            flights,
+            currency: currency.to_string(),
+            passengers: 1,
+            alternatives: vec![],
+            request_id: None,
+            origin: None,
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_solutions() {
+        let rates = ExchangeRates::new(&HashMap::from([("EUR".to_string(), 0.5)])).unwrap();
+        let was = solution(
+            vec![leg("YVR", "YYZ", 3, 200.0), leg("YYZ", "YVR", 1, 300.0), leg("YVR", "SEA", 5, 100.0)],
+            BASE_CURRENCY,
+        );
+        // The same trip a day later going home, and through Calgary rather than Seattle, priced in euros
+        let now = solution(
+            vec![leg("YYZ", "YVR", 1, 100.0), leg("YVR", "YYZ", 4, 125.0), leg("YVR", "YYC", 5, 50.0)],
+            "EUR",
+        );
+
+        let diff = diff_solutions(&was, &now, &rates).unwrap();
+        assert_eq!(diff.currency, BASE_CURRENCY);
+        assert_eq!((diff.was_total, diff.now_total, diff.total_change()), (600.0, 550.0, -50.0));
+
+        let legs: Vec<_> = diff
+            .legs
+            .iter()
+            .map(|l| (l.src.to_string(), l.dest.to_string(), l.price_change, l.days_moved))
+            .collect();
+        assert_eq!(
+            legs,
+            [
+                ("YYZ".to_string(), "YVR".to_string(), Some(-100.0), Some(0)),
+                ("YVR".to_string(), "YYZ".to_string(), Some(50.0), Some(1)),
+                ("YVR".to_string(), "SEA".to_string(), None, None),
+                ("YVR".to_string(), "YYC".to_string(), None, None),
+            ]
+        );
+        assert_eq!(diff.legs[3].now.as_ref().unwrap().price, 100.0);
+
+        assert!(diff_solutions(&was, &solution(vec![], "XYZ"), &rates).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Estimated fares for days the provider has no quote for, so a gap in its data doesn't rule out every route through it.
+//!
+//! A missing day is priced from the nearest quoted days either side on the same route, interpolating between them by
+//! distance, or taking the one side's fare if only one side has a quote. Routes are compared on the estimate plus an
+//! uncertainty penalty, so a quoted fare wins over an estimate of the same price. Estimated legs are flagged in the
+//! solution so they can be checked before booking.
+
+/// How far to look for quotes around a missing day and how much to distrust what's found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceEstimates {
+    /// Furthest a quoted day can be from the missing one, either side
+    pub max_gap_days: u32,
+    /// Fraction of the estimate added to it when comparing routes
+    pub uncertainty: f32,
+}
+
+impl PriceEstimates {
+    /// Fare between the nearest quote before and after the missing day, each given as `(days away, price)`. `None`
+    /// if neither side has one.
+    pub fn interpolate(before: Option<(u32, f32)>, after: Option<(u32, f32)>) -> Option<f32> {
+        match (before, after) {
+            (Some((before_days, before_price)), Some((after_days, after_price))) => {
+                let weight = before_days as f32 / (before_days + after_days) as f32;
+                Some(before_price + (after_price - before_price) * weight)
+            }
+            (Some((_, price)), None) | (None, Some((_, price))) => Some(price),
+            (None, None) => None,
+        }
+    }
+
+    /// What an estimated fare of `price` counts as when comparing routes
+    pub fn penalized(&self, price: f32) -> f32 {
+        price * (1.0 + self.uncertainty)
+    }
+}
+
+#[cfg(test)]
+mod estimates_tests {
+    use super::PriceEstimates;
+
+    #[test]
+    fn test_interpolate() {
+        // A day after the 100 fare and three before the 300 one is a quarter of the way between them
+        assert_eq!(PriceEstimates::interpolate(Some((1, 100.0)), Some((3, 300.0))), Some(150.0));
+        assert_eq!(PriceEstimates::interpolate(None, Some((2, 300.0))), Some(300.0));
+        assert_eq!(PriceEstimates::interpolate(None, None), None);
+
+        let estimates = PriceEstimates {
+            max_gap_days: 3,
+            uncertainty: 0.25,
+        };
+        assert_eq!(estimates.penalized(200.0), 250.0);
+    }
+}
@@ -0,0 +1,495 @@
+//! Flat tables of fetched quotes and solved routes, for analysing fares in pandas, Polars or a spreadsheet.
+//!
+//! Quotes come from the price cache, one row per quote. Results come from finished jobs, one row per leg with the
+//! solve it belongs to repeated on each. Tables are written as CSV, or as Parquet when built with the `parquet-export`
+//! feature. The same tables are served by the admin export endpoint and written by `route-solver export`.
+
+use std::{io::Write, path::PathBuf, str::FromStr};
+
+use chrono::NaiveDateTime;
+use route_solver_shared::{
+    date_expr::parse_dates,
+    queries::{CabinClass, Date, JobId, JobStatus, RouteSolution, TransportMode, BASE_CURRENCY},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    cache::{CacheError, PriceCache},
+    config::Config,
+    job_store::{JobStore, JobStoreError},
+    snapshot::{SnapshotFilter, SnapshotQuote},
+};
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Could not write CSV.")]
+    Csv(csv::Error),
+    #[error("Could not write JSON.")]
+    Json(serde_json::Error),
+    #[cfg(feature = "parquet-export")]
+    #[error("Could not lay out rows for Parquet.")]
+    Arrow(arrow_schema::ArrowError),
+    #[cfg(feature = "parquet-export")]
+    #[error("Could not write Parquet.")]
+    Parquet(parquet::errors::ParquetError),
+    #[error("Export format {0:?} was not compiled in.")]
+    FormatUnavailable(ExportFormat),
+    #[error("Could not read the price cache.")]
+    Cache(#[from] CacheError),
+    #[error("Could not read finished jobs.")]
+    Jobs(#[from] JobStoreError),
+    #[error("Could not write export file.")]
+    Io(std::io::Error),
+    #[error("{0}")]
+    Usage(String),
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// The rows as objects, see [JsonTable]
+    Json,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(ExportError::Usage(format!("Unknown export format {}, expected csv, json or parquet", s))),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportTable {
+    /// Every cached quote, see [QuoteRow]
+    Quotes,
+    /// Every leg of every finished solve, see [LegRow]
+    Results,
+}
+
+impl FromStr for ExportTable {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quotes" => Ok(ExportTable::Quotes),
+            "results" => Ok(ExportTable::Results),
+            _ => Err(ExportError::Usage(format!("Unknown table {}, expected quotes or results", s))),
+        }
+    }
+}
+
+/// A row of an exported table. Rows serialize to their columns in order, Parquet needs their types spelled out too.
+pub trait ExportRow: Serialize {
+    #[cfg(feature = "parquet-export")]
+    fn fields() -> Vec<arrow_schema::Field>;
+}
+
+/// A cached quote, prices are per passenger
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuoteRow {
+    pub src: String,
+    pub dest: String,
+    pub date: Date,
+    pub cabin: CabinClass,
+    pub market: String,
+    pub price: f32,
+    pub currency: String,
+    pub direct: bool,
+    pub arrives: Option<NaiveDateTime>,
+    pub quoted_at: Option<NaiveDateTime>,
+    pub spread: f32,
+}
+
+impl From<&SnapshotQuote> for QuoteRow {
+    fn from(SnapshotQuote { leg, quote }: &SnapshotQuote) -> Self {
+        QuoteRow {
+            src: leg.flight.src.to_string(),
+            dest: leg.flight.dest.to_string(),
+            date: leg.flight.date,
+            cabin: leg.cabin,
+            market: leg.market.clone(),
+            price: quote.min_price,
+            currency: quote.currency.map_or(BASE_CURRENCY.to_string(), |c| c.to_string()),
+            direct: quote.direct,
+            arrives: quote.arrives,
+            quoted_at: quote.quoted_at,
+            spread: quote.spread,
+        }
+    }
+}
+
+impl ExportRow for QuoteRow {
+    #[cfg(feature = "parquet-export")]
+    fn fields() -> Vec<arrow_schema::Field> {
+        use arrow_schema::{DataType, Field, TimeUnit};
+
+        vec![
+            Field::new("src", DataType::Utf8, false),
+            Field::new("dest", DataType::Utf8, false),
+            Field::new("date", DataType::Date32, false),
+            Field::new("cabin", DataType::Utf8, false),
+            Field::new("market", DataType::Utf8, false),
+            Field::new("price", DataType::Float32, false),
+            Field::new("currency", DataType::Utf8, false),
+            Field::new("direct", DataType::Boolean, false),
+            Field::new("arrives", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new("quoted_at", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new("spread", DataType::Float32, false),
+        ]
+    }
+}
+
+/// A leg of a solved route, prices are in the solution's currency
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LegRow {
+    pub job_id: JobId,
+    pub request_id: Option<String>,
+    /// Where the leg comes in the route, from 0
+    pub leg: u32,
+    pub src: String,
+    pub dest: String,
+    pub date: Date,
+    pub arrival: Option<Date>,
+    pub mode: TransportMode,
+    pub price: f32,
+    pub stay_price: f32,
+    pub estimated: bool,
+    pub std_dev: f32,
+    pub quoted_at: Option<NaiveDateTime>,
+    pub total_price: f32,
+    pub currency: String,
+    pub passengers: u32,
+}
+
+impl LegRow {
+    /// The solution's legs `filter` matches, in the order they're travelled. Legs are numbered before they're filtered.
+    pub fn for_solution(job_id: JobId, solution: &RouteSolution, filter: &SnapshotFilter) -> Vec<LegRow> {
+        let mut flights: Vec<_> = solution.flights.iter().collect();
+        flights.sort_by_key(|f| f.flight.date);
+
+        flights
+            .into_iter()
+            .enumerate()
+            .filter(|(_, f)| filter.matches(&f.flight))
+            .map(|(leg, f)| LegRow {
+                job_id,
+                request_id: solution.request_id.clone(),
+                leg: leg as u32,
+                src: f.flight.src.to_string(),
+                dest: f.flight.dest.to_string(),
+                date: f.flight.date,
+                arrival: f.arrival,
+                mode: f.mode,
+                price: f.price,
+                stay_price: f.stay_price,
+                estimated: f.source.is_estimate(),
+                std_dev: f.std_dev,
+                quoted_at: f.quoted_at,
+                total_price: solution.total_price,
+                currency: solution.currency.clone(),
+                passengers: solution.passengers,
+            })
+            .collect()
+    }
+}
+
+impl ExportRow for LegRow {
+    #[cfg(feature = "parquet-export")]
+    fn fields() -> Vec<arrow_schema::Field> {
+        use arrow_schema::{DataType, Field, TimeUnit};
+
+        vec![
+            Field::new("job_id", DataType::UInt64, false),
+            Field::new("request_id", DataType::Utf8, true),
+            Field::new("leg", DataType::UInt32, false),
+            Field::new("src", DataType::Utf8, false),
+            Field::new("dest", DataType::Utf8, false),
+            Field::new("date", DataType::Date32, false),
+            Field::new("arrival", DataType::Date32, true),
+            Field::new("mode", DataType::Utf8, false),
+            Field::new("price", DataType::Float32, false),
+            Field::new("stay_price", DataType::Float32, false),
+            Field::new("estimated", DataType::Boolean, false),
+            Field::new("std_dev", DataType::Float32, false),
+            Field::new("quoted_at", DataType::Timestamp(TimeUnit::Second, None), true),
+            Field::new("total_price", DataType::Float32, false),
+            Field::new("currency", DataType::Utf8, false),
+            Field::new("passengers", DataType::UInt32, false),
+        ]
+    }
+}
+
+/// Rows for every cached quote `filter` matches
+pub fn quote_rows(cache: &PriceCache, filter: &SnapshotFilter) -> Result<Vec<QuoteRow>, CacheError> {
+    Ok(cache.export(filter)?.quotes.iter().map(QuoteRow::from).collect())
+}
+
+/// Rows for the legs `filter` matches of every completed job
+pub fn leg_rows(jobs: impl IntoIterator<Item = (JobId, JobStatus)>, filter: &SnapshotFilter) -> Vec<LegRow> {
+    jobs.into_iter()
+        .filter_map(|(id, status)| match status {
+            JobStatus::Completed { solution } => Some(LegRow::for_solution(id, &solution, filter)),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+pub fn write_table<R: ExportRow>(rows: &[R], format: ExportFormat, out: impl Write + Send) -> Result<(), ExportError> {
+    match format {
+        ExportFormat::Csv => write_csv(rows, out),
+        ExportFormat::Json => serde_json::to_writer(out, &JsonTable::new(rows)).map_err(ExportError::Json),
+        #[cfg(feature = "parquet-export")]
+        ExportFormat::Parquet => write_parquet(rows, out),
+        #[allow(unreachable_patterns)]
+        format => Err(ExportError::FormatUnavailable(format)),
+    }
+}
+
+/// An exported table as JSON, the rows with the version of their layout. The version goes up when a column is removed,
+/// renamed or changes meaning, not when one is added.
+#[derive(Serialize, Debug)]
+pub struct JsonTable<'a, R> {
+    pub schema_version: u32,
+    pub rows: &'a [R],
+}
+
+impl<'a, R: ExportRow> JsonTable<'a, R> {
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    pub fn new(rows: &'a [R]) -> JsonTable<'a, R> {
+        JsonTable {
+            schema_version: Self::SCHEMA_VERSION,
+            rows,
+        }
+    }
+}
+
+fn write_csv<R: ExportRow>(rows: &[R], out: impl Write) -> Result<(), ExportError> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row).map_err(ExportError::Csv)?;
+    }
+    writer.flush().map_err(|e| ExportError::Csv(e.into()))
+}
+
+#[cfg(feature = "parquet-export")]
+fn write_parquet<R: ExportRow>(rows: &[R], out: impl Write + Send) -> Result<(), ExportError> {
+    use std::sync::Arc;
+
+    let schema = Arc::new(arrow_schema::Schema::new(R::fields()));
+    let mut decoder = arrow_json::ReaderBuilder::new(Arc::clone(&schema))
+        .build_decoder()
+        .map_err(ExportError::Arrow)?;
+    decoder.serialize(rows).map_err(ExportError::Arrow)?;
+
+    let mut writer = parquet::arrow::ArrowWriter::try_new(out, schema, None).map_err(ExportError::Parquet)?;
+    if let Some(batch) = decoder.flush().map_err(ExportError::Arrow)? {
+        writer.write(&batch).map_err(ExportError::Parquet)?;
+    }
+    writer.close().map_err(ExportError::Parquet)?;
+    Ok(())
+}
+
+/// `route-solver export <quotes|results> <path> [--format csv|json|parquet] [--airports A,B] [--from DATE] [--to DATE]
+/// [--dates EXPR]`. The format defaults to the one the path's extension names, CSV failing that. `--dates` takes any
+/// expression [parse_dates] reads, e.g. `"early June"`, and sets both ends of the range to its first and last days.
+pub struct ExportCommand {
+    pub table: ExportTable,
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    pub filter: SnapshotFilter,
+}
+
+impl ExportCommand {
+    /// Read the command from the arguments after `export`
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<ExportCommand, ExportError> {
+        let usage = || ExportError::Usage(Self::USAGE.to_string());
+        let table = args.next().ok_or_else(usage)?.parse()?;
+        let path = PathBuf::from(args.next().ok_or_else(usage)?);
+        let mut format = path.extension().and_then(|e| e.to_str()?.parse().ok()).unwrap_or_default();
+        let mut filter = SnapshotFilter::default();
+
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(usage)?;
+            let date = |value: &str| {
+                value
+                    .parse()
+                    .map_err(|_| ExportError::Usage(format!("{} isn't a YYYY-MM-DD date", value)))
+            };
+            match flag.as_str() {
+                "--format" => format = value.parse()?,
+                "--airports" => {
+                    let codes = value.split(',').map(|code| code.trim().parse()).collect::<Result<_, _>>();
+                    filter.airports = Some(codes.map_err(|e| ExportError::Usage(format!("{}", e)))?);
+                }
+                "--from" => filter.from = Some(date(&value)?),
+                "--to" => filter.to = Some(date(&value)?),
+                "--dates" => {
+                    let today = chrono::Utc::now().date_naive();
+                    let dates = parse_dates(&value, today).map_err(|e| ExportError::Usage(e.to_string()))?.span();
+                    filter.from = Some(dates.first());
+                    filter.to = Some(dates.last());
+                }
+                _ => return Err(usage()),
+            }
+        }
+
+        Ok(ExportCommand {
+            table,
+            path,
+            format,
+            filter,
+        })
+    }
+
+    const USAGE: &'static str =
+        "Usage: route-solver export <quotes|results> <path> [--format csv|json|parquet] [--airports A,B] [--from DATE] [--to DATE] [--dates EXPR]";
+
+    /// Write the table from the cache and job store `config` points at, returns how many rows were written. Quotes
+    /// only outlive the server in the SQLite and Redis caches, results only with a job state directory.
+    pub fn run(&self, config: &Config) -> Result<usize, ExportError> {
+        let file = std::fs::File::create(&self.path).map_err(ExportError::Io)?;
+        match self.table {
+            ExportTable::Quotes => {
+                let rows = quote_rows(&PriceCache::from_config(&config.cache)?, &self.filter)?;
+                write_table(&rows, self.format, file)?;
+                Ok(rows.len())
+            }
+            ExportTable::Results => {
+                let Some(dir) = &config.jobs.state_dir else {
+                    return Err(ExportError::Usage("No job state directory is configured".to_string()));
+                };
+                let records = JobStore::open(dir)?.load_all()?;
+                let rows = leg_rows(records.into_iter().map(|r| (r.id, r.status)), &self.filter);
+                write_table(&rows, self.format, file)?;
+                Ok(rows.len())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use route_solver_shared::queries::{CabinClass, Date, Flight, FlightPrice, JobStatus, PriceSource, RouteSolution, TransportMode};
+
+    use super::{leg_rows, quote_rows, write_table, ExportCommand, ExportFormat};
+    use crate::{cache::PriceCache, flight_api::Quote, snapshot::SnapshotFilter};
+
+    #[test]
+    fn test_export_tables() {
+        let flight = |dest: &str, day| Flight {
+            src: "YYZ".parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+        };
+        let cache = PriceCache::in_memory();
+        let quote = Quote {
+            min_price: 120.5,
+            direct: true,
+            arrives: None,
+            quoted_at: Date::from_ymd_opt(2023, 1, 20).unwrap().and_hms_opt(8, 0, 0),
+            spread: 0.1,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        cache.put_quote(&flight("YVR", 1), CabinClass::Business, "GB", quote);
+
+        let quotes = quote_rows(&cache, &SnapshotFilter::default()).unwrap();
+        let mut csv = Vec::new();
+        write_table(&quotes, ExportFormat::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "src,dest,date,cabin,market,price,currency,direct,arrives,quoted_at,spread\n\
+             YYZ,YVR,2023-02-01,business,GB,120.5,USD,true,,2023-01-20T08:00:00,0.1\n"
+        );
+
+        let leg = |dest, day| FlightPrice {
+            flight: flight(dest, day),
+            price: 100.0,
+            stay_price: 0.0,
+            mode: TransportMode::Flight,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 0.0,
+            alternative: None,
+        };
+        let solution = RouteSolution {
+            flights: vec![leg("YVR", 5), leg("SEA", 2)],
+            total_price: 200.0,
+            currency: "USD".to_string(),
+            passengers: 1,
+            alternatives: Vec::new(),
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+        let jobs = vec![(1, JobStatus::Completed { solution }), (2, JobStatus::Queued)];
+        let rows = leg_rows(jobs, &SnapshotFilter::default());
+        let legs: Vec<_> = rows.iter().map(|r| (r.job_id, r.leg, r.dest.as_str())).collect();
+        assert_eq!(legs, [(1, 0, "SEA"), (1, 1, "YVR")]);
+
+        let mut json = Vec::new();
+        write_table(&rows, ExportFormat::Json, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["rows"][1]["dest"], "YVR");
+
+        #[cfg(feature = "parquet-export")]
+        {
+            use parquet::file::reader::{FileReader, SerializedFileReader};
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("results.parquet");
+            write_table(&rows, ExportFormat::Parquet, std::fs::File::create(&path).unwrap()).unwrap();
+            let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+            assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+            assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), 16);
+            // Quote timestamps have to parse into the timestamp columns
+            write_table(&quotes, ExportFormat::Parquet, std::fs::File::create(&path).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_export_command() {
+        let args = |args: &[&str]| ExportCommand::parse(args.iter().map(|arg| arg.to_string()));
+        let command = args(&["quotes", "out.csv", "--dates", "2023-02-01..2023-02-05"]).unwrap();
+        assert_eq!(command.filter.from, Date::from_ymd_opt(2023, 2, 1));
+        assert_eq!(command.filter.to, Date::from_ymd_opt(2023, 2, 5));
+        assert!(args(&["quotes", "out.csv", "--dates", "someday"]).is_err());
+        assert!(args(&["quotes"]).is_err());
+    }
+}
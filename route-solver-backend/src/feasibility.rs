@@ -0,0 +1,326 @@
+//! Quick checks of whether an itinerary's dates can work at all, from its windows alone so the builder can point out a
+//! problem as the itinerary is typed in rather than after a solve has priced its way to nothing.
+//!
+//! The check walks the same stops, orders and days as the router, see [Router::calc](crate::router::Router::calc), but
+//! without fares, arrival times or the route's limits. So a feasible itinerary can still fail to solve, for want of
+//! flights, but an infeasible one never solves.
+//!
+//! The legs the walk comes across are also what a solve could look up, so they're what [estimate_calls] counts a
+//! solve's provider calls from.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use route_solver_shared::queries::{
+    window_conflicts, AirportCode, Date, Feasibility, Flight, RouteQuery, SearchStrategy, SolveEstimate, WindowConflict,
+};
+
+use crate::{cache::PriceCache, flight_api::PointOfSale};
+
+/// Most states the check tries before giving up on telling, each a set of stops visited, where the trip is and the day
+/// it got there
+pub const MAX_STATES: usize = 100_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    /// Bit `i` set once stop `i` is visited, like the router's nodes
+    visited: u64,
+    stop: usize,
+    /// `None` at the start, which isn't arrived at
+    arrival: Option<Date>,
+}
+
+/// What walking the query's stops, orders and days came to
+struct Walk {
+    /// The stops in an order their dates allow, start to end, empty if there isn't one
+    ordering: Vec<AirportCode>,
+    /// Every leg some order of the stops could fly, on each day it could fly on
+    legs: HashSet<Flight>,
+    /// By the number of stops visited, how many states had been and the most legs any of them could fly next
+    depths: BTreeMap<u32, (usize, usize)>,
+    gave_up: bool,
+}
+
+fn walk(query: &RouteQuery) -> Walk {
+    let stops = query.destinations();
+    let last = stops.len() - 1;
+    let mut origins = vec![stops[0].iata];
+    for &origin in &query.alternate_origins {
+        if !origins.contains(&origin) {
+            origins.push(origin);
+        }
+    }
+
+    let start = State {
+        visited: 1,
+        stop: 0,
+        arrival: None,
+    };
+    // Each state reached and the one it was reached from, to read the ordering back
+    let mut reached_from = HashMap::from([(start, None)]);
+    let mut to_visit = vec![start];
+    let mut legs = HashSet::new();
+    let mut depths = BTreeMap::new();
+    let mut end = None;
+    let mut gave_up = false;
+
+    'search: while let Some(state) = to_visit.pop() {
+        if state.stop == last {
+            end.get_or_insert(state);
+            continue;
+        }
+        let unvisited: Vec<usize> = (1..last).filter(|&i| state.visited & (1 << i) == 0).collect();
+        let next_stops = if unvisited.is_empty() { vec![last] } else { unvisited };
+
+        // The trip may leave from any of the origins, they all share the start's dates
+        let from = if state.stop == 0 { &origins[..] } else { std::slice::from_ref(&stops[state.stop].iata) };
+        let (states, most_out) = depths.entry(state.visited.count_ones()).or_insert((0, 0));
+        *states += 1;
+        let mut out = 0;
+        for next in next_stops {
+            let dates = &stops[state.stop].dates;
+            for date in dates.get_intersect_iter_with_next(&stops[next].dates, state.arrival) {
+                out += from.len();
+                legs.extend(from.iter().map(|&src| Flight {
+                    src,
+                    dest: stops[next].iata,
+                    date,
+                }));
+
+                let after = State {
+                    visited: state.visited | 1 << next,
+                    stop: next,
+                    arrival: Some(date),
+                };
+                if reached_from.contains_key(&after) {
+                    continue;
+                }
+                if reached_from.len() >= MAX_STATES {
+                    gave_up = true;
+                    break 'search;
+                }
+                reached_from.insert(after, Some(state));
+                to_visit.push(after);
+            }
+        }
+        *most_out = (*most_out).max(out);
+    }
+
+    let mut ordering = Vec::new();
+    let mut state = end;
+    while let Some(at) = state {
+        ordering.push(stops[at.stop].iata);
+        state = reached_from[&at];
+    }
+    ordering.reverse();
+
+    Walk {
+        ordering,
+        legs,
+        depths,
+        gave_up,
+    }
+}
+
+/// Whether the query's stops can be visited in an order their dates allow, which windows clash and how many fares a
+/// solve of it could look up
+pub fn check_dates(query: &RouteQuery) -> Feasibility {
+    let stops = query.destinations();
+    let walk = walk(query);
+
+    Feasibility {
+        feasible: match (walk.ordering.is_empty(), walk.gave_up) {
+            (false, _) => Some(true),
+            (true, false) => Some(false),
+            (true, true) => None,
+        },
+        conflicts: window_conflicts(&stops.iter().map(|s| s.dates.clone()).collect::<Vec<_>>())
+            .into_iter()
+            .map(|(first, second)| WindowConflict {
+                first: stops[first].iata,
+                second: stops[second].iata,
+            })
+            .collect(),
+        legs: walk.legs.len() as u64,
+        ordering: walk.ordering,
+    }
+}
+
+/// How many provider calls a solve of the query would make, every leg it could fly which isn't in `cache` already.
+/// A beam only carries on from so many routes at each number of stops visited, so it makes no more calls than that
+/// many of the routes with the most legs to choose from next would. A solution of the whole query `tenant` has cached,
+/// `None` for the default tenant, makes none. `confirm_above` is passed through, see
+/// [SolveEstimate::needs_confirmation].
+pub fn estimate_calls(
+    query: &RouteQuery,
+    cache: &PriceCache,
+    tenant: Option<&str>,
+    confirm_above: Option<u64>,
+) -> SolveEstimate {
+    let walk = walk(query);
+    let market = PointOfSale::of(query).market;
+    let cached = walk
+        .legs
+        .iter()
+        .filter(|leg| {
+            cache.get_quote(leg, query.cabin_class, &market).is_some() || cache.is_no_fare(leg, query.cabin_class, &market)
+        })
+        .count() as u64;
+    let legs = walk.legs.len() as u64;
+
+    let mut calls = legs - cached;
+    if let SearchStrategy::Beam { width } = query.strategy {
+        let beam: usize = walk.depths.values().map(|&(states, most_out)| states.min(width) * most_out).sum();
+        calls = calls.min(beam as u64);
+    }
+    if cache.get_result(query, tenant).is_some() {
+        calls = 0;
+    }
+
+    SolveEstimate {
+        legs,
+        cached,
+        calls,
+        partial: walk.gave_up,
+        confirm_above,
+        remaining: None,
+    }
+}
+
+#[cfg(test)]
+mod feasibility_tests {
+    use std::collections::BTreeMap;
+
+    use route_solver_shared::queries::{
+        CabinClass, Date, DateWindow, DestinationQuery, Flight, Objective, Passengers, RouteLimits, RouteQuery,
+        SearchStrategy, WindowConflict,
+    };
+
+    use super::{check_dates, estimate_calls};
+    use crate::{cache::PriceCache, flight_api::Quote};
+
+    fn day(d: u32) -> Date {
+        Date::from_ymd_opt(2023, 6, d).unwrap()
+    }
+
+    fn stop(iata: &str, arrival: Option<(u32, u32)>, departure: Option<(u32, u32)>) -> DestinationQuery {
+        DestinationQuery {
+            iata: iata.parse().unwrap(),
+            arrival_window: arrival.and_then(|(first, last)| DateWindow::new(day(first), day(last))),
+            departure_window: departure.and_then(|(first, last)| DateWindow::new(day(first), day(last))),
+            min_days: None,
+            max_days: None,
+            event: None,
+            max_leg_price: None,
+        }
+    }
+
+    fn query(hops: Vec<DestinationQuery>) -> RouteQuery {
+        RouteQuery {
+            start_city: stop("YYZ", None, Some((1, 6))),
+            end_city: stop("YYZ", Some((12, 21)), None),
+            hops,
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            currency: None,
+            market: None,
+            locale: None,
+            passengers: Passengers::default(),
+            cabin_class: CabinClass::Economy,
+            strategy: SearchStrategy::default(),
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        }
+    }
+
+    #[test]
+    fn test_feasible_ordering() {
+        // Listed out of order, only Calgary then Vancouver fits
+        let feasible = check_dates(&query(vec![
+            stop("YVR", Some((6, 9)), Some((12, 15))),
+            stop("YYC", Some((1, 3)), Some((5, 8))),
+        ]));
+        assert_eq!(feasible.feasible, Some(true));
+        let ordering: Vec<String> = feasible.ordering.iter().map(|a| a.to_string()).collect();
+        assert_eq!(ordering, ["YYZ", "YYC", "YVR", "YYZ"]);
+        assert!(feasible.conflicts.is_empty());
+        // Toronto to Calgary on the 1st to 3rd and to Vancouver on the 6th, which goes nowhere, then Calgary to Vancouver
+        // on the 6th to 8th and home on the 12th to 15th
+        assert_eq!(feasible.legs, 3 + 1 + 3 + 4);
+
+        // Each origin's first legs count
+        let mut with_origin = query(vec![stop("YYC", Some((1, 3)), Some((12, 13)))]);
+        with_origin.alternate_origins.push("YTZ".parse().unwrap());
+        assert_eq!(check_dates(&with_origin).legs, 2 * 3 + 2);
+    }
+
+    #[test]
+    fn test_conflicting_windows() {
+        // Both want the same days, so whichever goes second is reached too late
+        let clash = check_dates(&query(vec![
+            stop("YVR", Some((5, 6)), Some((9, 10))),
+            stop("YYC", Some((5, 6)), Some((9, 10))),
+        ]));
+        assert_eq!(clash.feasible, Some(false));
+        assert!(clash.ordering.is_empty());
+        assert_eq!(
+            clash.conflicts,
+            [WindowConflict {
+                first: "YVR".parse().unwrap(),
+                second: "YYC".parse().unwrap(),
+            }]
+        );
+
+        // A stop the trip has to leave after it's due home
+        let late = check_dates(&query(vec![stop("YVR", Some((18, 19)), Some((22, 23)))]));
+        assert_eq!(late.feasible, Some(false));
+        assert_eq!(late.conflicts.len(), 1);
+        assert_eq!(late.conflicts[0].second, "YYZ");
+    }
+
+    #[test]
+    fn test_estimate_calls() {
+        let trip = query(vec![stop("YYC", Some((1, 3)), Some((12, 13)))]);
+        let cache = PriceCache::in_memory();
+        let leg = |src: &str, dest: &str, d| Flight {
+            src: src.parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date: day(d),
+        };
+        let quote = Quote {
+            min_price: 120.0,
+            direct: true,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.0,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
+        };
+        cache.put_quote(&leg("YYZ", "YYC", 1), CabinClass::Economy, "US", quote);
+        cache.put_no_fare(&leg("YYC", "YYZ", 12), CabinClass::Economy, "US");
+        // Only fares for the query's cabin count
+        cache.put_no_fare(&leg("YYC", "YYZ", 13), CabinClass::Business, "US");
+
+        let estimate = estimate_calls(&trip, &cache, None, Some(2));
+        assert_eq!((estimate.legs, estimate.cached, estimate.calls), (5, 2, 3));
+        assert!(estimate.needs_confirmation());
+        assert!(!estimate.is_confirmed_by(None) && !estimate.is_confirmed_by(Some(2)));
+        assert!(estimate.is_confirmed_by(Some(3)));
+        assert!(!estimate_calls(&trip, &cache, None, Some(3)).needs_confirmation());
+        assert!(!estimate_calls(&trip, &cache, None, None).needs_confirmation());
+
+        // Stops which can go in any order, a narrow beam only carries on from a few of the ways there
+        let hops = ["YVR", "YYC", "YUL"].map(|iata| stop(iata, Some((1, 6)), Some((1, 12)))).to_vec();
+        let loose = query(hops);
+        let empty = PriceCache::in_memory();
+        let every = estimate_calls(&loose, &empty, None, None);
+        assert_eq!(every.calls, every.legs);
+        let beam_query = RouteQuery { strategy: SearchStrategy::Beam { width: 1 }, ..loose };
+        let beam = estimate_calls(&beam_query, &empty, None, None);
+        assert!(beam.calls < every.calls);
+    }
+}
@@ -1,22 +1,75 @@
 //! Flight API module to communicate to API.
 //!
-//! Handles communication with flight pricing API, right now we use the SkyScanner REST API.
+//! Handles communication with flight pricing API, right now we use the SkyScanner REST API. Its indicative prices are
+//! cheap cached estimates, its live search slower bookable fares.
 
-use route_solver_shared::queries::{Date, Flight, SingleDateRange};
-use serde::{ser::SerializeStruct, Serialize};
-use std::{collections::HashMap, time};
+use route_solver_shared::queries::{ApiError, CabinClass, Date, Flight, RouteQuery, DateWindow, BASE_CURRENCY};
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time,
+};
 use thiserror::Error;
-use chrono::{NaiveDate, Datelike};
+
+use crate::{
+    cache::CacheStats,
+    config::{HttpConfig, SimulationConfig},
+    currency::{CurrencyCode, CurrencyError, ExchangeRates},
+    log,
+};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde_json::Value;
 
 const SKYSCANNER_IND_PRICES_ENDPOINT: &str =
     "https://partners.api.skyscanner.net/apiservices/v3/flights/indicative/search";
+const SKYSCANNER_LIVE_CREATE_ENDPOINT: &str =
+    "https://partners.api.skyscanner.net/apiservices/v3/flights/live/search/create";
+const SKYSCANNER_LIVE_POLL_ENDPOINT: &str = "https://partners.api.skyscanner.net/apiservices/v3/flights/live/search/poll";
 const SKYSCANNER_PUB_API_KEY: &str = "sh428739766321522266746152871799";
 
+/// Spread of SkyScanner's indicative prices, cached from searches rather than live, see [Quote::spread]
+const SKYSCANNER_SPREAD: f32 = 0.1;
+/// Where each field of an indicative quote has been seen, tried in order so a renamed field still turns up
+const INDICATIVE_QUOTES: &[&[&str]] = &[&["content", "results", "quotes"], &["results", "quotes"], &["quotes"]];
+const INDICATIVE_PRICE: &[&[&str]] = &[&["minPrice", "amount"], &["minPrice", "value"], &["price", "amount"]];
+const INDICATIVE_DIRECT: &[&[&str]] = &[&["isDirect"], &["direct"], &["is_direct"]];
+const INDICATIVE_CURRENCY: &[&[&str]] = &[&["minPrice", "currency"], &["price", "currency"], &["currency"]];
+const INDICATIVE_QUOTED_AT: &[&[&str]] = &[
+    &["outboundLeg", "quoteCreationTimestamp"],
+    &["quoteCreationTimestamp"],
+    &["outboundLeg", "quoteCreatedAt"],
+];
+
+/// Market fares are bought in unless the query says otherwise, see [PointOfSale]
+pub const DEFAULT_MARKET: &str = "US";
+/// Locale providers are asked in unless the query says otherwise
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// How long to wait between polls of a live search that hasn't finished
+const LIVE_POLL_INTERVAL: time::Duration = time::Duration::from_millis(500);
+/// Polls of a live search before settling for whatever it has found, SkyScanner usually completes within a few
+const LIVE_MAX_POLLS: usize = 20;
+/// Status of a live search once every partner has answered
+const LIVE_STATUS_COMPLETE: &str = "RESULT_STATUS_COMPLETE";
+
+/// Spread a quote gains each day it ages
+const SPREAD_PER_DAY: f32 = 0.01;
+/// Spread a quote can reach however old it is
+const MAX_SPREAD: f32 = 0.5;
+
+/// Bytes of a response saved by [capture_response], the rest is cut off
+const MAX_CAPTURE_BYTES: usize = 256 * 1024;
+/// Responses [capture_response] keeps in its directory before it stops saving more
+const MAX_CAPTURE_FILES: usize = 200;
+
 #[derive(Clone)]
 pub struct LegQuery {
     pub start: String,
     pub end: String,
-    pub date: SingleDateRange,
+    pub date: DateWindow,
 }
 
 #[derive(Debug, Error)]
@@ -31,20 +84,145 @@ pub enum QueryError {
     ResponseUnexpectedFormatErr(String),
     #[error("Rate limit for API exceeded")]
     RateLimitExceeded,
+    #[error("Daily API quota for this user exceeded")]
+    QuotaExceeded,
     #[error("Bad response from API.")]
     BadResponse(u16),
-    #[error("[Test only] legs don't exist")]
-    NonExistentLeg,
+    /// The provider answered but has no fare for the flight, e.g. nothing flies between the cities that day
+    #[error("No fare found for the flight.")]
+    NoFare,
+    /// The provider priced in a currency there's no exchange rate for, so its fares can't be compared with others'
+    #[error("Provider quoted in an unsupported currency.")]
+    Currency(#[from] CurrencyError),
+}
+
+impl QueryError {
+    /// Whether the provider failed to answer, rather than answering there's no fare or the call not being allowed. A
+    /// solve can carry on without the flight, see [Router](crate::router::Router).
+    pub fn is_outage(&self) -> bool {
+        matches!(
+            self,
+            QueryError::ResponseConversionErr(..)
+                | QueryError::ReqwestErr(_)
+                | QueryError::ResponseUnexpectedFormatErr(_)
+                | QueryError::RateLimitExceeded
+                | QueryError::BadResponse(_)
+        )
+    }
+}
+
+impl From<QueryError> for ApiError {
+    fn from(e: QueryError) -> ApiError {
+        match e {
+            QueryError::NoLegs => ApiError::Validation(e.to_string()),
+            QueryError::QuotaExceeded => ApiError::RateLimited(e.to_string()),
+            _ => ApiError::ProviderUnavailable(e.to_string()),
+        }
+    }
 }
 
 #[async_trait::async_trait]
-pub trait PriceQuery {
+pub trait PriceQuery: Send {
     fn new() -> Self;
     async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError>;
+
+    /// Quotes fetched so far, used to checkpoint a solve so it can be resumed without re-querying.
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        Vec::new()
+    }
+
+    /// Seed the provider with quotes from an earlier checkpoint.
+    fn restore_quotes(&mut self, _quotes: Vec<(Flight, Quote)>) {}
+
+    /// Quote fares in `cabin` from here on, economy until this is called.
+    fn set_cabin_class(&mut self, _cabin: CabinClass) {}
+
+    /// Quote fares bought in `pos` from here on, [PointOfSale::default] until this is called.
+    fn set_point_of_sale(&mut self, _pos: &PointOfSale) {}
+
+    /// Make outbound calls with `client` from here on, see [http_client]. Providers start out with a client of their own.
+    fn set_http_client(&mut self, _client: &reqwest::Client) {}
+
+    /// Authenticate with `key` from here on, in place of the credentials the provider starts out with. Providers which
+    /// don't need any ignore it.
+    fn set_api_key(&mut self, _key: &str) {}
+
+    /// Save responses nothing could be made of to `dir`, to work out how the provider's format changed. Nothing is
+    /// saved if `None`, which is where providers start.
+    fn set_response_capture(&mut self, _dir: Option<Arc<PathBuf>>) {}
+
+    /// Convert fares quoted in other currencies to [BASE_CURRENCY] with `rates` from here on, the built in rates until
+    /// this is called.
+    fn set_exchange_rates(&mut self, _rates: Arc<ExchangeRates>) {}
+
+    /// Make fares up with the model `config` describes from here on. Only simulated providers do, the others ignore
+    /// it.
+    fn set_simulation(&mut self, _config: &SimulationConfig) {}
+
+    /// How many quotes a cache in front of the provider has answered so far, `None` if there isn't one.
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Calls the provider can still be asked for today before the user's quota runs out, `None` if nothing's keeping
+    /// count or the user has no limit.
+    fn remaining_calls(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Where fares are bought and the language providers are asked in, see [RouteQuery::market]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointOfSale {
+    /// ISO 3166 country code, upper case
+    pub market: String,
+    /// BCP 47 language tag
+    pub locale: String,
+}
+
+impl Default for PointOfSale {
+    fn default() -> Self {
+        PointOfSale {
+            market: DEFAULT_MARKET.to_string(),
+            locale: DEFAULT_LOCALE.to_string(),
+        }
+    }
+}
+
+impl PointOfSale {
+    /// Where the query's fares are bought, the defaults for whatever it leaves unset
+    pub fn of(query: &RouteQuery) -> PointOfSale {
+        PointOfSale {
+            market: query.market.as_deref().unwrap_or(DEFAULT_MARKET).to_uppercase(),
+            locale: query.locale.clone().unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+        }
+    }
 }
 
 pub struct SkyScannerApiQuery {
     db: HashMap<Flight, Quote>,
+    cabin: CabinClass,
+    pos: PointOfSale,
+    client: reqwest::Client,
+    /// Sent as `x-api-key`, the server's own unless a tenant has credentials of its own
+    api_key: String,
+    capture_dir: Option<Arc<PathBuf>>,
+    rates: Arc<ExchangeRates>,
+}
+
+/// Prices flights with SkyScanner's live search, bookable fares rather than [SkyScannerApiQuery]'s cached estimates.
+///
+/// Each flight is a search session of its own, created and then polled until every partner has answered, so a fare
+/// takes several calls and a few seconds to come back.
+pub struct SkyScannerLiveQuery {
+    db: HashMap<Flight, Quote>,
+    cabin: CabinClass,
+    pos: PointOfSale,
+    client: reqwest::Client,
+    /// Sent as `x-api-key`, the server's own unless a tenant has credentials of its own
+    api_key: String,
+    capture_dir: Option<Arc<PathBuf>>,
+    rates: Arc<ExchangeRates>,
 }
 
 pub struct TestPriceApiQuery {
@@ -56,16 +234,144 @@ pub struct Query {
     market: String,
     locale: String,
     currency: String,
+    #[serde(rename = "cabinClass", serialize_with = "serialize_cabin_class")]
+    cabin_class: CabinClass,
     #[serde(rename = "queryLegs")]
     query_legs: Vec<LegQuery>,
     #[serde(rename = "dateTimeGroupingType")]
     date_time_grouping_type: String,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Quote {
     pub min_price: f32,
     pub direct: bool,
+    /// Local time at the destination the flight lands, if the provider has a schedule for it. Indicative prices don't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrives: Option<NaiveDateTime>,
+    /// When the provider saw the fare, in UTC. `None` for live fares and providers which don't say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoted_at: Option<NaiveDateTime>,
+    /// How far the fare usually strays from what's quoted, as a fraction of it, before the quote ages at all. Depends on
+    /// the provider, live fares don't stray.
+    #[serde(default)]
+    pub spread: f32,
+    /// Currency `min_price` is in, [BASE_CURRENCY] if `None`. Providers hand out quotes in the base so the router
+    /// compares like with like, see [Quote::into_base].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<CurrencyCode>,
+    /// Airline selling the fare, `None` for providers which don't say. Indicative prices don't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carrier: Option<CarrierCode>,
+    /// What the fare with a checked bag costs on top of `min_price`, for providers with fare families to quote it from.
+    /// `None` leaves it to be estimated, see [BagFees](crate::baggage::BagFees).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bag_fee: Option<f32>,
+    /// Quoted by a fallback provider as the one being asked failed, see [FallbackPriceQuery](crate::providers::FallbackPriceQuery)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fallback: bool,
+}
+
+/// An airline's two character IATA code, e.g. "AC". Goes over the wire as a plain string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CarrierCode([u8; 2]);
+
+impl CarrierCode {
+    pub fn as_str(&self) -> &str {
+        // Only built by from_str, which only lets ASCII through
+        std::str::from_utf8(&self.0).expect("carrier codes are ASCII")
+    }
+}
+
+impl std::str::FromStr for CarrierCode {
+    type Err = String;
+
+    /// Parses two ASCII letters or digits, in either case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[a, b] if s.bytes().all(|b| b.is_ascii_alphanumeric()) => Ok(CarrierCode([a, b].map(|b| b.to_ascii_uppercase()))),
+            _ => Err(format!("\"{}\" isn't a carrier code", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for CarrierCode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<CarrierCode> for String {
+    fn from(code: CarrierCode) -> String {
+        code.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for CarrierCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for CarrierCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Quote {
+    /// The quote with its price converted to [BASE_CURRENCY] by `rates`
+    pub fn into_base(self, rates: &ExchangeRates) -> Result<Quote, QueryError> {
+        match self.currency {
+            Some(currency) if !currency.is_base() => Ok(Quote {
+                min_price: rates.to_base(self.min_price, currency.as_str())?,
+                bag_fee: self.bag_fee.map(|fee| rates.to_base(fee, currency.as_str())).transpose()?,
+                currency: None,
+                ..self
+            }),
+            _ => Ok(Quote { currency: None, ..self }),
+        }
+    }
+
+    /// One standard deviation of what the fare may be by `now`, wider the older the quote
+    pub fn std_dev(&self, now: NaiveDateTime) -> f32 {
+        let age_days = self.quoted_at.map_or(0.0, |at| (now - at).num_hours().max(0) as f32 / 24.0);
+        self.min_price * (self.spread + SPREAD_PER_DAY * age_days).min(MAX_SPREAD)
+    }
+}
+
+/// As much of a quote as could be found in a provider's response, fields it left out or spelled in a way nothing probes
+/// for are `None`. Formats drift, so a quote missing a detail is still worth having.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PartialQuote {
+    pub min_price: Option<f32>,
+    pub direct: Option<bool>,
+    pub arrives: Option<NaiveDateTime>,
+    pub quoted_at: Option<NaiveDateTime>,
+    /// What the provider said it priced in, the currency it was asked for if it didn't say
+    pub currency: Option<CurrencyCode>,
+    pub carrier: Option<CarrierCode>,
+}
+
+impl PartialQuote {
+    /// The quote, with the provider's `spread`, if its price is known. A flight not known to be direct is taken to stop.
+    pub fn complete(self, spread: f32) -> Option<Quote> {
+        let min_price = self.min_price.filter(|p| p.is_finite() && *p >= 0.0)?;
+        Some(Quote {
+            min_price,
+            direct: self.direct.unwrap_or(false),
+            arrives: self.arrives,
+            quoted_at: self.quoted_at,
+            spread,
+            currency: self.currency,
+            carrier: self.carrier,
+            bag_fee: None,
+            fallback: false,
+        })
+    }
 }
 
 impl Serialize for LegQuery {
@@ -84,19 +390,18 @@ impl Serialize for LegQuery {
             "destinationPlace",
             &HashMap::from([("queryPlace", &HashMap::from([("iata", self.end.clone())]))]),
         )?;
-        match &self.date {
-            SingleDateRange::None => {
-                panic!("Should not be sending a single date range none type to sky scanner")
-            }
-            SingleDateRange::FixedDate(date) => state.serialize_field(
+        let (date1, date2) = (self.date.first(), self.date.last());
+        if date1 == date2 {
+            state.serialize_field(
                 "fixedDate",
                 &HashMap::from([
-                    ("year", date.year()),
-                    ("month", date.month().try_into().unwrap()),
-                    ("day", date.day().try_into().unwrap()),
+                    ("year", date1.year()),
+                    ("month", date1.month().try_into().unwrap()),
+                    ("day", date1.day().try_into().unwrap()),
                 ]),
-            ),
-            SingleDateRange::DateRange(date1, date2) => state.serialize_field(
+            )?;
+        } else {
+            state.serialize_field(
                 "dateRange",
                 &HashMap::from([
                     (
@@ -116,52 +421,108 @@ impl Serialize for LegQuery {
                         ]),
                     ),
                 ]),
-            ),
-        }?;
+            )?;
+        }
         state.end()
     }
 }
 
+fn serialize_cabin_class<S: serde::Serializer>(cabin: &CabinClass, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(cabin_class_name(*cabin))
+}
+
+fn cabin_class_name(cabin: CabinClass) -> &'static str {
+    match cabin {
+        CabinClass::Economy => "CABIN_CLASS_ECONOMY",
+        CabinClass::PremiumEconomy => "CABIN_CLASS_PREMIUM_ECONOMY",
+        CabinClass::Business => "CABIN_CLASS_BUSINESS",
+        CabinClass::First => "CABIN_CLASS_FIRST",
+    }
+}
+
 impl Query {
-    fn new(market: String, currency: String, legs: Vec<LegQuery>) -> Query {
+    fn new(pos: &PointOfSale, currency: String, cabin_class: CabinClass, legs: Vec<LegQuery>) -> Query {
         Query {
-            market,
-            locale: "en-US".to_string(),
+            market: pos.market.clone(),
+            locale: pos.locale.clone(),
             currency,
+            cabin_class,
             query_legs: legs,
             date_time_grouping_type: "DATE_TIME_GROUPING_TYPE_UNSPECIFIED".to_string(),
         }
     }
 }
 
-fn skyscanner_quote_to_price(val: &serde_json::Value) -> Result<Quote, QueryError> {
-    use serde_json::Value::{Bool, Object, String};
+/// First of `paths` present in `val`
+fn probe<'a>(val: &'a Value, paths: &[&[&str]]) -> Option<&'a Value> {
+    paths
+        .iter()
+        .map(|path| path.iter().fold(val, |v, key| &v[*key]))
+        .find(|v| !v.is_null())
+}
 
-    let Object(value) = val else {
-        return Err(QueryError::ResponseUnexpectedFormatErr("Skyscanner quote has invalid format...".to_string()));
-    };
+fn as_currency(val: &Value) -> Option<CurrencyCode> {
+    val.as_str()?.parse().ok()
+}
 
-    let price_str_val = &value["minPrice"]["amount"];
-    let price = if let String(price_str) = price_str_val {
-        price_str.parse::<f32>().map_err(|_| {
-            QueryError::ResponseUnexpectedFormatErr(
-                "Skyscanner quoted price cannot be converted to a number".to_string(),
-            )
-        })?
-    } else {
-        return Err(QueryError::ResponseUnexpectedFormatErr(
-            "Price in quote is not a string type".to_string(),
-        ));
+/// A number, whether it's sent as one or as a string
+fn as_number(val: &Value) -> Option<f64> {
+    val.as_f64().or_else(|| val.as_str()?.trim().parse().ok())
+}
+
+fn skyscanner_partial_quote(val: &Value) -> PartialQuote {
+    PartialQuote {
+        min_price: probe(val, INDICATIVE_PRICE).and_then(as_number).map(|p| p as f32),
+        direct: probe(val, INDICATIVE_DIRECT).and_then(Value::as_bool),
+        arrives: None,
+        quoted_at: probe(val, INDICATIVE_QUOTED_AT).and_then(skyscanner_timestamp),
+        currency: probe(val, INDICATIVE_CURRENCY).and_then(as_currency),
+        carrier: None,
+    }
+}
+
+/// Every quote in an indicative search's response, `None` if it has no quotes section
+fn skyscanner_indicative_quotes(resp: &Value) -> Option<Vec<PartialQuote>> {
+    let quotes: Vec<&Value> = match probe(resp, INDICATIVE_QUOTES)? {
+        Value::Object(quotes) => quotes.values().collect(),
+        Value::Array(quotes) => quotes.iter().collect(),
+        _ => return None,
     };
+    Some(quotes.into_iter().map(skyscanner_partial_quote).collect())
+}
 
-    let Bool(direct) = &value["isDirect"] else {
-        return Err(QueryError::ResponseUnexpectedFormatErr("Is direct quote is not a bool type".to_string()));
+/// Save a response nothing could be made of to `dir`, if there is one, cut to [MAX_CAPTURE_BYTES]. Nothing more is
+/// saved once `dir` holds [MAX_CAPTURE_FILES] captures.
+fn capture_response(dir: Option<&Path>, raw: &str) {
+    log!("Nothing usable in Skyscanner's response");
+    let Some(dir) = dir else {
+        return;
     };
+    let captured = fs::read_dir(dir).map_or(0, |entries| {
+        entries.filter_map(Result::ok).filter(|e| e.file_name().to_string_lossy().starts_with("skyscanner-")).count()
+    });
+    if captured >= MAX_CAPTURE_FILES {
+        return;
+    }
+    let path = dir.join(format!("skyscanner-{}.json", chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f")));
+    let raw = &raw.as_bytes()[..raw.len().min(MAX_CAPTURE_BYTES)];
+    if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, raw)) {
+        log!("Failed to capture Skyscanner response to {}: {}", path.display(), e);
+    }
+}
 
-    Ok(Quote {
-        min_price: price,
-        direct: *direct,
-    })
+/// SkyScanner's `{year, month, day, hour, minute, second}` timestamps, or ISO 8601 ones in UTC, `None` if it's missing
+/// or malformed
+fn skyscanner_timestamp(val: &Value) -> Option<NaiveDateTime> {
+    if let Some(iso) = val.as_str() {
+        return NaiveDateTime::parse_from_str(iso.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S").ok();
+    }
+    let part = |name: &str| val[name].as_u64().and_then(|p| u32::try_from(p).ok());
+    NaiveDate::from_ymd_opt(part("year")? as i32, part("month")?, part("day")?)?.and_hms_opt(
+        part("hour")?,
+        part("minute")?,
+        part("second")?,
+    )
 }
 
 impl SkyScannerApiQuery {
@@ -174,7 +535,7 @@ impl SkyScannerApiQuery {
 
             match this_resp {
                 Err(QueryError::RateLimitExceeded) => {
-                    println!("Flight API rate limit hit, sleeping");
+                    log!("Flight API rate limit hit, sleeping");
                     std::thread::sleep(time::Duration::from_millis(250));
                 }
                 _ => break this_resp,
@@ -182,28 +543,52 @@ impl SkyScannerApiQuery {
         }
     }
 
+    /// Usable quotes for the legs, none if the provider has no fare. A response that can't be read fails with
+    /// [QueryError::ResponseUnexpectedFormatErr], which isn't cached as no fare.
     async fn get_indicative_prices_simplified(
         &self,
         legs: Vec<LegQuery>,
     ) -> Result<Vec<Quote>, QueryError> {
-        use serde_json::Value::Object;
-
-        let prices_resp = self.get_indicative_price(legs).await?;
-        let quotes = &prices_resp["content"]["results"]["quotes"];
-
-        let Object(quotes_arr) = quotes else {
-            return Err(QueryError::ResponseUnexpectedFormatErr("Skyscanner quotes section has an unexpected format".to_string()));
+        let prices_resp = match self.get_indicative_price(legs).await {
+            Err(QueryError::ResponseConversionErr(_, raw)) => {
+                capture_response(self.capture_dir.as_deref().map(PathBuf::as_path), &raw);
+                return Err(QueryError::ResponseUnexpectedFormatErr(
+                    "Skyscanner indicative response isn't JSON".to_string(),
+                ));
+            }
+            resp => resp?,
         };
+        self.usable_quotes(&prices_resp)
+    }
 
-        quotes_arr.values().map(skyscanner_quote_to_price).collect()
+    /// The quotes in an indicative response with a price. Quotes without one are dropped, but a response with quotes
+    /// and none priced, or no quotes section, is a format the parsing doesn't know.
+    fn usable_quotes(&self, resp: &Value) -> Result<Vec<Quote>, QueryError> {
+        let capture_dir = self.capture_dir.as_deref().map(PathBuf::as_path);
+        let Some(quotes) = skyscanner_indicative_quotes(resp) else {
+            capture_response(capture_dir, &resp.to_string());
+            return Err(QueryError::ResponseUnexpectedFormatErr(
+                "Skyscanner indicative response has no quotes".to_string(),
+            ));
+        };
+        let usable: Vec<Quote> = quotes.iter().filter_map(|q| q.complete(SKYSCANNER_SPREAD)).collect();
+        if usable.len() < quotes.len() {
+            capture_response(capture_dir, &resp.to_string());
+        }
+        if usable.is_empty() && !quotes.is_empty() {
+            return Err(QueryError::ResponseUnexpectedFormatErr(
+                "Skyscanner indicative quotes have no prices".to_string(),
+            ));
+        }
+        usable.into_iter().map(|q| q.into_base(&self.rates)).collect()
     }
 
     pub async fn get_indicative_price(
         &self,
         legs: Vec<LegQuery>,
     ) -> Result<serde_json::Value, QueryError> {
-        // TODO: Query options configurable
-        let jquery = serde_json::to_string(&Query::new("US".to_string(), "USD".to_string(), legs));
+        // Always quoted in the base currency, which is what the cache holds, solutions are converted once solved
+        let jquery = serde_json::to_string(&Query::new(&self.pos, BASE_CURRENCY.to_string(), self.cabin, legs));
         let jquery = match jquery {
             Ok(s) => {
                 format!("{{ \"query\": {} }}", s)
@@ -213,64 +598,329 @@ impl SkyScannerApiQuery {
             }
         };
 
-        // Temporary, put this construction somewhere earlier and pass it through
-        let client = reqwest::Client::new();
-        let req = client
-            .post(SKYSCANNER_IND_PRICES_ENDPOINT)
-            .header("x-api-key", SKYSCANNER_PUB_API_KEY)
-            .body(jquery)
-            .send()
-            .await
-            .map_err(|e| QueryError::ReqwestErr(e))?
-            .error_for_status()
-            .map_err(|e| {
-                if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
-                    QueryError::RateLimitExceeded
-                } else {
-                    let status_code = e
-                        .status()
-                        .expect("Fatal error gracefully handling status error")
-                        .as_u16();
-                    QueryError::BadResponse(status_code)
-                }
-            })?
-            .text()
-            .await
-            .map_err(|e| QueryError::ReqwestErr(e))?;
+        skyscanner_post(&self.client, &self.api_key, SKYSCANNER_IND_PRICES_ENDPOINT, jquery).await
+    }
+}
+
+/// The client providers share, proxied and timed out as `config` says
+pub fn http_client(config: &HttpConfig) -> Result<reqwest::Client, reqwest::Error> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(&config.user_agent)
+        .connect_timeout(time::Duration::from_secs(config.connect_timeout_secs))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host);
+    if config.timeout_secs > 0 {
+        builder = builder.timeout(time::Duration::from_secs(config.timeout_secs));
+    }
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    builder.build()
+}
+
+/// POST `body` to a SkyScanner endpoint, answering with the JSON response
+async fn skyscanner_post(
+    client: &reqwest::Client,
+    api_key: &str,
+    endpoint: &str,
+    body: String,
+) -> Result<serde_json::Value, QueryError> {
+    let req = client
+        .post(endpoint)
+        .header("x-api-key", api_key)
+        .body(body)
+        .send()
+        .await
+        .map_err(QueryError::ReqwestErr)?
+        .error_for_status()
+        .map_err(|e| {
+            if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                QueryError::RateLimitExceeded
+            } else {
+                let status_code = e
+                    .status()
+                    .expect("Fatal error gracefully handling status error")
+                    .as_u16();
+                QueryError::BadResponse(status_code)
+            }
+        })?
+        .text()
+        .await
+        .map_err(QueryError::ReqwestErr)?;
 
-        println!("Response from skyscanner: {}", req);
-        let response_obj = serde_json::from_str(&req)
-            .map_err(|e| QueryError::ResponseConversionErr(e, req.clone()))?;
+    log!("Response from skyscanner: {}", req);
+    let response_obj = serde_json::from_str(&req)
+        .map_err(|e| QueryError::ResponseConversionErr(e, req.clone()))?;
 
-        Ok(response_obj)
+    Ok(response_obj)
+}
+
+/// [skyscanner_post], sleeping through rate limits
+async fn skyscanner_post_retry(
+    client: &reqwest::Client,
+    api_key: &str,
+    endpoint: &str,
+    body: String,
+) -> Result<serde_json::Value, QueryError> {
+    loop {
+        match skyscanner_post(client, api_key, endpoint, body.clone()).await {
+            Err(QueryError::RateLimitExceeded) => {
+                log!("Flight API rate limit hit, sleeping");
+                tokio::time::sleep(time::Duration::from_millis(250)).await;
+            }
+            resp => break resp,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl PriceQuery for SkyScannerApiQuery {
     fn new() -> Self {
-        SkyScannerApiQuery { db: HashMap::new() }
+        SkyScannerApiQuery {
+            db: HashMap::new(),
+            cabin: CabinClass::Economy,
+            pos: PointOfSale::default(),
+            client: reqwest::Client::new(),
+            api_key: SKYSCANNER_PUB_API_KEY.to_string(),
+            capture_dir: None,
+            rates: Arc::default(),
+        }
     }
 
     async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
         let leg_q = vec![LegQuery {
-            start: flight.src.clone(),
-            end: flight.dest.clone(),
-            date: SingleDateRange::FixedDate(flight.date.clone()),
+            start: flight.src.to_string(),
+            end: flight.dest.to_string(),
+            date: DateWindow::day(flight.date),
         }];
 
         let db_val = self.db.get(&flight);
         match db_val {
-            Some(v) => Ok(v.clone()),
+            Some(v) => Ok(*v),
             None => {
                 // Need to query API
-                let quote = self.get_indicative_prices_simplified_retry(leg_q).await?[0];
+                let quotes = self.get_indicative_prices_simplified_retry(leg_q).await?;
+                let quote = *quotes.first().ok_or(QueryError::NoFare)?;
                 self.db.insert(flight.clone(), quote);
 
                 Ok(quote)
             }
         }
     }
+
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        self.db.iter().map(|(f, q)| (f.clone(), *q)).collect()
+    }
+
+    fn restore_quotes(&mut self, quotes: Vec<(Flight, Quote)>) {
+        self.db.extend(quotes);
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        if cabin != self.cabin {
+            self.db.clear();
+            self.cabin = cabin;
+        }
+    }
+
+    fn set_point_of_sale(&mut self, pos: &PointOfSale) {
+        if pos.market != self.pos.market {
+            self.db.clear();
+        }
+        self.pos = pos.clone();
+    }
+
+    fn set_http_client(&mut self, client: &reqwest::Client) {
+        self.client = client.clone();
+    }
+
+    fn set_api_key(&mut self, key: &str) {
+        self.api_key = key.to_string();
+    }
+
+    fn set_response_capture(&mut self, dir: Option<Arc<PathBuf>>) {
+        self.capture_dir = dir;
+    }
+
+    fn set_exchange_rates(&mut self, rates: Arc<ExchangeRates>) {
+        self.rates = rates;
+    }
+}
+
+/// The cheapest fare in a live search's results, the quote lands when its itinerary's first leg does. Without a price
+/// if there are no itineraries, `None` if there's no itineraries section or none of their prices could be read.
+fn skyscanner_live_quote(results: &Value) -> Option<PartialQuote> {
+    let itineraries: Vec<&Value> = match &results["itineraries"] {
+        Value::Object(itineraries) => itineraries.values().collect(),
+        Value::Array(itineraries) => itineraries.iter().collect(),
+        _ => return None,
+    };
+    if itineraries.is_empty() {
+        return Some(PartialQuote::default());
+    }
+
+    let mut cheapest: Option<(f32, &Value, &Value)> = None;
+    for itinerary in itineraries {
+        for option in itinerary["pricingOptions"].as_array().into_iter().flatten() {
+            let Some(price) = skyscanner_live_price(&option["price"]) else {
+                continue;
+            };
+            if cheapest.is_none_or(|(min, _, _)| price < min) {
+                cheapest = Some((price, itinerary, option));
+            }
+        }
+    }
+    let (min_price, itinerary, option) = cheapest?;
+    let leg = &results["legs"][itinerary["legIds"][0].as_str().unwrap_or_default()];
+
+    Some(PartialQuote {
+        min_price: Some(min_price),
+        direct: leg["stopCount"].as_u64().map(|stops| stops == 0),
+        arrives: skyscanner_timestamp(&leg["arrivalDateTime"]),
+        quoted_at: None,
+        currency: as_currency(&option["price"]["currency"]),
+        carrier: leg["marketingCarrierIds"][0]
+            .as_str()
+            .and_then(|id| results["carriers"][id]["iata"].as_str()?.parse().ok()),
+    })
+}
+
+/// SkyScanner's live `{amount, unit}` prices, the amount counts whole, centi, milli or micro units
+fn skyscanner_live_price(price: &Value) -> Option<f32> {
+    let amount = as_number(&price["amount"])?;
+    let per_whole = match price["unit"].as_str()? {
+        "PRICE_UNIT_WHOLE" => 1.0,
+        "PRICE_UNIT_CENTI" => 100.0,
+        "PRICE_UNIT_MILLI" => 1_000.0,
+        "PRICE_UNIT_MICRO" => 1_000_000.0,
+        _ => return None,
+    };
+    Some((amount / per_whole) as f32)
+}
+
+impl SkyScannerLiveQuery {
+    /// Search for the flight's fares, polling the session until it completes or [LIVE_MAX_POLLS] runs out
+    async fn live_search(&self, flight: &Flight) -> Result<Quote, QueryError> {
+        let mut resp = skyscanner_post_retry(&self.client, &self.api_key, SKYSCANNER_LIVE_CREATE_ENDPOINT, self.live_search_body(flight)).await?;
+        let Some(token) = resp["sessionToken"].as_str().map(str::to_string) else {
+            return Err(QueryError::ResponseUnexpectedFormatErr(
+                "Skyscanner live search has no session token".to_string(),
+            ));
+        };
+
+        let mut polls = 0;
+        while resp["status"].as_str() != Some(LIVE_STATUS_COMPLETE) && polls < LIVE_MAX_POLLS {
+            tokio::time::sleep(LIVE_POLL_INTERVAL).await;
+            let endpoint = format!("{}/{}", SKYSCANNER_LIVE_POLL_ENDPOINT, token);
+            resp = skyscanner_post_retry(&self.client, &self.api_key, &endpoint, String::new()).await?;
+            polls += 1;
+        }
+        if resp["status"].as_str() != Some(LIVE_STATUS_COMPLETE) {
+            log!(
+                "Live search for {} to {} on {} still incomplete after {} polls, using what it found",
+                flight.src,
+                flight.dest,
+                flight.date,
+                polls
+            );
+        }
+
+        match skyscanner_live_quote(&resp["content"]["results"]) {
+            Some(quote) => quote.complete(0.0).ok_or(QueryError::NoFare)?.into_base(&self.rates),
+            None => {
+                capture_response(self.capture_dir.as_deref().map(PathBuf::as_path), &resp.to_string());
+                Err(QueryError::ResponseUnexpectedFormatErr(
+                    "Skyscanner live results have no priced itineraries".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn live_search_body(&self, flight: &Flight) -> String {
+        serde_json::json!({
+            "query": {
+                "market": self.pos.market,
+                "locale": self.pos.locale,
+                "currency": BASE_CURRENCY,
+                "queryLegs": [{
+                    "originPlaceId": { "iata": flight.src.to_string() },
+                    "destinationPlaceId": { "iata": flight.dest.to_string() },
+                    "date": { "year": flight.date.year(), "month": flight.date.month(), "day": flight.date.day() },
+                }],
+                "cabinClass": cabin_class_name(self.cabin),
+                "adults": 1,
+            }
+        })
+        .to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceQuery for SkyScannerLiveQuery {
+    fn new() -> Self {
+        SkyScannerLiveQuery {
+            db: HashMap::new(),
+            cabin: CabinClass::Economy,
+            pos: PointOfSale::default(),
+            client: reqwest::Client::new(),
+            api_key: SKYSCANNER_PUB_API_KEY.to_string(),
+            capture_dir: None,
+            rates: Arc::default(),
+        }
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        if let Some(quote) = self.db.get(&flight) {
+            return Ok(*quote);
+        }
+
+        let quote = match self.live_search(&flight).await {
+            Err(QueryError::ResponseConversionErr(_, raw)) => {
+                capture_response(self.capture_dir.as_deref().map(PathBuf::as_path), &raw);
+                return Err(QueryError::ResponseUnexpectedFormatErr("Skyscanner live response isn't JSON".to_string()));
+            }
+            quote => quote?,
+        };
+        self.db.insert(flight, quote);
+        Ok(quote)
+    }
+
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        self.db.iter().map(|(f, q)| (f.clone(), *q)).collect()
+    }
+
+    fn restore_quotes(&mut self, quotes: Vec<(Flight, Quote)>) {
+        self.db.extend(quotes);
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        if cabin != self.cabin {
+            self.db.clear();
+            self.cabin = cabin;
+        }
+    }
+
+    fn set_point_of_sale(&mut self, pos: &PointOfSale) {
+        if pos.market != self.pos.market {
+            self.db.clear();
+        }
+        self.pos = pos.clone();
+    }
+
+    fn set_http_client(&mut self, client: &reqwest::Client) {
+        self.client = client.clone();
+    }
+
+    fn set_api_key(&mut self, key: &str) {
+        self.api_key = key.to_string();
+    }
+
+    fn set_response_capture(&mut self, dir: Option<Arc<PathBuf>>) {
+        self.capture_dir = dir;
+    }
+
+    fn set_exchange_rates(&mut self, rates: Arc<ExchangeRates>) {
+        self.rates = rates;
+    }
 }
 
 #[async_trait::async_trait]
@@ -297,8 +947,8 @@ impl PriceQuery for TestPriceApiQuery {
             let record = row.unwrap();
             for src_idx in 0..4 {
                 let insert = Flight {
-                    src: translate(src_idx).to_string(),
-                    dest: translate(row_count).to_string(),
+                    src: translate(src_idx).parse().unwrap(),
+                    dest: translate(row_count).parse().unwrap(),
                     date: Date::from_ymd_opt(2023, 2, day_count).unwrap(),
                 };
 
@@ -315,17 +965,33 @@ impl PriceQuery for TestPriceApiQuery {
     }
 
     async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
-        let val = self.data.get(&flight).ok_or(QueryError::NonExistentLeg)?;
+        let val = self.data.get(&flight).ok_or(QueryError::NoFare)?;
         Ok(Quote {
             min_price: *val,
             direct: false,
+            arrives: None,
+            quoted_at: None,
+            spread: 0.0,
+            currency: None,
+            carrier: None,
+            bag_fee: None,
+            fallback: false,
         })
     }
 }
 
 #[cfg(test)]
 mod flight_api_tests {
-    use crate::flight_api::{PriceQuery, SkyScannerApiQuery, TestPriceApiQuery};
+    use crate::{
+        config::HttpConfig,
+        flight_api::{
+            capture_response, http_client, skyscanner_indicative_quotes, skyscanner_live_quote,
+            skyscanner_partial_quote, PriceQuery, QueryError, Quote, SkyScannerApiQuery, TestPriceApiQuery,
+            MAX_CAPTURE_BYTES, MAX_CAPTURE_FILES, SKYSCANNER_SPREAD,
+        },
+        currency::ExchangeRates,
+    };
+    use std::collections::HashMap;
     use route_solver_shared::queries::Date;
     use route_solver_shared::queries::Flight;
 
@@ -335,8 +1001,8 @@ mod flight_api_tests {
 
     //     let quote = api
     //         .get_price(Flight {
-    //             src: "JFK".to_string(),
-    //             dest: "YVR".to_string(),
+    //             src: "JFK".parse().unwrap(),
+    //             dest: "YVR".parse().unwrap(),
     //             date: Date::new(10, 8, 2023),
     //         })
     //         .await
@@ -345,13 +1011,159 @@ mod flight_api_tests {
     //     println!("{:?}", quote.min_price);
     // }
 
+    #[test]
+    fn test_quote_std_dev() {
+        let quote = skyscanner_partial_quote(&serde_json::json!({
+            "minPrice": { "amount": "200" },
+            "isDirect": true,
+            "outboundLeg": {
+                "quoteCreationTimestamp": { "year": 2023, "month": 1, "day": 1, "hour": 12, "minute": 0, "second": 0 }
+            }
+        }))
+        .complete(SKYSCANNER_SPREAD)
+        .unwrap();
+        let quoted_at = quote.quoted_at.unwrap();
+        assert_eq!(quoted_at, Date::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap());
+
+        // Fresh it's the provider's spread, ten days on a tenth more of the fare, and no more than half of it ever
+        assert_eq!(quote.std_dev(quoted_at), 20.0);
+        assert!((quote.std_dev(quoted_at + chrono::Duration::days(10)) - 40.0).abs() < 0.01);
+        assert_eq!(quote.std_dev(quoted_at + chrono::Duration::days(365)), 100.0);
+    }
+
+    #[test]
+    fn test_live_quote() {
+        let results = serde_json::json!({
+            "itineraries": {
+                "one-stop": {
+                    "pricingOptions": [{ "price": { "amount": "180000", "unit": "PRICE_UNIT_MILLI" } }],
+                    "legIds": ["yyz-ord-yvr"]
+                },
+                "direct": {
+                    "pricingOptions": [
+                        { "price": { "amount": "260000", "unit": "PRICE_UNIT_MILLI" } },
+                        { "price": { "amount": "245500", "unit": "PRICE_UNIT_MILLI" } }
+                    ],
+                    "legIds": ["yyz-yvr"]
+                }
+            },
+            "legs": {
+                "yyz-ord-yvr": {
+                    "stopCount": 1,
+                    "arrivalDateTime": { "year": 2023, "month": 2, "day": 2, "hour": 1, "minute": 15, "second": 0 }
+                },
+                "yyz-yvr": { "stopCount": 0 }
+            }
+        });
+
+        // The connection is cheaper than either of the direct flight's fares
+        let quote = skyscanner_live_quote(&results).unwrap().complete(0.0).unwrap();
+        assert_eq!(quote.min_price, 180.0);
+        assert!(!quote.direct);
+        assert_eq!(quote.arrives, Date::from_ymd_opt(2023, 2, 2).unwrap().and_hms_opt(1, 15, 0));
+        assert_eq!(quote.quoted_at, None);
+
+        // No itineraries is no fare, no itineraries section at all is a format the parsing doesn't know
+        let nothing = serde_json::json!({ "itineraries": {}, "legs": {} });
+        assert_eq!(skyscanner_live_quote(&nothing).unwrap().min_price, None);
+        assert_eq!(skyscanner_live_quote(&serde_json::json!({ "results": [] })), None);
+    }
+
+    #[test]
+    fn test_indicative_schema_drift() {
+        let resp = serde_json::json!({
+            "content": { "results": { "quotes": [
+                // Renamed fields, a numeric price and an ISO timestamp
+                { "price": { "amount": 150.5 }, "direct": false, "quoteCreationTimestamp": "2023-01-01T12:00:00Z" },
+                // No direct flag, taken to stop
+                { "minPrice": { "amount": "90" }, "somethingNew": { "nested": true } },
+                // No price anywhere, dropped
+                { "isDirect": true }
+            ] } }
+        });
+        let quotes = skyscanner_indicative_quotes(&resp).unwrap();
+        assert_eq!(quotes.len(), 3);
+
+        let usable: Vec<_> = quotes.iter().filter_map(|q| q.complete(SKYSCANNER_SPREAD)).collect();
+        assert_eq!(usable.len(), 2);
+        assert_eq!(usable[0].min_price, 150.5);
+        assert_eq!(usable[0].quoted_at, Date::from_ymd_opt(2023, 1, 1).unwrap().and_hms_opt(12, 0, 0));
+        assert_eq!((usable[1].min_price, usable[1].direct), (90.0, false));
+
+        assert_eq!(skyscanner_indicative_quotes(&serde_json::json!({ "error": "Moved" })), None);
+    }
+
+    #[test]
+    fn test_indicative_unreadable() {
+        let api = SkyScannerApiQuery::new();
+        let usable = |resp| api.usable_quotes(&resp);
+
+        // No quotes is no fare, but no quotes section or none with a price isn't, so it isn't cached as one
+        assert!(usable(serde_json::json!({ "content": { "results": { "quotes": {} } } })).unwrap().is_empty());
+        let unpriced = serde_json::json!({ "quotes": [{ "isDirect": true }] });
+        assert!(matches!(usable(unpriced), Err(QueryError::ResponseUnexpectedFormatErr(_))));
+        let moved = serde_json::json!({ "error": "Moved" });
+        assert!(matches!(usable(moved), Err(QueryError::ResponseUnexpectedFormatErr(_))));
+    }
+
+    #[test]
+    fn test_capture_response_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        capture_response(Some(dir.path()), &"x".repeat(MAX_CAPTURE_BYTES * 2));
+        let saved = std::fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        assert_eq!(saved.metadata().unwrap().len(), MAX_CAPTURE_BYTES as u64);
+
+        for _ in 0..MAX_CAPTURE_FILES + 5 {
+            capture_response(Some(dir.path()), "{}");
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), MAX_CAPTURE_FILES);
+    }
+
+    #[test]
+    fn test_http_client_config() {
+        let config = HttpConfig {
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            ..HttpConfig::default()
+        };
+        assert!(http_client(&config).is_ok());
+
+        let config = HttpConfig {
+            proxy: Some("not a proxy".to_string()),
+            ..HttpConfig::default()
+        };
+        assert!(http_client(&config).is_err());
+    }
+
+    #[test]
+    fn test_quote_into_base() {
+        let rates = ExchangeRates::new(&HashMap::from([("EUR".to_string(), 0.5)])).unwrap();
+        let quote = skyscanner_partial_quote(&serde_json::json!({
+            "minPrice": { "amount": "100", "currency": "eur" },
+            "isDirect": true
+        }))
+        .complete(SKYSCANNER_SPREAD)
+        .unwrap();
+        assert_eq!(quote.currency.map(|c| c.to_string()).as_deref(), Some("EUR"));
+
+        // Converted once, converting again leaves it be
+        let base = quote.into_base(&rates).unwrap();
+        assert_eq!((base.min_price, base.currency), (200.0, None));
+        assert_eq!(base.into_base(&rates).unwrap().min_price, 200.0);
+
+        let unknown = Quote {
+            currency: Some("XYZ".parse().unwrap()),
+            ..quote
+        };
+        assert!(matches!(unknown.into_base(&rates), Err(QueryError::Currency(_))));
+    }
+
     #[tokio::test]
     async fn test_test_api_returns_basic_values() {
         let mut api = TestPriceApiQuery::new();
         let quotes = api
             .get_price(Flight {
-                src: "YYZ".to_string(),
-                dest: "YYC".to_string(),
+                src: "YYZ".parse().unwrap(),
+                dest: "YYC".parse().unwrap(),
                 date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
             })
             .await
@@ -0,0 +1,130 @@
+//! Overland legs, for hops between nearby stops which are cheaper or easier by train or bus than by air.
+//!
+//! Prices come from a [GroundPriceProvider]. [GroundRoutes] is the one the server ships with, a CSV of fares between
+//! pairs of airports the config points at. [GroundLegs] is what the router asks, it only offers a leg overland if the
+//! stops are within a set distance of each other.
+
+use std::{collections::HashMap, fs::File, io::Read, path::Path, sync::Arc};
+
+use route_solver_shared::queries::{AirportCode, Date};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::airports::{great_circle_km, AirportDirectory};
+
+#[derive(Debug, Error)]
+pub enum GroundError {
+    #[error("Could not read ground routes file.")]
+    Io(std::io::Error),
+    #[error("Could not parse ground routes.")]
+    Parse(csv::Error),
+    #[error("Ground fare from {0} to {1} isn't a price.")]
+    InvalidPrice(AirportCode, AirportCode),
+}
+
+pub trait GroundPriceProvider: Send + Sync {
+    /// Fare overland from near `src` to near `dest` on `date`, `None` if there's no way to make the trip
+    fn ground_price(&self, src: AirportCode, dest: AirportCode, date: Date) -> Option<f32>;
+}
+
+#[derive(Deserialize)]
+struct RouteRow {
+    src: AirportCode,
+    dest: AirportCode,
+    price: f32,
+}
+
+/// The same fare every day between pairs of cities
+pub struct GroundRoutes {
+    prices: HashMap<(AirportCode, AirportCode), f32>,
+}
+
+impl GroundRoutes {
+    pub fn from_file(path: &Path) -> Result<GroundRoutes, GroundError> {
+        Self::from_csv(File::open(path).map_err(GroundError::Io)?)
+    }
+
+    /// Read fares from CSV with a `src,dest,price` header. A fare goes both ways unless the other way is listed too.
+    pub fn from_csv(reader: impl Read) -> Result<GroundRoutes, GroundError> {
+        let mut listed = HashMap::new();
+        for row in csv::Reader::from_reader(reader).deserialize::<RouteRow>() {
+            let row = row.map_err(GroundError::Parse)?;
+            if !row.price.is_finite() || row.price < 0.0 {
+                return Err(GroundError::InvalidPrice(row.src, row.dest));
+            }
+            listed.insert((row.src, row.dest), row.price);
+        }
+
+        let mut prices = listed.clone();
+        for ((src, dest), price) in listed {
+            prices.entry((dest, src)).or_insert(price);
+        }
+        Ok(GroundRoutes { prices })
+    }
+}
+
+impl GroundPriceProvider for GroundRoutes {
+    fn ground_price(&self, src: AirportCode, dest: AirportCode, _date: Date) -> Option<f32> {
+        self.prices.get(&(src, dest)).copied()
+    }
+}
+
+/// Overland legs the router may take instead of flying
+pub struct GroundLegs {
+    provider: Arc<dyn GroundPriceProvider>,
+    /// `(lat, lon)` of every airport in the directory
+    positions: HashMap<AirportCode, (f64, f64)>,
+    max_km: f64,
+}
+
+impl GroundLegs {
+    /// Legs priced by `provider` between airports in `directory` at most `max_km` apart
+    pub fn new(provider: impl GroundPriceProvider + 'static, directory: &AirportDirectory, max_km: f64) -> GroundLegs {
+        let positions = directory
+            .all()
+            .into_iter()
+            .filter_map(|airport| Some((airport.iata.parse().ok()?, (airport.lat, airport.lon))))
+            .collect();
+
+        GroundLegs {
+            provider: Arc::new(provider),
+            positions,
+            max_km,
+        }
+    }
+
+    /// Fare overland from `src` to `dest` on `date`, `None` if they're too far apart, either isn't in the directory or
+    /// the provider has no fare
+    pub fn price(&self, src: AirportCode, dest: AirportCode, date: Date) -> Option<f32> {
+        let distance = great_circle_km(*self.positions.get(&src)?, *self.positions.get(&dest)?);
+        if distance > self.max_km {
+            return None;
+        }
+        self.provider.ground_price(src, dest, date)
+    }
+}
+
+#[cfg(test)]
+mod ground_tests {
+    use route_solver_shared::queries::Date;
+
+    use super::{GroundLegs, GroundRoutes};
+    use crate::airports::AirportDirectory;
+
+    #[test]
+    fn test_ground_legs() {
+        let routes = GroundRoutes::from_csv("src,dest,price\nYVR,SEA,45\nSEA,YVR,50\nYYZ,YUL,80\nYYZ,YVR,300\n".as_bytes())
+            .unwrap();
+        let legs = GroundLegs::new(routes, &AirportDirectory::embedded().unwrap(), 600.0);
+        let date = Date::from_ymd_opt(2023, 2, 1).unwrap();
+        let price = |src: &str, dest: &str| legs.price(src.parse().unwrap(), dest.parse().unwrap(), date);
+
+        assert_eq!(price("YVR", "SEA"), Some(45.0));
+        assert_eq!(price("SEA", "YVR"), Some(50.0));
+        // Listed one way only goes both
+        assert_eq!(price("YUL", "YYZ"), Some(80.0));
+        // Across the country is too far to offer, however cheap
+        assert_eq!(price("YYZ", "YVR"), None);
+        assert_eq!(price("YVR", "YYC"), None);
+    }
+}
@@ -0,0 +1,360 @@
+//! Fares past solves have seen, and the seasonal priors fitted to them.
+//!
+//! Every quote a solve fetches from the provider is recorded by cabin, route and travel date. A route's [SeasonalPrior]
+//! is its average fare scaled by how its fares vary with the day of the week and the month, rough but enough to guess
+//! which legs are worth pricing before any of them have been. A route's prior is only refit when it gets new fares.
+//!
+//! History opened from a file is written back to it after each batch of fares is recorded, so it outlives restarts and
+//! is there for the cold caches after one. [PriceHistory::default] keeps it in memory only.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::Datelike;
+use route_solver_shared::queries::{AirportCode, CabinClass, Date, Flight};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{flight_api::Quote, log};
+
+/// Travel dates kept per route, the earliest are forgotten first
+const MAX_DATES_PER_ROUTE: usize = 366;
+
+/// Fares a route needs on record before a prior is fitted to it
+const MIN_SAMPLES: usize = 3;
+
+/// Times the month factors are fitted to fares with the weekday factors taken out, and the other way around
+const FIT_ROUNDS: usize = 5;
+
+type Route = (AirportCode, AirportCode);
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("Price history IO error.")]
+    Io(std::io::Error),
+    #[error("Price history could not be (de)serialized.")]
+    Serde(serde_json::Error),
+}
+
+#[derive(Default)]
+pub struct PriceHistory {
+    fares: Mutex<Fares>,
+    /// File the history is kept in, in memory only if `None`
+    path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Fares {
+    by_route: HashMap<(CabinClass, Route), BTreeMap<Date, f32>>,
+    /// Prior of each route with enough fares, fitted when it last got some
+    priors: HashMap<(CabinClass, Route), SeasonalPrior>,
+    /// Priors of each cabin as last handed out, dropped once any of its routes is refit
+    by_cabin: HashMap<CabinClass, Arc<SeasonalPriors>>,
+}
+
+impl Fares {
+    fn refit(&mut self, key: (CabinClass, Route)) {
+        match self.by_route.get(&key).and_then(SeasonalPrior::fit) {
+            Some(prior) => self.priors.insert(key, prior),
+            None => self.priors.remove(&key),
+        };
+        self.by_cabin.remove(&key.0);
+    }
+}
+
+/// A route's fares as kept on disk
+#[derive(Serialize, Deserialize)]
+struct RouteFares {
+    cabin: CabinClass,
+    src: AirportCode,
+    dest: AirportCode,
+    fares: BTreeMap<Date, f32>,
+}
+
+impl PriceHistory {
+    /// History kept in the file at `path`, starting from what's there if it exists
+    pub fn open(path: impl AsRef<Path>) -> Result<PriceHistory, HistoryError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(HistoryError::Io)?;
+        }
+
+        let mut fares = Fares::default();
+        let routes: Vec<RouteFares> = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(HistoryError::Serde)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(HistoryError::Io(e)),
+        };
+        for route in routes {
+            let key = (route.cabin, (route.src, route.dest));
+            fares.by_route.insert(key, route.fares);
+            fares.refit(key);
+        }
+
+        Ok(PriceHistory {
+            fares: Mutex::new(fares),
+            path: Some(path),
+        })
+    }
+
+    /// Record `quotes` for flights in `cabin`, replacing any fare already on record for the same flight
+    pub fn record(&self, cabin: CabinClass, quotes: &[(Flight, Quote)]) {
+        if quotes.is_empty() {
+            return;
+        }
+
+        let mut fares = self.fares.lock().unwrap();
+        let mut touched = Vec::new();
+        for (flight, quote) in quotes {
+            let key = (cabin, (flight.src, flight.dest));
+            let dates = fares.by_route.entry(key).or_default();
+            dates.insert(flight.date, quote.min_price);
+            if dates.len() > MAX_DATES_PER_ROUTE {
+                dates.pop_first();
+            }
+            if !touched.contains(&key) {
+                touched.push(key);
+            }
+        }
+        for key in touched {
+            fares.refit(key);
+        }
+
+        if let Some(path) = &self.path {
+            // Written under the lock so saves of two batches can't land out of order
+            if let Err(e) = Self::save(path, &fares) {
+                log!("Could not save price history to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Priors for every route in `cabin` with enough fares on record
+    pub fn priors(&self, cabin: CabinClass) -> Arc<SeasonalPriors> {
+        let mut fares = self.fares.lock().unwrap();
+        if let Some(priors) = fares.by_cabin.get(&cabin) {
+            return Arc::clone(priors);
+        }
+
+        let routes = fares
+            .priors
+            .iter()
+            .filter(|((prior_cabin, _), _)| *prior_cabin == cabin)
+            .map(|((_, route), prior)| (*route, prior.clone()))
+            .collect();
+        let priors = Arc::new(SeasonalPriors { routes });
+        fares.by_cabin.insert(cabin, Arc::clone(&priors));
+        priors
+    }
+
+    /// Write `fares` to a temporary file then rename it over `path`, so a crash mid-write keeps the last history whole
+    fn save(path: &Path, fares: &Fares) -> Result<(), HistoryError> {
+        let routes: Vec<RouteFares> = fares
+            .by_route
+            .iter()
+            .map(|((cabin, (src, dest)), fares)| RouteFares {
+                cabin: *cabin,
+                src: *src,
+                dest: *dest,
+                fares: fares.clone(),
+            })
+            .collect();
+        let contents = serde_json::to_vec(&routes).map_err(HistoryError::Serde)?;
+        let tmp_path = path.with_extension("json.tmp");
+
+        fs::write(&tmp_path, contents).map_err(HistoryError::Io)?;
+        fs::rename(&tmp_path, path).map_err(HistoryError::Io)
+    }
+}
+
+/// The fare a route usually costs, scaled by day of the week and month
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeasonalPrior {
+    mean: f32,
+    /// Multiple of `mean` flights on each day of the week cost, Monday first
+    weekday: [f32; 7],
+    /// Multiple of `mean` flights in each month cost, January first
+    month: [f32; 12],
+}
+
+impl SeasonalPrior {
+    /// Prior for a route's fares by travel date, `None` with fewer than [MIN_SAMPLES] of them. Days and months with no
+    /// fares on record cost the average.
+    ///
+    /// Each set of factors is fitted to the fares with the other taken out, so a dear month whose fares on record
+    /// happen to be mostly weekends doesn't also make weekends look dear, and the other way around.
+    pub fn fit(fares: &BTreeMap<Date, f32>) -> Option<SeasonalPrior> {
+        if fares.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mean = fares.values().sum::<f32>() / fares.len() as f32;
+        if mean <= 0.0 {
+            return None;
+        }
+
+        let mut weekday = [1.0; 7];
+        let mut month = [1.0; 12];
+        for _ in 0..FIT_ROUNDS {
+            month = factors(fares, mean, |date| date.month0() as usize, |date| weekday[weekday_idx(date)]);
+            weekday = factors(fares, mean, weekday_idx, |date| month[date.month0() as usize]);
+        }
+
+        Some(SeasonalPrior { mean, weekday, month })
+    }
+
+    pub fn expected_price(&self, date: Date) -> f32 {
+        self.mean * self.weekday[weekday_idx(date)] * self.month[date.month0() as usize]
+    }
+
+    /// Fare on the cheapest day of the week in the cheapest month
+    pub fn cheapest(&self) -> f32 {
+        let min = |factors: &[f32]| factors.iter().copied().fold(f32::INFINITY, f32::min);
+        self.mean * min(&self.weekday) * min(&self.month)
+    }
+}
+
+fn weekday_idx(date: Date) -> usize {
+    date.weekday().num_days_from_monday() as usize
+}
+
+/// Multiple of `mean` the fares in each of `N` buckets cost on average, once what `others` makes of each fare's date is
+/// taken out. Buckets without fares cost `mean`.
+fn factors<const N: usize>(
+    fares: &BTreeMap<Date, f32>,
+    mean: f32,
+    bucket: impl Fn(Date) -> usize,
+    others: impl Fn(Date) -> f32,
+) -> [f32; N] {
+    let mut sums = [(0.0, 0); N];
+    for (date, price) in fares {
+        let expected = mean * others(*date);
+        if expected <= 0.0 {
+            continue;
+        }
+        let (total, count) = &mut sums[bucket(*date)];
+        *total += price / expected;
+        *count += 1;
+    }
+    sums.map(|(total, count)| if count == 0 { 1.0 } else { total / count as f32 })
+}
+
+/// Priors for the routes of one cabin, see [PriceHistory::priors]
+#[derive(Debug, Default)]
+pub struct SeasonalPriors {
+    routes: HashMap<Route, SeasonalPrior>,
+}
+
+impl SeasonalPriors {
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// What a flight from `src` to `dest` on `date` is expected to cost, `None` without a prior for the route
+    pub fn expected_price(&self, src: AirportCode, dest: AirportCode, date: Date) -> Option<f32> {
+        Some(self.routes.get(&(src, dest))?.expected_price(date))
+    }
+
+    /// See [SeasonalPrior::cheapest]
+    pub fn cheapest(&self, src: AirportCode, dest: AirportCode) -> Option<f32> {
+        Some(self.routes.get(&(src, dest))?.cheapest())
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use std::sync::Arc;
+
+    use chrono::Datelike;
+    use route_solver_shared::queries::{CabinClass, Date, Flight};
+
+    use super::PriceHistory;
+    use crate::flight_api::Quote;
+
+    /// Fares from YYZ to YVR on the days of `month` 2023 in `days`, at what `price` says for each
+    fn quotes(month: u32, days: impl Iterator<Item = u32>, price: impl Fn(Date) -> f32) -> Vec<(Flight, Quote)> {
+        days.map(|day| {
+            let date = Date::from_ymd_opt(2023, month, day).unwrap();
+            let flight = Flight {
+                src: "YYZ".parse().unwrap(),
+                dest: "YVR".parse().unwrap(),
+                date,
+            };
+            let quote = Quote {
+                min_price: price(date),
+                direct: true,
+                arrives: None,
+                quoted_at: None,
+                spread: 0.0,
+                currency: None,
+                carrier: None,
+                bag_fee: None,
+                fallback: false,
+            };
+            (flight, quote)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_seasonal_prior() {
+        // Weekends cost double through February 2023, the 4th was a Saturday
+        let quotes = quotes(2, 1..=28, |date| if matches!(date.day0() % 7, 3 | 4) { 200.0 } else { 100.0 });
+
+        let history = PriceHistory::default();
+        history.record(CabinClass::Economy, &quotes);
+        let priors = history.priors(CabinClass::Economy);
+        let (yyz, yvr) = ("YYZ".parse().unwrap(), "YVR".parse().unwrap());
+        let day = |y, m, d| Date::from_ymd_opt(y, m, d).unwrap();
+
+        // A Saturday next February is expected at the weekend fare, a Wednesday at the weekday one
+        assert!((priors.expected_price(yyz, yvr, day(2024, 2, 3)).unwrap() - 200.0).abs() < 0.01);
+        assert!((priors.expected_price(yyz, yvr, day(2024, 2, 7)).unwrap() - 100.0).abs() < 0.01);
+        assert!((priors.cheapest(yyz, yvr).unwrap() - 100.0).abs() < 0.01);
+
+        // Nothing's known the other way, or in other cabins
+        assert_eq!(priors.expected_price(yvr, yyz, day(2024, 2, 3)), None);
+        assert!(history.priors(CabinClass::Business).is_empty());
+
+        // Handed out again until the route gets new fares
+        assert!(Arc::ptr_eq(&priors, &history.priors(CabinClass::Economy)));
+        history.record(CabinClass::Economy, &quotes[..1]);
+        assert!(!Arc::ptr_eq(&priors, &history.priors(CabinClass::Economy)));
+    }
+
+    #[test]
+    fn test_factors_not_counted_twice() {
+        // Every day costs 100 through February, but January's fares on record are all Saturdays at 200. That's January
+        // being dear, not Saturdays.
+        let mut fares = quotes(2, 1..=28, |_| 100.0);
+        fares.extend(quotes(1, [7, 14, 21, 28].into_iter(), |_| 200.0));
+        let history = PriceHistory::default();
+        history.record(CabinClass::Economy, &fares);
+        let priors = history.priors(CabinClass::Economy);
+        let (yyz, yvr) = ("YYZ".parse().unwrap(), "YVR".parse().unwrap());
+        let expected = |m, d| priors.expected_price(yyz, yvr, Date::from_ymd_opt(2024, m, d).unwrap()).unwrap();
+
+        // A Saturday and a Wednesday of each month
+        assert!((expected(1, 6) - 200.0).abs() < 1.0);
+        assert!((expected(1, 10) - 200.0).abs() < 1.0);
+        assert!((expected(2, 3) - 100.0).abs() < 1.0);
+        assert!((expected(2, 7) - 100.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_history_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history").join("default.json");
+        let history = PriceHistory::open(&path).unwrap();
+        history.record(CabinClass::Economy, &quotes(2, 1..=28, |date| 100.0 + date.day() as f32));
+
+        let reopened = PriceHistory::open(&path).unwrap();
+        let (yyz, yvr) = ("YYZ".parse().unwrap(), "YVR".parse().unwrap());
+        let date = Date::from_ymd_opt(2024, 2, 3).unwrap();
+        let price = |history: &PriceHistory| history.priors(CabinClass::Economy).expected_price(yyz, yvr, date);
+        assert!(price(&history).is_some());
+        assert_eq!(price(&reopened), price(&history));
+    }
+}
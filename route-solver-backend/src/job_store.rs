@@ -0,0 +1,111 @@
+//! On disk persistence for solve jobs.
+//!
+//! Each job is kept as a JSON file named after its ID in the store directory. Writes go to a temporary file which
+//! is then renamed over the old record, so a crash mid-write never leaves a half written job behind.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use route_solver_shared::queries::{Flight, JobId, JobStatus, RouteQuery};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{flight_api::Quote, worker_pool::Priority};
+
+#[derive(Debug, Error)]
+pub enum JobStoreError {
+    #[error("Job store IO error.")]
+    Io(std::io::Error),
+    #[error("Job record could not be (de)serialized.")]
+    Serde(serde_json::Error),
+}
+
+/// Everything needed to rerun a job after a restart
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobRecord {
+    pub id: JobId,
+    pub query: RouteQuery,
+    pub status: JobStatus,
+    /// User the job's API calls are charged to
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Tenant who submitted the job, see [Tenant::name](crate::tenants::Tenant::name). Only they can see it.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// ID of the request which submitted the job, tags the solve's log lines
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Queue the job waits in, kept when it's re-queued after a restart
+    #[serde(default)]
+    pub priority: Priority,
+    /// Quotes fetched by the solve so far, cleared once the job finishes
+    #[serde(default)]
+    pub quotes: Vec<(Flight, Quote)>,
+}
+
+impl JobRecord {
+    pub fn new(id: JobId, query: RouteQuery, user: Option<String>) -> JobRecord {
+        JobRecord {
+            id,
+            query,
+            status: JobStatus::Queued,
+            user,
+            tenant: None,
+            request_id: None,
+            priority: Priority::default(),
+            quotes: Vec::new(),
+        }
+    }
+
+    /// Whether the job still needs to be run to completion
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self.status, JobStatus::Queued | JobStatus::Running { .. })
+    }
+}
+
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    pub fn open(dir: impl AsRef<Path>) -> Result<JobStore, JobStoreError> {
+        fs::create_dir_all(dir.as_ref()).map_err(JobStoreError::Io)?;
+        Ok(JobStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn save(&self, record: &JobRecord) -> Result<(), JobStoreError> {
+        let contents = serde_json::to_vec(record).map_err(JobStoreError::Serde)?;
+        let tmp_path = self.dir.join(format!("{}.json.tmp", record.id));
+
+        fs::write(&tmp_path, contents).map_err(JobStoreError::Io)?;
+        fs::rename(&tmp_path, self.record_path(record.id)).map_err(JobStoreError::Io)
+    }
+
+    pub fn remove(&self, id: JobId) -> Result<(), JobStoreError> {
+        fs::remove_file(self.record_path(id)).map_err(JobStoreError::Io)
+    }
+
+    pub fn load_all(&self) -> Result<Vec<JobRecord>, JobStoreError> {
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(JobStoreError::Io)? {
+            let path = entry.map_err(JobStoreError::Io)?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let contents = fs::read(&path).map_err(JobStoreError::Io)?;
+            records.push(serde_json::from_slice(&contents).map_err(JobStoreError::Serde)?);
+        }
+
+        records.sort_by_key(|r: &JobRecord| r.id);
+        Ok(records)
+    }
+
+    fn record_path(&self, id: JobId) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
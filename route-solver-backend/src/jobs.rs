@@ -0,0 +1,1020 @@
+//! Solve jobs, tracks the status of each submitted route query and dispatches the solve onto the
+//! [WorkerPool](crate::worker_pool::WorkerPool).
+//!
+//! When a [JobStore] is attached every state change is written through to disk, along with periodic snapshots of the
+//! quotes fetched mid-solve. A job is only recorded as finished once its result is saved, so after a restart
+//! [JobManager::resume] re-queues anything still queued or running and every job completes at least once.
+//!
+//! [JobManager::shutdown] stops new submissions and cancels running solves, which flush their quotes and go back to
+//! queued so they pick up where they left off on the next start.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use route_solver_shared::queries::{
+    AdminStats, ApiError, JobId, JobStatus, PriceSource, RouteQuery, RouteSolution, RouterStats, ServerMetrics, ThrottleLevel,
+    TransportMode,
+};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    airports::AirportDirectory,
+    arrivals::ArrivalClock,
+    audit::{AuditEvent, AuditLog},
+    baggage::BagFees,
+    cache::{CachedPriceQuery, PriceCache},
+    config::{Config, QuotaConfig, SimulationConfig},
+    currency::ExchangeRates,
+    estimates::PriceEstimates,
+    flight_api::{self, PointOfSale, PriceQuery, QueryError, SkyScannerApiQuery, SkyScannerLiveQuery},
+    ground::{GroundLegs, GroundRoutes},
+    job_store::{JobRecord, JobStore, JobStoreError},
+    log,
+    providers::{self, Provider, ProviderOf, ProviderRegistry},
+    quota::MeteredPriceQuery,
+    request_id,
+    router::{FlightGraph, Router, RouterError, RouterProblem},
+    snapshot::PriceSnapshot,
+    stats::ServerStats,
+    stays::{NightlyRates, StayCostProvider},
+    tenants::{Tenant, Tenants},
+    throttle::QuotaThrottle,
+    worker_pool::{PoolError, Priority, WorkerPool},
+};
+
+/// Number of API calls between quote snapshots of a running job
+const CHECKPOINT_EVERY: u64 = 10;
+
+/// Number of nodes the search expands between progress updates of a running job
+const PROGRESS_EVERY: usize = 25;
+
+type JobMap = Mutex<HashMap<JobId, JobRecord>>;
+
+/// Settings every provider is created with, whatever it prices with
+#[derive(Clone, Default)]
+struct ProviderSetup {
+    /// See [PriceQuery::set_http_client]
+    client: reqwest::Client,
+    /// See [PriceQuery::set_response_capture]
+    capture_dir: Option<Arc<PathBuf>>,
+    /// See [PriceQuery::set_exchange_rates]
+    rates: Arc<ExchangeRates>,
+    /// See [PriceQuery::set_simulation]
+    simulation: Arc<SimulationConfig>,
+}
+
+impl ProviderSetup {
+    fn apply(&self, api: &mut impl PriceQuery) {
+        api.set_http_client(&self.client);
+        api.set_response_capture(self.capture_dir.clone());
+        api.set_exchange_rates(Arc::clone(&self.rates));
+        api.set_simulation(&self.simulation);
+    }
+}
+
+pub struct JobManager {
+    pool: WorkerPool,
+    jobs: Arc<JobMap>,
+    store: Option<Arc<JobStore>>,
+    cache: PriceCache,
+    /// Whose quotas, credentials and price history each job goes by
+    tenants: Arc<Tenants>,
+    stats: Arc<ServerStats>,
+    /// Where solves and their provider calls are recorded
+    audit: Arc<AuditLog>,
+    /// Converts solutions to the currency their query asked for
+    rates: Arc<ExchangeRates>,
+    /// Routes each solve looks for, see [Router::set_candidates]
+    candidates: usize,
+    /// Where solves dump the graph they explored, see [Router::set_record_graph]
+    graph_dir: Option<Arc<PathBuf>>,
+    /// Which day flights land, see [Router::set_arrival_clock]
+    arrivals: Option<Arc<ArrivalClock>>,
+    /// Prices the nights between flights, see [Router::set_stay_costs]
+    stay_costs: Option<Arc<dyn StayCostProvider>>,
+    /// Estimates checked bag fees the provider doesn't quote, see [Router::set_bag_fees]
+    bag_fees: Arc<BagFees>,
+    /// Legs which may go overland, see [Router::set_ground_legs]
+    ground: Option<Arc<GroundLegs>>,
+    /// Prices days without a fare, see [Router::set_price_estimates]
+    estimates: Option<PriceEstimates>,
+    /// Coarsens solves as their user's quota runs low, see [Router::set_quota_throttle]
+    throttle: Option<QuotaThrottle>,
+    /// Provider the server prices with, see [JobManager::with_price_provider]
+    provider: Arc<dyn Provider>,
+    /// Re-price solved routes with the live search, see [verify_fares]
+    verify_live: bool,
+    /// What every provider a solve or caller gets is set up with
+    setup: ProviderSetup,
+    next_id: AtomicU64,
+    /// Parent of every job's cancellation token, cancelled on shutdown
+    cancel: CancellationToken,
+}
+
+impl JobManager {
+    pub fn new(pool: WorkerPool, store: Option<JobStore>, cache: PriceCache) -> JobManager {
+        JobManager {
+            pool,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            store: store.map(Arc::new),
+            cache,
+            tenants: Arc::new(Tenants::new(QuotaConfig::default())),
+            stats: Arc::new(ServerStats::default()),
+            audit: Arc::new(AuditLog::default()),
+            rates: Arc::new(ExchangeRates::default()),
+            candidates: 1,
+            graph_dir: None,
+            arrivals: None,
+            stay_costs: None,
+            bag_fees: Arc::default(),
+            ground: None,
+            estimates: None,
+            throttle: None,
+            provider: Arc::new(ProviderOf::<SkyScannerApiQuery>::default()),
+            verify_live: false,
+            setup: ProviderSetup::default(),
+            next_id: AtomicU64::new(1),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Manager set up as `config` says, with the cache, job store and data files it points at opened. Panics if any of
+    /// them can't be, it's only called at startup.
+    pub fn from_config(config: &Config, airports: &AirportDirectory, registry: &ProviderRegistry) -> JobManager {
+        let provider = registry.get(&config.provider.source).unwrap_or_else(|e| panic!("{}", e));
+        let pool = WorkerPool::new(&config.workers);
+        let store = config
+            .jobs
+            .state_dir
+            .as_ref()
+            .map(|dir| JobStore::open(dir).expect("Failed to open job state directory"));
+
+        // Simulated fares mustn't pass for real ones in a cache shared with instances pricing with a provider
+        let simulated = config.provider.source == providers::SIMULATED;
+        let cache = PriceCache::from_config(&config.cache)
+            .expect("Failed to open price cache")
+            .with_namespace(simulated.then_some(providers::SIMULATED));
+        if let Some(path) = &config.cache.snapshot_path {
+            let snapshot = PriceSnapshot::read(path).expect("Failed to read price snapshot");
+            cache.import(&snapshot);
+            println!("Imported {} cached fares from {}", snapshot.len(), path.display());
+        }
+
+        let stay_costs = config
+            .stays
+            .rates_path
+            .as_ref()
+            .map(|path| NightlyRates::from_file(path).expect("Failed to load nightly rates"));
+        let ground_legs = config.ground.routes_path.as_ref().map(|path| {
+            let routes = GroundRoutes::from_file(path).expect("Failed to load ground routes");
+            GroundLegs::new(routes, airports, config.ground.max_km)
+        });
+        let estimates = (config.estimates.max_gap_days > 0).then_some(PriceEstimates {
+            max_gap_days: config.estimates.max_gap_days,
+            uncertainty: config.estimates.uncertainty,
+        });
+
+        JobManager::new(pool, store, cache)
+            .with_tenants(Tenants::from_config(config).expect("Invalid tenants"))
+            .with_audit_log(AuditLog::from_config(&config.audit).expect("Failed to open audit log"))
+            .with_candidates(config.jobs.candidates)
+            .with_graph_dir(config.jobs.graph_dir.clone())
+            .with_arrival_clock(ArrivalClock::new(airports))
+            .with_stay_costs(stay_costs)
+            .with_bag_fees(BagFees::from_config(&config.baggage).expect("Invalid bag fees"))
+            .with_ground_legs(ground_legs)
+            .with_price_estimates(estimates)
+            .with_quota_throttle(QuotaThrottle::from_config(&config.throttle))
+            .with_rates(ExchangeRates::new(&config.currency.rates).expect("Invalid exchange rates"))
+            .with_http_client(flight_api::http_client(&config.http).expect("Invalid HTTP client config"))
+            .with_response_capture(config.provider.capture_dir.clone())
+            .with_simulation(config.provider.simulation.clone())
+            .with_price_provider(provider)
+            // Only indicative fares want checking, live ones already are and others needn't be sold anywhere
+            .with_live_verification(
+                config.provider.verify_live
+                    && [providers::INDICATIVE, providers::INDICATIVE_OR_LIVE].contains(&config.provider.source.as_str()),
+            )
+    }
+
+    /// Run each job as the tenant in `tenants` who submitted it, charging its API calls to its user's quota there
+    pub fn with_tenants(mut self, tenants: Tenants) -> JobManager {
+        self.tenants = Arc::new(tenants);
+        self
+    }
+
+    /// Record each solve and the provider calls it makes in `log`
+    pub fn with_audit_log(mut self, log: AuditLog) -> JobManager {
+        self.audit = Arc::new(log);
+        self
+    }
+
+    /// Have solves find up to `candidates` routes, the runners up are returned as alternatives to the best
+    pub fn with_candidates(mut self, candidates: usize) -> JobManager {
+        self.candidates = candidates;
+        self
+    }
+
+    /// Write the graph each solve explores to `dir` as `job-<id>.dot` and `job-<id>.json`, nothing is written if `None`
+    pub fn with_graph_dir(mut self, dir: Option<PathBuf>) -> JobManager {
+        self.graph_dir = dir.map(Arc::new);
+        self
+    }
+
+    /// Count stays from the day flights land by `clock`, otherwise flights land the day they leave
+    pub fn with_arrival_clock(mut self, clock: ArrivalClock) -> JobManager {
+        self.arrivals = Some(Arc::new(clock));
+        self
+    }
+
+    /// Price routes on their flights and what `costs` charges for the nights between them, flights alone if `None`
+    pub fn with_stay_costs(mut self, costs: Option<impl StayCostProvider + 'static>) -> JobManager {
+        self.stay_costs = costs.map(|c| Arc::new(c) as Arc<dyn StayCostProvider>);
+        self
+    }
+
+    /// Estimate the checked bag fees of queries asking for a bag with `fees`, where the provider doesn't quote them
+    pub fn with_bag_fees(mut self, fees: BagFees) -> JobManager {
+        self.bag_fees = Arc::new(fees);
+        self
+    }
+
+    /// Let solves go overland where `legs` has a fare, every leg is flown if `None`
+    pub fn with_ground_legs(mut self, legs: Option<GroundLegs>) -> JobManager {
+        self.ground = legs.map(Arc::new);
+        self
+    }
+
+    /// Estimate fares for days the provider has none for as `estimates` says, those days are ruled out if `None`
+    pub fn with_price_estimates(mut self, estimates: Option<PriceEstimates>) -> JobManager {
+        self.estimates = estimates;
+        self
+    }
+
+    /// Search coarser as the quota of the user a solve is for runs low, as `throttle` says
+    pub fn with_quota_throttle(mut self, throttle: QuotaThrottle) -> JobManager {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Record which provider the server prices with, callers go through [JobManager::price_provider] rather than
+    /// picking a [PriceQuery] themselves
+    pub fn with_price_provider(mut self, provider: Arc<dyn Provider>) -> JobManager {
+        self.provider = provider;
+        self
+    }
+
+    /// Re-price the flights of each solved route with SkyScanner's live search before it's returned. Only worth it
+    /// when solves are priced with indicative fares.
+    pub fn with_live_verification(mut self, verify: bool) -> JobManager {
+        self.verify_live = verify;
+        self
+    }
+
+    /// Have every provider make its calls with `client`, so they share its connections and proxy settings
+    pub fn with_http_client(mut self, client: reqwest::Client) -> JobManager {
+        self.setup.client = client;
+        self
+    }
+
+    /// Have providers save responses they can't make sense of to `dir`, nothing is saved if `None`
+    pub fn with_response_capture(mut self, dir: Option<PathBuf>) -> JobManager {
+        self.setup.capture_dir = dir.map(Arc::new);
+        self
+    }
+
+    /// Have the simulated provider price with the model `simulation` describes
+    pub fn with_simulation(mut self, simulation: SimulationConfig) -> JobManager {
+        self.setup.simulation = Arc::new(simulation);
+        self
+    }
+
+    /// Convert solutions to the currency their query asked for, and fares providers quote in other currencies to the
+    /// base, with `rates`
+    pub fn with_rates(mut self, rates: ExchangeRates) -> JobManager {
+        self.rates = Arc::new(rates);
+        self.setup.rates = Arc::clone(&self.rates);
+        self
+    }
+
+    /// Queue a solve of the given query on behalf of `tenant`'s `user`, priced using `Api`, in the queue for `priority`.
+    ///
+    /// Returns the ID to poll the job with, and a receiver which resolves with the final status once the solve ends.
+    pub fn submit<Api: PriceQuery + 'static>(
+        &self,
+        query: RouteQuery,
+        priority: Priority,
+        tenant: &Arc<Tenant>,
+        user: Option<String>,
+    ) -> Result<(JobId, oneshot::Receiver<JobStatus>), PoolError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.stats.record_search(&query);
+        let solve = AuditEvent::Solve {
+            job: id,
+            query: Box::new(query.clone()),
+        };
+        let audit_user = user.clone();
+        let mut record = JobRecord::new(id, query, user);
+        record.tenant = tenant.name().map(str::to_string);
+        record.request_id = request_id::current();
+        record.priority = priority;
+
+        persist(self.store.as_deref(), &record);
+        self.jobs.lock().unwrap().insert(id, record);
+
+        match self.dispatch::<Api>(id, Arc::clone(tenant)) {
+            Ok(done) => {
+                self.audit.record(tenant.name(), audit_user.as_deref(), solve);
+                Ok((id, done))
+            }
+            Err(e) => {
+                self.jobs.lock().unwrap().remove(&id);
+                if let Some(store) = &self.store {
+                    if let Err(e) = store.remove(id) {
+                        log!("Failed to remove rejected job {}: {}", id, e);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Load jobs from the store, re-queueing any that were still queued or running when the server stopped.
+    ///
+    /// Returns the number of jobs re-queued.
+    pub fn resume<Api: PriceQuery + 'static>(&self) -> Result<usize, JobStoreError> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let records = store.load_all()?;
+        if let Some(max_id) = records.iter().map(|r| r.id).max() {
+            self.next_id.fetch_max(max_id + 1, Ordering::SeqCst);
+        }
+
+        let incomplete: Vec<(JobId, Option<String>)> =
+            records.iter().filter(|r| r.is_incomplete()).map(|r| (r.id, r.tenant.clone())).collect();
+        self.jobs
+            .lock()
+            .unwrap()
+            .extend(records.into_iter().map(|r| (r.id, r)));
+
+        let mut requeued = 0;
+        for (id, tenant) in incomplete {
+            let Some(tenant) = self.tenants.get(tenant.as_deref()) else {
+                log!("Could not re-queue job {}: its tenant {:?} is no longer configured", id, tenant);
+                continue;
+            };
+            match self.dispatch::<Api>(id, Arc::clone(tenant)) {
+                Ok(_) => requeued += 1,
+                // Left on disk as incomplete, it'll be picked up on the next restart
+                Err(e) => log!("Could not re-queue job {}: {}", id, e),
+            }
+        }
+
+        Ok(requeued)
+    }
+
+    /// Stop accepting jobs, cancel running solves and wait up to `timeout` for them to wind down, then write every
+    /// job's state to the store.
+    ///
+    /// Cancelled jobs are left queued with their quotes saved, so [JobManager::resume] picks them back up.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.pool.close();
+        self.cancel.cancel();
+
+        if !self.pool.wait_idle(timeout).await {
+            log!("Timed out waiting for running solves to stop");
+        }
+
+        if let Some(store) = &self.store {
+            let records: Vec<JobRecord> = self.jobs.lock().unwrap().values().cloned().collect();
+            for record in records {
+                persist(Some(store), &record);
+            }
+        }
+    }
+
+    /// Status of a job `tenant` submitted, other tenants' jobs are as good as missing
+    pub fn status(&self, id: JobId, tenant: &Tenant) -> Option<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .filter(|r| tenant.owns(r.tenant.as_deref()))
+            .map(|r| r.status.clone())
+    }
+
+    /// Status of every job the manager knows, oldest first, whichever tenant submitted it
+    pub fn statuses(&self) -> Vec<(JobId, JobStatus)> {
+        let mut statuses: Vec<_> = self.jobs.lock().unwrap().values().map(|r| (r.id, r.status.clone())).collect();
+        statuses.sort_by_key(|(id, _)| *id);
+        statuses
+    }
+
+    /// The route query a job was submitted with, if `tenant` submitted it, see [JobManager::status]
+    pub fn query(&self, id: JobId, tenant: &Tenant) -> Option<RouteQuery> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .filter(|r| tenant.owns(r.tenant.as_deref()))
+            .map(|r| r.query.clone())
+    }
+
+    /// Provider for pricing outside of a solve on behalf of `tenant`'s `user`, with the same cache and metering solves
+    /// get
+    pub fn provider<Api: PriceQuery>(&self, tenant: &Tenant, user: Option<String>) -> CachedPriceQuery<MeteredPriceQuery<Api>> {
+        let mut metered =
+            MeteredPriceQuery::with_meters(Api::new(), Arc::clone(tenant.quota()), Arc::clone(&self.stats), user)
+                .with_audit(Arc::clone(&self.audit), tenant.name().map(str::to_string), None);
+        self.setup.apply(&mut metered);
+        tenant.authenticate(&mut metered);
+        CachedPriceQuery::with_cache(metered, self.cache.clone()).with_tenant(tenant.name())
+    }
+
+    pub fn metrics(&self) -> ServerMetrics {
+        ServerMetrics {
+            jobs: self.pool.metrics(),
+            router: self.stats.router(),
+        }
+    }
+
+    pub fn price_provider(&self) -> Arc<dyn Provider> {
+        Arc::clone(&self.provider)
+    }
+
+    /// See [WorkerPool::wait_for_room]
+    pub async fn wait_for_room(&self, priority: Priority) {
+        self.pool.wait_for_room(priority).await
+    }
+
+    pub fn tenants(&self) -> &Tenants {
+        &self.tenants
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    pub fn rates(&self) -> &ExchangeRates {
+        &self.rates
+    }
+
+    pub fn cache(&self) -> &PriceCache {
+        &self.cache
+    }
+
+    /// Client providers make their calls with, for anything else calling out
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.setup.client
+    }
+
+    pub fn admin_stats(&self) -> AdminStats {
+        self.stats.snapshot(self.pool.metrics(), self.cache.entry_count())
+    }
+
+    fn dispatch<Api: PriceQuery + 'static>(
+        &self,
+        id: JobId,
+        tenant: Arc<Tenant>,
+    ) -> Result<oneshot::Receiver<JobStatus>, PoolError> {
+        let (done_tx, done_rx) = oneshot::channel();
+        let (request_id, priority) = match self.jobs.lock().unwrap().get(&id) {
+            Some(record) => (record.request_id.clone(), record.priority),
+            None => (None, Priority::default()),
+        };
+        let jobs = Arc::clone(&self.jobs);
+        let store = self.store.clone();
+        let cache = self.cache.clone();
+        let stats = Arc::clone(&self.stats);
+        let audit = Arc::clone(&self.audit);
+        let rates = Arc::clone(&self.rates);
+        let cancel = self.cancel.child_token();
+        let candidates = self.candidates;
+        let graph_dir = self.graph_dir.clone();
+        let arrivals = self.arrivals.clone();
+        let stay_costs = self.stay_costs.clone();
+        let bag_fees = Arc::clone(&self.bag_fees);
+        let ground = self.ground.clone();
+        let estimates = self.estimates;
+        let throttle = self.throttle;
+        let verify_live = self.verify_live;
+        let setup = self.setup.clone();
+
+        self.pool.submit(priority, Box::new(move || {
+            Box::pin(request_id::scope(request_id.clone(), async move {
+                // Jobs still queued at shutdown are picked up already cancelled, leave them untouched
+                if cancel.is_cancelled() {
+                    let status = jobs.lock().unwrap().get(&id).map(|r| r.status.clone());
+                    let _ = done_tx.send(status.unwrap_or(JobStatus::Queued));
+                    return;
+                }
+
+                let Some(record) = update(&jobs, store.as_deref(), id, |r| {
+                    r.status = JobStatus::Running { api_calls: 0, nodes_expanded: 0 }
+                }) else {
+                    return;
+                };
+
+                if let Some(mut solution) = cache.get_result(&record.query, tenant.name()) {
+                    // The stats are the original solve's, nothing was solved for this one
+                    solution.stats = None;
+                    stats.record_solve(&RouterStats::default(), true);
+                    let status = JobStatus::Completed { solution };
+                    update(&jobs, store.as_deref(), id, |r| {
+                        r.status = status.clone();
+                        r.quotes.clear();
+                    });
+                    let _ = done_tx.send(status);
+                    return;
+                }
+
+                // Run the solve as its own task so a panic inside the router still gives the job a final status
+                let checkpoint_jobs = Arc::clone(&jobs);
+                let progress_jobs = Arc::clone(&jobs);
+                let checkpoint_store = store.clone();
+                let query = record.query.clone();
+                let mut problem = RouterProblem::from(&query);
+                // The query's prices are in its currency and the router's in the base. An unsupported currency fails the
+                // job once it's solved.
+                if let Some(per_unit) = query.currency.as_deref().and_then(|c| rates.to_base(1.0, c).ok()) {
+                    problem.value_of_day *= per_unit;
+                    for penalty in problem.origin_penalties.values_mut() {
+                        *penalty *= per_unit;
+                    }
+                    problem.limits.max_leg_price = problem.limits.max_leg_price.map(|max| max * per_unit);
+                    for dest in &mut problem.dest_list {
+                        dest.max_leg_price = dest.max_leg_price.map(|max| max * per_unit);
+                    }
+                }
+                let solve_cache = cache.clone();
+                let solve_stats = Arc::clone(&stats);
+                let verify_user = record.user.clone();
+                let solve_setup = setup.clone();
+                let solve_tenant = Arc::clone(&tenant);
+                let solve_audit = Arc::clone(&audit);
+                let priors = tenant.history().priors(query.cabin_class);
+                let solve = tokio::task::spawn_local(request_id::scope(request_id, async move {
+                    // Metered beneath the cache so only calls which reach the provider count against the quota
+                    let metered =
+                        MeteredPriceQuery::with_meters(Api::new(), Arc::clone(solve_tenant.quota()), solve_stats, record.user)
+                            .with_audit(solve_audit, solve_tenant.name().map(str::to_string), Some(id));
+                    let mut api = CachedPriceQuery::with_cache(metered, solve_cache).with_tenant(solve_tenant.name());
+                    api.set_cabin_class(record.query.cabin_class);
+                    api.set_point_of_sale(&PointOfSale::of(&record.query));
+                    solve_setup.apply(&mut api);
+                    solve_tenant.authenticate(&mut api);
+                    api.restore_quotes(record.quotes);
+
+                    let mut router = Router::with_api(api);
+                    router.set_cancel_token(cancel);
+                    router.set_candidates(candidates);
+                    router.set_record_graph(graph_dir.is_some());
+                    if let Some(clock) = arrivals {
+                        router.set_arrival_clock(clock);
+                    }
+                    if let Some(costs) = stay_costs {
+                        router.set_stay_costs(costs);
+                    }
+                    router.set_bag_fees(bag_fees);
+                    if let Some(legs) = ground {
+                        router.set_ground_legs(legs);
+                    }
+                    if let Some(estimates) = estimates {
+                        router.set_price_estimates(estimates);
+                    }
+                    if let Some(throttle) = throttle {
+                        router.set_quota_throttle(throttle);
+                    }
+                    if !priors.is_empty() {
+                        router.set_price_priors(priors);
+                    }
+                    router.set_checkpoint(CHECKPOINT_EVERY, move |api: &CachedPriceQuery<MeteredPriceQuery<Api>>, calls| {
+                        let quotes = api.cached_quotes();
+                        update(&checkpoint_jobs, checkpoint_store.as_deref(), id, |r| {
+                            r.quotes = quotes;
+                            if let JobStatus::Running { api_calls, .. } = &mut r.status {
+                                *api_calls = calls;
+                            }
+                        });
+                    });
+                    // Kept in memory only, the next checkpoint saves it with the quotes
+                    router.set_progress(PROGRESS_EVERY, move |progress| {
+                        update(&progress_jobs, None, id, |r| {
+                            if let JobStatus::Running { api_calls, nodes_expanded } = &mut r.status {
+                                *api_calls = progress.api_calls;
+                                *nodes_expanded = progress.nodes_expanded as u64;
+                            }
+                        });
+                    });
+
+                    let res = router.calc(problem).await;
+                    if let Some(dir) = &graph_dir {
+                        dump_graph(dir, id, router.explored_graph());
+                    }
+                    let router_stats = *router.stats();
+                    if router_stats.cache_hit_rate.is_some() {
+                        log!(
+                            "Job {} quotes: {} from cache, {} from the provider",
+                            id,
+                            router_stats.cache_hits,
+                            router_stats.cache_misses
+                        );
+                    }
+                    let quotes = router.api().cached_quotes();
+                    solve_tenant.history().record(record.query.cabin_class, &quotes);
+                    (res, quotes, router_stats)
+                }));
+
+                let (status, quotes) = match solve.await {
+                    Ok((Ok(result), _, router_stats)) => {
+                        let mut solution: RouteSolution = result.into();
+                        solution.stats = Some(Box::new(router_stats));
+                        solution.passengers = query.passengers.count();
+                        for alternative in &mut solution.alternatives {
+                            alternative.passengers = solution.passengers;
+                        }
+                        if verify_live {
+                            let mut live = MeteredPriceQuery::with_meters(
+                                SkyScannerLiveQuery::new(),
+                                Arc::clone(tenant.quota()),
+                                Arc::clone(&stats),
+                                verify_user,
+                            )
+                            .with_audit(Arc::clone(&audit), tenant.name().map(str::to_string), Some(id));
+                            live.set_cabin_class(query.cabin_class);
+                            live.set_point_of_sale(&PointOfSale::of(&query));
+                            setup.apply(&mut live);
+                            tenant.authenticate(&mut live);
+                            if let Err(e) = verify_fares(&mut live, &mut solution).await {
+                                log!("Job {} keeps its indicative fares, verifying them live failed: {}", id, e);
+                            }
+                        }
+                        let converted = match &query.currency {
+                            Some(currency) => rates.convert(&mut solution, currency),
+                            None => Ok(()),
+                        };
+                        match converted {
+                            Ok(()) => {
+                                stats.record_solve(&router_stats, true);
+                                // A solve with more quota to spare could do better than a throttled one
+                                if router_stats.throttle == ThrottleLevel::Full {
+                                    cache.put_result(&query, tenant.name(), &solution);
+                                }
+                                (JobStatus::Completed { solution }, Vec::new())
+                            }
+                            Err(e) => {
+                                stats.record_solve(&router_stats, false);
+                                (JobStatus::Failed { error: e.into() }, Vec::new())
+                            }
+                        }
+                    }
+                    // Back in the queue with everything fetched so far, to be resumed on the next start
+                    Ok((Err(RouterError::Cancelled), quotes, _)) => (JobStatus::Queued, quotes),
+                    Ok((Err(e), _, router_stats)) => {
+                        stats.record_solve(&router_stats, false);
+                        (JobStatus::Failed { error: e.into() }, Vec::new())
+                    }
+                    Err(_) => (
+                        JobStatus::Failed {
+                            error: ApiError::Internal("Solver crashed unexpectedly".to_string()),
+                        },
+                        Vec::new(),
+                    ),
+                };
+
+                update(&jobs, store.as_deref(), id, |r| {
+                    r.status = status.clone();
+                    r.quotes = quotes;
+                });
+                let _ = done_tx.send(status);
+            }))
+        }))?;
+
+        Ok(done_rx)
+    }
+}
+
+/// Re-price the flights of `solution` with `api`, overland legs keep their fares. The solution is left as it was if
+/// any flight can't be priced, and its alternatives keep the fares they were found with.
+pub async fn verify_fares(api: &mut impl PriceQuery, solution: &mut RouteSolution) -> Result<(), QueryError> {
+    let mut quotes = Vec::new();
+    for (i, leg) in solution.flights.iter().enumerate() {
+        if leg.mode == TransportMode::Flight {
+            quotes.push((i, api.get_price(leg.flight.clone()).await?));
+        }
+    }
+
+    for (i, quote) in quotes {
+        let leg = &mut solution.flights[i];
+        // The bag's fee stays as it was, live fares are headline fares
+        let price = quote.min_price + leg.bag_fee;
+        solution.total_price += price - leg.price;
+        leg.price = price;
+        leg.source = PriceSource::Quoted;
+        leg.std_dev = 0.0;
+        leg.quoted_at = quote.quoted_at;
+    }
+    Ok(())
+}
+
+/// Apply `f` to the job's record and write it through to the store, returns the updated record
+fn update(jobs: &JobMap, store: Option<&JobStore>, id: JobId, f: impl FnOnce(&mut JobRecord)) -> Option<JobRecord> {
+    let record = {
+        let mut jobs = jobs.lock().unwrap();
+        let record = jobs.get_mut(&id)?;
+        f(record);
+        record.clone()
+    };
+
+    persist(store, &record);
+    Some(record)
+}
+
+/// Write a solve's explored graph for debugging, failures are only logged as the solve itself went fine
+fn dump_graph(dir: &Path, id: JobId, graph: &FlightGraph) {
+    let written = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(dir.join(format!("job-{}.dot", id)), graph.to_dot()))
+        .and_then(|_| fs::write(dir.join(format!("job-{}.json", id)), serde_json::to_vec(graph)?));
+    if let Err(e) = written {
+        log!("Failed to write explored graph of job {}: {}", id, e);
+    }
+}
+
+fn persist(store: Option<&JobStore>, record: &JobRecord) {
+    if let Some(store) = store {
+        if let Err(e) = store.save(record) {
+            log!("Failed to persist job {}: {}", record.id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod jobs_tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        time::Duration,
+    };
+
+    use route_solver_shared::queries::{
+        ApiError, CabinClass, Date, DestinationQuery, Flight, FlightPrice, JobStatus, Objective, Passengers, PriceSource, RouteLimits,
+        RouteQuery, RouteSolution, SearchStrategy, DateWindow, TransportMode,
+    };
+
+    use crate::{
+        cache::PriceCache,
+        config::{Config, JobConfig, QuotaConfig, TenantConfig, WorkerConfig},
+        flight_api::{PriceQuery, TestPriceApiQuery},
+        job_store::{JobRecord, JobStore},
+        tenants::Tenants,
+        worker_pool::{Priority, WorkerPool},
+    };
+
+    use super::{verify_fares, JobManager};
+
+    fn dest(iata: &str, inbound: Option<DateWindow>, outbound: Option<DateWindow>) -> DestinationQuery {
+        DestinationQuery {
+            iata: iata.parse().unwrap(),
+            arrival_window: inbound,
+            departure_window: outbound,
+            min_days: None,
+            max_days: None,
+            event: None,
+            max_leg_price: None,
+        }
+    }
+
+    fn test_query() -> RouteQuery {
+        RouteQuery {
+            start_city: dest(
+                "YYZ",
+                None,
+                DateWindow::new(Date::from_ymd_opt(2023, 2, 1).unwrap(), Date::from_ymd_opt(2023, 2, 3).unwrap()),
+            ),
+            end_city: dest(
+                "YYZ",
+                Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 8).unwrap())),
+                None,
+            ),
+            hops: vec![dest(
+                "YVR",
+                DateWindow::new(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap()),
+                DateWindow::new(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()),
+            )],
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            currency: None,
+            market: None,
+            locale: None,
+            passengers: Passengers::default(),
+            cabin_class: CabinClass::Economy,
+            strategy: SearchStrategy::default(),
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        }
+    }
+
+    fn test_pool() -> WorkerPool {
+        WorkerPool::new(&WorkerConfig {
+            concurrency: 2,
+            queue_capacity: 4,
+            ..WorkerConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_job_completes_on_pool() {
+        let graph_dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(test_pool(), None, PriceCache::in_memory())
+            .with_graph_dir(Some(graph_dir.path().to_path_buf()));
+        let tenant = manager.tenants().default_tenant();
+
+        let (id, done) = manager.submit::<TestPriceApiQuery>(test_query(), Priority::Interactive, tenant, None).unwrap();
+        let status = done.await.unwrap();
+
+        let JobStatus::Completed { solution } = status else {
+            panic!("Job did not complete: {:?}", status);
+        };
+        assert_eq!(solution.flights.len(), 2);
+        assert_eq!(solution.flights.last().unwrap().flight.dest, "YYZ");
+        assert!(matches!(manager.status(id, tenant), Some(JobStatus::Completed { .. })));
+
+        let dot = std::fs::read_to_string(graph_dir.path().join(format!("job-{}.dot", id))).unwrap();
+        assert!(dot.contains("best route"));
+        assert!(graph_dir.path().join(format!("job-{}.json", id)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_job_fails_once_quota_used_up() {
+        let config = Config {
+            tenants: HashMap::from([(
+                "acme".to_string(),
+                TenantConfig {
+                    quota: QuotaConfig {
+                        daily_limit: Some(1),
+                        ..QuotaConfig::default()
+                    },
+                    ..TenantConfig::default()
+                },
+            )]),
+            jobs: JobConfig {
+                state_dir: None,
+                ..JobConfig::default()
+            },
+            ..Config::default()
+        };
+        let manager = JobManager::new(test_pool(), None, PriceCache::in_memory())
+            .with_tenants(Tenants::from_config(&config).unwrap());
+        let (acme, default) = (manager.tenants().get(Some("acme")).unwrap(), manager.tenants().default_tenant());
+
+        let (id, done) = manager
+            .submit::<TestPriceApiQuery>(test_query(), Priority::Interactive, acme, Some("user".to_string()))
+            .unwrap();
+
+        assert!(matches!(done.await.unwrap(), JobStatus::Failed { .. }));
+        assert_eq!(acme.quota().remaining("user"), Some(0));
+        // The same user of another tenant has a quota of their own, and can't see the job
+        assert_eq!(default.quota().remaining("user"), None);
+        assert!(manager.status(id, acme).is_some());
+        assert!(manager.status(id, default).is_none() && manager.query(id, default).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_requeues_incomplete_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Simulate a server which died with one job mid-solve and one finished
+        {
+            let store = JobStore::open(dir.path()).unwrap();
+            let mut running = JobRecord::new(3, test_query(), None);
+            running.status = JobStatus::Running { api_calls: 0, nodes_expanded: 0 };
+            store.save(&running).unwrap();
+
+            let mut failed = JobRecord::new(7, test_query(), None);
+            failed.status = JobStatus::Failed {
+                error: ApiError::Infeasible("Itinerary cannot solve".to_string()),
+            };
+            store.save(&failed).unwrap();
+        }
+
+        let manager = JobManager::new(
+            test_pool(),
+            Some(JobStore::open(dir.path()).unwrap()),
+            PriceCache::in_memory(),
+        );
+        let tenant = manager.tenants().default_tenant();
+        assert_eq!(manager.resume::<TestPriceApiQuery>().unwrap(), 1);
+        assert!(matches!(manager.status(7, tenant), Some(JobStatus::Failed { .. })));
+
+        // New jobs must not reuse IDs from before the restart
+        let (id, done) = manager.submit::<TestPriceApiQuery>(test_query(), Priority::Interactive, tenant, None).unwrap();
+        assert_eq!(id, 8);
+        done.await.unwrap();
+
+        // The resumed job's result is written back to disk, which lands just after the in memory status changes
+        let store = JobStore::open(dir.path()).unwrap();
+        let mut resumed = None;
+        for _ in 0..100 {
+            let record = store.load_all().unwrap().into_iter().find(|r| r.id == 3).unwrap();
+            if !record.is_incomplete() {
+                resumed = Some(record);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let resumed = resumed.expect("Resumed job never finished");
+        assert!(matches!(resumed.status, JobStatus::Completed { .. }));
+        assert!(resumed.quotes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_leaves_unfinished_jobs_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(
+            WorkerPool::new(&WorkerConfig {
+                concurrency: 1,
+                queue_capacity: 8,
+                ..WorkerConfig::default()
+            }),
+            Some(JobStore::open(dir.path()).unwrap()),
+            PriceCache::in_memory(),
+        );
+
+        let tenant = manager.tenants().default_tenant();
+        let ids: Vec<_> = (0..4)
+            .map(|_| manager.submit::<TestPriceApiQuery>(test_query(), Priority::Interactive, tenant, None).unwrap().0)
+            .collect();
+        manager.shutdown(Duration::from_secs(5)).await;
+
+        assert!(manager.submit::<TestPriceApiQuery>(test_query(), Priority::Interactive, tenant, None).is_err());
+
+        // Whatever didn't finish before the cancel must be left to resume, never failed
+        let stored = JobStore::open(dir.path()).unwrap().load_all().unwrap();
+        assert_eq!(stored.len(), ids.len());
+        for record in stored {
+            assert!(
+                matches!(record.status, JobStatus::Completed { .. } | JobStatus::Queued),
+                "Unexpected status after shutdown: {:?}",
+                record.status
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_fares() {
+        let leg = |src: &str, dest: &str, day, price, mode| FlightPrice {
+            flight: Flight {
+                src: src.parse().unwrap(),
+                dest: dest.parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+            },
+            price,
+            stay_price: 0.0,
+            mode,
+            source: PriceSource::NearbyDays,
+            std_dev: 25.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 0.0,
+            alternative: None,
+        };
+        let mut solution = RouteSolution {
+            flights: vec![
+                leg("YYZ", "YYC", 1, 250.0, TransportMode::Flight),
+                FlightPrice {
+                    stay_price: 20.0,
+                    ..leg("YYC", "YVR", 3, 50.0, TransportMode::Ground)
+                },
+            ],
+            total_price: 320.0,
+            currency: "USD".to_string(),
+            passengers: 1,
+            alternatives: Vec::new(),
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+
+        // The flight takes the provider's fare and loses its uncertainty, the overland leg and the stay are untouched
+        verify_fares(&mut TestPriceApiQuery::new(), &mut solution).await.unwrap();
+        assert_eq!(solution.flights[0].price, 300.0);
+        assert!(solution.flights[0].source.is_quoted());
+        assert_eq!(solution.flights[0].std_dev, 0.0);
+        assert_eq!(solution.flights[1].price, 50.0);
+        assert_eq!(solution.total_price, 370.0);
+    }
+}
@@ -0,0 +1,44 @@
+//! Core of the pathfinder backend, shared by the web server and the standalone scheduler.
+//!
+//! Everything that solves itineraries, prices flights and serves them lives here, the binaries only wire it up.
+
+pub mod airports;
+pub mod arrivals;
+pub mod audit;
+pub mod baggage;
+pub mod cache;
+pub mod calendar;
+pub mod compression;
+pub mod config;
+pub mod currency;
+pub mod diff;
+pub mod estimates;
+pub mod export;
+pub mod feasibility;
+pub mod flight_api;
+pub mod ground;
+pub mod history;
+pub mod job_store;
+pub mod jobs;
+pub mod locale;
+pub mod prefetch;
+pub mod providers;
+pub mod quota;
+pub mod remote;
+pub mod request_id;
+pub mod router;
+pub mod scheduler;
+pub mod server;
+pub mod simulated;
+pub mod snapshot;
+pub mod static_files;
+pub mod stats;
+pub mod stays;
+pub mod summary;
+pub mod templates;
+pub mod tenants;
+#[cfg(test)]
+pub mod testing;
+pub mod throttle;
+pub mod web_app;
+pub mod worker_pool;
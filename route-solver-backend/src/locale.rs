@@ -0,0 +1,119 @@
+//! Where a request's fares are bought, worked out from its `Accept-Language` header.
+//!
+//! Browsers list the user's languages best first with optional weights, e.g. `fr-CA,fr;q=0.9,en-GB;q=0.8`. The most
+//! preferred language with a region gives the market and locale, languages without one don't say where the user is so
+//! they're passed over. Anything the query sets itself wins over the header.
+
+use route_solver_shared::queries::RouteQuery;
+
+use crate::flight_api::PointOfSale;
+
+/// Currency each market usually pays in, for the markets whose currency has a built in exchange rate
+const MARKET_CURRENCIES: &[(&str, &str)] = &[
+    ("US", "USD"),
+    ("CA", "CAD"),
+    ("GB", "GBP"),
+    ("AU", "AUD"),
+    ("JP", "JPY"),
+    ("MX", "MXN"),
+    ("IN", "INR"),
+    ("AT", "EUR"),
+    ("BE", "EUR"),
+    ("DE", "EUR"),
+    ("ES", "EUR"),
+    ("FI", "EUR"),
+    ("FR", "EUR"),
+    ("GR", "EUR"),
+    ("IE", "EUR"),
+    ("IT", "EUR"),
+    ("NL", "EUR"),
+    ("PT", "EUR"),
+];
+
+/// Whether `code` looks like an ISO 3166 country code, two letters of either case
+pub fn is_market(code: &str) -> bool {
+    code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Whether `tag` looks like a BCP 47 language tag, e.g. `en-GB` or `zh-Hant-TW`
+pub fn is_locale(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag
+            .split('-')
+            .all(|part| (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// The market and locale of the most preferred language in an `Accept-Language` header with a region, `None` if none
+/// of them has one
+pub fn from_accept_language(header: &str) -> Option<PointOfSale> {
+    let mut languages: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut params = entry.split(';').map(str::trim);
+            let tag = params.next().filter(|tag| is_locale(tag))?;
+            let weight = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            Some((tag, weight))
+        })
+        .collect();
+    // Stable, so languages of equal weight keep the order they were listed in
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    languages.into_iter().filter(|(_, weight)| *weight > 0.0).find_map(|(tag, _)| {
+        let mut parts = tag.split('-');
+        let language = parts.next()?;
+        let region = parts.find(|part| is_market(part))?.to_uppercase();
+        Some(PointOfSale {
+            locale: format!("{}-{}", language.to_lowercase(), region),
+            market: region,
+        })
+    })
+}
+
+/// Currency fares are usually shown in for `market`, `None` for markets without one built in
+pub fn market_currency(market: &str) -> Option<&'static str> {
+    MARKET_CURRENCIES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(market))
+        .map(|(_, currency)| *currency)
+}
+
+/// Fill in whatever of the query's market, locale and currency it leaves unset from the request's `Accept-Language`.
+/// The currency is the market's if `supported` can convert to it, otherwise it's left to the base.
+pub fn localize(query: &mut RouteQuery, accept_language: Option<&str>, supported: impl Fn(&str) -> bool) {
+    if let Some(detected) = accept_language.and_then(from_accept_language) {
+        query.market.get_or_insert(detected.market);
+        query.locale.get_or_insert(detected.locale);
+    }
+    if query.currency.is_none() {
+        query.currency = query
+            .market
+            .as_deref()
+            .and_then(market_currency)
+            .filter(|currency| supported(currency))
+            .map(str::to_string);
+    }
+}
+
+#[cfg(test)]
+mod locale_tests {
+    use super::{from_accept_language, is_locale, market_currency};
+
+    #[test]
+    fn test_from_accept_language() {
+        let pos = from_accept_language("fr;q=0.9, en-gb;q=0.8, de-DE;q=0").unwrap();
+        assert_eq!((pos.market.as_str(), pos.locale.as_str()), ("GB", "en-GB"));
+
+        // Weights beat the order languages are listed in, scripts are skipped on the way to the region
+        let pos = from_accept_language("en-US;q=0.5,zh-Hant-TW").unwrap();
+        assert_eq!((pos.market.as_str(), pos.locale.as_str()), ("TW", "zh-TW"));
+
+        assert_eq!(from_accept_language("en, *;q=0.5"), None);
+        assert_eq!(from_accept_language(""), None);
+        assert!(!is_locale("en_GB"));
+
+        assert_eq!(market_currency("gb"), Some("GBP"));
+        assert_eq!(market_currency("TW"), None);
+    }
+}
@@ -2,20 +2,72 @@
 //!
 //! Uses actix to serve the backend functionality, importantly taking in user travel itineraries and optimizing.
 
-pub mod flight_api;
-pub mod router;
-pub mod web_app;
+use std::io::Write;
 
-use actix_web::{App, HttpServer};
-use actix_files as fs;
+use route_solver::{airports, config, export, jobs, providers::ProviderRegistry, remote, server};
+
+/// `route-solver export ...`, see [export::ExportCommand]
+fn run_export(config: &config::Config, args: impl Iterator<Item = String>) -> std::io::Result<()> {
+    let command = export::ExportCommand::parse(args).map_err(std::io::Error::other)?;
+    let rows = command.run(config).map_err(std::io::Error::other)?;
+    println!("Wrote {} rows to {}", rows, command.path.display());
+    Ok(())
+}
+
+/// `route-solver prefetch ...` with the configured provider, see [route_solver::prefetch::Prefetch]
+async fn run_prefetch(
+    config: &config::Config,
+    registry: &ProviderRegistry,
+    args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+    let airports = airports::AirportDirectory::embedded().expect("Bundled airport data is malformed");
+    let jobs = jobs::JobManager::from_config(config, &airports, registry);
+    let state_dir = config.jobs.state_dir.as_deref();
+    jobs.price_provider().prefetch(&jobs, state_dir, args.collect()).await
+}
+
+/// `route-solver solve ...`, see [remote::SolveCommand]. Progress goes to stderr, over one line unless it's JSON, and the
+/// route to stdout. JSON progress lines are [remote::SolveProgress]es.
+async fn run_solve(args: impl Iterator<Item = String>) -> std::io::Result<()> {
+    let command = remote::SolveCommand::parse(args).map_err(std::io::Error::other)?;
+    let json = command.format == remote::OutputFormat::Json;
+    let solved = command
+        .run(|status| {
+            if json {
+                let progress = remote::SolveProgress::new(status);
+                eprintln!("{}", serde_json::to_string(&progress).expect("Job statuses always serialize"));
+            } else {
+                eprint!("\r\x1b[2K{}", remote::describe_progress(status));
+                let _ = std::io::stderr().flush();
+            }
+        })
+        .await;
+    if !json {
+        eprintln!();
+    }
+    let (job, solution) = solved.map_err(std::io::Error::other)?;
+
+    match command.format {
+        remote::OutputFormat::Table => print!("{}", remote::solution_table(&solution)),
+        remote::OutputFormat::Json => {
+            let summary = command.summary(job).await.map_err(std::io::Error::other)?;
+            println!("{}", serde_json::to_string(&summary).expect("Trip summaries always serialize"));
+        }
+    }
+    Ok(())
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| App::new()
-        .service(web_app::compute)
-        .service(web_app::echo)
-        .service(fs::Files::new("/", "../route-solver-frontend/dist")))
-        .bind(("127.0.0.1", 8080))?
-        .run()
-        .await
+    let config = config::Config::load().expect("Failed to load server config");
+    let registry = ProviderRegistry::builtin();
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("export") => return run_export(&config, args),
+        Some("solve") => return run_solve(args).await,
+        Some("prefetch") => return run_prefetch(&config, &registry, args).await,
+        _ => {}
+    }
+
+    server::serve(config, registry).await
 }
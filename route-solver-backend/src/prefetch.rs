@@ -0,0 +1,307 @@
+//! Warming the price cache ahead of solves, `route-solver prefetch SRC DEST FROM TO [--cabin CABIN]`.
+//!
+//! A prefetch prices every flight on one route over a range of dates through the cache, so the solves after it find
+//! their fares there. It goes through the dates [CHUNK_DAYS] at a time and, given a directory to keep it in, records
+//! each chunk once every date in it has been priced or found to have no fare. A run that dies midway, whether the
+//! quota or the provider's rate limit ran out or the process went down, leaves the record behind and running the same
+//! prefetch again only fetches the chunks it doesn't list. Dates that failed for any other reason keep their chunk off
+//! the record, so they're tried again too. The record goes once a run gets through every chunk.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use route_solver_shared::queries::{AirportCode, CabinClass, Date, Flight};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    flight_api::{PriceQuery, QueryError, Quote},
+    jobs::JobManager,
+    log,
+};
+
+/// Days of a prefetch recorded as done together
+pub const CHUNK_DAYS: usize = 7;
+
+/// User prefetches run from the command line are charged to, of the default [Tenant](crate::tenants::Tenant)
+pub const PREFETCH_USER: &str = "prefetch";
+
+#[derive(Debug, Error)]
+pub enum PrefetchError {
+    #[error("{0}")]
+    Usage(String),
+}
+
+/// Every flight from `src` to `dest` leaving between `from` and `to`, inclusive, in `cabin`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prefetch {
+    pub src: AirportCode,
+    pub dest: AirportCode,
+    pub from: Date,
+    pub to: Date,
+    pub cabin: CabinClass,
+}
+
+/// What a [Prefetch::run] came to
+#[derive(Debug, Default)]
+pub struct PrefetchRun {
+    /// Fares fetched, or found in the cache, this run
+    pub quotes: Vec<(Flight, Quote)>,
+    /// Whether every chunk is done, `false` if there's some left for the next run to pick up
+    pub complete: bool,
+}
+
+/// Chunks of a prefetch finished so far, see the module docs
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct PrefetchProgress {
+    /// First day of each finished chunk
+    done: BTreeSet<Date>,
+}
+
+impl PrefetchProgress {
+    /// Progress saved at `path`, none if there's nothing there or it can't be read
+    fn load(path: &Path) -> PrefetchProgress {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return PrefetchProgress::default(),
+            Err(e) => {
+                log!("Could not read prefetch progress {}, starting over: {}", path.display(), e);
+                return PrefetchProgress::default();
+            }
+        };
+        serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            log!("Prefetch progress {} is unreadable, starting over: {}", path.display(), e);
+            PrefetchProgress::default()
+        })
+    }
+
+    /// Written to a temporary file then renamed over `path`, so a crash mid-write keeps the last progress whole
+    fn save(&self, path: &Path) {
+        let tmp_path = path.with_extension("json.tmp");
+        let saved = fs::write(&tmp_path, serde_json::to_vec(self).expect("Progress always serializes"))
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if let Err(e) = saved {
+            log!("Could not save prefetch progress {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl Prefetch {
+    const USAGE: &'static str = "Usage: route-solver prefetch SRC DEST FROM TO [--cabin CABIN]";
+
+    /// The prefetch `route-solver prefetch` was asked for, from the arguments after `prefetch`
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Prefetch, PrefetchError> {
+        let usage = |reason: String| PrefetchError::Usage(format!("{}\n{}", reason, Self::USAGE));
+        let mut next = |what: &str| args.next().ok_or_else(|| usage(format!("Missing {}", what)));
+        let (src, dest, from, to) = (next("SRC")?, next("DEST")?, next("FROM")?, next("TO")?);
+        let airport = |code: &str| code.parse().map_err(|_| usage(format!("Invalid airport {}", code)));
+        let date = |date: &str| date.parse().map_err(|_| usage(format!("Invalid date {}, expected YYYY-MM-DD", date)));
+
+        let mut prefetch = Prefetch {
+            src: airport(&src)?,
+            dest: airport(&dest)?,
+            from: date(&from)?,
+            to: date(&to)?,
+            cabin: CabinClass::Economy,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--cabin" => {
+                    let name = args.next().ok_or_else(|| usage("Missing value for --cabin".to_string()))?;
+                    prefetch.cabin = CabinClass::ALL
+                        .into_iter()
+                        .find(|cabin| serde_json::to_value(cabin).is_ok_and(|value| value == name.as_str()))
+                        .ok_or_else(|| usage(format!("Unknown cabin {}", name)))?;
+                }
+                other => return Err(usage(format!("Unknown argument {}", other))),
+            }
+        }
+        if prefetch.to < prefetch.from {
+            return Err(usage("TO is before FROM".to_string()));
+        }
+        Ok(prefetch)
+    }
+
+    /// File the prefetch's progress is kept in under `dir`. Named after the route, dates and cabin, so changing any of
+    /// them starts over.
+    pub fn progress_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}-{}-{}-{}-{:?}.json", self.src, self.dest, self.from, self.to, self.cabin))
+    }
+
+    /// Price the prefetch's flights with `api`, skipping the chunks progress kept in `progress_dir` says are done and
+    /// dates before `today`, nothing flies in the past. Progress isn't kept without a `progress_dir`.
+    pub async fn run(&self, api: &mut impl PriceQuery, progress_dir: Option<&Path>, today: Date) -> PrefetchRun {
+        let route = format!("{}-{}", self.src, self.dest);
+        let path = progress_dir.map(|dir| self.progress_path(dir));
+        let mut progress = path.as_deref().map(PrefetchProgress::load).unwrap_or_default();
+        api.set_cabin_class(self.cabin);
+
+        let dates: Vec<Date> = self.from.iter_days().take_while(|date| *date <= self.to).collect();
+        let mut run = PrefetchRun::default();
+        for chunk in dates.chunks(CHUNK_DAYS) {
+            if progress.done.contains(&chunk[0]) {
+                continue;
+            }
+            let mut finished = true;
+            for date in chunk.iter().copied().filter(|date| *date >= today) {
+                let flight = Flight {
+                    src: self.src,
+                    dest: self.dest,
+                    date,
+                };
+                match api.get_price(flight.clone()).await {
+                    Ok(quote) => run.quotes.push((flight, quote)),
+                    Err(QueryError::NoFare) => {}
+                    // Nothing more will be priced this run, the rest is left for the next
+                    Err(e @ (QueryError::QuotaExceeded | QueryError::RateLimitExceeded)) => {
+                        log!("Prefetch of {} stopped on {}: {}", route, date, e);
+                        return run;
+                    }
+                    Err(e) => {
+                        log!("Prefetch of {} couldn't price {}: {}", route, date, e);
+                        finished = false;
+                    }
+                }
+            }
+            if !finished {
+                continue;
+            }
+            progress.done.insert(chunk[0]);
+            if let Some(path) = &path {
+                progress.save(path);
+            }
+        }
+
+        run.complete = dates.chunks(CHUNK_DAYS).all(|chunk| progress.done.contains(&chunk[0]));
+        if let Some(path) = path.filter(|_| run.complete) {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    log!("Could not clear prefetch progress {}: {}", path.display(), e)
+                }
+                _ => {}
+            }
+        }
+        run
+    }
+}
+
+/// `route-solver prefetch ...`, see [Prefetch::parse]. Fares are fetched with `Api` through `jobs`' cache and charged
+/// to [PREFETCH_USER]. Progress is kept in a `prefetch` directory under `state_dir`, if there is one.
+pub async fn run_command<Api: PriceQuery>(
+    jobs: &JobManager,
+    state_dir: Option<&Path>,
+    args: impl Iterator<Item = String>,
+) -> std::io::Result<()> {
+    let prefetch = Prefetch::parse(args).map_err(std::io::Error::other)?;
+    let progress_dir = state_dir.map(|dir| dir.join("prefetch"));
+    if let Some(dir) = &progress_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tenant = jobs.tenants().default_tenant();
+    let mut api = jobs.provider::<Api>(tenant, Some(PREFETCH_USER.to_string()));
+    let run = prefetch.run(&mut api, progress_dir.as_deref(), Utc::now().date_naive()).await;
+    println!("Prefetched {} fares from {} to {}", run.quotes.len(), prefetch.src, prefetch.dest);
+    if !run.complete {
+        println!("Some dates are left, run the same prefetch again to fetch them");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod prefetch_tests {
+    use std::collections::HashSet;
+
+    use route_solver_shared::queries::{CabinClass, Date, Flight};
+
+    use super::{Prefetch, CHUNK_DAYS};
+    use crate::flight_api::{PriceQuery, QueryError, Quote, TestPriceApiQuery};
+
+    /// Prices every flight at the test data's fare for the 1st but fails on the `failing` dates, and runs out of quota
+    /// after `budget` calls
+    struct FlakyApi {
+        data: TestPriceApiQuery,
+        budget: Option<u64>,
+        failing: HashSet<Date>,
+        asked: Vec<Date>,
+    }
+
+    #[async_trait::async_trait]
+    impl PriceQuery for FlakyApi {
+        fn new() -> Self {
+            FlakyApi {
+                data: TestPriceApiQuery::new(),
+                budget: None,
+                failing: HashSet::new(),
+                asked: Vec::new(),
+            }
+        }
+
+        async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+            if let Some(budget) = &mut self.budget {
+                *budget = budget.checked_sub(1).ok_or(QueryError::QuotaExceeded)?;
+            }
+            self.asked.push(flight.date);
+            if self.failing.contains(&flight.date) {
+                return Err(QueryError::BadResponse(503));
+            }
+            self.data.get_price(Flight { date: day(1), ..flight }).await
+        }
+    }
+
+    fn day(d: u32) -> Date {
+        Date::from_ymd_opt(2023, 2, d).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        // Three chunks of dates
+        let prefetch = Prefetch {
+            src: "YYZ".parse().unwrap(),
+            dest: "YVR".parse().unwrap(),
+            from: day(1),
+            to: day(3 * CHUNK_DAYS as u32),
+            cabin: CabinClass::Economy,
+        };
+
+        // The 10th fails, then the quota runs out partway through the third chunk. Only the first is done.
+        let mut api = FlakyApi {
+            budget: Some(17),
+            failing: HashSet::from([day(10)]),
+            ..FlakyApi::new()
+        };
+        let run = prefetch.run(&mut api, Some(dir.path()), day(1)).await;
+        assert!(!run.complete);
+        assert_eq!(run.quotes.len(), 16);
+        assert!(prefetch.progress_path(dir.path()).exists());
+
+        // Running it again fetches the second chunk, the 10th included, and the third
+        let mut api = FlakyApi::new();
+        let run = prefetch.run(&mut api, Some(dir.path()), day(1)).await;
+        assert!(run.complete);
+        assert_eq!(api.asked, (8..=21).map(day).collect::<Vec<_>>());
+        assert!(!prefetch.progress_path(dir.path()).exists());
+
+        // After which there's nothing to resume, the next run fetches everything from today
+        let mut api = FlakyApi::new();
+        prefetch.run(&mut api, Some(dir.path()), day(5)).await;
+        assert_eq!(api.asked, (5..=21).map(day).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_prefetch() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter();
+        let prefetch =
+            Prefetch::parse(args(&["YYZ", "YVR", "2023-02-01", "2023-02-21", "--cabin", "business"])).unwrap();
+        assert_eq!((prefetch.from, prefetch.to, prefetch.cabin), (day(1), day(21), CabinClass::Business));
+
+        assert!(Prefetch::parse(args(&["YYZ", "YVR", "2023-02-01"])).is_err());
+        assert!(Prefetch::parse(args(&["YYZ", "YVR", "2023-02-21", "2023-02-01"])).is_err());
+        assert!(Prefetch::parse(args(&["YYZ", "YVR", "2023-02-01", "2023-02-21", "--cabin", "steerage"])).is_err());
+    }
+}
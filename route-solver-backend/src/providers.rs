@@ -0,0 +1,324 @@
+//! Price providers by name, so the one solves use is picked in the config and new ones plug in from outside the crate.
+//!
+//! Everything that prices flights is generic over the [PriceQuery] it prices with. A [ProviderRegistry] maps names to
+//! [Provider]s, which run those generic pieces with their own `PriceQuery`, and `[provider] source` picks one of them.
+//! The backend registers its own providers in [ProviderRegistry::builtin]. A crate adding a data source implements
+//! `PriceQuery` for it and serves the backend with a registry it's been added to:
+//!
+//! ```ignore
+//! let registry = ProviderRegistry::builtin().with_provider::<MyFaresQuery>("my-fares");
+//! route_solver::server::serve(config, registry).await
+//! ```
+//!
+//! A provider can also be registered with another to fall back on for the flights it fails to price, see
+//! [ProviderRegistry::with_fallback].
+
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use route_solver_shared::queries::{CabinClass, CalendarDay, Flight, JobId, JobStatus, RouteQuery};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::{
+    cache::CacheStats,
+    calendar::price_calendar,
+    config::SimulationConfig,
+    currency::ExchangeRates,
+    flight_api::{PointOfSale, PriceQuery, QueryError, Quote, SkyScannerApiQuery, SkyScannerLiveQuery},
+    job_store::JobStoreError,
+    jobs::JobManager,
+    log, prefetch,
+    scheduler::Scheduler,
+    simulated::SimulatedApiQuery,
+    tenants::Tenant,
+    worker_pool::{PoolError, Priority},
+};
+
+/// SkyScanner's indicative prices, estimates from cached searches at one call a fare. The default.
+pub const INDICATIVE: &str = "indicative";
+/// SkyScanner's live search, bookable fares at several calls and a few seconds a fare
+pub const LIVE: &str = "live";
+/// [INDICATIVE], with the [LIVE] search pricing the flights it fails to
+pub const INDICATIVE_OR_LIVE: &str = "indicative-or-live";
+/// Fares made up by the seeded model in [crate::simulated], for load testing and demos without a provider
+pub const SIMULATED: &str = "simulated";
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("No price provider is registered as {name:?}, expected one of {known:?}")]
+    Unknown { name: String, known: Vec<String> },
+}
+
+/// The parts of the backend which price flights, run with one [PriceQuery]
+#[async_trait::async_trait]
+pub trait Provider: Send + Sync {
+    /// See [JobManager::submit]
+    fn submit(
+        &self,
+        jobs: &JobManager,
+        query: RouteQuery,
+        tenant: &Arc<Tenant>,
+        user: Option<String>,
+    ) -> Result<(JobId, oneshot::Receiver<JobStatus>), PoolError>;
+
+    /// See [JobManager::resume]
+    fn resume(&self, jobs: &JobManager) -> Result<usize, JobStoreError>;
+
+    /// Quotes for `around`'s leg on the `days` either side of its date, see [price_calendar]
+    async fn calendar(
+        &self,
+        jobs: &JobManager,
+        tenant: &Tenant,
+        user: String,
+        pos: &PointOfSale,
+        around: Flight,
+        days: u64,
+    ) -> Vec<CalendarDay>;
+
+    /// See [Scheduler::run]
+    async fn run_scheduler(&self, scheduler: &Scheduler);
+
+    /// `route-solver prefetch` with `args`, see [prefetch::run_command]
+    async fn prefetch(&self, jobs: &JobManager, state_dir: Option<&Path>, args: Vec<String>) -> std::io::Result<()>;
+}
+
+/// [Provider] pricing with `Api`
+pub struct ProviderOf<Api>(PhantomData<fn() -> Api>);
+
+impl<Api> Default for ProviderOf<Api> {
+    fn default() -> Self {
+        ProviderOf(PhantomData)
+    }
+}
+
+#[async_trait::async_trait]
+impl<Api: PriceQuery + 'static> Provider for ProviderOf<Api> {
+    fn submit(
+        &self,
+        jobs: &JobManager,
+        query: RouteQuery,
+        tenant: &Arc<Tenant>,
+        user: Option<String>,
+    ) -> Result<(JobId, oneshot::Receiver<JobStatus>), PoolError> {
+        jobs.submit::<Api>(query, Priority::Interactive, tenant, user)
+    }
+
+    fn resume(&self, jobs: &JobManager) -> Result<usize, JobStoreError> {
+        jobs.resume::<Api>()
+    }
+
+    async fn calendar(
+        &self,
+        jobs: &JobManager,
+        tenant: &Tenant,
+        user: String,
+        pos: &PointOfSale,
+        around: Flight,
+        days: u64,
+    ) -> Vec<CalendarDay> {
+        let mut api = jobs.provider::<Api>(tenant, Some(user));
+        api.set_point_of_sale(pos);
+        price_calendar(&mut api, around.src, around.dest, around.date, days).await
+    }
+
+    async fn run_scheduler(&self, scheduler: &Scheduler) {
+        scheduler.run::<Api>().await
+    }
+
+    async fn prefetch(&self, jobs: &JobManager, state_dir: Option<&Path>, args: Vec<String>) -> std::io::Result<()> {
+        prefetch::run_command::<Api>(jobs, state_dir, args.into_iter()).await
+    }
+}
+
+/// Prices with `Api`, and with `Fallback` the flights `Api` fails to answer for, e.g. while it's partly down. Quotes
+/// `Fallback` made are marked as [Quote::fallback]. Having no fare isn't a failure, so isn't asked of `Fallback`.
+pub struct FallbackPriceQuery<Api, Fallback> {
+    primary: Api,
+    fallback: Fallback,
+}
+
+impl<Api: PriceQuery, Fallback: PriceQuery> FallbackPriceQuery<Api, Fallback> {
+    pub fn with_fallback(primary: Api, fallback: Fallback) -> Self {
+        FallbackPriceQuery { primary, fallback }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Api: PriceQuery, Fallback: PriceQuery> PriceQuery for FallbackPriceQuery<Api, Fallback> {
+    fn new() -> Self {
+        Self::with_fallback(Api::new(), Fallback::new())
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        match self.primary.get_price(flight.clone()).await {
+            Err(e) if e.is_outage() => {
+                log!("Pricing {}-{} on {} with the fallback provider: {}", flight.src, flight.dest, flight.date, e);
+                let quote = self.fallback.get_price(flight).await?;
+                Ok(Quote { fallback: true, ..quote })
+            }
+            res => res,
+        }
+    }
+
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        self.primary.cached_quotes()
+    }
+
+    fn restore_quotes(&mut self, quotes: Vec<(Flight, Quote)>) {
+        self.primary.restore_quotes(quotes);
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        self.primary.set_cabin_class(cabin);
+        self.fallback.set_cabin_class(cabin);
+    }
+
+    fn set_point_of_sale(&mut self, pos: &PointOfSale) {
+        self.primary.set_point_of_sale(pos);
+        self.fallback.set_point_of_sale(pos);
+    }
+
+    fn set_http_client(&mut self, client: &reqwest::Client) {
+        self.primary.set_http_client(client);
+        self.fallback.set_http_client(client);
+    }
+
+    fn set_api_key(&mut self, key: &str) {
+        self.primary.set_api_key(key);
+        self.fallback.set_api_key(key);
+    }
+
+    fn set_response_capture(&mut self, dir: Option<Arc<PathBuf>>) {
+        self.primary.set_response_capture(dir.clone());
+        self.fallback.set_response_capture(dir);
+    }
+
+    fn set_exchange_rates(&mut self, rates: Arc<ExchangeRates>) {
+        self.primary.set_exchange_rates(Arc::clone(&rates));
+        self.fallback.set_exchange_rates(rates);
+    }
+
+    fn set_simulation(&mut self, config: &SimulationConfig) {
+        self.primary.set_simulation(config);
+        self.fallback.set_simulation(config);
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.primary.cache_stats()
+    }
+
+    fn remaining_calls(&self) -> Option<u64> {
+        self.primary.remaining_calls()
+    }
+}
+
+/// [Provider]s by the name the config picks them with
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: BTreeMap<String, Arc<dyn Provider>>,
+}
+
+impl ProviderRegistry {
+    /// Registry of the providers the backend ships with
+    pub fn builtin() -> ProviderRegistry {
+        ProviderRegistry::default()
+            .with_provider::<SkyScannerApiQuery>(INDICATIVE)
+            .with_provider::<SkyScannerLiveQuery>(LIVE)
+            .with_fallback::<SkyScannerApiQuery, SkyScannerLiveQuery>(INDICATIVE_OR_LIVE)
+            .with_provider::<SimulatedApiQuery>(SIMULATED)
+    }
+
+    /// Make `Api` available as `name`, in place of any provider already registered as it
+    pub fn with_provider<Api: PriceQuery + 'static>(mut self, name: &str) -> ProviderRegistry {
+        self.providers.insert(name.to_string(), Arc::new(ProviderOf::<Api>::default()));
+        self
+    }
+
+    /// Make `Api` available as `name`, falling back on `Fallback` for the flights it fails to price, see
+    /// [FallbackPriceQuery]
+    pub fn with_fallback<Api: PriceQuery + 'static, Fallback: PriceQuery + 'static>(self, name: &str) -> ProviderRegistry {
+        self.with_provider::<FallbackPriceQuery<Api, Fallback>>(name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Provider>, RegistryError> {
+        self.providers.get(name).cloned().ok_or_else(|| RegistryError::Unknown {
+            name: name.to_string(),
+            known: self.names().map(str::to_string).collect(),
+        })
+    }
+
+    /// Names of the registered providers, alphabetically
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.providers.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod providers_tests {
+    use route_solver_shared::queries::{Date, Flight};
+
+    use super::{FallbackPriceQuery, ProviderRegistry, SIMULATED};
+    use crate::{
+        cache::PriceCache,
+        config::WorkerConfig,
+        flight_api::{PriceQuery, QueryError, TestPriceApiQuery},
+        jobs::JobManager,
+        simulated::SimulatedApiQuery,
+        testing::FakeApi,
+        worker_pool::WorkerPool,
+    };
+
+    #[tokio::test]
+    async fn test_registry() {
+        let registry = ProviderRegistry::builtin().with_provider::<TestPriceApiQuery>("mock");
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["indicative", "indicative-or-live", "live", "mock", "simulated"]);
+        let error = registry.get("csv").err().unwrap().to_string();
+        assert!(error.contains("\"csv\"") && error.contains("\"mock\""));
+
+        // Whichever provider is picked prices the calls made through it, each with its own cache so neither sees the
+        // other's quotes
+        let around = Flight {
+            src: "YYZ".parse().unwrap(),
+            dest: "YYC".parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+        };
+        let calendar = |name| {
+            let provider = registry.get(name).unwrap();
+            let jobs = JobManager::new(WorkerPool::new(&WorkerConfig::default()), None, PriceCache::in_memory());
+            let around = around.clone();
+            async move {
+                let tenant = jobs.tenants().default_tenant();
+                provider.calendar(&jobs, tenant, "user".to_string(), &Default::default(), around, 0).await
+            }
+        };
+        // The mock's CSV quotes 300 for the flight, the simulated fares never come out round
+        assert_eq!(calendar("mock").await[0].price, Some(300.0));
+        assert_ne!(calendar(SIMULATED).await[0].price, Some(300.0));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_provider() {
+        let flight = |src: &str| Flight {
+            src: src.parse().unwrap(),
+            dest: "YYC".parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+        };
+
+        // The primary provider is down
+        let down_api = FakeApi::new().with_failure(|_| Some(QueryError::BadResponse(503)));
+        let mut down = FallbackPriceQuery::with_fallback(down_api, TestPriceApiQuery::new());
+        let quote = down.get_price(flight("YYZ")).await.unwrap();
+        assert!(quote.fallback);
+        assert_eq!(quote.min_price, 300.0);
+
+        // Answers from the primary provider are kept, having no fare included
+        let mut up = FallbackPriceQuery::<TestPriceApiQuery, SimulatedApiQuery>::new();
+        assert!(!up.get_price(flight("YYZ")).await.unwrap().fallback);
+        assert!(matches!(up.get_price(flight("LHR")).await, Err(QueryError::NoFare)));
+    }
+}
@@ -0,0 +1,243 @@
+//! Per-user accounting of pricing API calls.
+//!
+//! Every quote that actually goes out to a provider is charged to the user who submitted the solve, cache hits are
+//! free. Users are identified by their `X-Api-Key` header if it's a key the server knows, one with a `[quota]` override
+//! or a tenant's, otherwise by their IP address, so a made up key can't get anyone a fresh quota. Usage resets at UTC
+//! midnight and is only kept in memory, so a restart also gives everyone a fresh quota.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use route_solver_shared::queries::{CabinClass, Date, Flight, JobId};
+
+use crate::{
+    audit::{AuditEvent, AuditLog, CallOutcome},
+    cache::CacheStats,
+    config::{QuotaConfig, SimulationConfig},
+    currency::ExchangeRates,
+    flight_api::{PointOfSale, PriceQuery, QueryError, Quote},
+    stats::ServerStats,
+};
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+struct Usage {
+    day: Date,
+    calls: u64,
+}
+
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl QuotaTracker {
+    pub fn new(config: QuotaConfig) -> QuotaTracker {
+        QuotaTracker {
+            config,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn unlimited() -> QuotaTracker {
+        QuotaTracker::new(QuotaConfig::default())
+    }
+
+    /// Daily call limit for the user, `None` if they're unlimited
+    pub fn limit(&self, user: &str) -> Option<u64> {
+        self.config.keys.get(user).copied().or(self.config.daily_limit)
+    }
+
+    /// Whether `key` has a limit of its own in [QuotaConfig::keys]
+    pub fn has_key(&self, key: &str) -> bool {
+        self.config.keys.contains_key(key)
+    }
+
+    /// Calls the user has left today, `None` if they're unlimited
+    pub fn remaining(&self, user: &str) -> Option<u64> {
+        let limit = self.limit(user)?;
+        Some(limit.saturating_sub(self.used_today(user)))
+    }
+
+    /// Most calls a solve is estimated to make before the user has to confirm it, see [QuotaConfig::confirm_calls_above]
+    pub fn confirm_calls_above(&self) -> Option<u64> {
+        self.config.confirm_calls_above
+    }
+
+    pub fn used_today(&self, user: &str) -> u64 {
+        match self.usage.lock().unwrap().get(user) {
+            Some(usage) if usage.day == Self::today() => usage.calls,
+            _ => 0,
+        }
+    }
+
+    /// Charge one call to the user, fails without charging anything once their quota is used up
+    pub fn try_charge(&self, user: &str) -> Result<(), QueryError> {
+        let limit = self.limit(user);
+        let today = Self::today();
+
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(user.to_string()).or_insert(Usage { day: today, calls: 0 });
+        if entry.day != today {
+            *entry = Usage { day: today, calls: 0 };
+        }
+
+        if limit.is_some_and(|limit| entry.calls >= limit) {
+            return Err(QueryError::QuotaExceeded);
+        }
+
+        entry.calls += 1;
+        Ok(())
+    }
+
+    fn today() -> Date {
+        chrono::Utc::now().date_naive()
+    }
+}
+
+/// Wraps a provider so each call made through it is charged to a user's quota and counted in the server stats
+pub struct MeteredPriceQuery<Api> {
+    inner: Api,
+    quota: Arc<QuotaTracker>,
+    stats: Arc<ServerStats>,
+    /// Solves without a user (e.g. resumed from before quotas existed) aren't metered
+    user: Option<String>,
+    audit: Option<AuditTrail>,
+}
+
+/// Where a metered provider's calls are recorded, see [MeteredPriceQuery::with_audit]
+struct AuditTrail {
+    log: Arc<AuditLog>,
+    tenant: Option<String>,
+    job: Option<JobId>,
+}
+
+impl<Api: PriceQuery> MeteredPriceQuery<Api> {
+    pub fn with_meters(inner: Api, quota: Arc<QuotaTracker>, stats: Arc<ServerStats>, user: Option<String>) -> Self {
+        MeteredPriceQuery {
+            inner,
+            quota,
+            stats,
+            user,
+            audit: None,
+        }
+    }
+
+    /// Record every call that reaches the provider in `log`, as made for `tenant` by the job `job` if any
+    pub fn with_audit(mut self, log: Arc<AuditLog>, tenant: Option<String>, job: Option<JobId>) -> Self {
+        self.audit = Some(AuditTrail { log, tenant, job });
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<Api: PriceQuery> PriceQuery for MeteredPriceQuery<Api> {
+    fn new() -> Self {
+        Self::with_meters(
+            Api::new(),
+            Arc::new(QuotaTracker::unlimited()),
+            Arc::new(ServerStats::default()),
+            None,
+        )
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        if let Some(user) = &self.user {
+            self.quota.try_charge(user)?;
+        }
+
+        let res = self.inner.get_price(flight.clone()).await;
+        // Having no fare is an answer, not a failure
+        self.stats.record_provider_call(matches!(res, Ok(_) | Err(QueryError::NoFare)));
+        if let Some(trail) = &self.audit {
+            let outcome = match &res {
+                Ok(_) => CallOutcome::Quoted,
+                Err(QueryError::NoFare) => CallOutcome::NoFare,
+                Err(_) => CallOutcome::Failed,
+            };
+            let event = AuditEvent::ProviderCall {
+                job: trail.job,
+                flight,
+                outcome,
+            };
+            trail.log.record(trail.tenant.as_deref(), self.user.as_deref(), event);
+        }
+        res
+    }
+
+    fn cached_quotes(&self) -> Vec<(Flight, Quote)> {
+        self.inner.cached_quotes()
+    }
+
+    fn restore_quotes(&mut self, quotes: Vec<(Flight, Quote)>) {
+        self.inner.restore_quotes(quotes);
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        self.inner.set_cabin_class(cabin);
+    }
+
+    fn set_point_of_sale(&mut self, pos: &PointOfSale) {
+        self.inner.set_point_of_sale(pos);
+    }
+
+    fn set_http_client(&mut self, client: &reqwest::Client) {
+        self.inner.set_http_client(client);
+    }
+
+    fn set_api_key(&mut self, key: &str) {
+        self.inner.set_api_key(key);
+    }
+
+    fn set_response_capture(&mut self, dir: Option<Arc<PathBuf>>) {
+        self.inner.set_response_capture(dir);
+    }
+
+    fn set_exchange_rates(&mut self, rates: Arc<ExchangeRates>) {
+        self.inner.set_exchange_rates(rates);
+    }
+
+    fn set_simulation(&mut self, config: &SimulationConfig) {
+        self.inner.set_simulation(config);
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn remaining_calls(&self) -> Option<u64> {
+        self.quota.remaining(self.user.as_deref()?)
+    }
+}
+
+#[cfg(test)]
+mod quota_tests {
+    use std::collections::HashMap;
+
+    use crate::config::QuotaConfig;
+
+    use super::QuotaTracker;
+
+    #[test]
+    fn test_quota_enforced_per_user() {
+        let quota = QuotaTracker::new(QuotaConfig {
+            daily_limit: Some(2),
+            keys: HashMap::from([("heavy-user".to_string(), 5)]),
+            ..QuotaConfig::default()
+        });
+
+        assert!(quota.try_charge("ip:10.0.0.1").is_ok());
+        assert!(quota.try_charge("ip:10.0.0.1").is_ok());
+        assert!(quota.try_charge("ip:10.0.0.1").is_err());
+        assert_eq!(quota.remaining("ip:10.0.0.1"), Some(0));
+
+        // One user running dry doesn't touch anyone else's quota
+        assert_eq!(quota.remaining("ip:10.0.0.2"), Some(2));
+        assert_eq!(quota.remaining("heavy-user"), Some(5));
+        assert!(quota.try_charge("heavy-user").is_ok());
+        assert_eq!(quota.remaining("heavy-user"), Some(4));
+    }
+}
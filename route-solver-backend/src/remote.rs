@@ -0,0 +1,315 @@
+//! Solving on a running server from the command line, `route-solver solve --remote URL --itinerary ID`.
+//!
+//! The itinerary is one the server has already solved, looked up by that job's ID, and solved again as a new job so
+//! the fares are fresh. Requests carry the user's API key, so the solve is charged to their quota like one from the
+//! web app.
+//!
+//! Everything `--format json` writes is versioned for scripts to check: the route on stdout is a [TripSummary] and each
+//! progress line on stderr a [SolveProgress], each with its own `schema_version`.
+
+use std::time::Duration;
+
+use reqwest::{RequestBuilder, Response};
+use route_solver_shared::queries::{
+    ApiError, JobHandle, JobId, JobStatus, PriceSource, RouteQuery, RouteSolution, TransportMode, TripSummary,
+    CONFIRMED_CALLS_HEADER,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::quota::API_KEY_HEADER;
+
+/// Environment variable the API key is read from when `--api-key` isn't given
+pub const API_KEY_ENV_VAR: &str = "ROUTE_SOLVER_API_KEY";
+
+#[derive(Debug, Error)]
+pub enum RemoteError {
+    #[error("Could not reach the server.")]
+    Http(#[from] reqwest::Error),
+    #[error("Could not read the server's answer.")]
+    Parse(serde_json::Error),
+    #[error("Server answered: {0}")]
+    Api(ApiError),
+    #[error("{0}")]
+    Usage(String),
+}
+
+/// How the solved route is printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// For reading, see [solution_table]
+    #[default]
+    Table,
+    /// The trip's [TripSummary], and progress as a [SolveProgress] per line, for scripts
+    Json,
+}
+
+/// A line of progress `--format json` writes while the job runs
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SolveProgress {
+    /// See [SolveProgress::SCHEMA_VERSION]
+    pub schema_version: u32,
+    pub status: JobStatus,
+}
+
+impl SolveProgress {
+    /// Version of the JSON layout of progress lines, bumped when a field is removed or changes meaning. `status` is
+    /// laid out as the server's [JobStatus], which a new version of either changes.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    pub fn new(status: &JobStatus) -> SolveProgress {
+        SolveProgress {
+            schema_version: Self::SCHEMA_VERSION,
+            status: status.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveCommand {
+    /// Base URL of the server, e.g. `https://routes.example.com`
+    pub remote: String,
+    /// Job whose itinerary is solved
+    pub itinerary: JobId,
+    pub api_key: Option<String>,
+    /// How often the job's progress is checked
+    pub poll_interval: Duration,
+    pub format: OutputFormat,
+    /// Calls the solve is confirmed to make, for servers asking before large solves
+    pub confirm_calls: Option<u64>,
+}
+
+impl SolveCommand {
+    const USAGE: &'static str = "Usage: route-solver solve --remote URL --itinerary JOB_ID [--api-key KEY] [--poll-ms MS] [--format table|json] [--confirm-calls N]";
+
+    /// Read the command from the arguments after `solve`, the API key falling back to [API_KEY_ENV_VAR]
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<SolveCommand, RemoteError> {
+        let usage = || RemoteError::Usage(Self::USAGE.to_string());
+        let (mut remote, mut itinerary, mut api_key) = (None, None, None);
+        let mut poll_interval = Duration::from_secs(1);
+        let mut format = OutputFormat::default();
+        let mut confirm_calls = None;
+
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or_else(usage)?;
+            match flag.as_str() {
+                "--remote" => remote = Some(value.trim_end_matches('/').to_string()),
+                "--itinerary" => {
+                    itinerary = Some(value.parse().map_err(|_| RemoteError::Usage(format!("{} isn't a job ID", value)))?)
+                }
+                "--api-key" => api_key = Some(value),
+                "--poll-ms" => {
+                    let ms = value.parse().map_err(|_| RemoteError::Usage(format!("{} isn't milliseconds", value)))?;
+                    poll_interval = Duration::from_millis(ms);
+                }
+                "--format" => {
+                    format = match value.as_str() {
+                        "table" => OutputFormat::Table,
+                        "json" => OutputFormat::Json,
+                        _ => return Err(RemoteError::Usage(format!("Unknown format {}, expected table or json", value))),
+                    }
+                }
+                "--confirm-calls" => {
+                    let calls = value.parse().map_err(|_| RemoteError::Usage(format!("{} isn't a call count", value)))?;
+                    confirm_calls = Some(calls);
+                }
+                _ => return Err(usage()),
+            }
+        }
+
+        Ok(SolveCommand {
+            remote: remote.ok_or_else(usage)?,
+            itinerary: itinerary.ok_or_else(usage)?,
+            api_key: api_key.or_else(|| std::env::var(API_KEY_ENV_VAR).ok()),
+            poll_interval,
+            format,
+            confirm_calls,
+        })
+    }
+
+    fn authed(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        }
+    }
+
+    /// Solve the itinerary, calling `progress` with the job's status each time it's checked until it's done. Returns the
+    /// new job's ID with its route.
+    pub async fn run(&self, mut progress: impl FnMut(&JobStatus)) -> Result<(JobId, RouteSolution), RemoteError> {
+        let client = reqwest::Client::new();
+        let jobs = format!("{}/api/v1/jobs", self.remote);
+
+        let query: RouteQuery = read(self.authed(client.get(format!("{}/{}/query", jobs, self.itinerary))).send().await?).await?;
+        let mut submit = client
+            .post(&jobs)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(&query).expect("Route queries always serialize"));
+        if let Some(calls) = self.confirm_calls {
+            submit = submit.header(CONFIRMED_CALLS_HEADER, calls.to_string());
+        }
+        let handle: JobHandle = read(self.authed(submit).send().await?).await?;
+
+        loop {
+            let status: JobStatus = read(self.authed(client.get(format!("{}/{}", jobs, handle.id))).send().await?).await?;
+            progress(&status);
+            match status {
+                JobStatus::Completed { solution } => return Ok((handle.id, solution)),
+                JobStatus::Failed { error } => return Err(RemoteError::Api(error)),
+                JobStatus::Queued | JobStatus::Running { .. } => tokio::time::sleep(self.poll_interval).await,
+            }
+        }
+    }
+
+    /// Summary of a job [SolveCommand::run] finished
+    pub async fn summary(&self, job: JobId) -> Result<TripSummary, RemoteError> {
+        let url = format!("{}/api/v1/jobs/{}/summary", self.remote, job);
+        read(self.authed(reqwest::Client::new().get(url)).send().await?).await
+    }
+}
+
+/// The body of a successful response, otherwise the [ApiError] the server answered with
+async fn read<T: DeserializeOwned>(response: Response) -> Result<T, RemoteError> {
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(RemoteError::Api(ApiError::from_status(status.as_u16(), body)));
+    }
+    serde_json::from_str(&body).map_err(RemoteError::Parse)
+}
+
+/// The job's progress on one line, e.g. "Solving, 120 prices looked up, 4031 routes tried"
+pub fn describe_progress(status: &JobStatus) -> String {
+    match status {
+        JobStatus::Queued => "Queued".to_string(),
+        JobStatus::Running {
+            api_calls,
+            nodes_expanded,
+        } => format!("Solving, {} prices looked up, {} routes tried", api_calls, nodes_expanded),
+        JobStatus::Completed { .. } => "Solved".to_string(),
+        JobStatus::Failed { .. } => "Failed".to_string(),
+    }
+}
+
+/// The route's legs in date order as a plain text table, with the total underneath
+pub fn solution_table(solution: &RouteSolution) -> String {
+    let mut legs: Vec<_> = solution.flights.iter().collect();
+    legs.sort_by_key(|f| f.flight.date);
+
+    let mut table = format!("{:<10}  {:<4}  {:<4}  {:<6}  {:>10}\n", "Date", "From", "To", "By", "Price");
+    for leg in legs {
+        let mode = match leg.mode {
+            TransportMode::Flight => "Flight",
+            TransportMode::Ground => "Ground",
+        };
+        let source = match leg.source {
+            PriceSource::Quoted => "",
+            PriceSource::Fallback => " (fallback)",
+            PriceSource::NearbyDays | PriceSource::History => " (estimated)",
+        };
+        table += &format!(
+            "{:<10}  {:<4}  {:<4}  {:<6}  {:>10.2}{}\n",
+            leg.flight.date, leg.flight.src, leg.flight.dest, mode, leg.price, source
+        );
+    }
+    table += &format!("{:<32}  {:>10.2} {}\n", "Total", solution.total_price, solution.currency);
+    table
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use std::time::Duration;
+
+    use route_solver_shared::queries::{Date, Flight, FlightPrice, JobStatus, PriceSource, RouteSolution, TransportMode};
+
+    use super::{solution_table, OutputFormat, SolveCommand, SolveProgress};
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_parse_solve_command() {
+        let command = SolveCommand::parse(args(&[
+            "--remote",
+            "https://routes.example.com/",
+            "--itinerary",
+            "42",
+            "--api-key",
+            "secret",
+            "--poll-ms",
+            "250",
+            "--format",
+            "json",
+            "--confirm-calls",
+            "1500",
+        ]))
+        .unwrap();
+        assert_eq!(
+            command,
+            SolveCommand {
+                remote: "https://routes.example.com".to_string(),
+                itinerary: 42,
+                api_key: Some("secret".to_string()),
+                poll_interval: Duration::from_millis(250),
+                format: OutputFormat::Json,
+                confirm_calls: Some(1500),
+            }
+        );
+
+        assert!(SolveCommand::parse(args(&["--remote", "https://routes.example.com"])).is_err());
+        assert!(SolveCommand::parse(args(&["--remote", "https://routes.example.com", "--itinerary", "abc"])).is_err());
+        assert!(SolveCommand::parse(args(&["--itinerary", "42", "--remote"])).is_err());
+        assert!(SolveCommand::parse(args(&["--remote", "https://routes.example.com", "--itinerary", "42", "--format", "xml"])).is_err());
+    }
+
+    #[test]
+    fn test_progress_versioned() {
+        let status = JobStatus::Running {
+            api_calls: 12,
+            nodes_expanded: 40,
+        };
+        let json = serde_json::to_value(SolveProgress::new(&status)).unwrap();
+
+        assert_eq!(json["schema_version"], SolveProgress::SCHEMA_VERSION);
+        assert_eq!(json["status"], serde_json::to_value(&status).unwrap());
+    }
+
+    #[test]
+    fn test_solution_table() {
+        let leg = |src: &str, dest: &str, day: u32, price: f32, mode: TransportMode| FlightPrice {
+            flight: Flight {
+                src: src.parse().unwrap(),
+                dest: dest.parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 6, day).unwrap(),
+            },
+            price,
+            stay_price: 0.0,
+            mode,
+            // Estimated overland, the flight quoted by the fallback provider
+            source: if mode == TransportMode::Ground { PriceSource::NearbyDays } else { PriceSource::Fallback },
+            std_dev: 0.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 0.0,
+            alternative: None,
+        };
+        let solution = RouteSolution {
+            flights: vec![leg("AMS", "YYZ", 10, 640.5, TransportMode::Flight), leg("CDG", "AMS", 4, 89.0, TransportMode::Ground)],
+            total_price: 729.5,
+            currency: "CAD".to_string(),
+            passengers: 1,
+            alternatives: vec![],
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+
+        let table = solution_table(&solution);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("2023-06-04  CDG   AMS   Ground") && lines[1].ends_with("89.00 (estimated)"));
+        assert!(lines[2].starts_with("2023-06-10  AMS   YYZ   Flight") && lines[2].ends_with("640.50 (fallback)"));
+        assert!(lines[3].starts_with("Total") && lines[3].ends_with("729.50 CAD"));
+    }
+}
@@ -0,0 +1,109 @@
+//! Request IDs, for correlating what a user saw with the server logs.
+//!
+//! Every request is given an ID, taken from its `X-Request-Id` header when the client sends a usable one. The ID is
+//! echoed back in the response header, recorded on any job the request submits, and prefixed to each line logged with
+//! [log!](crate::log) while handling the request or running its solve.
+
+use std::future::Future;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest inbound ID accepted, anything longer is replaced with a generated one
+const MAX_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: Option<String>;
+}
+
+/// ID of the request the current task is working on, if any
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok().flatten()
+}
+
+/// Run `fut` with `id` as the current request ID. Task locals aren't inherited, so spawned tasks need scoping too.
+pub fn scope<F: Future>(id: Option<String>, fut: F) -> impl Future<Output = F::Output> {
+    REQUEST_ID.scope(id, fut)
+}
+
+/// `println!`, prefixed with the current request ID if there is one
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        match $crate::request_id::current() {
+            Some(id) => println!("[{}] {}", id, format_args!($($arg)*)),
+            None => println!($($arg)*),
+        }
+    };
+}
+
+/// Middleware assigning each request its ID, register it last so it wraps everything else
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut res = scope(Some(id.clone()), next.call(req)).await?;
+
+    // Always a valid header value, inbound IDs are restricted to a safe character set
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+    }
+    Ok(res)
+}
+
+/// Inbound IDs end up in the logs, so only accept short ones made of unremarkable characters
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod request_id_tests {
+    use actix_web::{
+        middleware::from_fn,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    use super::{assign_request_id, current, REQUEST_ID_HEADER};
+
+    #[actix_web::test]
+    async fn test_request_id_propagated() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(assign_request_id))
+                .route("/", web::get().to(|| async { HttpResponse::Ok().body(current().unwrap()) })),
+        )
+        .await;
+
+        // A sensible inbound ID is kept and visible to the handler
+        let req = TestRequest::get().insert_header((REQUEST_ID_HEADER, "client-abc.1")).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "client-abc.1");
+        assert_eq!(read_body(res).await, "client-abc.1");
+
+        // Anything which could mess with the logs is replaced
+        let req = TestRequest::get().insert_header((REQUEST_ID_HEADER, "bad id\twith junk")).to_request();
+        let res = call_service(&app, req).await;
+        let id = res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap().to_string();
+        assert_ne!(id, "bad id\twith junk");
+        assert_eq!(read_body(res).await, id);
+    }
+}
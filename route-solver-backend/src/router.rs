@@ -3,83 +3,603 @@
 //! todo: Explain algorithm
 
 use std::{
-    cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
-    fmt,
-    rc::Rc,
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    fmt::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::flight_api::PriceQuery;
+use crate::{
+    arrivals::ArrivalClock,
+    baggage::BagFees,
+    estimates::PriceEstimates,
+    flight_api::{PriceQuery, QueryError, Quote},
+    ground::GroundLegs,
+    history::SeasonalPriors,
+    log,
+    stays::StayCostProvider,
+    throttle::QuotaThrottle,
+};
+use chrono::NaiveDateTime;
 use route_solver_shared::queries::*;
+use serde::Serialize;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Error)]
+pub enum RouterError {
+    #[error("Itinerary cannot solve, adjust parameters")]
+    NoRoute,
+    #[error("Solve was cancelled")]
+    Cancelled,
+    #[error("Price lookup failed: {0}")]
+    Query(QueryError),
+    #[error("Price lookup returned a price that isn't a number")]
+    InvalidPrice,
+    #[error("Itinerary has {0} stops, at most {MAX_DESTINATIONS} are supported")]
+    TooManyDestinations(usize),
+}
 
-#[derive(Clone)]
-struct RouterProblem {
-    dest_list: Vec<Destination>,
+impl From<RouterError> for ApiError {
+    fn from(e: RouterError) -> ApiError {
+        match e {
+            RouterError::NoRoute => ApiError::Infeasible(e.to_string()),
+            RouterError::Cancelled => ApiError::Unavailable(e.to_string()),
+            RouterError::Query(e) => e.into(),
+            RouterError::InvalidPrice => ApiError::ProviderUnavailable(e.to_string()),
+            RouterError::TooManyDestinations(_) => ApiError::Validation(e.to_string()),
+        }
+    }
 }
 
-/// Router Stats
-struct RouterStats {
-    api_calls: u16,
-    enabled: bool,
+/// Most destinations a problem can have, including the start and end, one per bit of [FlightNode::visited]
+pub const MAX_DESTINATIONS: usize = u64::BITS as usize;
+
+#[derive(Clone)]
+pub struct RouterProblem {
+    pub dest_list: Vec<Destination>,
+    /// Airports the trip may start from besides the first destination's, on its dates
+    pub alternate_origins: Vec<AirportCode>,
+    /// What starting from each origin costs in [BASE_CURRENCY], added to the price of routes from it when comparing them
+    pub origin_penalties: BTreeMap<AirportCode, f32>,
+    pub strategy: SearchStrategy,
+    pub limits: RouteLimits,
+    /// Cost of each calendar day the trip takes in [BASE_CURRENCY], added to routes' prices when comparing them
+    pub value_of_day: f32,
+    pub objective: Objective,
+    /// Add a checked bag's fee to each flight, see [Router::set_bag_fees]
+    pub checked_bag: bool,
+    /// Price some days first and refine around the best routes, see [DateSampling]
+    pub sampling: Option<DateSampling>,
 }
 
 /// Main router class, maintains a database of already seen prices.
-struct Router<Api: PriceQuery> {
+pub struct Router<Api: PriceQuery> {
     api: Api,
     stats: RouterStats,
+    /// Time spent waiting on prices and on the rest of the search, kept finer than [RouterStats] has them
+    pricing_time: Duration,
+    search_time: Duration,
+    checkpoint: Option<Checkpoint<Api>>,
+    progress: Option<Progress>,
+    cancel: Option<CancellationToken>,
+    /// Number of routes to find, the best and then the next cheapest as alternatives
+    candidates: usize,
+    /// Graph explored by the last solve, only kept once the solve is over if `record_graph` is set
+    graph: FlightGraph,
+    record_graph: bool,
+    /// Works out which day flights land, they land the day they leave without one
+    arrivals: Option<Arc<ArrivalClock>>,
+    /// Prices the nights between flights, routes are compared on flights alone without one
+    stay_costs: Option<Arc<dyn StayCostProvider>>,
+    /// Offers legs overland as well as by air, every leg is flown without it
+    ground: Option<Arc<GroundLegs>>,
+    /// Prices days the provider has no fare for from the days around them, those legs are ruled out without it
+    estimates: Option<PriceEstimates>,
+    /// What past solves saw each route cost, to guess which legs are worth pricing
+    priors: Option<Arc<SeasonalPriors>>,
+    /// Estimates bag fees the provider didn't quote, only the provider's are added without it
+    bag_fees: Option<Arc<BagFees>>,
+    /// Last failure of the provider the solve carried on past, see [Router::degraded]
+    outage: Option<QueryError>,
+    /// Coarsens the search as the quota runs low, see [Router::set_quota_throttle]
+    throttle: Option<QuotaThrottle>,
+    /// Legs off the sampled days which the second pass of a sampled search prices, `None` outside of it. See
+    /// [Router::sampled_graph_search].
+    refining: Option<HashSet<Flight>>,
+}
+
+/// Called with the provider and the number of API calls made so far
+type CheckpointFn<Api> = Box<dyn FnMut(&Api, u64)>;
+
+/// Callback run every `every` API calls during a solve, lets callers persist the provider's quotes and report progress
+/// mid-solve
+struct Checkpoint<Api> {
+    every: u64,
+    callback: CheckpointFn<Api>,
+}
+
+/// How far a solve has got, see [Router::set_progress]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SearchProgress {
+    pub nodes_expanded: usize,
+    /// Priced nodes still waiting to be expanded
+    pub queue_size: usize,
+    /// Priority of the node last expanded. For the strategies which don't estimate no route left to find is cheaper.
+    pub best_bound: f32,
+    pub api_calls: u64,
+}
+
+/// Callback run every `every` nodes expanded during a solve
+struct Progress {
+    every: usize,
+    callback: Box<dyn FnMut(SearchProgress)>,
 }
 
 /// Wrapper for the result of the solve
-struct RouterResult {
-    result: Vec<FlightPrice>,
+pub struct RouterResult {
+    pub result: Vec<FlightPrice>,
+    /// Next cheapest routes after `result`, cheapest first
+    pub alternatives: Vec<Vec<FlightPrice>>,
+}
+
+/// Index of a node in a [FlightGraph]
+type NodeId = usize;
+
+/// A leg a node could be expanded with, before it's priced
+#[derive(Debug, Clone, Copy)]
+struct Leg {
+    /// Index in the problem's destination list of where it goes
+    dest: usize,
+    date: Date,
+    flight_days_in_a_row: u32,
+}
+
+/// One way of travelling a [Leg] once it's priced
+struct PricedLeg {
+    mode: TransportMode,
+    price: f32,
+    bag_fee: f32,
+    std_dev: f32,
+    quoted_at: Option<NaiveDateTime>,
+    arrival: Date,
+    source: PriceSource,
 }
 
 /// Graph node for main flights graph. The flights graph represents all possible flight/date combinations given the route problem.
 ///
-/// Each node contains a [Flight](route_solver_shared::Queries::Flight), a price, and an child list. Price is lazy loaded to not kill the API.
-#[derive(Debug)]
+/// Each node contains a [Flight](route_solver_shared::Queries::Flight), its price and the price of the route up to and
+/// including it. Nodes are only created, and so priced, as the search reaches them to not kill the API.
+#[derive(Debug, Serialize)]
 struct FlightNode {
     flight: Flight,
-    back_price: Option<f32>,
-    price: Option<f32>,
-    prev: Option<Rc<FlightNode>>,
-    dest_ref: Destination,
+    /// How the leg is travelled, the flight's date and route are the same overland
+    mode: TransportMode,
+    /// Day the flight lands, in the destination's local time. Stays at the destination are counted from it.
+    arrival: Date,
+    /// What the route up to and including this leg costs: its legs, the stays between them and the value of the days
+    /// it's taken
+    back_price: f32,
+    price: f32,
+    /// Part of `price` that's a checked bag's fee
+    bag_fee: f32,
+    /// Accommodation where the flight leaves from, between the flight in landing and this one leaving
+    stay_price: f32,
+    /// Where `price` came from, see [FlightPrice::source]
+    source: PriceSource,
+    /// How far `price` may be off by the time the route is booked, see [Quote::std_dev]
+    std_dev: f32,
+    /// See [Quote::quoted_at]
+    quoted_at: Option<NaiveDateTime>,
+    /// Node this one was expanded from, `None` for the seed
+    prev: Option<NodeId>,
+    /// Where the route up to this node starts, the problem's first destination or one of its alternate origins
+    origin: AirportCode,
+    /// Index in the problem's destination list of the destination the flight arrives at
+    dest: usize,
+    /// Bit `i` is set if the route up to and including this node has been to destination `i`
+    visited: u64,
+    /// Days in a row the route has flown on, ending with this node's
+    flight_days_in_a_row: u32,
+    state: NodeState,
+}
+
+/// How far the search got with a node
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum NodeState {
+    /// Priced but never expanded, either the search had its routes before this was the cheapest one left or the
+    /// [SolverStrategy] dropped it. Nodes start out pruned until the search reaches them.
+    Pruned,
+    /// Expanded, `order` counts the nodes expanded before it
+    Expanded { order: usize },
+    /// End of a route the search found, rank 0 being the best
+    Route { rank: usize },
+    /// Queued at the fare the route's prior expects instead of being priced. If the search reaches it the leg is priced
+    /// as nodes of its own, this one is left as it is.
+    Deferred,
+}
+
+impl fmt::Display for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeState::Pruned => write!(f, "pruned"),
+            NodeState::Expanded { order } => write!(f, "expanded #{}", order),
+            NodeState::Route { rank: 0 } => write!(f, "best route"),
+            NodeState::Route { rank } => write!(f, "alternative {}", rank),
+            NodeState::Deferred => write!(f, "deferred"),
+        }
+    }
+}
+
+/// Every node a search has created, nodes refer to each other by their index. Serializes to JSON for debugging solves.
+#[derive(Debug, Default, Serialize)]
+pub struct FlightGraph {
+    nodes: Vec<FlightNode>,
 }
 
-impl RouterStats {
-    fn new() -> RouterStats {
-        RouterStats {
-            api_calls: 0,
-            enabled: true,
+impl FlightGraph {
+    fn push(&mut self, node: FlightNode) -> NodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn node(&self, id: NodeId) -> &FlightNode {
+        &self.nodes[id]
+    }
+
+    fn set_state(&mut self, id: NodeId, state: NodeState) {
+        self.nodes[id].state = state;
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// `id` and the nodes before it, back to the seed
+    fn ancestors(&self, id: NodeId) -> impl Iterator<Item = &FlightNode> {
+        std::iter::successors(Some(self.node(id)), |node| node.prev.map(|prev| self.node(prev)))
+    }
+
+    /// The nodes from the seed to `id`, in the order they're flown
+    fn path(&self, id: NodeId) -> Vec<&FlightNode> {
+        let mut path: Vec<&FlightNode> = self.ancestors(id).collect();
+        path.reverse();
+        path
+    }
+
+    /// The least any route through each node could cost, as far as the search got: the least [FlightNode::back_price]
+    /// of the nodes it didn't expand after it, which is exact for the ends of routes. Infinite for nodes whose every
+    /// route was ruled out.
+    fn frontier_bounds(&self) -> Vec<f32> {
+        let mut bounds: Vec<f32> = self
+            .nodes
+            .iter()
+            .map(|node| match node.state {
+                NodeState::Expanded { .. } => f32::INFINITY,
+                _ => node.back_price,
+            })
+            .collect();
+        // Nodes are always pushed after the node they were expanded from
+        for (id, node) in self.nodes.iter().enumerate().rev() {
+            if let Some(prev) = node.prev {
+                bounds[prev] = bounds[prev].min(bounds[id]);
+            }
         }
+        bounds
+    }
+
+    /// For each leg of the route ending at `end`, the cheapest other leg the search priced from where it leaves, see
+    /// [FlightPrice::alternative]
+    fn alternatives(&self, end: NodeId) -> Vec<Option<LegAlternative>> {
+        let bounds = self.frontier_bounds();
+        let total = self.node(end).back_price;
+        let mut route: Vec<NodeId> = std::iter::successors(Some(end), |&id| self.node(id).prev).collect();
+        route.reverse();
+
+        route
+            .into_iter()
+            .skip(1)
+            .map(|chosen| {
+                let leg = self.node(chosen);
+                let other = self.nodes.iter().enumerate().filter(|(_, node)| {
+                    // Deferred legs were never priced, only guessed at
+                    node.prev == leg.prev && node.flight != leg.flight && node.state != NodeState::Deferred
+                });
+                let (id, node) = other.min_by(|(a, a_node), (b, b_node)| {
+                    (a_node.price + a_node.stay_price)
+                        .total_cmp(&(b_node.price + b_node.stay_price))
+                        .then(bounds[*a].total_cmp(&bounds[*b]))
+                })?;
+                Some(LegAlternative {
+                    flight: node.flight.clone(),
+                    price: node.price + node.stay_price,
+                    saving: bounds[id].is_finite().then(|| bounds[id] - total),
+                })
+            })
+            .collect()
     }
 
-    fn record_call(&mut self) {
-        if self.enabled {
-            self.api_calls += 1;
+    /// The graph in GraphViz DOT, each node labelled with its flight, prices and how far the search got with it
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph explored {\n    rankdir=LR;\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let Some(prev) = node.prev else {
+                let _ = writeln!(dot, "    n{} [label=\"start {}\", shape=ellipse];", id, node.flight.dest);
+                continue;
+            };
+
+            let via = match node.mode {
+                TransportMode::Flight => "",
+                TransportMode::Ground => " (ground)",
+            };
+            let source = match node.source {
+                PriceSource::Quoted => "",
+                PriceSource::Fallback => " (fallback)",
+                PriceSource::NearbyDays | PriceSource::History => " (estimated)",
+            };
+            let style = match node.state {
+                NodeState::Pruned => ", style=dashed, color=gray",
+                NodeState::Deferred => ", style=dotted, color=gray",
+                NodeState::Expanded { .. } => "",
+                NodeState::Route { rank: 0 } => ", style=bold, color=darkgreen",
+                NodeState::Route { .. } => ", color=blue",
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{} -> {}{}\\n{}\\n${:.2}{} (route ${:.2})\\n{}\"{}];",
+                id,
+                node.flight.src,
+                node.flight.dest,
+                via,
+                node.flight.date,
+                node.price,
+                source,
+                node.back_price,
+                node.state,
+                style
+            );
+            let _ = writeln!(dot, "    n{} -> n{};", prev, id);
         }
+        dot.push_str("}\n");
+        dot
     }
 }
 
-impl fmt::Display for RouterStats {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Api calls: {}", self.api_calls)
+/// Decides the order the search expands nodes in and which it drops. The search loop pops the node with the lowest
+/// priority, stops there if it reaches the final destination and otherwise expands it, if the strategy keeps it.
+trait SolverStrategy {
+    /// Where a newly priced node goes in the queue, lowest first. `legs_left` counts the legs still to fly after it.
+    fn priority(&self, node: &FlightNode, _legs_left: usize) -> f32 {
+        node.back_price
+    }
+
+    /// Whether to expand `node`, it's left pruned if not
+    fn keep(&mut self, _node: &FlightNode) -> bool {
+        true
+    }
+
+    /// Called with the price of every flight looked up
+    fn observe_price(&mut self, _price: f32) {}
+
+    /// Whether legs the priors expect a fare for are queued at it and only priced once they come off the queue. Only for
+    /// strategies which don't promise the cheapest route, as a leg can cost less than expected.
+    fn defers_pricing(&self) -> bool {
+        false
+    }
+}
+
+/// `cheapest_into` is the cheapest the priors expect a leg into each destination to be, by index, for the strategies
+/// which estimate the legs left
+fn solver_strategy(strategy: SearchStrategy, cheapest_into: Vec<Option<f32>>) -> Box<dyn SolverStrategy> {
+    match strategy {
+        SearchStrategy::Dijkstra => Box::new(Dijkstra),
+        SearchStrategy::AStar => Box::new(AStar {
+            cheapest_leg: None,
+            cheapest_into,
+        }),
+        SearchStrategy::Beam { width } => Box::new(Beam {
+            width,
+            expanded: HashMap::new(),
+        }),
+        SearchStrategy::Dp => Box::new(Dp::default()),
+    }
+}
+
+struct Dijkstra;
+
+impl SolverStrategy for Dijkstra {}
+
+/// Counts each leg left at the cheapest fare the priors expect into the stop it goes to, or without one the cheapest
+/// fare seen so far. Fares can be cheaper than either, so the estimate can be over and the cheapest route missed.
+struct AStar {
+    cheapest_leg: Option<f32>,
+    /// By destination index, empty without priors
+    cheapest_into: Vec<Option<f32>>,
+}
+
+impl SolverStrategy for AStar {
+    fn priority(&self, node: &FlightNode, legs_left: usize) -> f32 {
+        let fallback = self.cheapest_leg.unwrap_or(0.0);
+        if self.cheapest_into.is_empty() {
+            return node.back_price + fallback * legs_left as f32;
+        }
+        let left: f32 = (1..self.cheapest_into.len())
+            .filter(|i| node.visited & 1 << i == 0)
+            .map(|i| self.cheapest_into[i].unwrap_or(fallback))
+            .sum();
+        node.back_price + left
+    }
+
+    fn observe_price(&mut self, price: f32) {
+        self.cheapest_leg = Some(self.cheapest_leg.map_or(price, |c| c.min(price)));
+    }
+
+    fn defers_pricing(&self) -> bool {
+        true
+    }
+}
+
+/// Nodes come off the queue cheapest first, so the first `width` at each depth are the cheapest to get there. Good
+/// answers come quickly for big, loosely constrained trips, as most of the ways of getting to each depth are dropped.
+struct Beam {
+    width: usize,
+    /// Nodes expanded so far by the number of places they've been to
+    expanded: HashMap<u32, usize>,
+}
+
+impl SolverStrategy for Beam {
+    fn keep(&mut self, node: &FlightNode) -> bool {
+        let expanded = self.expanded.entry(node.visited.count_ones()).or_default();
+        *expanded += 1;
+        *expanded <= self.width
+    }
+}
+
+/// Where a route can go from a node depends only on where it started and is, when it flew there and landed, where it's
+/// been and how many days running it's flown, so of the nodes sharing those only the first off the queue, the cheapest,
+/// needs expanding
+#[derive(Default)]
+struct Dp {
+    expanded: HashSet<(AirportCode, usize, u64, Date, Date, u32)>,
+}
+
+impl SolverStrategy for Dp {
+    fn keep(&mut self, node: &FlightNode) -> bool {
+        self.expanded.insert((
+            node.origin,
+            node.dest,
+            node.visited,
+            node.flight.date,
+            node.arrival,
+            node.flight_days_in_a_row,
+        ))
+    }
+}
+
+/// Price of a route so far, never NaN so it has a total order
+#[derive(Debug, Clone, Copy)]
+struct RoutePrice(f32);
+
+impl RoutePrice {
+    fn new(price: f32) -> Option<RoutePrice> {
+        (!price.is_nan()).then_some(RoutePrice(price))
+    }
+}
+
+impl PartialEq for RoutePrice {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RoutePrice {}
+
+impl PartialOrd for RoutePrice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RoutePrice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A node waiting in the search queue. The priority is reversed so the max-heap [BinaryHeap] pops the lowest first,
+/// ties go to the newest node.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct QueuedNode {
+    priority: Reverse<RoutePrice>,
+    id: NodeId,
+}
+
+impl QueuedNode {
+    fn new(priority: f32, id: NodeId) -> Result<QueuedNode, RouterError> {
+        let price = RoutePrice::new(priority).ok_or(RouterError::InvalidPrice)?;
+        Ok(QueuedNode {
+            priority: Reverse(price),
+            id,
+        })
+    }
+}
+
+impl From<&RouteQuery> for RouterProblem {
+    fn from(query: &RouteQuery) -> Self {
+        RouterProblem {
+            dest_list: query.destinations(),
+            alternate_origins: query.alternate_origins.clone(),
+            // In the query's currency, the caller converts it
+            origin_penalties: query.origin_penalties.clone(),
+            strategy: query.strategy,
+            limits: query.limits,
+            // In the query's currency, the caller converts it
+            value_of_day: query.value_of_day,
+            objective: query.objective,
+            checked_bag: query.checked_bag,
+            sampling: query.sampling,
+        }
     }
 }
 
 impl RouterResult {
-    fn total_price(&self) -> f32 {
-        self.result.iter().fold(0.0, |acc, f| acc + f.price)
+    pub fn total_price(&self) -> f32 {
+        route_price(&self.result)
+    }
+
+    /// What each destination of the best route costs, see [RouteSolution::breakdown]
+    pub fn breakdown(&self) -> Vec<DestinationCost> {
+        solution_of(self.result.clone()).breakdown()
+    }
+}
+
+/// Flights and the stays between them
+fn route_price(flights: &[FlightPrice]) -> f32 {
+    flights.iter().fold(0.0, |acc, f| acc + f.price + f.stay_price)
+}
+
+fn solution_of(flights: Vec<FlightPrice>) -> RouteSolution {
+    RouteSolution {
+        total_price: route_price(&flights),
+        currency: BASE_CURRENCY.to_string(),
+        passengers: 1,
+        origin: flights.first().map(|f| f.flight.src),
+        flights,
+        alternatives: Vec::new(),
+        request_id: None,
+        stats: None,
+    }
+}
+
+impl From<RouterResult> for RouteSolution {
+    fn from(res: RouterResult) -> Self {
+        RouteSolution {
+            alternatives: res.alternatives.into_iter().map(solution_of).collect(),
+            ..solution_of(res.result)
+        }
     }
 }
 
 impl fmt::Display for RouterResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut res = "".to_string();
-        for flight in &self.result {
+        for (flight, cost) in self.result.iter().zip(self.breakdown()) {
             let curr_val = format!(
-                "{} : {} -> {} : ${}, ",
-                flight.flight.date, flight.flight.src, flight.flight.dest, flight.price
+                "{} : {} -> {} : ${}, {} nights ${}, {:.0}% (${} so far), ",
+                flight.flight.date,
+                flight.flight.src,
+                flight.flight.dest,
+                cost.inbound,
+                cost.nights,
+                cost.lodging,
+                cost.share * 100.0,
+                cost.running_total
             );
             res += &curr_val;
         }
@@ -88,301 +608,945 @@ impl fmt::Display for RouterResult {
     }
 }
 
+impl<Api: PriceQuery> Default for Router<Api> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<Api: PriceQuery> Router<Api> {
-    fn new() -> Router<Api> {
+    pub fn new() -> Router<Api> {
+        Self::with_api(Api::new())
+    }
+
+    pub fn with_api(api: Api) -> Router<Api> {
         Router {
-            api: Api::new(),
-            stats: RouterStats::new(),
+            api,
+            stats: RouterStats::default(),
+            pricing_time: Duration::ZERO,
+            search_time: Duration::ZERO,
+            checkpoint: None,
+            progress: None,
+            cancel: None,
+            candidates: 1,
+            graph: FlightGraph::default(),
+            record_graph: false,
+            arrivals: None,
+            stay_costs: None,
+            ground: None,
+            estimates: None,
+            priors: None,
+            bag_fees: None,
+            outage: None,
+            throttle: None,
+            refining: None,
         }
     }
 
+    pub fn api(&self) -> &Api {
+        &self.api
+    }
+
+    /// Number of prices looked up so far
+    pub fn api_calls(&self) -> u64 {
+        self.stats.api_calls
+    }
+
+    /// What the solves so far have done, the cache's counts are as of the last price looked up
+    pub fn stats(&self) -> &RouterStats {
+        &self.stats
+    }
+
+    /// Call `callback` with the price API and the API call count every `every` API calls.
+    pub fn set_checkpoint(&mut self, every: u64, callback: impl FnMut(&Api, u64) + 'static) {
+        self.checkpoint = Some(Checkpoint {
+            every: every.max(1),
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Call `callback` with how far the search has got every `every` nodes expanded.
+    pub fn set_progress(&mut self, every: usize, callback: impl FnMut(SearchProgress) + 'static) {
+        self.progress = Some(Progress {
+            every: every.max(1),
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Stop the solve early with [RouterError::Cancelled] once `token` is cancelled.
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.cancel = Some(token);
+    }
+
+    /// Find up to `candidates` routes rather than just the cheapest. Each extra route means searching on past the
+    /// best one, which costs more API calls.
+    pub fn set_candidates(&mut self, candidates: usize) {
+        self.candidates = candidates.max(1);
+    }
+
+    /// Keep the graph each solve explores for [Router::explored_graph]. Off by default, as the graph holds every flight
+    /// the search priced.
+    pub fn set_record_graph(&mut self, record: bool) {
+        self.record_graph = record;
+    }
+
+    /// Count stays from the day flights land by `clock` rather than the day they leave, so overnight flights don't eat
+    /// into the minimum stay or leave before they've landed
+    pub fn set_arrival_clock(&mut self, clock: Arc<ArrivalClock>) {
+        self.arrivals = Some(clock);
+    }
+
+    /// Add what `costs` charges for the nights between flights to the price of routes, so a cheap flight which means
+    /// an extra night somewhere expensive can lose out
+    pub fn set_stay_costs(&mut self, costs: Arc<dyn StayCostProvider>) {
+        self.stay_costs = Some(costs);
+    }
+
+    /// Consider going overland between stops `legs` has fares for, alongside flying
+    pub fn set_ground_legs(&mut self, legs: Arc<GroundLegs>) {
+        self.ground = Some(legs);
+    }
+
+    /// Price days the provider has no fare for from quotes on nearby days, as set out by `estimates`, rather than ruling
+    /// them out. Each estimate can cost a few extra API calls looking for those quotes.
+    pub fn set_price_estimates(&mut self, estimates: PriceEstimates) {
+        self.estimates = Some(estimates);
+    }
+
+    /// Guess which legs are worth pricing from what `priors` expect them to cost. A* counts the legs left at the
+    /// cheapest fares they expect and only prices a leg they cover once it looks the most promising, the other
+    /// strategies price the legs expected to be cheap first. Legs the provider fails to price, and which can't be
+    /// estimated from nearby days, are priced at what the priors expect too.
+    pub fn set_price_priors(&mut self, priors: Arc<SeasonalPriors>) {
+        self.priors = Some(priors);
+    }
+
+    /// Search coarser as the provider's [remaining_calls](PriceQuery::remaining_calls) fall below what `throttle` allows
+    /// for, rather than running out partway. Without it the search is as the problem asks until the quota runs out.
+    pub fn set_quota_throttle(&mut self, throttle: QuotaThrottle) {
+        self.throttle = Some(throttle);
+    }
+
+    /// Estimate the fee for a checked bag with `fees` on flights the provider didn't quote one for, for problems asking
+    /// for a bag
+    pub fn set_bag_fees(&mut self, fees: Arc<BagFees>) {
+        self.bag_fees = Some(fees);
+    }
+
+    /// Every flight the last solve looked at, for debugging. Empty unless [Router::set_record_graph] is on.
+    pub fn explored_graph(&self) -> &FlightGraph {
+        &self.graph
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
     /// Main solver routine, takes in problem and outputs route.
     ///
     /// The algorithm performs the following general steps to create the route
     /// 1. Construct a graph of all possible ```Flight```s between the anchor SRC and anchor DEST
-    ///     a. A ```Flight``` represents a src/dest with a date of travel
-    ///     b. Each node on the graph represents a flight with a cost of that flight (lazy calculated)
-    /// 2. Djikstra search from SRC to DEST anchor
-    async fn calc(&mut self, problem: RouterProblem) -> RouterResult {
-        let problem_res = self.perform_graph_search(problem).await;
-
-        // For now panic if flight not possible
-        let problem_res_unwrap = problem_res.unwrap();
-        RouterResult {
-            result: problem_res_unwrap
-                .iter()
-                .skip(1) // First node is a dummy for seeding heap expansion
-                .map(|f| FlightPrice {
-                    flight: f.flight.clone(),
-                    price: f.price.unwrap(),
+    ///    a. A ```Flight``` represents a src/dest with a date of travel
+    ///    b. Each node on the graph represents a flight with a cost of that flight (lazy calculated)
+    /// 2. Search from SRC to DEST anchor, Djikstra unless the problem asks for another [SearchStrategy]
+    pub async fn calc(&mut self, problem: RouterProblem) -> Result<RouterResult, RouterError> {
+        let (started, priced_before) = (Instant::now(), self.pricing_time);
+        let found = match problem.sampling {
+            Some(sampling) => self.sampled_graph_search(problem, sampling).await,
+            None => self.perform_graph_search(problem).await,
+        };
+        self.search_time += started.elapsed().saturating_sub(self.pricing_time - priced_before);
+        self.stats.pricing_ms = self.pricing_time.as_millis() as u64;
+        self.stats.search_ms = self.search_time.as_millis() as u64;
+
+        let found = found.map(|final_nodes| {
+            final_nodes
+                .into_iter()
+                .enumerate()
+                .map(|(rank, id)| {
+                    // The runners up are among what the best route was chosen over, they aren't explained themselves
+                    let mut alternatives = match rank {
+                        0 => self.graph.alternatives(id),
+                        _ => Vec::new(),
+                    }
+                    .into_iter();
+                    self.graph
+                        .path(id)
+                        .iter()
+                        .skip(1) // First node is a dummy for seeding heap expansion
+                        .map(|f| FlightPrice {
+                            flight: f.flight.clone(),
+                            price: f.price,
+                            stay_price: f.stay_price,
+                            mode: f.mode,
+                            source: f.source,
+                            std_dev: f.std_dev,
+                            arrival: Some(f.arrival),
+                            quoted_at: f.quoted_at,
+                            bag_fee: f.bag_fee,
+                            alternative: alternatives.next().flatten(),
+                        })
+                        .collect::<Vec<_>>()
                 })
-                .collect(),
+                .collect::<Vec<_>>()
+        });
+        // Kept for failed solves too, they're the ones most worth debugging
+        if !self.record_graph {
+            self.graph = FlightGraph::default();
         }
+        let outage = self.outage.take();
+        let mut routes = match found {
+            Err(RouterError::NoRoute) => Vec::new().into_iter(),
+            found => found?.into_iter(),
+        };
+        // A route might have been found had the provider answered, its failure says more than there being none
+        let Some(result) = routes.next() else {
+            return Err(outage.map_or(RouterError::NoRoute, RouterError::Query));
+        };
+
+        Ok(RouterResult {
+            result,
+            alternatives: routes.collect(),
+        })
     }
 
-    fn backtrace_helper(&mut self, curr_node: Rc<FlightNode>, output: &mut Vec<Rc<FlightNode>>) {
-        if let Some(prev) = &curr_node.prev {
-            self.backtrace_helper(Rc::clone(prev), output);
+    /// Quote for `flight`, `None` if nothing flies it that day
+    async fn quote(&mut self, flight: &Flight) -> Result<Option<Quote>, RouterError> {
+        let started = Instant::now();
+        let quote = self.api.get_price(flight.clone()).await;
+        self.pricing_time += started.elapsed();
+        let quote = match quote {
+            Ok(quote) => Some(quote),
+            Err(QueryError::NoFare) => None,
+            Err(e) => return Err(RouterError::Query(e)),
+        };
+        self.stats.api_calls += 1;
+        if let Some(cache) = self.api.cache_stats() {
+            self.stats.set_cache(cache.hits, cache.misses);
         }
 
-        output.push(curr_node);
+        if let Some(cp) = &mut self.checkpoint {
+            if self.stats.api_calls.is_multiple_of(cp.every) {
+                (cp.callback)(&self.api, self.stats.api_calls);
+            }
+        }
+        Ok(quote)
     }
 
-    fn backtrace_node(&mut self, final_node: Rc<FlightNode>) -> Vec<Rc<FlightNode>> {
-        let mut output_vec = Vec::<Rc<FlightNode>>::new();
-        self.backtrace_helper(final_node, &mut output_vec);
-
-        output_vec
+    /// Fare for `flight` from the nearest quoted days either side of it on the same route
+    async fn estimate(&mut self, flight: &Flight, estimates: PriceEstimates) -> Result<Option<f32>, RouterError> {
+        let mut nearest = [None, None];
+        for (side, towards) in nearest.iter_mut().zip([-1, 1]) {
+            for days in 1..=estimates.max_gap_days {
+                let Some(date) = flight.date.checked_add_signed(chrono::Duration::days(towards * days as i64)) else {
+                    break;
+                };
+                let near = Flight { date, ..flight.clone() };
+                match self.quote(&near).await {
+                    Ok(Some(quote)) => {
+                        *side = Some((days, quote.min_price));
+                        break;
+                    }
+                    Ok(None) => {}
+                    // A day the provider failed for is as good as one it has no fare for
+                    Err(RouterError::Query(e)) if carries_on(&e) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(PriceEstimates::interpolate(nearest[0], nearest[1]))
     }
 
-    async fn expand_node(
+    /// Fare for `flight` from the days `sampling` samples either side of it in the leg's `window`, on the same route
+    async fn sampled_estimate(
         &mut self,
-        src: Rc<FlightNode>,
-        remaining_dests: Vec<Destination>,
-        main_queue: &mut BinaryHeap<Rc<FlightNode>>,
-    ) {
-        for next_dest in remaining_dests.iter() {
-            println!("Flight {} -> {}", src.flight.dest, next_dest.iata);
-            for possible_date in src.dest_ref.dates.get_intersect_iter_with_next(&next_dest.dates, Some(src.flight.date)) {
-                println!("  Checking date {}", possible_date);
-                // Create next nodes
-                let flight = Flight {
-                    src: src.flight.dest.clone(),
-                    dest: next_dest.iata.clone(),
-                    date: possible_date,
-                };
+        flight: &Flight,
+        sampling: DateSampling,
+        window: DateWindow,
+    ) -> Result<Option<f32>, RouterError> {
+        let mut nearest = [None, None];
+        for (side, (days, date)) in nearest.iter_mut().zip(sampling.sampled_around(flight.date, window)) {
+            let sampled = Flight { date, ..flight.clone() };
+            match self.quote(&sampled).await {
+                Ok(quote) => *side = quote.map(|quote| (days, quote.min_price)),
+                Err(RouterError::Query(e)) if carries_on(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(PriceEstimates::interpolate(nearest[0], nearest[1]))
+    }
 
-                let price_query = self.api.get_price(flight.clone()).await.unwrap().min_price;
-                self.stats.record_call();
+    /// How coarse to search with the calls the quota has left, see [QuotaThrottle::level]
+    fn throttle_level(&mut self) -> ThrottleLevel {
+        let Some(throttle) = self.throttle else {
+            return ThrottleLevel::Full;
+        };
+        let remaining = self.api.remaining_calls();
+        let level = throttle.level(remaining);
+        if level > self.stats.throttle {
+            log!("  {} provider calls left today, searching {:?}", remaining.unwrap_or_default(), level);
+            self.stats.throttle = level;
+        }
+        level
+    }
 
-                let node = FlightNode {
-                    flight,
-                    price: Some(price_query),
-                    back_price: Some(src.back_price.unwrap() + price_query),
-                    prev: Some(Rc::clone(&src)),
-                    dest_ref: next_dest.clone(),
-                };
+    /// Price for `flight` after the provider failed to quote it, or the quota ran out: estimated from nearby days if
+    /// they're quoted, otherwise what the route usually costs on the day. `None` rules the leg out. The failure is kept
+    /// to fail the solve with if it finds no route.
+    async fn degraded(&mut self, flight: &Flight, error: QueryError) -> Result<Option<(Quote, PriceSource)>, RouterError> {
+        if matches!(error, QueryError::QuotaExceeded) {
+            // Only said once, every leg not in the cache fails the same way from here on
+            if self.stats.throttle < ThrottleLevel::Exhausted {
+                log!("  Quota ran out, carrying on with the fares already fetched: {}", error);
+            }
+            self.stats.throttle = ThrottleLevel::Exhausted;
+        } else {
+            log!("  Provider failed to price {}-{} on {}, carrying on without it: {}", flight.src, flight.dest, flight.date, error);
+        }
+        self.stats.degraded_legs += 1;
+        self.outage = Some(error);
 
-                // Insert into queue
-                main_queue.push(Rc::new(node));
+        if let Some(estimates) = self.estimates {
+            if let Some(price) = self.estimate(flight, estimates).await? {
+                return Ok(Some((estimated_quote(price), PriceSource::NearbyDays)));
             }
         }
+        let usual = self.priors.as_ref().and_then(|priors| priors.expected_price(flight.src, flight.dest, flight.date));
+        Ok(usual.map(|price| (estimated_quote(price), PriceSource::History)))
     }
 
-    fn fill_dest_list(
-        &self,
-        curr_node: Rc<FlightNode>,
-        final_dest: &Destination,
-        init_dest_list: &Vec<Destination>,
-    ) -> Vec<Destination> {
-        let filter_pred = |e: &Destination| -> Option<Destination> {
-            // TODO: The way we do this makes having duplicate city entries in itinerary unsupported...
-            if curr_node.flight.dest == e.iata {
-                return None;
-            }
+    async fn expand_node(
+        &mut self,
+        src: NodeId,
+        remaining_dests: Vec<usize>,
+        problem: &RouterProblem,
+        strategy: &mut dyn SolverStrategy,
+        main_queue: &mut BinaryHeap<QueuedNode>,
+    ) -> Result<(), RouterError> {
+        let src_node = self.graph.node(src);
+        let (src_flight, src_back_price, src_visited) = (src_node.flight.clone(), src_node.back_price, src_node.visited);
+        let (src_arrival, src_origin) = (src_node.arrival, src_node.origin);
+        // The seed's date is made up, the first flight always starts a run
+        let src_run = if src_node.prev.is_none() { 0 } else { src_node.flight_days_in_a_row };
+        let (dests, limits) = (&problem.dest_list, &problem.limits);
+        let src_dest = &dests[src_node.dest];
+
+        // The legs flown so far, plus this one
+        let flights = src_visited.count_ones() as usize;
+        if limits.max_flights.is_some_and(|max| flights > max) {
+            self.stats.nodes_pruned.limits += 1;
+            return Ok(());
+        }
 
-            let mut temp_curr_node = &curr_node;
-            while let Some(prev) = &temp_curr_node.prev {
-                if prev.flight.dest == e.iata {
-                    return None;
+        let mut legs = Vec::new();
+        for next_idx in remaining_dests {
+            let next_dest = &dests[next_idx];
+            log!("Flight {} -> {}", src_flight.dest, next_dest.iata);
+            let window = src_dest.dates.leg_window(&next_dest.dates);
+            for possible_date in src_dest.dates.get_intersect_iter_with_next(&next_dest.dates, Some(src_arrival)) {
+                let flight_days_in_a_row = match possible_date.days_since(src_flight.date) {
+                    0 if src_run > 0 => src_run,
+                    1 => src_run + 1,
+                    _ => 1,
+                };
+                // Checked before pricing, so routes which break the limit cost nothing
+                if limits.max_flight_days_in_a_row.is_some_and(|max| flight_days_in_a_row > max) {
+                    self.stats.nodes_pruned.limits += 1;
+                    continue;
+                }
+                // Refining a sampled search, the days between the sampled ones are only flown near the best routes
+                if let (Some(sampling), Some(around)) = (problem.sampling, &self.refining) {
+                    let flight = Flight {
+                        src: src_flight.dest,
+                        dest: next_dest.iata,
+                        date: possible_date,
+                    };
+                    let priced = window.is_some_and(|window| sampling.prices(possible_date, window));
+                    if !priced && !around.contains(&flight) {
+                        self.stats.nodes_pruned.strategy += 1;
+                        continue;
+                    }
                 }
 
-                temp_curr_node = &prev;
+                let leg = Leg {
+                    dest: next_idx,
+                    date: possible_date,
+                    flight_days_in_a_row,
+                };
+                let expected = self
+                    .priors
+                    .as_ref()
+                    .and_then(|priors| priors.expected_price(src_flight.dest, next_dest.iata, possible_date));
+                legs.push((leg, expected));
             }
+        }
+        // The legs expected to be cheapest are priced first, the order they were found in otherwise
+        legs.sort_by(|(_, a), (_, b)| a.unwrap_or(f32::INFINITY).total_cmp(&b.unwrap_or(f32::INFINITY)));
 
-            Some(e.clone())
-        };
+        for (leg, expected) in legs {
+            // Leave the rest unpriced, the search loop picks up the cancellation
+            if self.is_cancelled() {
+                return Ok(());
+            }
 
-        let mut dest_list: Vec<Destination> =
-            init_dest_list.iter().filter_map(filter_pred).collect();
-        if dest_list.len() == 0 {
-            dest_list.push(final_dest.clone());
+            match expected {
+                Some(price) if strategy.defers_pricing() => {
+                    let id = self.graph.push(FlightNode {
+                        flight: Flight {
+                            src: src_flight.dest,
+                            dest: dests[leg.dest].iata,
+                            date: leg.date,
+                        },
+                        mode: TransportMode::Flight,
+                        arrival: leg.date,
+                        back_price: src_back_price + price,
+                        price,
+                        stay_price: 0.0,
+                        bag_fee: 0.0,
+                        source: PriceSource::Quoted,
+                        std_dev: 0.0,
+                        quoted_at: None,
+                        prev: Some(src),
+                        origin: src_origin,
+                        dest: leg.dest,
+                        visited: src_visited | 1 << leg.dest,
+                        flight_days_in_a_row: leg.flight_days_in_a_row,
+                        state: NodeState::Deferred,
+                    });
+                    self.enqueue(id, dests.len(), strategy, main_queue)?;
+                }
+                _ => self.price_leg(src, leg, problem, strategy, main_queue).await?,
+            }
         }
 
-        dest_list
+        Ok(())
     }
 
-    async fn perform_graph_search(
+    /// Price `leg` from `src` and queue a node for each way of travelling it
+    async fn price_leg(
         &mut self,
-        problem: RouterProblem,
-    ) -> Result<Vec<Rc<FlightNode>>, String> {
-        // For a router problem, the anchors SRC and DEST are given at the front and back respectively of the Destination list, grab these
-        let src = problem.dest_list[0].clone();
-        let inter_dests_sl = &problem.dest_list[1..(problem.dest_list.len() - 1)];
-        let final_dest = &problem.dest_list[problem.dest_list.len() - 1];
+        src: NodeId,
+        leg: Leg,
+        problem: &RouterProblem,
+        strategy: &mut dyn SolverStrategy,
+        main_queue: &mut BinaryHeap<QueuedNode>,
+    ) -> Result<(), RouterError> {
+        let src_node = self.graph.node(src);
+        let (src_back_price, src_visited, src_arrival) = (src_node.back_price, src_node.visited, src_node.arrival);
+        let src_origin = src_node.origin;
+        // Nobody stays anywhere before the first flight
+        let is_seed = src_node.prev.is_none();
+        let (next_idx, possible_date) = (leg.dest, leg.date);
+        let next_dest = &problem.dest_list[next_idx];
+        let window = problem.dest_list[src_node.dest].dates.leg_window(&next_dest.dates);
+        let now = chrono::Utc::now().naive_utc();
+
+        log!("  Checking date {}", possible_date);
+        // Create next nodes
+        let flight = Flight {
+            src: src_node.flight.dest,
+            dest: next_dest.iata,
+            date: possible_date,
+        };
 
-        let mut main_queue = BinaryHeap::<Rc<FlightNode>>::new();
-        let init_dest_list = inter_dests_sl.to_vec();
+        // The first pass of a sampled search, or short on calls, days between the sampled ones are estimated from them.
+        // The day itself is still priced if neither has a fare.
+        let level = self.throttle_level();
+        let sampling = match (problem.sampling, &self.refining) {
+            (Some(sampling), None) => Some(sampling),
+            _ => self.throttle.filter(|_| level >= ThrottleLevel::Sampled).map(|throttle| throttle.sampling()),
+        };
+        // Windows no wider than the days between sampled ones are priced in full
+        let sampled = match sampling.zip(window) {
+            Some((sampling, window)) if !sampling.prices(possible_date, window) => {
+                self.sampled_estimate(&flight, sampling, window).await?
+            }
+            _ => None,
+        };
 
-        // TODO: Generalize flight data to be able to include more or less metadata depending on the API
-        main_queue.push(Rc::new(FlightNode {
-            flight: Flight {
-                src: "".to_string(),
-                dest: src.iata.clone(),
-                date: Date::from_ymd_opt(2020, 1, 1).unwrap(),
+        // Nothing flying the leg that day rules it out like a date outside the windows, unless it can be estimated
+        let quote = match sampled {
+            Some(price) => Some((estimated_quote(price), PriceSource::NearbyDays)),
+            None => match self.quote(&flight).await {
+                Ok(Some(quote)) if quote.fallback => Some((quote, PriceSource::Fallback)),
+                Ok(Some(quote)) => Some((quote, PriceSource::Quoted)),
+                Ok(None) => match self.estimates {
+                    Some(estimates) => self
+                        .estimate(&flight, estimates)
+                        .await?
+                        .map(|price| (estimated_quote(price), PriceSource::NearbyDays)),
+                    None => None,
+                },
+                // The provider failing for one leg, or the quota running out, needn't fail the whole solve
+                Err(RouterError::Query(e)) if carries_on(&e) => self.degraded(&flight, e).await?,
+                Err(e) => return Err(e),
             },
-            back_price: Some(0.0),
-            price: Some(0.0),
-            prev: None,
-            dest_ref: src.clone(),
-        }));
-
-        let final_node: Rc<FlightNode> = loop {
-            let top = main_queue.pop();
-
-            if let None = top {
-                break None;
+        };
+        if quote.is_none() {
+            self.stats.nodes_pruned.no_fare += 1;
+        }
+        // Only the cheapest fare is quoted, if it stops there's no telling whether a direct flight exists
+        let quote = match quote {
+            Some((quote, _)) if problem.limits.direct_only && !quote.direct => {
+                self.stats.nodes_pruned.limits += 1;
+                None
             }
+            quote => quote,
+        };
 
-            let top_n = top.unwrap();
-
-            if top_n.flight.dest == final_dest.iata && top_n.prev.is_some() {
-                break Some(top_n);
+        let stay_price = match &self.stay_costs {
+            Some(costs) if !is_seed => costs.stay_cost(flight.src, src_arrival, possible_date),
+            _ => 0.0,
+        };
+        // The days the trip has taken since the last leg landed, or for the first leg since it left. Summed over the
+        // route that's every calendar day from the first departure to the last landing.
+        let days_since = |arrival: Date| {
+            if is_seed {
+                arrival.days_since(possible_date) + 1
+            } else {
+                arrival.days_since(src_arrival)
             }
+        };
 
-            // Can afford to linear search path and filter nodes that exist, path's aren't going to be long (hopefully)
-            let dest_list = self.fill_dest_list(Rc::clone(&top_n), &final_dest, &init_dest_list);
-
-            self.expand_node(Rc::clone(&top_n), dest_list, &mut main_queue)
-                .await;
+        // The stop's own ceiling if it's lower than the whole trip's
+        let max_leg_price = [problem.limits.max_leg_price, next_dest.max_leg_price].into_iter().flatten().reduce(f32::min);
+
+        // Going overland is its own node, it arrives the day it leaves
+        let ground = self.ground.as_ref().and_then(|g| g.price(flight.src, flight.dest, possible_date));
+        let flown = quote.map(|(quote, source)| {
+            // The provider's fee for the bag if it quoted one, otherwise the estimate
+            let bag_fee = match &self.bag_fees {
+                _ if !problem.checked_bag => 0.0,
+                Some(fees) => fees.fee(&quote),
+                None => quote.bag_fee.unwrap_or(0.0),
+            };
+            PricedLeg {
+                mode: TransportMode::Flight,
+                price: quote.min_price + bag_fee,
+                bag_fee,
+                std_dev: quote.std_dev(now),
+                quoted_at: quote.quoted_at,
+                arrival: match &self.arrivals {
+                    Some(clock) => clock.arrival_date(&flight, &quote),
+                    None => flight.date,
+                },
+                source,
+            }
+        });
+        // Ground fares are fixed
+        let overland = ground.map(|price| PricedLeg {
+            mode: TransportMode::Ground,
+            price,
+            bag_fee: 0.0,
+            std_dev: 0.0,
+            quoted_at: None,
+            arrival: possible_date,
+            source: PriceSource::Quoted,
+        });
+        for PricedLeg {
+            mode,
+            price,
+            bag_fee,
+            std_dev,
+            quoted_at,
+            arrival,
+            source,
+        } in flown.into_iter().chain(overland)
+        {
+            // The day it leaves is before any event at the next stop, but an overnight flight can land after
+            if !next_dest.dates.arrives_in_time(arrival) {
+                self.stats.nodes_pruned.late_arrival += 1;
+                continue;
+            }
+            // Ruled out however cheap the rest of the route is, like a leg with no fare
+            if max_leg_price.is_some_and(|max| price > max) {
+                self.stats.nodes_pruned.leg_price += 1;
+                continue;
+            }
+            strategy.observe_price(price);
+            // An estimate is compared as a little dearer than a quote of the same price
+            let weighed = match self.estimates {
+                Some(estimates) if source.is_estimate() => estimates.penalized(price),
+                _ => price,
+            };
+            // Summing the legs' deviations bounds the route's from above, as their fares move together
+            let weighed = match problem.objective {
+                Objective::Expected => weighed,
+                Objective::Pessimistic { sigmas } => weighed + sigmas * std_dev,
+            };
+            let id = self.graph.push(FlightNode {
+                flight: flight.clone(),
+                mode,
+                arrival,
+                price,
+                bag_fee,
+                stay_price,
+                source,
+                std_dev,
+                quoted_at,
+                back_price: src_back_price + stay_price + weighed + problem.value_of_day * days_since(arrival) as f32,
+                prev: Some(src),
+                origin: src_origin,
+                dest: next_idx,
+                visited: src_visited | 1 << next_idx,
+                flight_days_in_a_row: leg.flight_days_in_a_row,
+                state: NodeState::Pruned,
+            });
+            self.enqueue(id, problem.dest_list.len(), strategy, main_queue)?;
         }
-        .ok_or("Itinerary cannot solve, adjust parameters".to_string())?;
 
-        let list_flights = self.backtrace_node(final_node);
+        Ok(())
+    }
 
-        Ok(list_flights)
+    /// Queue `id` where `strategy` puts it
+    fn enqueue(
+        &self,
+        id: NodeId,
+        dest_count: usize,
+        strategy: &dyn SolverStrategy,
+        main_queue: &mut BinaryHeap<QueuedNode>,
+    ) -> Result<(), RouterError> {
+        let node = self.graph.node(id);
+        let legs_left = dest_count - node.visited.count_ones() as usize;
+        main_queue.push(QueuedNode::new(strategy.priority(node, legs_left), id)?);
+        Ok(())
     }
-}
 
-impl PartialEq<FlightNode> for FlightNode {
-    fn eq(&self, other: &Self) -> bool {
-        if let Some(s_p) = self.back_price {
-            if let Some(o_p) = other.back_price {
-                return s_p == o_p;
-            }
+    /// Indices of the intermediate destinations the route up to `node` hasn't been to yet, or of the final destination
+    /// once it's been to them all
+    fn fill_dest_list(&self, node: NodeId, dest_count: usize) -> Vec<usize> {
+        let final_idx = dest_count - 1;
+        let visited = self.graph.node(node).visited;
+        let dest_list: Vec<usize> = (1..final_idx).filter(|&i| visited & (1 << i) == 0).collect();
+
+        if dest_list.is_empty() {
+            vec![final_idx]
+        } else {
+            dest_list
         }
-
-        false
     }
-}
 
-impl PartialOrd<FlightNode> for FlightNode {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if let Some(s_p) = self.back_price {
-            if let Some(o_p) = other.back_price {
-                if s_p < o_p {
-                    return Some(Ordering::Greater);
-                } else if s_p > o_p {
-                    return Some(Ordering::Less);
-                }
-
-                return Some(Ordering::Equal);
+    /// Search on the days `sampling` samples, the days between estimated from them, for its `refine` best routes. Then
+    /// search again pricing the days around each leg of those, up to a sample apart, and dropping the other days between
+    /// the sampled ones. Returns what the second search found.
+    async fn sampled_graph_search(
+        &mut self,
+        problem: RouterProblem,
+        sampling: DateSampling,
+    ) -> Result<Vec<NodeId>, RouterError> {
+        let candidates = self.candidates;
+        self.candidates = candidates.max(sampling.refine);
+        let coarse = self.perform_graph_search(problem.clone()).await;
+        self.candidates = candidates;
+
+        let near = sampling.every.saturating_sub(1) as i64;
+        let mut around = HashSet::new();
+        for &id in &coarse? {
+            for node in self.graph.path(id).iter().skip(1) {
+                let dates = (-near..=near).filter_map(|days| node.flight.date.checked_add_signed(chrono::Duration::days(days)));
+                around.extend(dates.map(|date| Flight { date, ..node.flight.clone() }));
             }
         }
+        log!("Refining the best sampled routes, {} legs around them", around.len());
 
-        None
+        self.refining = Some(around);
+        let found = self.perform_graph_search(problem).await;
+        self.refining = None;
+        found
     }
-}
 
-impl Eq for FlightNode {}
-
-impl Ord for FlightNode {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if let Some(s_p) = self.back_price {
-            if let Some(o_p) = other.back_price {
-                if s_p < o_p {
-                    return Ordering::Greater;
-                } else if s_p > o_p {
-                    return Ordering::Less;
-                }
+    /// Search for the cheapest routes, returns the last node of up to `candidates` of them, cheapest first
+    async fn perform_graph_search(&mut self, problem: RouterProblem) -> Result<Vec<NodeId>, RouterError> {
+        // For a router problem, the anchors SRC and DEST are given at the front and back respectively of the Destination list, grab these
+        let dests = &problem.dest_list;
+        if dests.len() > MAX_DESTINATIONS {
+            return Err(RouterError::TooManyDestinations(dests.len()));
+        }
+        let src = &dests[0];
+        let final_idx = dests.len() - 1;
 
-                return Ordering::Equal;
+        self.graph = FlightGraph::default();
+        // The cheapest leg the priors expect into each stop, from any other stop or origin
+        let cheapest_into = match &self.priors {
+            Some(priors) => dests
+                .iter()
+                .map(|to| {
+                    let from = dests.iter().map(|d| d.iata).chain(problem.alternate_origins.iter().copied());
+                    from.filter_map(|from| priors.cheapest(from, to.iata)).reduce(f32::min)
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut strategy = solver_strategy(problem.strategy, cheapest_into);
+        let mut main_queue = BinaryHeap::<QueuedNode>::new();
+
+        // One seed per origin, they all stand in for the first destination and share its dates
+        let mut origins = vec![src.iata];
+        for &origin in &problem.alternate_origins {
+            if !origins.contains(&origin) {
+                origins.push(origin);
             }
         }
+        for origin in origins {
+            // Routes from a less convenient origin carry its penalty, so they only win by saving more than it
+            let penalty = problem.origin_penalties.get(&origin).copied().unwrap_or(0.0);
+            // TODO: Generalize flight data to be able to include more or less metadata depending on the API
+            let seed = self.graph.push(FlightNode {
+                flight: Flight {
+                    // The seed only marks where the trip starts, it's never priced or part of the solution
+                    src: origin,
+                    dest: origin,
+                    date: Date::from_ymd_opt(2020, 1, 1).unwrap(),
+                },
+                mode: TransportMode::Flight,
+                arrival: Date::from_ymd_opt(2020, 1, 1).unwrap(),
+                back_price: penalty,
+                price: 0.0,
+                stay_price: 0.0,
+                bag_fee: 0.0,
+                source: PriceSource::Quoted,
+                std_dev: 0.0,
+                quoted_at: None,
+                prev: None,
+                origin,
+                dest: 0,
+                visited: 1,
+                flight_days_in_a_row: 0,
+                state: NodeState::Pruned,
+            });
+            main_queue.push(QueuedNode::new(penalty, seed)?);
+        }
 
-        panic!("Comparing empty price");
-    }
-}
+        // Complete routes come off the queue roughly cheapest first, exactly so for the strategies which don't estimate,
+        // so the search can carry on for the runners up
+        let mut final_nodes = Vec::new();
+        let mut expanded = 0;
+        // Started once the throttle narrows the search
+        let mut narrowed: Option<Beam> = None;
+        while final_nodes.len() < self.candidates {
+            if self.is_cancelled() {
+                return Err(RouterError::Cancelled);
+            }
+            self.stats.peak_queue_size = self.stats.peak_queue_size.max(main_queue.len() as u64);
+
+            let Some(top_n) = main_queue.pop() else {
+                break;
+            };
+
+            let level = self.throttle_level();
+            let node = self.graph.node(top_n.id);
+            if let (NodeState::Deferred, Some(src)) = (node.state, node.prev) {
+                let leg = Leg {
+                    dest: node.dest,
+                    date: node.flight.date,
+                    flight_days_in_a_row: node.flight_days_in_a_row,
+                };
+                self.price_leg(src, leg, &problem, strategy.as_mut(), &mut main_queue).await?;
+                continue;
+            }
+            if node.dest == final_idx {
+                final_nodes.push(top_n.id);
+                continue;
+            }
+            if !strategy.keep(node) {
+                self.stats.nodes_pruned.strategy += 1;
+                continue;
+            }
+            // Short on calls, only the cheapest few routes at each number of stops carry on whatever the strategy
+            if let Some(throttle) = self.throttle.filter(|_| level >= ThrottleLevel::Narrowed) {
+                let beam = narrowed.get_or_insert_with(|| Beam {
+                    width: throttle.beam_width,
+                    expanded: HashMap::new(),
+                });
+                if !beam.keep(node) {
+                    self.stats.nodes_pruned.strategy += 1;
+                    continue;
+                }
+            }
+            self.graph.set_state(top_n.id, NodeState::Expanded { order: expanded });
+            expanded += 1;
+            self.stats.nodes_expanded += 1;
+
+            let dest_list = self.fill_dest_list(top_n.id, dests.len());
+
+            self.expand_node(top_n.id, dest_list, &problem, strategy.as_mut(), &mut main_queue)
+                .await?;
+
+            if let Some(progress) = &mut self.progress {
+                if expanded.is_multiple_of(progress.every) {
+                    (progress.callback)(SearchProgress {
+                        nodes_expanded: expanded,
+                        queue_size: main_queue.len(),
+                        best_bound: top_n.priority.0 .0,
+                        api_calls: self.stats.api_calls,
+                    });
+                }
+            }
+        }
 
-#[cfg(test)]
-mod router_tests {
-    use std::{collections::BinaryHeap, rc::Rc};
+        if final_nodes.is_empty() {
+            return Err(RouterError::NoRoute);
+        }
+
+        final_nodes.sort_by(|a, b| self.graph.node(*a).back_price.total_cmp(&self.graph.node(*b).back_price));
+        for (rank, id) in final_nodes.iter().enumerate() {
+            self.graph.set_state(*id, NodeState::Route { rank });
+        }
+
+        Ok(final_nodes)
+    }
+}
 
-    use route_solver_shared::queries::{Date, DateRange, Destination, Flight, SingleDateRange, DateConstraints, DateRestrictions};
+/// Whether a solve carries on past `error` pricing a leg rather than failing, as it's the provider failing for the leg
+/// or the quota running out partway
+fn carries_on(error: &QueryError) -> bool {
+    error.is_outage() || matches!(error, QueryError::QuotaExceeded)
+}
 
-    use crate::{flight_api::TestPriceApiQuery, router::RouterProblem};
+/// Stands in for the provider's quote of a leg whose fare is estimated
+fn estimated_quote(price: f32) -> Quote {
+    Quote {
+        min_price: price,
+        direct: false,
+        arrives: None,
+        quoted_at: None,
+        spread: 0.0,
+        currency: None,
+        carrier: None,
+        bag_fee: None,
+        fallback: false,
+    }
+}
 
-    use super::{FlightNode, Router};
+#[cfg(test)]
+mod router_tests {
+    use std::{cell::RefCell, collections::{BTreeMap, BinaryHeap, HashSet}, rc::Rc, sync::Arc};
+
+    use chrono::Datelike;
+    use serde_json::Value;
+
+    use route_solver_shared::queries::{
+        CabinClass, Date, DateConstraints, DateRestrictions, DateSampling, Destination, EventDates, Flight, Objective, PriceSource, RouteLimits, RouteSolution, SearchStrategy,
+        DateWindow, ThrottleLevel, TransportMode,
+    };
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::{
+        airports::AirportDirectory,
+        arrivals::ArrivalClock,
+        baggage::BagFees,
+        estimates::PriceEstimates,
+        flight_api::{PriceQuery, QueryError, Quote, TestPriceApiQuery},
+        ground::{GroundLegs, GroundRoutes},
+        history::{PriceHistory, SeasonalPriors},
+        router::RouterProblem,
+        stays::NightlyRates,
+        testing::FakeApi,
+        throttle::QuotaThrottle,
+    };
+
+    use super::{Dijkstra, FlightNode, NodeState, QueuedNode, Router, RouterError, RouterResult, MAX_DESTINATIONS};
 
     #[tokio::test]
     async fn test_heap_expand() {
         let node_date_range =
-            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap());
+            DateWindow::new(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap());
 
         let mut router = Router::<TestPriceApiQuery>::new();
-        let node_to_expand = Rc::new(FlightNode {
+        let node_to_expand = router.graph.push(FlightNode {
             flight: Flight {
-                src: "YYZ".to_string(),
-                dest: "YVR".to_string(),
+                src: "YYZ".parse().unwrap(),
+                dest: "YVR".parse().unwrap(),
                 date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
             },
-            back_price: Some(0.0),
-            price: Some(250.0),
+            mode: TransportMode::Flight,
+            arrival: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+            back_price: 0.0,
+            price: 250.0,
+            stay_price: 0.0,
+            bag_fee: 0.0,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            quoted_at: None,
             prev: None,
-            dest_ref: Destination {
-                iata: "YYZ".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(SingleDateRange::None, node_date_range)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
-            },
+            origin: "YYZ".parse().unwrap(),
+            dest: 0,
+            visited: 1,
+            flight_days_in_a_row: 0,
+            state: NodeState::Pruned,
         });
 
         let test_dest_vec = vec![
             Destination {
-                iata: "YYC".to_string(),
+                iata: "YYZ".parse().unwrap(),
                 dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 2, 3).unwrap()),
-                        SingleDateRange::None)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
+                    arrival_window: None,
+                    departure_window: node_date_range,
+                    date_restrictions: DateRestrictions::default(),
+                    event: None,
+                },
+                max_leg_price: None,
             },
             Destination {
-                iata: "SEA".to_string(),
+                iata: "YYC".parse().unwrap(),
+                dates: DateConstraints {
+                    arrival_window: Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 3).unwrap())),
+                    departure_window: None,
+                    date_restrictions: DateRestrictions::default(),
+                    event: None,
+                },
+                max_leg_price: None,
+            },
+            Destination {
+                iata: "SEA".parse().unwrap(),
                 dates: DateConstraints { 
-                    date_range: Some(DateRange(
-                    SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 6).unwrap()),
-                    SingleDateRange::None)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
+                    arrival_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 6).unwrap()),
+                    departure_window: None,
+                    date_restrictions: DateRestrictions::default(),
+                    event: None,
+                },
+                max_leg_price: None,
             },
             Destination {
-                iata: "YYZ".to_string(),
+                iata: "YYZ".parse().unwrap(),
                 dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 2, 4).unwrap()),
-                        SingleDateRange::None)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
+                    arrival_window: Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 4).unwrap())),
+                    departure_window: None,
+                    date_restrictions: DateRestrictions::default(),
+                    event: None,
+                },
+                max_leg_price: None,
             },
         ];
 
-        let mut main_queue = BinaryHeap::<Rc<FlightNode>>::new();
+        let problem = RouterProblem {
+            dest_list: test_dest_vec,
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            strategy: SearchStrategy::Dijkstra,
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        };
+        let mut main_queue = BinaryHeap::<QueuedNode>::new();
 
         router
-            .expand_node(node_to_expand, test_dest_vec, &mut main_queue)
-            .await;
+            .expand_node(node_to_expand, vec![1, 2, 3], &problem, &mut Dijkstra, &mut main_queue)
+            .await
+            .unwrap();
 
-        let heap_vec = main_queue.into_vec();
+        let heap_vec: Vec<&FlightNode> = main_queue.into_vec().iter().map(|q| router.graph.node(q.id)).collect();
 
         assert!(heap_vec
             .iter()
             .find(|e| {
                 e.flight
                     == Flight {
-                        src: "YVR".to_string(),
-                        dest: "YYC".to_string(),
+                        src: "YVR".parse().unwrap(),
+                        dest: "YYC".parse().unwrap(),
                         date: Date::from_ymd_opt(2023, 2, 3).unwrap(),
                     }
             })
@@ -393,8 +1557,8 @@ mod router_tests {
             .find(|e| {
                 e.flight
                     == Flight {
-                        src: "YVR".to_string(),
-                        dest: "SEA".to_string(),
+                        src: "YVR".parse().unwrap(),
+                        dest: "SEA".parse().unwrap(),
                         date: Date::from_ymd_opt(2023, 2, 2).unwrap(),
                     }
             })
@@ -404,8 +1568,8 @@ mod router_tests {
             .find(|e| {
                 e.flight
                     == Flight {
-                        src: "YVR".to_string(),
-                        dest: "SEA".to_string(),
+                        src: "YVR".parse().unwrap(),
+                        dest: "SEA".parse().unwrap(),
                         date: Date::from_ymd_opt(2023, 2, 3).unwrap(),
                     }
             })
@@ -415,8 +1579,8 @@ mod router_tests {
             .find(|e| {
                 e.flight
                     == Flight {
-                        src: "YVR".to_string(),
-                        dest: "SEA".to_string(),
+                        src: "YVR".parse().unwrap(),
+                        dest: "SEA".parse().unwrap(),
                         date: Date::from_ymd_opt(2023, 2, 4).unwrap(),
                     }
             })
@@ -426,8 +1590,8 @@ mod router_tests {
             .find(|e| {
                 e.flight
                     == Flight {
-                        src: "YVR".to_string(),
-                        dest: "YYZ".to_string(),
+                        src: "YVR".parse().unwrap(),
+                        dest: "YYZ".parse().unwrap(),
                         date: Date::from_ymd_opt(2023, 2, 4).unwrap(),
                     }
             })
@@ -438,177 +1602,918 @@ mod router_tests {
 
     #[test]
     fn test_dest_list_fill() {
+        // YYZ, then YVR, YYC and SEA in any order, ending at FEA
+        let dest_count = 5;
+
         let mut router = Router::<TestPriceApiQuery>::new();
-        let init_dest_list = vec![
-            Destination {
-                // Source
-                iata: "YYZ".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::None,
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 1).unwrap(), Date::from_ymd_opt(2023, 2, 3).unwrap()))),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
-            },
-            Destination {
-                iata: "YVR".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap()),
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()))),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
-            },
-            Destination {
-                iata: "YYC".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 3).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()))),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
-            },
-            Destination {
-                iata: "SEA".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 5).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
-                        SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 6).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()))),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
-            },
-            Destination {
-                iata: "FEA".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(
-                        SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 2, 8).unwrap()),
-                        SingleDateRange::None)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
-                }
+        let seed = router.graph.push(FlightNode {
+            flight: Flight {
+                src: "YYZ".parse().unwrap(),
+                dest: "YYZ".parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
             },
-        ];
-
-        let curr_node = FlightNode {
+            mode: TransportMode::Flight,
+            arrival: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+            back_price: 0.0,
+            price: 0.0,
+            stay_price: 0.0,
+            bag_fee: 0.0,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            quoted_at: None,
+            prev: None,
+            origin: "YYZ".parse().unwrap(),
+            dest: 0,
+            visited: 1,
+            flight_days_in_a_row: 0,
+            state: NodeState::Pruned,
+        });
+        let curr_node = router.graph.push(FlightNode {
             flight: Flight {
-                src: "YYZ".to_string(),
-                dest: "YVR".to_string(),
+                src: "YYZ".parse().unwrap(),
+                dest: "YVR".parse().unwrap(),
                 date: Date::from_ymd_opt(2023, 2, 2).unwrap(),
             },
-            back_price: Some(100.0),
-            price: Some(100.0),
-            prev: Some(Rc::new(FlightNode {
+            mode: TransportMode::Flight,
+            arrival: Date::from_ymd_opt(2023, 2, 2).unwrap(),
+            back_price: 100.0,
+            price: 100.0,
+            stay_price: 0.0,
+            bag_fee: 0.0,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            quoted_at: None,
+            prev: Some(seed),
+            origin: "YYZ".parse().unwrap(),
+            dest: 1,
+            visited: 0b11,
+            flight_days_in_a_row: 1,
+            state: NodeState::Expanded { order: 1 },
+        });
+
+        // YVR has been visited, the final destination waits until the rest have been
+        assert_eq!(router.fill_dest_list(curr_node, dest_count), [2, 3]);
+
+        let mut last = curr_node;
+        for (dest, iata) in [(2, "YYC"), (3, "SEA")] {
+            last = router.graph.push(FlightNode {
                 flight: Flight {
-                    src: "".to_string(),
-                    dest: "YYZ".to_string(),
-                    date: Date::from_ymd_opt(2023, 2, 1).unwrap(),
+                    src: router.graph.node(last).flight.dest,
+                    dest: iata.parse().unwrap(),
+                    date: Date::from_ymd_opt(2023, 2, 5).unwrap(),
                 },
-                back_price: Some(200.0),
-                price: Some(100.0),
-                prev: None,
-                dest_ref: Destination {
-                    iata: "YYZ".to_string(),
+                mode: TransportMode::Flight,
+                arrival: Date::from_ymd_opt(2023, 2, 5).unwrap(),
+                back_price: 100.0,
+                price: 100.0,
+                stay_price: 0.0,
+                bag_fee: 0.0,
+                source: PriceSource::Quoted,
+                std_dev: 0.0,
+                quoted_at: None,
+                prev: Some(last),
+                origin: "YYZ".parse().unwrap(),
+                dest,
+                visited: router.graph.node(last).visited | 1 << dest,
+                flight_days_in_a_row: 1,
+                state: NodeState::Pruned,
+            });
+        }
+        assert_eq!(router.fill_dest_list(last, dest_count), [4]);
+    }
+
+    /// YYZ to YVR, YYC and SEA in any order and back
+    fn test_problem() -> RouterProblem {
+        RouterProblem {
+            dest_list: vec![
+                Destination {
+                    // Source
+                    iata: "YYZ".parse().unwrap(),
                     dates: DateConstraints {
-                        date_range: Some(DateRange(SingleDateRange::None, SingleDateRange::None)),
-                        date_restrictions: Rc::new(DateRestrictions::default())
-                    }
+                        arrival_window: None,
+                        departure_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 1).unwrap(), Date::from_ymd_opt(2023, 2, 3).unwrap()),
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
                 },
-            })),
-            dest_ref: Destination {
-                iata: "YVR".to_string(),
-                dates: DateConstraints {
-                    date_range: Some(DateRange(SingleDateRange::None, SingleDateRange::None)),
-                    date_restrictions: Rc::new(DateRestrictions::default())
+                Destination {
+                    iata: "YVR".parse().unwrap(),
+                    dates: DateConstraints {
+                        arrival_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap()),
+                        departure_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()),
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
+                },
+                Destination {
+                    iata: "YYC".parse().unwrap(),
+                    dates: DateConstraints {
+                        arrival_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 3).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
+                        departure_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()),
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
+                },
+                Destination {
+                    iata: "SEA".parse().unwrap(),
+                    dates: DateConstraints {
+                        arrival_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 5).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
+                        departure_window: DateWindow::new(Date::from_ymd_opt(2023, 2, 6).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()),
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
+                },
+                Destination {
+                    iata: "YYZ".parse().unwrap(),
+                    dates: DateConstraints {
+                        arrival_window: Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 8).unwrap())),
+                        departure_window: None,
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
+                },
+            ],
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            strategy: SearchStrategy::Dijkstra,
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_graph_search() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+
+        let problem = test_problem();
+
+        router.set_record_graph(true);
+        let result = router.calc(problem.clone()).await.unwrap();
+        assert_eq!(result.result.last().unwrap().flight.dest, problem.dest_list.last().unwrap().iata);
+        assert_eq!(result.result.len(), problem.dest_list.len() - 1);
+        assert!(result.alternatives.is_empty());
+        println!("Result: {}", result);
+        println!("Total price: ${}", result.total_price());
+        println!("Stats: {:?}", router.stats);
+        let stats = router.stats();
+        assert!(stats.nodes_expanded > 0 && stats.peak_queue_size > 0);
+        let expanded = router.explored_graph().nodes.iter().filter(|n| matches!(n.state, NodeState::Expanded { .. }));
+        assert_eq!(stats.nodes_expanded as usize, expanded.count());
+        // No cache in front of the test provider
+        assert_eq!(stats.cache_hit_rate, None);
+
+        // The explored graph serializes for debugging, starting from the seed
+        let graph = serde_json::to_value(router.explored_graph()).unwrap();
+        assert_eq!(graph["nodes"].as_array().unwrap().len(), router.explored_graph().len());
+        assert_eq!(graph["nodes"][0]["prev"], Value::Null);
+
+        // And as DOT, with an edge into every node but the seed
+        let dot = router.explored_graph().to_dot();
+        assert!(dot.starts_with("digraph explored {"));
+        assert_eq!(dot.matches(" -> n").count(), router.explored_graph().len() - 1);
+        assert_eq!(dot.matches("best route").count(), 1);
+
+        // Runners up are complete routes, no cheaper than the best and in price order
+        let mut router = Router::<TestPriceApiQuery>::new();
+        router.set_candidates(3);
+        let solution: RouteSolution = router.calc(problem.clone()).await.unwrap().into();
+        assert!(router.explored_graph().is_empty());
+        assert_eq!(solution.total_price, result.total_price());
+        assert_eq!(solution.alternatives.len(), 2);
+        let mut last_price = solution.total_price;
+        for alt in &solution.alternatives {
+            assert_eq!(alt.flights.len(), problem.dest_list.len() - 1);
+            assert!(alt.total_price >= last_price);
+            last_price = alt.total_price;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strategies() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let best = router.calc(test_problem()).await.unwrap();
+        let dijkstra_calls = router.api_calls();
+
+        // DP is exact too, it just skips routes it knows can't be cheaper
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let dp = router.calc(RouterProblem { strategy: SearchStrategy::Dp, ..test_problem() }).await.unwrap();
+        assert_eq!(dp.total_price(), best.total_price());
+        assert!(router.api_calls() <= dijkstra_calls);
+
+        // The rest still find complete routes, if not always the cheapest
+        for strategy in [SearchStrategy::AStar, SearchStrategy::Beam { width: 1 }] {
+            let mut router = Router::<TestPriceApiQuery>::new();
+            let result = router.calc(RouterProblem { strategy, ..test_problem() }).await.unwrap();
+            assert_eq!(result.result.len(), test_problem().dest_list.len() - 1);
+            assert!(result.total_price() >= best.total_price());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_limits() {
+        // Left alone, the cheapest route flies on consecutive days, so the limit below has something to rule out
+        let best = Router::<TestPriceApiQuery>::new().calc(test_problem()).await.unwrap();
+        let consecutive = |route: &RouterResult| {
+            route.result.windows(2).any(|pair| pair[1].flight.date - pair[0].flight.date == chrono::Duration::days(1))
+        };
+        assert!(consecutive(&best));
+
+        let rested = RouteLimits {
+            max_flight_days_in_a_row: Some(1),
+            ..RouteLimits::default()
+        };
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let result = router.calc(RouterProblem { limits: rested, ..test_problem() }).await.unwrap();
+        // Flights on the same day are one flying day, only the day after is out
+        assert!(!consecutive(&result));
+
+        // The trip takes four flights, so capping it at three leaves nothing
+        let capped = RouteLimits {
+            max_flights: Some(3),
+            ..RouteLimits::default()
+        };
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let result = router.calc(RouterProblem { limits: capped, ..test_problem() }).await;
+        assert!(matches!(result, Err(RouterError::NoRoute)));
+    }
+
+    #[tokio::test]
+    async fn test_max_leg_price() {
+        // A stop's ceiling rules out the best route's leg into Calgary, but not the other ways in
+        let best = Router::<TestPriceApiQuery>::new().calc(test_problem()).await.unwrap();
+        let into_yyc = |route: &RouterResult| route.result.iter().find(|f| f.flight.dest == "YYC").unwrap().price;
+        let mut problem = test_problem();
+        problem.dest_list[2].max_leg_price = Some(into_yyc(&best) - 1.0);
+        let result = Router::<TestPriceApiQuery>::new().calc(problem).await.unwrap();
+        assert!(into_yyc(&result) < into_yyc(&best));
+        assert!(result.total_price() >= best.total_price());
+
+        // Nothing is free, so a zero ceiling on every leg leaves nothing
+        let free = RouteLimits {
+            max_leg_price: Some(0.0),
+            ..RouteLimits::default()
+        };
+        let result = Router::<TestPriceApiQuery>::new().calc(RouterProblem { limits: free, ..test_problem() }).await;
+        assert!(matches!(result, Err(RouterError::NoRoute)));
+    }
+
+    #[tokio::test]
+    async fn test_direct_only() {
+        // None of the test data's fares are direct, every one of the valley's is
+        let direct = RouteLimits {
+            direct_only: true,
+            ..RouteLimits::default()
+        };
+        let result = Router::<TestPriceApiQuery>::new().calc(RouterProblem { limits: direct, ..test_problem() }).await;
+        assert!(matches!(result, Err(RouterError::NoRoute)));
+        let result = Router::with_api(valley_api()).calc(RouterProblem { limits: direct, ..valley_problem() }).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_overnight_arrival() {
+        // Put Vancouver on Tokyo time so the evening flight in from Toronto lands the next day
+        let airports = AirportDirectory::from_csv(
+            "iata,name,city,country,lat,lon,tz\n\
+             YYZ,Toronto,Toronto,CA,43.68,-79.62,America/Toronto\n\
+             YVR,Vancouver,Vancouver,CA,49.19,-123.18,Asia/Tokyo\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut router = Router::<TestPriceApiQuery>::new();
+        router.set_arrival_clock(Arc::new(ArrivalClock::new(&airports)));
+        router.set_record_graph(true);
+        router.calc(test_problem()).await.unwrap();
+
+        // Nothing leaves Vancouver before the flight in has landed, a day after it left Toronto
+        let graph = router.explored_graph();
+        let mut checked = 0;
+        for node in &graph.nodes {
+            let Some(prev) = node.prev.map(|prev| graph.node(prev)) else {
+                continue;
+            };
+            if prev.flight.src == "YYZ" && prev.flight.dest == "YVR" {
+                assert_eq!(prev.arrival, prev.flight.date + chrono::Duration::days(1));
+                assert!(node.flight.date >= prev.arrival);
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+
+    #[tokio::test]
+    async fn test_stay_costs() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let flights_only = router.calc(test_problem()).await.unwrap();
+
+        // Nights in Vancouver cost more than any flight, so the route spends as few there as it can
+        let rates = NightlyRates::from_csv("iata,nightly_rate\nYVR,1000\n".as_bytes()).unwrap();
+        router.set_stay_costs(Arc::new(rates));
+        let result = router.calc(test_problem()).await.unwrap();
+
+        let into_yvr = result.result.iter().position(|f| f.flight.dest == "YVR").unwrap();
+        let out_of_yvr = &result.result[into_yvr + 1];
+        let nights = (out_of_yvr.flight.date - result.result[into_yvr].flight.date).num_days();
+        assert_eq!(out_of_yvr.stay_price, 1000.0 * nights as f32);
+        assert!(result.result.iter().all(|f| f.flight.src == "YVR" || f.stay_price == 0.0));
+
+        // The stay counts towards the route's price, which can't beat flying alone
+        let flights: f32 = result.result.iter().map(|f| f.price).sum();
+        assert_eq!(result.total_price(), flights + out_of_yvr.stay_price);
+        assert!(result.total_price() >= flights_only.total_price());
+
+        // The breakdown puts the stay against Vancouver and adds up to the whole trip
+        let breakdown = result.breakdown();
+        let yvr = &breakdown[into_yvr];
+        assert_eq!((yvr.city.to_string().as_str(), yvr.nights as i64), ("YVR", nights));
+        assert_eq!(yvr.lodging, out_of_yvr.stay_price);
+        assert_eq!(breakdown.last().unwrap().running_total, result.total_price());
+        assert!((breakdown.iter().map(|d| d.share).sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[tokio::test]
+    async fn test_checked_bag() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        router.set_bag_fees(Arc::new(BagFees::default()));
+        let carry_on = router.calc(test_problem()).await.unwrap();
+        assert!(carry_on.result.iter().all(|f| f.bag_fee == 0.0));
+
+        // Every flight's fare goes up by the same estimate, so the route is the same one with the bag on top
+        let result = router.calc(RouterProblem { checked_bag: true, ..test_problem() }).await.unwrap();
+        let fee = BagFees::default().fee(&TestPriceApiQuery::new().get_price(carry_on.result[0].flight.clone()).await.unwrap());
+        assert!(result.result.iter().all(|f| f.bag_fee == fee));
+        let fees = fee * result.result.len() as f32;
+        assert!((result.total_price() - carry_on.total_price() - fees).abs() < 1e-3);
+    }
+
+    #[tokio::test]
+    async fn test_ground_legs() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let flown = router.calc(test_problem()).await.unwrap();
+
+        // A cheap bus between Vancouver and Seattle, while Calgary is too far for one to be offered
+        let routes = GroundRoutes::from_csv("src,dest,price\nYVR,SEA,1\nYYC,SEA,1\n".as_bytes()).unwrap();
+        let legs = GroundLegs::new(routes, &AirportDirectory::embedded().unwrap(), 500.0);
+        router.set_ground_legs(Arc::new(legs));
+        let result = router.calc(test_problem()).await.unwrap();
+
+        let overland: Vec<_> = result.result.iter().filter(|f| f.mode == TransportMode::Ground).collect();
+        assert_eq!(overland.len(), 1);
+        assert_eq!(overland[0].flight.src, "YVR");
+        assert_eq!(overland[0].flight.dest, "SEA");
+        assert_eq!(overland[0].price, 1.0);
+        assert!(result.total_price() < flown.total_price());
+    }
+
+    #[tokio::test]
+    async fn test_event_dates() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let problem = || {
+            let mut problem = test_problem();
+            // In Calgary from the 3rd to the 4th, which only a flight straight there from Toronto makes
+            problem.dest_list[2].dates.event = Some(EventDates { first_day: day(3), last_day: day(4) });
+            problem
+        };
+
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let result = router.calc(problem()).await.unwrap();
+        let into_yyc = result.result.iter().position(|f| f.flight.dest == "YYC").unwrap();
+        assert_eq!(into_yyc, 0);
+        assert_eq!(result.result[0].flight.date, day(3));
+        assert!(result.result[1].flight.date >= day(4));
+
+        // Landing in Calgary on Tokyo time, the flight the day of the event gets in too late
+        let airports = AirportDirectory::from_csv(
+            "iata,name,city,country,lat,lon,tz\n\
+             YYZ,Toronto,Toronto,CA,43.68,-79.62,America/Toronto\n\
+             YYC,Calgary,Calgary,CA,51.13,-114.01,Asia/Tokyo\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        router.set_arrival_clock(Arc::new(ArrivalClock::new(&airports)));
+        assert!(matches!(router.calc(problem()).await, Err(RouterError::NoRoute)));
+    }
+
+    #[tokio::test]
+    async fn test_alternate_origins() {
+        // Calgary is somewhere to start from rather than a stop
+        let problem = |origin: &str, alternates: &[&str]| {
+            let mut problem = test_problem();
+            problem.dest_list.remove(2);
+            problem.dest_list[0].iata = origin.parse().unwrap();
+            problem.alternate_origins = alternates.iter().map(|a| a.parse().unwrap()).collect();
+            problem
+        };
+
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let from_yyz = router.calc(problem("YYZ", &[])).await.unwrap().total_price();
+        let from_yyc = router.calc(problem("YYC", &[])).await.unwrap().total_price();
+        assert_ne!(from_yyz, from_yyc);
+
+        // Listing the start as an alternate too is harmless
+        let either = RouteSolution::from(router.calc(problem("YYZ", &["YYC", "YYZ"])).await.unwrap());
+        assert_eq!(either.total_price, from_yyz.min(from_yyc));
+        assert_eq!(either.origin.unwrap(), if from_yyc < from_yyz { "YYC" } else { "YYZ" });
+        assert_eq!(either.origin, either.flights.first().map(|f| f.flight.src));
+
+        // Penalizing the cheaper origin by more than it saves moves the trip to the other, at its own price
+        let (cheaper, dearer) = if from_yyc < from_yyz { ("YYC", from_yyz) } else { ("YYZ", from_yyc) };
+        let mut penalized = problem("YYZ", &["YYC"]);
+        penalized.origin_penalties = BTreeMap::from([(cheaper.parse().unwrap(), (from_yyz - from_yyc).abs() + 1.0)]);
+        let moved = RouteSolution::from(router.calc(penalized.clone()).await.unwrap());
+        assert_ne!(moved.origin.unwrap(), cheaper);
+        assert_eq!(moved.total_price, dearer);
+
+        // One smaller than the saving leaves it where it was, and out of the price
+        penalized.origin_penalties.insert(cheaper.parse().unwrap(), (from_yyz - from_yyc).abs() - 1.0);
+        let kept = RouteSolution::from(router.calc(penalized).await.unwrap());
+        assert_eq!(kept.origin.unwrap(), cheaper);
+        assert_eq!(kept.total_price, from_yyz.min(from_yyc));
+    }
+
+    #[tokio::test]
+    async fn test_dp_alternate_origins() {
+        // Every origin's seed stands in for the first destination, DP still expands each of them
+        for (origin, alternate) in [("YYZ", "YYC"), ("YYC", "YYZ")] {
+            let mut problem = test_problem();
+            problem.dest_list.remove(2);
+            problem.dest_list[0].iata = origin.parse().unwrap();
+            problem.alternate_origins = vec![alternate.parse().unwrap()];
+
+            let best = Router::<TestPriceApiQuery>::new().calc(problem.clone()).await.unwrap();
+            let dp = RouterProblem {
+                strategy: SearchStrategy::Dp,
+                ..problem
+            };
+            let result = Router::<TestPriceApiQuery>::new().calc(dp).await.unwrap();
+            assert_eq!(result.total_price(), best.total_price());
+            assert_eq!(result.result[0].flight.src, best.result[0].flight.src);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unpriced_legs_pruned() {
+        // Nothing in the test data flies from Heathrow, its legs are ruled out rather than failing the solve
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let cheapest = router.calc(test_problem()).await.unwrap().total_price();
+        let problem = RouterProblem {
+            alternate_origins: vec!["LHR".parse().unwrap()],
+            ..test_problem()
+        };
+        let solution = RouteSolution::from(router.calc(problem).await.unwrap());
+        assert_eq!(solution.total_price, cheapest);
+        assert_eq!(solution.origin.unwrap(), "YYZ");
+    }
+
+    /// The test data, failing with `error` for anything on the 2nd
+    fn failing_on_2nd(error: fn() -> QueryError) -> FakeApi {
+        FakeApi::new().with_failure(move |flight| (flight.date == Date::from_ymd_opt(2023, 2, 2).unwrap()).then(error))
+    }
+
+    #[tokio::test]
+    async fn test_price_estimates() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let leg = |d| Flight {
+            src: "YYZ".parse().unwrap(),
+            dest: "YVR".parse().unwrap(),
+            date: day(d),
+        };
+        let estimates = PriceEstimates {
+            max_gap_days: 2,
+            uncertainty: 0.0,
+        };
+
+        // Nothing flies on the 2nd
+        let mut router = Router::with_api(failing_on_2nd(|| QueryError::NoFare));
+        let quoted = |d| async move { TestPriceApiQuery::new().get_price(leg(d)).await.unwrap().min_price };
+        let (first, third) = (quoted(1).await, quoted(3).await);
+        assert_eq!(router.estimate(&leg(2), estimates).await.unwrap(), Some((first + third) / 2.0));
+
+        // Leaving on the 2nd is the only way to go, so the solve needs the estimate
+        let mut problem = test_problem();
+        problem.dest_list[0].dates.departure_window = Some(DateWindow::day(day(2)));
+        assert!(matches!(router.calc(problem.clone()).await, Err(RouterError::NoRoute)));
+
+        router.set_price_estimates(estimates);
+        let result = router.calc(problem).await.unwrap();
+        assert_eq!(result.result[0].source, PriceSource::NearbyDays);
+        assert_eq!(result.result[0].flight.date, day(2));
+        assert!(result.result[1..].iter().all(|f| f.source.is_quoted()));
+    }
+
+    #[tokio::test]
+    async fn test_provider_outage_degrades() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let mut problem = test_problem();
+        problem.dest_list[0].dates.departure_window = Some(DateWindow::day(day(2)));
+
+        // Nothing to stand in for the leg, the provider's failure is what the solve fails with
+        let mut router = Router::with_api(failing_on_2nd(|| QueryError::BadResponse(503)));
+        assert!(matches!(router.calc(problem.clone()).await, Err(RouterError::Query(QueryError::BadResponse(503)))));
+        assert!(router.stats().degraded_legs > 0);
+
+        router.set_price_estimates(PriceEstimates {
+            max_gap_days: 2,
+            uncertainty: 0.0,
+        });
+        let result = router.calc(problem).await.unwrap();
+        assert_eq!(result.result[0].flight.date, day(2));
+        assert_eq!(result.result[0].source, PriceSource::NearbyDays);
+        assert!(result.result[1..].iter().all(|f| f.source.is_quoted()));
+    }
+
+    #[tokio::test]
+    async fn test_pessimistic_objective() {
+        // The fares on the 2nd were quoted a month ago by an unreliable provider
+        let shaky_day = Date::from_ymd_opt(2023, 2, 2).unwrap();
+        let shaky = FakeApi::new().with_fares(move |flight, quote| {
+            let quote = quote?;
+            if flight.date != shaky_day {
+                return Some(quote);
+            }
+            Some(Quote {
+                quoted_at: Some(chrono::Utc::now().naive_utc() - chrono::Duration::days(30)),
+                spread: 0.2,
+                currency: None,
+                ..quote
+            })
+        });
+        let mut router = Router::with_api(shaky);
+
+        // The cheapest route leaves on the 2nd, but those fares are likely gone
+        let expected = router.calc(test_problem()).await.unwrap();
+        assert_eq!(expected.result[0].flight.date, shaky_day);
+        assert!(expected.result[0].std_dev > 0.0);
+
+        let problem = RouterProblem {
+            objective: Objective::Pessimistic { sigmas: 2.0 },
+            ..test_problem()
+        };
+        let pessimistic = router.calc(problem).await.unwrap();
+        assert!(pessimistic.result.iter().all(|f| f.flight.date != shaky_day && f.std_dev == 0.0));
+        assert!(pessimistic.total_price() >= expected.total_price());
+    }
+
+    /// Priors from every fare in the test data, as if they were all on record from earlier solves
+    async fn test_priors() -> Arc<SeasonalPriors> {
+        let mut api = TestPriceApiQuery::new();
+        let airports = ["YYZ", "YVR", "YYC", "SEA"];
+        let mut quotes = Vec::new();
+        for src in airports {
+            for dest in airports {
+                for day in 1..=8 {
+                    let flight = Flight {
+                        src: src.parse().unwrap(),
+                        dest: dest.parse().unwrap(),
+                        date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+                    };
+                    if let Ok(quote) = api.get_price(flight.clone()).await {
+                        quotes.push((flight, quote));
+                    }
                 }
-            },
+            }
+        }
+        let history = PriceHistory::default();
+        history.record(CabinClass::Economy, &quotes);
+        history.priors(CabinClass::Economy)
+    }
+
+    #[tokio::test]
+    async fn test_price_priors() {
+        let priors = test_priors().await;
+        let problem = RouterProblem {
+            strategy: SearchStrategy::AStar,
+            ..test_problem()
         };
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let cold = router.calc(problem.clone()).await.unwrap();
+        let cold_calls = router.api_calls();
+
+        // Legs are only priced once they look worth it, which is fewer of them
+        let mut router = Router::<TestPriceApiQuery>::new();
+        router.set_price_priors(priors);
+        router.set_record_graph(true);
+        let warm = router.calc(problem).await.unwrap();
+        assert!(router.api_calls() < cold_calls);
+        assert_eq!(warm.result.len(), cold.result.len());
+        assert!(router.explored_graph().to_dot().contains("deferred"));
+    }
+
+    #[tokio::test]
+    async fn test_quota_throttle() {
+        let throttle = QuotaThrottle {
+            sample_below: Some(10_000),
+            narrow_below: None,
+            sample_every: 2,
+            beam_width: 1,
+        };
+        let off_grid = |api: &FakeApi| api.asked.iter().filter(|f| !throttle.sampling().is_sampled(f.date)).count();
+        let solve = |left, throttle| async move {
+            let mut router = Router::with_api(FakeApi::new().with_budget(left));
+            router.set_quota_throttle(throttle);
+            let res = router.calc(test_problem()).await;
+            (res, router)
+        };
+
+        // Users without a limit are never throttled
+        let (full, unlimited) = solve(None, throttle).await;
+        full.unwrap();
+        assert_eq!(unlimited.stats().throttle, ThrottleLevel::Full);
+
+        // Fewer days priced in the windows, the rest estimated from them
+        let (sampled, router) = solve(Some(1000), throttle).await;
+        sampled.unwrap();
+        assert_eq!(router.stats().throttle, ThrottleLevel::Sampled);
+        assert!(off_grid(router.api()) < off_grid(unlimited.api()));
+        // Unless the windows are no wider than the gap between sampled days, then each is priced in full
+        let weekly = QuotaThrottle {
+            sample_every: 7,
+            ..throttle
+        };
+        let (sampled, router) = solve(Some(1000), weekly).await;
+        sampled.unwrap();
+        assert_eq!(router.stats().throttle, ThrottleLevel::Sampled);
+        assert_eq!(router.api().asked, unlimited.api().asked);
+
+        let narrow = QuotaThrottle {
+            narrow_below: Some(10_000),
+            beam_width: 3,
+            ..throttle
+        };
+        let (narrowed, router) = solve(Some(1000), narrow).await;
+        narrowed.unwrap();
+        assert_eq!(router.stats().throttle, ThrottleLevel::Narrowed);
+        assert!(router.stats().nodes_expanded < unlimited.stats().nodes_expanded);
+
+        // Running out partway fails the solve with the quota's error if nothing stands in for the fares
+        let (starved, router) = solve(Some(5), throttle).await;
+        assert!(matches!(starved, Err(RouterError::Query(QueryError::QuotaExceeded))));
+        assert_eq!(router.stats().throttle, ThrottleLevel::Exhausted);
+
+        // But carries on at what the legs usually cost with priors
+        let mut router = Router::with_api(FakeApi::new().with_budget(Some(5)));
+        router.set_price_priors(test_priors().await);
+        let result = router.calc(test_problem()).await.unwrap();
+        assert!(result.result.iter().any(|f| f.source == PriceSource::History));
+        assert!(router.stats().degraded_legs > 0);
+    }
+
+    /// Direct fares on any route, cheapest on the 15th and dearer the further from it
+    fn valley_api() -> FakeApi {
+        FakeApi::new().with_fares(|flight, _| {
+            Some(Quote {
+                min_price: 100.0 + 10.0 * (flight.date.day() as f32 - 15.0).abs(),
+                direct: true,
+                arrives: None,
+                quoted_at: None,
+                spread: 0.0,
+                currency: None,
+                carrier: None,
+                bag_fee: None,
+                fallback: false,
+            })
+        })
+    }
 
-        let final_dest = Destination {
-            iata: "YYZ".to_string(),
+    /// There and back any day of March
+    fn valley_problem() -> RouterProblem {
+        let month = DateWindow::new(Date::from_ymd_opt(2023, 3, 1).unwrap(), Date::from_ymd_opt(2023, 3, 31).unwrap());
+        let stop = |iata: &str, arrival, departure| Destination {
+            iata: iata.parse().unwrap(),
             dates: DateConstraints {
-                date_range: Some(DateRange(SingleDateRange::None, SingleDateRange::None)),
-                date_restrictions: Rc::new(DateRestrictions::default())
-            }
+                arrival_window: arrival,
+                departure_window: departure,
+                date_restrictions: DateRestrictions::default(),
+                event: None,
+            },
+            max_leg_price: None,
         };
+        RouterProblem {
+            dest_list: vec![stop("YYZ", None, month), stop("YVR", month, month), stop("YYZ", month, None)],
+            ..test_problem()
+        }
+    }
 
-        let dest_list = router.fill_dest_list(Rc::new(curr_node), &final_dest, &init_dest_list);
+    #[tokio::test]
+    async fn test_sampled_search() {
+        let problem = valley_problem();
+        let mut full = Router::with_api(valley_api());
+        let cheapest = full.calc(problem.clone()).await.unwrap();
 
-        assert!(dest_list
-            .iter()
-            .find(|d| d.iata == "YYC".to_string())
-            .is_some());
-        assert!(dest_list
-            .iter()
-            .find(|d| d.iata == "SEA".to_string())
-            .is_some());
-        assert!(dest_list
-            .iter()
-            .find(|d| d.iata == "FEA".to_string())
-            .is_some());
+        let mut sampled = Router::with_api(valley_api());
+        let problem = RouterProblem {
+            sampling: Some(DateSampling { every: 7, refine: 2 }),
+            ..problem
+        };
+        let result = sampled.calc(problem).await.unwrap();
+        // Fewer days priced for the same route, which is priced on quotes alone
+        assert!(sampled.api().asked.len() < full.api().asked.len());
+        assert_eq!(result.total_price(), cheapest.total_price());
+        assert!(result.result.iter().all(|f| f.source.is_quoted()));
+
+        // The way out's window is narrower than the gap between sampled days, it's all priced and nothing outside it
+        let week = DateWindow::new(Date::from_ymd_opt(2023, 3, 10).unwrap(), Date::from_ymd_opt(2023, 3, 12).unwrap());
+        let mut narrow = valley_problem();
+        narrow.dest_list[0].dates.departure_window = week;
+        narrow.dest_list[1].dates.arrival_window = week;
+        let mut full = Router::with_api(valley_api());
+        full.calc(narrow.clone()).await.unwrap();
+        let mut sampled = Router::with_api(valley_api());
+        let problem = RouterProblem {
+            sampling: Some(DateSampling { every: 7, refine: 2 }),
+            ..narrow
+        };
+        sampled.calc(problem).await.unwrap();
+        let out = |router: &Router<FakeApi>| {
+            router.api().asked.iter().filter(|f| f.src == "YYZ").cloned().collect::<HashSet<_>>()
+        };
+        assert_eq!(out(&sampled), out(&full));
+        assert!(out(&sampled).iter().all(|f| week.unwrap().contains(f.date)));
+    }
 
-        assert_eq!(dest_list.len(), 3);
+    #[tokio::test]
+    async fn test_route_explanation() {
+        let mut router = Router::with_api(valley_api());
+        let result = router.calc(valley_problem()).await.unwrap();
+        let [out, back] = &result.result[..] else {
+            panic!("Expected a route there and back, got {}", result);
+        };
+        assert_eq!((out.flight.date.day(), back.flight.date.day()), (15, 15));
+
+        // Either way a day off the 15th costs 10 more, for the leg and so the route
+        for leg in [out, back] {
+            let alternative = leg.alternative.as_ref().unwrap();
+            assert_eq!(alternative.flight.src, leg.flight.src);
+            assert_eq!(alternative.flight.date.day().abs_diff(15), 1);
+            assert_eq!(alternative.price, 110.0);
+            assert_eq!(alternative.saving, Some(10.0));
+        }
+        // Runners up aren't explained
+        let mut router = Router::with_api(valley_api());
+        router.set_candidates(2);
+        let result = router.calc(valley_problem()).await.unwrap();
+        assert!(result.alternatives[0].iter().all(|f| f.alternative.is_none()));
     }
 
     #[tokio::test]
-    async fn test_graph_search() {
+    async fn test_value_of_day() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let cheapest = router.calc(test_problem()).await.unwrap();
+
+        // Every trip gets home on the 8th, a day worth more than any fare makes it leave as late as it can
+        let valued = router.calc(RouterProblem { value_of_day: 10_000.0, ..test_problem() }).await.unwrap();
+        assert_eq!(valued.result[0].flight.date, Date::from_ymd_opt(2023, 2, 3).unwrap());
+        assert!(cheapest.result[0].flight.date < valued.result[0].flight.date);
+        // The days' worth only steers the search, the price is still what the route costs
+        assert!(valued.total_price() >= cheapest.total_price());
+        assert_eq!(valued.total_price(), valued.result.iter().map(|f| f.price).sum::<f32>());
+    }
+
+    #[tokio::test]
+    async fn test_progress() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
         let mut router = Router::<TestPriceApiQuery>::new();
+        let sink = Rc::clone(&reports);
+        router.set_progress(2, move |progress| sink.borrow_mut().push(progress));
+        let result = router.calc(test_problem()).await.unwrap();
+
+        let reports = reports.borrow();
+        assert!(!reports.is_empty());
+        for (i, report) in reports.iter().enumerate() {
+            assert_eq!(report.nodes_expanded, 2 * (i + 1));
+        }
+        // Dijkstra takes nodes off the queue cheapest first, none of them dearer than the route it finds
+        for pair in reports.windows(2) {
+            assert!(pair[0].best_bound <= pair[1].best_bound);
+            assert!(pair[0].api_calls <= pair[1].api_calls);
+        }
+        assert!(reports.last().unwrap().best_bound <= result.total_price());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_solve_stops() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let token = CancellationToken::new();
+        router.set_cancel_token(token.clone());
+        token.cancel();
 
-        // Setup router problem
         let problem = RouterProblem {
             dest_list: vec![
                 Destination {
-                    // Source
-                    iata: "YYZ".to_string(),
+                    iata: "YYZ".parse().unwrap(),
                     dates: DateConstraints {
-                        date_range: Some(DateRange(
-                            SingleDateRange::None,
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 1).unwrap(), Date::from_ymd_opt(2023, 2, 3).unwrap()))),
-                        date_restrictions: Rc::new(DateRestrictions::default())
-                    }
+                        arrival_window: None,
+                        departure_window: Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 1).unwrap())),
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
                 },
                 Destination {
-                    iata: "YVR".to_string(),
+                    iata: "YVR".parse().unwrap(),
                     dates: DateConstraints {
-                        date_range: Some(DateRange(
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 2).unwrap(), Date::from_ymd_opt(2023, 2, 4).unwrap()),
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()))),
-                        date_restrictions: Rc::new(DateRestrictions::default())
-                    }
-                },
-                Destination {
-                    iata: "YYC".to_string(),
-                    dates: DateConstraints {
-                        date_range: Some(DateRange(
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 3).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 4).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()))),
-                        date_restrictions: Rc::new(DateRestrictions::default()),
-                    }
-                },
-                Destination {
-                    iata: "SEA".to_string(),
-                    dates: DateConstraints {
-                        date_range: Some(DateRange(
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 5).unwrap(), Date::from_ymd_opt(2023, 2, 7).unwrap()),
-                            SingleDateRange::DateRange(Date::from_ymd_opt(2023, 2, 6).unwrap(), Date::from_ymd_opt(2023, 2, 8).unwrap()))),
-                        date_restrictions: Rc::new(DateRestrictions::default())
-                    }
-                },
-                Destination {
-                    iata: "YYZ".to_string(),
-                    dates: DateConstraints {
-                        date_range: Some(DateRange(
-                            SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 2, 8).unwrap()),
-                            SingleDateRange::None)),
-                        date_restrictions: Rc::new(DateRestrictions::default())
-                    }
+                        arrival_window: Some(DateWindow::day(Date::from_ymd_opt(2023, 2, 1).unwrap())),
+                        departure_window: None,
+                        date_restrictions: DateRestrictions::default(),
+                        event: None,
+                    },
+                    max_leg_price: None,
                 },
             ],
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            strategy: SearchStrategy::Dijkstra,
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
         };
 
-        let result = router.calc(problem.clone()).await;
-        assert_eq!(result.result.last().unwrap().flight.dest, problem.dest_list.last().unwrap().iata);
-        assert_eq!(result.result.len(), problem.dest_list.len() - 1);
-        println!("Result: {}", result);
-        println!("Total price: ${}", result.total_price());
-        println!("Stats: {}", router.stats);
+        assert!(matches!(router.calc(problem).await, Err(RouterError::Cancelled)));
+        assert_eq!(router.stats.api_calls, 0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_too_many_destinations() {
+        let mut router = Router::<TestPriceApiQuery>::new();
+        let stop = Destination {
+            iata: "YYZ".parse().unwrap(),
+            dates: DateConstraints {
+                arrival_window: None,
+                departure_window: None,
+                date_restrictions: DateRestrictions::default(),
+                event: None,
+            },
+            max_leg_price: None,
+        };
+        let problem = RouterProblem {
+            dest_list: vec![stop; MAX_DESTINATIONS + 1],
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            strategy: SearchStrategy::Dijkstra,
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        };
+
+        assert!(matches!(router.calc(problem).await, Err(RouterError::TooManyDestinations(_))));
+        assert_eq!(router.stats.api_calls, 0);
+    }
+
+    #[test]
+    fn test_queue_pops_cheapest() {
+        let mut queue: BinaryHeap<QueuedNode> = [(300.0, 0), (100.0, 1), (200.0, 2), (100.0, 3)]
+            .into_iter()
+            .map(|(price, id)| QueuedNode::new(price, id).unwrap())
+            .collect();
+
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|q| q.id).collect();
+        assert_eq!(order, [3, 1, 2, 0]);
+        assert!(matches!(QueuedNode::new(f32::NAN, 4), Err(RouterError::InvalidPrice)));
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::test_runner::Config::with_cases(64))]
+
+        /// Whatever the stops and their dates, a solve either finds a route which starts and ends where it should,
+        /// inside the start's and end's windows, or finds none
+        #[test]
+        fn test_routes_fit_windows(dest_list in proptest::collection::vec(proptest::arbitrary::any::<Destination>(), 2..4)) {
+            let problem = RouterProblem {
+                dest_list,
+                alternate_origins: Vec::new(),
+                origin_penalties: BTreeMap::new(),
+                strategy: SearchStrategy::Dijkstra,
+                limits: RouteLimits::default(),
+                value_of_day: 0.0,
+                objective: Objective::Expected,
+                checked_bag: false,
+                sampling: None,
+            };
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            match runtime.block_on(Router::<TestPriceApiQuery>::new().calc(problem.clone())) {
+                Ok(result) => {
+                    let (start, end) = (&problem.dest_list[0], problem.dest_list.last().unwrap());
+                    let (first, last) = (&result.result[0].flight, &result.result.last().unwrap().flight);
+                    proptest::prop_assert_eq!(result.result.len(), problem.dest_list.len() - 1);
+                    proptest::prop_assert_eq!((first.src, last.dest), (start.iata, end.iata));
+                    proptest::prop_assert!(start.dates.departure_window.is_none_or(|w| w.contains(first.date)));
+                    proptest::prop_assert!(end.dates.arrival_window.is_none_or(|w| w.contains(last.date)));
+                    proptest::prop_assert!(result.result.windows(2).all(|pair| pair[0].flight.date <= pair[1].flight.date));
+                }
+                Err(e) => proptest::prop_assert!(matches!(e, RouterError::NoRoute), "{}", e),
+            }
+        }
+    }
+}
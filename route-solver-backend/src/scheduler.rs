@@ -0,0 +1,519 @@
+//! Re-pricing on a schedule, so fares are fresh and history builds up without anyone solving.
+//!
+//! Each task re-solves a saved itinerary or re-prices a watched route whenever its cron schedule comes round, usually
+//! overnight when the provider and the solver are quiet. Fares found go into the cache and price history like any
+//! solve's, and a run that finds one at or under the task's threshold sends an [Alert].
+//!
+//! The scheduler runs inside the web server when `[scheduler] enabled` is set, or on its own as `route-scheduler`. Run
+//! on its own it shares fares with the server through the SQLite or Redis cache, history stays in its own process.
+//!
+//! Re-pricing runs are [Prefetch]es, so with a `progress_dir` configured a run cut short by the quota, the provider or
+//! a restart is picked up where it stopped. That's on the task's next turn, or straight away on the next start.
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike, Utc};
+use route_solver_shared::queries::{Date, Flight, JobStatus, RouteQuery, BASE_CURRENCY};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    audit::AuditEvent,
+    config::{SchedulerConfig, TaskKind},
+    flight_api::PriceQuery,
+    jobs::JobManager,
+    log,
+    prefetch::Prefetch,
+    worker_pool::Priority,
+};
+
+/// User scheduled runs are charged to, of the default [Tenant](crate::tenants::Tenant)
+pub const SCHEDULER_USER: &str = "scheduler";
+
+/// Minutes searched for a schedule's next run before giving up on it, a little over four years for `29 2` schedules
+const MAX_SEARCH_MINUTES: i64 = 4 * 366 * 24 * 60 + 1;
+
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error("Invalid schedule {0:?}: {1}")]
+    Schedule(String, String),
+    #[error("Could not read saved itinerary {0:?}.")]
+    ItineraryIo(PathBuf, std::io::Error),
+    #[error("Saved itinerary {0:?} isn't a route query.")]
+    ItineraryParse(PathBuf, serde_json::Error),
+    #[error("Could not create re-pricing progress directory {0:?}.")]
+    ProgressDir(PathBuf, std::io::Error),
+}
+
+/// When a five field cron expression fires: minute, hour, day of the month, month and day of the week (0 or 7 is
+/// Sunday). Fields take `*`, numbers, `a-b` ranges and `/n` steps, separated by commas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// Like cron, when both the day of the month and of the week are given a day matching either will do
+    any_day: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<CronSchedule, SchedulerError> {
+        let error = |reason: &str| SchedulerError::Schedule(expr.to_string(), reason.to_string());
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(error("expected 5 fields"));
+        };
+
+        let weekdays = parse_field(weekday, 0, 7).map_err(error)?;
+        Ok(CronSchedule {
+            minutes: parse_field(minute, 0, 59).map_err(error)?,
+            hours: parse_field(hour, 0, 23).map_err(error)? as u32,
+            days: parse_field(day, 1, 31).map_err(error)? as u32,
+            months: parse_field(month, 1, 12).map_err(error)? as u16,
+            // Sunday is both 0 and 7
+            weekdays: ((weekdays | (weekdays >> 7)) & 0x7f) as u8,
+            any_day: day != "*" && weekday != "*",
+        })
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let in_month = self.days & (1 << date.day()) != 0;
+        let in_week = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        self.months & (1 << date.month()) != 0 && if self.any_day { in_month || in_week } else { in_month && in_week }
+    }
+
+    /// First minute after `after` the schedule fires, `None` if it never does, e.g. on the 31st of February
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut next = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = next + Duration::minutes(MAX_SEARCH_MINUTES);
+        while next < end {
+            if !self.day_matches(next.date()) {
+                next = next.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << next.hour()) == 0 {
+                next = next.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << next.minute()) == 0 {
+                next += Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+}
+
+/// Bits set for each value a cron field lets through, `min..=max` are valid values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, &'static str> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| "step isn't a number")?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => {
+                let number = |s: &str| s.parse::<u32>().map_err(|_| "value isn't a number");
+                match range.split_once('-') {
+                    Some((start, end)) => (number(start)?, number(end)?),
+                    // `5/15` runs from 5 to the end of the field
+                    None if step > 1 => (number(range)?, max),
+                    None => (number(range)?, number(range)?),
+                }
+            }
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err("value out of range");
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// What a task does each time it runs
+#[derive(Debug, Clone)]
+pub enum Job {
+    Resolve(Box<RouteQuery>),
+    Reprice(Prefetch),
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledTask {
+    pub name: String,
+    pub schedule: CronSchedule,
+    pub job: Job,
+    pub alert_below: Option<f32>,
+}
+
+/// A run found something at or under its task's threshold, posted to the webhook as JSON
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// See [Alert::SCHEMA_VERSION]
+    pub schema_version: u32,
+    pub task: String,
+    /// The fare found, or the trip's total for re-solved itineraries
+    pub price: f32,
+    pub currency: String,
+    /// The flight the fare is for, `None` for itineraries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flight: Option<Flight>,
+    pub found_at: NaiveDateTime,
+}
+
+impl Alert {
+    /// Version of the JSON layout webhooks are sent, bumped when a field is removed or changes meaning
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+pub struct Scheduler {
+    tasks: Vec<ScheduledTask>,
+    jobs: Arc<JobManager>,
+    alert_webhook: Option<String>,
+    /// See [SchedulerConfig::progress_dir]
+    progress_dir: Option<PathBuf>,
+}
+
+impl Scheduler {
+    /// Scheduler for the tasks in `config`, with their saved itineraries read in
+    pub fn from_config(config: &SchedulerConfig, jobs: Arc<JobManager>) -> Result<Scheduler, SchedulerError> {
+        let tasks = config
+            .tasks
+            .iter()
+            .map(|task| {
+                let job = match &task.kind {
+                    TaskKind::Resolve { itinerary } => {
+                        let contents =
+                            fs::read(itinerary).map_err(|e| SchedulerError::ItineraryIo(itinerary.clone(), e))?;
+                        let query = serde_json::from_slice(&contents)
+                            .map_err(|e| SchedulerError::ItineraryParse(itinerary.clone(), e))?;
+                        Job::Resolve(Box::new(query))
+                    }
+                    TaskKind::Reprice { src, dest, from, to, cabin } => Job::Reprice(Prefetch {
+                        src: *src,
+                        dest: *dest,
+                        from: *from,
+                        to: *to,
+                        cabin: *cabin,
+                    }),
+                };
+                Ok(ScheduledTask {
+                    name: task.name.clone(),
+                    schedule: CronSchedule::parse(&task.schedule)?,
+                    job,
+                    alert_below: task.alert_below,
+                })
+            })
+            .collect::<Result<_, SchedulerError>>()?;
+
+        if let Some(dir) = &config.progress_dir {
+            fs::create_dir_all(dir).map_err(|e| SchedulerError::ProgressDir(dir.clone(), e))?;
+        }
+
+        Ok(Scheduler {
+            tasks,
+            jobs,
+            alert_webhook: config.alert_webhook.clone(),
+            progress_dir: config.progress_dir.clone(),
+        })
+    }
+
+    pub fn tasks(&self) -> &[ScheduledTask] {
+        &self.tasks
+    }
+
+    /// Run each task whenever its schedule comes round, forever. A run that overruns the next one's time only delays
+    /// it, runs are never skipped or overlapped. Re-pricing runs cut short last time are finished first.
+    pub async fn run<Api: PriceQuery + 'static>(&self) {
+        let mut cursor = Utc::now().naive_utc();
+        for task in self.tasks.iter().filter(|task| self.progress_path(task).is_some_and(|path| path.exists())) {
+            log!("Resuming scheduled task {}", task.name);
+            for alert in self.run_task::<Api>(task).await {
+                self.send(&alert).await;
+            }
+        }
+        loop {
+            let next = self
+                .tasks
+                .iter()
+                .filter_map(|task| Some((task.schedule.next_after(cursor)?, task)))
+                .min_by_key(|(at, _)| *at);
+            let Some((at, _)) = next else {
+                log!("No scheduled task will ever run again, scheduler stopping");
+                return;
+            };
+
+            if let Ok(wait) = (at - Utc::now().naive_utc()).to_std() {
+                tokio::time::sleep(wait).await;
+            }
+            for task in self.tasks.iter().filter(|task| task.schedule.next_after(cursor) == Some(at)) {
+                for alert in self.run_task::<Api>(task).await {
+                    self.send(&alert).await;
+                }
+            }
+            cursor = at;
+        }
+    }
+
+    /// Run `task` once now, returning the alerts it raised
+    pub async fn run_task<Api: PriceQuery + 'static>(&self, task: &ScheduledTask) -> Vec<Alert> {
+        log!("Running scheduled task {}", task.name);
+        let found_at = Utc::now().naive_utc();
+        let under = |price: f32| task.alert_below.is_some_and(|below| price <= below);
+
+        match &task.job {
+            Job::Resolve(query) => {
+                let tenant = self.jobs.tenants().default_tenant();
+                // Nobody's waiting on it, so it waits its turn behind other batch work rather than being turned away
+                self.jobs.wait_for_room(Priority::Batch).await;
+                let user = Some(SCHEDULER_USER.to_string());
+                let done = match self.jobs.submit::<Api>((**query).clone(), Priority::Batch, tenant, user) {
+                    Ok((_, done)) => done,
+                    Err(e) => {
+                        log!("Scheduled task {} couldn't queue its solve: {}", task.name, e);
+                        return Vec::new();
+                    }
+                };
+                match done.await {
+                    Ok(JobStatus::Completed { solution }) if under(solution.total_price) => vec![Alert {
+                        schema_version: Alert::SCHEMA_VERSION,
+                        task: task.name.clone(),
+                        price: solution.total_price,
+                        currency: solution.currency,
+                        flight: None,
+                        found_at,
+                    }],
+                    Ok(JobStatus::Completed { .. }) => Vec::new(),
+                    status => {
+                        log!("Scheduled task {} didn't solve: {:?}", task.name, status);
+                        Vec::new()
+                    }
+                }
+            }
+            Job::Reprice(prefetch) => {
+                let tenant = self.jobs.tenants().default_tenant();
+                let mut api = self.jobs.provider::<Api>(tenant, Some(SCHEDULER_USER.to_string()));
+                let run = prefetch.run(&mut api, self.progress_dir.as_deref(), found_at.date()).await;
+                if !run.complete {
+                    log!("Scheduled task {} was cut short, its next run carries on", task.name);
+                }
+                tenant.history().record(prefetch.cabin, &run.quotes);
+
+                run.quotes
+                    .into_iter()
+                    .filter(|(_, quote)| under(quote.min_price))
+                    .map(|(flight, quote)| Alert {
+                        schema_version: Alert::SCHEMA_VERSION,
+                        task: task.name.clone(),
+                        price: quote.min_price,
+                        currency: BASE_CURRENCY.to_string(),
+                        flight: Some(flight),
+                        found_at,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// File the progress of `task`'s runs is kept in, `None` if progress isn't kept or it's not a re-pricing task
+    fn progress_path(&self, task: &ScheduledTask) -> Option<PathBuf> {
+        let Job::Reprice(prefetch) = &task.job else {
+            return None;
+        };
+        Some(prefetch.progress_path(self.progress_dir.as_ref()?))
+    }
+
+    async fn send(&self, alert: &Alert) {
+        match &alert.flight {
+            Some(flight) => log!(
+                "Alert from {}: {}-{} on {} for {} {}",
+                alert.task,
+                flight.src,
+                flight.dest,
+                flight.date,
+                alert.price,
+                alert.currency
+            ),
+            None => log!("Alert from {}: trip for {} {}", alert.task, alert.price, alert.currency),
+        }
+        let event = AuditEvent::Alert {
+            task: alert.task.clone(),
+            price: alert.price,
+            currency: alert.currency.clone(),
+            flight: alert.flight.clone(),
+        };
+        self.jobs.audit().record(None, None, event);
+        let Some(url) = &self.alert_webhook else {
+            return;
+        };
+        let sent = self
+            .jobs
+            .http_client()
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(alert).expect("Alerts always serialize"))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = sent {
+            log!("Alert webhook failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use std::{
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use chrono::{NaiveDateTime, Utc};
+    use route_solver_shared::queries::{CabinClass, Date, Flight};
+
+    use super::{CronSchedule, Job, ScheduledTask, Scheduler};
+    use crate::{
+        cache::PriceCache,
+        config::WorkerConfig,
+        flight_api::{PriceQuery, QueryError, Quote},
+        jobs::JobManager,
+        prefetch::Prefetch,
+        simulated::SimulatedApiQuery,
+        worker_pool::WorkerPool,
+    };
+
+    /// Set once [FlakyApi] has failed its date
+    static FLAKY_FAILED: AtomicBool = AtomicBool::new(false);
+
+    /// Simulated fares, except the provider fails the first time it's asked for the 10th day from today
+    struct FlakyApi(SimulatedApiQuery);
+
+    #[async_trait::async_trait]
+    impl PriceQuery for FlakyApi {
+        fn new() -> Self {
+            FlakyApi(SimulatedApiQuery::new())
+        }
+
+        async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+            let flaky_date = Utc::now().date_naive() + chrono::Duration::days(9);
+            if flight.date == flaky_date && !FLAKY_FAILED.swap(true, Ordering::SeqCst) {
+                return Err(QueryError::BadResponse(503));
+            }
+            self.0.get_price(flight).await
+        }
+    }
+
+    /// Scheduler of no tasks, with fresh jobs and cache so every fare a run prices is a provider call
+    fn scheduler(progress_dir: Option<&Path>) -> Scheduler {
+        let pool = WorkerPool::new(&WorkerConfig::default());
+        Scheduler {
+            tasks: Vec::new(),
+            jobs: Arc::new(JobManager::new(pool, None, PriceCache::in_memory())),
+            alert_webhook: None,
+            progress_dir: progress_dir.map(Path::to_path_buf),
+        }
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let nightly = CronSchedule::parse("30 3 * * *").unwrap();
+        assert_eq!(nightly.next_after(at("2023-02-01 04:00")), Some(at("2023-02-02 03:30")));
+        assert_eq!(nightly.next_after(at("2023-02-01 03:29")), Some(at("2023-02-01 03:30")));
+
+        // 2023-02-03 is a Friday, the next weekday is Monday the 6th
+        let weekdays = CronSchedule::parse("*/20 9-10 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at("2023-02-03 10:45")), Some(at("2023-02-06 09:00")));
+        assert_eq!(weekdays.next_after(at("2023-02-06 09:00")), Some(at("2023-02-06 09:20")));
+
+        // Either the 1st or a Sunday, 7 is Sunday too
+        let either = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert_eq!(either.next_after(at("2023-02-01 12:00")), Some(at("2023-02-05 00:00")));
+        assert_eq!(CronSchedule::parse("0 0 29 2 *").unwrap().next_after(at("2023-03-01 00:00")), Some(at("2024-02-29 00:00")));
+
+        assert_eq!(CronSchedule::parse("0 0 31 2 *").unwrap().next_after(at("2023-01-01 00:00")), None);
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reprice_alerts() {
+        let today = Utc::now().date_naive();
+        let task = |alert_below| ScheduledTask {
+            name: "YYZ-YUL".to_string(),
+            schedule: CronSchedule::parse("0 3 * * *").unwrap(),
+            job: Job::Reprice(Prefetch {
+                src: "YYZ".parse().unwrap(),
+                dest: "YUL".parse().unwrap(),
+                from: Date::from_ymd_opt(2000, 1, 1).unwrap(),
+                to: today + chrono::Duration::days(6),
+                cabin: CabinClass::Economy,
+            }),
+            alert_below,
+        };
+        let scheduler = scheduler(None);
+        let jobs = &scheduler.jobs;
+
+        // Only the week from today is priced, every fare under the threshold alerts
+        let alerts = scheduler.run_task::<SimulatedApiQuery>(&task(Some(f32::MAX))).await;
+        assert_eq!(alerts.len(), 7);
+        assert!(alerts.iter().all(|a| a.flight.as_ref().unwrap().date >= today && a.currency == "USD"));
+        assert_eq!(jobs.admin_stats().provider_calls, 7);
+
+        // Fares were cached along the way, so the second run didn't go to the provider
+        assert!(scheduler.run_task::<SimulatedApiQuery>(&task(None)).await.is_empty());
+        assert_eq!(jobs.admin_stats().provider_calls, 7);
+    }
+
+    #[tokio::test]
+    async fn test_reprice_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let today = Utc::now().date_naive();
+        // Three weeks of dates
+        let task = ScheduledTask {
+            name: "YYZ-YUL".to_string(),
+            schedule: CronSchedule::parse("0 3 * * *").unwrap(),
+            job: Job::Reprice(Prefetch {
+                src: "YYZ".parse().unwrap(),
+                dest: "YUL".parse().unwrap(),
+                from: today,
+                to: today + chrono::Duration::days(20),
+                cabin: CabinClass::Economy,
+            }),
+            alert_below: Some(f32::MAX),
+        };
+
+        // The provider fails a date in the second week, the other two are recorded as done
+        let flaky = scheduler(Some(dir.path()));
+        assert_eq!(flaky.run_task::<FlakyApi>(&task).await.len(), 20);
+        assert!(FLAKY_FAILED.load(Ordering::SeqCst));
+        assert_eq!(flaky.jobs.admin_stats().provider_calls, 21);
+        let path = flaky.progress_path(&task).unwrap();
+        assert!(path.exists());
+
+        // The next run only prices the second week, failed date included, then there's nothing left to resume
+        let resumed = scheduler(Some(dir.path()));
+        let alerts = resumed.run_task::<FlakyApi>(&task).await;
+        assert_eq!(alerts.len(), 7);
+        assert!(alerts.iter().any(|a| a.flight.as_ref().unwrap().date == today + chrono::Duration::days(9)));
+        assert_eq!(resumed.jobs.admin_stats().provider_calls, 7);
+        assert!(!path.exists());
+
+        // So the run after that prices all three
+        let next = scheduler(Some(dir.path()));
+        next.run_task::<FlakyApi>(&task).await;
+        assert_eq!(next.jobs.admin_stats().provider_calls, 21);
+
+        // Without a directory to keep progress in there's none to resume
+        assert!(scheduler(None).progress_path(&task).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! The web server, the API and the frontend wired up behind actix.
+
+use std::time::Duration;
+
+use actix_web::{
+    middleware::{from_fn, Compress},
+    web, App, HttpServer,
+};
+
+use crate::{
+    airports::AirportDirectory, compression, config::Config, jobs::JobManager, providers::ProviderRegistry, request_id,
+    scheduler::Scheduler, static_files, templates::TemplateLibrary, tenants, web_app,
+};
+
+/// Resolves on SIGTERM (or ctrl-c)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut term = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = term.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Serve the API and frontend until SIGTERM, pricing with the provider `registry` has under `[provider] source`.
+/// Solves still running at shutdown are drained first.
+pub async fn serve(config: Config, registry: ProviderRegistry) -> std::io::Result<()> {
+    let airports = AirportDirectory::embedded().expect("Bundled airport data is malformed");
+    let jobs = JobManager::from_config(&config, &airports, &registry);
+    let requeued = jobs.price_provider().resume(&jobs).expect("Failed to load persisted jobs");
+    if requeued > 0 {
+        println!("Re-queued {} unfinished jobs", requeued);
+    }
+    let jobs = web::Data::new(jobs);
+
+    if config.scheduler.enabled {
+        let scheduler =
+            Scheduler::from_config(&config.scheduler, jobs.clone().into_inner()).expect("Invalid scheduler config");
+        println!("Scheduling {} tasks", scheduler.tasks().len());
+        let provider = jobs.price_provider();
+        actix_web::rt::spawn(async move { provider.run_scheduler(&scheduler).await });
+    }
+
+    let admin = web::Data::new(config.admin.clone());
+    let airports = web::Data::new(airports);
+    let templates = match &config.templates.path {
+        Some(path) => TemplateLibrary::from_file(path).expect("Failed to load trip templates"),
+        None => TemplateLibrary::embedded().expect("Bundled trip templates are malformed"),
+    };
+    let templates = web::Data::new(templates);
+
+    let app_jobs = jobs.clone();
+    // Signals are handled below so solves are drained before the HTTP server goes away
+    let server = HttpServer::new(move || App::new()
+        .wrap(from_fn(compression::skip_incompressible))
+        .wrap(Compress::default())
+        .wrap(from_fn(tenants::select_tenant))
+        .wrap(from_fn(request_id::assign_request_id))
+        .app_data(app_jobs.clone())
+        .app_data(admin.clone())
+        .app_data(airports.clone())
+        .app_data(templates.clone())
+        .app_data(web::JsonConfig::default().error_handler(web_app::rejected_request))
+        .app_data(web::PathConfig::default().error_handler(web_app::rejected_request))
+        .app_data(web::QueryConfig::default().error_handler(web_app::rejected_request))
+        .service(web_app::compute)
+        .service(web_app::echo)
+        .service(web_app::submit_job)
+        .service(web_app::lint)
+        .service(web_app::feasibility)
+        .service(web_app::estimate)
+        .service(web_app::job_status)
+        .service(web_app::job_query)
+        .service(web_app::job_summary)
+        .service(web_app::job_diff)
+        .service(web_app::diff_against)
+        .service(web_app::what_if)
+        .service(web_app::compare)
+        .service(web_app::sweep)
+        .service(web_app::calendar)
+        .service(web_app::metrics)
+        .service(web_app::airports)
+        .service(web_app::currencies)
+        .service(web_app::templates)
+        .service(web_app::admin_stats)
+        .service(web_app::audit_log)
+        .service(web_app::export_snapshot)
+        .service(web_app::import_snapshot)
+        .service(web_app::export_table)
+        .service(static_files::service(static_files::DIST_DIR.as_ref())))
+        .disable_signals()
+        .bind(("127.0.0.1", 8080))?
+        .run();
+
+    let handle = server.handle();
+    let drain_timeout = Duration::from_secs(config.workers.drain_timeout_secs);
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        println!("Shutting down, draining running solves");
+        jobs.shutdown(drain_timeout).await;
+        handle.stop(true).await;
+    });
+
+    server.await
+}
@@ -0,0 +1,275 @@
+//! Simulated fares, for exercising the solver on any route without a network or a CSV of prices.
+//!
+//! A flight's fare is a base fare for the distance it covers, scaled by the day of the week it leaves, with some noise
+//! and the odd spike on top. Noise and spikes come from a hash of the model's seed and the flight, so the same seed
+//! always quotes a flight the same fare whatever order it's asked in. [Scenario]s then script changes on top, e.g. a
+//! route that stops flying.
+
+use std::sync::{Arc, OnceLock};
+
+use route_solver_shared::queries::{AirportCode, CabinClass, Date, Flight};
+
+use chrono::Datelike;
+use serde::Deserialize;
+
+use crate::{
+    airports::{great_circle_km, AirportDirectory},
+    config::SimulationConfig,
+    flight_api::{PriceQuery, QueryError, Quote},
+};
+
+/// Seed of the model [SimulatedApiQuery::new] prices with
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// What any flight costs before its distance, in US dollars
+const BASE_FARE: f32 = 60.0;
+/// What each kilometre flown adds
+const FARE_PER_KM: f32 = 0.11;
+/// Multiple of the fare flights leaving each day of the week cost, Monday first
+const WEEKDAY_FACTORS: [f32; 7] = [1.0, 0.9, 0.9, 1.0, 1.2, 1.1, 1.25];
+/// Fraction of the fare noise can add or take away
+const NOISE: f32 = 0.15;
+/// Chance any one flight's fare spikes, and the most it's multiplied by when it does
+const SPIKE_CHANCE: f32 = 0.05;
+const MAX_SPIKE: f32 = 3.0;
+/// Flights shorter than this fly direct
+const DIRECT_KM: f64 = 2500.0;
+/// Spread of simulated fares, the same as indicative prices so solves behave as they would against the provider
+const SPREAD: f32 = 0.1;
+/// What the simulated fare family with a checked bag adds to economy fares, the other cabins include a bag
+const BAG_FEE: f32 = 30.0;
+
+/// A scripted change to the fares the model would otherwise quote
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Scenario {
+    /// Nothing flies from `src` to `dest` from `from` on
+    Unavailable {
+        src: AirportCode,
+        dest: AirportCode,
+        from: Date,
+    },
+    /// Flights from `src` to `dest` leaving between `from` and `to`, inclusive, cost `factor` times as much
+    Surge {
+        src: AirportCode,
+        dest: AirportCode,
+        from: Date,
+        to: Date,
+        factor: f32,
+    },
+}
+
+impl Scenario {
+    fn route(&self) -> (AirportCode, AirportCode) {
+        match self {
+            Scenario::Unavailable { src, dest, .. } | Scenario::Surge { src, dest, .. } => (*src, *dest),
+        }
+    }
+}
+
+/// Prices flights from [SimulatedApiQuery]'s model, see the module docs
+pub struct SimulatedApiQuery {
+    airports: Arc<AirportDirectory>,
+    seed: u64,
+    scenarios: Vec<Scenario>,
+    cabin: CabinClass,
+}
+
+/// The bundled airports the model measures flights with, read once however many solves price with it
+fn airports() -> Arc<AirportDirectory> {
+    static AIRPORTS: OnceLock<Arc<AirportDirectory>> = OnceLock::new();
+    let airports =
+        AIRPORTS.get_or_init(|| Arc::new(AirportDirectory::embedded().expect("Bundled airport data is malformed")));
+    Arc::clone(airports)
+}
+
+impl SimulatedApiQuery {
+    /// Fares from the model seeded with `seed`, changed as `scenarios` script. Later scenarios apply on top of earlier.
+    pub fn with_model(seed: u64, scenarios: Vec<Scenario>) -> SimulatedApiQuery {
+        SimulatedApiQuery {
+            airports: airports(),
+            seed,
+            scenarios,
+            cabin: CabinClass::Economy,
+        }
+    }
+
+    fn distance_km(&self, flight: &Flight) -> Option<f64> {
+        let (src, dest) = (self.airports.get(flight.src.as_str())?, self.airports.get(flight.dest.as_str())?);
+        Some(great_circle_km((src.lat, src.lon), (dest.lat, dest.lon)))
+    }
+
+    /// A number in `[0, 1)` drawn for `flight`, different for each `salt`
+    fn draw(&self, flight: &Flight, salt: u64) -> f32 {
+        let mut key = self.seed ^ salt.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        for byte in flight.src.as_str().bytes().chain(flight.dest.as_str().bytes()) {
+            key = splitmix64(key ^ byte as u64);
+        }
+        key = splitmix64(key ^ flight.date.num_days_from_ce() as u64);
+        (key >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Multiple of economy each cabin costs
+fn cabin_factor(cabin: CabinClass) -> f32 {
+    match cabin {
+        CabinClass::Economy => 1.0,
+        CabinClass::PremiumEconomy => 1.6,
+        CabinClass::Business => 3.5,
+        CabinClass::First => 6.0,
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceQuery for SimulatedApiQuery {
+    fn new() -> Self {
+        SimulatedApiQuery::with_model(DEFAULT_SEED, Vec::new())
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        if flight.src == flight.dest {
+            return Err(QueryError::NoFare);
+        }
+        let km = self.distance_km(&flight).ok_or(QueryError::NoFare)?;
+
+        let weekday = WEEKDAY_FACTORS[flight.date.weekday().num_days_from_monday() as usize];
+        let noise = 1.0 + NOISE * (2.0 * self.draw(&flight, 1) - 1.0);
+        let spike = if self.draw(&flight, 2) < SPIKE_CHANCE {
+            1.0 + (MAX_SPIKE - 1.0) * self.draw(&flight, 3)
+        } else {
+            1.0
+        };
+        let mut price = (BASE_FARE + FARE_PER_KM * km as f32) * weekday * noise * spike * cabin_factor(self.cabin);
+
+        for scenario in self.scenarios.iter().filter(|s| s.route() == (flight.src, flight.dest)) {
+            match scenario {
+                Scenario::Unavailable { from, .. } if flight.date >= *from => return Err(QueryError::NoFare),
+                Scenario::Surge { from, to, factor, .. } if (*from..=*to).contains(&flight.date) => price *= factor,
+                _ => {}
+            }
+        }
+
+        Ok(Quote {
+            min_price: (price * 100.0).round() / 100.0,
+            direct: km < DIRECT_KM,
+            arrives: None,
+            quoted_at: None,
+            spread: SPREAD,
+            currency: None,
+            carrier: None,
+            bag_fee: Some(if self.cabin == CabinClass::Economy { BAG_FEE } else { 0.0 }),
+            fallback: false,
+        })
+    }
+
+    fn set_cabin_class(&mut self, cabin: CabinClass) {
+        self.cabin = cabin;
+    }
+
+    fn set_simulation(&mut self, config: &SimulationConfig) {
+        self.seed = config.seed;
+        self.scenarios = config.scenarios.clone();
+    }
+}
+
+#[cfg(test)]
+mod simulated_tests {
+    use route_solver_shared::queries::{Date, Flight};
+
+    use super::{Scenario, SimulatedApiQuery};
+    use crate::{
+        config::Config,
+        flight_api::{PriceQuery, QueryError},
+    };
+
+    fn flight(src: &str, dest: &str, day: u32) -> Flight {
+        Flight {
+            src: src.parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+        }
+    }
+
+    async fn fares(api: &mut SimulatedApiQuery, src: &str, dest: &str) -> Vec<Option<f32>> {
+        let mut fares = Vec::new();
+        for day in 1..=28 {
+            fares.push(api.get_price(flight(src, dest, day)).await.ok().map(|q| q.min_price));
+        }
+        fares
+    }
+
+    #[tokio::test]
+    async fn test_simulated_fares() {
+        // The same seed quotes the same fares, another seed others
+        let mut api = SimulatedApiQuery::with_model(7, Vec::new());
+        let short = fares(&mut api, "YYZ", "YUL").await;
+        assert_eq!(short, fares(&mut SimulatedApiQuery::with_model(7, Vec::new()), "YYZ", "YUL").await);
+        assert_ne!(short, fares(&mut SimulatedApiQuery::with_model(8, Vec::new()), "YYZ", "YUL").await);
+
+        // Crossing the country costs more than the next city over, on average
+        let long = fares(&mut api, "YYZ", "YVR").await;
+        let mean = |fares: &[Option<f32>]| fares.iter().flatten().sum::<f32>() / fares.len() as f32;
+        assert!(mean(&long) > 2.0 * mean(&short));
+
+        assert!(matches!(api.get_price(flight("YYZ", "XXX", 1)).await, Err(QueryError::NoFare)));
+    }
+
+    #[tokio::test]
+    async fn test_scenarios() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let (yyz, yvr) = ("YYZ".parse().unwrap(), "YVR".parse().unwrap());
+        let mut api = SimulatedApiQuery::with_model(
+            7,
+            vec![
+                Scenario::Unavailable { src: yyz, dest: yvr, from: day(20) },
+                Scenario::Surge { src: yyz, dest: yvr, from: day(10), to: day(11), factor: 2.0 },
+            ],
+        );
+        let scripted = fares(&mut api, "YYZ", "YVR").await;
+        let unscripted = fares(&mut SimulatedApiQuery::with_model(7, Vec::new()), "YYZ", "YVR").await;
+
+        assert_eq!(scripted[..9], unscripted[..9]);
+        assert!((scripted[9].unwrap() - 2.0 * unscripted[9].unwrap()).abs() < 0.02);
+        assert!(scripted[19..].iter().all(Option::is_none));
+        // Only the scripted direction changes
+        assert_eq!(
+            fares(&mut api, "YVR", "YYZ").await,
+            fares(&mut SimulatedApiQuery::with_model(7, Vec::new()), "YVR", "YYZ").await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_from_config() {
+        let config = Config::from_toml(
+            r#"
+            [provider.simulation]
+            seed = 7
+            [[provider.simulation.scenarios]]
+            kind = "surge"
+            src = "YYZ"
+            dest = "YVR"
+            from = "2023-02-10"
+            to = "2023-02-11"
+            factor = 2.0
+            "#,
+        )
+        .unwrap();
+        let mut api = SimulatedApiQuery::new();
+        api.set_simulation(&config.provider.simulation);
+
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let (yyz, yvr) = ("YYZ".parse().unwrap(), "YVR".parse().unwrap());
+        let surge = Scenario::Surge { src: yyz, dest: yvr, from: day(10), to: day(11), factor: 2.0 };
+        assert_eq!(
+            fares(&mut api, "YYZ", "YVR").await,
+            fares(&mut SimulatedApiQuery::with_model(7, vec![surge]), "YYZ", "YVR").await
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Portable snapshots of the price cache, for solving somewhere else against exactly the fares solved with here.
+//!
+//! A snapshot holds the cached quotes, and the flights known to have no fare, for some airports and dates. Imported
+//! into another server's cache, or loaded at startup with `[cache] snapshot_path`, solves there find those legs
+//! already priced and come to the answers they would have here without calling the provider. Imported entries expire
+//! on the importing cache's TTLs, set them to 0 to keep a snapshot for good.
+
+use std::{collections::HashSet, fs, path::Path};
+
+use chrono::NaiveDateTime;
+use route_solver_shared::queries::{AirportCode, CabinClass, Date, Flight};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::flight_api::Quote;
+
+/// Format version written into snapshots, bumped when older servers couldn't read them
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Could not read or write snapshot file.")]
+    Io(std::io::Error),
+    #[error("Snapshot is malformed.")]
+    Parse(serde_json::Error),
+    #[error("Snapshot version {0} is newer than this server understands.")]
+    UnsupportedVersion(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PriceSnapshot {
+    pub version: u32,
+    /// When the snapshot was taken, in UTC
+    pub exported_at: NaiveDateTime,
+    pub quotes: Vec<SnapshotQuote>,
+    /// Flights the provider had no fare for
+    pub no_fares: Vec<SnapshotLeg>,
+}
+
+/// What a cached quote is for, see [PointOfSale::market](crate::flight_api::PointOfSale::market)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotLeg {
+    pub flight: Flight,
+    pub cabin: CabinClass,
+    pub market: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotQuote {
+    #[serde(flatten)]
+    pub leg: SnapshotLeg,
+    pub quote: Quote,
+}
+
+impl PriceSnapshot {
+    pub fn read(path: &Path) -> Result<PriceSnapshot, SnapshotError> {
+        let snapshot: PriceSnapshot =
+            serde_json::from_slice(&fs::read(path).map_err(SnapshotError::Io)?).map_err(SnapshotError::Parse)?;
+        snapshot.check_version()?;
+        Ok(snapshot)
+    }
+
+    /// Fails for snapshots written by a newer server, which this one may misread
+    pub fn check_version(&self) -> Result<(), SnapshotError> {
+        match self.version {
+            version if version > SNAPSHOT_VERSION => Err(SnapshotError::UnsupportedVersion(version)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SnapshotError> {
+        fs::write(path, serde_json::to_vec(self).map_err(SnapshotError::Parse)?).map_err(SnapshotError::Io)
+    }
+
+    pub fn len(&self) -> usize {
+        self.quotes.len() + self.no_fares.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Which flights a snapshot takes, every cached one by default
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    /// Only flights both leaving from and landing at one of these
+    pub airports: Option<HashSet<AirportCode>>,
+    /// Only flights leaving on or after this
+    pub from: Option<Date>,
+    /// Only flights leaving on or before this
+    pub to: Option<Date>,
+}
+
+impl SnapshotFilter {
+    pub fn matches(&self, flight: &Flight) -> bool {
+        let known = |code| self.airports.as_ref().is_none_or(|airports| airports.contains(code));
+        known(&flight.src)
+            && known(&flight.dest)
+            && self.from.is_none_or(|from| flight.date >= from)
+            && self.to.is_none_or(|to| flight.date <= to)
+    }
+}
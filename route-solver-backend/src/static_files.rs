@@ -0,0 +1,120 @@
+//! Serving of the built frontend.
+//!
+//! Unknown paths fall back to `index.html` so client side routes still work on a refresh. Trunk fingerprints the
+//! wasm, JS and CSS bundles with a content hash, so those are cached for a year while `index.html` (which points at
+//! the current hashes) and anything else unhashed is always revalidated.
+
+use std::path::{Path, PathBuf};
+
+use actix_files::{Files, NamedFile};
+use actix_web::{
+    body::MessageBody,
+    dev::{fn_service, HttpServiceFactory, ServiceRequest, ServiceResponse},
+    http::header::{HeaderValue, CACHE_CONTROL},
+    middleware::{from_fn, Next},
+    web, Error, HttpResponse,
+};
+
+pub const DIST_DIR: &str = "../route-solver-frontend/dist";
+const INDEX_FILE: &str = "index.html";
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const NO_CACHE: &str = "no-cache";
+
+/// Service for the frontend in `dist`, register it after every other route as it matches everything
+pub fn service(dist: &Path) -> impl HttpServiceFactory {
+    let index = dist.join(INDEX_FILE);
+
+    let files = Files::new("/", dist)
+        .index_file(INDEX_FILE)
+        .default_handler(fn_service(move |req: ServiceRequest| spa_fallback(req, index.clone())));
+
+    web::scope("").wrap(from_fn(cache_headers)).service(files)
+}
+
+/// Serve `index.html` for paths which look like client side routes, anything else really is missing
+async fn spa_fallback(req: ServiceRequest, index: PathBuf) -> Result<ServiceResponse, Error> {
+    let (req, _) = req.into_parts();
+
+    let is_file = req.path().rsplit('/').next().is_some_and(|name| name.contains('.'));
+    if req.path().starts_with("/api/") || is_file {
+        return Ok(ServiceResponse::new(req, HttpResponse::NotFound().finish()));
+    }
+
+    let res = NamedFile::open_async(index).await?.into_response(&req);
+    Ok(ServiceResponse::new(req, res))
+}
+
+async fn cache_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let hashed = is_hashed_asset(req.path());
+    let mut res = next.call(req).await?;
+
+    if res.status().is_success() {
+        let policy = if hashed { IMMUTABLE } else { NO_CACHE };
+        res.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static(policy));
+    }
+
+    Ok(res)
+}
+
+/// Whether the file name carries trunk's `-<16 hex digit>` content hash, e.g. `route-solver-frontend-<hash>_bg.wasm`
+fn is_hashed_asset(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let stem = name.split('.').next().unwrap_or_default();
+    let stem = stem.strip_suffix("_bg").unwrap_or(stem);
+
+    stem.rsplit_once('-')
+        .is_some_and(|(_, hash)| hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod static_files_tests {
+    use std::fs;
+
+    use actix_web::{
+        http::{header::CACHE_CONTROL, StatusCode},
+        test::{call_service, init_service, read_body, TestRequest},
+        App,
+    };
+
+    use super::{is_hashed_asset, service};
+
+    #[test]
+    fn test_hashed_asset_detection() {
+        assert!(is_hashed_asset("/route-solver-frontend-4c8d8e1f2a3b5c6d_bg.wasm"));
+        assert!(is_hashed_asset("/main-0123456789abcdef.css"));
+        assert!(!is_hashed_asset("/index.html"));
+        assert!(!is_hashed_asset("/res/plane-icon.svg"));
+    }
+
+    #[actix_web::test]
+    async fn test_spa_fallback_and_cache_headers() {
+        let dist = tempfile::tempdir().unwrap();
+        fs::write(dist.path().join("index.html"), "<html></html>").unwrap();
+        fs::write(dist.path().join("main-0123456789abcdef.css"), "body {}").unwrap();
+
+        let app = init_service(App::new().service(service(dist.path()))).await;
+
+        // Client side routes get the app shell, which must never be cached
+        let res = call_service(&app, TestRequest::get().uri("/trips/3").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "no-cache");
+        assert_eq!(read_body(res).await, "<html></html>");
+
+        let res = call_service(
+            &app,
+            TestRequest::get().uri("/main-0123456789abcdef.css").to_request(),
+        )
+        .await;
+        assert!(res.headers().get(CACHE_CONTROL).unwrap().to_str().unwrap().contains("immutable"));
+
+        // Missing assets and API routes are real 404s rather than the app shell
+        let res = call_service(&app, TestRequest::get().uri("/missing.wasm").to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = call_service(&app, TestRequest::get().uri("/api/v1/nope").to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
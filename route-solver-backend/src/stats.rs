@@ -0,0 +1,179 @@
+//! Server wide statistics for the admin page.
+//!
+//! Counters are aggregated across every solve since the server started, they aren't persisted.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use route_solver_shared::queries::{AdminStats, RouteQuery, RouteSearchCount, RouterStats, WorkerPoolMetrics};
+
+/// Number of routes listed in [AdminStats::top_routes]
+const TOP_ROUTES: usize = 10;
+
+#[derive(Default)]
+pub struct ServerStats {
+    solves_completed: AtomicU64,
+    solves_failed: AtomicU64,
+    /// What the router did, summed over every finished solve
+    router: Mutex<RouterStats>,
+    provider_calls: AtomicU64,
+    provider_errors: AtomicU64,
+    routes: Mutex<HashMap<String, u64>>,
+}
+
+impl ServerStats {
+    pub fn record_search(&self, query: &RouteQuery) {
+        *self.routes.lock().unwrap().entry(route_key(query)).or_insert(0) += 1;
+    }
+
+    /// Record a finished solve and what the router did for it
+    pub fn record_solve(&self, router: &RouterStats, succeeded: bool) {
+        self.router.lock().unwrap().merge(router);
+        if succeeded {
+            self.solves_completed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.solves_failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_provider_call(&self, succeeded: bool) {
+        self.provider_calls.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.provider_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn router(&self) -> RouterStats {
+        *self.router.lock().unwrap()
+    }
+
+    pub fn snapshot(&self, jobs: WorkerPoolMetrics, cache_entries: Option<u64>) -> AdminStats {
+        let provider_calls = self.provider_calls.load(Ordering::Relaxed);
+        let provider_errors = self.provider_errors.load(Ordering::Relaxed);
+
+        let mut top_routes: Vec<RouteSearchCount> = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(route, count)| RouteSearchCount {
+                route: route.clone(),
+                count: *count,
+            })
+            .collect();
+        top_routes.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.route.cmp(&b.route)));
+        top_routes.truncate(TOP_ROUTES);
+
+        AdminStats {
+            solves_completed: self.solves_completed.load(Ordering::Relaxed),
+            solves_failed: self.solves_failed.load(Ordering::Relaxed),
+            router_api_calls: self.router().api_calls,
+            provider_calls,
+            provider_errors,
+            provider_error_rate: if provider_calls == 0 {
+                0.0
+            } else {
+                provider_errors as f64 / provider_calls as f64
+            },
+            cache_entries,
+            jobs,
+            top_routes,
+        }
+    }
+}
+
+fn route_key(query: &RouteQuery) -> String {
+    std::iter::once(&query.start_city)
+        .chain(&query.hops)
+        .chain(std::iter::once(&query.end_city))
+        .map(|d| d.iata.as_str())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::collections::BTreeMap;
+
+    use route_solver_shared::queries::{
+        CabinClass, DestinationQuery, Objective, Passengers, PrunedNodes, RouteLimits, RouteQuery, RouterStats,
+        SearchStrategy, WorkerPoolMetrics,
+    };
+
+    use super::ServerStats;
+
+    fn query(hops: &[&str]) -> RouteQuery {
+        let dest = |iata: &str| DestinationQuery {
+            iata: iata.parse().unwrap(),
+            arrival_window: None,
+            departure_window: None,
+            min_days: None,
+            max_days: None,
+            event: None,
+            max_leg_price: None,
+        };
+
+        RouteQuery {
+            start_city: dest("YYZ"),
+            end_city: dest("YYZ"),
+            hops: hops.iter().map(|h| dest(h)).collect(),
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            currency: None,
+            market: None,
+            locale: None,
+            passengers: Passengers::default(),
+            cabin_class: CabinClass::Economy,
+            strategy: SearchStrategy::default(),
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_aggregates() {
+        let stats = ServerStats::default();
+        stats.record_search(&query(&["YVR"]));
+        stats.record_search(&query(&["SEA", "YYC"]));
+        stats.record_search(&query(&["SEA", "YYC"]));
+        let solve = |api_calls, peak_queue_size, hits| {
+            let mut router = RouterStats {
+                api_calls,
+                peak_queue_size,
+                nodes_pruned: PrunedNodes { strategy: 2, ..Default::default() },
+                ..Default::default()
+            };
+            router.set_cache(hits, api_calls - hits);
+            router
+        };
+        stats.record_solve(&solve(12, 40, 9), true);
+        stats.record_solve(&solve(3, 10, 0), false);
+        stats.record_provider_call(true);
+        stats.record_provider_call(true);
+        stats.record_provider_call(true);
+        stats.record_provider_call(false);
+
+        let snapshot = stats.snapshot(WorkerPoolMetrics::default(), Some(5));
+
+        assert_eq!(snapshot.solves_completed, 1);
+        assert_eq!(snapshot.solves_failed, 1);
+        assert_eq!(snapshot.router_api_calls, 15);
+        // Counts add up across solves, the peak queue is the largest any of them saw
+        let router = stats.router();
+        assert_eq!(router.nodes_pruned.total(), 4);
+        assert_eq!(router.peak_queue_size, 40);
+        assert_eq!(router.cache_hit_rate, Some(0.6));
+        assert_eq!(snapshot.provider_error_rate, 0.25);
+        assert_eq!(snapshot.top_routes[0].route, "YYZ -> SEA -> YYC -> YYZ");
+        assert_eq!(snapshot.top_routes[0].count, 2);
+        assert_eq!(snapshot.top_routes.len(), 2);
+    }
+}
@@ -0,0 +1,96 @@
+//! Accommodation costs, so routes are compared on the flights and the nights spent between them rather than flights
+//! alone.
+//!
+//! Costs come from a [StayCostProvider]. [NightlyRates] is the one the server ships with, a CSV of per city rates the
+//! config points at. Rates are per passenger in [BASE_CURRENCY](route_solver_shared::queries::BASE_CURRENCY), like
+//! flight quotes.
+
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use route_solver_shared::queries::{nights_between, AirportCode, Date};
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StayError {
+    #[error("Could not read nightly rates file.")]
+    Io(std::io::Error),
+    #[error("Could not parse nightly rates.")]
+    Parse(csv::Error),
+    #[error("Nightly rate for {0} isn't a price.")]
+    InvalidRate(AirportCode),
+}
+
+pub trait StayCostProvider: Send + Sync {
+    /// Price of the night starting on `night` near `iata`, `None` if it isn't known
+    fn nightly_rate(&self, iata: AirportCode, night: Date) -> Option<f32>;
+
+    /// Price of staying near `iata` from landing on `arrival` to leaving on `departure`, nights with no known rate
+    /// are free
+    fn stay_cost(&self, iata: AirportCode, arrival: Date, departure: Date) -> f32 {
+        arrival
+            .iter_days()
+            .take(nights_between(arrival, departure) as usize)
+            .filter_map(|night| self.nightly_rate(iata, night))
+            .sum()
+    }
+}
+
+#[derive(Deserialize)]
+struct RateRow {
+    iata: AirportCode,
+    nightly_rate: f32,
+}
+
+/// The same rate every night in each city
+pub struct NightlyRates {
+    rates: HashMap<AirportCode, f32>,
+}
+
+impl NightlyRates {
+    pub fn from_file(path: &Path) -> Result<NightlyRates, StayError> {
+        Self::from_csv(File::open(path).map_err(StayError::Io)?)
+    }
+
+    /// Read rates from CSV with an `iata,nightly_rate` header
+    pub fn from_csv(reader: impl Read) -> Result<NightlyRates, StayError> {
+        let mut rates = HashMap::new();
+        for row in csv::Reader::from_reader(reader).deserialize::<RateRow>() {
+            let row = row.map_err(StayError::Parse)?;
+            // The router orders routes by price, a rate that isn't one would poison every route through the city
+            if !row.nightly_rate.is_finite() || row.nightly_rate < 0.0 {
+                return Err(StayError::InvalidRate(row.iata));
+            }
+            rates.insert(row.iata, row.nightly_rate);
+        }
+
+        Ok(NightlyRates { rates })
+    }
+}
+
+impl StayCostProvider for NightlyRates {
+    fn nightly_rate(&self, iata: AirportCode, _night: Date) -> Option<f32> {
+        self.rates.get(&iata).copied()
+    }
+}
+
+#[cfg(test)]
+mod stays_tests {
+    use route_solver_shared::queries::Date;
+
+    use super::{NightlyRates, StayCostProvider, StayError};
+
+    #[test]
+    fn test_nightly_rates() {
+        let rates = NightlyRates::from_csv("iata,nightly_rate\nyvr,120\nSEA,95.5\n".as_bytes()).unwrap();
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+
+        // Landing on the 2nd and leaving on the 5th is three nights
+        assert_eq!(rates.stay_cost("YVR".parse().unwrap(), day(2), day(5)), 360.0);
+        assert_eq!(rates.stay_cost("SEA".parse().unwrap(), day(5), day(5)), 0.0);
+        assert_eq!(rates.stay_cost("YYC".parse().unwrap(), day(2), day(5)), 0.0);
+
+        let negative = NightlyRates::from_csv("iata,nightly_rate\nYVR,-1\n".as_bytes());
+        assert!(matches!(negative, Err(StayError::InvalidRate(code)) if code == "YVR"));
+    }
+}
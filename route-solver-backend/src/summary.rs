@@ -0,0 +1,191 @@
+//! Trip summaries, everything about a solved trip in one payload for the results page and anything exporting trips.
+//!
+//! Durations and emissions are estimated from the great circle distance between the airports, legs to or from airports
+//! the directory doesn't know go without. Emissions are a typical figure per passenger kilometre, not the aircraft's.
+
+use route_solver_shared::queries::{
+    AirportCode, Date, DateMath, FlightPrice, LegSummary, QuoteFreshness, RouteSolution, TransportMode, TripStats, TripSummary,
+    TripTotals,
+};
+
+use crate::{
+    airports::{great_circle_km, AirportDirectory},
+    arrivals::flight_minutes,
+};
+
+/// Kilograms of CO₂ per passenger for each kilometre flown, averaged over short and long haul in economy
+const FLIGHT_CO2_KG_PER_KM: f64 = 0.12;
+/// The same overland, by train or coach
+const GROUND_CO2_KG_PER_KM: f64 = 0.04;
+
+const BOOKING_BASE_URL: &str = "https://www.skyscanner.com/transport/flights";
+
+pub fn summarize(solution: &RouteSolution, airports: &AirportDirectory) -> TripSummary {
+    let mut flights: Vec<&FlightPrice> = solution.flights.iter().collect();
+    flights.sort_by_key(|f| f.flight.date);
+
+    let mut distance_km = 0.0;
+    let legs: Vec<LegSummary> = flights
+        .iter()
+        .map(|f| {
+            let distance = distance_km_between(airports, f.flight.src, f.flight.dest);
+            distance_km += distance.unwrap_or(0.0);
+            let flown = f.mode == TransportMode::Flight;
+            let co2_per_km = if flown { FLIGHT_CO2_KG_PER_KM } else { GROUND_CO2_KG_PER_KM };
+
+            LegSummary {
+                flight: f.flight.clone(),
+                mode: f.mode,
+                arrival: f.arrival.unwrap_or(f.flight.date),
+                price: f.price,
+                airline: None,
+                duration_minutes: distance.filter(|_| flown).map(|d| flight_minutes(d) as u32),
+                deep_link: flown.then(|| booking_link(f, solution.passengers)),
+                co2_kg: distance.map(|d| (d * co2_per_km) as f32),
+                estimated: f.source.is_estimate(),
+            }
+        })
+        .collect();
+
+    let stays = solution.stays();
+    let days = match (legs.first(), legs.last()) {
+        (Some(first), Some(last)) => last.arrival.days_since(first.flight.date) as u32 + 1,
+        _ => 0,
+    };
+    let quoted = flights.iter().filter_map(|f| f.quoted_at);
+
+    TripSummary {
+        schema_version: TripSummary::SCHEMA_VERSION,
+        totals: TripTotals {
+            flights: legs.iter().map(|l| l.price).sum(),
+            lodging: stays.iter().map(|s| s.price).sum(),
+            total: solution.total_price,
+            co2_kg: legs.iter().filter_map(|l| l.co2_kg).sum(),
+        },
+        stats: TripStats {
+            days,
+            nights: stays.iter().map(|s| s.nights).sum(),
+            stops: stays.len().saturating_sub(2),
+            distance_km: distance_km as f32,
+        },
+        freshness: QuoteFreshness {
+            oldest: quoted.clone().min(),
+            newest: quoted.max(),
+            estimated_legs: legs.iter().filter(|l| l.estimated).count(),
+        },
+        legs,
+        stays,
+        breakdown: solution.breakdown(),
+        currency: solution.currency.clone(),
+        passengers: solution.passengers,
+        request_id: solution.request_id.clone(),
+    }
+}
+
+fn distance_km_between(airports: &AirportDirectory, src: AirportCode, dest: AirportCode) -> Option<f64> {
+    let (src, dest) = (airports.get(&src.to_string())?, airports.get(&dest.to_string())?);
+    Some(great_circle_km((src.lat, src.lon), (dest.lat, dest.lon)))
+}
+
+/// Search for the leg's flights on its date, for everyone travelling
+fn booking_link(leg: &FlightPrice, passengers: u32) -> String {
+    let code = |code: AirportCode| code.to_string().to_lowercase();
+    format!(
+        "{}/{}/{}/{}/?adults={}",
+        BOOKING_BASE_URL,
+        code(leg.flight.src),
+        code(leg.flight.dest),
+        yymmdd(leg.flight.date),
+        passengers
+    )
+}
+
+fn yymmdd(date: Date) -> String {
+    date.format("%y%m%d").to_string()
+}
+
+#[cfg(test)]
+mod summary_tests {
+    use route_solver_shared::queries::{Date, Flight, FlightPrice, PriceSource, RouteSolution, TransportMode};
+
+    use super::summarize;
+    use crate::airports::AirportDirectory;
+
+    fn leg(src: &str, dest: &str, day: u32, mode: TransportMode) -> FlightPrice {
+        FlightPrice {
+            flight: Flight {
+                src: src.parse().unwrap(),
+                dest: dest.parse().unwrap(),
+                date: Date::from_ymd_opt(2023, 2, day).unwrap(),
+            },
+            price: 100.0,
+            stay_price: 0.0,
+            mode,
+            source: PriceSource::Quoted,
+            std_dev: 0.0,
+            arrival: None,
+            quoted_at: None,
+            bag_fee: 0.0,
+            alternative: None,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let solution = RouteSolution {
+            flights: vec![
+                leg("YVR", "SEA", 4, TransportMode::Ground),
+                FlightPrice {
+                    stay_price: 150.0,
+                    ..leg("SEA", "YYZ", 6, TransportMode::Flight)
+                },
+                FlightPrice {
+                    // Lands the day after it leaves
+                    arrival: Date::from_ymd_opt(2023, 2, 2),
+                    ..leg("YYZ", "YVR", 1, TransportMode::Flight)
+                },
+            ],
+            total_price: 450.0,
+            currency: "USD".to_string(),
+            passengers: 2,
+            alternatives: Vec::new(),
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+        let summary = summarize(&solution, &AirportDirectory::embedded().unwrap());
+
+        // Legs in the order they're travelled, only flights get a booking link and a flight time
+        let order: Vec<_> = summary.legs.iter().map(|l| l.flight.date.to_string()).collect();
+        assert_eq!(order, ["2023-02-01", "2023-02-04", "2023-02-06"]);
+        assert_eq!(
+            summary.legs[0].deep_link.as_deref(),
+            Some("https://www.skyscanner.com/transport/flights/yyz/yvr/230201/?adults=2")
+        );
+        assert!(summary.legs[1].deep_link.is_none() && summary.legs[1].duration_minutes.is_none());
+        assert!(summary.legs[0].duration_minutes.unwrap() > 4 * 60);
+
+        // Two nights in Vancouver from landing on the 2nd, two in Seattle
+        let nights: Vec<_> = summary.stays.iter().map(|s| (s.city.to_string(), s.nights)).collect();
+        assert_eq!(nights[1..3], [("YVR".to_string(), 2), ("SEA".to_string(), 2)]);
+        assert_eq!(summary.stats.nights, 4);
+        assert_eq!(summary.stats.stops, 2);
+        assert_eq!(summary.stats.days, 6);
+
+        assert_eq!(summary.totals.flights, 300.0);
+        assert_eq!(summary.totals.lodging, 150.0);
+        // Overland is a fraction of flying per kilometre
+        assert!(summary.legs[1].co2_kg.unwrap() < summary.legs[0].co2_kg.unwrap() / 10.0);
+        assert_eq!(summary.freshness.oldest, None);
+
+        // The layout documented on TripSummary
+        let json = serde_json::to_value(&summary).unwrap();
+        let mut fields: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            ["breakdown", "currency", "freshness", "legs", "passengers", "schema_version", "stats", "stays", "totals"]
+        );
+        assert_eq!(json["schema_version"], 1);
+    }
+}
@@ -0,0 +1,59 @@
+//! Trip templates, example itineraries the builder can start from.
+//!
+//! Templates are data rather than code: a JSON list of [TripTemplate]s compiled into the binary, which the config can
+//! point at a different file to replace.
+
+use std::{fs, path::Path};
+
+use route_solver_shared::queries::TripTemplate;
+use thiserror::Error;
+
+const EMBEDDED_JSON: &str = include_str!("../data/templates.json");
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Could not read templates file.")]
+    Io(std::io::Error),
+    #[error("Could not parse templates.")]
+    Parse(serde_json::Error),
+}
+
+pub struct TemplateLibrary {
+    templates: Vec<TripTemplate>,
+}
+
+impl TemplateLibrary {
+    /// Templates bundled with the server
+    pub fn embedded() -> Result<TemplateLibrary, TemplateError> {
+        Self::from_json(EMBEDDED_JSON)
+    }
+
+    pub fn from_file(path: &Path) -> Result<TemplateLibrary, TemplateError> {
+        Self::from_json(&fs::read_to_string(path).map_err(TemplateError::Io)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<TemplateLibrary, TemplateError> {
+        let templates = serde_json::from_str(json).map_err(TemplateError::Parse)?;
+        Ok(TemplateLibrary { templates })
+    }
+
+    /// Every template, in the order they're listed
+    pub fn all(&self) -> &[TripTemplate] {
+        &self.templates
+    }
+}
+
+#[cfg(test)]
+mod templates_tests {
+    use super::TemplateLibrary;
+
+    #[test]
+    fn test_embedded_templates() {
+        let library = TemplateLibrary::embedded().unwrap();
+
+        let ids: Vec<&str> = library.all().iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["southeast-asia-loop", "europe-capitals", "weekend-double-city"]);
+        // Every template needs somewhere to start and somewhere to end
+        assert!(library.all().iter().all(|t| t.stops.len() >= 2));
+    }
+}
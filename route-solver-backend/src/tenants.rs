@@ -0,0 +1,270 @@
+//! Tenants, customers sharing one server who mustn't see each other's data, e.g. the clients of a small agency.
+//!
+//! Each tenant calls providers with its own credentials, has its own quotas and price history, and only sees the jobs
+//! it submitted. A request is a tenant's when it carries one of the tenant's API keys, see [select_tenant]. Tenants
+//! without keys are routed to by their subdomain instead, the subdomain alone never gets a request into a tenant that
+//! has keys. Anything else is the default tenant's, which goes by the top level `[quota]` and the server's own
+//! credentials, so a server without tenants works as it always has. Fares are cached for everyone alike, a fare is the
+//! same whoever looked it up, but each tenant's solves fetch them with its own credentials and only reuse its own
+//! solutions.
+//!
+//! Price histories are kept in a `history` directory beside the job records when those are persisted, one file per
+//! tenant.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error, HttpMessage,
+};
+use thiserror::Error;
+
+use crate::{
+    config::{Config, QuotaConfig},
+    flight_api::PriceQuery,
+    history::{HistoryError, PriceHistory},
+    jobs::JobManager,
+    quota::{QuotaTracker, API_KEY_HEADER},
+};
+
+#[derive(Debug, Error)]
+pub enum TenantError {
+    #[error("API key of tenant {0} is also another tenant's")]
+    SharedApiKey(String),
+    #[error("Subdomain of tenant {0} is also another tenant's")]
+    SharedSubdomain(String),
+    #[error("Price history of tenant {0:?} could not be opened: {1}")]
+    History(Option<String>, HistoryError),
+}
+
+pub struct Tenant {
+    /// Name in the config, `None` for the default tenant
+    name: Option<String>,
+    /// Credentials providers are called with, their own if `None`
+    provider_key: Option<String>,
+    /// `X-Api-Key`s the tenant's requests are made with, none for the default tenant
+    api_keys: HashSet<String>,
+    quota: Arc<QuotaTracker>,
+    /// Fares the tenant's solves have fetched, their priors guide its later solves
+    history: PriceHistory,
+}
+
+impl Tenant {
+    fn new(
+        name: Option<String>,
+        provider_key: Option<String>,
+        api_keys: HashSet<String>,
+        quota: QuotaConfig,
+        history: PriceHistory,
+    ) -> Tenant {
+        Tenant {
+            name,
+            provider_key,
+            api_keys,
+            quota: Arc::new(QuotaTracker::new(quota)),
+            history,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn quota(&self) -> &Arc<QuotaTracker> {
+        &self.quota
+    }
+
+    pub fn history(&self) -> &PriceHistory {
+        &self.history
+    }
+
+    /// Whether `key` is one the tenant's requests are made with or one its quota has a limit for, rather than one the
+    /// client made up
+    pub fn knows_key(&self, key: &str) -> bool {
+        self.api_keys.contains(key) || self.quota.has_key(key)
+    }
+
+    /// Whether something recorded as the tenant named `name`'s, `None` for the default, is this tenant's
+    pub fn owns(&self, name: Option<&str>) -> bool {
+        self.name() == name
+    }
+
+    /// Have `api` call its provider with the tenant's credentials
+    pub fn authenticate(&self, api: &mut impl PriceQuery) {
+        if let Some(key) = &self.provider_key {
+            api.set_api_key(key);
+        }
+    }
+}
+
+pub struct Tenants {
+    default: Arc<Tenant>,
+    named: HashMap<String, Arc<Tenant>>,
+    by_api_key: HashMap<String, Arc<Tenant>>,
+    /// Lower case
+    by_subdomain: HashMap<String, Arc<Tenant>>,
+}
+
+impl Tenants {
+    /// Just the default tenant, with `quota`
+    pub fn new(quota: QuotaConfig) -> Tenants {
+        Tenants {
+            default: Arc::new(Tenant::new(None, None, HashSet::new(), quota, PriceHistory::default())),
+            named: HashMap::new(),
+            by_api_key: HashMap::new(),
+            by_subdomain: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Tenants, TenantError> {
+        let history_dir = config.jobs.state_dir.as_ref().map(|dir| dir.join("history"));
+        let history_dir = history_dir.as_deref();
+        let mut tenants = Tenants::new(config.quota.clone());
+        tenants.default = Arc::new(Tenant::new(
+            None,
+            None,
+            HashSet::new(),
+            config.quota.clone(),
+            Self::open_history(history_dir, None)?,
+        ));
+        for (name, tenant_config) in &config.tenants {
+            let tenant = Arc::new(Tenant::new(
+                Some(name.clone()),
+                tenant_config.provider_key.clone(),
+                tenant_config.api_keys.iter().cloned().collect(),
+                tenant_config.quota.clone(),
+                Self::open_history(history_dir, Some(name))?,
+            ));
+            for key in &tenant_config.api_keys {
+                if tenants.by_api_key.insert(key.clone(), Arc::clone(&tenant)).is_some() {
+                    return Err(TenantError::SharedApiKey(name.clone()));
+                }
+            }
+            if let Some(subdomain) = &tenant_config.subdomain {
+                if tenants.by_subdomain.insert(subdomain.to_lowercase(), Arc::clone(&tenant)).is_some() {
+                    return Err(TenantError::SharedSubdomain(name.clone()));
+                }
+            }
+            tenants.named.insert(name.clone(), tenant);
+        }
+        Ok(tenants)
+    }
+
+    /// Price history of the tenant named `name`, the default if `None`, kept in `dir`. In memory only without a `dir`.
+    fn open_history(dir: Option<&Path>, name: Option<&str>) -> Result<PriceHistory, TenantError> {
+        let Some(dir) = dir else {
+            return Ok(PriceHistory::default());
+        };
+        let file = match name {
+            Some(name) => format!("tenant-{}.json", name),
+            None => "default.json".to_string(),
+        };
+        PriceHistory::open(dir.join(file)).map_err(|e| TenantError::History(name.map(str::to_string), e))
+    }
+
+    pub fn default_tenant(&self) -> &Arc<Tenant> {
+        &self.default
+    }
+
+    /// Tenant named `name`, the default if `None`. `None` if there's no such tenant (any more).
+    pub fn get(&self, name: Option<&str>) -> Option<&Arc<Tenant>> {
+        match name {
+            Some(name) => self.named.get(name),
+            None => Some(&self.default),
+        }
+    }
+
+    /// Tenant a request with `api_key` made on `host` is for, the default if it's none of theirs. The subdomain only
+    /// picks tenants without API keys, the others need one of theirs.
+    pub fn select(&self, api_key: Option<&str>, host: Option<&str>) -> &Arc<Tenant> {
+        let by_key = api_key.and_then(|key| self.by_api_key.get(key));
+        // A bare host like `localhost` has no subdomain
+        let subdomain = host
+            .and_then(|host| host.split(':').next())
+            .and_then(|host| host.split_once('.'))
+            .map(|(subdomain, _)| subdomain.to_lowercase());
+        let by_subdomain = subdomain
+            .and_then(|subdomain| self.by_subdomain.get(&subdomain))
+            .filter(|tenant| tenant.api_keys.is_empty());
+        by_key.or(by_subdomain).unwrap_or(&self.default)
+    }
+}
+
+/// Middleware recording which tenant each request is for, see [request_tenant]. Needs the [JobManager] as app data.
+pub async fn select_tenant(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(jobs) = req.app_data::<web::Data<JobManager>>() {
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let tenant = Arc::clone(jobs.tenants().select(header(API_KEY_HEADER), header(header::HOST.as_str())));
+        req.extensions_mut().insert(tenant);
+    }
+    next.call(req).await
+}
+
+/// Tenant [select_tenant] found the request is for, the default one if it didn't run
+pub fn request_tenant(req: &impl HttpMessage, jobs: &JobManager) -> Arc<Tenant> {
+    req.extensions()
+        .get::<Arc<Tenant>>()
+        .cloned()
+        .unwrap_or_else(|| Arc::clone(jobs.tenants().default_tenant()))
+}
+
+#[cfg(test)]
+mod tenants_tests {
+    use std::collections::HashMap;
+
+    use super::Tenants;
+    use crate::config::{Config, JobConfig, TenantConfig};
+
+    #[test]
+    fn test_select_tenant() {
+        let tenant = |keys: &[&str], subdomain: &str| TenantConfig {
+            api_keys: keys.iter().map(|k| k.to_string()).collect(),
+            subdomain: Some(subdomain.to_string()),
+            ..TenantConfig::default()
+        };
+        let mut config = Config {
+            tenants: HashMap::from([
+                ("acme".to_string(), tenant(&["acme-key"], "acme")),
+                ("globex".to_string(), tenant(&["globex-key"], "Globex")),
+                ("hooli".to_string(), tenant(&[], "hooli")),
+            ]),
+            jobs: JobConfig {
+                state_dir: None,
+                ..JobConfig::default()
+            },
+            ..Config::default()
+        };
+        let tenants = Tenants::from_config(&config).unwrap();
+        let name = |key, host| tenants.select(key, host).name();
+
+        assert_eq!(name(Some("acme-key"), None), Some("acme"));
+        assert_eq!(name(Some("globex-key"), Some("globex.routes.example.com:8080")), Some("globex"));
+        assert_eq!(name(None, Some("Hooli.routes.example.com:8080")), Some("hooli"));
+        // A tenant with keys needs one of them, its subdomain alone isn't enough
+        assert_eq!(name(None, Some("globex.routes.example.com")), None);
+        assert_eq!(name(Some("someone"), Some("globex.routes.example.com")), None);
+        // The key says whose the request is wherever it's made
+        assert_eq!(name(Some("acme-key"), Some("globex.routes.example.com")), Some("acme"));
+        assert_eq!(name(Some("someone"), Some("routes.example.com")), None);
+        assert_eq!(name(None, Some("localhost:8080")), None);
+        assert!(tenants.get(Some("acme")).is_some() && tenants.get(Some("initech")).is_none());
+
+        // Only keys the server gave out identify a user to charge
+        assert!(tenants.select(Some("acme-key"), None).knows_key("acme-key"));
+        assert!(!tenants.select(Some("someone"), None).knows_key("someone"));
+        assert!(!tenants.select(None, Some("globex.routes.example.com")).knows_key("acme-key"));
+
+        config.tenants.insert("initech".to_string(), tenant(&["acme-key"], "initech"));
+        assert!(Tenants::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Price provider for tests, made to misbehave however a test needs.
+
+use std::collections::HashSet;
+
+use route_solver_shared::queries::Flight;
+
+use crate::flight_api::{PriceQuery, QueryError, Quote, TestPriceApiQuery};
+
+type FareFn = Box<dyn Fn(&Flight, Option<Quote>) -> Option<Quote> + Send>;
+type FailureFn = Box<dyn Fn(&Flight) -> Option<QueryError> + Send>;
+
+/// The test data by default, with its fares rewritten, failures where a test wants them and a budget of calls, keeping
+/// track of the flights asked for
+pub struct FakeApi {
+    data: TestPriceApiQuery,
+    /// Fare for a flight given the test data's, `None` for no fare
+    fares: FareFn,
+    /// Error asking for a flight fails with, `None` to price it
+    failure: FailureFn,
+    /// Calls left before the quota runs out, unlimited if `None`
+    budget: Option<u64>,
+    /// Flights asked for within the budget
+    pub asked: HashSet<Flight>,
+}
+
+impl FakeApi {
+    /// Price flights with `fares` instead, given the test data's fare for each
+    pub fn with_fares(self, fares: impl Fn(&Flight, Option<Quote>) -> Option<Quote> + Send + 'static) -> Self {
+        FakeApi {
+            fares: Box::new(fares),
+            ..self
+        }
+    }
+
+    /// Fail to price flights with whatever `failure` returns for them
+    pub fn with_failure(self, failure: impl Fn(&Flight) -> Option<QueryError> + Send + 'static) -> Self {
+        FakeApi {
+            failure: Box::new(failure),
+            ..self
+        }
+    }
+
+    /// Allow `budget` calls, unlimited if `None`, then fail with [QueryError::QuotaExceeded]
+    pub fn with_budget(self, budget: Option<u64>) -> Self {
+        FakeApi { budget, ..self }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceQuery for FakeApi {
+    fn new() -> Self {
+        FakeApi {
+            data: TestPriceApiQuery::new(),
+            fares: Box::new(|_, quote| quote),
+            failure: Box::new(|_| None),
+            budget: None,
+            asked: HashSet::new(),
+        }
+    }
+
+    async fn get_price(&mut self, flight: Flight) -> Result<Quote, QueryError> {
+        if let Some(budget) = &mut self.budget {
+            *budget = budget.checked_sub(1).ok_or(QueryError::QuotaExceeded)?;
+        }
+        self.asked.insert(flight.clone());
+        if let Some(e) = (self.failure)(&flight) {
+            return Err(e);
+        }
+        let quote = self.data.get_price(flight.clone()).await.ok();
+        (self.fares)(&flight, quote).ok_or(QueryError::NoFare)
+    }
+
+    fn remaining_calls(&self) -> Option<u64> {
+        self.budget
+    }
+}
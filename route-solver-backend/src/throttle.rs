@@ -0,0 +1,87 @@
+//! Coarser searches as a user's daily quota of provider calls runs low, so a solve started with little quota left makes
+//! do with it rather than failing partway with the quota used up.
+//!
+//! The router checks how many calls are left as it goes, see [PriceQuery::remaining_calls](crate::flight_api::PriceQuery::remaining_calls).
+//! Below [QuotaThrottle::sample_below] it only prices every few days of windows wider than that and estimates the days
+//! between from them, below [QuotaThrottle::narrow_below] it also only carries on from the cheapest few routes at each
+//! number of stops, like a beam search. Once the quota is used up the legs left are estimated like those the provider
+//! fails to price, or ruled out if they can't be. How coarse a solve got is in its stats.
+
+use route_solver_shared::queries::{DateSampling, ThrottleLevel};
+
+use crate::config::ThrottleConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaThrottle {
+    /// Calls left below which the search samples days, never if `None`
+    pub sample_below: Option<u64>,
+    /// Calls left below which the search narrows, never if `None`
+    pub narrow_below: Option<u64>,
+    /// Days apart the days priced are when sampling, from 1 to [DateSampling::MAX_EVERY]
+    pub sample_every: u32,
+    /// Routes carried on from at each number of stops when narrowed, at least 1
+    pub beam_width: usize,
+}
+
+impl QuotaThrottle {
+    pub fn from_config(config: &ThrottleConfig) -> QuotaThrottle {
+        QuotaThrottle {
+            sample_below: config.sample_below,
+            narrow_below: config.narrow_below,
+            sample_every: config.sample_every.clamp(1, DateSampling::MAX_EVERY),
+            beam_width: config.beam_width.max(1),
+        }
+    }
+
+    /// How coarse to search with `remaining` calls left, `None` being unlimited
+    pub fn level(&self, remaining: Option<u64>) -> ThrottleLevel {
+        let Some(remaining) = remaining else {
+            return ThrottleLevel::Full;
+        };
+        let below = |threshold: Option<u64>| threshold.is_some_and(|threshold| remaining < threshold);
+        if remaining == 0 {
+            ThrottleLevel::Exhausted
+        } else if below(self.narrow_below) {
+            ThrottleLevel::Narrowed
+        } else if below(self.sample_below) {
+            ThrottleLevel::Sampled
+        } else {
+            ThrottleLevel::Full
+        }
+    }
+
+    /// Days priced when sampling, the days between are estimated from them
+    pub fn sampling(&self) -> DateSampling {
+        DateSampling::every(self.sample_every)
+    }
+}
+
+#[cfg(test)]
+mod throttle_tests {
+    use route_solver_shared::queries::{DateSampling, ThrottleLevel};
+
+    use super::QuotaThrottle;
+    use crate::config::ThrottleConfig;
+
+    #[test]
+    fn test_throttle_levels() {
+        let throttle = QuotaThrottle::from_config(&ThrottleConfig {
+            sample_below: Some(100),
+            narrow_below: Some(10),
+            sample_every: 3,
+            beam_width: 5,
+        });
+        assert_eq!(throttle.level(None), ThrottleLevel::Full);
+        assert_eq!(throttle.level(Some(100)), ThrottleLevel::Full);
+        assert_eq!(throttle.level(Some(99)), ThrottleLevel::Sampled);
+        assert_eq!(throttle.level(Some(9)), ThrottleLevel::Narrowed);
+        assert_eq!(throttle.level(Some(0)), ThrottleLevel::Exhausted);
+
+        assert_eq!(throttle.sampling().every, 3);
+        let monthly = QuotaThrottle::from_config(&ThrottleConfig {
+            sample_every: u32::MAX,
+            ..ThrottleConfig::default()
+        });
+        assert_eq!(monthly.sampling().every, DateSampling::MAX_EVERY);
+    }
+}
@@ -1,8 +1,44 @@
 //! Main web app module containing web routings to access API etc.
 
-use actix_web::{get, post, web, HttpResponse, Responder, HttpRequest};
-use serde::Deserialize;
-use route_solver_shared::queries::{EchoQuery, RouteQuery};
+use std::sync::Arc;
+
+use actix_web::{
+    error::InternalError,
+    get,
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        StatusCode,
+    },
+    post, web, HttpRequest, HttpResponse, Responder,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use route_solver_shared::queries::{
+    AirportCode, ApiError, Date, EchoQuery, Flight, JobHandle, JobId, JobStatus, MonthSweep, Passengers, RouteQuery, RouteSolution,
+    SolveEstimate, SweepMonth, TripComparison, TripOption, TripStructure, WhatIf, WhatIfQuery, CONFIRMED_CALLS_HEADER,
+    MAX_SWEEP_MONTHS,
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    airports::AirportDirectory,
+    audit::{AuditEvent, AuditFilter},
+    export::{leg_rows, quote_rows, write_table, ExportFormat, ExportTable},
+    config::AdminConfig,
+    diff::diff_solutions,
+    feasibility::{check_dates, estimate_calls},
+    flight_api::PointOfSale,
+    jobs::JobManager,
+    locale, log,
+    quota::{QuotaTracker, API_KEY_HEADER},
+    request_id,
+    router::MAX_DESTINATIONS,
+    snapshot::{PriceSnapshot, SnapshotError, SnapshotFilter},
+    summary::summarize,
+    templates::TemplateLibrary,
+    tenants::{request_tenant, Tenant},
+    worker_pool::PoolError,
+};
 
 
 #[derive(Deserialize)]
@@ -23,19 +59,866 @@ pub async fn hello() -> impl Responder {
 
 #[post("/echo")]
 pub async fn echo(json: web::Json<EchoQuery>) -> impl Responder {
-    println!("Received: {0}", json.input);
+    log!("Received: {0}", json.input);
     HttpResponse::Ok().body(format!("Received: {0}", json.input))
 }
 
-/// Endpoint for running route computation
+/// Answer with `error` as the JSON body, under the status it maps to
+pub fn error_response(error: ApiError) -> HttpResponse {
+    let status = StatusCode::from_u16(error.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    HttpResponse::build(status).json(error)
+}
+
+/// Error handler for the JSON, path and query extractors, so a request that can't be read is answered with an
+/// [ApiError::Validation] like any other invalid request
+pub fn rejected_request<E: std::fmt::Display>(e: E, _req: &HttpRequest) -> actix_web::Error {
+    let message = e.to_string();
+    InternalError::from_response(message.clone(), error_response(ApiError::Validation(message))).into()
+}
+
+/// User a request's API calls are charged to, their API key if it's one `tenant` knows, otherwise their IP. Keys the
+/// server never gave out aren't trusted, or each made up one would come with a fresh quota.
+fn quota_user(req: &HttpRequest, tenant: &Tenant) -> String {
+    match req.headers().get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(key) if tenant.knows_key(key) => key.to_string(),
+        // The socket address rather than forwarding headers, which the client could just make up
+        _ => match req.peer_addr() {
+            Some(addr) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        },
+    }
+}
+
+/// Rejects requests asking for a currency there's no exchange rate for, before any API calls are spent on them
+fn unsupported_currency(jobs: &JobManager, currency: Option<&str>) -> Option<ApiError> {
+    let currency = currency?;
+    (!jobs.rates().is_supported(currency))
+        .then(|| ApiError::Validation(format!("Currency {} is not supported", currency)))
+}
+
+fn invalid_point_of_sale(market: Option<&str>, locale: Option<&str>) -> Option<ApiError> {
+    if market.is_some_and(|m| !locale::is_market(m)) {
+        return Some(ApiError::Validation("A market is a two letter country code".to_string()));
+    }
+    if locale.is_some_and(|l| !locale::is_locale(l)) {
+        return Some(ApiError::Validation("A locale is a language tag like en-GB".to_string()));
+    }
+    None
+}
+
+/// Rejects parties that can't be booked together, itineraries too long for the router, negative days' worth or leg
+/// price ceilings, beams too narrow to search, sampling which prices nothing or too few days, stops whose dates can't
+/// be met, malformed markets and locales and currencies there's no rate for, see [unsupported_currency]
+fn invalid_query(jobs: &JobManager, query: &RouteQuery) -> Option<ApiError> {
+    let stops = || std::iter::once(&query.start_city).chain(&query.hops).chain(std::iter::once(&query.end_city));
+    if !query.passengers.is_valid() {
+        return Some(ApiError::Validation(format!(
+            "A booking needs at least one adult and at most {} passengers",
+            Passengers::MAX
+        )));
+    }
+    // Two more for the start and end
+    if query.hops.len() + 2 > MAX_DESTINATIONS {
+        return Some(ApiError::Validation(format!(
+            "An itinerary can have at most {} stops",
+            MAX_DESTINATIONS - 2
+        )));
+    }
+    if !query.value_of_day.is_finite() || query.value_of_day < 0.0 {
+        return Some(ApiError::Validation("A day's worth can't be negative".to_string()));
+    }
+    if let Some(origin) = query.origin_penalties.iter().find(|(_, p)| !p.is_finite() || **p < 0.0).map(|(o, _)| o) {
+        return Some(ApiError::Validation(format!("Starting from {} can't cost less than nothing", origin)));
+    }
+    let ceilings = std::iter::once(query.limits.max_leg_price).chain(stops().map(|stop| stop.max_leg_price));
+    if ceilings.flatten().any(|max| !max.is_finite() || max < 0.0) {
+        return Some(ApiError::Validation("A leg's price ceiling can't be negative".to_string()));
+    }
+    if !query.objective.is_valid() {
+        return Some(ApiError::Validation(
+            "A pessimistic objective needs a finite number of sigmas, at least 0".to_string(),
+        ));
+    }
+    if !query.strategy.is_valid() {
+        return Some(ApiError::Validation("A beam search needs a width of at least 1".to_string()));
+    }
+    if query.sampling.is_some_and(|sampling| !sampling.is_valid()) {
+        return Some(ApiError::Validation(
+            "Sampling needs to price at least every so many days and refine at least 1 route".to_string(),
+        ));
+    }
+    for stop in stops() {
+        if let Err(e) = stop.validate() {
+            return Some(ApiError::Validation(format!("Can't stay at {}, {}", stop.iata, e)));
+        }
+    }
+    invalid_point_of_sale(query.market.as_deref(), query.locale.as_deref())
+        .or_else(|| unsupported_currency(jobs, query.currency.as_deref()))
+}
+
+/// Fill in the query's market, locale and currency from the request's `Accept-Language` where it leaves them unset, see
+/// [locale::localize]
+fn localize_query(req: &HttpRequest, jobs: &JobManager, query: &mut RouteQuery) {
+    locale::localize(query, accept_language(req), |currency| jobs.rates().is_supported(currency));
+}
+
+fn accept_language(req: &HttpRequest) -> Option<&str> {
+    req.headers().get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok())
+}
+
+fn quota_exceeded_response() -> HttpResponse {
+    error_response(ApiError::RateLimited("Daily API quota exceeded, try again tomorrow".to_string()))
+}
+
+/// Attach the user's quota limit and what's left of it, skipped for unlimited users
+fn with_quota_headers(mut resp: HttpResponse, quota: &QuotaTracker, user: &str) -> HttpResponse {
+    if let (Some(limit), Some(remaining)) = (quota.limit(user), quota.remaining(user)) {
+        let headers = resp.headers_mut();
+        headers.insert(HeaderName::from_static("x-quota-limit"), HeaderValue::from(limit));
+        headers.insert(HeaderName::from_static("x-quota-remaining"), HeaderValue::from(remaining));
+    }
+    resp
+}
+
+/// Whether what's left of the user's quota covers the calls `queries` are estimated to make between them, see
+/// [estimate_calls]. Solves queued side by side are turned down together rather than some running out part way.
+fn quota_covers<'a>(
+    jobs: &JobManager,
+    tenant: &Tenant,
+    user: &str,
+    queries: impl IntoIterator<Item = &'a RouteQuery>,
+) -> bool {
+    let Some(remaining) = tenant.quota().remaining(user) else {
+        return true;
+    };
+    let calls: u64 = queries
+        .into_iter()
+        .map(|query| estimate_calls(query, jobs.cache(), tenant.name(), None).calls)
+        .sum();
+    remaining > 0 && calls <= remaining
+}
+
+/// Rejects solves estimated to make more provider calls between them than the server lets through unasked, unless the
+/// request confirms that many with [CONFIRMED_CALLS_HEADER]. See [estimate_calls].
+fn unconfirmed_solves<'a>(
+    req: &HttpRequest,
+    jobs: &JobManager,
+    queries: impl IntoIterator<Item = &'a RouteQuery>,
+) -> Option<ApiError> {
+    let tenant = request_tenant(req, jobs);
+    let confirm_above = tenant.quota().confirm_calls_above();
+    let total = queries
+        .into_iter()
+        .map(|query| estimate_calls(query, jobs.cache(), tenant.name(), confirm_above))
+        .reduce(|a, b| SolveEstimate {
+            legs: a.legs + b.legs,
+            cached: a.cached + b.cached,
+            calls: a.calls + b.calls,
+            partial: a.partial || b.partial,
+            ..a
+        })?;
+    let confirmed = req
+        .headers()
+        .get(CONFIRMED_CALLS_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    if total.is_confirmed_by(confirmed) {
+        return None;
+    }
+    Some(ApiError::Validation(format!(
+        "This may make {} price lookups, confirm them by sending it again with the {} header set to {}",
+        total.calls, CONFIRMED_CALLS_HEADER, total.calls
+    )))
+}
+
+/// Endpoint for running route computation, waits on the solve and responds with the solution
 #[post("/compute_route")]
-pub async fn compute(json: web::Json<RouteQuery>) -> impl Responder {
-    HttpResponse::Ok().body(format!(
-        "Start city {0}, End City {1}, num_hops {2}",
-        json.start_city,
-        json.end_city,
+pub async fn compute(req: HttpRequest, jobs: web::Data<JobManager>, mut json: web::Json<RouteQuery>) -> impl Responder {
+    log!(
+        "Compute: start city {0}, end city {1}, num_hops {2}",
+        json.start_city.iata,
+        json.end_city.iata,
         json.hops.len()
-    ))
+    );
+
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    localize_query(&req, &jobs, &mut json);
+    if let Some(error) = unconfirmed_solves(&req, &jobs, [&*json]) {
+        return error_response(error);
+    }
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    if tenant.quota().remaining(&user) == Some(0) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+
+    let done = match queue_solve(&jobs, json.into_inner(), &tenant, Some(user.clone())) {
+        Ok((_, done)) => done,
+        Err(e) => return error_response(e.into()),
+    };
+
+    let resp = match done.await {
+        Ok(JobStatus::Completed { mut solution }) => {
+            solution.request_id = request_id::current();
+            HttpResponse::Ok().json(solution)
+        }
+        // The solve most likely failed because it ran the quota dry part way through
+        Ok(JobStatus::Failed { .. }) if tenant.quota().remaining(&user) == Some(0) => quota_exceeded_response(),
+        Ok(JobStatus::Failed { error }) => error_response(error),
+        Ok(JobStatus::Queued) => error_response(ApiError::Unavailable(
+            "Server is shutting down, the solve will resume after restart".to_string(),
+        )),
+        _ => error_response(ApiError::Internal("Solve ended without a result".to_string())),
+    };
+
+    with_quota_headers(resp, tenant.quota(), &user)
+}
+
+/// Queue a solve in the background, poll [job_status] with the returned ID for the result
+#[post("/api/v1/jobs")]
+pub async fn submit_job(req: HttpRequest, jobs: web::Data<JobManager>, mut json: web::Json<RouteQuery>) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    localize_query(&req, &jobs, &mut json);
+    if let Some(error) = unconfirmed_solves(&req, &jobs, [&*json]) {
+        return error_response(error);
+    }
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    if tenant.quota().remaining(&user) == Some(0) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+
+    let resp = match queue_solve(&jobs, json.into_inner(), &tenant, Some(user.clone())) {
+        Ok((id, _)) => HttpResponse::Accepted().json(JobHandle {
+            id,
+            request_id: request_id::current(),
+        }),
+        Err(e) => error_response(e.into()),
+    };
+
+    with_quota_headers(resp, tenant.quota(), &user)
+}
+
+/// Warnings about an itinerary which can be solved but probably isn't what was meant, see [RouteQuery::lint]. Nothing
+/// is priced, itineraries [submit_job] would reject are rejected the same way.
+#[post("/api/v1/lint")]
+pub async fn lint(
+    jobs: web::Data<JobManager>,
+    directory: web::Data<AirportDirectory>,
+    json: web::Json<RouteQuery>,
+) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    HttpResponse::Ok().json(json.lint(|code| directory.get(code.as_str()).map(|a| (a.lat, a.lon))))
+}
+
+/// Whether an itinerary's dates allow some order of its stops, which of them clash and how big a solve of it would be,
+/// see [check_dates]. Quick enough to ask on every edit, nothing is priced.
+#[post("/api/v1/feasibility")]
+pub async fn feasibility(jobs: web::Data<JobManager>, json: web::Json<RouteQuery>) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    HttpResponse::Ok().json(check_dates(&json))
+}
+
+/// How many provider calls a solve of an itinerary would make, whether it needs confirming first and how many calls
+/// the user has left to make it with, see [estimate_calls]. Nothing is priced.
+#[post("/api/v1/estimate")]
+pub async fn estimate(req: HttpRequest, jobs: web::Data<JobManager>, mut json: web::Json<RouteQuery>) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    // The market decides which cached fares count
+    localize_query(&req, &jobs, &mut json);
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    let mut calls = estimate_calls(&json, jobs.cache(), tenant.name(), tenant.quota().confirm_calls_above());
+    calls.remaining = tenant.quota().remaining(&user);
+    with_quota_headers(HttpResponse::Ok().json(calls), tenant.quota(), &user)
+}
+
+/// Queue a solve priced by the provider the server is configured with
+fn queue_solve(
+    jobs: &JobManager,
+    query: RouteQuery,
+    tenant: &Arc<Tenant>,
+    user: Option<String>,
+) -> Result<(JobId, oneshot::Receiver<JobStatus>), PoolError> {
+    jobs.price_provider().submit(jobs, query, tenant, user)
+}
+
+/// The solution a finished solve came to, or why there isn't one
+fn solve_outcome(status: Result<JobStatus, oneshot::error::RecvError>) -> Result<RouteSolution, ApiError> {
+    match status {
+        Ok(JobStatus::Completed { solution }) => Ok(solution),
+        Ok(JobStatus::Failed { error }) => Err(error),
+        Ok(JobStatus::Queued) => Err(ApiError::Unavailable("Server is shutting down".to_string())),
+        _ => Err(ApiError::Internal("Solve ended without a result".to_string())),
+    }
+}
+
+/// Price an itinerary as one loop and as a round trip to each stop, answering with whichever is cheaper and what each
+/// trip of both would be
+#[post("/api/v1/compare")]
+pub async fn compare(req: HttpRequest, jobs: web::Data<JobManager>, mut json: web::Json<RouteQuery>) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    localize_query(&req, &jobs, &mut json);
+    if json.hops.len() < 2 {
+        return error_response(ApiError::Validation("Comparing trips needs at least two stops".to_string()));
+    }
+    let query = json.into_inner();
+    let round_trips = query.round_trips();
+    if let Some(error) = unconfirmed_solves(&req, &jobs, std::iter::once(&query).chain(&round_trips)) {
+        return error_response(error);
+    }
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    if !quota_covers(&jobs, &tenant, &user, std::iter::once(&query).chain(&round_trips)) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+    // Everything is queued before waiting on any of it so the solves can run side by side
+    let mut solves = Vec::with_capacity(round_trips.len() + 1);
+    for query in std::iter::once(query).chain(round_trips) {
+        match queue_solve(&jobs, query, &tenant, Some(user.clone())) {
+            Ok((_, done)) => solves.push(done),
+            Err(e) => return error_response(e.into()),
+        }
+    }
+
+    let mut outcomes = Vec::with_capacity(solves.len());
+    for done in solves {
+        outcomes.push(solve_outcome(done.await));
+    }
+    let separate = outcomes.split_off(1);
+    let mut comparison = TripComparison::new(vec![
+        TripOption::new(TripStructure::Combined, outcomes),
+        TripOption::new(TripStructure::SeparateRoundTrips, separate),
+    ]);
+    for trip in comparison.options.iter_mut().flat_map(|option| &mut option.trips) {
+        trip.request_id = request_id::current();
+    }
+
+    with_quota_headers(HttpResponse::Ok().json(comparison), tenant.quota(), &user)
+}
+
+#[derive(Deserialize)]
+pub struct SweepQuery {
+    /// Months to price the itinerary in, its own and the ones after
+    months: u32,
+}
+
+/// Price an itinerary moved to each of the coming months, see [RouteQuery::shifted_months], answering with what the
+/// trip costs in each and which is cheapest
+#[post("/api/v1/sweep")]
+pub async fn sweep(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    mut json: web::Json<RouteQuery>,
+    sweep: web::Query<SweepQuery>,
+) -> impl Responder {
+    if let Some(error) = invalid_query(&jobs, &json) {
+        return error_response(error);
+    }
+    localize_query(&req, &jobs, &mut json);
+    if sweep.months == 0 || sweep.months > MAX_SWEEP_MONTHS {
+        return error_response(ApiError::Validation(format!(
+            "A sweep covers between 1 and {} months",
+            MAX_SWEEP_MONTHS
+        )));
+    }
+    let shifted: Option<Vec<_>> = (0..sweep.months).map(|months| json.shifted_months(months)).collect();
+    let Some(shifted) = shifted else {
+        return error_response(ApiError::Validation("Sweeping months needs a trip with dates".to_string()));
+    };
+    if let Some(error) = unconfirmed_solves(&req, &jobs, shifted.iter().map(|(_, query)| query)) {
+        return error_response(error);
+    }
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    if !quota_covers(&jobs, &tenant, &user, shifted.iter().map(|(_, query)| query)) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+
+    // Everything is queued before waiting on any of it so the solves can run side by side
+    let mut solves = Vec::with_capacity(shifted.len());
+    for (starts, query) in shifted {
+        match queue_solve(&jobs, query, &tenant, Some(user.clone())) {
+            Ok((_, done)) => solves.push((starts, done)),
+            Err(e) => return error_response(e.into()),
+        }
+    }
+
+    let mut months = Vec::with_capacity(solves.len());
+    for (starts, done) in solves {
+        months.push(SweepMonth::new(starts, solve_outcome(done.await)));
+    }
+
+    with_quota_headers(HttpResponse::Ok().json(MonthSweep::new(months)), tenant.quota(), &user)
+}
+
+#[get("/api/v1/jobs/{id}")]
+pub async fn job_status(req: HttpRequest, jobs: web::Data<JobManager>, path: web::Path<JobId>) -> impl Responder {
+    match jobs.status(path.into_inner(), &request_tenant(&req, &jobs)) {
+        Some(status) => HttpResponse::Ok().json(status),
+        None => error_response(ApiError::NotFound("No job with that ID".to_string())),
+    }
+}
+
+/// The query a job was submitted with, so the client can re-solve it with changes
+#[get("/api/v1/jobs/{id}/query")]
+pub async fn job_query(req: HttpRequest, jobs: web::Data<JobManager>, path: web::Path<JobId>) -> impl Responder {
+    match jobs.query(path.into_inner(), &request_tenant(&req, &jobs)) {
+        Some(query) => HttpResponse::Ok().json(query),
+        None => error_response(ApiError::NotFound("No job with that ID".to_string())),
+    }
+}
+
+/// Everything about a solved job's trip in one payload, see [summarize]
+#[get("/api/v1/jobs/{id}/summary")]
+pub async fn job_summary(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    directory: web::Data<AirportDirectory>,
+    path: web::Path<JobId>,
+) -> impl Responder {
+    match jobs.status(path.into_inner(), &request_tenant(&req, &jobs)) {
+        Some(JobStatus::Completed { solution }) => HttpResponse::Ok().json(summarize(&solution, &directory)),
+        Some(_) => error_response(ApiError::NotReady("Job hasn't solved a trip yet".to_string())),
+        None => error_response(ApiError::NotFound("No job with that ID".to_string())),
+    }
+}
+
+/// Route job `id` solved, if `tenant` submitted it
+fn solved_route(jobs: &JobManager, tenant: &Tenant, id: JobId) -> Result<RouteSolution, ApiError> {
+    match jobs.status(id, tenant) {
+        Some(JobStatus::Completed { solution }) => Ok(solution),
+        Some(_) => Err(ApiError::NotReady(format!("Job {} hasn't solved a trip yet", id))),
+        None => Err(ApiError::NotFound(format!("No job with ID {}", id))),
+    }
+}
+
+/// What changed between two solves of the same itinerary, from job `id`'s route to job `against`'s, see
+/// [diff_solutions]. Jobs which solved different itineraries are turned down, job IDs start over on a restart without
+/// a job store so `id` needn't be the job the client remembers.
+#[get("/api/v1/jobs/{id}/diff/{against}")]
+pub async fn job_diff(req: HttpRequest, jobs: web::Data<JobManager>, path: web::Path<(JobId, JobId)>) -> impl Responder {
+    let tenant = request_tenant(&req, &jobs);
+    let (id, against) = path.into_inner();
+    let diff = solved_route(&jobs, &tenant, id).and_then(|was| {
+        let now = solved_route(&jobs, &tenant, against)?;
+        let query = |id| jobs.query(id, &tenant).and_then(|query| serde_json::to_value(query).ok());
+        if query(id) != query(against) {
+            return Err(ApiError::Validation(format!("Jobs {} and {} solved different itineraries", id, against)));
+        }
+        Ok(diff_solutions(&was, &now, jobs.rates())?)
+    });
+    match diff {
+        Ok(diff) => HttpResponse::Ok().json(diff),
+        Err(e) => error_response(e),
+    }
+}
+
+/// What changed from the posted route, one the client kept from an earlier solve of the itinerary, to job `id`'s, see
+/// [diff_solutions]. Unlike [job_diff] the earlier solve's job needn't still be around.
+#[post("/api/v1/jobs/{id}/diff")]
+pub async fn diff_against(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    path: web::Path<JobId>,
+    json: web::Json<RouteSolution>,
+) -> impl Responder {
+    let tenant = request_tenant(&req, &jobs);
+    let diff = solved_route(&jobs, &tenant, path.into_inner())
+        .and_then(|now| Ok(diff_solutions(&json, &now, jobs.rates())?));
+    match diff {
+        Ok(diff) => HttpResponse::Ok().json(diff),
+        Err(e) => error_response(e),
+    }
+}
+
+/// What a finished job's trip would cost with one thing about its itinerary changed, see [WhatIfDelta], and how that
+/// differs from the job's route. The changed itinerary is solved like any other, but most of its legs were priced for
+/// the job and are still in the price cache, so only the legs the change brings in are looked up.
+///
+/// [WhatIfDelta]: route_solver_shared::queries::WhatIfDelta
+#[post("/api/v1/whatif")]
+pub async fn what_if(req: HttpRequest, jobs: web::Data<JobManager>, json: web::Json<WhatIfQuery>) -> impl Responder {
+    let tenant = request_tenant(&req, &jobs);
+    let WhatIfQuery { job, delta } = json.into_inner();
+    let was = match jobs.status(job, &tenant) {
+        Some(JobStatus::Completed { solution }) => solution,
+        Some(_) => return error_response(ApiError::NotReady("Job hasn't solved a trip yet".to_string())),
+        None => return error_response(ApiError::NotFound("No job with that ID".to_string())),
+    };
+    // Already checked and localized when the job was submitted
+    let Some(mut query) = jobs.query(job, &tenant) else {
+        return error_response(ApiError::NotFound("No job with that ID".to_string()));
+    };
+    if !query.apply(&delta) {
+        return error_response(ApiError::Validation(
+            "The itinerary has no such stop, stops count from 0 at the start and only those between the start and end can be dropped"
+                .to_string(),
+        ));
+    }
+    if let Some(error) = invalid_query(&jobs, &query) {
+        return error_response(error);
+    }
+    if let Some(error) = unconfirmed_solves(&req, &jobs, [&query]) {
+        return error_response(error);
+    }
+    let user = quota_user(&req, &tenant);
+    if tenant.quota().remaining(&user) == Some(0) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+
+    let (id, done) = match queue_solve(&jobs, query, &tenant, Some(user.clone())) {
+        Ok(queued) => queued,
+        Err(e) => return error_response(e.into()),
+    };
+    let what_if = solve_outcome(done.await).and_then(|mut solution| {
+        solution.request_id = request_id::current();
+        Ok(WhatIf {
+            job: id,
+            diff: diff_solutions(&was, &solution, jobs.rates())?,
+            solution,
+        })
+    });
+    let resp = match what_if {
+        Ok(what_if) => HttpResponse::Ok().json(what_if),
+        Err(e) => error_response(e),
+    };
+
+    with_quota_headers(resp, tenant.quota(), &user)
+}
+
+#[derive(Deserialize)]
+pub struct CalendarQuery {
+    src: AirportCode,
+    dest: AirportCode,
+    date: Date,
+    /// Days either side of `date` to price
+    #[serde(default = "default_calendar_days")]
+    days: u64,
+    /// Currency to give prices in, the providers' own if unset
+    currency: Option<String>,
+    /// Where the fares are bought, taken from `Accept-Language` if unset, see [RouteQuery::market]
+    market: Option<String>,
+    locale: Option<String>,
+}
+
+fn default_calendar_days() -> u64 {
+    7
+}
+
+/// Prices of a leg across the days around a date, charged to the user's quota like a solve
+#[get("/api/v1/calendar")]
+pub async fn calendar(req: HttpRequest, jobs: web::Data<JobManager>, query: web::Query<CalendarQuery>) -> impl Responder {
+    let invalid = invalid_point_of_sale(query.market.as_deref(), query.locale.as_deref())
+        .or_else(|| unsupported_currency(&jobs, query.currency.as_deref()));
+    if let Some(error) = invalid {
+        return error_response(error);
+    }
+    let tenant = request_tenant(&req, &jobs);
+    let user = quota_user(&req, &tenant);
+    if tenant.quota().remaining(&user) == Some(0) {
+        return with_quota_headers(quota_exceeded_response(), tenant.quota(), &user);
+    }
+
+    let detected = accept_language(&req).and_then(locale::from_accept_language).unwrap_or_default();
+    let pos = PointOfSale {
+        market: query.market.as_deref().map_or(detected.market, str::to_uppercase),
+        locale: query.locale.clone().unwrap_or(detected.locale),
+    };
+    let around = Flight {
+        src: query.src,
+        dest: query.dest,
+        date: query.date,
+    };
+    let mut days = jobs.price_provider().calendar(&jobs, &tenant, user.clone(), &pos, around, query.days).await;
+    if let Some(currency) = &query.currency {
+        for day in &mut days {
+            day.price = day.price.and_then(|p| jobs.rates().convert_price(p, currency).ok());
+        }
+    }
+
+    with_quota_headers(HttpResponse::Ok().json(days), tenant.quota(), &user)
+}
+
+#[get("/api/v1/metrics")]
+pub async fn metrics(jobs: web::Data<JobManager>) -> impl Responder {
+    HttpResponse::Ok().json(jobs.metrics())
+}
+
+#[derive(Deserialize)]
+pub struct AirportsQuery {
+    /// Comma separated IATA codes, every known airport if left out
+    codes: Option<String>,
+}
+
+/// Locations of airports, unknown codes are left out of the response
+#[get("/api/v1/airports")]
+pub async fn airports(directory: web::Data<AirportDirectory>, query: web::Query<AirportsQuery>) -> impl Responder {
+    let airports = match &query.codes {
+        Some(codes) => directory.lookup(codes.split(',')),
+        None => directory.all(),
+    };
+    HttpResponse::Ok().json(airports)
+}
+
+/// Currencies solutions can be priced in
+#[get("/api/v1/currencies")]
+pub async fn currencies(jobs: web::Data<JobManager>) -> impl Responder {
+    HttpResponse::Ok().json(jobs.rates().supported())
+}
+
+/// Example itineraries the builder can start from
+#[get("/api/v1/templates")]
+pub async fn templates(library: web::Data<TemplateLibrary>) -> impl Responder {
+    HttpResponse::Ok().json(library.all())
+}
+
+/// Whether the request carries the configured admin bearer token
+fn is_admin(req: &HttpRequest, admin: &AdminConfig) -> bool {
+    let Some(token) = &admin.token else {
+        return false;
+    };
+
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    // Compare digests so the check doesn't leak how much of the token matched
+    given.is_some_and(|given| Sha256::digest(given) == Sha256::digest(token))
+}
+
+/// Who an admin request is recorded in the audit log as. There's one admin token, so admins are told apart by their IP.
+fn admin_user(req: &HttpRequest) -> String {
+    match req.peer_addr() {
+        Some(addr) => format!("admin:{}", addr.ip()),
+        None => "admin:unknown".to_string(),
+    }
+}
+
+/// Aggregate solve, cache and provider statistics for the admin page
+#[get("/api/admin/stats")]
+pub async fn admin_stats(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    admin: web::Data<AdminConfig>,
+) -> impl Responder {
+    if let Err(error) = require_admin(&req, &admin) {
+        return error_response(error);
+    }
+
+    HttpResponse::Ok().json(jobs.admin_stats())
+}
+
+/// Entries [audit_log] returns when the request doesn't say how many
+const AUDIT_PAGE: usize = 1000;
+
+/// Audit log entries the query's filter matches, the latest [AUDIT_PAGE] unless it sets a limit. See
+/// [crate::audit::AuditFilter] for the parameters.
+#[get("/api/admin/audit")]
+pub async fn audit_log(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    admin: web::Data<AdminConfig>,
+    filter: web::Query<AuditFilter>,
+) -> impl Responder {
+    if let Err(error) = require_admin(&req, &admin) {
+        return error_response(error);
+    }
+    let mut filter = filter.into_inner();
+    filter.limit = filter.limit.or(Some(AUDIT_PAGE));
+
+    match jobs.audit().entries(&filter) {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => error_response(ApiError::Internal(format!("Couldn't read the audit log: {}", e))),
+    }
+}
+
+/// Largest snapshot [import_snapshot] takes
+const MAX_SNAPSHOT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Admin endpoints don't exist unless an admin token is configured, and want it otherwise
+fn require_admin(req: &HttpRequest, admin: &AdminConfig) -> Result<(), ApiError> {
+    if admin.token.is_none() {
+        return Err(ApiError::NotFound(String::new()));
+    }
+    if !is_admin(req, admin) {
+        return Err(ApiError::Unauthorized("Admin token required".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct SnapshotQuery {
+    /// Comma separated IATA codes, flights between any airports if left out
+    airports: Option<String>,
+    /// First and last departure dates, inclusive
+    from: Option<Date>,
+    to: Option<Date>,
+}
+
+impl SnapshotQuery {
+    fn filter(&self) -> Result<SnapshotFilter, ApiError> {
+        let codes = match &self.airports {
+            Some(codes) => Some(
+                codes
+                    .split(',')
+                    .map(|code| code.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| ApiError::Validation(format!("Invalid airport in filter: {}", e)))?,
+            ),
+            None => None,
+        };
+        Ok(SnapshotFilter {
+            airports: codes,
+            from: self.from,
+            to: self.to,
+        })
+    }
+}
+
+/// The cached fares for flights between the given airports on the given dates, for importing into another server
+#[get("/api/admin/snapshot")]
+pub async fn export_snapshot(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    admin: web::Data<AdminConfig>,
+    query: web::Query<SnapshotQuery>,
+) -> impl Responder {
+    if let Err(error) = require_admin(&req, &admin) {
+        return error_response(error);
+    }
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(error) => return error_response(error),
+    };
+
+    match jobs.cache().export(&filter) {
+        Ok(snapshot) => {
+            let export = AuditEvent::Export { what: "snapshot".to_string() };
+            jobs.audit().record(None, Some(&admin_user(&req)), export);
+            HttpResponse::Ok()
+            .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"price-snapshot.json\""))
+                .json(snapshot)
+        }
+        Err(e) => error_response(ApiError::Internal(format!("Couldn't read the price cache: {}", e))),
+    }
+}
+
+#[derive(Serialize)]
+struct ImportedSnapshot {
+    quotes: usize,
+    no_fares: usize,
+}
+
+/// Cache the fares in a snapshot exported by [export_snapshot], over any cached for the same flights
+#[post("/api/admin/snapshot")]
+pub async fn import_snapshot(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    admin: web::Data<AdminConfig>,
+    payload: web::Payload,
+) -> impl Responder {
+    if let Err(error) = require_admin(&req, &admin) {
+        return error_response(error);
+    }
+    // Snapshots run well past the JSON extractor's limit, which suits every other endpoint
+    let body = match payload.to_bytes_limited(MAX_SNAPSHOT_BYTES).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return error_response(ApiError::Validation(format!("Couldn't read snapshot: {}", e))),
+        Err(_) => {
+            let limit = MAX_SNAPSHOT_BYTES / (1024 * 1024);
+            return error_response(ApiError::Validation(format!("Snapshot is larger than {} MiB", limit)));
+        }
+    };
+    let snapshot = serde_json::from_slice::<PriceSnapshot>(&body)
+        .map_err(SnapshotError::Parse)
+        .and_then(|snapshot| snapshot.check_version().map(|_| snapshot));
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => return error_response(ApiError::Validation(e.to_string())),
+    };
+
+    jobs.cache().import(&snapshot);
+    log!("Imported {} cached fares from a snapshot", snapshot.len());
+    HttpResponse::Ok().json(ImportedSnapshot {
+        quotes: snapshot.quotes.len(),
+        no_fares: snapshot.no_fares.len(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    airports: Option<String>,
+    from: Option<Date>,
+    to: Option<Date>,
+}
+
+/// Cached quotes or the legs of finished solves as a CSV, JSON or Parquet table, for analysis. Takes the same filters as
+/// [export_snapshot].
+#[get("/api/admin/export/{table}")]
+pub async fn export_table(
+    req: HttpRequest,
+    jobs: web::Data<JobManager>,
+    admin: web::Data<AdminConfig>,
+    table: web::Path<ExportTable>,
+    query: web::Query<ExportQuery>,
+) -> impl Responder {
+    if let Err(error) = require_admin(&req, &admin) {
+        return error_response(error);
+    }
+    let query = query.into_inner();
+    let filter = SnapshotQuery {
+        airports: query.airports,
+        from: query.from,
+        to: query.to,
+    }
+    .filter();
+    let filter = match filter {
+        Ok(filter) => filter,
+        Err(error) => return error_response(error),
+    };
+
+    let mut body = Vec::new();
+    let written = match *table {
+        ExportTable::Quotes => match quote_rows(jobs.cache(), &filter) {
+            Ok(rows) => write_table(&rows, query.format, &mut body),
+            Err(e) => return error_response(ApiError::Internal(format!("Couldn't read the price cache: {}", e))),
+        },
+        ExportTable::Results => write_table(&leg_rows(jobs.statuses(), &filter), query.format, &mut body),
+    };
+    if let Err(e) = written {
+        return error_response(ApiError::Internal(format!("Couldn't write export: {}", e)));
+    }
+
+    let name = match *table {
+        ExportTable::Quotes => "quotes",
+        ExportTable::Results => "results",
+    };
+    let file = format!("{}.{}", name, query.format.extension());
+    jobs.audit().record(None, Some(&admin_user(&req)), AuditEvent::Export { what: file.clone() });
+    HttpResponse::Ok()
+        .content_type(query.format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file),
+        ))
+        .body(body)
 }
 
 #[post("/get_price")]
@@ -0,0 +1,475 @@
+//! Background worker pool solves are dispatched to, so long solves don't tie up the actix HTTP workers.
+//!
+//! Each worker is a dedicated OS thread driving its own single threaded tokio runtime. Tasks are handed over as a
+//! `Send` closure which builds the future on the worker thread, so the router (whose progress callbacks aren't `Send`)
+//! never has to be `Send` itself. Submissions go through a bounded queue per [Priority] and are rejected once theirs
+//! fills up. A free worker always takes an interactive task first, and only so many batch tasks run at once, so a backlog
+//! of batch work neither fills the interactive queue nor holds up its tasks for long. At least one worker is kept for
+//! interactive tasks, except in a pool of one, whose only worker runs both.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use route_solver_shared::queries::{ApiError, WorkerPoolMetrics};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{sync::Notify, task::LocalSet};
+
+use crate::config::WorkerConfig;
+
+/// Unit of work run on a worker, the closure is called on the worker thread to produce the future
+pub type Task = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+#[derive(Debug, Error)]
+pub enum PoolError {
+    #[error("Worker pool queue is full.")]
+    QueueFull,
+    #[error("Worker pool has shut down.")]
+    Closed,
+}
+
+impl From<PoolError> for ApiError {
+    fn from(e: PoolError) -> ApiError {
+        ApiError::Unavailable(e.to_string())
+    }
+}
+
+/// Which queue a task waits in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Someone is waiting on it, e.g. a solve from the builder
+    #[default]
+    Interactive,
+    /// Nobody is, e.g. a scheduled re-solve. Only runs on up to [WorkerConfig::batch_concurrency] workers.
+    Batch,
+}
+
+#[derive(Default)]
+struct ClassQueue {
+    tasks: VecDeque<Task>,
+    running: usize,
+}
+
+#[derive(Default)]
+struct Queues {
+    interactive: ClassQueue,
+    batch: ClassQueue,
+}
+
+impl Queues {
+    fn class(&mut self, priority: Priority) -> &mut ClassQueue {
+        match priority {
+            Priority::Interactive => &mut self.interactive,
+            Priority::Batch => &mut self.batch,
+        }
+    }
+
+    /// Task a free worker should run next, `None` if there's none it may
+    fn next(&mut self, batch_concurrency: usize) -> Option<(Priority, Task)> {
+        let priority = if !self.interactive.tasks.is_empty() {
+            Priority::Interactive
+        } else if self.batch.running < batch_concurrency {
+            Priority::Batch
+        } else {
+            return None;
+        };
+        let class = self.class(priority);
+        let task = class.tasks.pop_front()?;
+        class.running += 1;
+        Some((priority, task))
+    }
+
+    fn queued(&self) -> usize {
+        self.interactive.tasks.len() + self.batch.tasks.len()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.queued() == 0 && self.interactive.running == 0 && self.batch.running == 0
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    queues: Mutex<Queues>,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    closed: AtomicBool,
+    /// Set once every handle to the pool is gone, the workers exit when they've run what's left
+    dropped: AtomicBool,
+    /// Woken every time a task is queued or finishes
+    changed: Notify,
+}
+
+/// Shared by every clone of a [WorkerPool], stops the workers when the last one is dropped
+struct PoolHandle(Arc<PoolState>);
+
+impl Drop for PoolHandle {
+    fn drop(&mut self) {
+        self.0.dropped.store(true, Ordering::SeqCst);
+        self.0.changed.notify_waiters();
+    }
+}
+
+#[derive(Clone)]
+pub struct WorkerPool {
+    state: Arc<PoolState>,
+    _handle: Arc<PoolHandle>,
+    concurrency: usize,
+    queue_capacity: usize,
+    batch_concurrency: usize,
+    batch_queue_capacity: usize,
+}
+
+impl WorkerPool {
+    pub fn new(config: &WorkerConfig) -> WorkerPool {
+        let concurrency = config.concurrency.max(1);
+        // One worker is kept from batch tasks, so batch work never holds up an interactive task behind it
+        let most_batch = (concurrency - 1).max(1);
+        let batch_concurrency = config.batch_concurrency.unwrap_or(concurrency / 2).clamp(1, most_batch);
+        let queue_capacity = config.queue_capacity.max(1);
+        let batch_queue_capacity = config.batch_queue_capacity.max(1);
+
+        let state = Arc::new(PoolState::default());
+        for idx in 0..concurrency {
+            let state = Arc::clone(&state);
+            thread::Builder::new()
+                .name(format!("solver-worker-{}", idx))
+                .spawn(move || worker_loop(state, batch_concurrency))
+                .expect("Failed to spawn solver worker thread");
+        }
+
+        WorkerPool {
+            _handle: Arc::new(PoolHandle(Arc::clone(&state))),
+            state,
+            concurrency,
+            queue_capacity,
+            batch_concurrency,
+            batch_queue_capacity,
+        }
+    }
+
+    fn capacity(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::Interactive => self.queue_capacity,
+            Priority::Batch => self.batch_queue_capacity,
+        }
+    }
+
+    /// Queue a task, fails immediately rather than waiting if its priority's queue is full.
+    pub fn submit(&self, priority: Priority, task: Task) -> Result<(), PoolError> {
+        if self.state.closed.load(Ordering::SeqCst) {
+            return Err(PoolError::Closed);
+        }
+
+        {
+            let mut queues = self.state.queues.lock().unwrap();
+            let class = queues.class(priority);
+            if class.tasks.len() >= self.capacity(priority) {
+                return Err(PoolError::QueueFull);
+            }
+            class.tasks.push_back(task);
+        }
+        self.state.changed.notify_waiters();
+        Ok(())
+    }
+
+    /// Wait until the queue for `priority` has room for another task, or the pool is closed. For submitters who can
+    /// hold on to their work rather than have it rejected.
+    pub async fn wait_for_room(&self, priority: Priority) {
+        loop {
+            // Register before checking so a task starting in between isn't missed
+            let changed = self.state.changed.notified();
+            let queued = self.state.queues.lock().unwrap().class(priority).tasks.len();
+            if queued < self.capacity(priority) || self.state.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    /// Stop accepting new tasks, tasks already queued still run.
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::SeqCst);
+        self.state.changed.notify_waiters();
+    }
+
+    /// Wait for the queue to empty and all running tasks to finish, up to `timeout`.
+    ///
+    /// Returns whether the pool went idle in time.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let drained = async {
+            loop {
+                // Register before checking so a task finishing in between isn't missed
+                let changed = self.state.changed.notified();
+                if self.state.queues.lock().unwrap().is_idle() {
+                    break;
+                }
+                changed.await;
+            }
+        };
+
+        tokio::time::timeout(timeout, drained).await.is_ok()
+    }
+
+    pub fn metrics(&self) -> WorkerPoolMetrics {
+        let queues = self.state.queues.lock().unwrap();
+        WorkerPoolMetrics {
+            concurrency: self.concurrency,
+            queue_capacity: self.queue_capacity,
+            queued: queues.queued(),
+            running: queues.interactive.running + queues.batch.running,
+            batch_concurrency: self.batch_concurrency,
+            batch_queue_capacity: self.batch_queue_capacity,
+            batch_queued: queues.batch.tasks.len(),
+            batch_running: queues.batch.running,
+            completed: self.state.completed.load(Ordering::SeqCst),
+            panicked: self.state.panicked.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn worker_loop(state: Arc<PoolState>, batch_concurrency: usize) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build solver worker runtime");
+
+    LocalSet::new().block_on(&rt, async move {
+        loop {
+            let changed = state.changed.notified();
+            let next = state.queues.lock().unwrap().next(batch_concurrency);
+            let Some((priority, task)) = next else {
+                if state.dropped.load(Ordering::SeqCst) && state.queues.lock().unwrap().queued() == 0 {
+                    break;
+                }
+                changed.await;
+                continue;
+            };
+
+            // Spawning the task isolates the worker from panics inside a solve
+            let res = tokio::task::spawn_local(task()).await;
+
+            match res {
+                Ok(_) => state.completed.fetch_add(1, Ordering::SeqCst),
+                Err(_) => state.panicked.fetch_add(1, Ordering::SeqCst),
+            };
+            state.queues.lock().unwrap().class(priority).running -= 1;
+            state.changed.notify_waiters();
+        }
+    });
+}
+
+#[cfg(test)]
+mod worker_pool_tests {
+    use std::time::Duration;
+
+    use tokio::sync::oneshot;
+
+    use crate::config::WorkerConfig;
+
+    use super::{PoolError, Priority, WorkerPool};
+
+    #[tokio::test]
+    async fn test_pool_runs_tasks_and_survives_panics() {
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 1,
+            queue_capacity: 4,
+            ..WorkerConfig::default()
+        });
+
+        pool.submit(Priority::Interactive, Box::new(|| Box::pin(async { panic!("Solve blew up") })))
+            .unwrap();
+
+        let (tx, rx) = oneshot::channel();
+        pool.submit(Priority::Interactive, Box::new(move || {
+            Box::pin(async move {
+                let _ = tx.send(42);
+            })
+        }))
+        .unwrap();
+
+        assert_eq!(rx.await.unwrap(), 42);
+
+        assert!(pool.wait_idle(Duration::from_secs(1)).await);
+        let metrics = pool.metrics();
+        assert_eq!(metrics.completed, 1);
+        assert_eq!(metrics.panicked, 1);
+        assert_eq!(metrics.queued, 0);
+        assert_eq!(metrics.running, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_rejects_when_queue_full() {
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 1,
+            queue_capacity: 1,
+            ..WorkerConfig::default()
+        });
+
+        // Park the only worker until we release it
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        pool.submit(Priority::Interactive, Box::new(move || {
+            Box::pin(async move {
+                let _ = started_tx.send(());
+                let _ = release_rx.await;
+            })
+        }))
+        .unwrap();
+        started_rx.await.unwrap();
+
+        pool.submit(Priority::Interactive, Box::new(|| Box::pin(async {}))).unwrap();
+        assert!(matches!(
+            pool.submit(Priority::Interactive, Box::new(|| Box::pin(async {}))),
+            Err(PoolError::QueueFull)
+        ));
+        assert_eq!(pool.metrics().queued, 1);
+
+        release_tx.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_closed_pool_rejects_and_drains() {
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 1,
+            queue_capacity: 4,
+            ..WorkerConfig::default()
+        });
+
+        pool.submit(Priority::Interactive, Box::new(|| {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            })
+        }))
+        .unwrap();
+        pool.close();
+
+        assert!(matches!(
+            pool.submit(Priority::Interactive, Box::new(|| Box::pin(async {}))),
+            Err(PoolError::Closed)
+        ));
+        assert!(pool.wait_idle(Duration::from_secs(1)).await);
+        assert_eq!(pool.metrics().completed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_tasks_leave_workers_free() {
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 2,
+            queue_capacity: 4,
+            batch_concurrency: Some(1),
+            batch_queue_capacity: 1,
+            ..WorkerConfig::default()
+        });
+
+        // A batch task holds its one worker, the next batch task waits even though the other worker is free
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        pool.submit(Priority::Batch, Box::new(move || {
+            Box::pin(async move {
+                let _ = started_tx.send(());
+                let _ = release_rx.await;
+            })
+        }))
+        .unwrap();
+        started_rx.await.unwrap();
+        let (batch_tx, batch_rx) = oneshot::channel();
+        pool.submit(Priority::Batch, Box::new(move || {
+            Box::pin(async move {
+                let _ = batch_tx.send(());
+            })
+        }))
+        .unwrap();
+        assert!(matches!(
+            pool.submit(Priority::Batch, Box::new(|| Box::pin(async {}))),
+            Err(PoolError::QueueFull)
+        ));
+
+        // Interactive tasks have their own queue and the free worker
+        let (tx, rx) = oneshot::channel();
+        pool.submit(Priority::Interactive, Box::new(move || {
+            Box::pin(async move {
+                let _ = tx.send(42);
+            })
+        }))
+        .unwrap();
+        assert_eq!(rx.await.unwrap(), 42);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.batch_running, metrics.batch_queued), (1, 1));
+
+        release_tx.send(()).unwrap();
+        pool.wait_for_room(Priority::Batch).await;
+        batch_rx.await.unwrap();
+        assert!(pool.wait_idle(Duration::from_secs(1)).await);
+        assert_eq!(pool.metrics().completed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_interactive_worker_reserved() {
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 4,
+            batch_concurrency: Some(4),
+            ..WorkerConfig::default()
+        });
+        assert_eq!((pool.metrics().concurrency, pool.metrics().batch_concurrency), (4, 3));
+
+        // A batch task holding its worker leaves the other for interactive tasks
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 2,
+            batch_concurrency: Some(2),
+            ..WorkerConfig::default()
+        });
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let (started_tx, started_rx) = oneshot::channel::<()>();
+        pool.submit(Priority::Batch, Box::new(move || {
+            Box::pin(async move {
+                let _ = started_tx.send(());
+                let _ = release_rx.await;
+            })
+        }))
+        .unwrap();
+        started_rx.await.unwrap();
+        let (tx, rx) = oneshot::channel();
+        pool.submit(Priority::Interactive, Box::new(move || {
+            Box::pin(async move {
+                let _ = tx.send(42);
+            })
+        }))
+        .unwrap();
+        assert_eq!(rx.await.unwrap(), 42);
+
+        release_tx.send(()).unwrap();
+        assert!(pool.wait_idle(Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn test_single_worker_runs_batch_tasks() {
+        // Configured with one worker, which is left at one and runs batch tasks too
+        let pool = WorkerPool::new(&WorkerConfig {
+            concurrency: 1,
+            batch_concurrency: Some(1),
+            ..WorkerConfig::default()
+        });
+        assert_eq!((pool.metrics().concurrency, pool.metrics().batch_concurrency), (1, 1));
+
+        let (tx, rx) = oneshot::channel();
+        pool.submit(Priority::Batch, Box::new(move || {
+            Box::pin(async move {
+                let _ = tx.send(42);
+            })
+        }))
+        .unwrap();
+        assert_eq!(rx.await.unwrap(), 42);
+        assert!(pool.wait_idle(Duration::from_secs(1)).await);
+    }
+}
@@ -0,0 +1,303 @@
+# English messages for the frontend, the ones every other locale falls back on
+
+## Shared
+
+close = Close
+cancel = Cancel
+edit = Edit
+retry = Retry
+airport-code = Airport Code
+open = Open
+delete = Delete
+total = Total
+per-passenger = Per passenger
+back-to-builder = Back to the itinerary
+currency-label = Currency
+trip-untitled = Untitled trip
+language-label = Language
+
+## Errors
+
+error-hint-infeasible = Try widening the date windows or stay lengths, or dropping a stop.
+error-hint-provider = The flight price provider isn't answering, retry in a minute.
+error-hint-network = Check your connection and retry.
+
+## Solving
+
+solve-running = Solving, { $prices } prices checked and { $routes } routes explored
+solve-queued = Waiting for a free solver
+solve-elapsed = { $detail } ({ $seconds }s)
+announce-solving = Solving the route
+
+## Date inputs
+
+dates-start = Start
+dates-end = End
+dates-free-text = Or e.g. early June
+dates-no-today = Couldn't tell today's date
+dates-empty = No dates were given
+dates-unrecognized = Couldn't read "{ $text }" as dates, try 2024-06-01..2024-06-10, early June or any weekend in July
+dates-reversed = { $last } is before { $first }
+dates-span = { $first } to { $last }
+dates-span-with-gaps = { $first } to { $last }, including the days between
+
+## Leg table
+
+leg-explore-dates = Explore dates
+leg-date = Date
+leg-from = From
+leg-to = To
+leg-price = Price
+leg-reprice = Re-price
+leg-spread-title = How far the fare may have moved since it was quoted
+leg-overland = Overland
+leg-estimated = Estimated
+leg-estimated-title = No fare was quoted for this day, it's estimated from nearby days. Check it before booking
+leg-fallback = Backup provider
+leg-fallback-title = The usual provider couldn't price this flight, a backup one did
+leg-typical = Typical price
+leg-typical-title = No fare could be quoted for this day, this is what the route usually costs. Check it before booking
+leg-bag-included = Includes { $price } for a checked bag
+leg-stay-before = + { $price } stay before
+leg-alternative-dearer = Why not { $dest } on { $date }? It was { $price }, but the trip would cost at least { $saving } more
+leg-alternative-even = { $dest } on { $date } was { $price }, the trip could cost about the same
+leg-alternative-dead-end = Why not { $dest } on { $date }? It was { $price }, but no trip through it fits the dates
+leg-alternative-title = The cheapest other flight the search priced from here, and what taking this one saves on the whole trip
+
+## Talking to the server
+
+api-unreachable = Could not reach the server
+api-no-window = No window to fetch from
+api-bad-response = Unexpected response from the server
+api-no-such-leg = The trip has no leg from { $from } to { $to }
+
+## Itinerary builder
+
+builder-title = Itinerary
+builder-bad-date = "{ $text }" isn't a valid date
+builder-reversed-dates = { $first } is after { $last }
+builder-no-airport = Every row needs an airport code
+builder-bad-stay = Can't stay at { $airport }, { $reason }
+builder-too-few-rows = Add at least a start and an end airport
+builder-done = Done
+builder-duplicate = Duplicate
+builder-add-fly-in = Add fly in dates
+builder-add-fly-out = Add fly out dates
+builder-add-event = Add event dates
+builder-adults = Adults
+builder-children = Children
+builder-cabin = Cabin class
+builder-checked-bag = Include a checked bag
+builder-checked-bag-hint = Adds the airline's bag fee to each flight, estimated where the fare doesn't say
+builder-sampled = Quick search
+builder-sampled-hint = Prices every few days first, then only the days around the cheapest routes. Fewer fares to look up in wide windows, but a cheap day between can be missed
+builder-max-flights = Max flights
+builder-max-per-leg = Max per leg
+builder-max-per-leg-label = Max price per leg
+builder-any = Any
+builder-rest-days = No flying two days running
+builder-day-value = A day is worth
+builder-nothing = Nothing
+builder-return-to-start = Return to the start
+builder-return-to = Return to { $airport }
+builder-alternate-origins = Or start from, e.g. SEA, PDX
+builder-origin-penalty = Only start from { $airport } to save more than
+builder-round-trip = Return to origin
+builder-add-row = Add new row
+builder-go = Go!
+builder-save = Save trip
+builder-share = Share
+builder-saved = Saved to{" "}
+builder-saved-link = your trips
+remove-title = Remove this stop?
+remove-lost = Removing it loses:
+remove-airport = The airport, { $airport }
+remove-fly-in = Fly in dates, { $dates }
+remove-fly-out = Fly out dates, { $dates }
+remove-event = Event dates, { $dates }
+remove-confirm = Remove
+builder-row = Stop { $airport }
+builder-row-new = New stop
+builder-row-number = Stop { $number }
+builder-conflict = These dates clash with { $airports }, there's no getting from one to the other in time
+builder-remove-row = Remove this stop
+announce-row-added = Row added, { $rows ->
+    [one] { $rows } row
+   *[other] { $rows } rows
+}
+announce-row-duplicated = Row duplicated, { $rows } rows
+announce-row-removed = Row removed, { $rows ->
+    [one] { $rows } row
+   *[other] { $rows } rows
+}
+announce-template = { $rows ->
+    [one] { $rows } row
+   *[other] { $rows } rows
+} filled in from the template
+
+cabin-economy = Economy
+cabin-premium-economy = Premium economy
+cabin-business = Business
+cabin-first = First
+
+summary-arrive = arrive { $dates }
+summary-leave = leave { $dates }
+summary-event = event { $dates }
+summary-nights = { $nights ->
+    [one] 1 night
+   *[other] { $nights } nights
+}
+summary-nights-range = { $shortest }–{ $longest } nights
+
+lint-title = This itinerary might not be what you meant
+lint-solve-anyway = Solve anyway
+lint-short-stay = At most a night at { $airport } after flying { $distance } km to get there
+lint-wide-window = { $airport } has a { $days } day window, searching it will be slow and use many API calls
+lint-same-day = { $from } and { $to } have the same dates, the trip may have to leave { $from } the day it gets there
+
+feasibility-none = No order of the stops fits their dates.
+feasibility-conflict = { $first } and { $second }: whichever comes first can't be left in time to reach the other
+feasibility-fares = The dates fit, a solve may look up to { $legs } fares.
+feasibility-unknown = Too many ways to order the stops to check their dates quickly.
+
+calls-title = Solve this itinerary?
+calls-estimate = Solving it may look up as many as { $calls } fares, { $cached } of the { $legs } it could need are already known.
+calls-remaining = You have { $remaining } lookups left today.
+calls-confirm = Solve
+
+template-label = Start from a template
+template-prompt = Start from a template...
+
+## Builder tabs
+
+workspace-tabs = Trips
+workspace-first-tab = Trip 1
+workspace-new-name = Trip { $number }
+workspace-copy-name = { $name } (copy)
+workspace-new = New trip
+workspace-duplicate = Duplicate trip
+workspace-rename = Rename
+workspace-name = Trip name
+workspace-close = Close { $name }
+workspace-close-title = Close this trip?
+workspace-close-lost = { $name } and its itinerary will be gone for good.
+workspace-close-confirm = Close trip
+
+## Results
+
+results-title = Results
+results-best = Best route found: { $price }
+results-party-total = {" "}per passenger, { $price } for all { $passengers }
+results-was = {" "}(was { $price })
+results-origin = Starting from { $airport }
+results-summary = { $days } days, { $nights } nights in { $stops } stops, { $distance } km, ~{ $co2 } kg CO₂ per passenger
+print = Print / Save as PDF
+print-scan = Scan to open this trip
+
+budget-title = Budget
+budget-destination = Destination
+budget-getting-there = Getting there
+budget-nights = Nights
+budget-lodging = Lodging
+budget-share = Share
+budget-running-total = Running total
+
+timeline-title = Timeline
+timeline-nights = { $nights ->
+    [0] same day
+    [one] 1 night
+   *[other] { $nights } nights
+}
+timeline-stay = { $city }: { $arrive } to { $depart }
+timeline-flight = { $date }: { $from } -> { $to }
+
+compare-title = Compare routes
+compare-best = Best
+compare-days = { $days } days
+compare-route = Route
+compare-length = Length
+compare-legs = Legs
+compare-nights-per-city = Nights per city
+compare-show-legs = Show legs
+compare-hide-legs = Hide legs
+
+explore-title = { $from } -> { $to } around { $date }
+explore-no-prices = No prices found around this date.
+explore-chart-label = Prices from { $first } to { $last }
+explore-loading = Loading prices
+explore-pin = Fly on { $date } and re-solve
+
+tweak-title = What if…
+tweak-hint = Try one change to the trip. Only the flights it changes are looked up, the rest are already priced.
+tweak-earlier = { $stop } a day earlier
+tweak-later = { $stop } a day later
+tweak-skip = Skip { $stop }
+tweak-direct = Direct flights only
+tweak-loading = Loading the itinerary
+tweak-pricing = Pricing the change
+tweak-result = { $change }: { $price }
+tweak-keep = Keep this route
+
+details-title = Solve details
+details-solve-time = Solve time
+details-pricing = Waiting on prices
+details-searching = Searching
+details-lookups = Price lookups
+details-cache = Cache hit rate
+details-no-cache = No cache
+details-cache-rate = { $percent }% ({ $hits } of { $lookups })
+details-expanded = Nodes expanded
+details-pruned = Nodes pruned
+details-pruned-counts = { $total } ({ $strategy } by the search, { $limits } over limits, { $leg_price } over leg prices, { $late } landing late, { $no_fare } without fares)
+details-queue = Largest queue
+details-degraded = Legs the provider failed to price
+details-throttle = Quota running low
+details-throttle-sampled = Only some days were priced, the others estimated from them
+details-throttle-narrowed = Only some days and the cheapest routes so far were priced
+details-throttle-exhausted = The quota ran out, fares from then on were estimated
+details-request-id = Request ID
+
+## Saved trips and searches
+
+saved-title = Saved trips
+saved-empty = Nothing saved yet, trips saved from the{" "}
+saved-empty-link = itinerary
+saved-empty-end = {" "}show up here.
+
+searches-title = My searches
+searches-empty = No searches yet, solves run from the{" "}
+searches-no-route = No route found
+searches-run-again = Run again
+searches-compare = Compare with now
+
+diff-title = Then and now
+announce-diff-ready = Comparison ready
+diff-searched = { $name }, searched { $time }
+diff-missing = That search isn't in your history any more.
+diff-unsolved = That search didn't find a route to compare against.
+diff-back = Back to my searches
+diff-leg = Leg
+diff-then = Then
+diff-now = Now
+diff-change = Change
+diff-new-leg = New leg
+diff-dropped-leg = No longer flown
+diff-later = { $days ->
+    [one] +1 day
+   *[other] +{ $days } days
+}
+diff-earlier = { $days ->
+    [one] -1 day
+   *[other] -{ $days } days
+}
+
+shared-title = Shared trip
+shared-broken = This link doesn't contain a trip, it may have been cut short when it was copied.
+shared-airport = Airport
+shared-fly-in = Fly in
+shared-fly-out = Fly out
+shared-event = Event
+shared-any-time = Any time
+shared-edit-copy = Edit a copy
+
+not-found-title = Page not found
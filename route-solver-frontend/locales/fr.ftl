@@ -0,0 +1,303 @@
+# Messages en français du frontend, ceux qui manquent sont affichés en anglais
+
+## Shared
+
+close = Fermer
+cancel = Annuler
+edit = Modifier
+retry = Réessayer
+airport-code = Code d'aéroport
+open = Ouvrir
+delete = Supprimer
+total = Total
+per-passenger = Par passager
+back-to-builder = Retour à l'itinéraire
+currency-label = Devise
+trip-untitled = Voyage sans titre
+language-label = Langue
+
+## Errors
+
+error-hint-infeasible = Essayez d'élargir les dates ou les durées de séjour, ou de retirer une étape.
+error-hint-provider = Le fournisseur de tarifs ne répond pas, réessayez dans une minute.
+error-hint-network = Vérifiez votre connexion et réessayez.
+
+## Solving
+
+solve-running = Calcul en cours, { $prices } prix vérifiés et { $routes } itinéraires explorés
+solve-queued = En attente d'un calculateur libre
+solve-elapsed = { $detail } ({ $seconds } s)
+announce-solving = Calcul de l'itinéraire
+
+## Date inputs
+
+dates-start = Début
+dates-end = Fin
+dates-free-text = Ou p. ex. early June
+dates-no-today = Impossible de connaître la date du jour
+dates-empty = Aucune date n'a été donnée
+dates-unrecognized = Impossible de lire « { $text } » comme des dates, essayez 2024-06-01..2024-06-10, early June ou any weekend in July
+dates-reversed = Le { $last } est avant le { $first }
+dates-span = Du { $first } au { $last }
+dates-span-with-gaps = Du { $first } au { $last }, jours intermédiaires compris
+
+## Leg table
+
+leg-explore-dates = Explorer les dates
+leg-date = Date
+leg-from = De
+leg-to = À
+leg-price = Prix
+leg-reprice = Recalculer
+leg-spread-title = De combien le tarif a pu changer depuis sa cotation
+leg-overland = Par voie terrestre
+leg-estimated = Estimé
+leg-estimated-title = Aucun tarif n'a été coté pour ce jour, il est estimé à partir des jours voisins. Vérifiez-le avant de réserver
+leg-fallback = Fournisseur de secours
+leg-fallback-title = Le fournisseur habituel n'a pas pu coter ce vol, un fournisseur de secours l'a fait
+leg-typical = Prix habituel
+leg-typical-title = Aucun tarif n'a pu être coté pour ce jour, c'est ce que coûte habituellement ce trajet. Vérifiez-le avant de réserver
+leg-bag-included = Dont { $price } pour un bagage en soute
+leg-stay-before = + { $price } de séjour avant
+leg-alternative-dearer = Pourquoi pas { $dest } le { $date } ? C'était { $price }, mais le voyage coûterait au moins { $saving } de plus
+leg-alternative-even = { $dest } le { $date } était à { $price }, le voyage pourrait coûter à peu près autant
+leg-alternative-dead-end = Pourquoi pas { $dest } le { $date } ? C'était { $price }, mais aucun voyage par là ne respecte les dates
+leg-alternative-title = Le vol le moins cher parmi les autres cotés d'ici, et ce que prendre celui-ci fait économiser sur tout le voyage
+
+## Talking to the server
+
+api-unreachable = Impossible de joindre le serveur
+api-no-window = Aucune fenêtre depuis laquelle charger
+api-bad-response = Réponse inattendue du serveur
+api-no-such-leg = Le voyage n'a pas de trajet de { $from } à { $to }
+
+## Itinerary builder
+
+builder-title = Itinéraire
+builder-bad-date = « { $text } » n'est pas une date valide
+builder-reversed-dates = { $first } est après { $last }
+builder-no-airport = Chaque ligne doit avoir un code d'aéroport
+builder-bad-stay = Séjour impossible à { $airport } : { $reason }
+builder-too-few-rows = Ajoutez au moins un aéroport de départ et un d'arrivée
+builder-done = Terminé
+builder-duplicate = Dupliquer
+builder-add-fly-in = Ajouter des dates d'arrivée
+builder-add-fly-out = Ajouter des dates de départ
+builder-add-event = Ajouter des dates d'événement
+builder-adults = Adultes
+builder-children = Enfants
+builder-cabin = Classe de cabine
+builder-checked-bag = Inclure un bagage en soute
+builder-checked-bag-hint = Ajoute les frais de bagage de la compagnie à chaque vol, estimés quand le tarif ne les indique pas
+builder-sampled = Recherche rapide
+builder-sampled-hint = Cote d'abord un jour sur quelques-uns, puis seulement les jours autour des itinéraires les moins chers. Moins de tarifs à chercher sur de longues périodes, mais un jour bon marché entre les deux peut être manqué
+builder-max-flights = Vols max.
+builder-max-per-leg = Max. par trajet
+builder-max-per-leg-label = Prix max. par trajet
+builder-any = Illimité
+builder-rest-days = Pas de vol deux jours de suite
+builder-day-value = Une journée vaut
+builder-nothing = Rien
+builder-return-to-start = Retour au départ
+builder-return-to = Retour à { $airport }
+builder-alternate-origins = Ou partir de, p. ex. SEA, PDX
+builder-origin-penalty = Ne partir de { $airport } que pour économiser plus de
+builder-round-trip = Revenir au point de départ
+builder-add-row = Ajouter une ligne
+builder-go = C'est parti !
+builder-save = Enregistrer le voyage
+builder-share = Partager
+builder-saved = Enregistré dans{" "}
+builder-saved-link = vos voyages
+remove-title = Retirer cette étape ?
+remove-lost = La retirer fera perdre :
+remove-airport = L'aéroport, { $airport }
+remove-fly-in = Les dates d'arrivée, { $dates }
+remove-fly-out = Les dates de départ, { $dates }
+remove-event = Les dates d'événement, { $dates }
+remove-confirm = Retirer
+builder-row = Étape { $airport }
+builder-row-new = Nouvelle étape
+builder-row-number = Étape { $number }
+builder-conflict = Ces dates sont incompatibles avec { $airports }, impossible de passer de l'une à l'autre à temps
+builder-remove-row = Retirer cette étape
+announce-row-added = Ligne ajoutée, { $rows ->
+    [one] { $rows } ligne
+   *[other] { $rows } lignes
+}
+announce-row-duplicated = Ligne dupliquée, { $rows } lignes
+announce-row-removed = Ligne retirée, { $rows ->
+    [one] { $rows } ligne
+   *[other] { $rows } lignes
+}
+announce-template = { $rows ->
+    [one] { $rows } ligne remplie
+   *[other] { $rows } lignes remplies
+} à partir du modèle
+
+cabin-economy = Économique
+cabin-premium-economy = Économique premium
+cabin-business = Affaires
+cabin-first = Première
+
+summary-arrive = arrivée { $dates }
+summary-leave = départ { $dates }
+summary-event = événement { $dates }
+summary-nights = { $nights ->
+    [one] { $nights } nuit
+   *[other] { $nights } nuits
+}
+summary-nights-range = { $shortest } à { $longest } nuits
+
+lint-title = Cet itinéraire n'est peut-être pas ce que vous vouliez
+lint-solve-anyway = Calculer quand même
+lint-short-stay = Au plus une nuit à { $airport } après { $distance } km de vol pour y arriver
+lint-wide-window = { $airport } a une fenêtre de { $days } jours, la recherche sera lente et fera beaucoup d'appels d'API
+lint-same-day = { $from } et { $to } ont les mêmes dates, le voyage devra peut-être quitter { $from } le jour de l'arrivée
+
+feasibility-none = Aucun ordre des étapes ne correspond à leurs dates.
+feasibility-conflict = { $first } et { $second } : dans un sens comme dans l'autre, on ne peut pas repartir à temps pour rejoindre la suivante
+feasibility-fares = Les dates conviennent, une recherche peut consulter jusqu'à { $legs } tarifs.
+feasibility-unknown = Trop d'ordres possibles des étapes pour vérifier leurs dates rapidement.
+
+calls-title = Calculer cet itinéraire ?
+calls-estimate = Le calcul peut consulter jusqu'à { $calls } tarifs, { $cached } des { $legs } dont il pourrait avoir besoin sont déjà connus.
+calls-remaining = Il vous reste { $remaining } consultations aujourd'hui.
+calls-confirm = Calculer
+
+template-label = Partir d'un modèle
+template-prompt = Partir d'un modèle…
+
+## Builder tabs
+
+workspace-tabs = Voyages
+workspace-first-tab = Voyage 1
+workspace-new-name = Voyage { $number }
+workspace-copy-name = { $name } (copie)
+workspace-new = Nouveau voyage
+workspace-duplicate = Dupliquer le voyage
+workspace-rename = Renommer
+workspace-name = Nom du voyage
+workspace-close = Fermer { $name }
+workspace-close-title = Fermer ce voyage ?
+workspace-close-lost = { $name } et son itinéraire seront perdus pour de bon.
+workspace-close-confirm = Fermer le voyage
+
+## Results
+
+results-title = Résultats
+results-best = Meilleur itinéraire trouvé : { $price }
+results-party-total = {" "}par passager, { $price } pour les { $passengers }
+results-was = {" "}(auparavant { $price })
+results-origin = Au départ de { $airport }
+results-summary = { $days } jours, { $nights } nuits en { $stops } étapes, { $distance } km, ~{ $co2 } kg de CO₂ par passager
+print = Imprimer / Enregistrer en PDF
+print-scan = Scannez pour ouvrir ce voyage
+
+budget-title = Budget
+budget-destination = Destination
+budget-getting-there = Pour y aller
+budget-nights = Nuits
+budget-lodging = Hébergement
+budget-share = Part
+budget-running-total = Cumul
+
+timeline-title = Chronologie
+timeline-nights = { $nights ->
+    [0] même jour
+    [one] { $nights } nuit
+   *[other] { $nights } nuits
+}
+timeline-stay = { $city } : du { $arrive } au { $depart }
+timeline-flight = { $date } : { $from } -> { $to }
+
+compare-title = Comparer les itinéraires
+compare-best = Meilleur
+compare-days = { $days } jours
+compare-route = Itinéraire
+compare-length = Durée
+compare-legs = Trajets
+compare-nights-per-city = Nuits par ville
+compare-show-legs = Voir les trajets
+compare-hide-legs = Masquer les trajets
+
+explore-title = { $from } -> { $to } autour du { $date }
+explore-no-prices = Aucun prix trouvé autour de cette date.
+explore-chart-label = Prix du { $first } au { $last }
+explore-loading = Chargement des prix
+explore-pin = Partir le { $date } et recalculer
+
+tweak-title = Et si…
+tweak-hint = Essayez un changement au voyage. Seuls les vols qu'il modifie sont recherchés, les autres sont déjà cotés.
+tweak-earlier = { $stop } un jour plus tôt
+tweak-later = { $stop } un jour plus tard
+tweak-skip = Sauter { $stop }
+tweak-direct = Vols directs uniquement
+tweak-loading = Chargement de l'itinéraire
+tweak-pricing = Cotation du changement
+tweak-result = { $change } : { $price }
+tweak-keep = Garder cet itinéraire
+
+details-title = Détails du calcul
+details-solve-time = Durée du calcul
+details-pricing = Attente des prix
+details-searching = Recherche
+details-lookups = Prix consultés
+details-cache = Taux de succès du cache
+details-no-cache = Pas de cache
+details-cache-rate = { $percent } % ({ $hits } sur { $lookups })
+details-expanded = Nœuds développés
+details-pruned = Nœuds élagués
+details-pruned-counts = { $total } ({ $strategy } par la recherche, { $limits } hors limites, { $leg_price } trop chers, { $late } arrivés trop tard, { $no_fare } sans tarif)
+details-queue = File la plus longue
+details-degraded = Trajets que le fournisseur n'a pas pu coter
+details-throttle = Quota presque épuisé
+details-throttle-sampled = Seuls certains jours ont été cotés, les autres estimés à partir d'eux
+details-throttle-narrowed = Seuls certains jours et les itinéraires les moins chers jusque-là ont été cotés
+details-throttle-exhausted = Le quota a été épuisé, les tarifs suivants ont été estimés
+details-request-id = ID de requête
+
+## Saved trips and searches
+
+saved-title = Voyages enregistrés
+saved-empty = Rien d'enregistré pour l'instant, les voyages enregistrés depuis l'
+saved-empty-link = itinéraire
+saved-empty-end = {" "}apparaissent ici.
+
+searches-title = Mes recherches
+searches-empty = Aucune recherche pour l'instant, les calculs lancés depuis l'
+searches-no-route = Aucun itinéraire trouvé
+searches-run-again = Relancer
+searches-compare = Comparer avec maintenant
+
+diff-title = Avant et maintenant
+announce-diff-ready = Comparaison prête
+diff-searched = { $name }, recherché le { $time }
+diff-missing = Cette recherche n'est plus dans votre historique.
+diff-unsolved = Cette recherche n'a trouvé aucun itinéraire à comparer.
+diff-back = Retour à mes recherches
+diff-leg = Trajet
+diff-then = Avant
+diff-now = Maintenant
+diff-change = Écart
+diff-new-leg = Nouveau trajet
+diff-dropped-leg = Plus emprunté
+diff-later = { $days ->
+    [one] +{ $days } jour
+   *[other] +{ $days } jours
+}
+diff-earlier = { $days ->
+    [one] -{ $days } jour
+   *[other] -{ $days } jours
+}
+
+shared-title = Voyage partagé
+shared-broken = Ce lien ne contient pas de voyage, il a peut-être été tronqué lors de la copie.
+shared-airport = Aéroport
+shared-fly-in = Arrivée
+shared-fly-out = Départ
+shared-event = Événement
+shared-any-time = N'importe quand
+shared-edit-copy = Modifier une copie
+
+not-found-title = Page introuvable
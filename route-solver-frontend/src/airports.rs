@@ -0,0 +1,50 @@
+//! Airport details from the server, to show places by name rather than by bare code.
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use route_solver_shared::queries::{Airport, AirportCode, ApiError, RouteSolution};
+use yew::prelude::*;
+
+use crate::api::{get_json, parse_json};
+
+/// Known airports by IATA code, codes the server doesn't know are missing
+pub type Airports = Rc<HashMap<String, Airport>>;
+
+/// Look up every airport the solution and its alternatives fly through
+pub async fn load_airports(solution: &RouteSolution) -> Result<Airports, ApiError> {
+    let codes: HashSet<&str> = std::iter::once(solution)
+        .chain(&solution.alternatives)
+        .flat_map(|candidate| &candidate.flights)
+        .flat_map(|f| [f.flight.src.as_str(), f.flight.dest.as_str()])
+        .collect();
+    let url = format!("/api/v1/airports?codes={}", codes.into_iter().collect::<Vec<_>>().join(","));
+
+    let airports: Vec<Airport> = parse_json(&get_json(&url).await?)?;
+    Ok(Rc::new(airports.into_iter().map(|a| (a.iata.clone(), a)).collect()))
+}
+
+#[derive(Properties, PartialEq)]
+pub struct AirportNameProps {
+    pub iata: AirportCode,
+    #[prop_or_default]
+    pub airports: Airports,
+}
+
+/// City and flag of an airport with its code, e.g. "🇨🇦 Toronto (YYZ)". Just the code if the airport isn't known.
+#[function_component(AirportName)]
+pub fn airport_name(AirportNameProps { iata, airports }: &AirportNameProps) -> Html {
+    match airports.get(iata.as_str()) {
+        Some(airport) => html! {
+            <span title={ format!("{}, {}", airport.name, airport.country) }>
+                if let Some(flag) = airport.flag() {
+                    <span class="me-1" aria-hidden="true">{ flag }</span>
+                }
+                { format!("{} ({})", airport.city, airport.iata) }
+            </span>
+        },
+        None => html! { { iata.to_string() } },
+    }
+}
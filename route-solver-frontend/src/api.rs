@@ -0,0 +1,93 @@
+//! Talking to the backend.
+
+use route_solver_shared::queries::{ApiError, Flight, JobHandle, JobId, RouteQuery, WhatIf, WhatIfDelta, WhatIfQuery};
+use serde::de::DeserializeOwned;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, Response};
+
+/// How often a running solve's status is polled
+pub const SOLVE_POLL_INTERVAL_MS: u32 = 1000;
+
+/// POST `body` as JSON to `url`, see [fetch_text]
+pub async fn post_json(url: &str, body: String) -> Result<String, ApiError> {
+    fetch_text("POST", url, Some(body), &[]).await
+}
+
+/// POST `body` as JSON to `url` with extra `headers`, see [fetch_text]
+pub async fn post_json_with_headers(url: &str, body: String, headers: &[(&str, String)]) -> Result<String, ApiError> {
+    fetch_text("POST", url, Some(body), headers).await
+}
+
+/// GET `url`, see [fetch_text]
+pub async fn get_json(url: &str) -> Result<String, ApiError> {
+    fetch_text("GET", url, None, &[]).await
+}
+
+/// Fetch `url` and return the response body, non-2xx responses are turned into an [ApiError] from their status and body
+async fn fetch_text(method: &str, url: &str, body: Option<String>, headers: &[(&str, String)]) -> Result<String, ApiError> {
+    let network_err = |e: JsValue| {
+        web_sys::console::error_1(&e);
+        ApiError::Network(t!("api-unreachable"))
+    };
+
+    let opts = RequestInit::new();
+    opts.set_method(method);
+    if let Some(body) = body {
+        opts.set_body(&JsValue::from(body));
+    }
+    let request = Request::new_with_str_and_init(url, &opts).map_err(network_err)?;
+    request.headers().set("content-type", "application/json").map_err(network_err)?;
+    for (name, value) in headers {
+        request.headers().set(name, value).map_err(network_err)?;
+    }
+
+    let window = web_sys::window().ok_or_else(|| ApiError::Network(t!("api-no-window")))?;
+    let resp: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(network_err)?
+        .dyn_into()
+        .map_err(network_err)?;
+
+    let text = JsFuture::from(resp.text().map_err(network_err)?)
+        .await
+        .map_err(network_err)?
+        .as_string()
+        .unwrap_or_default();
+
+    if resp.ok() {
+        Ok(text)
+    } else {
+        Err(ApiError::from_status(resp.status(), text))
+    }
+}
+
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, ApiError> {
+    serde_json::from_str(text).map_err(|_| ApiError::Internal(t!("api-bad-response")))
+}
+
+/// Queue a solve of `query`, returns the new job
+pub async fn submit_query(query: &RouteQuery) -> Result<JobId, ApiError> {
+    let body = serde_json::to_string(query).map_err(|e| ApiError::Validation(e.to_string()))?;
+    let handle: JobHandle = parse_json(&post_json("/api/v1/jobs", body).await?)?;
+    Ok(handle.id)
+}
+
+/// Re-solve a job's query with the leg `original` covers swapped for `replacement` and pinned to its date, returns
+/// the new job. See [RouteQuery::swap_leg] for what can change.
+///
+/// Quotes from the earlier solve are still in the server's price cache, so only what the change affects is re-priced.
+pub async fn resolve_swapped(job: JobId, original: &Flight, replacement: &Flight) -> Result<JobId, ApiError> {
+    let mut query: RouteQuery = parse_json(&get_json(&format!("/api/v1/jobs/{}/query", job)).await?)?;
+    if !query.swap_leg(original, replacement) {
+        return Err(ApiError::Validation(t!("api-no-such-leg", from = original.src.to_string(), to = original.dest.to_string())));
+    }
+    submit_query(&query).await
+}
+
+/// What job `job`'s trip would cost with `delta`, solved as a job of its own. Quicker than a fresh solve as the job's
+/// quotes are still in the server's price cache.
+pub async fn what_if(job: JobId, delta: WhatIfDelta) -> Result<WhatIf, ApiError> {
+    let body = serde_json::to_string(&WhatIfQuery { job, delta }).map_err(|e| ApiError::Validation(e.to_string()))?;
+    parse_json(&post_json("/api/v1/whatif", body).await?)
+}
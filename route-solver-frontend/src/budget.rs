@@ -0,0 +1,65 @@
+//! Where a solved trip's money goes, one row per place it stops with the leg in and the stay there.
+
+use route_solver_shared::queries::RouteSolution;
+use yew::prelude::*;
+
+use crate::{
+    airports::{AirportName, Airports},
+    currency::format_price,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct BudgetBreakdownProps {
+    pub solution: RouteSolution,
+    #[prop_or_default]
+    pub airports: Airports,
+}
+
+#[function_component(BudgetBreakdown)]
+pub fn budget_breakdown(BudgetBreakdownProps { solution, airports }: &BudgetBreakdownProps) -> Html {
+    let breakdown = solution.breakdown();
+    if breakdown.is_empty() {
+        return html! {};
+    }
+    let price = |amount: f32| format_price(amount, &solution.currency);
+    // Lodging is only priced when the server knows nightly rates, the columns would be all zeroes otherwise
+    let has_lodging = breakdown.iter().any(|d| d.lodging > 0.0);
+
+    let rows = breakdown.iter().map(|dest| {
+        html! {
+            <tr>
+                <td><AirportName iata={ dest.city } airports={ airports.clone() } /></td>
+                <td>{ price(dest.inbound) }</td>
+                <td>{ dest.nights }</td>
+                if has_lodging {
+                    <td>{ price(dest.lodging) }</td>
+                }
+                <td>{ format!("{:.0}%", dest.share * 100.0) }</td>
+                <td>{ price(dest.running_total) }</td>
+            </tr>
+        }
+    });
+
+    html! {
+        <div class="my-3">
+            <h5>{ t!("budget-title") }</h5>
+            <table class="table table-sm align-middle">
+                <thead>
+                    <tr>
+                        <th>{ t!("budget-destination") }</th>
+                        <th>{ t!("budget-getting-there") }</th>
+                        <th>{ t!("budget-nights") }</th>
+                        if has_lodging {
+                            <th>{ t!("budget-lodging") }</th>
+                        }
+                        <th>{ t!("budget-share") }</th>
+                        <th>{ t!("budget-running-total") }</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for rows }
+                </tbody>
+            </table>
+        </div>
+    }
+}
@@ -0,0 +1,92 @@
+//! Side by side comparison of the best route and its runners up.
+
+use route_solver_shared::queries::{DateMath, RouteSolution};
+use yew::prelude::*;
+
+use crate::{airports::Airports, components::LegTable, currency::format_price};
+
+#[derive(Properties, PartialEq)]
+pub struct CandidateComparisonProps {
+    /// The best route, with the runners up in its alternatives
+    pub solution: RouteSolution,
+    #[prop_or_default]
+    pub airports: Airports,
+}
+
+/// Nights spent in each city along the way, e.g. "YVR 2, YYC 3"
+fn nights_summary(candidate: &RouteSolution) -> String {
+    let stays = candidate.stays();
+    let middle = stays.get(1..stays.len().saturating_sub(1)).unwrap_or_default();
+
+    middle
+        .iter()
+        .map(|stay| format!("{} {}", stay.city, stay.nights))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn trip_days(candidate: &RouteSolution) -> i64 {
+    let dates = candidate.flights.iter().map(|f| f.flight.date);
+    match (dates.clone().min(), dates.max()) {
+        (Some(first), Some(last)) => last.days_since(first),
+        _ => 0,
+    }
+}
+
+#[function_component(CandidateComparison)]
+pub fn candidate_comparison(CandidateComparisonProps { solution, airports }: &CandidateComparisonProps) -> Html {
+    let expanded = use_state(|| None::<usize>);
+    let candidates: Vec<&RouteSolution> = std::iter::once(solution).chain(&solution.alternatives).collect();
+
+    let toggle = |idx: usize| {
+        let expanded = expanded.clone();
+        Callback::from(move |_| expanded.set(if *expanded == Some(idx) { None } else { Some(idx) }))
+    };
+
+    let rows = candidates.iter().enumerate().map(|(idx, candidate)| {
+        let extra = candidate.total_price - solution.total_price;
+        html! {
+            <tr class={ classes!((idx == 0).then_some("table-success")) }>
+                <td>{ if idx == 0 { t!("compare-best") } else { format!("#{}", idx + 1) } }</td>
+                <td>
+                    { format_price(candidate.total_price, &candidate.currency) }
+                    if idx > 0 {
+                        <span class="text-body-secondary small">{ format!(" (+{})", format_price(extra, &candidate.currency)) }</span>
+                    }
+                </td>
+                <td>{ t!("compare-days", days = trip_days(candidate)) }</td>
+                <td>{ candidate.flights.len() }</td>
+                <td>{ nights_summary(candidate) }</td>
+                <td>
+                    <button type="button" class="btn btn-outline-primary btn-sm" onclick={ toggle(idx) }>
+                        { if *expanded == Some(idx) { t!("compare-hide-legs") } else { t!("compare-show-legs") } }
+                    </button>
+                </td>
+            </tr>
+        }
+    });
+
+    html! {
+        <div class="my-3">
+            <h5>{ t!("compare-title") }</h5>
+            <table class="table align-middle">
+                <thead>
+                    <tr>
+                        <th>{ t!("compare-route") }</th>
+                        <th>{ if solution.passengers > 1 { t!("per-passenger") } else { t!("total") } }</th>
+                        <th>{ t!("compare-length") }</th>
+                        <th>{ t!("compare-legs") }</th>
+                        <th>{ t!("compare-nights-per-city") }</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for rows }
+                </tbody>
+            </table>
+            if let Some(candidate) = expanded.and_then(|idx| candidates.get(idx)) {
+                <LegTable flights={ candidate.flights.clone() } currency={ candidate.currency.clone() } airports={ airports.clone() } />
+            }
+        </div>
+    }
+}
@@ -0,0 +1,783 @@
+//! Building blocks shared between pages.
+
+use std::{cell::Cell, ops::Deref};
+
+use route_solver_shared::{
+    date_expr::{parse_dates, DateParseError},
+    queries::{ApiError, Confidence, Date, Flight, FlightPrice, JobStatus, LegAlternative, PriceSource, TransportMode},
+};
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlElement, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::{
+    airports::{AirportName, Airports},
+    currency::format_price,
+    i18n::{format_date, DateStyle},
+};
+
+#[derive(Properties, PartialEq)]
+pub struct TextBoxProps {
+    pub text: String,
+    pub type_name: String,
+    pub text_update_handler: Callback<String, ()>,
+    #[prop_or_default]
+    pub initial_value: String,
+    /// Bound to the input, for moving focus to it
+    #[prop_or_default]
+    pub node_ref: NodeRef,
+    /// ID of the input, for a `<label>` to name it by. Without one the input is named by its placeholder text.
+    #[prop_or_default]
+    pub id: Option<AttrValue>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ButtonProps {
+    pub text: String,
+    pub on_click: Callback<()>,
+    #[prop_or_default]
+    pub disabled: bool,
+    /// Bound to the button, for moving focus to it
+    #[prop_or_default]
+    pub node_ref: NodeRef,
+}
+
+thread_local! {
+    static NEXT_ID: Cell<u32> = const { Cell::new(0) };
+}
+
+/// An element ID no other call returns, for tying labels to inputs in components rendered more than once
+pub fn unique_id(prefix: &str) -> String {
+    NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        format!("{}-{}", prefix, id)
+    })
+}
+
+/// Move keyboard focus to the element `node` is bound to, if it's rendered
+pub fn focus(node: &NodeRef) {
+    if let Some(element) = node.cast::<HtmlElement>() {
+        let _ = element.focus();
+    }
+}
+
+/// Move keyboard focus to the first input inside the element `node` is bound to
+pub fn focus_first_input(node: &NodeRef) {
+    let input = node
+        .cast::<Element>()
+        .and_then(|element| element.query_selector("input").ok().flatten())
+        .and_then(|input| input.dyn_into::<HtmlElement>().ok());
+    if let Some(input) = input {
+        let _ = input.focus();
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ModalProps {
+    pub id: String,
+    pub main_text: String,
+    pub internal_html: Html,
+    /// Shown by the app rather than by a [ModalTriggerButton], it's up to `on_close` to stop showing it
+    #[prop_or_default]
+    pub open: bool,
+    /// Called by the close buttons, Escape and clicking outside an open modal, instead of Bootstrap hiding it
+    #[prop_or_default]
+    pub on_close: Option<Callback<()>>,
+    /// Buttons to the left of the close button, e.g. to go ahead with what the modal asks about
+    #[prop_or_default]
+    pub footer: Html,
+    /// Text of the footer's close button, "Close" if not given
+    #[prop_or_default]
+    pub close_text: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Properties, PartialEq)]
+pub struct ModalTriggerProps {
+    pub id: String,
+    pub text: String,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct DropDownProps {
+    /// Shown on the button until an option is picked, and names the menu
+    pub text: String,
+    pub opts: Vec<String>,
+    /// Called with the option picked
+    pub on_select: Callback<String>,
+    /// Option picked to start with
+    #[prop_or_default]
+    pub selected: Option<String>,
+    #[prop_or_default]
+    pub node_ref: NodeRef,
+}
+
+pub struct TextBox {
+    input_value: String
+}
+
+pub enum TextMsg {
+    InputChanged(InputEvent),
+}
+
+impl Component for TextBox {
+    type Message = TextMsg;
+    type Properties = TextBoxProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        TextBox { input_value: ctx.props().initial_value.clone() }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            TextMsg::InputChanged(e) => {
+                let target = e.target();
+                let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+
+                if let Some(input) = input {
+                    self.input_value = input.value();
+                    ctx.props().text_update_handler.emit(self.input_value.clone());
+                }
+            }
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let aria_label = props.id.is_none().then(|| props.text.clone());
+        html! {
+            <input ref={props.node_ref.clone()} id={props.id.clone()} class={"form-control"} placeholder={props.text.clone()} aria-label={aria_label} type={props.type_name.clone()} value={self.input_value.clone()} oninput={ctx.link().callback(|e: InputEvent| TextMsg::InputChanged(e))} />
+        }
+    }
+}
+
+#[function_component(Button)]
+pub fn button(ButtonProps { text, on_click, disabled, node_ref }: &ButtonProps) -> Html {
+    let on_click_fn = {
+        let on_click = on_click.clone();
+        Callback::from(move |_| on_click.emit(()))
+    };
+
+    html! {
+        <button ref={node_ref.clone()} type={"button"} onclick={on_click_fn} disabled={*disabled} class={"btn btn-primary my-2"}>{ text.clone() }</button>
+    }
+}
+
+#[function_component(ModalTriggerButton)]
+pub fn modal_trigger(ModalTriggerProps { id, text }: &ModalTriggerProps) -> Html {
+    html! {
+        <button type={"button"} data-bs-toggle="modal" data-bs-target={ String::from("#") + id } class={"btn btn-primary"}>{ text.clone() }</button>
+    }
+}
+
+#[function_component(Modal)]
+pub fn modal(
+    ModalProps {
+        id,
+        main_text,
+        internal_html,
+        open,
+        on_close,
+        footer,
+        close_text,
+    }: &ModalProps,
+) -> Html {
+    let title_id = format!("{}-title", id);
+    let dialog_ref = use_node_ref();
+    {
+        // Into the dialog when it opens, so Escape and Tab work on it straight away
+        let dialog_ref = dialog_ref.clone();
+        use_effect_with_deps(
+            move |open| {
+                if *open {
+                    focus(&dialog_ref);
+                }
+            },
+            *open,
+        );
+    }
+    // Left to Bootstrap unless the app is showing the modal itself
+    let dismiss = on_close.is_none().then_some("modal");
+    let close = on_close.clone().map(|on_close| Callback::from(move |_: MouseEvent| on_close.emit(())));
+    let onkeydown = on_close.clone().map(|on_close| {
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Escape" {
+                on_close.emit(());
+            }
+        })
+    });
+    // Clicks on the dialog itself bubble up too, only ones on the backdrop around it close it
+    let onclick = on_close.clone().map(|on_close| {
+        let dialog_ref = dialog_ref.clone();
+        Callback::from(move |e: MouseEvent| {
+            let outside = e.target().zip(dialog_ref.get()).is_some_and(|(target, dialog)| {
+                target.dyn_ref::<web_sys::Node>().is_some_and(|target| target.is_same_node(Some(&dialog)))
+            });
+            if outside {
+                on_close.emit(());
+            }
+        })
+    });
+
+    html! {
+      <>
+        <div id={ id.clone() } ref={ dialog_ref } class={ classes!("modal", "fade", open.then_some("show d-block")) } tabindex="-1"
+            role="dialog" aria-modal="true" aria-labelledby={ title_id.clone() } {onkeydown} {onclick}>
+          <div class="modal-dialog">
+            <div class="modal-content">
+              <div class="modal-header">
+                <h5 class="modal-title" id={ title_id }>{ main_text }</h5>
+                <button type="button" class="btn-close" data-bs-dismiss={ dismiss } aria-label={ t!("close") } onclick={ close.clone() }></button>
+              </div>
+              <div class="modal-body">
+                  { internal_html.clone() }
+              </div>
+              <div class="modal-footer">
+                { footer.clone() }
+                <button type="button" class="btn btn-secondary" data-bs-dismiss={ dismiss } onclick={ close }>
+                  { close_text.clone().unwrap_or_else(|| t!("close")) }
+                </button>
+              </div>
+            </div>
+          </div>
+        </div>
+        if *open {
+          <div class="modal-backdrop fade show"></div>
+        }
+      </>
+    }
+}
+
+#[function_component(CloseButton)]
+pub fn close_button(ButtonProps { text, on_click, .. }: &ButtonProps) -> Html {
+    let on_click_fn = {
+        let on_click = on_click.clone();
+        Callback::from(move |_| on_click.emit(()))
+    };
+
+    html! {
+        <button type={"button"} onclick={on_click_fn} class={"btn-close my-2"} aria-label={text.clone()}></button>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ErrorToastProps {
+    pub error: ApiError,
+    pub on_dismiss: Callback<()>,
+    pub on_retry: Callback<()>,
+}
+
+#[function_component(ErrorToast)]
+pub fn error_toast(ErrorToastProps { error, on_dismiss, on_retry }: &ErrorToastProps) -> Html {
+    let dismiss = {
+        let on_dismiss = on_dismiss.clone();
+        Callback::from(move |_| on_dismiss.emit(()))
+    };
+    let retry = {
+        let on_retry = on_retry.clone();
+        Callback::from(move |_| on_retry.emit(()))
+    };
+
+    // Problems the user can fix themselves are warnings, with a hint at what to change
+    let (class, hint) = match error {
+        ApiError::Validation(_) => ("text-bg-warning", None),
+        ApiError::Infeasible(_) => ("text-bg-warning", Some(t!("error-hint-infeasible"))),
+        ApiError::RateLimited(_) => ("text-bg-warning", None),
+        ApiError::ProviderUnavailable(_) => ("text-bg-danger", Some(t!("error-hint-provider"))),
+        ApiError::Network(_) => ("text-bg-danger", Some(t!("error-hint-network"))),
+        _ => ("text-bg-danger", None),
+    };
+
+    html! {
+        <div class="toast-container position-fixed bottom-0 end-0 p-3">
+            <div class={ classes!("toast", "show", class) } role="alert" aria-live="assertive" aria-atomic="true">
+                <div class="d-flex">
+                    <div class="toast-body">
+                        { error.to_string() }
+                        if let Some(hint) = hint {
+                            <div class="small mt-1">{ hint }</div>
+                        }
+                    </div>
+                    <button
+                        type="button"
+                        class={ classes!("btn-close", "me-2", "m-auto", (class == "text-bg-danger").then_some("btn-close-white")) }
+                        aria-label={ t!("close") }
+                        onclick={dismiss}></button>
+                </div>
+                if error.is_retryable() {
+                    <div class="px-3 pb-2">
+                        <button type="button" class="btn btn-light btn-sm" onclick={retry}>{ t!("retry") }</button>
+                    </div>
+                }
+            </div>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct LiveRegionProps {
+    pub message: String,
+}
+
+/// Hidden text read out by screen readers whenever it changes, for telling them about things that happen away from
+/// their focus like a row being added or a solve finishing
+#[function_component(LiveRegion)]
+pub fn live_region(LiveRegionProps { message }: &LiveRegionProps) -> Html {
+    html! {
+        <div class="visually-hidden" role="status" aria-live="polite" aria-atomic="true">{ message.clone() }</div>
+    }
+}
+
+/// A solve in flight, as last reported by the job API
+#[derive(PartialEq, Clone)]
+pub struct SolveProgress {
+    pub started_ms: f64,
+    pub status: JobStatus,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SolveIndicatorProps {
+    pub progress: SolveProgress,
+}
+
+#[function_component(SolveIndicator)]
+pub fn solve_indicator(SolveIndicatorProps { progress }: &SolveIndicatorProps) -> Html {
+    let elapsed_secs = ((js_sys::Date::now() - progress.started_ms) / 1000.0).max(0.0) as u64;
+    let detail = match &progress.status {
+        JobStatus::Running { api_calls, nodes_expanded } => {
+            t!("solve-running", prices = *api_calls, routes = *nodes_expanded)
+        }
+        _ => t!("solve-queued"),
+    };
+
+    // The elapsed time ticks every second, too often to read out, pages announce the solve starting and finishing instead
+    html! {
+        <div class="d-flex align-items-center my-2">
+            <div class="spinner-border spinner-border-sm me-2" aria-hidden="true"></div>
+            <span>{ t!("solve-elapsed", detail = detail, seconds = elapsed_secs) }</span>
+        </div>
+    }
+}
+
+/// Why free text couldn't be read as dates, the parser's own messages are in English
+fn describe_parse_error(error: &DateParseError) -> String {
+    match error {
+        DateParseError::Empty => t!("dates-empty"),
+        DateParseError::Unrecognized(text) => t!("dates-unrecognized", text = text.clone()),
+        DateParseError::Reversed(window) => t!(
+            "dates-reversed",
+            first = format_date(window.first, DateStyle::Long),
+            last = format_date(window.last, DateStyle::Long),
+        ),
+    }
+}
+
+/// Today in the browser's time zone
+pub fn today() -> Option<Date> {
+    let now = js_sys::Date::new_0();
+    Date::from_ymd_opt(now.get_full_year() as i32, now.get_month() + 1, now.get_date())
+}
+
+#[derive(PartialEq, Clone, Properties)]
+pub struct FlyInProps {
+    pub fly_in_update_handler: Callback<(String, String), ()>,
+    #[prop_or_default]
+    pub initial: (String, String),
+}
+
+#[function_component(FlyInComponent)]
+pub fn fly_in(FlyInProps { fly_in_update_handler, initial }: &FlyInProps) -> Html {
+    let curr_vals = use_state(|| [initial.0.clone(), initial.1.clone()]);
+    // Bumped when the free text fills in the dates, so the date boxes are rebuilt with them
+    let filled = use_state(|| 0u32);
+    let hint = use_state(|| None::<Result<String, String>>);
+    let ids = use_state(|| [unique_id("dates-start"), unique_id("dates-end")]);
+
+    let box_callback_gen = |id: usize| {
+        let curr_vals = curr_vals.clone();
+        let fly_in_cb = fly_in_update_handler.clone();
+        Callback::from(move |new_val: String| {
+            let mut new_vals = curr_vals.deref().clone();
+            new_vals[id] = new_val;
+            fly_in_cb.clone().emit((new_vals[0].clone(), new_vals[1].clone()));
+            curr_vals.set(new_vals);
+        })
+    };
+
+    // Fills both dates from an expression like "early June", a stop only takes one window so a set of them is spanned
+    let free_text_callback = {
+        let (curr_vals, filled, hint) = (curr_vals.clone(), filled.clone(), hint.clone());
+        let fly_in_cb = fly_in_update_handler.clone();
+        Callback::from(move |text: String| {
+            if text.trim().is_empty() {
+                hint.set(None);
+                return;
+            }
+            match today().ok_or_else(|| t!("dates-no-today")).and_then(|today| {
+                parse_dates(&text, today).map_err(|e| describe_parse_error(&e))
+            }) {
+                Ok(dates) => {
+                    let span = dates.span();
+                    let new_vals = [span.first().to_string(), span.last().to_string()];
+                    fly_in_cb.emit((new_vals[0].clone(), new_vals[1].clone()));
+                    curr_vals.set(new_vals);
+                    filled.set(*filled + 1);
+                    let (first, last) = (format_date(span.first(), DateStyle::Long), format_date(span.last(), DateStyle::Long));
+                    hint.set(Some(Ok(match dates.windows().len() {
+                        1 => t!("dates-span", first = first, last = last),
+                        _ => t!("dates-span-with-gaps", first = first, last = last),
+                    })));
+                }
+                Err(e) => hint.set(Some(Err(e))),
+            }
+        })
+    };
+
+    html! {
+        <div>
+            // Side by side where there's room, wrapping one to a line on phones
+            <div class="d-flex flex-wrap gap-2">
+                <div class="input-group flex-nowrap w-auto">
+                    <label class="input-group-text" for={ ids[0].clone() }>{ t!("dates-start") }</label>
+                    <TextBox key={format!("start-{}", *filled)} id={ ids[0].clone() } text={ t!("dates-start") } type_name="date" initial_value={curr_vals[0].clone()} text_update_handler={box_callback_gen(0)} />
+                </div>
+                <div class="input-group flex-nowrap w-auto">
+                    <label class="input-group-text" for={ ids[1].clone() }>{ t!("dates-end") }</label>
+                    <TextBox key={format!("end-{}", *filled)} id={ ids[1].clone() } text={ t!("dates-end") } type_name="date" initial_value={curr_vals[1].clone()} text_update_handler={box_callback_gen(1)} />
+                </div>
+                <div class="input-group flex-nowrap w-auto">
+                    <TextBox text={ t!("dates-free-text") } type_name="text" text_update_handler={free_text_callback} />
+                </div>
+            </div>
+            // Always there so what the free text was read as is announced as it changes
+            <div aria-live="polite">
+                {
+                    match &*hint {
+                        Some(Ok(dates)) => html! { <div class="form-text">{ dates.clone() }</div> },
+                        Some(Err(e)) => html! { <div class="form-text text-danger">{ e.clone() }</div> },
+                        None => html! {},
+                    }
+                }
+            </div>
+        </div>
+    }
+}
+
+#[derive(PartialEq, Clone, Properties)]
+pub struct ListItemProps {
+    pub text: String,
+    pub children: Children,
+    #[prop_or_default]
+    pub start_open: bool,
+}
+
+/// A button which opens into its children, closed again with the close button or Escape. Focus moves into the children
+/// when it's opened and back to the button when it's closed.
+#[function_component(ListItem)]
+pub fn list_item(ListItemProps { text, children, start_open }: &ListItemProps) -> Html {
+    let open = use_state(|| *start_open);
+    // Set when the user opens or closes the item rather than it starting open, only then does focus follow
+    let toggled = use_mut_ref(|| false);
+    let button_ref = use_node_ref();
+    let content_ref = use_node_ref();
+    {
+        let (toggled, button_ref, content_ref) = (toggled.clone(), button_ref.clone(), content_ref.clone());
+        use_effect_with_deps(
+            move |open| {
+                if std::mem::take(&mut *toggled.borrow_mut()) {
+                    if *open {
+                        focus_first_input(&content_ref);
+                    } else {
+                        focus(&button_ref);
+                    }
+                }
+            },
+            *open,
+        );
+    }
+    let set_open = |value: bool| {
+        let (open, toggled) = (open.clone(), toggled.clone());
+        Callback::from(move |_| {
+            *toggled.borrow_mut() = true;
+            open.set(value);
+        })
+    };
+    let onkeydown = {
+        let onclose = set_open(false);
+        Callback::from(move |e: KeyboardEvent| {
+            if e.key() == "Escape" {
+                onclose.emit(());
+            }
+        })
+    };
+    let onclose = set_open(false);
+
+    html! {
+        if !*open {
+            <div class="col-md-auto">
+                <Button text={text.clone()} on_click={set_open(true)} node_ref={button_ref} />
+            </div>
+        } else {
+            <div class="col-md-auto my-2" ref={content_ref} role="group" aria-label={text.clone()} {onkeydown}>
+                { children.clone() }
+            </div>
+            <div class="col-md-auto my-2">
+                <CloseButton text={ t!("close") } on_click={onclose} />
+            </div>
+        }
+    }
+}
+
+/// A button which drops down a menu of options, showing the one last picked
+#[function_component(DropDown)]
+pub fn dropdown(
+    DropDownProps {
+        text,
+        opts,
+        on_select,
+        selected,
+        node_ref,
+    }: &DropDownProps,
+) -> Html {
+    let current = use_state(|| selected.clone());
+    let opts_html = opts.iter().map(|opt| {
+        let onclick = {
+            let (current, on_select, opt) = (current.clone(), on_select.clone(), opt.clone());
+            Callback::from(move |_| {
+                current.set(Some(opt.clone()));
+                on_select.emit(opt.clone());
+            })
+        };
+        let active = current.as_ref() == Some(opt);
+        html! {
+            <li>
+                <button type={"button"} class={ classes!("dropdown-item", active.then_some("active")) } aria-current={ active.then_some("true") } {onclick}>
+                    { opt.clone() }
+                </button>
+            </li>
+        }
+    });
+
+    html! {
+        <div class={"dropdown"} ref={ node_ref.clone() } >
+          <button class={"btn btn-secondary dropdown-toggle"} type={"button"} data-bs-toggle={"dropdown"} aria-haspopup={"true"} aria-expanded={"false"}>
+            if let Some(current) = &*current {
+                // The option alone doesn't say what it's an option for
+                <span class="visually-hidden">{ format!("{}: ", text) }</span>
+                { current.clone() }
+            } else {
+                { text.clone() }
+            }
+          </button>
+          <ul class={"dropdown-menu"} aria-label={ text.clone() }>
+            { for opts_html }
+          </ul>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct LegTableProps {
+    pub flights: Vec<FlightPrice>,
+    /// Currency the prices are in
+    pub currency: String,
+    /// Used to show airports by name, codes missing from it are shown as they are
+    #[prop_or_default]
+    pub airports: Airports,
+    /// Adds an "Explore dates" button to each leg, called with the leg's index
+    #[prop_or_default]
+    pub on_explore: Option<Callback<usize>>,
+    /// Lets each leg's date and destination be edited inline, called with the leg's index and the edited flight
+    #[prop_or_default]
+    pub on_edit: Option<Callback<(usize, Flight)>>,
+}
+
+/// Badge saying where a leg's price came from unless the provider quoted it, coloured by how far it can be trusted
+fn source_badge(source: PriceSource) -> Html {
+    let (text, title) = match source {
+        PriceSource::Quoted => return html! {},
+        PriceSource::Fallback => (t!("leg-fallback"), t!("leg-fallback-title")),
+        PriceSource::NearbyDays => (t!("leg-estimated"), t!("leg-estimated-title")),
+        PriceSource::History => (t!("leg-typical"), t!("leg-typical-title")),
+    };
+    let class = match source.confidence() {
+        Confidence::High => "bg-info text-dark",
+        Confidence::Medium => "bg-warning text-dark",
+        Confidence::Low => "bg-danger",
+    };
+    html! {
+        <span class={ classes!("badge", "ms-1", class) } title={ title }>{ text }</span>
+    }
+}
+
+/// Why a leg was chosen over the next cheapest the search priced, for the "why not fly the day before?" question
+fn alternative_note(alternative: &LegAlternative, currency: &str) -> Html {
+    let date = format_date(alternative.flight.date, DateStyle::Long);
+    let dest = alternative.flight.dest.to_string();
+    let price = format_price(alternative.price, currency);
+    let note = match alternative.saving {
+        // Less than a cent either way is the same trip to anyone reading it
+        Some(saving) if saving >= 0.01 => t!(
+            "leg-alternative-dearer",
+            date = date,
+            dest = dest,
+            price = price,
+            saving = format_price(saving, currency)
+        ),
+        Some(_) => t!("leg-alternative-even", date = date, dest = dest, price = price),
+        None => t!("leg-alternative-dead-end", date = date, dest = dest, price = price),
+    };
+    html! {
+        <div class="small text-muted" title={ t!("leg-alternative-title") }>{ note }</div>
+    }
+}
+
+#[function_component(LegTable)]
+pub fn leg_table(
+    LegTableProps {
+        flights,
+        currency,
+        airports,
+        on_explore,
+        on_edit,
+    }: &LegTableProps,
+) -> Html {
+    // Leg being edited and its edits so far
+    let editing = use_state(|| None::<(usize, Flight)>);
+    // Whether the destination typed in is an airport code, the draft keeps the last one that was
+    let dest_valid = use_state(|| true);
+
+    let explore = |idx: usize| {
+        on_explore.as_ref().map(|on_explore| {
+            let on_explore = on_explore.clone();
+            html! {
+                <button type="button" class="btn btn-outline-primary btn-sm me-1" onclick={ Callback::from(move |_| on_explore.emit(idx)) }>
+                    { t!("leg-explore-dates") }
+                </button>
+            }
+        })
+    };
+    let start_edit = |idx: usize, flight: &Flight| {
+        on_edit.as_ref().map(|_| {
+            let editing = editing.clone();
+            let dest_valid = dest_valid.clone();
+            let flight = flight.clone();
+            let onclick = Callback::from(move |_| {
+                editing.set(Some((idx, flight.clone())));
+                dest_valid.set(true);
+            });
+            html! {
+                <button type="button" class="btn btn-outline-secondary btn-sm" {onclick}>
+                    { t!("edit") }
+                </button>
+            }
+        })
+    };
+
+    let edit_row = |idx: usize, draft: &Flight| {
+        let set_date = {
+            let editing = editing.clone();
+            let draft = draft.clone();
+            Callback::from(move |input: String| {
+                if let Ok(date) = input.parse() {
+                    editing.set(Some((idx, Flight { date, ..draft.clone() })));
+                }
+            })
+        };
+        let set_dest = {
+            let editing = editing.clone();
+            let dest_valid = dest_valid.clone();
+            let draft = draft.clone();
+            Callback::from(move |input: String| match input.trim().parse() {
+                Ok(dest) => {
+                    editing.set(Some((idx, Flight { dest, ..draft.clone() })));
+                    dest_valid.set(true);
+                }
+                Err(_) => dest_valid.set(false),
+            })
+        };
+        let save = {
+            let editing = editing.clone();
+            let on_edit = on_edit.clone();
+            let draft = draft.clone();
+            Callback::from(move |_| {
+                if let Some(on_edit) = &on_edit {
+                    on_edit.emit((idx, draft.clone()));
+                }
+                editing.set(None);
+            })
+        };
+        let cancel = {
+            let editing = editing.clone();
+            Callback::from(move |_| editing.set(None))
+        };
+
+        html! {
+            <tr>
+                <td data-label={ t!("leg-date") }><TextBox text={ t!("leg-date") } type_name="date" initial_value={ draft.date.to_string() } text_update_handler={ set_date } /></td>
+                <td data-label={ t!("leg-from") }><AirportName iata={ draft.src } airports={ airports.clone() } /></td>
+                <td data-label={ t!("leg-to") }><TextBox text={ t!("airport-code") } type_name="text" initial_value={ draft.dest.to_string() } text_update_handler={ set_dest } /></td>
+                <td class="d-none d-md-table-cell"></td>
+                <td>
+                    <button type="button" class="btn btn-primary btn-sm me-1" disabled={ !*dest_valid } onclick={ save }>{ t!("leg-reprice") }</button>
+                    <button type="button" class="btn btn-outline-secondary btn-sm" onclick={ cancel }>{ t!("cancel") }</button>
+                </td>
+            </tr>
+        }
+    };
+
+    html! {
+        // Each leg is a card rather than a row on phones, the cells labelling themselves in place of the header
+        <table class="table align-middle table-cards">
+            <thead>
+                <tr>
+                    <th>{ t!("leg-date") }</th><th>{ t!("leg-from") }</th><th>{ t!("leg-to") }</th><th>{ t!("leg-price") }</th>
+                    if on_explore.is_some() || on_edit.is_some() {
+                        <th class="d-print-none"></th>
+                    }
+                </tr>
+            </thead>
+            <tbody>
+                { for flights.iter().enumerate().map(|(idx, f)| match &*editing {
+                    Some((editing_idx, draft)) if *editing_idx == idx => edit_row(idx, draft),
+                    _ => html! {
+                        <tr>
+                            <td data-label={ t!("leg-date") }>{ format_date(f.flight.date, DateStyle::Long) }</td>
+                            <td data-label={ t!("leg-from") }><AirportName iata={ f.flight.src } airports={ airports.clone() } /></td>
+                            <td data-label={ t!("leg-to") }><AirportName iata={ f.flight.dest } airports={ airports.clone() } /></td>
+                            <td data-label={ t!("leg-price") }>
+                                { format_price(f.price, currency) }
+                                if f.std_dev > 0.0 {
+                                    <span class="small text-muted ms-1" title={ t!("leg-spread-title") }>
+                                        { format!("± {}", format_price(f.std_dev, currency)) }
+                                    </span>
+                                }
+                                if f.mode == TransportMode::Ground {
+                                    <span class="badge bg-secondary ms-1">{ t!("leg-overland") }</span>
+                                }
+                                { source_badge(f.source) }
+                                if f.bag_fee > 0.0 {
+                                    <div class="small text-muted">{ t!("leg-bag-included", price = format_price(f.bag_fee, currency)) }</div>
+                                }
+                                if f.stay_price > 0.0 {
+                                    <div class="small text-muted">{ t!("leg-stay-before", price = format_price(f.stay_price, currency)) }</div>
+                                }
+                                if let Some(alternative) = &f.alternative {
+                                    { alternative_note(alternative, currency) }
+                                }
+                            </td>
+                            if on_explore.is_some() || on_edit.is_some() {
+                                <td class="d-print-none">
+                                    { explore(idx) }
+                                    { start_edit(idx, &f.flight) }
+                                </td>
+                            }
+                        </tr>
+                    },
+                }) }
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,72 @@
+//! Showing prices in the user's chosen currency.
+
+use route_solver_shared::queries::BASE_CURRENCY;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::{
+    api::{get_json, parse_json},
+    i18n::format_currency,
+    trips,
+};
+
+/// Currency prices are shown in, the user's last pick or the providers' own
+pub fn preferred_currency() -> String {
+    trips::load_currency().unwrap_or_else(|| BASE_CURRENCY.to_string())
+}
+
+/// Format `amount` of `currency` the way the page's language writes prices, e.g. "€12.50", see [format_currency]
+pub fn format_price(amount: f32, currency: &str) -> String {
+    format_currency(amount, currency)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CurrencySelectProps {
+    pub value: String,
+    pub on_change: Callback<String>,
+}
+
+/// Dropdown of the currencies the server can price in. The pick is remembered for later visits.
+#[function_component(CurrencySelect)]
+pub fn currency_select(CurrencySelectProps { value, on_change }: &CurrencySelectProps) -> Html {
+    let supported = use_state(Vec::<String>::new);
+    {
+        let supported = supported.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    // Leaves just the current pick to choose from, which is still a valid request
+                    if let Ok(codes) = get_json("/api/v1/currencies").await.and_then(|r| parse_json(&r)) {
+                        supported.set(codes);
+                    }
+                });
+            },
+            (),
+        );
+    }
+
+    let onchange = {
+        let on_change = on_change.clone();
+        Callback::from(move |e: Event| {
+            if let Some(select) = e.target().and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()) {
+                trips::store_currency(&select.value());
+                on_change.emit(select.value());
+            }
+        })
+    };
+
+    let mut options = (*supported).clone();
+    if !options.contains(value) {
+        options.insert(0, value.clone());
+    }
+
+    html! {
+        <select class="form-select w-auto" aria-label={ t!("currency-label") } {onchange}>
+            { for options.iter().map(|code| html! {
+                <option value={ code.clone() } selected={ code == value }>{ code }</option>
+            }) }
+        </select>
+    }
+}
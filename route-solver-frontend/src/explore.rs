@@ -0,0 +1,230 @@
+//! Price-vs-date chart for one leg of a solved trip, with the option of re-solving with the leg moved to another day.
+
+use route_solver_shared::queries::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::{html::Scope, prelude::*};
+use yew_router::prelude::*;
+
+use crate::{
+    api::{get_json, parse_json, resolve_swapped},
+    components::{Button, CloseButton, ErrorToast},
+    currency::format_price,
+    i18n::{format_date, DateStyle},
+    Route,
+};
+
+/// Days either side of the leg's date to chart
+const CALENDAR_DAYS: u64 = 7;
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+/// Space around the plot for the axis labels
+const CHART_PADDING: f64 = 30.0;
+
+#[derive(Properties, PartialEq)]
+pub struct DateExplorerProps {
+    /// Job the leg belongs to, its query is what gets re-solved
+    pub job_id: JobId,
+    pub leg: FlightPrice,
+    /// Currency to chart prices in, the same as the rest of the trip
+    pub currency: String,
+    pub on_close: Callback<()>,
+}
+
+pub struct DateExplorer {
+    calendar: Option<Vec<CalendarDay>>,
+    selected: Date,
+    resolving: bool,
+    error: Option<ApiError>,
+}
+
+pub enum DateExplorerMessage {
+    Loaded(Vec<CalendarDay>),
+    Select(Date),
+    Pin,
+    Failed(ApiError),
+    Retry,
+    DismissError,
+}
+
+impl DateExplorer {
+    async fn load(link: Scope<Self>, flight: Flight, currency: String) {
+        let url = format!(
+            "/api/v1/calendar?src={}&dest={}&date={}&days={}&currency={}",
+            flight.src, flight.dest, flight.date, CALENDAR_DAYS, currency
+        );
+        match get_json(&url).await.and_then(|r| parse_json(&r)) {
+            Ok(calendar) => link.send_message(DateExplorerMessage::Loaded(calendar)),
+            Err(e) => link.send_message(DateExplorerMessage::Failed(e)),
+        }
+    }
+
+    fn start_load(&mut self, ctx: &Context<Self>) {
+        self.calendar = None;
+        self.error = None;
+        let props = ctx.props();
+        spawn_local(Self::load(ctx.link().clone(), props.leg.flight.clone(), props.currency.clone()));
+    }
+
+    /// Line chart of the calendar, days without a price leave a gap. Clicking a day selects it.
+    fn chart(&self, ctx: &Context<Self>, calendar: &[CalendarDay]) -> Html {
+        let prices = calendar.iter().filter_map(|d| d.price);
+        let min = prices.clone().fold(f32::INFINITY, f32::min);
+        let max = prices.fold(f32::NEG_INFINITY, f32::max);
+        if !min.is_finite() {
+            return html! { <p>{ t!("explore-no-prices") }</p> };
+        }
+
+        let span = (max - min).max(1.0) as f64;
+        let x = |i: usize| CHART_PADDING + i as f64 * (CHART_WIDTH - 2.0 * CHART_PADDING) / (calendar.len().max(2) - 1) as f64;
+        let y = |price: f32| CHART_HEIGHT - CHART_PADDING - (price - min) as f64 / span * (CHART_HEIGHT - 2.0 * CHART_PADDING);
+
+        // One polyline per run of priced days
+        let mut lines: Vec<Vec<String>> = vec![vec![]];
+        for (i, day) in calendar.iter().enumerate() {
+            match day.price {
+                Some(price) => lines.last_mut().unwrap().push(format!("{:.1},{:.1}", x(i), y(price))),
+                None => lines.push(vec![]),
+            }
+        }
+
+        let current = ctx.props().leg.flight.date;
+        let points = calendar.iter().enumerate().filter_map(|(i, day)| {
+            let price = day.price?;
+            let date = day.date;
+            let class = if date == self.selected {
+                "chart-point chart-point-selected"
+            } else if date == current {
+                "chart-point chart-point-current"
+            } else {
+                "chart-point"
+            };
+            Some(html! {
+                <circle class={ class } cx={ format!("{:.1}", x(i)) } cy={ format!("{:.1}", y(price)) } r="5"
+                    onclick={ ctx.link().callback(move |_| DateExplorerMessage::Select(date)) }>
+                    <title>{ format!("{}: {}", format_date(date, DateStyle::Long), format_price(price, &ctx.props().currency)) }</title>
+                </circle>
+            })
+        });
+
+        let first = calendar.first().map(|d| format_date(d.date, DateStyle::Short)).unwrap_or_default();
+        let last = calendar.last().map(|d| format_date(d.date, DateStyle::Short)).unwrap_or_default();
+
+        html! {
+            <svg class="price-chart" viewBox={ format!("0 0 {} {}", CHART_WIDTH, CHART_HEIGHT) } role="img"
+                aria-label={ t!("explore-chart-label", first = first.clone(), last = last.clone()) }>
+                { for lines.iter().filter(|l| !l.is_empty()).map(|l| html! {
+                    <polyline class="chart-line" points={ l.join(" ") } />
+                }) }
+                { for points }
+                <text x={ CHART_PADDING.to_string() } y={ (CHART_HEIGHT - 8.0).to_string() } class="chart-label">{ first }</text>
+                <text x={ (CHART_WIDTH - CHART_PADDING).to_string() } y={ (CHART_HEIGHT - 8.0).to_string() } class="chart-label" text-anchor="end">{ last }</text>
+                <text x="4" y={ (y(max) + 4.0).to_string() } class="chart-label">{ format_price(max, &ctx.props().currency) }</text>
+                <text x="4" y={ (y(min) + 4.0).to_string() } class="chart-label">{ format_price(min, &ctx.props().currency) }</text>
+            </svg>
+        }
+    }
+}
+
+impl Component for DateExplorer {
+    type Properties = DateExplorerProps;
+    type Message = DateExplorerMessage;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut explorer = Self {
+            calendar: None,
+            selected: ctx.props().leg.flight.date,
+            resolving: false,
+            error: None,
+        };
+        explorer.start_load(ctx);
+        explorer
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.selected = ctx.props().leg.flight.date;
+        self.start_load(ctx);
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            DateExplorerMessage::Loaded(calendar) => self.calendar = Some(calendar),
+            DateExplorerMessage::Select(date) => self.selected = date,
+            DateExplorerMessage::Pin => {
+                self.resolving = true;
+                let link = ctx.link().clone();
+                let job_id = ctx.props().job_id;
+                let original = ctx.props().leg.flight.clone();
+                let replacement = Flight {
+                    date: self.selected,
+                    ..original.clone()
+                };
+                spawn_local(async move {
+                    match resolve_swapped(job_id, &original, &replacement).await {
+                        Ok(id) => {
+                            if let Some(navigator) = link.navigator() {
+                                navigator.push(&Route::Results { id });
+                            }
+                        }
+                        Err(e) => link.send_message(DateExplorerMessage::Failed(e)),
+                    }
+                });
+            }
+            DateExplorerMessage::Failed(e) => {
+                self.resolving = false;
+                self.error = Some(e);
+            }
+            DateExplorerMessage::Retry => {
+                if self.calendar.is_none() {
+                    self.start_load(ctx);
+                } else {
+                    self.error = None;
+                }
+            }
+            DateExplorerMessage::DismissError => self.error = None,
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let leg = &ctx.props().leg.flight;
+        let on_close = ctx.props().on_close.clone();
+
+        html! {
+            <div class="my-3 p-3 bg-body-secondary rounded-3">
+                <div class="d-flex align-items-center">
+                    <h5 class="me-auto mb-0">{ t!(
+                        "explore-title",
+                        from = leg.src.to_string(),
+                        to = leg.dest.to_string(),
+                        date = format_date(leg.date, DateStyle::Long),
+                    ) }</h5>
+                    <CloseButton text={ t!("close") } on_click={ on_close } />
+                </div>
+                {
+                    match &self.calendar {
+                        Some(calendar) => self.chart(ctx, calendar),
+                        None if self.error.is_none() => html! {
+                            <div class="spinner-border spinner-border-sm my-2" role="status" aria-label={ t!("explore-loading") }></div>
+                        },
+                        None => html! {},
+                    }
+                }
+                if self.selected != leg.date {
+                    <Button
+                        text={ t!("explore-pin", date = format_date(self.selected, DateStyle::Long)) }
+                        disabled={ self.resolving }
+                        on_click={ link.callback(|_| DateExplorerMessage::Pin) } />
+                }
+                if let Some(error) = &self.error {
+                    <ErrorToast
+                        error={ error.clone() }
+                        on_dismiss={ link.callback(|_| DateExplorerMessage::DismissError) }
+                        on_retry={ link.callback(|_| DateExplorerMessage::Retry) } />
+                }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Translations, and dates and prices formatted the way the user's language writes them.
+//!
+//! Messages are [Fluent](https://projectfluent.org), a `.ftl` file per [Locale] under `locales/`, looked up by ID with
+//! [t!]. A message a locale is missing falls back to English. The locale is picked once per page load, changing it
+//! stores the pick and reloads the page rather than re-rendering everything in place.
+
+use std::cell::OnceCell;
+
+use fluent::{FluentArgs, FluentBundle, FluentResource};
+use route_solver_shared::queries::Date;
+use unic_langid::LanguageIdentifier;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::trips;
+
+/// A message in the user's language, with any arguments the message takes given as `name = value`
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::translate($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = ::fluent::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::translate($id, Some(&args))
+    }};
+}
+
+/// Languages the frontend is translated into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    English,
+    French,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::English, Locale::French];
+
+    /// BCP 47 tag, what `Intl` formats with and the page's `lang` is set to
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::French => "fr",
+        }
+    }
+
+    /// The language's name in itself, as it's listed to pick from
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::French => "Français",
+        }
+    }
+
+    /// The locale for a language tag, going by its language alone so `fr-CA` is French
+    pub fn from_code(code: &str) -> Option<Locale> {
+        let language = code.split(['-', '_']).next()?.to_lowercase();
+        Locale::ALL.into_iter().find(|locale| locale.code() == language)
+    }
+
+    fn messages(self) -> &'static str {
+        match self {
+            Locale::English => include_str!("../locales/en.ftl"),
+            Locale::French => include_str!("../locales/fr.ftl"),
+        }
+    }
+
+    fn bundle(self) -> FluentBundle<FluentResource> {
+        let language: LanguageIdentifier = self.code().parse().expect("Locale codes are valid language tags");
+        let resource = FluentResource::try_new(self.messages().to_string())
+            .unwrap_or_else(|(_, errors)| panic!("Bad messages for {}: {:?}", self.code(), errors));
+        let mut bundle = FluentBundle::new(vec![language]);
+        // The isolation marks are for mixing text directions, which no locale here needs
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).expect("Messages are defined once per locale");
+        bundle
+    }
+}
+
+/// The user's pick if they've made one, otherwise the browser's language if it's translated into, otherwise English
+fn preferred_locale() -> Locale {
+    trips::load_locale()
+        .or_else(|| web_sys::window()?.navigator().language())
+        .and_then(|code| Locale::from_code(&code))
+        .unwrap_or(Locale::English)
+}
+
+/// Bundles of the page's locale and of English to fall back on, built the first time a message is looked up
+struct Messages {
+    locale: Locale,
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+thread_local! {
+    static MESSAGES: OnceCell<Messages> = const { OnceCell::new() };
+}
+
+fn with_messages<T>(f: impl FnOnce(&Messages) -> T) -> T {
+    MESSAGES.with(|messages| {
+        f(messages.get_or_init(|| {
+            let locale = preferred_locale();
+            Messages {
+                locale,
+                bundle: locale.bundle(),
+                fallback: Locale::English.bundle(),
+            }
+        }))
+    })
+}
+
+/// Locale the page is shown in
+pub fn current() -> Locale {
+    with_messages(|messages| messages.locale)
+}
+
+/// Message `id` in the page's locale, see [t!]. A message missing from English as well comes out as its ID, so it's
+/// noticed without breaking the page.
+pub fn translate(id: &str, args: Option<&FluentArgs>) -> String {
+    with_messages(|messages| {
+        [&messages.bundle, &messages.fallback]
+            .into_iter()
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(id)?.value()?;
+                let mut errors = vec![];
+                let text = bundle.format_pattern(pattern, args, &mut errors).into_owned();
+                if !errors.is_empty() {
+                    log::warn!("Formatting message {}: {:?}", id, errors);
+                }
+                Some(text)
+            })
+            .unwrap_or_else(|| id.to_string())
+    })
+}
+
+/// `Intl` options from `(name, value)` pairs
+fn intl_options(options: &[(&str, &str)]) -> js_sys::Object {
+    let object = js_sys::Object::new();
+    for (name, value) in options {
+        let _ = js_sys::Reflect::set(&object, &JsValue::from_str(name), &JsValue::from_str(value));
+    }
+    object
+}
+
+fn locales() -> js_sys::Array {
+    js_sys::Array::of1(&JsValue::from_str(current().code()))
+}
+
+/// `amount` of `currency` as the page's locale writes it, e.g. "€12.50" in English and "12,50 €" in French
+pub fn format_currency(amount: f32, currency: &str) -> String {
+    // Intl throws on codes that aren't three letters, leave those as they are
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return format!("{:.2} {}", amount, currency);
+    }
+    let format = js_sys::Intl::NumberFormat::new(&locales(), &intl_options(&[("style", "currency"), ("currency", currency)]));
+    format
+        .format()
+        .call1(&JsValue::UNDEFINED, &JsValue::from_f64(amount as f64))
+        .ok()
+        .and_then(|text| text.as_string())
+        .unwrap_or_else(|| format!("{:.2} {}", amount, currency))
+}
+
+/// How much of a date to write out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateStyle {
+    /// Month and day, e.g. "Jun 3"
+    Short,
+    /// Weekday, month, day and year, e.g. "Sat, Jun 3, 2023"
+    Long,
+}
+
+/// `date` as the page's locale writes it
+pub fn format_date(date: Date, style: DateStyle) -> String {
+    let options: &[(&str, &str)] = match style {
+        DateStyle::Short => &[("month", "short"), ("day", "numeric"), ("timeZone", "UTC")],
+        DateStyle::Long => &[
+            ("weekday", "short"),
+            ("month", "short"),
+            ("day", "numeric"),
+            ("year", "numeric"),
+            ("timeZone", "UTC"),
+        ],
+    };
+    // Midnight UTC, formatted in UTC so the day can't shift with the browser's time zone
+    let midnight = js_sys::Date::new(&JsValue::from_str(&format!("{}T00:00:00Z", date)));
+    midnight.to_locale_date_string(current().code(), &intl_options(options)).into()
+}
+
+/// A moment in milliseconds since the epoch, in the browser's time zone, e.g. "6/3/2023, 2:30:00 PM"
+pub fn format_time(ms: f64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(ms)).to_locale_string(current().code(), &JsValue::UNDEFINED).into()
+}
+
+/// Picks the page's language, reloading it in the new one
+#[function_component(LanguageSelect)]
+pub fn language_select() -> Html {
+    let onchange = Callback::from(|e: Event| {
+        let Some(select) = e.target().and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()) else {
+            return;
+        };
+        trips::store_locale(&select.value());
+        if let Some(window) = web_sys::window() {
+            let _ = window.location().reload();
+        }
+    });
+    let current = current();
+
+    html! {
+        <select class="form-select form-select-sm w-auto" aria-label={ t!("language-label") } {onchange}>
+            { for Locale::ALL.iter().map(|locale| html! {
+                <option value={ locale.code() } selected={ *locale == current }>{ locale.name() }</option>
+            }) }
+        </select>
+    }
+}
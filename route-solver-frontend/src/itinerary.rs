@@ -0,0 +1,1367 @@
+//! The itinerary builder, where the user lists the airports they want to visit and when.
+
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use route_solver_shared::queries::*;
+use serde::{Deserialize, Deserializer, Serialize};
+use wasm_bindgen::JsCast;
+use gloo_timers::future::TimeoutFuture;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::{html::Scope, prelude::*, virtual_dom::Key};
+use yew_router::prelude::*;
+
+use crate::{
+    api::{parse_json, post_json, post_json_with_headers},
+    components::{focus, unique_id, Button, CloseButton, DropDown, ErrorToast, FlyInComponent, ListItem, LiveRegion, Modal, TextBox},
+    currency::{preferred_currency, CurrencySelect},
+    i18n::{format_date, DateStyle},
+    templates::{self, TemplatePicker},
+    trips::{self, SavedSearch, SavedTrip, ShareQuery},
+    Route,
+};
+
+#[derive(PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct ListItemVals {
+    pub airport: String,
+    pub start_dates: (String, String),
+    pub end_dates: (String, String),
+    /// Days the traveller must be there for, e.g. a conference
+    #[serde(alias = "temp_constraints")]
+    pub event_dates: (String, String),
+}
+
+thread_local! {
+    static NEXT_ROW_ID: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Identifies a row of the builder for as long as it's around, however the rows around it are added, removed or moved.
+/// IDs are unique within the page, including ones read back from the autosave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct RowId(u32);
+
+impl RowId {
+    /// An ID no other row has
+    pub fn next() -> RowId {
+        NEXT_ROW_ID.with(|next| {
+            let id = next.get();
+            next.set(id + 1);
+            RowId(id)
+        })
+    }
+}
+
+/// IDs read back are reserved, so [RowId::next] doesn't hand them out again
+impl<'de> Deserialize<'de> for RowId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u32::deserialize(deserializer)?;
+        NEXT_ROW_ID.with(|next| next.set(next.get().max(id + 1)));
+        Ok(RowId(id))
+    }
+}
+
+impl fmt::Display for RowId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<RowId> for Key {
+    fn from(id: RowId) -> Key {
+        Key::from(id.0)
+    }
+}
+
+/// A row of the builder, autosaved with its ID. Saved trips, searches and shared links keep only the values, rows made
+/// from them get new IDs.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct Row {
+    /// Missing from rows autosaved before rows had IDs
+    #[serde(default = "RowId::next")]
+    pub id: RowId,
+    #[serde(flatten)]
+    pub vals: ListItemVals,
+}
+
+impl Row {
+    pub fn new(vals: ListItemVals) -> Row {
+        Row { id: RowId::next(), vals }
+    }
+}
+
+/// Parse a pair of date inputs, one date fixes the day and both give a window
+fn parse_date_inputs((start, end): &(String, String)) -> Result<Option<DateWindow>, ApiError> {
+    let parse = |s: &str| {
+        s.parse::<Date>()
+            .map_err(|_| ApiError::Validation(t!("builder-bad-date", text = s.to_string())))
+    };
+
+    Ok(match (start.is_empty(), end.is_empty()) {
+        (true, true) => None,
+        (false, true) => Some(DateWindow::day(parse(start)?)),
+        (true, false) => Some(DateWindow::day(parse(end)?)),
+        (false, false) => Some(
+            DateWindow::new(parse(start)?, parse(end)?)
+                .ok_or_else(|| ApiError::Validation(t!("builder-reversed-dates", first = start.clone(), last = end.clone())))?,
+        ),
+    })
+}
+
+/// Turn a row into a destination, the start of the trip has no fly in dates and the end no fly out dates
+fn destination_query(vals: &ListItemVals, fly_in: bool, fly_out: bool) -> Result<DestinationQuery, ApiError> {
+    let airport = vals.airport.trim();
+    if airport.is_empty() {
+        return Err(ApiError::Validation(t!("builder-no-airport")));
+    }
+    let iata = airport.parse().map_err(|e: InvalidAirportCode| ApiError::Validation(e.to_string()))?;
+
+    let inbound = if fly_in { parse_date_inputs(&vals.start_dates)? } else { None };
+    let outbound = if fly_out { parse_date_inputs(&vals.end_dates)? } else { None };
+
+    let event = parse_date_inputs(&vals.event_dates)?.map(|window| EventDates {
+        first_day: window.first(),
+        last_day: window.last(),
+    });
+
+    let query = DestinationQuery {
+        iata,
+        arrival_window: inbound,
+        departure_window: outbound,
+        min_days: None,
+        max_days: None,
+        event,
+        max_leg_price: None,
+    };
+    query
+        .validate()
+        .map_err(|e| ApiError::Validation(t!("builder-bad-stay", airport = iata.to_string(), reason = e.to_string())))?;
+    Ok(query)
+}
+
+/// The row's dates as the solver reads them, to check them against the other rows' as they're typed, see
+/// [window_conflicts]. Dates that aren't complete yet are left unbounded, which clash with nothing.
+fn row_dates(vals: &ListItemVals, fly_in: bool, fly_out: bool) -> DateConstraints {
+    let window = |dates| parse_date_inputs(dates).ok().flatten();
+    DateConstraints {
+        arrival_window: fly_in.then(|| window(&vals.start_dates)).flatten(),
+        departure_window: fly_out.then(|| window(&vals.end_dates)).flatten(),
+        date_restrictions: DateRestrictions::new(),
+        event: window(&vals.event_dates).map(|window| EventDates {
+            first_day: window.first(),
+            last_day: window.last(),
+        }),
+    }
+}
+
+/// Days of a window in short, e.g. "Jun 3–Jun 6" or "Jun 3"
+fn describe_window(window: DateWindow) -> String {
+    let (first, last) = (window.first(), window.last());
+    if first == last {
+        format_date(first, DateStyle::Short)
+    } else {
+        format!("{}–{}", format_date(first, DateStyle::Short), format_date(last, DateStyle::Short))
+    }
+}
+
+fn cabin_label(cabin: CabinClass) -> String {
+    match cabin {
+        CabinClass::Economy => t!("cabin-economy"),
+        CabinClass::PremiumEconomy => t!("cabin-premium-economy"),
+        CabinClass::Business => t!("cabin-business"),
+        CabinClass::First => t!("cabin-first"),
+    }
+}
+
+fn describe_warning(warning: &LintWarning) -> String {
+    match warning {
+        LintWarning::ShortStayAfterLongHaul { iata, distance_km } => {
+            t!("lint-short-stay", airport = iata.to_string(), distance = *distance_km)
+        }
+        LintWarning::WideWindow { iata, days } => t!("lint-wide-window", airport = iata.to_string(), days = *days),
+        LintWarning::SameDayHop { from, to } => t!("lint-same-day", from = from.to_string(), to = to.to_string()),
+    }
+}
+
+/// How long after the last edit the itinerary's dates are checked, so typing a date doesn't check every digit of it
+const FEASIBILITY_DELAY_MS: u32 = 600;
+
+/// What the server found checking the itinerary's dates, nothing while they fit and a solve is quick
+fn feasibility_note(feasibility: &Feasibility) -> Html {
+    match feasibility.feasible {
+        Some(false) => html! {
+            <div class="alert alert-warning my-2" role="status">
+                <p class="fw-semibold mb-1">{ t!("feasibility-none") }</p>
+                if !feasibility.conflicts.is_empty() {
+                    <ul class="mb-0">
+                        { for feasibility.conflicts.iter().map(|c| html! {
+                            <li>{ t!("feasibility-conflict", first = c.first.to_string(), second = c.second.to_string()) }</li>
+                        }) }
+                    </ul>
+                }
+            </div>
+        },
+        Some(true) => html! {
+            <p class="small text-body-secondary my-2">{ t!("feasibility-fares", legs = feasibility.legs) }</p>
+        },
+        None => html! {
+            <p class="small text-body-secondary my-2">{ t!("feasibility-unknown") }</p>
+        },
+    }
+}
+
+/// What's filled in on a row, a line per field, for asking before it's thrown away
+fn row_contents(vals: &ListItemVals) -> Vec<String> {
+    // Dates that don't make a window yet are listed as they were typed
+    let dates = |dates: &(String, String)| match parse_date_inputs(dates) {
+        Ok(Some(window)) => describe_window(window),
+        _ => [dates.0.as_str(), dates.1.as_str()].into_iter().filter(|d| !d.is_empty()).collect::<Vec<_>>().join("–"),
+    };
+    let has_dates = |dates: &(String, String)| !dates.0.is_empty() || !dates.1.is_empty();
+
+    let mut contents = vec![];
+    if !vals.airport.trim().is_empty() {
+        contents.push(t!("remove-airport", airport = vals.airport.trim().to_uppercase()));
+    }
+    if has_dates(&vals.start_dates) {
+        contents.push(t!("remove-fly-in", dates = dates(&vals.start_dates)));
+    }
+    if has_dates(&vals.end_dates) {
+        contents.push(t!("remove-fly-out", dates = dates(&vals.end_dates)));
+    }
+    if has_dates(&vals.event_dates) {
+        contents.push(t!("remove-event", dates = dates(&vals.event_dates)));
+    }
+    contents
+}
+
+/// One line summary of a filled in row, e.g. "LIS · arrive Jun 3–Jun 6 · leave Jun 9–Jun 10 · 3–7 nights"
+fn row_summary(vals: &ListItemVals) -> String {
+    let window = |dates| parse_date_inputs(dates).ok().flatten();
+    let (arrival, departure, event) = (window(&vals.start_dates), window(&vals.end_dates), window(&vals.event_dates));
+
+    let mut parts = vec![vals.airport.trim().to_uppercase()];
+    parts.extend(arrival.map(|w| t!("summary-arrive", dates = describe_window(w))));
+    parts.extend(departure.map(|w| t!("summary-leave", dates = describe_window(w))));
+    parts.extend(event.map(|w| t!("summary-event", dates = describe_window(w))));
+    if let Some((arrival, departure)) = arrival.zip(departure) {
+        let shortest = nights_between(arrival.last(), departure.first());
+        let longest = nights_between(arrival.first(), departure.last());
+        parts.push(match (shortest, longest) {
+            (shortest, longest) if shortest == longest => t!("summary-nights", nights = shortest),
+            (shortest, longest) => t!("summary-nights-range", shortest = shortest, longest = longest),
+        });
+    }
+    parts.join(" · ")
+}
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct ItineraryListItemProps {
+    pub id: RowId,
+    pub handlers: RowHandlers,
+    /// Values to start the row off with, e.g. when it's a copy of another row
+    #[prop_or_default]
+    pub initial_vals: ListItemVals,
+    /// Start expanded with the airport focused, for rows the user just added
+    #[prop_or_default]
+    pub focus: bool,
+    /// The other stops whose dates rule this one's out, whichever order they're visited in
+    #[prop_or_default]
+    pub conflicts: Vec<String>,
+}
+
+pub struct ItineraryRow {
+    list_item_vals: ListItemVals,
+    /// Showing the one line summary rather than the inputs, the inputs are rebuilt from the values when expanded
+    collapsed: bool,
+    airport_ref: NodeRef,
+    edit_ref: NodeRef,
+    /// Element to focus once the row has rendered, the airport when it's expanded and the edit button when it's collapsed
+    pending_focus: Option<NodeRef>,
+}
+
+pub enum ItineraryRowMsg {
+    FlyInUpdated(usize, String, String),
+    AirportUpdated(String),
+    SetCollapsed(bool),
+}
+
+impl ItineraryRow {
+    /// Whether there's enough in the row to summarise it
+    fn filled_in(&self) -> bool {
+        !self.list_item_vals.airport.trim().is_empty()
+    }
+}
+
+impl Component for ItineraryRow {
+    type Properties = ItineraryListItemProps;
+    type Message = ItineraryRowMsg;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut row = Self {
+            list_item_vals: ctx.props().initial_vals.clone(),
+            collapsed: false,
+            airport_ref: NodeRef::default(),
+            edit_ref: NodeRef::default(),
+            pending_focus: None,
+        };
+        // Rows restored already filled in start out summarised, ones the user just added are ready to type into
+        if ctx.props().focus {
+            row.pending_focus = Some(row.airport_ref.clone());
+        } else {
+            row.collapsed = row.filled_in();
+        }
+        row
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ItineraryRowMsg::FlyInUpdated(idx, start, end) => {
+                let fly_in_ref = match idx {
+                    0 => &mut self.list_item_vals.start_dates,
+                    1 => &mut self.list_item_vals.end_dates,
+                    2 => &mut self.list_item_vals.event_dates,
+                    _ => panic!("Bad messaging in ItineraryRow element")
+                };
+                *fly_in_ref = (start, end);
+            },
+            ItineraryRowMsg::AirportUpdated(text) => self.list_item_vals.airport = text,
+            ItineraryRowMsg::SetCollapsed(collapsed) => {
+                self.collapsed = collapsed && self.filled_in();
+                let target = if self.collapsed { &self.edit_ref } else { &self.airport_ref };
+                self.pending_focus = Some(target.clone());
+                return true;
+            }
+        }
+        ctx.props().handlers.vals_updated.emit((ctx.props().id, self.list_item_vals.clone()));
+        true
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if let Some(target) = self.pending_focus.take() {
+            focus(&target);
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let remove_handler_passthrough = {
+            let remove_handler = ctx.props().handlers.remove.clone();
+            let id = ctx.props().id;
+            Callback::from(move |_| {
+                remove_handler.emit(id);
+            })
+        };
+        let duplicate_handler_passthrough = {
+            let duplicate_handler = ctx.props().handlers.duplicate.clone();
+            let id = ctx.props().id;
+            Callback::from(move |_| {
+                duplicate_handler.emit(id);
+            })
+        };
+        let vals = &self.list_item_vals;
+        let has_dates = |dates: &(String, String)| !dates.0.is_empty() || !dates.1.is_empty();
+        // Screen readers name the row by its airport, the inputs and buttons in it are labelled the same from row to row
+        let label = match vals.airport.trim() {
+            "" => t!("builder-row-new"),
+            airport => t!("builder-row", airport = airport.to_uppercase()),
+        };
+        // Enter in any of the row's inputs adds a row after it, on buttons it's left to click them
+        let onkeydown = {
+            let add_after_handler = ctx.props().handlers.add_after.clone();
+            let id = ctx.props().id;
+            Callback::from(move |e: KeyboardEvent| {
+                let in_input = e.target().is_some_and(|t| t.dyn_into::<HtmlInputElement>().is_ok());
+                if e.key() == "Enter" && in_input {
+                    e.prevent_default();
+                    add_after_handler.emit(id);
+                }
+            })
+        };
+
+        // Outlined in red with the reason given, on hover and underneath, while the dates clash with another row's
+        let conflict = (!ctx.props().conflicts.is_empty()).then(|| t!("builder-conflict", airports = ctx.props().conflicts.join(", ")));
+        let class = classes!(
+            "row",
+            "my-1",
+            "justify-content-start",
+            "bg-body-secondary",
+            "p-1",
+            "rounded-3",
+            conflict.is_some().then_some("border"),
+            conflict.is_some().then_some("border-danger"),
+        );
+        let conflict_note = conflict.clone().map(|conflict| html! {
+            <p class="small text-danger mb-0 px-2">{ conflict }</p>
+        });
+
+        if self.collapsed {
+            return html! {
+                <div id={ format!("itin-row-{}", ctx.props().id) } {class} title={ conflict } role="group" aria-label={ label }>
+                    <div class="d-flex flex-wrap align-items-center p-2">
+                        <span class="me-auto text-break">{ row_summary(vals) }</span>
+                        <div class="pe-2">
+                            <Button text={ t!("edit") } node_ref={ self.edit_ref.clone() } on_click={ ctx.link().callback(|_| ItineraryRowMsg::SetCollapsed(false)) } />
+                        </div>
+                        <div class="pe-2">
+                            <Button text={ t!("builder-duplicate") } on_click={duplicate_handler_passthrough} />
+                        </div>
+                        <CloseButton text={ t!("builder-remove-row") } on_click={remove_handler_passthrough} />
+                    </div>
+                    { conflict_note.unwrap_or_default() }
+                </div>
+            };
+        }
+
+        // The row's actions come after its inputs, so tabbing goes from the airport through the dates before reaching them
+        html! {
+            <div id={ format!("itin-row-{}", ctx.props().id) } {class} title={ conflict } role="group" aria-label={ label } {onkeydown}>
+                <div class="container p-2">
+                    { conflict_note.unwrap_or_default() }
+                    <div class="row justify-content-start">
+                        <div class={"col-md-auto"}>
+                            <TextBox text={ t!("airport-code") } type_name={"text"} node_ref={ self.airport_ref.clone() } initial_value={ vals.airport.clone() } text_update_handler={ ctx.link().callback(|input: String| ItineraryRowMsg::AirportUpdated(input)) } />
+                        </div>
+                    </div>
+                    <div class="row justify-content-start">
+                        <ListItem text={ t!("builder-add-fly-in") } start_open={ has_dates(&vals.start_dates) }>
+                            <FlyInComponent initial={ vals.start_dates.clone() } fly_in_update_handler={ctx.link().callback(|input: (String, String)| ItineraryRowMsg::FlyInUpdated(0, input.0, input.1))} />
+                        </ListItem>
+                    </div>
+                    <div class="row justify-content-start">
+                        <ListItem text={ t!("builder-add-fly-out") } start_open={ has_dates(&vals.end_dates) }>
+                            <FlyInComponent initial={ vals.end_dates.clone() } fly_in_update_handler={ctx.link().callback(|input: (String, String)| ItineraryRowMsg::FlyInUpdated(1, input.0, input.1))} />
+                        </ListItem>
+                    </div>
+                    <div class="row justify-content-start">
+                        <ListItem text={ t!("builder-add-event") } start_open={ has_dates(&vals.event_dates) }>
+                            <FlyInComponent initial={ vals.event_dates.clone() } fly_in_update_handler={ctx.link().callback(|input: (String, String)| ItineraryRowMsg::FlyInUpdated(2, input.0, input.1))} />
+                        </ListItem>
+                    </div>
+                    <div class="row justify-content-start">
+                        <div class={"col-md-auto"}>
+                            <Button text={ t!("builder-done") } disabled={ !self.filled_in() } on_click={ ctx.link().callback(|_| ItineraryRowMsg::SetCollapsed(true)) } />
+                        </div>
+                        <div class={"col-md-auto"}>
+                            <Button text={ t!("builder-duplicate") } on_click={duplicate_handler_passthrough} />
+                        </div>
+                        <div class={"col-md-auto"}>
+                            <CloseButton text={ t!("builder-remove-row") } on_click={remove_handler_passthrough} />
+                        </div>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TripOptionsProps {
+    pub passengers: Passengers,
+    pub cabin_class: CabinClass,
+    pub checked_bag: bool,
+    pub sampled: bool,
+    pub on_passengers: Callback<Passengers>,
+    pub on_cabin_class: Callback<CabinClass>,
+    pub on_checked_bag: Callback<bool>,
+    pub on_sampled: Callback<bool>,
+}
+
+/// Who is travelling, in which cabin and with what luggage, applies to every leg of the trip. Also how thoroughly the
+/// windows are searched.
+#[function_component(TripOptions)]
+pub fn trip_options(
+    TripOptionsProps {
+        passengers,
+        cabin_class,
+        checked_bag,
+        sampled,
+        on_passengers,
+        on_cabin_class,
+        on_checked_bag,
+        on_sampled,
+    }: &TripOptionsProps,
+) -> Html {
+    // Every tab of the builder has its own
+    let bag_id = use_state(|| unique_id("checked-bag"));
+    let sampled_id = use_state(|| unique_id("sampled"));
+    let switch = |id: &str, checked: bool, on_change: &Callback<bool>, label: String, hint: String| {
+        html! {
+            <div class="form-check form-switch mb-0">
+                <input class="form-check-input" type="checkbox" role="switch" id={ id.to_string() } {checked}
+                    aria-describedby={ format!("{}-hint", id) }
+                    onchange={ on_change.reform(|e: Event| {
+                        e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok()).is_some_and(|i| i.checked())
+                    }) } />
+                <label class="form-check-label" for={ id.to_string() }>{ label }</label>
+                <div class="form-text mt-0" id={ format!("{}-hint", id) }>{ hint }</div>
+            </div>
+        }
+    };
+    let count_input = |label: String, value: u32, min: u32, set: fn(Passengers, u32) -> Passengers| {
+        let passengers = *passengers;
+        let on_passengers = on_passengers.clone();
+        let oninput = Callback::from(move |e: InputEvent| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            if let Some(count) = input.and_then(|i| i.value().parse().ok()) {
+                on_passengers.emit(set(passengers, count));
+            }
+        });
+        html! {
+            <div class="input-group w-auto">
+                <span class="input-group-text">{ label.clone() }</span>
+                <input type="number" class="form-control" style="width: 5rem;" aria-label={ label }
+                    min={ min.to_string() } max={ Passengers::MAX.to_string() } value={ value.to_string() } {oninput} />
+            </div>
+        }
+    };
+
+    let on_select = {
+        let on_cabin_class = on_cabin_class.clone();
+        Callback::from(move |label: String| {
+            if let Some(cabin) = CabinClass::ALL.into_iter().find(|cabin| cabin_label(*cabin) == label) {
+                on_cabin_class.emit(cabin);
+            }
+        })
+    };
+
+    html! {
+        <div class="d-flex flex-wrap align-items-center gap-2 mb-3">
+            { count_input(t!("builder-adults"), passengers.adults, 1, |p, adults| Passengers { adults, ..p }) }
+            { count_input(t!("builder-children"), passengers.children, 0, |p, children| Passengers { children, ..p }) }
+            <DropDown
+                text={ t!("builder-cabin") }
+                opts={ CabinClass::ALL.map(cabin_label).to_vec() }
+                selected={ cabin_label(*cabin_class) }
+                {on_select} />
+            { switch(&bag_id, *checked_bag, on_checked_bag, t!("builder-checked-bag"), t!("builder-checked-bag-hint")) }
+            { switch(&sampled_id, *sampled, on_sampled, t!("builder-sampled"), t!("builder-sampled-hint")) }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TripLimitsProps {
+    pub limits: RouteLimits,
+    pub on_change: Callback<RouteLimits>,
+}
+
+/// Caps on how tiring the trip is allowed to be, for travellers who'd pay a bit more to not be worn out
+#[function_component(TripLimits)]
+pub fn trip_limits(TripLimitsProps { limits, on_change }: &TripLimitsProps) -> Html {
+    // Every tab of the builder has its own
+    let rest_days_id = use_state(|| unique_id("rest-days"));
+    let oninput = {
+        let limits = *limits;
+        let on_change = on_change.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            if let Some(value) = input.map(|i| i.value()) {
+                // Left empty for no limit, anything else that isn't a count is ignored until it is one
+                let max_flights = match value.trim() {
+                    "" => None,
+                    count => match count.parse() {
+                        Ok(count) => Some(count),
+                        Err(_) => return,
+                    },
+                };
+                on_change.emit(RouteLimits { max_flights, ..limits });
+            }
+        })
+    };
+    let on_max_leg_price = {
+        let limits = *limits;
+        let on_change = on_change.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            if let Some(value) = input.map(|i| i.value()) {
+                // Left empty for no ceiling, like the flight count
+                let max_leg_price = match value.trim() {
+                    "" => None,
+                    price => match price.parse::<f32>() {
+                        Ok(price) if price.is_finite() && price >= 0.0 => Some(price),
+                        _ => return,
+                    },
+                };
+                on_change.emit(RouteLimits { max_leg_price, ..limits });
+            }
+        })
+    };
+    let onchange = {
+        let limits = *limits;
+        let on_change = on_change.clone();
+        Callback::from(move |e: Event| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            let max_flight_days_in_a_row = input.is_some_and(|i| i.checked()).then_some(1);
+            on_change.emit(RouteLimits { max_flight_days_in_a_row, ..limits });
+        })
+    };
+
+    html! {
+        <div class="d-flex flex-wrap align-items-center gap-2 mb-3">
+            <div class="input-group w-auto">
+                <span class="input-group-text">{ t!("builder-max-flights") }</span>
+                <input type="number" class="form-control" style="width: 5rem;" aria-label={ t!("builder-max-flights") } min="1" placeholder={ t!("builder-any") }
+                    value={ limits.max_flights.map(|m| m.to_string()).unwrap_or_default() } {oninput} />
+            </div>
+            <div class="input-group w-auto">
+                <span class="input-group-text">{ t!("builder-max-per-leg") }</span>
+                <input type="number" class="form-control" style="width: 7rem;" aria-label={ t!("builder-max-per-leg-label") } min="0" placeholder={ t!("builder-any") }
+                    value={ limits.max_leg_price.map(|m| m.to_string()).unwrap_or_default() } oninput={ on_max_leg_price } />
+            </div>
+            <div class="form-check">
+                <input class="form-check-input" type="checkbox" id={ (*rest_days_id).clone() }
+                    checked={ limits.max_flight_days_in_a_row == Some(1) } {onchange} />
+                <label class="form-check-label" for={ (*rest_days_id).clone() }>{ t!("builder-rest-days") }</label>
+            </div>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ValueOfDayProps {
+    pub value: f32,
+    pub currency: String,
+    pub on_change: Callback<f32>,
+}
+
+/// What a day of the trip is worth to the traveller, so the solver can trade price for a shorter trip
+#[function_component(ValueOfDay)]
+pub fn value_of_day(ValueOfDayProps { value, currency, on_change }: &ValueOfDayProps) -> Html {
+    let oninput = {
+        let on_change = on_change.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            if let Some(value) = input.map(|i| i.value()) {
+                // Left empty for price alone, anything else that isn't an amount is ignored until it is one
+                match value.trim() {
+                    "" => on_change.emit(0.0),
+                    amount => {
+                        if let Ok(amount) = amount.parse::<f32>() {
+                            on_change.emit(amount);
+                        }
+                    }
+                }
+            }
+        })
+    };
+
+    html! {
+        <div class="input-group w-auto mb-3">
+            <span class="input-group-text">{ t!("builder-day-value") }</span>
+            <input type="number" class="form-control" style="width: 7rem;" aria-label={ t!("builder-day-value") } min="0" placeholder={ t!("builder-nothing") }
+                value={ if *value > 0.0 { value.to_string() } else { String::new() } } {oninput} />
+            <span class="input-group-text">{ currency.clone() }</span>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct OriginPenaltiesProps {
+    /// The alternate origins, in the order they were typed
+    pub origins: Vec<AirportCode>,
+    pub penalties: BTreeMap<AirportCode, f32>,
+    pub currency: String,
+    pub on_change: Callback<(AirportCode, f32)>,
+}
+
+/// How much less convenient each alternate origin is than the start, so the solver only starts from one if it saves
+/// more than that
+#[function_component(OriginPenalties)]
+pub fn origin_penalties(OriginPenaltiesProps { origins, penalties, currency, on_change }: &OriginPenaltiesProps) -> Html {
+    let penalty = |origin: AirportCode| {
+        let on_change = on_change.clone();
+        let oninput = Callback::from(move |e: InputEvent| {
+            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+            if let Some(value) = input.map(|i| i.value()) {
+                // Empty for no penalty, like the day's worth
+                match value.trim() {
+                    "" => on_change.emit((origin, 0.0)),
+                    amount => {
+                        if let Ok(amount) = amount.parse::<f32>() {
+                            on_change.emit((origin, amount.max(0.0)));
+                        }
+                    }
+                }
+            }
+        });
+        let value = penalties.get(&origin).copied().filter(|p| *p > 0.0);
+        let label = t!("builder-origin-penalty", airport = origin.to_string());
+
+        html! {
+            <div class="input-group w-auto">
+                <span class="input-group-text">{ label.clone() }</span>
+                <input type="number" class="form-control" style="width: 7rem;" aria-label={ label } min="0" placeholder={ t!("builder-nothing") }
+                    value={ value.map(|p| p.to_string()).unwrap_or_default() } {oninput} />
+                <span class="input-group-text">{ currency.clone() }</span>
+            </div>
+        }
+    };
+
+    if origins.is_empty() {
+        return html! {};
+    }
+    html! {
+        <div class="d-flex flex-wrap gap-2 mt-2">
+            { for origins.iter().map(|origin| penalty(*origin)) }
+        </div>
+    }
+}
+
+/// What a row tells the list about, each called with the row's ID. Made once for the list so handing them to rows
+/// doesn't count as a change of props.
+#[derive(PartialEq, Clone)]
+pub struct RowHandlers {
+    pub remove: Callback<RowId>,
+    pub duplicate: Callback<RowId>,
+    /// Called when Enter is pressed in one of the row's inputs, to add a row after it
+    pub add_after: Callback<RowId>,
+    pub vals_updated: Callback<(RowId, ListItemVals)>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ItineraryListProps {
+    /// The workspace tab the itinerary is in, it's autosaved under the tab
+    pub tab: u32,
+}
+
+pub struct ItineraryList {
+    /// The rows in the order they're shown
+    rows: Vec<Row>,
+    /// Row the user added last, it takes the keyboard focus when it's created
+    focus_row: Option<RowId>,
+    /// Row with something in it the user asked to remove, waiting on them to confirm it
+    confirming_remove: Option<RowId>,
+    row_handlers: RowHandlers,
+    /// Query of the last solve sent, kept so it can be retried
+    last_query: Option<String>,
+    /// Provider calls the user agreed the last query may make, see [SolveEstimate::needs_confirmation]
+    confirmed_calls: Option<u64>,
+    /// How many calls the last query would make, waiting on the user to confirm them
+    confirming_calls: Option<SolveEstimate>,
+    submitting: bool,
+    /// Whether the rows as they are now have been added to the saved trips
+    saved: bool,
+    /// Currency the solve is priced in
+    currency: String,
+    passengers: Passengers,
+    cabin_class: CabinClass,
+    /// Whether fares are priced with a checked bag for everyone
+    checked_bag: bool,
+    /// Whether the windows are searched a few days at a time, see [DateSampling]
+    sampled: bool,
+    limits: RouteLimits,
+    /// Per passenger in `currency`, zero to compare routes on price alone
+    value_of_day: f32,
+    /// Whether the trip ends back where it started, the first row's airport is added as the end automatically
+    round_trip: bool,
+    /// When the flight home may land, only used for round trips
+    return_dates: (String, String),
+    /// Other airports the trip may start from, as typed
+    alternate_origins: String,
+    /// What starting from each alternate origin costs over the start, per passenger in `currency`
+    origin_penalties: BTreeMap<AirportCode, f32>,
+    /// What the server warned about the last query sent, solved anyway on request
+    warnings: Vec<LintWarning>,
+    /// What the server made of the dates as they were last edited, `None` until they're complete enough to check
+    feasibility: Option<Feasibility>,
+    /// Counts the edits to the dates, a check only lands if they haven't been edited again since it was asked for
+    dates_edit: u32,
+    error: Option<ApiError>,
+    /// Last change to the rows, read out by screen readers as the list changes away from their focus
+    announcement: String,
+    /// ID of the round trip switch, unique as every tab has one
+    round_trip_id: String,
+}
+
+pub enum ItineraryListMessage {
+    AddChild,
+    /// Add a blank row after the row with the ID
+    AddChildAfter(RowId),
+    DuplicateChild(RowId),
+    /// Remove the row, asking first if there's anything in it
+    ConfirmRemove(RowId),
+    CancelRemove,
+    RemoveChild(RowId),
+    ChildUpdate(RowId, ListItemVals),
+    SendPost,
+    /// The server's warnings about the query, it's held back until they're dismissed
+    Linted(Vec<LintWarning>),
+    SolveAnyway,
+    /// The query makes more provider calls than the server solves without asking, it's held back until they're confirmed
+    Estimated(SolveEstimate),
+    ConfirmCalls,
+    CancelCalls,
+    RetryPost,
+    Submitted(JobId),
+    SubmitFailed(ApiError),
+    SaveTrip,
+    SetCurrency(String),
+    SetPassengers(Passengers),
+    SetCabinClass(CabinClass),
+    SetCheckedBag(bool),
+    SetSampled(bool),
+    SetLimits(RouteLimits),
+    SetValueOfDay(f32),
+    SetRoundTrip(bool),
+    /// Replace the rows with a template's stops
+    ApplyTemplate(TripTemplate),
+    ReturnDatesUpdate((String, String)),
+    AlternateOriginsUpdate(String),
+    SetOriginPenalty((AirportCode, f32)),
+    /// Check the dates if they're as they were after the numbered edit
+    CheckFeasibility(u32),
+    FeasibilityChecked(u32, Option<Feasibility>),
+    DismissError,
+}
+
+impl ItineraryList {
+    /// Have the server check the query for mistakes before solving it, going on to [Self::estimate_and_submit] if there
+    /// aren't any or it couldn't check
+    async fn lint_and_submit(link: Scope<Self>, query: String) {
+        match post_json("/api/v1/lint", query.clone()).await.and_then(|r| parse_json::<Vec<LintWarning>>(&r)) {
+            Ok(warnings) if !warnings.is_empty() => link.send_message(ItineraryListMessage::Linted(warnings)),
+            _ => Self::estimate_and_submit(link, query).await,
+        }
+    }
+
+    /// Ask the server how many provider calls solving the query makes, solving straight away unless the user has to
+    /// confirm them. Without an estimate the solve is sent as it is, the server turns it down if it needed confirming.
+    async fn estimate_and_submit(link: Scope<Self>, query: String) {
+        match post_json("/api/v1/estimate", query.clone()).await.and_then(|r| parse_json::<SolveEstimate>(&r)) {
+            Ok(estimate) if estimate.needs_confirmation() => link.send_message(ItineraryListMessage::Estimated(estimate)),
+            _ => Self::submit(link, query, None).await,
+        }
+    }
+
+    async fn check_feasibility(link: Scope<Self>, edit: u32, query: String) {
+        let feasibility = post_json("/api/v1/feasibility", query).await.and_then(|r| parse_json::<Feasibility>(&r));
+        link.send_message(ItineraryListMessage::FeasibilityChecked(edit, feasibility.ok()));
+    }
+
+    /// Submit the solve with the calls the user `confirmed` it may make, the results page takes it from there
+    async fn submit(link: Scope<Self>, query: String, confirmed: Option<u64>) {
+        let headers: Vec<_> = confirmed.map(|calls| (CONFIRMED_CALLS_HEADER, calls.to_string())).into_iter().collect();
+        match post_json_with_headers("/api/v1/jobs", query, &headers).await.and_then(|r| parse_json::<JobHandle>(&r)) {
+            Ok(handle) => link.send_message(ItineraryListMessage::Submitted(handle.id)),
+            Err(e) => link.send_message(ItineraryListMessage::SubmitFailed(e)),
+        }
+    }
+
+    /// Values of the rows, in display order
+    fn active_rows(&self) -> impl Iterator<Item = &ListItemVals> {
+        self.rows.iter().map(|row| &row.vals)
+    }
+
+    fn row(&self, id: RowId) -> Option<&Row> {
+        self.rows.iter().find(|row| row.id == id)
+    }
+
+    /// Save the rows to local storage, and to the page URL so it can be bookmarked or shared at any point
+    fn autosave(&self, ctx: &Context<Self>) {
+        trips::autosave(ctx.props().tab, &self.rows);
+
+        let rows: Vec<&ListItemVals> = self.active_rows().collect();
+        if let (Some(query), Some(navigator)) = (ShareQuery::encode(&rows), ctx.link().navigator()) {
+            let _ = navigator.replace_with_query(&Route::Builder, &query);
+        }
+    }
+
+    /// Add a row the user asked for right after row `after`, or at the end, it takes the keyboard focus
+    fn insert_row(&mut self, vals: ListItemVals, after: Option<RowId>) {
+        let position = after
+            .and_then(|after| self.rows.iter().position(|row| row.id == after))
+            .map_or(self.rows.len(), |p| p + 1);
+        let row = Row::new(vals);
+        self.focus_row = Some(row.id);
+        self.rows.insert(position, row);
+    }
+
+    fn start_submit(&mut self, ctx: &Context<Self>, query: String) {
+        self.error = None;
+        self.submitting = true;
+        spawn_local(Self::submit(ctx.link().clone(), query, self.confirmed_calls));
+    }
+
+    /// The end of a round trip, back at the first row's airport
+    fn return_row(&self) -> Option<ListItemVals> {
+        let first = self.active_rows().next()?;
+        Some(ListItemVals {
+            airport: first.airport.clone(),
+            start_dates: self.return_dates.clone(),
+            ..ListItemVals::default()
+        })
+    }
+
+    /// The stops each row's dates clash with, by the row's ID, see [window_conflicts]. Rows without an airport yet are
+    /// named by their place in the list.
+    fn row_conflicts(&self) -> HashMap<RowId, Vec<String>> {
+        let mut rows: Vec<&ListItemVals> = self.active_rows().collect();
+        let return_row = self.return_row();
+        if let Some(return_row) = return_row.as_ref().filter(|_| self.round_trip) {
+            rows.push(return_row);
+        }
+        let last = rows.len().saturating_sub(1);
+        let dates: Vec<DateConstraints> = rows.iter().enumerate().map(|(i, vals)| row_dates(vals, i > 0, i < last)).collect();
+        let name = |i: usize| match rows[i].airport.trim() {
+            "" => t!("builder-row-number", number = i + 1),
+            airport => airport.to_uppercase(),
+        };
+
+        let mut conflicts: HashMap<RowId, Vec<String>> = HashMap::new();
+        for (a, b) in window_conflicts(&dates) {
+            // The way home isn't one of the rows, it's only named on the row it clashes with
+            for (row, other) in [(a, b), (b, a)] {
+                if let Some(row) = self.rows.get(row) {
+                    conflicts.entry(row.id).or_default().push(name(other));
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn alternate_origins_typed(&self) -> impl Iterator<Item = &str> {
+        self.alternate_origins.split(|c: char| c == ',' || c.is_whitespace()).filter(|code| !code.is_empty())
+    }
+
+    /// Build the route query from the rows, the first row is where the trip starts and the last where it ends
+    fn build_query(&self) -> Result<RouteQuery, ApiError> {
+        let mut rows: Vec<&ListItemVals> = self.active_rows().collect();
+        let return_row = self.return_row();
+        if let Some(return_row) = return_row.as_ref().filter(|_| self.round_trip) {
+            rows.push(return_row);
+        }
+
+        let [first, hops @ .., last] = rows.as_slice() else {
+            return Err(ApiError::Validation(t!("builder-too-few-rows")));
+        };
+
+        let alternate_origins: Vec<AirportCode> = self
+            .alternate_origins_typed()
+            .map(|code| code.parse().map_err(|e: InvalidAirportCode| ApiError::Validation(e.to_string())))
+            .collect::<Result<_, _>>()?;
+
+        Ok(RouteQuery {
+            start_city: destination_query(first, false, true)?,
+            end_city: destination_query(last, true, false)?,
+            hops: hops
+                .iter()
+                .map(|vals| destination_query(vals, true, true))
+                .collect::<Result<_, _>>()?,
+            alternate_origins: alternate_origins.clone(),
+            // Only the origins still listed, a penalty is kept while its origin's code is being retyped
+            origin_penalties: self
+                .origin_penalties
+                .iter()
+                .filter(|(origin, penalty)| alternate_origins.contains(origin) && **penalty > 0.0)
+                .map(|(origin, penalty)| (*origin, *penalty))
+                .collect(),
+            currency: Some(self.currency.clone()),
+            market: None,
+            locale: None,
+            passengers: self.passengers,
+            cabin_class: self.cabin_class,
+            strategy: SearchStrategy::default(),
+            limits: self.limits,
+            value_of_day: self.value_of_day,
+            objective: Objective::default(),
+            checked_bag: self.checked_bag,
+            sampling: self.sampled.then(DateSampling::default),
+        })
+    }
+}
+
+impl Component for ItineraryList {
+    type Properties = ItineraryListProps;
+    type Message = ItineraryListMessage;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let link = ctx.link();
+        let mut list = Self {
+            rows: vec![],
+            focus_row: None,
+            confirming_remove: None,
+            row_handlers: RowHandlers {
+                remove: link.callback(ItineraryListMessage::ConfirmRemove),
+                duplicate: link.callback(ItineraryListMessage::DuplicateChild),
+                add_after: link.callback(ItineraryListMessage::AddChildAfter),
+                vals_updated: link.callback(|(id, vals)| ItineraryListMessage::ChildUpdate(id, vals)),
+            },
+            last_query: None,
+            confirmed_calls: None,
+            confirming_calls: None,
+            submitting: false,
+            saved: false,
+            currency: preferred_currency(),
+            passengers: Passengers::default(),
+            cabin_class: CabinClass::default(),
+            checked_bag: false,
+            sampled: false,
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            round_trip: false,
+            return_dates: Default::default(),
+            alternate_origins: String::new(),
+            origin_penalties: BTreeMap::new(),
+            warnings: Vec::new(),
+            feasibility: None,
+            dates_edit: 0,
+            error: None,
+            announcement: String::new(),
+            round_trip_id: unique_id("round-trip"),
+        };
+        // Shared links are put in a tab by the workspace before the tab's list is made
+        list.rows = trips::load_autosave(ctx.props().tab);
+        list
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        use web_sys::console;
+
+        let rows_changed = matches!(
+            msg,
+            ItineraryListMessage::AddChild
+                | ItineraryListMessage::AddChildAfter(_)
+                | ItineraryListMessage::DuplicateChild(_)
+                | ItineraryListMessage::RemoveChild(_)
+                | ItineraryListMessage::ChildUpdate(..)
+                | ItineraryListMessage::ApplyTemplate(_)
+        );
+        let dates_changed = rows_changed
+            || matches!(
+                msg,
+                ItineraryListMessage::SetRoundTrip(_)
+                    | ItineraryListMessage::ReturnDatesUpdate(_)
+                    | ItineraryListMessage::AlternateOriginsUpdate(_)
+            );
+        // Rows coming and going, rather than being edited
+        let announce: Option<fn(usize) -> String> = match msg {
+            ItineraryListMessage::AddChild | ItineraryListMessage::AddChildAfter(_) => {
+                Some(|rows| t!("announce-row-added", rows = rows))
+            }
+            ItineraryListMessage::DuplicateChild(_) => Some(|rows| t!("announce-row-duplicated", rows = rows)),
+            ItineraryListMessage::RemoveChild(_) => Some(|rows| t!("announce-row-removed", rows = rows)),
+            ItineraryListMessage::ApplyTemplate(_) => Some(|rows| t!("announce-template", rows = rows)),
+            _ => None,
+        };
+
+        match msg {
+            ItineraryListMessage::AddChild => self.insert_row(ListItemVals::default(), None),
+            ItineraryListMessage::AddChildAfter(id) => self.insert_row(ListItemVals::default(), Some(id)),
+            ItineraryListMessage::DuplicateChild(id) => {
+                let Some(vals) = self.row(id).map(|row| row.vals.clone()) else {
+                    return false;
+                };
+                self.insert_row(vals, Some(id));
+            }
+            ItineraryListMessage::ChildUpdate(id, vals) => {
+                if let Some(row) = self.rows.iter_mut().find(|row| row.id == id) {
+                    row.vals = vals;
+                }
+            }
+            ItineraryListMessage::SendPost => {
+                if self.submitting {
+                    return false;
+                }
+
+                match self.build_query() {
+                    Ok(query) => {
+                        let query = serde_json::to_string(&query).unwrap();
+                        console::log_1(&("Solving: ".to_string() + &query).into());
+                        self.last_query = Some(query.clone());
+                        self.confirmed_calls = None;
+                        self.error = None;
+                        self.warnings.clear();
+                        self.submitting = true;
+                        spawn_local(Self::lint_and_submit(ctx.link().clone(), query));
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            ItineraryListMessage::Linted(warnings) => {
+                self.submitting = false;
+                self.warnings = warnings;
+            }
+            ItineraryListMessage::SolveAnyway => {
+                self.warnings.clear();
+                if let (false, Some(query)) = (self.submitting, self.last_query.clone()) {
+                    self.submitting = true;
+                    spawn_local(Self::estimate_and_submit(ctx.link().clone(), query));
+                }
+            }
+            ItineraryListMessage::Estimated(estimate) => {
+                self.submitting = false;
+                self.confirming_calls = Some(estimate);
+            }
+            ItineraryListMessage::ConfirmCalls => {
+                let Some(estimate) = self.confirming_calls.take() else {
+                    return false;
+                };
+                self.confirmed_calls = Some(estimate.calls);
+                if let (false, Some(query)) = (self.submitting, self.last_query.clone()) {
+                    self.start_submit(ctx, query);
+                }
+            }
+            ItineraryListMessage::CancelCalls => self.confirming_calls = None,
+            ItineraryListMessage::RetryPost => {
+                if let (false, Some(query)) = (self.submitting, self.last_query.clone()) {
+                    self.start_submit(ctx, query);
+                }
+            }
+            ItineraryListMessage::Submitted(id) => {
+                self.submitting = false;
+                if let Some(query) = self.last_query.as_deref().and_then(|query| serde_json::from_str(query).ok()) {
+                    trips::record_search(SavedSearch::new(id, query, self.active_rows().cloned().collect()));
+                }
+                if let Some(navigator) = ctx.link().navigator() {
+                    navigator.push(&Route::Results { id });
+                }
+            }
+            ItineraryListMessage::SubmitFailed(e) => {
+                self.submitting = false;
+                self.error = Some(e);
+            }
+            ItineraryListMessage::SaveTrip => {
+                let mut saved_trips = trips::load_saved_trips();
+                saved_trips.push(SavedTrip::from_rows(self.active_rows().cloned().collect()));
+                trips::store_saved_trips(&saved_trips);
+                self.saved = true;
+            }
+            ItineraryListMessage::SetCurrency(currency) => self.currency = currency,
+            ItineraryListMessage::SetPassengers(passengers) => self.passengers = passengers,
+            ItineraryListMessage::SetCabinClass(cabin) => self.cabin_class = cabin,
+            ItineraryListMessage::SetCheckedBag(checked_bag) => self.checked_bag = checked_bag,
+            ItineraryListMessage::SetSampled(sampled) => self.sampled = sampled,
+            ItineraryListMessage::SetLimits(limits) => self.limits = limits,
+            ItineraryListMessage::SetValueOfDay(value) => self.value_of_day = value,
+            ItineraryListMessage::SetRoundTrip(round_trip) => self.round_trip = round_trip,
+            ItineraryListMessage::ApplyTemplate(template) => {
+                let Some(start) = templates::template_start() else {
+                    return false;
+                };
+                self.rows = templates::template_rows(&template, start).into_iter().map(Row::new).collect();
+                self.focus_row = None;
+                // Templates list their end explicitly
+                self.round_trip = false;
+            }
+            ItineraryListMessage::ReturnDatesUpdate(dates) => self.return_dates = dates,
+            ItineraryListMessage::AlternateOriginsUpdate(text) => self.alternate_origins = text,
+            ItineraryListMessage::SetOriginPenalty((origin, penalty)) => {
+                self.origin_penalties.insert(origin, penalty);
+            }
+            ItineraryListMessage::CheckFeasibility(edit) => {
+                if edit != self.dates_edit {
+                    return false;
+                }
+                match self.build_query() {
+                    Ok(query) => {
+                        let query = serde_json::to_string(&query).unwrap();
+                        spawn_local(Self::check_feasibility(ctx.link().clone(), edit, query));
+                        return false;
+                    }
+                    // Half typed in, nothing to say about it yet
+                    Err(_) => self.feasibility = None,
+                }
+            }
+            ItineraryListMessage::FeasibilityChecked(edit, feasibility) => {
+                if edit != self.dates_edit {
+                    return false;
+                }
+                self.feasibility = feasibility;
+            }
+            ItineraryListMessage::DismissError => self.error = None,
+            ItineraryListMessage::ConfirmRemove(id) => {
+                let Some(row) = self.row(id) else {
+                    return false;
+                };
+                if row_contents(&row.vals).is_empty() {
+                    ctx.link().send_message(ItineraryListMessage::RemoveChild(id));
+                    return false;
+                }
+                self.confirming_remove = Some(id);
+            }
+            ItineraryListMessage::CancelRemove => self.confirming_remove = None,
+            ItineraryListMessage::RemoveChild(id) => {
+                self.rows.retain(|row| row.id != id);
+                self.confirming_remove = None;
+            }
+        }
+
+        if let Some(announce) = announce {
+            self.announcement = announce(self.active_rows().count());
+        }
+        if rows_changed {
+            self.saved = false;
+            self.warnings.clear();
+            self.autosave(ctx);
+        }
+        if dates_changed {
+            self.dates_edit += 1;
+            let (link, edit) = (ctx.link().clone(), self.dates_edit);
+            spawn_local(async move {
+                TimeoutFuture::new(FEASIBILITY_DELAY_MS).await;
+                link.send_message(ItineraryListMessage::CheckFeasibility(edit));
+            });
+        }
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+
+        let mut conflicts = self.row_conflicts();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| html! {
+                <ItineraryRow
+                    key={ row.id }
+                    id={ row.id }
+                    initial_vals={ row.vals.clone() }
+                    focus={ self.focus_row == Some(row.id) }
+                    conflicts={ conflicts.remove(&row.id).unwrap_or_default() }
+                    handlers={ self.row_handlers.clone() } />
+            })
+            .collect::<Html>();
+        let share = ShareQuery::encode(&self.active_rows().collect::<Vec<_>>());
+
+        html! {
+            <>
+                <h1 class="text-body-emphasis">{ t!("builder-title") }</h1>
+                <TemplatePicker on_pick={ link.callback(ItineraryListMessage::ApplyTemplate) } />
+                <TripOptions
+                    passengers={ self.passengers }
+                    cabin_class={ self.cabin_class }
+                    checked_bag={ self.checked_bag }
+                    sampled={ self.sampled }
+                    on_passengers={ link.callback(ItineraryListMessage::SetPassengers) }
+                    on_cabin_class={ link.callback(ItineraryListMessage::SetCabinClass) }
+                    on_checked_bag={ link.callback(ItineraryListMessage::SetCheckedBag) }
+                    on_sampled={ link.callback(ItineraryListMessage::SetSampled) } />
+                <TripLimits limits={ self.limits } on_change={ link.callback(ItineraryListMessage::SetLimits) } />
+                <ValueOfDay value={ self.value_of_day } currency={ self.currency.clone() } on_change={ link.callback(ItineraryListMessage::SetValueOfDay) } />
+                { rows }
+                <LiveRegion message={ self.announcement.clone() } />
+                if let Some(feasibility) = &self.feasibility {
+                    { feasibility_note(feasibility) }
+                }
+                if let Some(return_row) = self.return_row().filter(|_| self.round_trip) {
+                    <div class="row my-1 justify-content-start bg-body-secondary p-1 rounded-3">
+                        <div class="container p-2">
+                            <p class="fw-semibold mb-1">
+                                if return_row.airport.trim().is_empty() {
+                                    { t!("builder-return-to-start") }
+                                } else {
+                                    { t!("builder-return-to", airport = return_row.airport.trim().to_uppercase()) }
+                                }
+                            </p>
+                            <ListItem text={ t!("builder-add-fly-in") } start_open={ true }>
+                                <FlyInComponent initial={ self.return_dates.clone() } fly_in_update_handler={ link.callback(ItineraryListMessage::ReturnDatesUpdate) } />
+                            </ListItem>
+                        </div>
+                    </div>
+                }
+                <div class="my-2">
+                    <TextBox text={ t!("builder-alternate-origins") } type_name={ "text" } initial_value={ self.alternate_origins.clone() }
+                        text_update_handler={ link.callback(ItineraryListMessage::AlternateOriginsUpdate) } />
+                    <OriginPenalties
+                        origins={ self.alternate_origins_typed().filter_map(|code| code.parse().ok()).collect::<Vec<_>>() }
+                        penalties={ self.origin_penalties.clone() }
+                        currency={ self.currency.clone() }
+                        on_change={ link.callback(ItineraryListMessage::SetOriginPenalty) } />
+                </div>
+                <div class="form-check form-switch my-2">
+                    <input class="form-check-input" type="checkbox" role="switch" id={ self.round_trip_id.clone() } checked={ self.round_trip }
+                        onchange={ link.callback(|e: Event| {
+                            let input = e.target().and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+                            ItineraryListMessage::SetRoundTrip(input.is_some_and(|i| i.checked()))
+                        }) } />
+                    <label class="form-check-label" for={ self.round_trip_id.clone() }>{ t!("builder-round-trip") }</label>
+                </div>
+                <div class="d-flex flex-wrap align-items-center">
+                    <div class="pe-2">
+                        <CurrencySelect value={ self.currency.clone() } on_change={ link.callback(ItineraryListMessage::SetCurrency) } />
+                    </div>
+                    <div class="pe-2">
+                        <Button text={ t!("builder-save") } disabled={ self.saved } on_click={ link.callback(|_| ItineraryListMessage::SaveTrip) } />
+                    </div>
+                    <div class="pe-2">
+                        <Link<Route, ShareQuery> to={ Route::SharedTrip } query={ share }>{ t!("builder-share") }</Link<Route, ShareQuery>>
+                    </div>
+                    <Link<Route> to={ Route::Searches }>{ t!("searches-title") }</Link<Route>>
+                </div>
+                // Kept in reach at the bottom of the screen however long the itinerary gets, which on a phone is soon
+                <div class="d-flex align-items-center sticky-bottom bg-body border-top py-1">
+                    <div class="pe-2">
+                        <Button text={ t!("builder-add-row") } on_click={ link.callback(|_| ItineraryListMessage::AddChild) } />
+                    </div>
+                    <Button text={ t!("builder-go") } disabled={ self.submitting } on_click={ link.callback(|_| ItineraryListMessage::SendPost) } />
+                </div>
+                if !self.warnings.is_empty() {
+                    <div class="alert alert-warning my-2" role="alert">
+                        <p class="fw-semibold mb-1">{ t!("lint-title") }</p>
+                        <ul class="mb-1">
+                            { for self.warnings.iter().map(|warning| html! { <li>{ describe_warning(warning) }</li> }) }
+                        </ul>
+                        <Button text={ t!("lint-solve-anyway") } on_click={ link.callback(|_| ItineraryListMessage::SolveAnyway) } />
+                    </div>
+                }
+                if self.saved {
+                    <p>{ t!("builder-saved") }<Link<Route> to={ Route::SavedTrips }>{ t!("builder-saved-link") }</Link<Route>></p>
+                }
+                if let Some((id, vals)) = self.confirming_remove.and_then(|id| self.row(id)).map(|row| (row.id, &row.vals)) {
+                    <Modal
+                        id={ format!("confirm-remove-{}", ctx.props().tab) }
+                        main_text={ t!("remove-title") }
+                        open={ true }
+                        on_close={ link.callback(|_| ItineraryListMessage::CancelRemove) }
+                        close_text={ t!("cancel") }
+                        internal_html={ html! {
+                            <>
+                                <p>{ t!("remove-lost") }</p>
+                                <ul class="mb-0">
+                                    { for row_contents(vals).into_iter().map(|line| html! { <li>{ line }</li> }) }
+                                </ul>
+                            </>
+                        } }
+                        footer={ html! {
+                            <button type="button" class="btn btn-danger" onclick={ link.callback(move |_| ItineraryListMessage::RemoveChild(id)) }>
+                                { t!("remove-confirm") }
+                            </button>
+                        } } />
+                }
+                if let Some(estimate) = &self.confirming_calls {
+                    <Modal
+                        id={ format!("confirm-calls-{}", ctx.props().tab) }
+                        main_text={ t!("calls-title") }
+                        open={ true }
+                        on_close={ link.callback(|_| ItineraryListMessage::CancelCalls) }
+                        close_text={ t!("cancel") }
+                        internal_html={ html! {
+                            <>
+                                <p>{ t!("calls-estimate", calls = estimate.calls, legs = estimate.legs, cached = estimate.cached) }</p>
+                                if let Some(remaining) = estimate.remaining {
+                                    <p class="mb-0">{ t!("calls-remaining", remaining = remaining) }</p>
+                                }
+                            </>
+                        } }
+                        footer={ html! {
+                            <button type="button" class="btn btn-primary" onclick={ link.callback(|_| ItineraryListMessage::ConfirmCalls) }>
+                                { t!("calls-confirm") }
+                            </button>
+                        } } />
+                }
+                if let Some(error) = &self.error {
+                    <ErrorToast
+                        error={ error.clone() }
+                        on_dismiss={ link.callback(|_| ItineraryListMessage::DismissError) }
+                        on_retry={ link.callback(|_| ItineraryListMessage::RetryPost) } />
+                }
+            </>
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Map of a solved route, drawn with Leaflet (loaded by `index.html`).
+
+use std::collections::HashMap;
+
+use route_solver_shared::queries::{Airport, RouteSolution};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlElement;
+use yew::prelude::*;
+
+use crate::{
+    airports::Airports,
+    currency::format_price,
+    i18n::{format_date, DateStyle},
+};
+
+const TILE_URL: &str = "https://{s}.tile.openstreetmap.org/{z}/{x}/{y}.png";
+const TILE_ATTRIBUTION: &str = "&copy; <a href=\"https://www.openstreetmap.org/copyright\">OpenStreetMap</a> contributors";
+/// Points per leg, enough for the arcs to look smooth
+const ARC_SEGMENTS: usize = 24;
+/// How far legs bow out from a straight line, relative to their length
+const ARC_BULGE: f64 = 0.2;
+
+#[wasm_bindgen]
+extern "C" {
+    type LeafletMap;
+    type Layer;
+
+    #[wasm_bindgen(js_namespace = L, js_name = map)]
+    fn leaflet_map(element: &HtmlElement) -> LeafletMap;
+    #[wasm_bindgen(method, js_name = fitBounds)]
+    fn fit_bounds(this: &LeafletMap, bounds: &JsValue, options: &JsValue);
+    #[wasm_bindgen(method)]
+    fn remove(this: &LeafletMap);
+
+    #[wasm_bindgen(js_namespace = L, js_name = tileLayer)]
+    fn tile_layer(url: &str, options: &JsValue) -> Layer;
+    #[wasm_bindgen(js_namespace = L)]
+    fn marker(latlng: &JsValue) -> Layer;
+    #[wasm_bindgen(js_namespace = L)]
+    fn polyline(latlngs: &JsValue, options: &JsValue) -> Layer;
+    #[wasm_bindgen(method, js_name = bindPopup)]
+    fn bind_popup(this: &Layer, content: &str) -> Layer;
+    #[wasm_bindgen(method, js_name = addTo)]
+    fn add_to(this: &Layer, map: &LeafletMap) -> Layer;
+}
+
+/// Leaflet takes plain JS objects for its options and coordinates
+fn js(value: serde_json::Value) -> JsValue {
+    js_sys::JSON::parse(&value.to_string()).unwrap_or(JsValue::NULL)
+}
+
+/// Points along a curve from `from` to `to`, so legs in opposite directions don't draw over each other
+fn arc_points(from: (f64, f64), to: (f64, f64)) -> Vec<[f64; 2]> {
+    let (d_lat, d_lon) = (to.0 - from.0, to.1 - from.1);
+    // Control point off the midpoint, to the left of the direction of travel
+    let control = (
+        (from.0 + to.0) / 2.0 + d_lon * ARC_BULGE,
+        (from.1 + to.1) / 2.0 - d_lat * ARC_BULGE,
+    );
+
+    (0..=ARC_SEGMENTS)
+        .map(|i| {
+            let t = i as f64 / ARC_SEGMENTS as f64;
+            let bezier = |a: f64, c: f64, b: f64| (1.0 - t).powi(2) * a + 2.0 * (1.0 - t) * t * c + t.powi(2) * b;
+            [bezier(from.0, control.0, to.0), bezier(from.1, control.1, to.1)]
+        })
+        .collect()
+}
+
+/// Green for the cheapest leg through to red for the most expensive
+fn price_color(price: f32, min: f32, max: f32) -> String {
+    let t = if max > min { (price - min) / (max - min) } else { 0.0 };
+    format!("hsl({:.0}, 80%, 40%)", 120.0 * (1.0 - t))
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RouteMapProps {
+    pub solution: RouteSolution,
+    /// Where the route's airports are, legs to airports missing from here aren't drawn
+    pub airports: Airports,
+}
+
+pub struct RouteMap {
+    node: NodeRef,
+    map: Option<LeafletMap>,
+}
+
+impl RouteMap {
+    fn draw(&self, element: &HtmlElement, solution: &RouteSolution, airports: &HashMap<String, Airport>) -> LeafletMap {
+        let map = leaflet_map(element);
+        tile_layer(TILE_URL, &js(json!({ "attribution": TILE_ATTRIBUTION, "maxZoom": 18 }))).add_to(&map);
+
+        let mut bounds = vec![];
+        for airport in airports.values() {
+            bounds.push([airport.lat, airport.lon]);
+            let flag = airport.flag().map(|f| f + " ").unwrap_or_default();
+            marker(&js(json!([airport.lat, airport.lon])))
+                .bind_popup(&format!("{}{} ({}): {}", flag, airport.city, airport.iata, airport.name))
+                .add_to(&map);
+        }
+
+        let prices = solution.flights.iter().map(|f| f.price);
+        let min = prices.clone().fold(f32::INFINITY, f32::min);
+        let max = prices.fold(f32::NEG_INFINITY, f32::max);
+
+        for leg in &solution.flights {
+            let (Some(src), Some(dest)) = (airports.get(leg.flight.src.as_str()), airports.get(leg.flight.dest.as_str())) else {
+                continue;
+            };
+            let points = arc_points((src.lat, src.lon), (dest.lat, dest.lon));
+            let options = json!({ "color": price_color(leg.price, min, max), "weight": 3 });
+            let price = format_price(leg.price, &solution.currency);
+
+            polyline(&js(json!(points)), &js(options))
+                .bind_popup(&format!(
+                    "{}: {} -> {} ({})",
+                    format_date(leg.flight.date, DateStyle::Short),
+                    src.city,
+                    dest.city,
+                    price
+                ))
+                .add_to(&map);
+        }
+
+        map.fit_bounds(&js(json!(bounds)), &js(json!({ "padding": [30, 30] })));
+        map
+    }
+}
+
+impl Component for RouteMap {
+    type Properties = RouteMapProps;
+    type Message = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node: NodeRef::default(),
+            map: None,
+        }
+    }
+
+    fn changed(&mut self, _ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        // Drawn again from scratch once rendered
+        if let Some(map) = self.map.take() {
+            map.remove();
+        }
+        true
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        let airports = &ctx.props().airports;
+        if self.map.is_some() || airports.is_empty() {
+            return;
+        }
+
+        if let Some(element) = self.node.cast::<HtmlElement>() {
+            self.map = Some(self.draw(&element, &ctx.props().solution, airports));
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        if let Some(map) = self.map.take() {
+            map.remove();
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div ref={ self.node.clone() } class="route-map my-3 rounded-3" hidden={ ctx.props().airports.is_empty() }></div>
+        }
+    }
+}
@@ -0,0 +1,746 @@
+//! Pages other than the itinerary builder.
+
+use std::{cell::Cell, rc::Rc};
+
+use gloo_timers::future::TimeoutFuture;
+use route_solver_shared::queries::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::{html::Scope, prelude::*};
+use yew_router::prelude::*;
+
+use crate::{
+    api::{get_json, parse_json, post_json, resolve_swapped, submit_query, SOLVE_POLL_INTERVAL_MS},
+    airports::{load_airports, Airports},
+    budget::BudgetBreakdown,
+    compare::CandidateComparison,
+    components::{Button, ErrorToast, LegTable, LiveRegion, SolveIndicator, SolveProgress},
+    currency::format_price,
+    explore::DateExplorer,
+    i18n::{format_date, format_time, DateStyle},
+    itinerary::ListItemVals,
+    map::RouteMap,
+    print::{PrintButton, PrintLink},
+    solve_details::SolveDetails,
+    timeline::TripTimeline,
+    trips::{self, SavedSearch, SavedTrip, ShareQuery},
+    tweak::TweakPanel,
+    Route,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct ResultsProps {
+    pub id: JobId,
+}
+
+/// Progress and then the outcome of a submitted solve
+pub struct ResultsPage {
+    progress: Option<SolveProgress>,
+    solution: Option<RouteSolution>,
+    /// Details of the solution's airports, empty until they've loaded
+    airports: Airports,
+    /// Days, distance and emissions of the solution, from the server once it's solved
+    summary: Option<TripSummary>,
+    error: Option<ApiError>,
+    /// Leg whose prices across nearby dates are being explored
+    exploring: Option<usize>,
+    /// Total of the route an edited leg is being re-priced against, kept on screen until the new route arrives
+    repriced_from: Option<f32>,
+    /// Total before the last edit, to show how the edit changed it
+    previous_total: Option<f32>,
+    /// Set when the page stops caring about the job, so its poll loop winds down
+    stopped: Rc<Cell<bool>>,
+}
+
+pub enum ResultsMessage {
+    Updated(JobStatus),
+    Finished(RouteSolution),
+    AirportsLoaded(Airports),
+    SummaryLoaded(TripSummary),
+    Failed(ApiError),
+    Retry,
+    DismissError,
+    Explore(Option<usize>),
+    /// Swap the leg at the index for the edited flight and re-solve
+    EditLeg(usize, Flight),
+}
+
+impl ResultsPage {
+    /// Poll the job until it finishes, reporting back through `link`
+    async fn poll(link: Scope<Self>, id: JobId, stopped: Rc<Cell<bool>>) {
+        while !stopped.get() {
+            let status = get_json(&format!("/api/v1/jobs/{}", id))
+                .await
+                .and_then(|r| parse_json::<JobStatus>(&r));
+
+            match status {
+                Ok(JobStatus::Completed { solution }) => return link.send_message(ResultsMessage::Finished(solution)),
+                Ok(JobStatus::Failed { error }) => return link.send_message(ResultsMessage::Failed(error)),
+                Ok(status) => link.send_message(ResultsMessage::Updated(status)),
+                Err(e) => return link.send_message(ResultsMessage::Failed(e)),
+            }
+
+            TimeoutFuture::new(SOLVE_POLL_INTERVAL_MS).await;
+        }
+    }
+
+    fn start_polling(&mut self, ctx: &Context<Self>) {
+        self.stopped.set(true);
+        self.stopped = Rc::new(Cell::new(false));
+        self.progress = Some(SolveProgress {
+            started_ms: js_sys::Date::now(),
+            status: JobStatus::Queued,
+        });
+        // Keep the old route up while an edit re-solves, so the page doesn't jump
+        if self.repriced_from.is_none() {
+            self.solution = None;
+        }
+        self.previous_total = None;
+        self.error = None;
+        self.exploring = None;
+        spawn_local(Self::poll(ctx.link().clone(), ctx.props().id, self.stopped.clone()));
+    }
+}
+
+impl Component for ResultsPage {
+    type Properties = ResultsProps;
+    type Message = ResultsMessage;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut page = Self {
+            progress: None,
+            solution: None,
+            airports: Airports::default(),
+            summary: None,
+            error: None,
+            exploring: None,
+            repriced_from: None,
+            previous_total: None,
+            stopped: Rc::new(Cell::new(false)),
+        };
+        page.start_polling(ctx);
+        page
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.start_polling(ctx);
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ResultsMessage::Updated(status) => {
+                if let Some(progress) = &mut self.progress {
+                    progress.status = status;
+                }
+            }
+            ResultsMessage::Finished(solution) => {
+                trips::record_solution(ctx.props().id, &solution);
+                self.progress = None;
+                self.previous_total = self.repriced_from.take();
+
+                // Names are a nicety, the codes are shown if they can't be loaded
+                let link = ctx.link().clone();
+                let loading = solution.clone();
+                spawn_local(async move {
+                    match load_airports(&loading).await {
+                        Ok(airports) => link.send_message(ResultsMessage::AirportsLoaded(airports)),
+                        Err(e) => web_sys::console::error_1(&format!("Couldn't load airports: {}", e).into()),
+                    }
+                });
+
+                self.summary = None;
+                let link = ctx.link().clone();
+                let id = ctx.props().id;
+                spawn_local(async move {
+                    let summary = get_json(&format!("/api/v1/jobs/{}/summary", id))
+                        .await
+                        .and_then(|r| parse_json::<TripSummary>(&r));
+                    match summary {
+                        Ok(summary) => link.send_message(ResultsMessage::SummaryLoaded(summary)),
+                        Err(e) => web_sys::console::error_1(&format!("Couldn't load trip summary: {}", e).into()),
+                    }
+                });
+                self.solution = Some(solution);
+            }
+            ResultsMessage::AirportsLoaded(airports) => self.airports = airports,
+            ResultsMessage::SummaryLoaded(summary) => self.summary = Some(summary),
+            ResultsMessage::Failed(e) => {
+                self.progress = None;
+                self.repriced_from = None;
+                self.error = Some(e);
+            }
+            ResultsMessage::Retry => self.start_polling(ctx),
+            ResultsMessage::DismissError => self.error = None,
+            ResultsMessage::Explore(leg) => self.exploring = leg,
+            ResultsMessage::EditLeg(idx, replacement) => {
+                let Some(solution) = &self.solution else {
+                    return false;
+                };
+                let Some(original) = solution.flights.get(idx).map(|f| f.flight.clone()) else {
+                    return false;
+                };
+                self.repriced_from = Some(solution.total_price);
+
+                let link = ctx.link().clone();
+                let job_id = ctx.props().id;
+                spawn_local(async move {
+                    match resolve_swapped(job_id, &original, &replacement).await {
+                        Ok(id) => {
+                            if let Some(navigator) = link.navigator() {
+                                navigator.push(&Route::Results { id });
+                            }
+                        }
+                        Err(e) => link.send_message(ResultsMessage::Failed(e)),
+                    }
+                });
+            }
+        }
+        true
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        self.stopped.set(true);
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let status = match (&self.progress, &self.solution) {
+            (Some(_), _) => t!("announce-solving"),
+            (None, Some(solution)) => t!("results-best", price = format_price(solution.total_price, &solution.currency)),
+            (None, None) => String::new(),
+        };
+
+        html! {
+            <>
+                <h1 class="text-body-emphasis">{ t!("results-title") }</h1>
+                <LiveRegion message={ status } />
+                if let Some(progress) = &self.progress {
+                    <SolveIndicator progress={ progress.clone() } />
+                }
+                if let Some(solution) = &self.solution {
+                    <div class={ classes!("my-2", self.progress.is_some().then_some("opacity-50")) }>
+                        if self.progress.is_none() {
+                            <div class="float-end"><PrintButton /></div>
+                        }
+                        <h5>
+                            { t!("results-best", price = format_price(solution.total_price, &solution.currency)) }
+                            if solution.passengers > 1 {
+                                { t!(
+                                    "results-party-total",
+                                    price = format_price(solution.party_total(), &solution.currency),
+                                    passengers = solution.passengers,
+                                ) }
+                            }
+                            if let Some(previous) = self.previous_total {
+                                <span class="text-body-secondary small">{ t!("results-was", price = format_price(previous, &solution.currency)) }</span>
+                            }
+                        </h5>
+                        if let Some(origin) = solution.origin {
+                            <p class="text-body-secondary">{ t!("results-origin", airport = origin.to_string()) }</p>
+                        }
+                        if let Some(summary) = &self.summary {
+                            <p class="text-body-secondary">{ describe_summary(summary) }</p>
+                        }
+                        <LegTable
+                            flights={ solution.flights.clone() }
+                            currency={ solution.currency.clone() }
+                            airports={ self.airports.clone() }
+                            on_explore={ link.callback(|idx| ResultsMessage::Explore(Some(idx))) }
+                            on_edit={ link.callback(|(idx, flight)| ResultsMessage::EditLeg(idx, flight)) } />
+                        if let Some(leg) = self.exploring.and_then(|idx| solution.flights.get(idx)) {
+                            <div class="d-print-none">
+                                <DateExplorer
+                                    job_id={ ctx.props().id }
+                                    leg={ leg.clone() }
+                                    currency={ solution.currency.clone() }
+                                    on_close={ link.callback(|_| ResultsMessage::Explore(None)) } />
+                            </div>
+                        }
+                        // Re-priced against the route on screen, so not while another is on its way
+                        if self.progress.is_none() {
+                            <div class="d-print-none">
+                                <TweakPanel job_id={ ctx.props().id } />
+                            </div>
+                        }
+                        <BudgetBreakdown solution={ solution.clone() } airports={ self.airports.clone() } />
+                        <TripTimeline solution={ solution.clone() } />
+                        // The map's tiles and the other routes are for exploring on screen, the printout is the route taken
+                        <div class="d-print-none">
+                            <RouteMap solution={ solution.clone() } airports={ self.airports.clone() } />
+                            if !solution.alternatives.is_empty() {
+                                <CandidateComparison solution={ solution.clone() } airports={ self.airports.clone() } />
+                            }
+                            <SolveDetails solution={ solution.clone() } />
+                        </div>
+                        <PrintLink id={ ctx.props().id } />
+                    </div>
+                }
+                <div class="d-print-none">
+                    <Link<Route> to={ Route::Builder }>{ t!("back-to-builder") }</Link<Route>>
+                </div>
+                if let Some(error) = &self.error {
+                    <ErrorToast
+                        error={ error.clone() }
+                        on_dismiss={ link.callback(|_| ResultsMessage::DismissError) }
+                        on_retry={ link.callback(|_| ResultsMessage::Retry) } />
+                }
+            </>
+        }
+    }
+}
+
+/// One line about the trip's length and footprint, e.g. "9 days, 7 nights in 3 stops, 8400 km, ~1000 kg CO₂ per passenger"
+fn describe_summary(summary: &TripSummary) -> String {
+    let TripStats { days, nights, stops, distance_km } = summary.stats;
+    t!(
+        "results-summary",
+        days = days,
+        nights = nights,
+        stops = stops,
+        distance = distance_km.round(),
+        co2 = summary.totals.co2_kg.round(),
+    )
+}
+
+/// Open `rows` in the builder, through the same query string a shared link uses
+fn open_in_builder(navigator: &Navigator, rows: &[ListItemVals]) {
+    if let Some(query) = ShareQuery::encode(rows) {
+        let _ = navigator.push_with_query(&Route::Builder, &query);
+    }
+}
+
+#[function_component(SavedTripsPage)]
+pub fn saved_trips_page() -> Html {
+    let saved = use_state(trips::load_saved_trips);
+    let navigator = use_navigator().expect("Saved trips page rendered outside of the router");
+
+    let delete = |idx: usize| {
+        let saved = saved.clone();
+        Callback::from(move |_| {
+            let mut remaining = (*saved).clone();
+            remaining.remove(idx);
+            trips::store_saved_trips(&remaining);
+            saved.set(remaining);
+        })
+    };
+    let open = |trip: &SavedTrip| {
+        let navigator = navigator.clone();
+        let rows = trip.rows.clone();
+        Callback::from(move |_| open_in_builder(&navigator, &rows))
+    };
+
+    html! {
+        <>
+            <h1 class="text-body-emphasis">{ t!("saved-title") }</h1>
+            if saved.is_empty() {
+                <p>{ t!("saved-empty") }<Link<Route> to={ Route::Builder }>{ t!("saved-empty-link") }</Link<Route>>{ t!("saved-empty-end") }</p>
+            }
+            <ul class="list-group">
+                { for saved.iter().enumerate().map(|(idx, trip)| html! {
+                    <li class="list-group-item d-flex align-items-center">
+                        <span class="me-auto">{ &trip.name }</span>
+                        <div class="pe-2">
+                            <Button text={ t!("open") } on_click={ open(trip) } />
+                        </div>
+                        <Button text={ t!("delete") } on_click={ delete(idx) } />
+                    </li>
+                }) }
+            </ul>
+        </>
+    }
+}
+
+/// Searches run from this browser, newest first, with what each found
+#[function_component(SearchesPage)]
+pub fn searches_page() -> Html {
+    let searches = use_state(trips::load_searches);
+    let navigator = use_navigator().expect("Searches page rendered outside of the router");
+    // The search that couldn't be run again, for the error toast to retry
+    let failed = use_state(|| None::<(ApiError, SavedSearch)>);
+
+    let run_again = |search: &SavedSearch| {
+        let (navigator, failed, search) = (navigator.clone(), failed.clone(), search.clone());
+        Callback::from(move |_| {
+            let (navigator, failed, search) = (navigator.clone(), failed.clone(), search.clone());
+            failed.set(None);
+            spawn_local(async move {
+                match submit_query(&search.query).await {
+                    Ok(id) => {
+                        trips::record_search(SavedSearch::new(id, search.query, search.rows));
+                        navigator.push(&Route::Results { id });
+                    }
+                    Err(e) => failed.set(Some((e, search))),
+                }
+            });
+        })
+    };
+    let go = |route: Route| {
+        let navigator = navigator.clone();
+        Callback::from(move |_| navigator.push(&route))
+    };
+    let edit = |search: &SavedSearch| {
+        let (navigator, rows) = (navigator.clone(), search.rows.clone());
+        Callback::from(move |_| open_in_builder(&navigator, &rows))
+    };
+    let delete = |idx: usize| {
+        let searches = searches.clone();
+        Callback::from(move |_| {
+            let mut remaining = (*searches).clone();
+            remaining.remove(idx);
+            trips::store_searches(&remaining);
+            searches.set(remaining);
+        })
+    };
+
+    html! {
+        <>
+            <h1 class="text-body-emphasis">{ t!("searches-title") }</h1>
+            if searches.is_empty() {
+                <p>{ t!("searches-empty") }<Link<Route> to={ Route::Builder }>{ t!("saved-empty-link") }</Link<Route>>{ t!("saved-empty-end") }</p>
+            }
+            <ul class="list-group">
+                { for searches.iter().enumerate().map(|(idx, search)| html! {
+                    <li class="list-group-item d-flex align-items-center">
+                        <div class="me-auto">
+                            <div>{ &search.name }</div>
+                            <div class="text-body-secondary small">
+                                { format_time(search.searched_ms) }
+                                { " · " }
+                                { search.solution.as_ref().map_or(t!("searches-no-route"), |s| format_price(s.total_price, &s.currency)) }
+                            </div>
+                        </div>
+                        <div class="pe-2">
+                            <Button text={ t!("open") } on_click={ go(Route::Results { id: search.job_id }) } />
+                        </div>
+                        <div class="pe-2">
+                            <Button text={ t!("edit") } on_click={ edit(search) } />
+                        </div>
+                        <div class="pe-2">
+                            <Button text={ t!("searches-run-again") } on_click={ run_again(search) } />
+                        </div>
+                        <div class="pe-2">
+                            <Button text={ t!("searches-compare") } disabled={ search.solution.is_none() } on_click={ go(Route::CompareSearch { id: search.job_id }) } />
+                        </div>
+                        <Button text={ t!("delete") } on_click={ delete(idx) } />
+                    </li>
+                }) }
+            </ul>
+            if let Some((error, search)) = &*failed {
+                <ErrorToast
+                    error={ error.clone() }
+                    on_dismiss={ { let failed = failed.clone(); Callback::from(move |_| failed.set(None)) } }
+                    on_retry={ run_again(search) } />
+            }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SearchComparisonProps {
+    pub id: JobId,
+}
+
+/// What changed in a past search's route when the same search is solved again now
+pub struct SearchComparisonPage {
+    /// The search from the history, `None` if it isn't there
+    search: Option<SavedSearch>,
+    progress: Option<SolveProgress>,
+    diff: Option<SolutionDiff>,
+    error: Option<ApiError>,
+    /// Set when the page stops caring about the re-price, so its poll loop winds down
+    stopped: Rc<Cell<bool>>,
+}
+
+pub enum SearchComparisonMessage {
+    Updated(JobStatus),
+    Finished(SolutionDiff),
+    Failed(ApiError),
+    Retry,
+    DismissError,
+}
+
+impl SearchComparisonPage {
+    /// Solve the query again, poll it until it finishes and have the server diff it against `was`, the route the search
+    /// found, reporting back through `link`. The search's own job may be gone, or another search's, since the server
+    /// restarted.
+    async fn reprice(link: Scope<Self>, was: RouteSolution, query: RouteQuery, stopped: Rc<Cell<bool>>) {
+        let id = match submit_query(&query).await {
+            Ok(id) => id,
+            Err(e) => return link.send_message(SearchComparisonMessage::Failed(e)),
+        };
+        while !stopped.get() {
+            let status = get_json(&format!("/api/v1/jobs/{}", id))
+                .await
+                .and_then(|r| parse_json::<JobStatus>(&r));
+
+            match status {
+                Ok(JobStatus::Completed { .. }) => {
+                    let body = serde_json::to_string(&was).map_err(|e| ApiError::Validation(e.to_string()));
+                    let diff = match body {
+                        Ok(body) => post_json(&format!("/api/v1/jobs/{}/diff", id), body)
+                            .await
+                            .and_then(|r| parse_json::<SolutionDiff>(&r)),
+                        Err(e) => Err(e),
+                    };
+                    return link.send_message(match diff {
+                        Ok(diff) => SearchComparisonMessage::Finished(diff),
+                        Err(e) => SearchComparisonMessage::Failed(e),
+                    });
+                }
+                Ok(JobStatus::Failed { error }) => return link.send_message(SearchComparisonMessage::Failed(error)),
+                Ok(status) => link.send_message(SearchComparisonMessage::Updated(status)),
+                Err(e) => return link.send_message(SearchComparisonMessage::Failed(e)),
+            }
+
+            TimeoutFuture::new(SOLVE_POLL_INTERVAL_MS).await;
+        }
+    }
+
+    fn start(&mut self, ctx: &Context<Self>) {
+        self.stopped.set(true);
+        self.stopped = Rc::new(Cell::new(false));
+        self.search = trips::load_searches().into_iter().find(|search| search.job_id == ctx.props().id);
+        self.diff = None;
+        self.error = None;
+        self.progress = None;
+        let Some(search) = &self.search else {
+            return;
+        };
+        let Some(was) = search.solution.clone() else {
+            return;
+        };
+        self.progress = Some(SolveProgress {
+            started_ms: js_sys::Date::now(),
+            status: JobStatus::Queued,
+        });
+        spawn_local(Self::reprice(ctx.link().clone(), was, search.query.clone(), self.stopped.clone()));
+    }
+}
+
+impl Component for SearchComparisonPage {
+    type Properties = SearchComparisonProps;
+    type Message = SearchComparisonMessage;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut page = Self {
+            search: None,
+            progress: None,
+            diff: None,
+            error: None,
+            stopped: Rc::new(Cell::new(false)),
+        };
+        page.start(ctx);
+        page
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.start(ctx);
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            SearchComparisonMessage::Updated(status) => {
+                if let Some(progress) = &mut self.progress {
+                    progress.status = status;
+                }
+            }
+            SearchComparisonMessage::Finished(diff) => {
+                self.progress = None;
+                self.diff = Some(diff);
+            }
+            SearchComparisonMessage::Failed(e) => {
+                self.progress = None;
+                self.error = Some(e);
+            }
+            SearchComparisonMessage::Retry => self.start(ctx),
+            SearchComparisonMessage::DismissError => self.error = None,
+        }
+        true
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        self.stopped.set(true);
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let status = match (&self.progress, &self.diff) {
+            (Some(_), _) => t!("announce-solving"),
+            (None, Some(_)) => t!("announce-diff-ready"),
+            (None, None) => String::new(),
+        };
+
+        html! {
+            <>
+                <h1 class="text-body-emphasis">{ t!("diff-title") }</h1>
+                <LiveRegion message={ status } />
+                if let Some(search) = &self.search {
+                    <p class="text-body-secondary">{ t!("diff-searched", name = search.name.clone(), time = format_time(search.searched_ms)) }</p>
+                } else {
+                    <p>{ t!("diff-missing") }</p>
+                }
+                if self.search.as_ref().is_some_and(|search| search.solution.is_none()) {
+                    <p>{ t!("diff-unsolved") }</p>
+                }
+                if let Some(progress) = &self.progress {
+                    <SolveIndicator progress={ progress.clone() } />
+                }
+                if let Some(diff) = &self.diff {
+                    <DiffTable diff={ diff.clone() } />
+                }
+                <Link<Route> to={ Route::Searches }>{ t!("diff-back") }</Link<Route>>
+                if let Some(error) = &self.error {
+                    <ErrorToast
+                        error={ error.clone() }
+                        on_dismiss={ link.callback(|_| SearchComparisonMessage::DismissError) }
+                        on_retry={ link.callback(|_| SearchComparisonMessage::Retry) } />
+                }
+            </>
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct DiffTableProps {
+    pub diff: SolutionDiff,
+}
+
+/// A change in price, with an arrow pointing up if it's gone up and down if it's come down
+fn price_change(change: f32, currency: &str) -> Html {
+    let (arrow, class) = match change {
+        c if c > 0.0 => ("▲ ", "text-danger"),
+        c if c < 0.0 => ("▼ ", "text-success"),
+        _ => ("", "text-body-secondary"),
+    };
+    html! { <span class={ class }>{ arrow }{ format_price(change.abs(), currency) }</span> }
+}
+
+/// Each leg of a [SolutionDiff] as it was and as it is now
+#[function_component(DiffTable)]
+pub fn diff_table(DiffTableProps { diff }: &DiffTableProps) -> Html {
+    let currency = &diff.currency;
+    let describe = |leg: &Option<FlightPrice>| {
+        leg.as_ref()
+            .map_or("—".to_string(), |leg| format!("{} · {}", format_date(leg.flight.date, DateStyle::Short), format_price(leg.price, currency)))
+    };
+    let moved = |days: i64| match days {
+        0 => html! {},
+        days => html! {
+            <span class="badge text-bg-secondary ms-2">
+                { if days > 0 { t!("diff-later", days = days) } else { t!("diff-earlier", days = -days) } }
+            </span>
+        },
+    };
+
+    html! {
+        <table class="table my-2">
+            <thead>
+                <tr><th>{ t!("diff-leg") }</th><th>{ t!("diff-then") }</th><th>{ t!("diff-now") }</th><th>{ t!("diff-change") }</th></tr>
+            </thead>
+            <tbody>
+                { for diff.legs.iter().map(|leg| html! {
+                    <tr>
+                        <td>{ format!("{} → {}", leg.src, leg.dest) }</td>
+                        <td>{ describe(&leg.was) }</td>
+                        <td>{ describe(&leg.now) }{ leg.days_moved.map_or(html! {}, moved) }</td>
+                        <td>
+                            { match leg.price_change {
+                                Some(change) => price_change(change, currency),
+                                None if leg.was.is_none() => html! { t!("diff-new-leg") },
+                                None => html! { t!("diff-dropped-leg") },
+                            } }
+                        </td>
+                    </tr>
+                }) }
+                <tr class="fw-semibold">
+                    <td>{ t!("total") }</td>
+                    <td>{ format_price(diff.was_total, currency) }</td>
+                    <td>{ format_price(diff.now_total, currency) }</td>
+                    <td>{ price_change(diff.total_change(), currency) }</td>
+                </tr>
+            </tbody>
+        </table>
+    }
+}
+
+fn describe_dates((start, end): &(String, String)) -> String {
+    // Inputs that aren't dates are shown as they were typed
+    let show = |input: &String| input.parse().map_or(input.clone(), |date| format_date(date, DateStyle::Long));
+    match (start.is_empty(), end.is_empty()) {
+        (true, true) => t!("shared-any-time"),
+        (false, true) => show(start),
+        (true, false) => show(end),
+        (false, false) => t!("dates-span", first = show(start), last = show(end)),
+    }
+}
+
+/// Read only view of an itinerary someone shared, which can be copied into the builder or saved
+#[function_component(SharedTripPage)]
+pub fn shared_trip_page() -> Html {
+    let navigator = use_navigator().expect("Shared trip page rendered outside of the router");
+    let rows = use_location()
+        .and_then(|location| location.query::<ShareQuery>().ok())
+        .and_then(|query| query.decode());
+
+    let Some(rows) = rows else {
+        return html! {
+            <>
+                <h1 class="text-body-emphasis">{ t!("shared-title") }</h1>
+                <p>{ t!("shared-broken") }</p>
+            </>
+        };
+    };
+
+    let edit = {
+        let navigator = navigator.clone();
+        let rows = rows.clone();
+        Callback::from(move |_| open_in_builder(&navigator, &rows))
+    };
+    let save = {
+        let rows = rows.clone();
+        Callback::from(move |_| {
+            let mut saved = trips::load_saved_trips();
+            saved.push(SavedTrip::from_rows(rows.clone()));
+            trips::store_saved_trips(&saved);
+            navigator.push(&Route::SavedTrips);
+        })
+    };
+
+    html! {
+        <>
+            <h1 class="text-body-emphasis">{ t!("shared-title") }</h1>
+            <table class="table">
+                <thead>
+                    <tr><th>{ t!("shared-airport") }</th><th>{ t!("shared-fly-in") }</th><th>{ t!("shared-fly-out") }</th><th>{ t!("shared-event") }</th></tr>
+                </thead>
+                <tbody>
+                    { for rows.iter().map(|vals| html! {
+                        <tr>
+                            <td>{ vals.airport.trim().to_uppercase() }</td>
+                            <td>{ describe_dates(&vals.start_dates) }</td>
+                            <td>{ describe_dates(&vals.end_dates) }</td>
+                            <td>{ describe_dates(&vals.event_dates) }</td>
+                        </tr>
+                    }) }
+                </tbody>
+            </table>
+            <div class="d-flex flex-row">
+                <div class="pe-2">
+                    <Button text={ t!("shared-edit-copy") } on_click={ edit } />
+                </div>
+                <Button text={ t!("builder-save") } on_click={ save } />
+            </div>
+        </>
+    }
+}
+
+#[function_component(NotFoundPage)]
+pub fn not_found_page() -> Html {
+    html! {
+        <>
+            <h1 class="text-body-emphasis">{ t!("not-found-title") }</h1>
+            <Link<Route> to={ Route::Builder }>{ t!("back-to-builder") }</Link<Route>>
+        </>
+    }
+}
@@ -0,0 +1,80 @@
+//! Printing results, or saving them as a PDF, through the browser's print dialog.
+//!
+//! What's printed is the results page itself, with the print stylesheet in `main.css` and Bootstrap's `d-print-*`
+//! classes leaving out the buttons and anything interactive. In their place goes a QR code of a link to the trip, so
+//! it can be picked up again from the paper copy.
+
+use qrcode::{render::svg, QrCode};
+use route_solver_shared::queries::JobId;
+use yew::prelude::*;
+use yew_router::Routable;
+
+use crate::{
+    trips::{self, ShareQuery},
+    Route,
+};
+
+/// Link to the trip the job solved. The itinerary's shared link if the search is in this browser's history, it outlives
+/// the job on the server, otherwise the results page.
+fn share_link(id: JobId) -> Option<String> {
+    let location = web_sys::window()?.location();
+    let shared = trips::load_searches()
+        .into_iter()
+        .find(|search| search.job_id == id)
+        .and_then(|search| ShareQuery::encode(&search.rows));
+
+    match shared {
+        Some(query) => Some(format!("{}{}?trip={}", location.origin().ok()?, Route::SharedTrip.to_path(), query.trip)),
+        None => location.href().ok(),
+    }
+}
+
+/// `text` as a QR code, `None` if it's too long to fit in one
+fn qr_code(text: &str) -> Option<Html> {
+    let code = QrCode::new(text.as_bytes()).ok()?;
+    let svg = code
+        .render::<svg::Color>()
+        .min_dimensions(160, 160)
+        .quiet_zone(false)
+        .build();
+    Some(Html::from_html_unchecked(AttrValue::from(svg)))
+}
+
+/// Opens the print dialog, not printed itself
+#[function_component(PrintButton)]
+pub fn print_button() -> Html {
+    let onclick = Callback::from(|_| {
+        if let Some(window) = web_sys::window() {
+            let _ = window.print();
+        }
+    });
+
+    html! {
+        <button type="button" class="btn btn-outline-secondary btn-sm d-print-none" {onclick}>{ t!("print") }</button>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct PrintLinkProps {
+    pub id: JobId,
+}
+
+/// The link to the trip as a QR code, only on the printed page
+#[function_component(PrintLink)]
+pub fn print_link(PrintLinkProps { id }: &PrintLinkProps) -> Html {
+    // Worked out once, the search history and page URL don't change while the page is up
+    let link = use_memo(|id| share_link(*id), *id);
+    let Some(link) = link.as_ref() else {
+        return html! {};
+    };
+
+    html! {
+        <div class="d-none d-print-flex align-items-center gap-3 mt-4 print-link">
+            { qr_code(link).unwrap_or_default() }
+            <div>
+                <p class="fw-semibold mb-1">{ t!("print-scan") }</p>
+                <p class="small text-break mb-0">{ link.clone() }</p>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,88 @@
+//! What the router did to find a route, folded away under the results for anyone wondering why a solve was slow or
+//! reporting a problem with one.
+
+use route_solver_shared::queries::{RouteSolution, ThrottleLevel};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct SolveDetailsProps {
+    pub solution: RouteSolution,
+}
+
+/// Milliseconds as seconds once there are enough of them, e.g. "850 ms" or "2.4 s"
+fn describe_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{} ms", ms)
+    } else {
+        format!("{:.1} s", ms as f64 / 1000.0)
+    }
+}
+
+#[function_component(SolveDetails)]
+pub fn solve_details(SolveDetailsProps { solution }: &SolveDetailsProps) -> Html {
+    // Older servers and solves answered from elsewhere don't say
+    let Some(stats) = solution.stats.as_deref().copied() else {
+        return html! {};
+    };
+    let pruned = stats.nodes_pruned;
+    let row = |label: String, value: String| {
+        html! {
+            <tr>
+                <th class="fw-normal text-body-secondary">{ label }</th>
+                <td>{ value }</td>
+            </tr>
+        }
+    };
+
+    html! {
+        <details class="my-3">
+            <summary>{ t!("details-title") }</summary>
+            <table class="table table-sm w-auto my-2">
+                <tbody>
+                    { row(t!("details-solve-time"), describe_ms(stats.pricing_ms + stats.search_ms)) }
+                    { row(t!("details-pricing"), describe_ms(stats.pricing_ms)) }
+                    { row(t!("details-searching"), describe_ms(stats.search_ms)) }
+                    { row(t!("details-lookups"), stats.api_calls.to_string()) }
+                    { row(
+                        t!("details-cache"),
+                        stats.cache_hit_rate.map_or(t!("details-no-cache"), |rate| {
+                            t!(
+                                "details-cache-rate",
+                                percent = (rate * 100.0).round(),
+                                hits = stats.cache_hits,
+                                lookups = stats.cache_hits + stats.cache_misses,
+                            )
+                        }),
+                    ) }
+                    { row(t!("details-expanded"), stats.nodes_expanded.to_string()) }
+                    { row(
+                        t!("details-pruned"),
+                        t!(
+                            "details-pruned-counts",
+                            total = pruned.total(),
+                            strategy = pruned.strategy,
+                            limits = pruned.limits,
+                            leg_price = pruned.leg_price,
+                            late = pruned.late_arrival,
+                            no_fare = pruned.no_fare,
+                        ),
+                    ) }
+                    { row(t!("details-queue"), stats.peak_queue_size.to_string()) }
+                    if stats.throttle != ThrottleLevel::Full {
+                        { row(t!("details-throttle"), match stats.throttle {
+                            ThrottleLevel::Full | ThrottleLevel::Sampled => t!("details-throttle-sampled"),
+                            ThrottleLevel::Narrowed => t!("details-throttle-narrowed"),
+                            ThrottleLevel::Exhausted => t!("details-throttle-exhausted"),
+                        }) }
+                    }
+                    if stats.degraded_legs > 0 {
+                        { row(t!("details-degraded"), stats.degraded_legs.to_string()) }
+                    }
+                    if let Some(request_id) = &solution.request_id {
+                        { row(t!("details-request-id"), request_id.clone()) }
+                    }
+                </tbody>
+            </table>
+        </details>
+    }
+}
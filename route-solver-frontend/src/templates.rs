@@ -0,0 +1,91 @@
+//! Picking one of the server's trip templates to start the itinerary from.
+
+use route_solver_shared::queries::{Date, TripTemplate};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::{
+    api::{get_json, parse_json},
+    components::today,
+    itinerary::ListItemVals,
+};
+
+/// Templates start this many days from today, far enough out that fares are usually still reasonable
+const TEMPLATE_LEAD_DAYS: u64 = 30;
+
+fn date_inputs(window: Option<(Date, Date)>) -> (String, String) {
+    window.map_or_else(Default::default, |(first, last)| (first.to_string(), last.to_string()))
+}
+
+/// The template's stops as builder rows, with its relative windows turned into dates from `start`
+pub fn template_rows(template: &TripTemplate, start: Date) -> Vec<ListItemVals> {
+    template
+        .stops
+        .iter()
+        .map(|stop| ListItemVals {
+            airport: stop.iata.clone(),
+            start_dates: date_inputs(stop.fly_in_dates(start)),
+            end_dates: date_inputs(stop.fly_out_dates(start)),
+            ..ListItemVals::default()
+        })
+        .collect()
+}
+
+/// The day a template picked now starts on
+pub fn template_start() -> Option<Date> {
+    today()?.iter_days().nth(TEMPLATE_LEAD_DAYS as usize)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TemplatePickerProps {
+    pub on_pick: Callback<TripTemplate>,
+}
+
+/// Dropdown of the server's templates, hidden if it has none or they couldn't be fetched
+#[function_component(TemplatePicker)]
+pub fn template_picker(TemplatePickerProps { on_pick }: &TemplatePickerProps) -> Html {
+    let templates = use_state(Vec::<TripTemplate>::new);
+    {
+        let templates = templates.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    if let Ok(list) = get_json("/api/v1/templates").await.and_then(|r| parse_json(&r)) {
+                        templates.set(list);
+                    }
+                });
+            },
+            (),
+        );
+    }
+
+    if templates.is_empty() {
+        return html! {};
+    }
+
+    let onchange = {
+        let templates = templates.clone();
+        let on_pick = on_pick.clone();
+        Callback::from(move |e: Event| {
+            let Some(select) = e.target().and_then(|t| t.dyn_into::<HtmlSelectElement>().ok()) else {
+                return;
+            };
+            if let Some(template) = templates.iter().find(|t| t.id == select.value()) {
+                on_pick.emit(template.clone());
+            }
+            // Back to the prompt, so the same template can be picked again
+            select.set_value("");
+        })
+    };
+
+    html! {
+        <select class="form-select w-auto mb-3" aria-label={ t!("template-label") } {onchange}>
+            <option value="" selected=true>{ t!("template-prompt") }</option>
+            { for templates.iter().map(|t| html! {
+                <option value={ t.id.clone() } title={ t.description.clone() }>{ &t.name }</option>
+            }) }
+        </select>
+    }
+}
@@ -0,0 +1,86 @@
+//! Timeline of a solved trip, one row per city with a bar from arrival to departure and flights joining the rows.
+
+use route_solver_shared::queries::{Date, DateMath, RouteSolution};
+use yew::prelude::*;
+
+use crate::i18n::{format_date, DateStyle};
+
+/// Height of each row in rem, flights are drawn as connectors this tall between rows
+const ROW_HEIGHT_REM: f64 = 2.5;
+
+#[derive(Properties, PartialEq)]
+pub struct TripTimelineProps {
+    pub solution: RouteSolution,
+}
+
+#[function_component(TripTimeline)]
+pub fn trip_timeline(TripTimelineProps { solution }: &TripTimelineProps) -> Html {
+    let stays = solution.stays();
+    let (Some(start), Some(end)) = (stays.first(), stays.last()) else {
+        return html! {};
+    };
+    let (start, end) = (start.arrive, end.depart);
+    let total_days = end.days_since(start).max(1) as f64;
+    // Horizontal position of a date, as a percentage of the track
+    let offset = |date: Date| date.days_since(start) as f64 / total_days * 100.0;
+    let last = stays.len() - 1;
+
+    let rows = stays.iter().enumerate().map(|(i, stay)| {
+        let (label, class) = match stay.nights {
+            _ if i == 0 || i == last => (String::new(), "timeline-bar timeline-endpoint"),
+            // Landing and leaving on the same day is easy to miss in the table, call it out
+            0 => (t!("timeline-nights", nights = 0), "timeline-bar bg-warning"),
+            n => (t!("timeline-nights", nights = n), "timeline-bar bg-primary"),
+        };
+        let style = format!(
+            "top: {}rem; left: {:.2}%; width: {:.2}%;",
+            i as f64 * ROW_HEIGHT_REM,
+            offset(stay.arrive),
+            offset(stay.depart) - offset(stay.arrive),
+        );
+
+        html! {
+            <>
+                <div class="timeline-label" style={ format!("top: {}rem;", i as f64 * ROW_HEIGHT_REM) }>{ stay.city.to_string() }</div>
+                <div class={ class } {style} title={ t!(
+                    "timeline-stay",
+                    city = stay.city.to_string(),
+                    arrive = format_date(stay.arrive, DateStyle::Short),
+                    depart = format_date(stay.depart, DateStyle::Short),
+                ) }>{ label }</div>
+            </>
+        }
+    });
+
+    // Each flight leaves the bottom half of one row and lands in the top half of the next
+    let flights = stays.windows(2).enumerate().map(|(i, pair)| {
+        let style = format!(
+            "top: {}rem; left: {:.2}%; height: {}rem;",
+            (i as f64 + 0.5) * ROW_HEIGHT_REM,
+            offset(pair[1].arrive),
+            ROW_HEIGHT_REM,
+        );
+        html! {
+            <div class="timeline-flight" {style} title={ t!(
+                "timeline-flight",
+                date = format_date(pair[1].arrive, DateStyle::Short),
+                from = pair[0].city.to_string(),
+                to = pair[1].city.to_string(),
+            ) }></div>
+        }
+    });
+
+    html! {
+        <div class="my-3">
+            <h5>{ t!("timeline-title") }</h5>
+            <div class="timeline d-flex justify-content-between text-body-secondary small">
+                <span>{ format_date(start, DateStyle::Long) }</span>
+                <span>{ format_date(end, DateStyle::Long) }</span>
+            </div>
+            <div class="timeline" style={ format!("height: {}rem;", stays.len() as f64 * ROW_HEIGHT_REM) }>
+                { for rows }
+                { for flights }
+            </div>
+        </div>
+    }
+}
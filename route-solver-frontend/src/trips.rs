@@ -0,0 +1,299 @@
+//! Keeping itineraries around: the autosave of each tab, shared links, trips saved by the user and the searches they've
+//! run. Also the user's currency and language.
+
+use std::io::{Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use route_solver_shared::queries::{JobId, RouteQuery, RouteSolution};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use web_sys::Storage;
+
+use crate::itinerary::{ListItemVals, Row};
+
+/// Local storage key the first tab's itinerary is autosaved under, other tabs add their ID to it
+const AUTOSAVE_KEY: &str = "route-solver.itinerary";
+/// Local storage key of the builder's tabs
+const WORKSPACE_KEY: &str = "route-solver.workspace";
+/// Local storage key of the saved trips list
+const SAVED_TRIPS_KEY: &str = "route-solver.saved-trips";
+/// Local storage key of the currency prices are shown in
+const CURRENCY_KEY: &str = "route-solver.currency";
+/// Local storage key of the language the page is shown in
+const LOCALE_KEY: &str = "route-solver.locale";
+/// Local storage key of the search history, newest first
+const SEARCHES_KEY: &str = "route-solver.searches";
+/// Searches kept in the history, the oldest are dropped past this
+const MAX_SEARCHES: usize = 25;
+
+/// Query string of a shared link, the itinerary is encoded into the `trip` parameter
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct ShareQuery {
+    pub trip: String,
+}
+
+impl ShareQuery {
+    pub fn encode<T: Serialize + ?Sized>(rows: &T) -> Option<ShareQuery> {
+        encode_share(rows).map(|trip| ShareQuery { trip })
+    }
+
+    pub fn decode(&self) -> Option<Vec<ListItemVals>> {
+        decode_share(&self.trip)
+    }
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct SavedTrip {
+    pub name: String,
+    pub rows: Vec<ListItemVals>,
+}
+
+impl SavedTrip {
+    pub fn from_rows(rows: Vec<ListItemVals>) -> SavedTrip {
+        SavedTrip {
+            name: trip_name(&rows),
+            rows,
+        }
+    }
+}
+
+/// Name trips after their airports, it's what people recognise them by
+pub fn trip_name(rows: &[ListItemVals]) -> String {
+    let name = rows
+        .iter()
+        .map(|vals| vals.airport.trim().to_uppercase())
+        .filter(|airport| !airport.is_empty())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    if name.is_empty() { t!("trip-untitled") } else { name }
+}
+
+/// A solve the user ran, kept so it can be reopened, run again or compared with a fresh re-price
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub job_id: JobId,
+    pub name: String,
+    /// When the search was run, in milliseconds since the epoch
+    pub searched_ms: f64,
+    pub query: RouteQuery,
+    /// The builder rows the query came from
+    pub rows: Vec<ListItemVals>,
+    /// The route the search found, `None` until it's solved or if it failed
+    #[serde(default)]
+    pub solution: Option<RouteSolution>,
+}
+
+impl SavedSearch {
+    pub fn new(job_id: JobId, query: RouteQuery, rows: Vec<ListItemVals>) -> SavedSearch {
+        SavedSearch {
+            job_id,
+            name: trip_name(&rows),
+            searched_ms: js_sys::Date::now(),
+            query,
+            rows,
+            solution: None,
+        }
+    }
+}
+
+/// A tab of the builder, its rows are autosaved separately under its ID
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct TripTab {
+    pub id: u32,
+    pub name: String,
+}
+
+/// The builder's tabs and which one is showing
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub tabs: Vec<TripTab>,
+    pub active: u32,
+}
+
+impl Workspace {
+    /// Add a tab after the others, returns its ID
+    pub fn add_tab(&mut self, name: String) -> u32 {
+        let id = self.tabs.iter().map(|tab| tab.id + 1).max().unwrap_or(0);
+        self.tabs.push(TripTab { id, name });
+        id
+    }
+
+    pub fn tab(&self, id: u32) -> Option<&TripTab> {
+        self.tabs.iter().find(|tab| tab.id == id)
+    }
+}
+
+/// A single tab, its itinerary is the one autosaved before there were tabs
+impl Default for Workspace {
+    fn default() -> Self {
+        Workspace {
+            tabs: vec![TripTab {
+                id: 0,
+                name: t!("workspace-first-tab"),
+            }],
+            active: 0,
+        }
+    }
+}
+
+fn storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn load<T: DeserializeOwned + Default>(key: &str) -> T {
+    storage()
+        .and_then(|storage| storage.get_item(key).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Storage can be full or disabled, losing a write isn't worth bothering the user over
+fn store<T: Serialize + ?Sized>(key: &str, value: &T) {
+    if let (Some(storage), Ok(json)) = (storage(), serde_json::to_string(value)) {
+        let _ = storage.set_item(key, &json);
+    }
+}
+
+fn autosave_key(tab: u32) -> String {
+    match tab {
+        0 => AUTOSAVE_KEY.to_string(),
+        tab => format!("{}.{}", AUTOSAVE_KEY, tab),
+    }
+}
+
+/// Rows of the tab autosaved by an earlier visit, empty if there's nothing usable saved
+pub fn load_autosave(tab: u32) -> Vec<Row> {
+    load(&autosave_key(tab))
+}
+
+pub fn autosave(tab: u32, rows: &[Row]) {
+    store(&autosave_key(tab), rows);
+}
+
+/// Forget a closed tab's rows
+pub fn delete_autosave(tab: u32) {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(&autosave_key(tab));
+    }
+}
+
+/// The tabs as they were left, a workspace with no tabs isn't usable so it's treated as missing
+pub fn load_workspace() -> Workspace {
+    let workspace: Option<Workspace> = load(WORKSPACE_KEY);
+    workspace
+        .filter(|workspace| workspace.tab(workspace.active).is_some())
+        .unwrap_or_default()
+}
+
+pub fn store_workspace(workspace: &Workspace) {
+    store(WORKSPACE_KEY, workspace);
+}
+
+pub fn load_saved_trips() -> Vec<SavedTrip> {
+    load(SAVED_TRIPS_KEY)
+}
+
+pub fn store_saved_trips(trips: &[SavedTrip]) {
+    store(SAVED_TRIPS_KEY, trips);
+}
+
+pub fn load_searches() -> Vec<SavedSearch> {
+    load(SEARCHES_KEY)
+}
+
+pub fn store_searches(searches: &[SavedSearch]) {
+    store(SEARCHES_KEY, searches);
+}
+
+/// Add a search to the front of the history
+pub fn record_search(search: SavedSearch) {
+    let mut searches = load_searches();
+    searches.insert(0, search);
+    searches.truncate(MAX_SEARCHES);
+    store_searches(&searches);
+}
+
+/// Keep the route a search found with it, ignored for jobs that aren't in the history, e.g. re-solves of an edited leg
+pub fn record_solution(job_id: JobId, solution: &RouteSolution) {
+    let mut searches = load_searches();
+    if let Some(search) = searches.iter_mut().find(|search| search.job_id == job_id) {
+        search.solution = Some(solution.clone());
+        store_searches(&searches);
+    }
+}
+
+pub fn load_currency() -> Option<String> {
+    load(CURRENCY_KEY)
+}
+
+pub fn store_currency(currency: &str) {
+    store(CURRENCY_KEY, currency);
+}
+
+pub fn load_locale() -> Option<String> {
+    load(LOCALE_KEY)
+}
+
+pub fn store_locale(locale: &str) {
+    store(LOCALE_KEY, locale);
+}
+
+/// Compress the rows into a URL safe string
+fn encode_share<T: Serialize + ?Sized>(rows: &T) -> Option<String> {
+    let json = serde_json::to_string(rows).ok()?;
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(json.as_bytes()).ok()?;
+    Some(URL_SAFE_NO_PAD.encode(encoder.finish().ok()?))
+}
+
+fn decode_share(encoded: &str) -> Option<Vec<ListItemVals>> {
+    let compressed = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    let mut json = String::new();
+    DeflateDecoder::new(compressed.as_slice()).read_to_string(&mut json).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+#[cfg(test)]
+mod trips_tests {
+    use super::{decode_share, encode_share};
+    use crate::itinerary::{ListItemVals, Row};
+
+    fn dates(from: &str, to: &str) -> (String, String) {
+        (from.to_string(), to.to_string())
+    }
+
+    #[test]
+    fn test_share_round_trip() {
+        let vals = vec![
+            ListItemVals {
+                airport: "YYZ".to_string(),
+                start_dates: dates("2023-02-01", "2023-02-01"),
+                ..ListItemVals::default()
+            },
+            ListItemVals {
+                airport: "YVR".to_string(),
+                start_dates: dates("2023-02-03", "2023-02-05"),
+                end_dates: dates("2023-02-08", "2023-02-10"),
+                event_dates: dates("2023-02-06", "2023-02-07"),
+            },
+        ];
+        // The builder shares its rows, which decode without their IDs
+        let rows: Vec<Row> = vals.iter().cloned().map(Row::new).collect();
+
+        let encoded = encode_share(&rows).unwrap();
+        assert!(encoded.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        assert!(decode_share(&encoded).unwrap() == vals);
+    }
+
+    #[test]
+    fn test_decode_share_rejects_garbage() {
+        let encoded = encode_share(&[ListItemVals::default()]).unwrap();
+
+        assert!(decode_share("").is_none());
+        assert!(decode_share("not base64!").is_none());
+        // Valid base64 of something that isn't deflate
+        assert!(decode_share("aGVsbG8gd29ybGQ").is_none());
+        // A link cut short
+        assert!(decode_share(&encoded[..encoded.len() / 2]).is_none());
+    }
+}
@@ -0,0 +1,205 @@
+//! "What if" panel of a solved trip: one change at a time to its itinerary, re-priced against its route from the warm
+//! price cache, with the option of keeping the changed route.
+
+use route_solver_shared::queries::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::{html::Scope, prelude::*};
+use yew_router::prelude::*;
+
+use crate::{
+    api::{get_json, parse_json, what_if},
+    components::{Button, ErrorToast},
+    currency::format_price,
+    pages::DiffTable,
+    Route,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct TweakPanelProps {
+    /// Job whose itinerary is changed
+    pub job_id: JobId,
+}
+
+pub struct TweakPanel {
+    /// The job's itinerary, for the stops there are to change
+    query: Option<RouteQuery>,
+    /// Last change tried and what it's called, kept to retry it
+    tried: Option<(WhatIfDelta, String)>,
+    pricing: bool,
+    /// What the last change tried came to
+    result: Option<WhatIf>,
+    error: Option<ApiError>,
+}
+
+pub enum TweakPanelMessage {
+    Loaded(Box<RouteQuery>),
+    Try(WhatIfDelta, String),
+    Priced(WhatIf),
+    /// Move on to the route the last change came to
+    Keep,
+    Failed(ApiError),
+    Retry,
+    DismissError,
+}
+
+impl TweakPanel {
+    async fn load(link: Scope<Self>, id: JobId) {
+        match get_json(&format!("/api/v1/jobs/{}/query", id)).await.and_then(|r| parse_json(&r)) {
+            Ok(query) => link.send_message(TweakPanelMessage::Loaded(query)),
+            Err(e) => link.send_message(TweakPanelMessage::Failed(e)),
+        }
+    }
+
+    fn start_load(&mut self, ctx: &Context<Self>) {
+        self.query = None;
+        self.tried = None;
+        self.result = None;
+        self.error = None;
+        spawn_local(Self::load(ctx.link().clone(), ctx.props().job_id));
+    }
+
+    fn start_pricing(&mut self, ctx: &Context<Self>) {
+        let Some((delta, _)) = self.tried.clone() else {
+            return;
+        };
+        self.pricing = true;
+        self.result = None;
+        self.error = None;
+        let link = ctx.link().clone();
+        let job_id = ctx.props().job_id;
+        spawn_local(async move {
+            match what_if(job_id, delta).await {
+                Ok(what_if) => link.send_message(TweakPanelMessage::Priced(what_if)),
+                Err(e) => link.send_message(TweakPanelMessage::Failed(e)),
+            }
+        });
+    }
+
+    /// Button trying `delta`, labelled `label`
+    fn change(&self, ctx: &Context<Self>, delta: WhatIfDelta, label: String) -> Html {
+        let text = label.clone();
+        let onclick = ctx.link().callback(move |_| TweakPanelMessage::Try(delta, label.clone()));
+        html! {
+            <button type="button" class="btn btn-outline-secondary btn-sm" disabled={ self.pricing } {onclick}>{ text }</button>
+        }
+    }
+
+    /// The changes on offer for stop `idx` of `query`: its dates a day either way, and leaving it out if it's between
+    /// the start and end
+    fn stop_changes(&self, ctx: &Context<Self>, query: &RouteQuery, idx: usize, stop: &DestinationQuery) -> Html {
+        let iata = stop.iata.to_string();
+        let shift = |days: i64| WhatIfDelta::Window {
+            stop: idx,
+            arrival_window: stop.arrival_window.map(|w| Some(w.shift(days))),
+            departure_window: stop.departure_window.map(|w| Some(w.shift(days))),
+        };
+        let has_dates = stop.arrival_window.is_some() || stop.departure_window.is_some();
+        let is_hop = idx != 0 && idx <= query.hops.len();
+
+        html! {
+            <>
+                if has_dates {
+                    { self.change(ctx, shift(-1), t!("tweak-earlier", stop = iata.clone())) }
+                    { self.change(ctx, shift(1), t!("tweak-later", stop = iata.clone())) }
+                }
+                if is_hop {
+                    { self.change(ctx, WhatIfDelta::DropStop { stop: idx }, t!("tweak-skip", stop = iata.clone())) }
+                }
+            </>
+        }
+    }
+}
+
+impl Component for TweakPanel {
+    type Properties = TweakPanelProps;
+    type Message = TweakPanelMessage;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut panel = Self {
+            query: None,
+            tried: None,
+            pricing: false,
+            result: None,
+            error: None,
+        };
+        panel.start_load(ctx);
+        panel
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+        self.start_load(ctx);
+        true
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            TweakPanelMessage::Loaded(query) => self.query = Some(*query),
+            TweakPanelMessage::Try(delta, label) => {
+                self.tried = Some((delta, label));
+                self.start_pricing(ctx);
+            }
+            TweakPanelMessage::Priced(what_if) => {
+                self.pricing = false;
+                self.result = Some(what_if);
+            }
+            TweakPanelMessage::Keep => {
+                if let (Some(what_if), Some(navigator)) = (&self.result, ctx.link().navigator()) {
+                    navigator.push(&Route::Results { id: what_if.job });
+                }
+            }
+            TweakPanelMessage::Failed(e) => {
+                self.pricing = false;
+                self.error = Some(e);
+            }
+            TweakPanelMessage::Retry => match self.query {
+                Some(_) => self.start_pricing(ctx),
+                None => self.start_load(ctx),
+            },
+            TweakPanelMessage::DismissError => self.error = None,
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+
+        html! {
+            <div class="my-3 p-3 bg-body-secondary rounded-3">
+                <h5>{ t!("tweak-title") }</h5>
+                <p class="small text-body-secondary">{ t!("tweak-hint") }</p>
+                if let Some(query) = &self.query {
+                    <div class="d-flex flex-wrap gap-1">
+                        { for std::iter::once(&query.start_city)
+                            .chain(&query.hops)
+                            .chain(std::iter::once(&query.end_city))
+                            .enumerate()
+                            .map(|(idx, stop)| self.stop_changes(ctx, query, idx, stop)) }
+                        if !query.limits.direct_only {
+                            { self.change(ctx, WhatIfDelta::DirectOnly, t!("tweak-direct")) }
+                        }
+                    </div>
+                } else if self.error.is_none() {
+                    <div class="spinner-border spinner-border-sm my-2" role="status" aria-label={ t!("tweak-loading") }></div>
+                }
+                if self.pricing {
+                    <div class="spinner-border spinner-border-sm my-2" role="status" aria-label={ t!("tweak-pricing") }></div>
+                }
+                if let (Some(what_if), Some((_, label))) = (&self.result, &self.tried) {
+                    <p class="mt-3 mb-1 fw-semibold">{ t!(
+                        "tweak-result",
+                        change = label.clone(),
+                        price = format_price(what_if.diff.now_total, &what_if.diff.currency),
+                    ) }</p>
+                    <DiffTable diff={ what_if.diff.clone() } />
+                    <Button text={ t!("tweak-keep") } on_click={ link.callback(|_| TweakPanelMessage::Keep) } />
+                }
+                if let Some(error) = &self.error {
+                    <ErrorToast
+                        error={ error.clone() }
+                        on_dismiss={ link.callback(|_| TweakPanelMessage::DismissError) }
+                        on_retry={ link.callback(|_| TweakPanelMessage::Retry) } />
+                }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Tabs of the builder, for working on several itineraries side by side, e.g. "Summer Europe" and "Fall Asia".
+//!
+//! Every tab's [ItineraryList] stays mounted with the ones not showing hidden, so switching back to a tab finds it as
+//! it was left, passengers, limits and all. Rows are autosaved per tab, see [trips::Workspace].
+
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{
+    components::{Modal, TextBox},
+    itinerary::{ItineraryList, ListItemVals, Row},
+    trips::{self, ShareQuery, Workspace},
+    Route,
+};
+
+fn tab_vals(tab: u32) -> Vec<ListItemVals> {
+    trips::load_autosave(tab).into_iter().map(|row| row.vals).collect()
+}
+
+fn autosave_vals(tab: u32, vals: Vec<ListItemVals>) {
+    trips::autosave(tab, &vals.into_iter().map(Row::new).collect::<Vec<_>>());
+}
+
+/// The workspace as it was left, with the itinerary of a shared link the page was opened with put in a tab. The tab
+/// showing if it's empty, otherwise a new one so opening a link doesn't lose what was being worked on. Reloading the
+/// builder opens the tab's own link, which leaves it as it is.
+fn open_workspace(shared: Option<Vec<ListItemVals>>) -> Workspace {
+    let mut workspace = trips::load_workspace();
+    if let Some(shared) = shared {
+        let current = tab_vals(workspace.active);
+        if current.is_empty() {
+            autosave_vals(workspace.active, shared);
+        } else if current != shared {
+            let id = workspace.add_tab(trips::trip_name(&shared));
+            autosave_vals(id, shared);
+            workspace.active = id;
+        }
+        trips::store_workspace(&workspace);
+    }
+    workspace
+}
+
+/// Point the page URL at the tab's itinerary, the lists only update it as they're edited
+fn link_tab(navigator: &Navigator, tab: u32) {
+    if let Some(query) = ShareQuery::encode(&tab_vals(tab)) {
+        let _ = navigator.replace_with_query(&Route::Builder, &query);
+    }
+}
+
+#[function_component(TripWorkspace)]
+pub fn trip_workspace() -> Html {
+    let navigator = use_navigator().expect("Workspace rendered outside of the router");
+    let shared = use_location()
+        .and_then(|location| location.query::<ShareQuery>().ok())
+        .and_then(|query| query.decode());
+    let workspace = use_state(|| open_workspace(shared));
+    // Name being typed in for the tab showing
+    let renaming = use_state(|| None::<String>);
+    // Tab with an itinerary in it waiting on the user to confirm closing it
+    let closing = use_state(|| None::<u32>);
+
+    let change = {
+        let (workspace, navigator) = (workspace.clone(), navigator.clone());
+        move |f: &dyn Fn(&mut Workspace)| {
+            let mut changed = (*workspace).clone();
+            f(&mut changed);
+            if changed.active != workspace.active {
+                link_tab(&navigator, changed.active);
+            }
+            trips::store_workspace(&changed);
+            workspace.set(changed);
+        }
+    };
+
+    let select = |id: u32| {
+        let (change, renaming) = (change.clone(), renaming.clone());
+        Callback::from(move |_| {
+            renaming.set(None);
+            change(&|workspace| workspace.active = id);
+        })
+    };
+    let add = {
+        let change = change.clone();
+        Callback::from(move |_| {
+            change(&|workspace| {
+                let name = t!("workspace-new-name", number = workspace.tabs.len() + 1);
+                workspace.active = workspace.add_tab(name);
+            })
+        })
+    };
+    let duplicate = {
+        let change = change.clone();
+        Callback::from(move |_| {
+            change(&|workspace| {
+                let Some(name) = workspace.tab(workspace.active).map(|tab| tab.name.clone()) else {
+                    return;
+                };
+                let id = workspace.add_tab(t!("workspace-copy-name", name = name));
+                autosave_vals(id, tab_vals(workspace.active));
+                workspace.active = id;
+            })
+        })
+    };
+    let close = {
+        let (change, closing) = (change.clone(), closing.clone());
+        Callback::from(move |id: u32| {
+            closing.set(None);
+            change(&|workspace| {
+                let Some(position) = workspace.tabs.iter().position(|tab| tab.id == id) else {
+                    return;
+                };
+                workspace.tabs.remove(position);
+                // Over to the tab which took its place, or the one before it if it was the last
+                if workspace.active == id {
+                    workspace.active = workspace.tabs[position.min(workspace.tabs.len() - 1)].id;
+                }
+            });
+            trips::delete_autosave(id);
+        })
+    };
+    let ask_close = |id: u32| {
+        let (close, closing) = (close.clone(), closing.clone());
+        Callback::from(move |_| {
+            if tab_vals(id).is_empty() {
+                close.emit(id);
+            } else {
+                closing.set(Some(id));
+            }
+        })
+    };
+    let rename = {
+        let (change, renaming) = (change.clone(), renaming.clone());
+        move |name: String| {
+            renaming.set(None);
+            let name = name.trim().to_string();
+            if !name.is_empty() {
+                change(&|workspace| {
+                    let active = workspace.active;
+                    if let Some(tab) = workspace.tabs.iter_mut().find(|tab| tab.id == active) {
+                        tab.name = name.clone();
+                    }
+                });
+            }
+        }
+    };
+
+    let active = workspace.tab(workspace.active).cloned();
+    let rename_box = renaming.as_ref().map(|draft| {
+        let on_draft = {
+            let renaming = renaming.clone();
+            Callback::from(move |text: String| renaming.set(Some(text)))
+        };
+        let onkeydown = {
+            let (rename, renaming, draft) = (rename.clone(), renaming.clone(), draft.clone());
+            Callback::from(move |e: KeyboardEvent| match e.key().as_str() {
+                "Enter" => rename(draft.clone()),
+                "Escape" => renaming.set(None),
+                _ => {}
+            })
+        };
+        let done = {
+            let (rename, draft) = (rename.clone(), draft.clone());
+            Callback::from(move |_| rename(draft.clone()))
+        };
+        html! {
+            <div class="d-flex align-items-center gap-2" {onkeydown}>
+                <TextBox text={ t!("workspace-name") } type_name="text" initial_value={ draft.clone() } text_update_handler={ on_draft } />
+                <button type="button" class="btn btn-primary btn-sm" onclick={ done }>{ t!("builder-done") }</button>
+            </div>
+        }
+    });
+    let start_rename = {
+        let (renaming, name) = (renaming.clone(), active.as_ref().map(|tab| tab.name.clone()).unwrap_or_default());
+        Callback::from(move |_| renaming.set(Some(name.clone())))
+    };
+    let closing_tab = closing.and_then(|id| workspace.tab(id)).cloned();
+    let cancel_close = {
+        let closing = closing.clone();
+        Callback::from(move |_| closing.set(None))
+    };
+    let confirm_close = |id: u32| {
+        let close = close.clone();
+        Callback::from(move |_| close.emit(id))
+    };
+
+    html! {
+        <>
+            <div class="d-flex flex-wrap align-items-end gap-2 mb-3 border-bottom">
+                <ul class="nav nav-tabs border-bottom-0 me-auto" role="tablist" aria-label={ t!("workspace-tabs") }>
+                    { for workspace.tabs.iter().map(|tab| {
+                        let selected = tab.id == workspace.active;
+                        html! {
+                            <li class="nav-item d-flex align-items-center" role="presentation">
+                                <button type="button" role="tab" id={ format!("trip-tab-{}", tab.id) }
+                                    class={ classes!("nav-link", selected.then_some("active")) }
+                                    aria-selected={ selected.to_string() } aria-controls={ format!("trip-panel-{}", tab.id) }
+                                    onclick={ select(tab.id) }>
+                                    { tab.name.clone() }
+                                </button>
+                                if workspace.tabs.len() > 1 {
+                                    <button type="button" class="btn-close btn-sm ms-1" style="font-size: 0.6rem;"
+                                        aria-label={ t!("workspace-close", name = tab.name.clone()) } onclick={ ask_close(tab.id) }></button>
+                                }
+                            </li>
+                        }
+                    }) }
+                </ul>
+                <div class="d-flex flex-wrap align-items-center gap-2 pb-1">
+                    { rename_box.unwrap_or_else(|| html! {
+                        <button type="button" class="btn btn-outline-secondary btn-sm" onclick={ start_rename }>{ t!("workspace-rename") }</button>
+                    }) }
+                    <button type="button" class="btn btn-outline-secondary btn-sm" onclick={ duplicate }>{ t!("workspace-duplicate") }</button>
+                    <button type="button" class="btn btn-outline-primary btn-sm" onclick={ add }>{ t!("workspace-new") }</button>
+                </div>
+            </div>
+            { for workspace.tabs.iter().map(|tab| html! {
+                <div key={ tab.id } role="tabpanel" id={ format!("trip-panel-{}", tab.id) } aria-labelledby={ format!("trip-tab-{}", tab.id) }
+                    class={ classes!((tab.id != workspace.active).then_some("d-none")) }>
+                    <ItineraryList tab={ tab.id } />
+                </div>
+            }) }
+            if let Some(tab) = closing_tab {
+                <Modal
+                    id="confirm-close-tab"
+                    main_text={ t!("workspace-close-title") }
+                    open={ true }
+                    on_close={ cancel_close }
+                    close_text={ t!("cancel") }
+                    internal_html={ html! { <p class="mb-0">{ t!("workspace-close-lost", name = tab.name.clone()) }</p> } }
+                    footer={ html! {
+                        <button type="button" class="btn btn-danger" onclick={ confirm_close(tab.id) }>
+                            { t!("workspace-close-confirm") }
+                        </button>
+                    } } />
+            }
+        </>
+    }
+}
@@ -0,0 +1,162 @@
+//! Free text date expressions, read into the days they name: `2024-06-01`, `2024-06-01..2024-06-10`, `June`,
+//! `early June 2024`, `late jul` or `any weekend in July`.
+//!
+//! A month given without a year is its next occurrence, counting the month `today` is in. Early, mid and late are the
+//! 1st to 10th, 11th to 20th and 21st to the end of the month.
+
+use std::fmt;
+
+use chrono::{Datelike, Months, Weekday};
+
+use crate::queries::{Date, DateMath, DateWindow, InvalidWindow};
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Days a date expression names, as the windows of consecutive days in it, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateSet {
+    windows: Vec<DateWindow>,
+}
+
+impl DateSet {
+    pub fn windows(&self) -> &[DateWindow] {
+        &self.windows
+    }
+
+    /// The first day named to the last, with any days between the windows
+    pub fn span(&self) -> DateWindow {
+        let (first, last) = (self.windows[0], self.windows[self.windows.len() - 1]);
+        DateWindow::new(first.first(), last.last()).unwrap()
+    }
+
+    pub fn contains(&self, date: Date) -> bool {
+        self.windows.iter().any(|window| window.contains(date))
+    }
+
+    /// Every day named, in order
+    pub fn days(&self) -> impl Iterator<Item = Date> + '_ {
+        self.windows
+            .iter()
+            .flat_map(|window| (0..window.num_days()).map(|days| window.first().add_days_saturating(days)))
+    }
+}
+
+impl From<DateWindow> for DateSet {
+    fn from(window: DateWindow) -> Self {
+        DateSet { windows: vec![window] }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateParseError {
+    Empty,
+    /// Text that isn't any of the expressions understood
+    Unrecognized(String),
+    /// A range ending before it starts
+    Reversed(InvalidWindow),
+}
+
+impl fmt::Display for DateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateParseError::Empty => write!(f, "No dates were given"),
+            DateParseError::Unrecognized(text) => write!(
+                f,
+                "Couldn't read \"{}\" as dates, try 2024-06-01..2024-06-10, early June or any weekend in July",
+                text
+            ),
+            DateParseError::Reversed(window) => write!(f, "{}", window),
+        }
+    }
+}
+
+/// Read `text` as the days it names, relative to `today` where it leaves out the year
+pub fn parse_dates(text: &str, today: Date) -> Result<DateSet, DateParseError> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return Err(DateParseError::Empty);
+    }
+    let unrecognized = || DateParseError::Unrecognized(text.clone());
+
+    if let Some((first, last)) = text.split_once("..") {
+        let (first, last) = (iso_date(first).ok_or_else(unrecognized)?, iso_date(last).ok_or_else(unrecognized)?);
+        return DateWindow::new(first, last)
+            .map(DateSet::from)
+            .ok_or(DateParseError::Reversed(InvalidWindow { first, last }));
+    }
+    if let Some(date) = iso_date(&text) {
+        return Ok(DateWindow::day(date).into());
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let words = match words.as_slice() {
+        ["any" | "every", rest @ ..] => rest,
+        words => words,
+    };
+    match words {
+        ["weekend" | "weekends", "in" | "of", month @ ..] => {
+            let month = month_window(month, today).ok_or_else(unrecognized)?;
+            Ok(DateSet { windows: weekends(month) })
+        }
+        [part @ ("early" | "mid" | "late"), month @ ..] => {
+            let month = month_window(month, today).ok_or_else(unrecognized)?;
+            // Days into the month the part starts and ends
+            let (from, to) = match *part {
+                "early" => (0, 9),
+                "mid" => (10, 19),
+                _ => (20, 30),
+            };
+            let last = month.last().min(month.first().add_days_saturating(to));
+            Ok(DateWindow::new(month.first().add_days_saturating(from), last).unwrap().into())
+        }
+        month => Ok(month_window(month, today).ok_or_else(unrecognized)?.into()),
+    }
+}
+
+fn iso_date(text: &str) -> Option<Date> {
+    text.trim().parse().ok()
+}
+
+/// The days of the month `words` names, a month's name or its first three letters and optionally a year
+fn month_window(words: &[&str], today: Date) -> Option<DateWindow> {
+    let (name, year) = match words {
+        [name] => (*name, None),
+        [name, year] => (*name, Some(year.parse().ok()?)),
+        _ => return None,
+    };
+    let month = MONTHS
+        .iter()
+        .position(|month| name.len() >= 3 && month.starts_with(name))? as u32
+        + 1;
+    let year = year.unwrap_or(if month >= today.month() { today.year() } else { today.year() + 1 });
+
+    let first = Date::from_ymd_opt(year, month, 1)?;
+    let last = first.checked_add_months(Months::new(1))?.add_days_saturating(-1);
+    DateWindow::new(first, last)
+}
+
+/// Each Saturday and Sunday in `month`, a window apiece, cut short where the month starts on a Sunday or ends on a
+/// Saturday
+fn weekends(month: DateWindow) -> Vec<DateWindow> {
+    DateSet::from(month)
+        .days()
+        .filter_map(|day| match day.weekday() {
+            Weekday::Sat => DateWindow::new(day, day.add_days_saturating(1).min(month.last())),
+            Weekday::Sun if day == month.first() => Some(DateWindow::day(day)),
+            _ => None,
+        })
+        .collect()
+}
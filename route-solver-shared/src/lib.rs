@@ -1,192 +1,2000 @@
 pub mod queries {
-    use chrono::{Days, NaiveDate, Duration};
+    use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, Duration};
     use std::{
         cmp::{max, min},
-        rc::Rc,
+        collections::BTreeMap,
+        fmt,
+        str::FromStr,
     };
-    use serde::{Deserialize, Serialize};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
     pub type Date = NaiveDate;
 
+    /// Day arithmetic on [Date]s, clamped to the ends of the calendar rather than panicking past them
+    pub trait DateMath {
+        /// The date `days` later, earlier if negative
+        fn add_days_saturating(self, days: i64) -> Date;
+
+        /// Whole days from `earlier` to this date, negative if `earlier` is after it
+        fn days_since(self, earlier: Date) -> i64;
+    }
+
+    impl DateMath for Date {
+        fn add_days_saturating(self, days: i64) -> Date {
+            let shifted = match u64::try_from(days) {
+                Ok(later) => self.checked_add_days(Days::new(later)),
+                Err(_) => self.checked_sub_days(Days::new(days.unsigned_abs())),
+            };
+            shifted.unwrap_or(if days < 0 { NaiveDate::MIN } else { NaiveDate::MAX })
+        }
+
+        fn days_since(self, earlier: Date) -> i64 {
+            (self - earlier).num_days()
+        }
+    }
+
+    /// Nights spent somewhere from landing on `arrival` to leaving on `departure`, none if it leaves the day it lands
+    /// or before
+    pub fn nights_between(arrival: Date, departure: Date) -> u32 {
+        departure.days_since(arrival).clamp(0, u32::MAX.into()) as u32
+    }
+
+    /// Currency the pricing providers quote in, solutions in other currencies are converted from it
+    pub const BASE_CURRENCY: &str = "USD";
+
+    fn base_currency() -> String {
+        BASE_CURRENCY.to_string()
+    }
+
+    /// An IATA airport code, e.g. "YYZ". Kept inline rather than as a `String` since the router copies, compares and
+    /// hashes codes for every flight it expands. Goes over the wire as a plain string.
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct AirportCode([u8; 3]);
+
+    impl AirportCode {
+        pub fn as_str(&self) -> &str {
+            // Only built by from_str, which only lets ASCII through
+            std::str::from_utf8(&self.0).expect("airport codes are ASCII")
+        }
+    }
+
+    /// The string given isn't three letters or digits
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct InvalidAirportCode(pub String);
+
+    impl fmt::Display for InvalidAirportCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "\"{}\" isn't an airport code", self.0)
+        }
+    }
+
+    impl std::error::Error for InvalidAirportCode {}
+
+    impl FromStr for AirportCode {
+        type Err = InvalidAirportCode;
+
+        /// Parses three ASCII letters or digits, in either case
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.as_bytes() {
+                &[a, b, c] if s.bytes().all(|b| b.is_ascii_alphanumeric()) => {
+                    Ok(AirportCode([a, b, c].map(|b| b.to_ascii_uppercase())))
+                }
+                _ => Err(InvalidAirportCode(s.to_string())),
+            }
+        }
+    }
+
+    impl fmt::Display for AirportCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.pad(self.as_str())
+        }
+    }
+
+    impl fmt::Debug for AirportCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(self.as_str(), f)
+        }
+    }
+
+    impl PartialEq<str> for AirportCode {
+        fn eq(&self, other: &str) -> bool {
+            self.as_str() == other
+        }
+    }
+
+    impl PartialEq<&str> for AirportCode {
+        fn eq(&self, other: &&str) -> bool {
+            self.as_str() == *other
+        }
+    }
+
+    impl Serialize for AirportCode {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AirportCode {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+        }
+    }
+
     #[derive(Serialize, Deserialize)]
     pub struct EchoQuery {
         pub input: String
     }
 
-    #[derive(Serialize, Deserialize)]
-    pub struct RouteQuery {
-        pub start_city: String,
-        pub end_city: String,
-        pub hops: Vec<String>,
+    /// A destination as sent over the wire, the date restrictions are given in whole days and the windows as
+    /// [window_serde] describes
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    #[serde(try_from = "DestinationQueryWire")]
+    pub struct DestinationQuery {
+        pub iata: AirportCode,
+        /// Days the trip may fly into the destination, unbounded if `None`
+        #[serde(with = "window_serde")]
+        pub arrival_window: Option<DateWindow>,
+        /// Days the trip may fly out of the destination, unbounded if `None`
+        #[serde(with = "window_serde")]
+        pub departure_window: Option<DateWindow>,
+        pub min_days: Option<i64>,
+        pub max_days: Option<i64>,
+        /// Days the trip must be at the destination for, whatever the windows allow
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub event: Option<EventDates>,
+        /// Most the leg into the destination may cost, see [RouteLimits::max_leg_price]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_leg_price: Option<f32>,
+    }
+
+    /// Days the trip must spend at a destination, e.g. for a conference. The trip arrives on or before the first day and
+    /// leaves on or after the last.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct EventDates {
+        pub first_day: Date,
+        pub last_day: Date,
+    }
+
+    /// What a [DestinationQuery] is read from, which also takes the `dates: [arrival, departure]` pair queries were
+    /// sent with before the windows had names, so saved jobs and old clients keep working
+    #[derive(Deserialize)]
+    struct DestinationQueryWire {
+        iata: AirportCode,
+        #[serde(default, deserialize_with = "window_serde::arrival")]
+        arrival_window: Option<Option<DateWindow>>,
+        #[serde(default, deserialize_with = "window_serde::departure")]
+        departure_window: Option<Option<DateWindow>>,
+        #[serde(default)]
+        dates: Option<(SingleDateRange, SingleDateRange)>,
+        #[serde(default)]
+        min_days: Option<i64>,
+        #[serde(default)]
+        max_days: Option<i64>,
+        #[serde(default)]
+        event: Option<EventDates>,
+        #[serde(default)]
+        max_leg_price: Option<f32>,
+    }
+
+    impl TryFrom<DestinationQueryWire> for DestinationQuery {
+        type Error = InvalidWindow;
+
+        fn try_from(wire: DestinationQueryWire) -> Result<Self, InvalidWindow> {
+            let (legacy_arrival, legacy_departure) = wire.dates.unzip();
+            let window = |window: Option<Option<DateWindow>>, legacy: Option<SingleDateRange>| match window {
+                Some(window) => Ok(window),
+                None => legacy.map_or(Ok(None), Option::<DateWindow>::try_from),
+            };
+            Ok(DestinationQuery {
+                iata: wire.iata,
+                arrival_window: window(wire.arrival_window, legacy_arrival)?,
+                departure_window: window(wire.departure_window, legacy_departure)?,
+                min_days: wire.min_days,
+                max_days: wire.max_days,
+                event: wire.event,
+                max_leg_price: wire.max_leg_price,
+            })
+        }
+    }
+
+    /// Why a destination's dates can't be met
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum InvalidStay {
+        /// The minimum stay is longer than the maximum
+        MinAboveMax,
+        /// The departure window closes before the arrival window opens
+        DepartsBeforeArrival,
+        /// Even arriving as early and leaving as late as the windows allow is shorter than the minimum stay
+        TooShort,
+        /// Even arriving as late and leaving as early as the windows allow is longer than the maximum stay
+        TooLong,
+        /// The event's last day is before its first
+        EventEndsBeforeStart,
+        /// The windows don't allow arriving by the event's first day or leaving after its last
+        MissesEvent,
+    }
+
+    impl fmt::Display for InvalidStay {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                InvalidStay::MinAboveMax => "the minimum stay is longer than the maximum",
+                InvalidStay::DepartsBeforeArrival => "the departure dates are all before the arrival dates",
+                InvalidStay::TooShort => "the dates don't allow for the minimum stay",
+                InvalidStay::TooLong => "the dates force a stay longer than the maximum",
+                InvalidStay::EventEndsBeforeStart => "the event ends before it starts",
+                InvalidStay::MissesEvent => "the dates don't allow being there for the event",
+            })
+        }
+    }
+
+    impl std::error::Error for InvalidStay {}
+
+    impl DestinationQuery {
+        /// Checks some arrival and departure in the windows are at most the maximum and at least the minimum stay apart.
+        /// Unbounded windows always allow a stay, so only destinations with both windows given can fail the date checks.
+        pub fn validate(&self) -> Result<(), InvalidStay> {
+            if let (Some(min), Some(max)) = (self.min_days, self.max_days) {
+                if min > max {
+                    return Err(InvalidStay::MinAboveMax);
+                }
+            }
+
+            let (arrive_first, arrive_last) = (self.arrival_window.map(|w| w.first()), self.arrival_window.map(|w| w.last()));
+            let (depart_first, depart_last) = (self.departure_window.map(|w| w.first()), self.departure_window.map(|w| w.last()));
+            if let (Some(arrive_first), Some(depart_last)) = (arrive_first, depart_last) {
+                let longest = depart_last.days_since(arrive_first);
+                if longest < 0 {
+                    return Err(InvalidStay::DepartsBeforeArrival);
+                }
+                if self.min_days.is_some_and(|min| longest < min) {
+                    return Err(InvalidStay::TooShort);
+                }
+            }
+            if let (Some(arrive_last), Some(depart_first)) = (arrive_last, depart_first) {
+                let shortest = depart_first.days_since(arrive_last);
+                if self.max_days.is_some_and(|max| shortest > max) {
+                    return Err(InvalidStay::TooLong);
+                }
+            }
+
+            if let Some(event) = self.event {
+                if event.last_day < event.first_day {
+                    return Err(InvalidStay::EventEndsBeforeStart);
+                }
+                if arrive_first.is_some_and(|first| first > event.first_day)
+                    || depart_last.is_some_and(|last| last < event.last_day)
+                {
+                    return Err(InvalidStay::MissesEvent);
+                }
+                if self.max_days.is_some_and(|max| event.last_day.days_since(event.first_day) > max) {
+                    return Err(InvalidStay::TooLong);
+                }
+            }
+            Ok(())
+        }
+
+        /// Most nights the stop's windows and maximum stay allow, `None` if neither limits it
+        fn longest_stay(&self) -> Option<i64> {
+            let by_windows = self.arrival_window.zip(self.departure_window).map(|(a, d)| d.last().days_since(a.first()));
+            self.max_days.into_iter().chain(by_windows).min()
+        }
+
+        /// The stop with its windows and event moved `days` later, earlier if negative
+        pub fn shifted(&self, days: i64) -> DestinationQuery {
+            DestinationQuery {
+                arrival_window: self.arrival_window.map(|w| w.shift(days)),
+                departure_window: self.departure_window.map(|w| w.shift(days)),
+                event: self.event.map(|event| EventDates {
+                    first_day: event.first_day.add_days_saturating(days),
+                    last_day: event.last_day.add_days_saturating(days),
+                }),
+                ..self.clone()
+            }
+        }
+
+        pub fn to_destination(&self) -> Destination {
+            let mut restrictions = DateRestrictions::new();
+            if let Some(md) = self.min_days {
+                restrictions.add_min_days_constraint(Duration::days(md));
+            }
+            if let Some(md) = self.max_days {
+                restrictions.add_max_days_constraint(Duration::days(md));
+            }
+
+            Destination {
+                iata: self.iata,
+                dates: DateConstraints {
+                    arrival_window: self.arrival_window,
+                    departure_window: self.departure_window,
+                    date_restrictions: restrictions,
+                    event: self.event,
+                },
+                max_leg_price: self.max_leg_price,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct RouteQuery {
+        pub start_city: DestinationQuery,
+        pub end_city: DestinationQuery,
+        pub hops: Vec<DestinationQuery>,
+        /// Airports the trip may start from instead of `start_city`, on its dates. The cheapest wins, see
+        /// [RouteSolution::origin].
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub alternate_origins: Vec<AirportCode>,
+        /// What starting from an origin costs the traveller over starting from the others, per passenger in the query's
+        /// currency, e.g. YVR 0 and SEA 80 for the drive down. A route from SEA only wins if it's more than 80 cheaper.
+        /// Origins left out cost nothing extra, and it's never part of the solution's price.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub origin_penalties: BTreeMap<AirportCode, f32>,
+        /// ISO 4217 code of the currency to price the solution in, [BASE_CURRENCY] if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub currency: Option<String>,
+        /// ISO 3166 code of the country the fares are bought in, what providers charge depends on it. The server takes
+        /// it from the request's `Accept-Language` if unset, the US failing that.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub market: Option<String>,
+        /// BCP 47 locale to ask providers in, e.g. `en-GB`. The server takes it from the request's `Accept-Language` if
+        /// unset, `en-US` failing that.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub locale: Option<String>,
+        #[serde(default)]
+        pub passengers: Passengers,
+        #[serde(default)]
+        pub cabin_class: CabinClass,
+        #[serde(default)]
+        pub strategy: SearchStrategy,
+        #[serde(default)]
+        pub limits: RouteLimits,
+        /// What each calendar day the trip takes is worth to the traveller, per passenger in the query's currency. Routes
+        /// are compared on their price plus their days' worth, so a shorter trip can win over a cheaper one. Zero
+        /// compares on price alone.
+        #[serde(default, skip_serializing_if = "is_zero")]
+        pub value_of_day: f32,
+        #[serde(default, skip_serializing_if = "Objective::is_expected")]
+        pub objective: Objective,
+        /// Price each passenger a checked bag on every flight, budget carriers' headline fares leave them out. Providers
+        /// with fare families quote fares with a bag, the server estimates the carrier's bag fee for the rest.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub checked_bag: bool,
+        /// Price only some days of each window first, then refine around the best routes that finds. Every day is
+        /// priced if `None`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub sampling: Option<DateSampling>,
+    }
+
+    /// Limits on how tiring the trip can be, on top of each stop's dates. Limits left unset don't apply.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    pub struct RouteLimits {
+        /// Most flights the whole trip may take
+        #[serde(default)]
+        pub max_flights: Option<usize>,
+        /// Most days in a row with a flight on them, 1 never flies two days running
+        #[serde(default)]
+        pub max_flight_days_in_a_row: Option<u32>,
+        /// Most any one leg may cost, per passenger in the query's currency, however cheap the rest of the trip is.
+        /// Stops can set a lower ceiling on the leg into them with [DestinationQuery::max_leg_price].
+        #[serde(default)]
+        pub max_leg_price: Option<f32>,
+        /// Only fly legs whose cheapest fare is direct. Providers quote the cheapest fare alone, so a leg whose cheapest
+        /// fare stops is ruled out even if a dearer direct flight exists, as are legs whose fare is estimated. Overland
+        /// legs are kept.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub direct_only: bool,
+    }
+
+    /// Who is travelling. Every passenger is quoted the same fare, providers' indicative prices don't discount children.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Passengers {
+        pub adults: u32,
+        pub children: u32,
+    }
+
+    impl Default for Passengers {
+        fn default() -> Self {
+            Passengers { adults: 1, children: 0 }
+        }
+    }
+
+    impl Passengers {
+        /// Most passengers one booking can hold
+        pub const MAX: u32 = 9;
+
+        /// Total travellers, saturating rather than overflowing on counts no booking could hold
+        pub fn count(&self) -> u32 {
+            self.adults.saturating_add(self.children)
+        }
+
+        /// At least one adult, and no more than [Passengers::MAX] in total
+        pub fn is_valid(&self) -> bool {
+            self.adults >= 1 && self.count() <= Self::MAX
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    #[serde(rename_all = "snake_case")]
+    pub enum CabinClass {
+        #[default]
+        Economy,
+        PremiumEconomy,
+        Business,
+        First,
+    }
+
+    impl CabinClass {
+        pub const ALL: [CabinClass; 4] = [CabinClass::Economy, CabinClass::PremiumEconomy, CabinClass::Business, CabinClass::First];
+
+        pub fn label(&self) -> &'static str {
+            match self {
+                CabinClass::Economy => "Economy",
+                CabinClass::PremiumEconomy => "Premium economy",
+                CabinClass::Business => "Business",
+                CabinClass::First => "First",
+            }
+        }
+    }
+
+    /// How the solver searches for routes. [SearchStrategy::Dijkstra] and [SearchStrategy::Dp] always find the cheapest
+    /// route, the others can miss it in exchange for fewer price lookups.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SearchStrategy {
+        /// Carry on from the cheapest route so far
+        #[default]
+        Dijkstra,
+        /// Carry on from the route which looks cheapest once the legs it has left are counted in, estimated from the
+        /// cheapest fare seen so far
+        AStar,
+        /// Only carry on from the `width` cheapest routes at each number of legs flown
+        Beam {
+            #[serde(default = "default_beam_width")]
+            width: usize,
+        },
+        /// Dijkstra which carries on only from the cheapest route to each stop, day and set of places visited
+        Dp,
+    }
+
+    impl SearchStrategy {
+        /// Beam width used when a request doesn't give one
+        pub const DEFAULT_BEAM_WIDTH: usize = 200;
+
+        /// False for a beam which couldn't keep any routes
+        pub fn is_valid(&self) -> bool {
+            !matches!(self, SearchStrategy::Beam { width: 0 })
+        }
+    }
+
+    /// What routes are compared on, besides the days' worth
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Objective {
+        /// The fares as quoted
+        #[default]
+        Expected,
+        /// Each fare plus `sigmas` times how far it's likely to have moved since it was quoted, so a route built on old
+        /// or unreliable quotes doesn't win on prices which are gone by the time it's booked
+        Pessimistic {
+            #[serde(default = "default_sigmas")]
+            sigmas: f32,
+        },
+    }
+
+    impl Objective {
+        /// Standard deviations added to fares when a request doesn't say
+        pub const DEFAULT_SIGMAS: f32 = 1.0;
+
+        pub fn is_expected(&self) -> bool {
+            *self == Objective::Expected
+        }
+
+        /// False for a negative or infinite number of standard deviations
+        pub fn is_valid(&self) -> bool {
+            match self {
+                Objective::Expected => true,
+                Objective::Pessimistic { sigmas } => sigmas.is_finite() && *sigmas >= 0.0,
+            }
+        }
+    }
+
+    /// A search in two passes, for wide windows where pricing every day would cost a lot of calls. The first prices
+    /// every `every`th day and estimates the days between from them, the second prices the days around the legs of the
+    /// `refine` best routes the first found and drops the rest. Windows no wider than `every` days are priced in full.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct DateSampling {
+        #[serde(default = "default_sample_every")]
+        pub every: u32,
+        #[serde(default = "default_refine")]
+        pub refine: usize,
+    }
+
+    impl DateSampling {
+        /// Days apart the sampled days are when a request doesn't say
+        pub const DEFAULT_EVERY: u32 = 3;
+        /// Routes refined when a request doesn't say
+        pub const DEFAULT_REFINE: usize = 5;
+        /// Most days apart the sampled days can be, a month
+        pub const MAX_EVERY: u32 = 31;
+
+        /// Sampling every `every` days, refining the default number of routes
+        pub fn every(every: u32) -> DateSampling {
+            DateSampling {
+                every,
+                refine: DateSampling::DEFAULT_REFINE,
+            }
+        }
+
+        /// False for sampling no days or days more than [DateSampling::MAX_EVERY] apart, or refining no routes
+        pub fn is_valid(&self) -> bool {
+            (1..=Self::MAX_EVERY).contains(&self.every) && self.refine > 0
+        }
+
+        /// Whether `date` is one of the days priced first. Every route samples the same days, so their quotes estimate the
+        /// days between on any route through them.
+        pub fn is_sampled(&self, date: Date) -> bool {
+            self.offset(date) == 0
+        }
+
+        /// Whether `date` is priced rather than estimated when flying in `window`: every day of a window no wider than
+        /// the days between sampled ones, otherwise the sampled days and the window's ends
+        pub fn prices(&self, date: Date, window: DateWindow) -> bool {
+            let at_end = date == window.first() || date == window.last();
+            window.num_days() <= self.every as i64 || at_end || self.is_sampled(date)
+        }
+
+        /// The sampled days before and after `date`, each with how many days away it is, kept to `window` by moving
+        /// them to its ends. Only for days [DateSampling::prices] doesn't price, which fall strictly inside the window.
+        pub fn sampled_around(&self, date: Date, window: DateWindow) -> [(u32, Date); 2] {
+            let offset = self.offset(date) as i64;
+            let before = date
+                .checked_add_signed(Duration::days(-offset))
+                .map_or(window.first(), |d| d.max(window.first()));
+            let after = date
+                .checked_add_signed(Duration::days(self.every as i64 - offset))
+                .map_or(window.last(), |d| d.min(window.last()));
+            [(date.days_since(before) as u32, before), (after.days_since(date) as u32, after)]
+        }
+
+        fn offset(&self, date: Date) -> u32 {
+            date.num_days_from_ce().rem_euclid(self.every.max(1) as i32) as u32
+        }
+    }
+
+    impl Default for DateSampling {
+        fn default() -> Self {
+            DateSampling::every(DateSampling::DEFAULT_EVERY)
+        }
+    }
+
+    fn default_sample_every() -> u32 {
+        DateSampling::DEFAULT_EVERY
+    }
+
+    fn default_refine() -> usize {
+        DateSampling::DEFAULT_REFINE
+    }
+
+    fn default_sigmas() -> f32 {
+        Objective::DEFAULT_SIGMAS
+    }
+
+    fn default_beam_width() -> usize {
+        SearchStrategy::DEFAULT_BEAM_WIDTH
+    }
+
+    fn is_zero(value: &f32) -> bool {
+        *value == 0.0
+    }
+
+    impl RouteQuery {
+        /// Flattens the query into the destination list the router expects, start and end anchors at
+        /// the front and back respectively
+        pub fn destinations(&self) -> Vec<Destination> {
+            let mut dests = vec![self.start_city.to_destination()];
+            dests.extend(self.hops.iter().map(DestinationQuery::to_destination));
+            dests.push(self.end_city.to_destination());
+            dests
+        }
+
+        /// The query split into a round trip from the start to each hop and back to the end, to compare against flying
+        /// them in one loop. Each trip keeps its hop's dates and has to leave and get back between the whole trip's first
+        /// and last days. The trips are solved apart, so their routes can overlap, see [TripOption::new].
+        pub fn round_trips(&self) -> Vec<RouteQuery> {
+            let first = self.start_city.departure_window.map(|w| w.first());
+            let last = self.end_city.arrival_window.map(|w| w.last());
+            let span = first.zip(last).and_then(|(first, last)| DateWindow::new(first, last));
+
+            self.hops
+                .iter()
+                .map(|hop| RouteQuery {
+                    start_city: DestinationQuery {
+                        departure_window: span,
+                        ..self.start_city.clone()
+                    },
+                    end_city: DestinationQuery {
+                        arrival_window: span,
+                        ..self.end_city.clone()
+                    },
+                    hops: vec![hop.clone()],
+                    ..self.clone()
+                })
+                .collect()
+        }
+
+        /// Earliest day any stop's windows or event allow, `None` if the query has no dates at all
+        pub fn first_date(&self) -> Option<Date> {
+            self.stops()
+                .flat_map(|stop| {
+                    [
+                        stop.arrival_window.map(|w| w.first()),
+                        stop.departure_window.map(|w| w.first()),
+                        stop.event.map(|event| event.first_day),
+                    ]
+                })
+                .flatten()
+                .min()
+        }
+
+        /// The whole query moved to start `months` calendar months later, every stop's dates by the same number of days
+        /// so the windows keep their lengths and spacing. Gives the day the moved query starts on along with it, `None`
+        /// if the query has no dates to move.
+        pub fn shifted_months(&self, months: u32) -> Option<(Date, RouteQuery)> {
+            let first = self.first_date()?;
+            let start = first.checked_add_months(Months::new(months))?;
+            let days = start.days_since(first);
+
+            let query = RouteQuery {
+                start_city: self.start_city.shifted(days),
+                end_city: self.end_city.shifted(days),
+                hops: self.hops.iter().map(|hop| hop.shifted(days)).collect(),
+                ..self.clone()
+            };
+            Some((start, query))
+        }
+
+        fn stops(&self) -> impl Iterator<Item = &DestinationQuery> {
+            std::iter::once(&self.start_city).chain(&self.hops).chain(std::iter::once(&self.end_city))
+        }
+
+        /// Warnings about dates which can be solved but probably aren't what was meant, see [LintWarning]. `coordinates`
+        /// gives an airport's `(lat, lon)`, legs to or from an airport it doesn't know aren't checked for being long-haul.
+        pub fn lint(&self, coordinates: impl Fn(AirportCode) -> Option<(f64, f64)>) -> Vec<LintWarning> {
+            let stops: Vec<&DestinationQuery> = self.stops().collect();
+            let mut warnings = Vec::new();
+
+            for stop in &stops {
+                let widest = [stop.arrival_window, stop.departure_window].into_iter().flatten().map(|w| w.num_days()).max();
+                if let Some(days) = widest.filter(|&days| days > LintWarning::WIDE_WINDOW_DAYS) {
+                    warnings.push(LintWarning::WideWindow { iata: stop.iata, days });
+                }
+            }
+
+            for (i, pair) in stops.windows(2).enumerate() {
+                let (from, to) = (pair[0], pair[1]);
+                let same_arrival = from.arrival_window.is_some() && from.arrival_window == to.arrival_window;
+                let same_departure = to.departure_window.is_some() && from.departure_window == to.departure_window;
+                if same_arrival || same_departure {
+                    warnings.push(LintWarning::SameDayHop { from: from.iata, to: to.iata });
+                }
+
+                // The end of the trip isn't stayed at
+                let is_end = i + 2 == stops.len();
+                if is_end || to.longest_stay().is_none_or(|nights| nights > 1) {
+                    continue;
+                }
+                let distance = coordinates(from.iata).zip(coordinates(to.iata)).map(|(a, b)| great_circle_km(a, b));
+                if let Some(distance) = distance.filter(|&km| km >= LintWarning::LONG_HAUL_KM) {
+                    warnings.push(LintWarning::ShortStayAfterLongHaul { iata: to.iata, distance_km: distance as u32 });
+                }
+            }
+            warnings
+        }
+
+        /// Fix the date of the leg from `flight.src` to `flight.dest` to `flight.date`, by pinning the day the trip
+        /// leaves the one and arrives at the other. Returns false, leaving the query alone, if the query has no such leg.
+        pub fn pin_leg(&mut self, flight: &Flight) -> bool {
+            let Some((departs, arrives)) = self.leg_stops(flight) else {
+                return false;
+            };
+            self.pin_stops(departs, arrives, flight.date);
+            true
+        }
+
+        /// Replace the leg `original` covers with `replacement`, which leaves from the same airport but may fly on
+        /// another day or to another airport. The stop the leg arrives at is moved to the new airport, so the leg after
+        /// it leaves from there too, and the replacement's date is pinned as in [RouteQuery::pin_leg]. Returns false,
+        /// leaving the query alone, if the query has no such leg.
+        pub fn swap_leg(&mut self, original: &Flight, replacement: &Flight) -> bool {
+            if original.src != replacement.src {
+                return false;
+            }
+            let Some((departs, arrives)) = self.leg_stops(original) else {
+                return false;
+            };
+
+            match self.hops.get_mut(arrives) {
+                Some(hop) => hop.iata = replacement.dest,
+                None => self.end_city.iata = replacement.dest,
+            }
+            self.pin_stops(departs, arrives, replacement.date);
+            true
+        }
+
+        /// Make the one change `delta` describes, see [WhatIfDelta]. Returns false, leaving the query alone, if the query
+        /// has no such stop.
+        pub fn apply(&mut self, delta: &WhatIfDelta) -> bool {
+            match *delta {
+                WhatIfDelta::Window {
+                    stop,
+                    arrival_window,
+                    departure_window,
+                } => {
+                    let last = self.hops.len() + 1;
+                    let dest = match stop {
+                        0 => &mut self.start_city,
+                        i if i == last => &mut self.end_city,
+                        i => match self.hops.get_mut(i - 1) {
+                            Some(hop) => hop,
+                            None => return false,
+                        },
+                    };
+                    if let Some(window) = arrival_window {
+                        dest.arrival_window = window;
+                    }
+                    if let Some(window) = departure_window {
+                        dest.departure_window = window;
+                    }
+                }
+                WhatIfDelta::DropStop { stop } => {
+                    if stop == 0 || stop > self.hops.len() {
+                        return false;
+                    }
+                    self.hops.remove(stop - 1);
+                }
+                WhatIfDelta::DirectOnly => self.limits.direct_only = true,
+            }
+            true
+        }
+
+        /// Where `flight` leaves from in the start and hops, and where it arrives in the hops and end
+        fn leg_stops(&self, flight: &Flight) -> Option<(usize, usize)> {
+            let departs = std::iter::once(&self.start_city)
+                .chain(&self.hops)
+                .position(|d| d.iata == flight.src)
+                .or_else(|| self.alternate_origins.contains(&flight.src).then_some(0))?;
+            let arrives = self
+                .hops
+                .iter()
+                .chain(std::iter::once(&self.end_city))
+                .position(|d| d.iata == flight.dest)?;
+            Some((departs, arrives))
+        }
+
+        fn pin_stops(&mut self, departs: usize, arrives: usize, date: Date) {
+            let pinned = Some(DateWindow::day(date));
+            match departs {
+                0 => self.start_city.departure_window = pinned,
+                i => self.hops[i - 1].departure_window = pinned,
+            }
+            match self.hops.get_mut(arrives) {
+                Some(hop) => hop.arrival_window = pinned,
+                None => self.end_city.arrival_window = pinned,
+            }
+        }
+    }
+
+    /// Solved route returned to the client. Prices are per passenger, see [RouteSolution::party_total]. The total covers
+    /// each flight's price and the stay before it.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct RouteSolution {
+        pub flights: Vec<FlightPrice>,
+        pub total_price: f32,
+        /// Passengers the route was priced for
+        #[serde(default = "one_passenger")]
+        pub passengers: u32,
+        /// ISO 4217 code of the currency every price in the solution is in
+        #[serde(default = "base_currency")]
+        pub currency: String,
+        /// Runners up to this route, cheapest first. Only filled in when the server is set to look for them.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub alternatives: Vec<RouteSolution>,
+        /// ID of the request which computed the solution, quote it when reporting problems
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub request_id: Option<String>,
+        /// Airport the route starts from, the start city or whichever of the alternate origins made it cheapest
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub origin: Option<AirportCode>,
+        /// What the router did to find the route. Only on the route a solve returns, not its alternatives.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub stats: Option<Box<RouterStats>>,
+    }
+
+    fn one_passenger() -> u32 {
+        1
+    }
+
+    impl RouteSolution {
+        /// What the route costs everyone travelling
+        pub fn party_total(&self) -> f32 {
+            self.total_price * self.passengers as f32
+        }
+
+        /// The stays between legs in the order they're travelled, leg `i` goes from stay `i` to stay `i + 1`
+        pub fn stays(&self) -> Vec<StaySummary> {
+            let mut legs: Vec<&FlightPrice> = self.flights.iter().collect();
+            legs.sort_by_key(|f| f.flight.date);
+
+            let Some(first) = legs.first() else {
+                return vec![];
+            };
+
+            let mut stays = vec![StaySummary {
+                city: first.flight.src,
+                arrive: first.flight.date,
+                depart: first.flight.date,
+                nights: 0,
+                price: 0.0,
+            }];
+            for (i, leg) in legs.iter().enumerate() {
+                let arrive = leg.arrival.unwrap_or(leg.flight.date);
+                let next = legs.get(i + 1);
+                let depart = next.map_or(arrive, |next| next.flight.date);
+                stays.push(StaySummary {
+                    city: leg.flight.dest,
+                    arrive,
+                    depart,
+                    nights: nights_between(arrive, depart),
+                    price: next.map_or(0.0, |next| next.stay_price),
+                });
+            }
+            stays
+        }
+
+        /// What each place the trip goes to costs, in the order they're reached
+        pub fn breakdown(&self) -> Vec<DestinationCost> {
+            let mut legs: Vec<&FlightPrice> = self.flights.iter().collect();
+            legs.sort_by_key(|f| f.flight.date);
+            let total: f32 = legs.iter().map(|f| f.price + f.stay_price).sum();
+
+            let mut running_total = 0.0;
+            legs.iter()
+                .zip(self.stays().into_iter().skip(1))
+                .map(|(leg, stay)| {
+                    let cost = leg.price + stay.price;
+                    running_total += cost;
+                    DestinationCost {
+                        city: stay.city,
+                        inbound: leg.price,
+                        nights: stay.nights,
+                        lodging: stay.price,
+                        share: if total > 0.0 { cost / total } else { 0.0 },
+                        running_total,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    /// One place a trip goes to and what getting there and staying costs, see [RouteSolution::breakdown]
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    pub struct DestinationCost {
+        pub city: AirportCode,
+        /// The leg into the city
+        pub inbound: f32,
+        pub nights: u32,
+        pub lodging: f32,
+        /// Fraction of the whole trip's price the leg in and the stay make up
+        pub share: f32,
+        /// The trip's price up to and including the stay
+        pub running_total: f32,
+    }
+
+    /// Everything about a solved trip in one payload, for the results page and anything exporting trips. Prices and
+    /// emissions are per passenger, in the solution's currency.
+    ///
+    /// The JSON layout is versioned for scripts and spreadsheets reading it. Fields are added without a new
+    /// [TripSummary::SCHEMA_VERSION], so readers should ignore ones they don't know, but removing or renaming one, or
+    /// changing what it means, bumps it. In JSON:
+    ///
+    /// - `schema_version`: number, see [TripSummary::SCHEMA_VERSION]
+    /// - `legs`: array of [LegSummary], `flight` being `{src, dest, date}` with IATA codes and `YYYY-MM-DD` dates
+    /// - `stays`: array of [StaySummary]
+    /// - `breakdown`: array of [DestinationCost]
+    /// - `totals`: [TripTotals]
+    /// - `stats`: [TripStats]
+    /// - `freshness`: [QuoteFreshness], times as `YYYY-MM-DDTHH:MM:SS` in UTC
+    /// - `currency`: ISO 4217 code
+    /// - `passengers`: number
+    /// - `request_id`: string, left out when the solve didn't have one
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct TripSummary {
+        /// Payloads from before the layout was versioned are laid out as version 1
+        #[serde(default = "first_schema_version")]
+        pub schema_version: u32,
+        /// In the order they're travelled
+        pub legs: Vec<LegSummary>,
+        /// See [RouteSolution::stays]
+        pub stays: Vec<StaySummary>,
+        /// See [RouteSolution::breakdown]
+        pub breakdown: Vec<DestinationCost>,
+        pub totals: TripTotals,
+        pub stats: TripStats,
+        pub freshness: QuoteFreshness,
+        /// Every price and total in the summary is in it
+        pub currency: String,
+        pub passengers: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub request_id: Option<String>,
+    }
+
+    impl TripSummary {
+        /// Version of the JSON layout this build writes
+        pub const SCHEMA_VERSION: u32 = 1;
+    }
+
+    fn first_schema_version() -> u32 {
+        1
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct LegSummary {
+        pub flight: Flight,
+        pub mode: TransportMode,
+        /// Day the leg lands, in the destination's local time
+        pub arrival: Date,
+        pub price: f32,
+        /// Who flies the leg, `None` when the provider doesn't say. Indicative prices don't.
+        pub airline: Option<String>,
+        /// Gate to gate, estimated from the distance. `None` overland and for airports the server doesn't know.
+        pub duration_minutes: Option<u32>,
+        /// Where to book the leg, `None` overland
+        pub deep_link: Option<String>,
+        /// `None` for airports the server doesn't know
+        pub co2_kg: Option<f32>,
+        /// Whether `price` is a guess rather than a quote, see [PriceSource::is_estimate]
+        pub estimated: bool,
+    }
+
+    /// Time spent in one place between legs. The start and end of the trip are zero length, they only have a departure
+    /// or an arrival.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct StaySummary {
+        pub city: AirportCode,
+        pub arrive: Date,
+        pub depart: Date,
+        pub nights: u32,
+        /// Accommodation for the stay, zero when the server doesn't know nightly rates
+        pub price: f32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    pub struct TripTotals {
+        /// Every leg, flown or overland
+        pub flights: f32,
+        pub lodging: f32,
+        pub total: f32,
+        /// Of the legs emissions are known for
+        pub co2_kg: f32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    pub struct TripStats {
+        /// Calendar days from the first departure to the last landing
+        pub days: u32,
+        pub nights: u32,
+        /// Places stayed in between the start and the end
+        pub stops: usize,
+        /// Of the legs between airports the server knows
+        pub distance_km: f32,
+    }
+
+    /// How current the trip's fares are
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    pub struct QuoteFreshness {
+        /// When the provider saw the oldest and newest fares, in UTC. `None` if every fare is live or estimated.
+        pub oldest: Option<NaiveDateTime>,
+        pub newest: Option<NaiveDateTime>,
+        pub estimated_legs: usize,
+    }
+
+    /// How an itinerary's stops are flown
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum TripStructure {
+        /// One loop from the start through every stop to the end
+        Combined,
+        /// A trip from the start to each stop and back, see [RouteQuery::round_trips]
+        SeparateRoundTrips,
+    }
+
+    /// One way of flying an itinerary and what it costs
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct TripOption {
+        pub structure: TripStructure,
+        /// The loop for a combined trip, a round trip per stop in the itinerary's order otherwise
+        pub trips: Vec<RouteSolution>,
+        /// What all the trips cost together per passenger, `None` if any of them couldn't be solved
+        pub total_price: Option<f32>,
+        /// Why a trip couldn't be solved
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub failure: Option<ApiError>,
+    }
+
+    /// An itinerary priced as one loop and as separate round trips
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct TripComparison {
+        pub options: Vec<TripOption>,
+        /// The cheaper of the options which could be solved
+        pub cheapest: Option<TripStructure>,
+    }
+
+    impl TripComparison {
+        /// Compare options priced in the same currency, ties go to the one listed first
+        pub fn new(options: Vec<TripOption>) -> TripComparison {
+            let cheapest = options
+                .iter()
+                .filter_map(|option| Some((option.structure, option.total_price?)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(structure, _)| structure);
+
+            TripComparison { options, cheapest }
+        }
+    }
+
+    impl TripOption {
+        /// The option made of `solves`, which failed if any of them did. Separate round trips also fail if any two of
+        /// them are away at once, there's no flying both.
+        pub fn new(structure: TripStructure, solves: Vec<Result<RouteSolution, ApiError>>) -> TripOption {
+            let mut trips = Vec::new();
+            let mut failure = None;
+            for solve in solves {
+                match solve {
+                    Ok(solution) => trips.push(solution),
+                    Err(error) => failure = failure.or(Some(error)),
+                }
+            }
+            if structure == TripStructure::SeparateRoundTrips {
+                failure = failure.or_else(|| Self::overlap(&trips));
+            }
+            let total_price = failure.is_none().then(|| trips.iter().map(|t| t.total_price).sum());
+
+            TripOption {
+                structure,
+                trips,
+                total_price,
+                failure,
+            }
+        }
+
+        /// Why some two of `trips` can't both be flown, `None` if none are away at once. One can leave the day another
+        /// gets back.
+        fn overlap(trips: &[RouteSolution]) -> Option<ApiError> {
+            let away = |trip: &RouteSolution| {
+                let leaves = trip.flights.iter().map(|f| f.flight.date).min()?;
+                let back = trip.flights.iter().map(|f| f.arrival.unwrap_or(f.flight.date)).max()?;
+                Some((leaves, back))
+            };
+            let spans: Vec<_> = trips.iter().enumerate().filter_map(|(i, trip)| Some((i, away(trip)?))).collect();
+            spans.iter().enumerate().find_map(|(n, &(i, (leaves, back)))| {
+                let (j, _) = spans[n + 1..]
+                    .iter()
+                    .find(|(_, (other_leaves, other_back))| leaves < *other_back && *other_leaves < back)?;
+                Some(ApiError::Infeasible(format!("Round trips {} and {} are away at the same time", i + 1, j + 1)))
+            })
+        }
+    }
+
+    /// How a leg changed between two solves of the same itinerary, legs are matched up by the airports they fly between
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct LegChange {
+        pub src: AirportCode,
+        pub dest: AirportCode,
+        /// The leg in the earlier solve, `None` if only the later route flies it
+        pub was: Option<FlightPrice>,
+        /// The leg in the later solve, `None` if only the earlier route flew it
+        pub now: Option<FlightPrice>,
+        /// What the leg costs now less what it cost, `None` unless both solves fly it
+        pub price_change: Option<f32>,
+        /// Days later the leg leaves now, negative if it's earlier. `None` unless both solves fly it.
+        pub days_moved: Option<i64>,
+    }
+
+    /// What changed between two solves of the same itinerary, with every price in the earlier solve's currency
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct SolutionDiff {
+        /// The earlier solve's legs in the order it flies them, then any only the later one flies
+        pub legs: Vec<LegChange>,
+        pub was_total: f32,
+        pub now_total: f32,
+        pub currency: String,
+    }
+
+    /// One change to a solved itinerary to see what its trip would cost with it, see [RouteQuery::apply]. Stops are
+    /// counted as in [RouteQuery::destinations], the start being 0 and the end last.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(tag = "kind", rename_all = "snake_case")]
+    pub enum WhatIfDelta {
+        /// Move a stop's windows, a window left out stays as it was. They're written as in [window_serde], so a window
+        /// can be opened up with `"flexible"`.
+        Window {
+            stop: usize,
+            #[serde(
+                default,
+                deserialize_with = "window_serde::arrival",
+                serialize_with = "window_serde::serialize_given",
+                skip_serializing_if = "Option::is_none"
+            )]
+            arrival_window: Option<Option<DateWindow>>,
+            #[serde(
+                default,
+                deserialize_with = "window_serde::departure",
+                serialize_with = "window_serde::serialize_given",
+                skip_serializing_if = "Option::is_none"
+            )]
+            departure_window: Option<Option<DateWindow>>,
+        },
+        /// Leave out one of the stops between the start and end
+        DropStop { stop: usize },
+        /// Only fly direct, see [RouteLimits::direct_only]
+        DirectOnly,
+    }
+
+    /// A finished job and what to change about its itinerary
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct WhatIfQuery {
+        pub job: JobId,
+        pub delta: WhatIfDelta,
+    }
+
+    /// What a job's trip would cost with a [WhatIfDelta]
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct WhatIf {
+        /// Job which solved the changed itinerary, to keep its route
+        pub job: JobId,
+        pub solution: RouteSolution,
+        /// From the job's route to the changed one's, in the job's currency
+        pub diff: SolutionDiff,
+    }
+
+    impl SolutionDiff {
+        /// What the trip costs now less what it cost
+        pub fn total_change(&self) -> f32 {
+            self.now_total - self.was_total
+        }
+    }
+
+    /// Something about an itinerary's dates that's allowed but probably not what the traveller meant, shown before
+    /// solving it, see [RouteQuery::lint]
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(tag = "kind", rename_all = "snake_case")]
+    pub enum LintWarning {
+        /// A night or less at a stop reached by a long-haul flight
+        ShortStayAfterLongHaul { iata: AirportCode, distance_km: u32 },
+        /// A window so wide searching it is slow and takes many API calls
+        WideWindow { iata: AirportCode, days: i64 },
+        /// Consecutive stops with the same arrival or departure dates, which can force leaving the first the day it's
+        /// reached
+        SameDayHop { from: AirportCode, to: AirportCode },
+    }
+
+    impl LintWarning {
+        /// Legs at least this long are long-haul, around five hours in the air
+        pub const LONG_HAUL_KM: f64 = 4000.0;
+        /// Windows longer than this are wide, every day in one is another round of fares to fetch
+        pub const WIDE_WINDOW_DAYS: i64 = 28;
+    }
+
+    impl fmt::Display for LintWarning {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                LintWarning::ShortStayAfterLongHaul { iata, distance_km } => {
+                    write!(f, "At most a night at {} after flying {} km to get there", iata, distance_km)
+                }
+                LintWarning::WideWindow { iata, days } => {
+                    write!(f, "{} has a {} day window, searching it will be slow and use many API calls", iata, days)
+                }
+                LintWarning::SameDayHop { from, to } => write!(
+                    f,
+                    "{} and {} have the same dates, the trip may have to leave {} the day it gets there",
+                    from, to, from
+                ),
+            }
+        }
+    }
+
+    /// What an itinerary's dates allow, worked out from its windows alone without pricing anything so it's quick enough
+    /// to check as the itinerary is edited
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct Feasibility {
+        /// Whether the stops can be visited in some order their dates allow, `None` if there were too many ways of
+        /// trying to tell quickly
+        pub feasible: Option<bool>,
+        /// The stops in an order their dates allow, start to end, empty if there isn't one
+        pub ordering: Vec<AirportCode>,
+        /// Pairs of stops whose windows rule out visiting them in either order, or in the only one allowed for the
+        /// start and end
+        pub conflicts: Vec<WindowConflict>,
+        /// Fares a solve may have to look up, one for every day each leg could fly on. A rough measure of how long it
+        /// takes, counted as far as it got if `feasible` is `None`.
+        pub legs: u64,
+    }
+
+    /// Stops which can't both be visited: whichever of them comes first can't be left before the other must be reached
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct WindowConflict {
+        pub first: AirportCode,
+        pub second: AirportCode,
+    }
+
+    /// Header a solve is sent with to confirm it may make as many provider calls as its [SolveEstimate] came to
+    pub const CONFIRMED_CALLS_HEADER: &str = "X-Confirmed-Calls";
+
+    /// How many calls to the pricing provider a solve of an itinerary would make, worked out before solving so a big
+    /// one doesn't use up the day's quota by accident
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct SolveEstimate {
+        /// Fares the solve could look up, as in [Feasibility::legs]
+        pub legs: u64,
+        /// Of `legs`, those the server already has a fare for
+        pub cached: u64,
+        /// Most calls the solve makes under the query's strategy, the legs it could look up which aren't cached
+        pub calls: u64,
+        /// Whether the itinerary had too many ways of being flown to count them all, `calls` is then the least it makes
+        pub partial: bool,
+        /// Solves making more calls than this have to be confirmed with [CONFIRMED_CALLS_HEADER], `None` if none do
+        pub confirm_above: Option<u64>,
+        /// Calls the user has left today, `None` if they're unlimited
+        pub remaining: Option<u64>,
+    }
+
+    impl SolveEstimate {
+        /// Whether the server only solves the itinerary once the user has confirmed the calls it makes
+        pub fn needs_confirmation(&self) -> bool {
+            self.confirm_above.is_some_and(|above| self.partial || self.calls > above)
+        }
+
+        /// Whether `confirmed`, the calls the user agreed to, cover the solve
+        pub fn is_confirmed_by(&self, confirmed: Option<u64>) -> bool {
+            !self.needs_confirmation() || confirmed.is_some_and(|confirmed| confirmed >= self.calls)
+        }
+    }
+
+    /// Most months a [MonthSweep] can look across
+    pub const MAX_SWEEP_MONTHS: u32 = 12;
+
+    /// An itinerary priced in each of the coming months, to pick when to travel before refining the dates. Each month
+    /// is the query moved as in [RouteQuery::shifted_months], the first is the query's own.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct MonthSweep {
+        pub months: Vec<SweepMonth>,
+        /// Day the cheapest month which could be solved starts on
+        pub cheapest: Option<Date>,
+    }
+
+    /// One month of a [MonthSweep] and what the trip costs in it
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct SweepMonth {
+        /// Earliest day the moved query allows
+        pub starts: Date,
+        /// Per passenger, `None` if the trip couldn't be solved this month
+        pub total_price: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub failure: Option<ApiError>,
+    }
+
+    impl SweepMonth {
+        pub fn new(starts: Date, solve: Result<RouteSolution, ApiError>) -> SweepMonth {
+            match solve {
+                Ok(solution) => SweepMonth {
+                    starts,
+                    total_price: Some(solution.total_price),
+                    failure: None,
+                },
+                Err(error) => SweepMonth {
+                    starts,
+                    total_price: None,
+                    failure: Some(error),
+                },
+            }
+        }
+    }
+
+    impl MonthSweep {
+        /// Compare months priced in the same currency, ties go to the earlier
+        pub fn new(months: Vec<SweepMonth>) -> MonthSweep {
+            let cheapest = months
+                .iter()
+                .filter_map(|month| Some((month.starts, month.total_price?)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(starts, _)| starts);
+
+            MonthSweep { months, cheapest }
+        }
+    }
+
+    pub type JobId = u64;
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct JobHandle {
+        pub id: JobId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub request_id: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(tag = "state", rename_all = "snake_case")]
+    pub enum JobStatus {
+        Queued,
+        Running {
+            /// Prices looked up by the solve so far, updated periodically
+            #[serde(default)]
+            api_calls: u64,
+            /// Partial routes the search has extended so far, updated periodically
+            #[serde(default)]
+            nodes_expanded: u64,
+        },
+        Completed { solution: RouteSolution },
+        Failed {
+            #[serde(alias = "reason", deserialize_with = "deserialize_failure")]
+            error: ApiError,
+        },
+    }
+
+    /// Error from an API call, as seen by the client. Endpoints answer with one as their JSON body, e.g.
+    /// `{"code": "infeasible", "message": "..."}`, clients can rely on the codes staying the same.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(tag = "code", content = "message", rename_all = "snake_case")]
+    pub enum ApiError {
+        /// The request is malformed or asks for something the server doesn't support
+        Validation(String),
+        /// The request is fine but no trip satisfies it, e.g. no flights fit the dates
+        Infeasible(String),
+        NotFound(String),
+        /// What was asked about isn't done yet, e.g. the summary of a job still solving
+        NotReady(String),
+        Unauthorized(String),
+        /// The user's daily quota of price lookups is spent
+        RateLimited(String),
+        /// The server is overloaded or shutting down
+        Unavailable(String),
+        /// The flight data provider failed or refused to answer
+        ProviderUnavailable(String),
+        Internal(String),
+        /// The request never got a response, never sent by the server
+        Network(String),
+    }
+
+    impl ApiError {
+        /// Classify a non-2xx response, from the error in its body or failing that its status code
+        pub fn from_status(status: u16, body: String) -> ApiError {
+            if let Ok(error) = serde_json::from_str(&body) {
+                return error;
+            }
+            match status {
+                401 | 403 => ApiError::Unauthorized(body),
+                404 => ApiError::NotFound(body),
+                409 => ApiError::NotReady(body),
+                422 => ApiError::Infeasible(body),
+                429 => ApiError::RateLimited(body),
+                502 => ApiError::ProviderUnavailable(body),
+                503 => ApiError::Unavailable(body),
+                400..=499 => ApiError::Validation(body),
+                _ => ApiError::Internal(body),
+            }
+        }
+
+        /// HTTP status the server answers with, 0 for [ApiError::Network] which never comes from it
+        pub fn status(&self) -> u16 {
+            match self {
+                ApiError::Validation(_) => 400,
+                ApiError::Infeasible(_) => 422,
+                ApiError::NotFound(_) => 404,
+                ApiError::NotReady(_) => 409,
+                ApiError::Unauthorized(_) => 401,
+                ApiError::RateLimited(_) => 429,
+                ApiError::Unavailable(_) => 503,
+                ApiError::ProviderUnavailable(_) => 502,
+                ApiError::Internal(_) => 500,
+                ApiError::Network(_) => 0,
+            }
+        }
+
+        /// Stable identifier of the kind of error, the `code` it's serialized with
+        pub fn code(&self) -> &'static str {
+            match self {
+                ApiError::Validation(_) => "validation",
+                ApiError::Infeasible(_) => "infeasible",
+                ApiError::NotFound(_) => "not_found",
+                ApiError::NotReady(_) => "not_ready",
+                ApiError::Unauthorized(_) => "unauthorized",
+                ApiError::RateLimited(_) => "rate_limited",
+                ApiError::Unavailable(_) => "unavailable",
+                ApiError::ProviderUnavailable(_) => "provider_unavailable",
+                ApiError::Internal(_) => "internal",
+                ApiError::Network(_) => "network",
+            }
+        }
+
+        pub fn message(&self) -> &str {
+            match self {
+                ApiError::Validation(m)
+                | ApiError::Infeasible(m)
+                | ApiError::NotFound(m)
+                | ApiError::NotReady(m)
+                | ApiError::Unauthorized(m)
+                | ApiError::RateLimited(m)
+                | ApiError::Unavailable(m)
+                | ApiError::ProviderUnavailable(m)
+                | ApiError::Internal(m)
+                | ApiError::Network(m) => m,
+            }
+        }
+
+        /// Whether sending the same request again could succeed
+        pub fn is_retryable(&self) -> bool {
+            matches!(
+                self,
+                ApiError::NotReady(_)
+                    | ApiError::Unavailable(_)
+                    | ApiError::ProviderUnavailable(_)
+                    | ApiError::Internal(_)
+                    | ApiError::Network(_)
+            )
+        }
+    }
+
+    impl fmt::Display for ApiError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let kind = match self {
+                ApiError::Validation(_) => "Request rejected",
+                ApiError::Infeasible(_) => "No route found",
+                ApiError::NotFound(_) => "Not found",
+                ApiError::NotReady(_) => "Not ready",
+                ApiError::Unauthorized(_) => "Not allowed",
+                ApiError::RateLimited(_) => "Quota exceeded",
+                ApiError::Unavailable(_) => "Server unavailable",
+                ApiError::ProviderUnavailable(_) => "Flight prices unavailable",
+                ApiError::Internal(_) => "Server error",
+                ApiError::Network(_) => "Network error",
+            };
+
+            if self.message().is_empty() {
+                write!(f, "{}", kind)
+            } else {
+                write!(f, "{}: {}", kind, self.message())
+            }
+        }
+    }
+
+    /// Reads a failed job's error, or the bare reason jobs failed with before errors had codes
+    fn deserialize_failure<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ApiError, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Failure {
+            Typed(ApiError),
+            Reason(String),
+        }
+
+        Ok(match Failure::deserialize(deserializer)? {
+            Failure::Typed(error) => error,
+            Failure::Reason(reason) => ApiError::Internal(reason),
+        })
+    }
+
+    /// Snapshot of the background worker pool the solves run on
+    #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+    pub struct WorkerPoolMetrics {
+        pub concurrency: usize,
+        /// Interactive solves allowed to wait
+        pub queue_capacity: usize,
+        /// Solves waiting and running, batch ones included
+        pub queued: usize,
+        pub running: usize,
+        /// Most batch solves running at once, and how many may wait
+        #[serde(default)]
+        pub batch_concurrency: usize,
+        #[serde(default)]
+        pub batch_queue_capacity: usize,
+        #[serde(default)]
+        pub batch_queued: usize,
+        #[serde(default)]
+        pub batch_running: usize,
+        pub completed: u64,
+        pub panicked: u64,
+    }
+
+    /// Nodes the router dropped rather than expanding or queueing, by why
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct PrunedNodes {
+        /// Left unexpanded by the search strategy, as its beam was full or a cheaper node had been to the same places
+        pub strategy: u64,
+        /// Would break the trip's limit on flights or flight days in a row
+        pub limits: u64,
+        /// Cost more than the trip or stop allows for a leg
+        pub leg_price: u64,
+        /// Landed too late for the next stop
+        pub late_arrival: u64,
+        /// Nothing flew the leg that day, and it couldn't be estimated
+        pub no_fare: u64,
+    }
+
+    impl PrunedNodes {
+        pub fn total(&self) -> u64 {
+            self.strategy + self.limits + self.leg_price + self.late_arrival + self.no_fare
+        }
+    }
+
+    /// What the router did for a solve, or for every solve since the server started in [ServerMetrics]
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    pub struct RouterStats {
+        /// Prices looked up, including ones answered from cache
+        pub api_calls: u64,
+        pub nodes_expanded: u64,
+        pub nodes_pruned: PrunedNodes,
+        /// Most priced nodes waiting to be expanded at once
+        pub peak_queue_size: u64,
+        /// Wall-clock milliseconds spent waiting on prices
+        pub pricing_ms: u64,
+        /// Wall-clock milliseconds spent searching, everything but waiting on prices
+        pub search_ms: u64,
+        pub cache_hits: u64,
+        pub cache_misses: u64,
+        /// Fraction of price lookups the cache answered, `None` if none went through one
+        pub cache_hit_rate: Option<f64>,
+        /// Legs the provider failed to price or the quota had run out for, which were priced some other way or ruled out
+        /// rather than failing the solve
+        #[serde(default)]
+        pub degraded_legs: u64,
+        /// Coarsest the search got as the user's quota ran low
+        #[serde(default)]
+        pub throttle: ThrottleLevel,
+    }
+
+    /// How much coarser than asked a search got to make do with the provider calls left in the user's quota for the day.
+    /// Each level does what the ones before it do too.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ThrottleLevel {
+        /// Searched as asked
+        #[default]
+        Full,
+        /// Only every few days were priced, the days between estimated from them
+        Sampled,
+        /// Only the cheapest few routes at each number of stops were carried on from, like a beam search
+        Narrowed,
+        /// The quota ran out, the legs left were estimated or ruled out
+        Exhausted,
+    }
+
+    impl RouterStats {
+        /// Record the provider's cache having answered `hits` lookups and passed `misses` on
+        pub fn set_cache(&mut self, hits: u64, misses: u64) {
+            self.cache_hits = hits;
+            self.cache_misses = misses;
+            self.cache_hit_rate = (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64);
+        }
+
+        /// Add `other`'s counts and times to these, keeping the larger peak queue size and the coarser throttle
+        pub fn merge(&mut self, other: &RouterStats) {
+            self.api_calls += other.api_calls;
+            self.nodes_expanded += other.nodes_expanded;
+            let (pruned, theirs) = (&mut self.nodes_pruned, &other.nodes_pruned);
+            pruned.strategy += theirs.strategy;
+            pruned.limits += theirs.limits;
+            pruned.leg_price += theirs.leg_price;
+            pruned.late_arrival += theirs.late_arrival;
+            pruned.no_fare += theirs.no_fare;
+            self.peak_queue_size = self.peak_queue_size.max(other.peak_queue_size);
+            self.pricing_ms += other.pricing_ms;
+            self.search_ms += other.search_ms;
+            self.degraded_legs += other.degraded_legs;
+            self.throttle = self.throttle.max(other.throttle);
+            self.set_cache(self.cache_hits + other.cache_hits, self.cache_misses + other.cache_misses);
+        }
+    }
+
+    /// Returned by the metrics endpoint, the worker pool's state alongside the router's work summed over every solve
+    #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+    pub struct ServerMetrics {
+        #[serde(flatten)]
+        pub jobs: WorkerPoolMetrics,
+        #[serde(default)]
+        pub router: RouterStats,
+    }
+
+    /// How many times a route was searched, routes are given as their IATA codes joined by " -> "
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct RouteSearchCount {
+        pub route: String,
+        pub count: u64,
+    }
+
+    /// Aggregate server statistics shown on the admin page
+    #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+    pub struct AdminStats {
+        pub solves_completed: u64,
+        pub solves_failed: u64,
+        /// Prices looked up by the router across every solve, including ones answered from cache
+        pub router_api_calls: u64,
+        /// Calls which actually reached a pricing provider
+        pub provider_calls: u64,
+        pub provider_errors: u64,
+        /// Fraction of provider calls which failed, 0 if there haven't been any
+        pub provider_error_rate: f64,
+        /// Entries in the price cache, `None` if the backend couldn't be asked
+        pub cache_entries: Option<u64>,
+        pub jobs: WorkerPoolMetrics,
+        /// Most searched routes, most popular first
+        pub top_routes: Vec<RouteSearchCount>,
+    }
+
+    /// Cheapest quote for a leg on one day, `None` if it couldn't be priced
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct CalendarDay {
+        pub date: Date,
+        pub price: Option<f32>,
+    }
+
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    /// Distance between two `(lat, lon)` points in degrees, along the earth's surface
+    pub fn great_circle_km((lat_a, lon_a): (f64, f64), (lat_b, lon_b): (f64, f64)) -> f64 {
+        let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+        let half_dlat = (lat_b - lat_a) / 2.0;
+        let half_dlon = (lon_b - lon_a).to_radians() / 2.0;
+        let h = half_dlat.sin().powi(2) + lat_a.cos() * lat_b.cos() * half_dlon.sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+    }
+
+    /// An airport and where it is, for drawing routes on a map
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct Airport {
+        pub iata: String,
+        pub name: String,
+        pub city: String,
+        pub country: String,
+        pub lat: f64,
+        pub lon: f64,
+        /// IANA name of the airport's time zone, e.g. "America/Toronto"
+        #[serde(default)]
+        pub tz: Option<String>,
+    }
+
+    impl Airport {
+        /// Flag emoji of the airport's country, from its ISO 3166 code. `None` if the code isn't two letters.
+        pub fn flag(&self) -> Option<String> {
+            let code = self.country.to_ascii_uppercase();
+            if code.len() != 2 || !code.bytes().all(|b| b.is_ascii_uppercase()) {
+                return None;
+            }
+            // Each letter maps onto its regional indicator symbol, pairs of which render as flags
+            code.bytes().map(|b| char::from_u32(0x1F1E6 + (b - b'A') as u32)).collect()
+        }
+    }
+
+    /// Ready made itinerary the builder can start from. Dates are given relative to the day the trip starts, so a
+    /// template stays useful whenever it's picked.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct TripTemplate {
+        pub id: String,
+        pub name: String,
+        pub description: String,
+        /// In trip order, the first is where the trip starts and the last where it ends
+        pub stops: Vec<TemplateStop>,
+    }
+
+    /// A stop of a [TripTemplate], its windows are the first and last day to fly in or out, counted in days from the
+    /// start of the trip. `None` leaves that date open.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct TemplateStop {
+        pub iata: String,
+        #[serde(default)]
+        pub fly_in: Option<(u64, u64)>,
+        #[serde(default)]
+        pub fly_out: Option<(u64, u64)>,
+    }
+
+    impl TemplateStop {
+        pub fn fly_in_dates(&self, start: Date) -> Option<(Date, Date)> {
+            Self::window_dates(self.fly_in, start)
+        }
+
+        pub fn fly_out_dates(&self, start: Date) -> Option<(Date, Date)> {
+            Self::window_dates(self.fly_out, start)
+        }
+
+        fn window_dates(window: Option<(u64, u64)>, start: Date) -> Option<(Date, Date)> {
+            let (first, last) = window?;
+            Some((start.checked_add_days(Days::new(first))?, start.checked_add_days(Days::new(last))?))
+        }
+    }
+
+    /// Days a flight may go on, from the first to the last inclusive. The first is never after the last, so there's
+    /// always at least one day. Windows the user may leave open are `Option<DateWindow>`s, unbounded if `None`.
+    #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+    pub struct DateWindow {
+        first: Date,
+        last: Date,
+    }
+
+    impl DateWindow {
+        /// The days from `first` to `last`, `None` if `first` is after `last`
+        pub fn new(first: Date, last: Date) -> Option<DateWindow> {
+            (first <= last).then_some(DateWindow { first, last })
+        }
+
+        /// Just `date`
+        pub fn day(date: Date) -> DateWindow {
+            DateWindow { first: date, last: date }
+        }
+
+        pub fn first(&self) -> Date {
+            self.first
+        }
+
+        pub fn last(&self) -> Date {
+            self.last
+        }
+
+        /// Days in the window, counting both ends
+        pub fn num_days(&self) -> i64 {
+            self.last.days_since(self.first) + 1
+        }
+
+        pub fn contains(&self, date: Date) -> bool {
+            (self.first..=self.last).contains(&date)
+        }
+
+        /// The window moved `days` later, earlier if negative
+        pub fn shift(&self, days: i64) -> DateWindow {
+            DateWindow {
+                first: self.first.add_days_saturating(days),
+                last: self.last.add_days_saturating(days),
+            }
+        }
+
+        /// Days in both windows, `None` if they don't overlap
+        pub fn intersect(&self, other: &DateWindow) -> Option<DateWindow> {
+            DateWindow::new(max(self.first, other.first), min(self.last, other.last))
+        }
+
+        /// The part of the window on or before `date`, `None` if none of it is
+        pub fn until(&self, date: Date) -> Option<DateWindow> {
+            DateWindow::new(self.first, min(self.last, date))
+        }
+
+        /// The part of the window on or after `date`, `None` if none of it is
+        pub fn since(&self, date: Date) -> Option<DateWindow> {
+            DateWindow::new(max(self.first, date), self.last)
+        }
+
+        /// The part of the window after `date`, `None` if none of it is
+        pub fn truncate(&self, date: Date) -> Option<DateWindow> {
+            self.since(date.add_days_saturating(1)).filter(|_| date < self.last)
+        }
+
+        /// Days in the window the stay `restrictions` allow, counting the stay from the window's first day
+        pub fn iter(&self, restrictions: DateRestrictions) -> DateWindowIter {
+            self.iter_partial(restrictions, Some(self.first))
+        }
+
+        /// Days in the window the stay `restrictions` allow leaving on, having arrived on `src_date`
+        pub fn iter_partial(&self, restrictions: DateRestrictions, src_date: Option<Date>) -> DateWindowIter {
+            DateWindowIter::new(Some(*self), restrictions, src_date)
+        }
     }
 
-    /// Date range for either the inbound or outbound flight, flexibility on whether the user wants
-    /// exact dates, or doesn't card
-    #[derive(Debug, Eq, PartialEq, Hash, Clone)]
+    /// How windows the user may leave open went over the wire before [window_serde], still read from saved jobs and
+    /// old clients. Convert to the `Option<DateWindow>`s in the queries with [From] and [TryFrom].
+    #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
     pub enum SingleDateRange {
         None,
         FixedDate(Date),
         DateRange(Date, Date),
     }
 
-    #[derive(Debug)]
-    pub struct SingleDateRangeIter {
-        date_range: SingleDateRange,
-        src_date: Option<Date>,
-        curr_date: Date,
-        day_count: u16,
-        restrictions: Rc<DateRestrictions>,
+    /// A [SingleDateRange::DateRange] ending before it starts
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InvalidWindow {
+        pub first: Date,
+        pub last: Date,
     }
 
-    impl SingleDateRange {
-        pub fn first_date(&self) -> Option<Date> {
-            match &self {
-                Self::None => None,
-                Self::FixedDate(d) => Some(d.clone()),
-                Self::DateRange(d1, _) => Some(d1.clone())
-            }
+    impl fmt::Display for InvalidWindow {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Date window ends on {} before it starts on {}", self.last, self.first)
         }
+    }
 
-        pub fn last_date(&self) -> Option<Date> {
-            match &self {
-                Self::None => None,
-                Self::FixedDate(d) => Some(d.clone()),
-                Self::DateRange(_, d2) => Some(d2.clone())
+    impl From<Option<DateWindow>> for SingleDateRange {
+        fn from(window: Option<DateWindow>) -> Self {
+            match window {
+                None => SingleDateRange::None,
+                Some(window) if window.first == window.last => SingleDateRange::FixedDate(window.first),
+                Some(window) => SingleDateRange::DateRange(window.first, window.last),
             }
         }
+    }
 
-        pub fn low_high(&self) -> (Option<Date>, Option<Date>) {
-            (self.first_date(), self.last_date())
-        }
+    impl TryFrom<SingleDateRange> for Option<DateWindow> {
+        type Error = InvalidWindow;
 
-        pub fn fixify(&self) -> Option<Self> {
-            // Temporary solution
-            match self {
-                SingleDateRange::FixedDate(d) => Some(SingleDateRange::DateRange(d.clone(), d.clone())),
-                SingleDateRange::DateRange(d1, d2) => Some(SingleDateRange::DateRange(d1.clone(), d2.clone())),
-                SingleDateRange::None => None,
+        fn try_from(range: SingleDateRange) -> Result<Self, InvalidWindow> {
+            match range {
+                SingleDateRange::None => Ok(None),
+                SingleDateRange::FixedDate(date) => Ok(Some(DateWindow::day(date))),
+                SingleDateRange::DateRange(first, last) => {
+                    DateWindow::new(first, last).map(Some).ok_or(InvalidWindow { first, last })
+                }
             }
         }
+    }
 
-        pub fn iter(&self, restrictions: Rc<DateRestrictions>) -> SingleDateRangeIter {
-            self.iter_partial(restrictions, self.first_date())
-        }
+    /// The JSON form of a window the user may leave open. A single day is its ISO-8601 date, `"2023-02-01"`, several
+    /// are a `{"start": "2023-02-01", "end": "2023-02-05"}` range, and an unbounded window is `"flexible"`. The
+    /// [SingleDateRange] form is still read, and `null` is read as flexible.
+    ///
+    /// Errors name the field the window was read for, [arrival] and [departure] read the destination windows.
+    pub mod window_serde {
+        use std::fmt;
 
-        pub fn iter_partial(
-            &self,
-            restrictions: Rc<DateRestrictions>,
-            src_date: Option<Date>,
-        ) -> SingleDateRangeIter {
-            let start_date = max(src_date.map(|d| d + restrictions.min_days.unwrap_or(Duration::days(0))), self.first_date());
-            match self {
-                Self::FixedDate(d) => SingleDateRangeIter {
-                    date_range: self.clone(),
-                    curr_date: start_date.unwrap(), // An error here is a hard error
-                    day_count: 0,
-                    src_date,
-                    restrictions,
-                },
-                Self::DateRange(d1, _) => SingleDateRangeIter {
-                    date_range: self.clone(),
-                    curr_date: start_date.unwrap(),
-                    day_count: 0,
-                    src_date,
-                    restrictions,
-                },
-                _ => SingleDateRangeIter {
-                    date_range: self.clone(),
-                    curr_date: NaiveDate::MIN,
-                    day_count: 0,
-                    src_date,
-                    restrictions,
-                },
+        use serde::{
+            de::{self, MapAccess, Visitor},
+            ser::SerializeMap,
+            Deserializer, Serializer,
+        };
+
+        use super::{Date, DateWindow};
+
+        pub const FLEXIBLE: &str = "flexible";
+
+        pub fn serialize<S: Serializer>(window: &Option<DateWindow>, serializer: S) -> Result<S::Ok, S::Error> {
+            match window {
+                None => serializer.serialize_str(FLEXIBLE),
+                Some(window) if window.first == window.last => serializer.collect_str(&window.first),
+                Some(window) => {
+                    let mut range = serializer.serialize_map(Some(2))?;
+                    range.serialize_entry("start", &window.first)?;
+                    range.serialize_entry("end", &window.last)?;
+                    range.end()
+                }
             }
         }
 
-        pub fn intersect(&self, other: &SingleDateRange) -> SingleDateRange {
-            let (s_maybe_low, s_maybe_high) = self.low_high();
-            let (o_maybe_low, o_maybe_high) = other.low_high();
+        /// A window which may not have been given, only for given ones so skip the others with `Option::is_none`
+        pub fn serialize_given<S: Serializer>(window: &Option<Option<DateWindow>>, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize(&window.flatten(), serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateWindow>, D::Error> {
+            deserializer.deserialize_any(WindowVisitor { field: "date window" })
+        }
 
-            let s_maybe_low = s_maybe_low.or(o_maybe_low);
-            let o_maybe_low = o_maybe_low.or(s_maybe_low);
-            let s_maybe_high = s_maybe_high.or(o_maybe_high);
-            let o_maybe_high = o_maybe_high.or(s_maybe_high);
+        /// A destination's `arrival_window`, `None` if it wasn't given
+        pub fn arrival<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<DateWindow>>, D::Error> {
+            deserializer.deserialize_any(WindowVisitor { field: "arrival_window" }).map(Some)
+        }
 
-            let dates = [s_maybe_low, s_maybe_high, o_maybe_low, o_maybe_high];
-            if dates.iter().any(|f| f.is_none()) { return SingleDateRange::None }
+        /// A destination's `departure_window`, `None` if it wasn't given
+        pub fn departure<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<DateWindow>>, D::Error> {
+            deserializer.deserialize_any(WindowVisitor { field: "departure_window" }).map(Some)
+        }
 
-            let lowest_date = max(s_maybe_low, o_maybe_low);
-            let high_date = min(s_maybe_high, o_maybe_high);
+        struct WindowVisitor {
+            field: &'static str,
+        }
 
-            if lowest_date > high_date { return SingleDateRange::None }
+        impl WindowVisitor {
+            fn date<E: de::Error>(&self, text: &str) -> Result<Date, E> {
+                text.parse()
+                    .map_err(|_| E::custom(format!("{}: \"{}\" isn't an ISO-8601 date like \"2023-02-01\"", self.field, text)))
+            }
 
-            if lowest_date == high_date {
-                SingleDateRange::FixedDate(lowest_date.unwrap())
-            } else {
-                SingleDateRange::DateRange(lowest_date.unwrap(), high_date.unwrap())
+            fn range<E: de::Error>(&self, start: Option<Date>, end: Option<Date>) -> Result<Option<DateWindow>, E> {
+                let start = start.ok_or_else(|| E::custom(format!("{}: range is missing its \"start\"", self.field)))?;
+                let end = end.ok_or_else(|| E::custom(format!("{}: range is missing its \"end\"", self.field)))?;
+                DateWindow::new(start, end)
+                    .map(Some)
+                    .ok_or_else(|| E::custom(format!("{}: range ends on {} before it starts on {}", self.field, end, start)))
             }
         }
 
-        /// Given a date truncate all dates before (inclusive) the given date.
-        pub fn truncate(&self, date: Date) -> Self {
-            match self {
-                Self::FixedDate(d) => {
-                    if *d > date {
-                        return SingleDateRange::FixedDate(d.clone());
-                    } else {
-                        return SingleDateRange::None;
-                    }
+        impl<'de> Visitor<'de> for WindowVisitor {
+            type Value = Option<DateWindow>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "{} to be a date like \"2023-02-01\", a {{\"start\", \"end\"}} range or \"{}\"",
+                    self.field, FLEXIBLE
+                )
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                match text {
+                    FLEXIBLE | "None" => Ok(None),
+                    text => self.date(text).map(|date| Some(DateWindow::day(date))),
                 }
-                Self::DateRange(d1, d2) => {
-                    if *d2 > date {
-                        // Don't particularly care if we return a date range where before and after are the same day
-                        // Shouldn't cause issues, but if it does fix here
-                        return SingleDateRange::DateRange(
-                            max(d1.clone(), date + Days::new(1)),
-                            d2.clone(),
-                        );
-                    } else {
-                        return SingleDateRange::None;
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(None)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (mut start, mut end) = (None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "start" => start = Some(self.date(&map.next_value::<String>()?)?),
+                        "end" => end = Some(self.date(&map.next_value::<String>()?)?),
+                        "FixedDate" => {
+                            let date = self.date(&map.next_value::<String>()?)?;
+                            (start, end) = (Some(date), Some(date));
+                        }
+                        "DateRange" => {
+                            let (first, last) = map.next_value::<(String, String)>()?;
+                            (start, end) = (Some(self.date(&first)?), Some(self.date(&last)?));
+                        }
+                        other => {
+                            let message = format!("{}: unexpected key \"{}\", expected \"start\" and \"end\"", self.field, other);
+                            return Err(de::Error::custom(message));
+                        }
                     }
                 }
-                Self::None => Self::None,
+                self.range(start, end)
             }
         }
     }
 
-    /// Contains the inbound and outbound dates for a flight, or the number of days the user wants
-    #[derive(Clone, Debug)]
-    pub struct DateRange(pub SingleDateRange, pub SingleDateRange);
+    /// Days of a window a stay allows leaving on, see [DateWindow::iter_partial]
+    #[derive(Debug)]
+    pub struct DateWindowIter {
+        /// Next day to give and the last, `None` once there are no more
+        remaining: Option<(Date, Date)>,
+        src_date: Option<Date>,
+        restrictions: DateRestrictions,
+    }
 
-    #[derive(Clone, Debug)]
+    /// How long a stay may be, sent as whole days
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
     pub struct DateRestrictions {
+        #[serde(default, with = "whole_days")]
         pub min_days: Option<Duration>,
+        #[serde(default, with = "whole_days")]
         pub max_days: Option<Duration>,
     }
 
-    impl Default for DateRestrictions {
-        fn default() -> Self {
-            Self { min_days: None, max_days: None }
+    /// Reads and writes a [Duration] as the whole days in it
+    mod whole_days {
+        use chrono::Duration;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(days: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+            days.map(|days| days.num_days()).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+            Ok(Option::<i64>::deserialize(deserializer)?.map(Duration::days))
         }
     }
 
     impl DateRestrictions {
-        fn new() -> Self {
+        pub fn new() -> Self {
             DateRestrictions {
                 min_days: None,
                 max_days: None,
             }
         }
 
-        fn add_min_days_constraint(&mut self, md: Duration) {
+        pub fn add_min_days_constraint(&mut self, md: Duration) {
             self.min_days = Some(md);
         }
 
-        fn add_max_days_constraint(&mut self, md: Duration) {
+        pub fn add_max_days_constraint(&mut self, md: Duration) {
             self.max_days = Some(md);
         }
 
-        fn within_constraints(&self, prev_date: Date, curr_date: Date) -> bool {
+        pub fn within_constraints(&self, prev_date: Date, curr_date: Date) -> bool {
             let dur = curr_date - prev_date;
             let min_met = if let Some(md) = &self.min_days {
                 dur >= *md
@@ -204,85 +2012,365 @@ pub mod queries {
         }
     }
 
-    #[derive(Clone, Debug)]
+    /// When the trip may reach and leave a destination, and how long it may stay
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
     pub struct DateConstraints {
-        pub date_range: Option<DateRange>,
-        pub date_restrictions: Rc<DateRestrictions>,
+        /// Days the flight in may land on, unbounded if `None`
+        #[serde(with = "window_serde")]
+        pub arrival_window: Option<DateWindow>,
+        /// Days the flight out may leave on, unbounded if `None`
+        #[serde(with = "window_serde")]
+        pub departure_window: Option<DateWindow>,
+        #[serde(default)]
+        pub date_restrictions: DateRestrictions,
+        /// Days the trip must be at the destination for
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub event: Option<EventDates>,
     }
 
     impl DateConstraints {
-        pub fn get_intersect_iter_with_next(&self, next: &DateConstraints, src_date: Option<Date>) -> SingleDateRangeIter {
-            let drs = (self.date_range.clone(), next.date_range.clone());
-            let sdr_intersect = match drs {
-                (Some(dr1), Some(dr2)) => dr1.1.intersect(&dr2.0),
-                (Some(dr1), None) => dr1.1,
-                (None, Some(dr2)) => dr2.0,
-                (None, None) => panic!("No date ranges should have been filtered and corrected by frontend")
+        /// Days the leg from this destination to `next` may fly on: inside both this destination's departure window and
+        /// the next one's arrival window, and within this destination's stay limits of arriving on `src_date`. A window
+        /// left unbounded doesn't narrow the other, and with both unbounded there are no days to fly.
+        ///
+        /// Events narrow the windows first, the leg can't leave before this destination's event is over or land after
+        /// the next one's has started. An unbounded window next to an event is only the event's first or last day.
+        pub fn get_intersect_iter_with_next(&self, next: &DateConstraints, src_date: Option<Date>) -> DateWindowIter {
+            DateWindowIter::new(self.leg_window(next), self.date_restrictions, src_date)
+        }
+
+        /// The window [DateConstraints::get_intersect_iter_with_next] flies in before the stay limits, `None` if there
+        /// are no days to fly
+        pub fn leg_window(&self, next: &DateConstraints) -> Option<DateWindow> {
+            // `None` if the event leaves no days to fly, `Some(None)` if the window's unbounded
+            let departure = match (self.event, self.departure_window) {
+                (Some(event), Some(window)) => window.since(event.last_day).map(Some),
+                (Some(event), None) => Some(Some(DateWindow::day(event.last_day))),
+                (None, window) => Some(window),
+            };
+            let arrival = match (next.event, next.arrival_window) {
+                (Some(event), Some(window)) => window.until(event.first_day).map(Some),
+                (Some(event), None) => Some(Some(DateWindow::day(event.first_day))),
+                (None, window) => Some(window),
             };
+            match departure.zip(arrival) {
+                Some((Some(departure), Some(arrival))) => departure.intersect(&arrival),
+                Some((window, None) | (None, window)) => window,
+                None => None,
+            }
+        }
 
-            sdr_intersect.iter_partial(self.date_restrictions.clone(), src_date) 
+        /// Whether landing on `arrival` is in time for the destination's event, if it has one
+        pub fn arrives_in_time(&self, arrival: Date) -> bool {
+            self.event.is_none_or(|event| arrival <= event.first_day)
         }
+
+        /// Whether the trip can leave here in time to reach `next` at some point after, going by the windows, minimum
+        /// stay and event alone. Dates left unbounded on either side always can.
+        pub fn can_precede(&self, next: &DateConstraints) -> bool {
+            match (self.earliest_departure(), next.latest_arrival()) {
+                (Some(departs), Some(arrives)) => departs <= arrives,
+                _ => true,
+            }
+        }
+
+        fn min_stay_days(&self) -> i64 {
+            self.date_restrictions.min_days.map_or(0, |d| d.num_days())
+        }
+
+        fn earliest_departure(&self) -> Option<Date> {
+            [
+                self.departure_window.map(|w| w.first()),
+                self.arrival_window.map(|w| w.first().add_days_saturating(self.min_stay_days())),
+                self.event.map(|e| e.last_day),
+            ]
+            .into_iter()
+            .flatten()
+            .max()
+        }
+
+        fn latest_arrival(&self) -> Option<Date> {
+            [
+                self.arrival_window.map(|w| w.last()),
+                self.departure_window.map(|w| w.last().add_days_saturating(-self.min_stay_days())),
+                self.event.map(|e| e.first_day),
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+        }
+    }
+
+    /// Pairs of the stops, by index, whose dates rule out visiting them in either order. The first stop is the start
+    /// and the last the end, which only have the one order to try. Cheap enough to run on every edit, in the browser
+    /// too, see [DateConstraints::can_precede].
+    pub fn window_conflicts(stops: &[DateConstraints]) -> Vec<(usize, usize)> {
+        let last = stops.len().saturating_sub(1);
+        let mut conflicts = Vec::new();
+        for (i, first) in stops.iter().enumerate() {
+            for (j, second) in stops.iter().enumerate().skip(i + 1) {
+                let fits = if i == 0 || j == last {
+                    first.can_precede(second)
+                } else {
+                    first.can_precede(second) || second.can_precede(first)
+                };
+                if !fits {
+                    conflicts.push((i, j));
+                }
+            }
+        }
+        conflicts
     }
 
     /// Represents a single destination, as the IATA (airport code), and a date range which gives
     /// flexibility on when the user wants to go
     #[derive(Clone, Debug)]
     pub struct Destination {
-        pub iata: String,
+        pub iata: AirportCode,
         pub dates: DateConstraints,
+        /// Most the leg into the destination may cost, in the same currency as the prices it's compared to
+        pub max_leg_price: Option<f32>,
+    }
+
+    impl DateWindowIter {
+        /// Days of `window` from `src_date` plus the minimum stay, none if there's no window
+        fn new(window: Option<DateWindow>, restrictions: DateRestrictions, src_date: Option<Date>) -> Self {
+            let earliest = src_date.map(|d| d + restrictions.min_days.unwrap_or(Duration::days(0)));
+            DateWindowIter {
+                remaining: window.and_then(|w| w.since(max(earliest, Some(w.first)).unwrap())).map(|w| (w.first, w.last)),
+                src_date,
+                restrictions,
+            }
+        }
     }
 
-    impl Iterator for SingleDateRangeIter {
+    impl Iterator for DateWindowIter {
         type Item = Date;
 
         fn next(&mut self) -> Option<Self::Item> {
-            // TODO: Having a seperate fixed date and None date type is in retrospect really stupid, fix this later
-            let end_date = match self.date_range.fixify() {
-                Some(SingleDateRange::DateRange(_, d)) => d,
-                _ => return None
-            };
+            let (curr_date, last) = self.remaining?;
 
             // Check max restriction
-            if self.restrictions.max_days.zip(self.src_date).map(|(max_days, src_date)| max_days <= self.curr_date.signed_duration_since(src_date)).unwrap_or(false) {
-                return None; 
-            }
-
-            // Check if is past max date
-            if self.curr_date > end_date {
+            if self.restrictions.max_days.zip(self.src_date).is_some_and(|(max_days, src_date)| curr_date.days_since(src_date) >= max_days.num_days()) {
+                self.remaining = None;
                 return None;
             }
 
-            let ret = Some(self.curr_date);
-            self.curr_date = self.curr_date + Days::new(1);
-            ret
+            // Nothing comes after the last day, even when it's the last the calendar has
+            self.remaining = (curr_date < last).then(|| (curr_date.add_days_saturating(1), last));
+            Some(curr_date)
         }
     }
 
     /// Represents a flight on a given day
-    #[derive(Debug, Eq, PartialEq, Hash, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
     pub struct Flight {
-        pub src: String,
-        pub dest: String,
+        pub src: AirportCode,
+        pub dest: AirportCode,
         pub date: Date,
     }
 
-    #[derive(Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(from = "FlightPriceWire")]
     pub struct FlightPrice {
         pub flight: Flight,
         pub price: f32,
+        /// Accommodation where the flight leaves from, since the flight before it landed. Only priced when the server
+        /// knows nightly rates.
+        #[serde(default)]
+        pub stay_price: f32,
+        /// How the leg is travelled, legs between nearby stops may go overland
+        #[serde(default)]
+        pub mode: TransportMode,
+        /// Where `price` came from, see [PriceSource::confidence] for how far to trust it
+        #[serde(default, skip_serializing_if = "PriceSource::is_quoted")]
+        pub source: PriceSource,
+        /// How far `price` is likely to be from the fare at booking, one standard deviation. Zero for live fares.
+        #[serde(default, skip_serializing_if = "is_zero")]
+        pub std_dev: f32,
+        /// Day the leg lands, in the destination's local time. `None` from servers which didn't say, take it to land the
+        /// day it leaves.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub arrival: Option<Date>,
+        /// When the provider saw the fare, in UTC, `None` for live fares and estimates
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub quoted_at: Option<NaiveDateTime>,
+        /// What a checked bag adds to the fare, already in `price`. Zero unless the query asked for one, see
+        /// [RouteQuery::checked_bag].
+        #[serde(default, skip_serializing_if = "is_zero")]
+        pub bag_fee: f32,
+        /// Why the leg was chosen, the next best the search considered instead of it. Only on the legs of the route a
+        /// solve returns, and only if the search considered another.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub alternative: Option<LegAlternative>,
+    }
+
+    /// What a [FlightPrice] is read from, which also takes the `estimated` flag legs were saved with before they had a
+    /// [PriceSource], so solutions from then keep their estimates
+    #[derive(Deserialize)]
+    struct FlightPriceWire {
+        flight: Flight,
+        price: f32,
+        #[serde(default)]
+        stay_price: f32,
+        #[serde(default)]
+        mode: TransportMode,
+        #[serde(default)]
+        estimated: bool,
+        #[serde(default)]
+        source: Option<PriceSource>,
+        #[serde(default)]
+        std_dev: f32,
+        #[serde(default)]
+        arrival: Option<Date>,
+        #[serde(default)]
+        quoted_at: Option<NaiveDateTime>,
+        #[serde(default)]
+        bag_fee: f32,
+        #[serde(default)]
+        alternative: Option<LegAlternative>,
+    }
+
+    impl From<FlightPriceWire> for FlightPrice {
+        fn from(wire: FlightPriceWire) -> Self {
+            let legacy = if wire.estimated { PriceSource::NearbyDays } else { PriceSource::Quoted };
+            FlightPrice {
+                flight: wire.flight,
+                price: wire.price,
+                stay_price: wire.stay_price,
+                mode: wire.mode,
+                source: wire.source.unwrap_or(legacy),
+                std_dev: wire.std_dev,
+                arrival: wire.arrival,
+                quoted_at: wire.quoted_at,
+                bag_fee: wire.bag_fee,
+                alternative: wire.alternative,
+            }
+        }
+    }
+
+    /// Cheapest other leg the search priced from where a leg leaves, on another day or to another stop
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct LegAlternative {
+        pub flight: Flight,
+        /// The alternative's fare, with the stay before it
+        pub price: f32,
+        /// What taking the chosen leg saves on the whole route, at least: every route through the alternative the search
+        /// got as far as costs this much more. Negative if a strategy which cuts corners left a cheaper one unexplored.
+        /// `None` if none of them could be finished.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub saving: Option<f32>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    #[serde(rename_all = "snake_case")]
+    pub enum TransportMode {
+        #[default]
+        Flight,
+        /// Train, bus or anything else overland
+        Ground,
+    }
+
+    /// Where a leg's price came from. Anything but a quote means the provider had no fare for the leg, or failed to
+    /// price it and the solve carried on without it.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    #[serde(rename_all = "snake_case")]
+    pub enum PriceSource {
+        /// Quoted by the provider the server prices with, or the fixed fare of a leg overland
+        #[default]
+        Quoted,
+        /// Quoted by the provider the server falls back on, its own failed to price the leg
+        Fallback,
+        /// Estimated from quotes on nearby days, the provider had no fare for the day
+        NearbyDays,
+        /// What the route usually costs on the day, from fares seen before
+        History,
+    }
+
+    impl PriceSource {
+        pub fn is_quoted(&self) -> bool {
+            *self == PriceSource::Quoted
+        }
+
+        /// Whether the price is a guess rather than a fare someone quoted
+        pub fn is_estimate(&self) -> bool {
+            matches!(self, PriceSource::NearbyDays | PriceSource::History)
+        }
+
+        pub fn confidence(&self) -> Confidence {
+            match self {
+                PriceSource::Quoted | PriceSource::Fallback => Confidence::High,
+                PriceSource::NearbyDays => Confidence::Medium,
+                PriceSource::History => Confidence::Low,
+            }
+        }
+    }
+
+    /// How far to trust a price to be near what the leg costs when it's booked
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Confidence {
+        Low,
+        Medium,
+        High,
     }
 }
 
-/**
+pub mod date_expr;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/*
  * Unit Tests
  */
 
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
 
-    use crate::queries::{Date, DateRestrictions, SingleDateRange};
-    use chrono::{Days, Duration};
+    use crate::date_expr::{parse_dates, DateParseError};
+    use crate::queries::{
+        nights_between, Airport, AirportCode, ApiError, CabinClass, Date, DateConstraints, DateMath, DateSampling, DateRestrictions, DateWindow, Destination, DestinationQuery, EventDates, Flight, LintWarning,
+        InvalidStay, JobStatus, FlightPrice, MonthSweep, Objective, Passengers, PriceSource, RouteLimits, RouteQuery, RouteSolution, SearchStrategy, SweepMonth, TemplateStop, TripComparison, TripOption, TripStructure, WhatIfDelta,
+        window_conflicts,
+    };
+    use chrono::Duration;
+    use std::collections::BTreeMap;
+
+    /// Stop at `iata` with no dates or limits of its own
+    fn test_dest(iata: &str) -> DestinationQuery {
+        DestinationQuery {
+            iata: iata.parse().unwrap(),
+            arrival_window: None,
+            departure_window: None,
+            min_days: None,
+            max_days: None,
+            event: None,
+            max_leg_price: None,
+        }
+    }
+
+    /// Undated round trip from Toronto through `hops`, one adult in economy
+    fn test_query(hops: &[&str]) -> RouteQuery {
+        RouteQuery {
+            start_city: test_dest("YYZ"),
+            end_city: test_dest("YYZ"),
+            hops: hops.iter().map(|iata| test_dest(iata)).collect(),
+            alternate_origins: Vec::new(),
+            origin_penalties: BTreeMap::new(),
+            currency: None,
+            market: None,
+            locale: None,
+            passengers: Passengers::default(),
+            cabin_class: CabinClass::Economy,
+            strategy: SearchStrategy::default(),
+            limits: RouteLimits::default(),
+            value_of_day: 0.0,
+            objective: Objective::Expected,
+            checked_bag: false,
+            sampling: None,
+        }
+    }
 
     #[test]
     fn test_date_cmp() {
@@ -300,14 +2388,15 @@ mod tests {
 
     #[test]
     fn test_date_iter_with_restrictions() {
-        let restrictions = Rc::new(DateRestrictions {
+        let restrictions = DateRestrictions {
             min_days: Some(Duration::days(2)),
             max_days: Some(Duration::days(4)),
-        });
-        let d_range = SingleDateRange::DateRange(
+        };
+        let d_range = DateWindow::new(
             Date::from_ymd_opt(2023, 3, 3).unwrap(),
             Date::from_ymd_opt(2023, 3, 10).unwrap(),
-        );
+        )
+        .unwrap();
         let mut d_r_iter = d_range.iter(restrictions);
 
         println!("Iter dump: {:?}", d_r_iter);
@@ -325,23 +2414,24 @@ mod tests {
 
     #[test]
     fn test_date_range_iter() {
-        let d_fixed_range = SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 3).unwrap());
-        let mut d_iter = d_fixed_range.iter(Rc::new(DateRestrictions {
+        let d_fixed_range = DateWindow::day(Date::from_ymd_opt(2023, 3, 3).unwrap());
+        let mut d_iter = d_fixed_range.iter(DateRestrictions {
             min_days: None,
             max_days: None,
-        }));
+        });
 
         assert_eq!(d_iter.next(), Some(Date::from_ymd_opt(2023, 3, 3).unwrap()));
         assert_eq!(d_iter.next(), None);
 
-        let d_range = SingleDateRange::DateRange(
+        let d_range = DateWindow::new(
             Date::from_ymd_opt(2023, 3, 3).unwrap(),
             Date::from_ymd_opt(2023, 3, 5).unwrap(),
-        );
-        let mut d_r_iter = d_range.iter(Rc::new(DateRestrictions {
+        )
+        .unwrap();
+        let mut d_r_iter = d_range.iter(DateRestrictions {
             min_days: None,
             max_days: None,
-        }));
+        });
 
         assert_eq!(
             d_r_iter.next(),
@@ -360,68 +2450,650 @@ mod tests {
 
     #[test]
     fn test_date_range_intersect() {
-        let d_fixed_fixed_no1 = SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 3).unwrap());
-        let d_fixed_fixed_no2 = SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 4).unwrap());
+        let day = |d| Date::from_ymd_opt(2023, 3, d).unwrap();
+        let window = |first, last| DateWindow::new(day(first), day(last)).unwrap();
 
-        assert_eq!(
-            d_fixed_fixed_no1.intersect(&d_fixed_fixed_no2),
-            SingleDateRange::None
-        );
+        assert_eq!(DateWindow::day(day(3)).intersect(&DateWindow::day(day(4))), None);
+        assert_eq!(DateWindow::day(day(3)).intersect(&DateWindow::day(day(3))), Some(DateWindow::day(day(3))));
+        assert_eq!(window(3, 10).intersect(&window(6, 18)), Some(window(6, 10)));
+        assert_eq!(window(3, 18).intersect(&window(6, 10)), Some(window(6, 10)));
+        assert_eq!(window(3, 6).intersect(&window(6, 10)), Some(DateWindow::day(day(6))));
 
-        let d_fixed_fixed1 = SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 3).unwrap());
-        let d_fixed_fixed2 = SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 3).unwrap());
+        // A window never ends before it starts
+        assert_eq!(DateWindow::new(day(4), day(3)), None);
+        assert_eq!(window(3, 6).until(day(2)), None);
+        assert_eq!(window(3, 6).since(day(5)), Some(window(5, 6)));
+        assert_eq!(window(3, 6).truncate(day(5)), Some(DateWindow::day(day(6))));
+        assert_eq!(window(3, 6).truncate(day(6)), None);
+    }
 
-        assert_eq!(
-            d_fixed_fixed1.intersect(&d_fixed_fixed2),
-            SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 3).unwrap())
-        );
+    #[test]
+    fn test_pin_leg() {
+        let mut query = test_query(&["YVR", "SEA"]);
+        let date = Date::from_ymd_opt(2023, 2, 4).unwrap();
+        let leg = |src: &str, dest: &str| Flight {
+            src: src.parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date,
+        };
 
-        let d_range_range1 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 3).unwrap(),
-            Date::from_ymd_opt(2023, 3, 10).unwrap(),
-        );
-        let d_range_range2 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 6).unwrap(),
-            Date::from_ymd_opt(2023, 3, 18).unwrap(),
-        );
+        assert!(query.pin_leg(&leg("YVR", "SEA")));
+        assert_eq!(query.hops[0].departure_window, Some(DateWindow::day(date)));
+        assert_eq!(query.hops[1].arrival_window, Some(DateWindow::day(date)));
 
-        assert_eq!(
-            d_range_range1.intersect(&d_range_range2),
-            SingleDateRange::DateRange(
-                Date::from_ymd_opt(2023, 3, 6).unwrap(),
-                Date::from_ymd_opt(2023, 3, 10).unwrap()
-            )
-        );
+        // Round trips pin the start's departure and the end's arrival, not the same destination twice
+        assert!(query.pin_leg(&leg("SEA", "YYZ")));
+        assert_eq!(query.end_city.arrival_window, Some(DateWindow::day(date)));
+        assert_eq!(query.start_city.departure_window, None);
 
-        let d_range_range_subset1 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 3).unwrap(),
-            Date::from_ymd_opt(2023, 3, 18).unwrap(),
-        );
-        let d_range_range_subset2 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 6).unwrap(),
-            Date::from_ymd_opt(2023, 3, 10).unwrap(),
-        );
+        assert!(!query.pin_leg(&leg("YVR", "LHR")));
+
+        // Leaving from another origin pins the start's departure
+        assert!(!query.pin_leg(&leg("YYC", "YVR")));
+        query.alternate_origins.push("YYC".parse().unwrap());
+        assert!(query.pin_leg(&leg("YYC", "YVR")));
+        assert_eq!(query.start_city.departure_window, Some(DateWindow::day(date)));
+    }
+
+    #[test]
+    fn test_swap_leg() {
+        let mut query = test_query(&["YVR", "SEA"]);
+        let date = Date::from_ymd_opt(2023, 2, 4).unwrap();
+        let leg = |src: &str, dest: &str| Flight {
+            src: src.parse().unwrap(),
+            dest: dest.parse().unwrap(),
+            date,
+        };
+
+        // Flying into Portland instead of Seattle moves the stop, the leg home now leaves from there
+        assert!(query.swap_leg(&leg("YVR", "SEA"), &leg("YVR", "PDX")));
+        assert_eq!(query.hops[1].iata, "PDX");
+        assert_eq!(query.hops[1].arrival_window, Some(DateWindow::day(date)));
+        assert_eq!(query.hops[0].departure_window, Some(DateWindow::day(date)));
 
+        // Only the destination and date can change
+        assert!(!query.swap_leg(&leg("PDX", "YYZ"), &leg("SEA", "YYZ")));
+        assert!(!query.swap_leg(&leg("YVR", "SEA"), &leg("YVR", "LAX")));
+    }
+
+    #[test]
+    fn test_what_if_apply() {
+        let mut query = test_query(&["YVR", "PDX"]);
+        let date = Date::from_ymd_opt(2023, 2, 4).unwrap();
+        let week = DateWindow::new(date, Date::from_ymd_opt(2023, 2, 10).unwrap());
+
+        // What-ifs change one thing
+        let mut what_if = query.clone();
+        assert!(what_if.apply(&WhatIfDelta::Window {
+            stop: 3,
+            arrival_window: Some(week),
+            departure_window: None,
+        }));
+        assert_eq!(what_if.end_city.arrival_window, week);
+        // And read back as they're written
+        let delta: WhatIfDelta = serde_json::from_str(r#"{"kind": "window", "stop": 3, "arrival_window": "flexible"}"#).unwrap();
         assert_eq!(
-            d_range_range_subset1.intersect(&d_range_range_subset2),
-            SingleDateRange::DateRange(
-                Date::from_ymd_opt(2023, 3, 6).unwrap(),
-                Date::from_ymd_opt(2023, 3, 10).unwrap()
-            )
+            delta,
+            WhatIfDelta::Window {
+                stop: 3,
+                arrival_window: Some(None),
+                departure_window: None,
+            }
         );
+        assert_eq!(serde_json::from_str::<WhatIfDelta>(&serde_json::to_string(&delta).unwrap()).unwrap(), delta);
+        assert!(what_if.apply(&WhatIfDelta::DropStop { stop: 1 }));
+        assert_eq!(what_if.hops.len(), 1);
+        assert_eq!(what_if.hops[0].iata, "PDX");
+        assert!(what_if.apply(&WhatIfDelta::DirectOnly));
+        assert!(what_if.limits.direct_only);
+        // The start and end can't be dropped, nor stops the itinerary doesn't have changed
+        assert!(!query.apply(&WhatIfDelta::DropStop { stop: 0 }));
+        assert!(!query.apply(&WhatIfDelta::DropStop { stop: 3 }));
+        assert!(!query.apply(&WhatIfDelta::Window {
+            stop: 4,
+            arrival_window: Some(week),
+            departure_window: None,
+        }));
+    }
 
-        let d_range_range_shared1 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 3).unwrap(),
-            Date::from_ymd_opt(2023, 3, 6).unwrap(),
-        );
-        let d_range_range_shared2 = SingleDateRange::DateRange(
-            Date::from_ymd_opt(2023, 3, 6).unwrap(),
-            Date::from_ymd_opt(2023, 3, 10).unwrap(),
-        );
+    #[test]
+    fn test_round_trips() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let dest = |iata, arrival_window, departure_window| DestinationQuery {
+            arrival_window,
+            departure_window,
+            ..test_dest(iata)
+        };
+        let query = RouteQuery {
+            start_city: dest("YYZ", None, Some(DateWindow::day(day(1)))),
+            end_city: dest("YYZ", Some(DateWindow::day(day(9))), None),
+            hops: vec![
+                dest("YVR", DateWindow::new(day(2), day(3)), None),
+                dest("SEA", None, DateWindow::new(day(6), day(8))),
+            ],
+            currency: Some("CAD".to_string()),
+            cabin_class: CabinClass::Business,
+            ..test_query(&[])
+        };
+
+        // Each trip can leave home and get back any time during the whole trip
+        let trips = query.round_trips();
+        assert_eq!(trips.len(), 2);
+        for (trip, hop) in trips.iter().zip(&query.hops) {
+            assert_eq!(trip.hops[0].iata, hop.iata);
+            assert_eq!(trip.start_city.departure_window, DateWindow::new(day(1), day(9)));
+            assert_eq!(trip.end_city.arrival_window, DateWindow::new(day(1), day(9)));
+            assert_eq!(trip.cabin_class, CabinClass::Business);
+        }
+
+        let solution = |total_price| RouteSolution {
+            flights: Vec::new(),
+            total_price,
+            passengers: 1,
+            currency: "CAD".to_string(),
+            alternatives: Vec::new(),
+            request_id: None,
+            origin: None,
+            stats: None,
+        };
+        let combined = TripOption::new(TripStructure::Combined, vec![Ok(solution(900.0))]);
+        let separate = TripOption::new(TripStructure::SeparateRoundTrips, vec![Ok(solution(400.0)), Ok(solution(450.0))]);
+        assert_eq!(separate.total_price, Some(850.0));
+        let comparison = TripComparison::new(vec![combined.clone(), separate]);
+        assert_eq!(comparison.cheapest, Some(TripStructure::SeparateRoundTrips));
+
+        // A failed round trip rules the structure out
+        let failed = TripOption::new(TripStructure::SeparateRoundTrips, vec![Ok(solution(1.0)), Err(ApiError::Infeasible("No route".to_string()))]);
+        assert_eq!(failed.total_price, None);
+        assert_eq!(failed.failure.as_ref().map(ApiError::message), Some("No route"));
+        assert_eq!(TripComparison::new(vec![combined, failed]).cheapest, Some(TripStructure::Combined));
+
+        // Round trips away at once can't both be flown, one can leave the day another gets back
+        let away = |to: &str, out: u32, back: u32| {
+            let leg = |src: &str, dest: &str, d: u32| {
+                let flight = format!(r#""src": "{}", "dest": "{}", "date": "2023-02-0{}""#, src, dest, d);
+                let json = format!(r#"{{"flight": {{{}}}, "price": 50.0}}"#, flight);
+                serde_json::from_str::<FlightPrice>(&json).unwrap()
+            };
+            Ok(RouteSolution {
+                flights: vec![leg("YYZ", to, out), leg(to, "YYZ", back)],
+                ..solution(100.0)
+            })
+        };
+        let separate = |trips| TripOption::new(TripStructure::SeparateRoundTrips, trips);
+        assert_eq!(separate(vec![away("YVR", 1, 3), away("SEA", 3, 5)]).total_price, Some(200.0));
+        let overlapping = separate(vec![away("YVR", 1, 4), away("SEA", 7, 9), away("SFO", 3, 5)]);
+        assert_eq!(overlapping.total_price, None);
+        assert!(matches!(overlapping.failure, Some(ApiError::Infeasible(_))));
+    }
+
+    #[test]
+    fn test_shifted_months() {
+        let day = |m, d| Date::from_ymd_opt(2023, m, d).unwrap();
+        let dest = |iata, arrival_window, departure_window| DestinationQuery {
+            arrival_window,
+            departure_window,
+            ..test_dest(iata)
+        };
+        let query = RouteQuery {
+            start_city: dest("YYZ", None, DateWindow::new(day(1, 31), day(2, 2))),
+            end_city: dest("YYZ", Some(DateWindow::day(day(2, 12))), None),
+            hops: vec![DestinationQuery {
+                event: Some(EventDates {
+                    first_day: day(2, 6),
+                    last_day: day(2, 7),
+                }),
+                ..dest("YVR", None, None)
+            }],
+            ..test_query(&[])
+        };
+        assert_eq!(query.shifted_months(0).unwrap().1.start_city.departure_window, query.start_city.departure_window);
+
+        // A month on from January 31st is the end of February, everything moves the same 28 days
+        let (starts, shifted) = query.shifted_months(1).unwrap();
+        assert_eq!(starts, day(2, 28));
+        assert_eq!(shifted.start_city.departure_window, DateWindow::new(day(2, 28), day(3, 2)));
+        assert_eq!(shifted.end_city.arrival_window, Some(DateWindow::day(day(3, 12))));
+        assert_eq!(shifted.hops[0].event.unwrap().first_day, day(3, 6));
+
+        // Nothing to move without dates
+        let undated = RouteQuery {
+            start_city: dest("YYZ", None, None),
+            end_city: dest("YYZ", None, None),
+            hops: Vec::new(),
+            ..query
+        };
+        assert!(undated.shifted_months(1).is_none());
+
+        let months = vec![
+            SweepMonth::new(day(3, 1), Ok(RouteSolution {
+                flights: Vec::new(),
+                total_price: 500.0,
+                passengers: 1,
+                currency: "USD".to_string(),
+                alternatives: Vec::new(),
+                request_id: None,
+                origin: None,
+                stats: None,
+            })),
+            SweepMonth::new(day(4, 1), Err(ApiError::Infeasible("No route".to_string()))),
+        ];
+        assert_eq!(MonthSweep::new(months).cheapest, Some(day(3, 1)));
+    }
+
+    #[test]
+    fn test_query_options_default() {
+        // Queries from before the options existed are one adult in economy
+        let query: RouteQuery = serde_json::from_str(
+            r#"{"start_city": {"iata": "YYZ", "dates": ["None", "None"], "min_days": null, "max_days": null},
+                "end_city": {"iata": "YVR", "dates": ["None", "None"], "min_days": null, "max_days": null},
+                "hops": []}"#,
+        )
+        .unwrap();
+
+        assert_eq!(query.passengers, Passengers { adults: 1, children: 0 });
+        assert_eq!(query.cabin_class, CabinClass::Economy);
+        assert_eq!(query.strategy, SearchStrategy::Dijkstra);
+        assert_eq!(query.limits, RouteLimits::default());
+        assert!(query.origin_penalties.is_empty());
+        let penalties: BTreeMap<AirportCode, f32> = serde_json::from_str(r#"{ "sea": 80.0 }"#).unwrap();
+        assert_eq!(penalties[&"SEA".parse::<AirportCode>().unwrap()], 80.0);
+
+        let beam: SearchStrategy = serde_json::from_str(r#"{ "beam": { "width": 50 } }"#).unwrap();
+        assert_eq!(beam, SearchStrategy::Beam { width: 50 });
+        let beam: SearchStrategy = serde_json::from_str(r#"{ "beam": {} }"#).unwrap();
+        assert_eq!(beam, SearchStrategy::Beam { width: SearchStrategy::DEFAULT_BEAM_WIDTH });
+        assert!(!SearchStrategy::Beam { width: 0 }.is_valid());
+        assert_eq!(query.sampling, None);
+        let sampling: DateSampling = serde_json::from_str(r#"{ "every": 7 }"#).unwrap();
+        assert_eq!(sampling, DateSampling::every(7));
+        assert!(!DateSampling { refine: 0, ..sampling }.is_valid());
+        assert!(!DateSampling::every(DateSampling::MAX_EVERY + 1).is_valid());
+        // The 1st falls between the sampled days either side, the same for every route
+        let day = |d| Date::from_ymd_opt(2023, 6, d).unwrap();
+        let june = DateWindow::new(Date::from_ymd_opt(2023, 5, 1).unwrap(), day(30)).unwrap();
+        assert!(!sampling.prices(day(1), june));
+        let [(before_days, before), (after_days, after)] = sampling.sampled_around(day(1), june);
+        assert!(sampling.is_sampled(before) && sampling.is_sampled(after));
+        assert_eq!((before_days + after_days, after - before), (7, Duration::days(7)));
+        assert_eq!(before + Duration::days(before_days as i64), day(1));
+        // Sampled days outside the window move to its ends, windows no wider than the gap between them are all priced
+        let first_week = DateWindow::new(before + Duration::days(1), after + Duration::days(3)).unwrap();
+        let clamped = [(before_days - 1, first_week.first()), (after_days, after)];
+        assert_eq!(sampling.sampled_around(day(1), first_week), clamped);
+        assert!(sampling.prices(day(1), DateWindow::new(before, after - Duration::days(1)).unwrap()));
+        // Sampling far enough apart to leave the calendar stays in the window
+        let monthly = DateSampling::every(DateSampling::MAX_EVERY);
+        let end = DateWindow::new(Date::MAX - Duration::days(40), Date::MAX).unwrap();
+        let [(_, before), (_, after)] = monthly.sampled_around(Date::MAX - Duration::days(1), end);
+        assert!(end.contains(before) && end.contains(after));
+        assert!(!Passengers { adults: 0, children: 2 }.is_valid());
+        assert!(!Passengers { adults: u32::MAX, children: 1 }.is_valid());
+
+        assert_eq!(query.objective, Objective::Expected);
+        let pessimistic: Objective = serde_json::from_str(r#"{ "pessimistic": {} }"#).unwrap();
+        assert_eq!(pessimistic, Objective::Pessimistic { sigmas: Objective::DEFAULT_SIGMAS });
+        assert!(!Objective::Pessimistic { sigmas: -1.0 }.is_valid());
+    }
+
+    #[test]
+    fn test_destination_windows() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+
+        // The old (arrival, departure) pair is read into the named windows, which take precedence when both are given
+        let legacy: DestinationQuery = serde_json::from_str(
+            r#"{"iata": "YVR", "dates": [{"FixedDate": "2023-02-01"}, {"DateRange": ["2023-02-03", "2023-02-05"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.arrival_window, Some(DateWindow::day(day(1))));
+        assert_eq!(legacy.departure_window, DateWindow::new(day(3), day(5)));
+        let both: DestinationQuery = serde_json::from_str(
+            r#"{"iata": "YVR", "dates": ["None", "None"], "departure_window": {"FixedDate": "2023-02-04"}}"#,
+        )
+        .unwrap();
+        assert_eq!(both.arrival_window, None);
+        assert_eq!(both.departure_window, Some(DateWindow::day(day(4))));
+        let json = serde_json::to_string(&both).unwrap();
+        assert!(json.contains(r#""arrival_window":"flexible","departure_window":"2023-02-04""#));
+        let round_trip: DestinationQuery = serde_json::from_str(&serde_json::to_string(&legacy).unwrap()).unwrap();
+        assert_eq!(round_trip.departure_window, legacy.departure_window);
+        // A window ending before it starts doesn't parse
+        let reversed = r#"{"iata": "YVR", "arrival_window": {"DateRange": ["2023-02-05", "2023-02-03"]}}"#;
+        assert!(serde_json::from_str::<DestinationQuery>(reversed).is_err());
+
+        // Stays of 2 to 4 days
+        let mut dest = DestinationQuery {
+            min_days: Some(3),
+            max_days: Some(3),
+            ..legacy
+        };
+        assert_eq!(dest.validate(), Ok(()));
+        // The stay limits go over the wire as whole days, along with the rest of the constraints
+        let json = serde_json::to_string(&dest.to_destination().dates).unwrap();
+        assert!(json.contains(r#""date_restrictions":{"min_days":3,"max_days":3}"#));
+        dest.min_days = Some(5);
+        dest.max_days = None;
+        assert_eq!(dest.validate(), Err(InvalidStay::TooShort));
+        dest.min_days = None;
+        dest.max_days = Some(1);
+        assert_eq!(dest.validate(), Err(InvalidStay::TooLong));
+        dest.min_days = Some(2);
+        assert_eq!(dest.validate(), Err(InvalidStay::MinAboveMax));
+
+        dest.min_days = None;
+        dest.max_days = None;
+        dest.departure_window = DateWindow::new(day(1) - Duration::days(10), day(1) - Duration::days(5));
+        assert_eq!(dest.validate(), Err(InvalidStay::DepartsBeforeArrival));
+        dest.arrival_window = None;
+        assert_eq!(dest.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_window_conflicts() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let stop = |arrival: Option<(u32, u32)>, departure: Option<(u32, u32)>| DateConstraints {
+            arrival_window: arrival.and_then(|(first, last)| DateWindow::new(day(first), day(last))),
+            departure_window: departure.and_then(|(first, last)| DateWindow::new(day(first), day(last))),
+            date_restrictions: DateRestrictions::default(),
+            event: None,
+        };
+
+        let start = stop(None, Some((1, 2)));
+        let end = stop(Some((20, 21)), None);
+        let early = stop(Some((3, 4)), Some((8, 9)));
+        let late = stop(Some((10, 12)), Some((14, 16)));
+        assert!(early.can_precede(&late) && !late.can_precede(&early));
+        assert_eq!(window_conflicts(&[start.clone(), late.clone(), early.clone(), end.clone()]), []);
+
+        // Two stops wanting the same days, and one that can't be left before the trip is due home
+        let clash = stop(Some((3, 4)), Some((8, 9)));
+        let too_late = stop(Some((19, 20)), Some((22, 23)));
+        let stops = [start.clone(), early.clone(), clash, too_late, end.clone()];
+        assert_eq!(window_conflicts(&stops), [(1, 2), (3, 4)]);
+
+        // A minimum stay or an event can rule an order out just as well as the windows
+        let mut long_stay = stop(Some((3, 4)), None);
+        long_stay.date_restrictions.add_min_days_constraint(Duration::days(10));
+        assert!(!long_stay.can_precede(&stop(Some((5, 12)), None)));
+        let mut event = stop(None, None);
+        event.event = Some(EventDates { first_day: day(15), last_day: day(17) });
+        assert!(!event.can_precede(&late) && late.can_precede(&event));
+        // Nothing to go on either side
+        assert!(stop(None, None).can_precede(&stop(None, None)));
+    }
+
+    #[test]
+    fn test_event_dates() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let window = |first, last| DateWindow::new(day(first), day(last));
+
+        // At a conference from the 4th to the 6th, with room either side
+        let mut dest = DestinationQuery {
+            iata: "YVR".parse().unwrap(),
+            arrival_window: window(1, 5),
+            departure_window: window(3, 9),
+            min_days: None,
+            max_days: None,
+            event: Some(EventDates { first_day: day(4), last_day: day(6) }),
+            max_leg_price: None,
+        };
+        assert_eq!(dest.validate(), Ok(()));
+
+        let before = DateConstraints {
+            arrival_window: None,
+            departure_window: window(1, 10),
+            date_restrictions: DateRestrictions::default(),
+            event: None,
+        };
+        let after = DateConstraints { arrival_window: window(1, 10), ..before.clone() };
+        let dates = dest.to_destination().dates;
+        let leg_in: Vec<Date> = before.get_intersect_iter_with_next(&dates, None).collect();
+        let leg_out: Vec<Date> = dates.get_intersect_iter_with_next(&after, None).collect();
+        assert_eq!(leg_in, [day(1), day(2), day(3), day(4)]);
+        assert_eq!(leg_out, [day(6), day(7), day(8), day(9)]);
+        assert!(dates.arrives_in_time(day(4)) && !dates.arrives_in_time(day(5)));
+
+        // Without windows the trip arrives the day the event starts
+        dest.arrival_window = None;
+        let leg_in: Vec<Date> = before.get_intersect_iter_with_next(&dest.to_destination().dates, None).collect();
+        assert_eq!(leg_in, [day(4)]);
+
+        dest.arrival_window = window(5, 6);
+        assert_eq!(dest.validate(), Err(InvalidStay::MissesEvent));
+        dest.arrival_window = window(1, 5);
+        dest.max_days = Some(1);
+        assert_eq!(dest.validate(), Err(InvalidStay::TooLong));
+        dest.max_days = None;
+        dest.event = Some(EventDates { first_day: day(6), last_day: day(4) });
+        assert_eq!(dest.validate(), Err(InvalidStay::EventEndsBeforeStart));
+    }
+
+    #[test]
+    fn test_airport_code() {
+        let code: AirportCode = "yyz".parse().unwrap();
+        assert_eq!(code, "YYZ");
+        assert_eq!(serde_json::to_string(&code).unwrap(), r#""YYZ""#);
+        assert_eq!(serde_json::from_str::<AirportCode>(r#""YVR""#).unwrap(), "YVR");
+
+        assert!("YY".parse::<AirportCode>().is_err());
+        assert!("YYZZ".parse::<AirportCode>().is_err());
+        assert!("Y Z".parse::<AirportCode>().is_err());
+        assert!(serde_json::from_str::<AirportCode>(r#""TORONTO""#).is_err());
+    }
+
+    #[test]
+    fn test_template_stop_dates() {
+        let stop = TemplateStop {
+            iata: "SIN".to_string(),
+            fly_in: Some((0, 2)),
+            fly_out: None,
+        };
+        let start = Date::from_ymd_opt(2023, 2, 27).unwrap();
 
         assert_eq!(
-            d_range_range_shared1.intersect(&d_range_range_shared2),
-            SingleDateRange::FixedDate(Date::from_ymd_opt(2023, 3, 6).unwrap())
+            stop.fly_in_dates(start),
+            Some((start, Date::from_ymd_opt(2023, 3, 1).unwrap()))
         );
+        assert_eq!(stop.fly_out_dates(start), None);
+    }
+
+    #[test]
+    fn test_airport_flag() {
+        let mut airport = Airport {
+            iata: "YYZ".to_string(),
+            name: "Toronto Pearson International".to_string(),
+            city: "Toronto".to_string(),
+            country: "CA".to_string(),
+            lat: 43.68,
+            lon: -79.62,
+            tz: Some("America/Toronto".to_string()),
+        };
+        assert_eq!(airport.flag().as_deref(), Some("\u{1F1E8}\u{1F1E6}"));
+
+        airport.country = "Canada".to_string();
+        assert_eq!(airport.flag(), None);
+    }
+
+    #[test]
+    fn test_api_error_codes() {
+        let error = ApiError::RateLimited("Daily API quota exceeded".to_string());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"code":"rate_limited","message":"Daily API quota exceeded"}"#);
+        assert_eq!(ApiError::from_status(error.status(), json), error);
+
+        // Bodies that aren't an error, e.g. from a proxy in front of the server, go by the status
+        assert_eq!(ApiError::from_status(502, "Bad gateway".to_string()).code(), "provider_unavailable");
+
+        // Jobs that failed before errors had codes still load
+        let old: JobStatus = serde_json::from_str(r#"{"state":"failed","reason":"Solver crashed unexpectedly"}"#).unwrap();
+        assert_eq!(old, JobStatus::Failed { error: ApiError::Internal("Solver crashed unexpectedly".to_string()) });
+    }
+
+    #[test]
+    fn test_lint() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let dest = |iata, arrival_window, departure_window| DestinationQuery {
+            arrival_window,
+            departure_window,
+            ..test_dest(iata)
+        };
+        let coordinates = |code: AirportCode| match code.as_str() {
+            "YYZ" => Some((43.68, -79.63)),
+            "LHR" => Some((51.47, -0.45)),
+            "CDG" => Some((49.01, 2.55)),
+            _ => None,
+        };
+        let mut query = RouteQuery {
+            start_city: dest("YYZ", None, Some(DateWindow::day(day(1)))),
+            end_city: dest("YYZ", Some(DateWindow::day(day(20))), None),
+            hops: vec![
+                dest("LHR", Some(DateWindow::day(day(2))), DateWindow::new(day(5), day(6))),
+                dest("CDG", None, None),
+            ],
+            ..test_query(&[])
+        };
+        assert_eq!(query.lint(coordinates), []);
+
+        // A night in London after crossing the Atlantic, and Paris reached the same day
+        query.hops[0].max_days = Some(1);
+        query.hops[1].arrival_window = Some(DateWindow::day(day(2)));
+        let warnings = query.lint(coordinates);
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(warnings[0], LintWarning::ShortStayAfterLongHaul { distance_km: 5000..=6000, .. }));
+        assert!(warnings[0].to_string().starts_with("At most a night at LHR"));
+        assert!(matches!(warnings[1], LintWarning::SameDayHop { .. }));
+
+        // A short hop is fine however short the stay, a long window isn't
+        query.hops[1] = dest("CDG", DateWindow::new(day(7), day(7).add_days_saturating(40)), None);
+        query.hops[1].max_days = Some(1);
+        let warnings = query.lint(coordinates);
+        assert!(matches!(warnings[..], [LintWarning::WideWindow { days: 41, .. }, LintWarning::ShortStayAfterLongHaul { .. }]));
+    }
+
+    #[test]
+    fn test_window_wire_format() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        let read = |json: &str| serde_json::from_str::<DestinationQuery>(json).map_err(|e| e.to_string());
+
+        let dest = read(r#"{"iata": "YVR", "arrival_window": {"start": "2023-02-01", "end": "2023-02-03"},
+            "departure_window": "2023-02-06"}"#)
+        .unwrap();
+        assert_eq!(dest.arrival_window, DateWindow::new(day(1), day(3)));
+        assert_eq!(dest.departure_window, Some(DateWindow::day(day(6))));
+        let json = serde_json::to_string(&dest).unwrap();
+        assert!(json.contains(r#""arrival_window":{"start":"2023-02-01","end":"2023-02-03"},"departure_window":"2023-02-06""#));
+        // Flexible whichever way it's said
+        for flexible in [r#""flexible""#, "null", r#""None""#] {
+            let dest = read(&format!(r#"{{"iata": "YVR", "arrival_window": {}}}"#, flexible)).unwrap();
+            assert_eq!(dest.arrival_window, None);
+        }
+
+        // Errors say which window was wrong and how
+        let error = read(r#"{"iata": "YVR", "departure_window": "Feb 3rd"}"#).unwrap_err();
+        assert!(error.starts_with(r#"departure_window: "Feb 3rd" isn't an ISO-8601 date"#), "{}", error);
+        let error = read(r#"{"iata": "YVR", "arrival_window": {"start": "2023-02-05", "end": "2023-02-03"}}"#).unwrap_err();
+        assert!(error.starts_with("arrival_window: range ends on 2023-02-03 before it starts on 2023-02-05"), "{}", error);
+        let error = read(r#"{"iata": "YVR", "arrival_window": {"start": "2023-02-05"}}"#).unwrap_err();
+        assert!(error.starts_with(r#"arrival_window: range is missing its "end""#), "{}", error);
+        let error = read(r#"{"iata": "YVR", "arrival_window": 20230205}"#).unwrap_err();
+        assert!(error.contains(r#"expected arrival_window to be a date like "2023-02-01""#), "{}", error);
+    }
+
+    #[test]
+    fn test_price_source_wire_format() {
+        let read = |extra: &str| {
+            let flight = r#""flight": {"src": "YYZ", "dest": "YVR", "date": "2023-02-01"}"#;
+            let json = format!(r#"{{{}, "price": 250.0{}}}"#, flight, extra);
+            serde_json::from_str::<FlightPrice>(&json).unwrap().source
+        };
+        assert_eq!(read(""), PriceSource::Quoted);
+        assert_eq!(read(r#", "source": "fallback""#), PriceSource::Fallback);
+        // Legs saved before they had a source only said whether they were estimated, from nearby days
+        assert_eq!(read(r#", "estimated": true"#), PriceSource::NearbyDays);
+        assert_eq!(read(r#", "estimated": true, "source": "history""#), PriceSource::History);
+    }
+
+    #[test]
+    fn test_date_math() {
+        let day = |d| Date::from_ymd_opt(2023, 2, d).unwrap();
+        assert_eq!(day(10).days_since(day(3)), 7);
+        assert_eq!(day(3).add_days_saturating(-5), Date::from_ymd_opt(2023, 1, 29).unwrap());
+        assert_eq!(Date::MAX.add_days_saturating(1), Date::MAX);
+        assert_eq!(Date::MIN.add_days_saturating(i64::MIN), Date::MIN);
+
+        // Leaving before landing isn't negative nights
+        assert_eq!(nights_between(day(3), day(6)), 3);
+        assert_eq!(nights_between(day(6), day(3)), 0);
+
+        let window = DateWindow::new(day(3), day(6)).unwrap();
+        assert_eq!(window.num_days(), 4);
+        assert_eq!(DateWindow::day(day(3)).num_days(), 1);
+        assert!(window.contains(day(6)) && !window.contains(day(7)));
+        assert_eq!(Some(window.shift(-2)), DateWindow::new(day(1), day(4)));
+
+        // The last day of the calendar is iterated once rather than forever
+        let end = DateWindow::new(Date::MAX.add_days_saturating(-1), Date::MAX).unwrap();
+        assert_eq!(end.iter(DateRestrictions::default()).count(), 2);
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let today = Date::from_ymd_opt(2024, 3, 15).unwrap();
+        let day = |m, d| Date::from_ymd_opt(2024, m, d).unwrap();
+        let window = |text| parse_dates(text, today).map(|dates| dates.span());
+
+        assert_eq!(window("2024-06-01..2024-06-10"), Ok(DateWindow::new(day(6, 1), day(6, 10)).unwrap()));
+        assert_eq!(window(" 2024-06-01 "), Ok(DateWindow::day(day(6, 1))));
+        assert_eq!(window("Early June"), Ok(DateWindow::new(day(6, 1), day(6, 10)).unwrap()));
+        assert_eq!(window("late feb 2024"), Ok(DateWindow::new(day(2, 21), day(2, 29)).unwrap()));
+        // Months already over this year are next year's
+        let next_year = |d| Date::from_ymd_opt(2025, 1, d).unwrap();
+        assert_eq!(window("mid jan"), Ok(DateWindow::new(next_year(11), next_year(20)).unwrap()));
+        assert_eq!(window("march").map(|w| w.first()), Ok(day(3, 1)));
+
+        // June 2024 starts on a Saturday and ends on a Sunday
+        let weekends = parse_dates("any weekend in June", today).unwrap();
+        assert_eq!(weekends.windows().len(), 5);
+        assert_eq!(weekends.windows()[0], DateWindow::new(day(6, 1), day(6, 2)).unwrap());
+        assert!(weekends.contains(day(6, 30)) && !weekends.contains(day(6, 3)));
+        assert_eq!(weekends.days().count(), 10);
+        // September 2024 starts on a Sunday and ends on a Monday
+        let weekends = parse_dates("weekends in sep", today).unwrap();
+        assert_eq!(weekends.windows()[0], DateWindow::day(day(9, 1)));
+        assert_eq!(weekends.span(), DateWindow::new(day(9, 1), day(9, 29)).unwrap());
+
+        assert_eq!(window(""), Err(DateParseError::Empty));
+        assert!(matches!(window("sometime soon"), Err(DateParseError::Unrecognized(_))));
+        assert!(matches!(window("ju"), Err(DateParseError::Unrecognized(_))));
+        assert!(matches!(window("2024-06-10..2024-06-01"), Err(DateParseError::Reversed(_))));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_intersect_commutes(a: DateWindow, b: DateWindow) {
+            proptest::prop_assert_eq!(a.intersect(&b), b.intersect(&a));
+        }
+
+        #[test]
+        fn test_constraints_round_trip(destination: Destination) {
+            let json = serde_json::to_string(&destination.dates).unwrap();
+            proptest::prop_assert_eq!(serde_json::from_str::<DateConstraints>(&json).unwrap(), destination.dates);
+        }
+
+        #[test]
+        fn test_iter_respects_restrictions(
+            window: DateWindow,
+            restrictions: DateRestrictions,
+            src_date in proptest::option::of(crate::testing::date()),
+        ) {
+            for date in window.iter_partial(restrictions, src_date) {
+                proptest::prop_assert!(window.contains(date));
+                if let Some(src_date) = src_date {
+                    let stay = Duration::days(date.days_since(src_date));
+                    proptest::prop_assert!(restrictions.min_days.is_none_or(|min| stay >= min));
+                    proptest::prop_assert!(restrictions.max_days.is_none_or(|max| stay < max));
+                }
+            }
+        }
     }
 }
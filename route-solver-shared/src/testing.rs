@@ -0,0 +1,103 @@
+//! Proptest strategies for the date model, behind the `testing` feature.
+//!
+//! Every date generated falls in the first days of February 2023, the days the backend's mock fares cover, so generated
+//! destinations can be solved as well as iterated. Windows always have their first day on or before their last.
+
+use chrono::Duration;
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    option,
+    prop_oneof,
+    sample::select,
+    strategy::{BoxedStrategy, Strategy},
+};
+
+use crate::queries::{
+    AirportCode, Date, DateConstraints, DateMath, DateRestrictions, DateWindow, Destination, EventDates,
+};
+
+/// Airports destinations are generated at, the ones the backend's mock fares cover
+pub const AIRPORTS: [&str; 4] = ["YYZ", "YVR", "YYC", "SEA"];
+
+/// First day dates are generated from
+pub fn first_day() -> Date {
+    Date::from_ymd_opt(2023, 2, 1).unwrap()
+}
+
+/// One of the first 8 days of February 2023
+pub fn date() -> impl Strategy<Value = Date> {
+    (0..8i64).prop_map(|offset| first_day().add_days_saturating(offset))
+}
+
+/// One of [AIRPORTS]
+pub fn airport() -> impl Strategy<Value = AirportCode> {
+    select(AIRPORTS.to_vec()).prop_map(|code| code.parse().unwrap())
+}
+
+impl Arbitrary for DateWindow {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<DateWindow>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            date().prop_map(DateWindow::day),
+            (date(), 0..5i64).prop_map(|(first, days)| DateWindow::new(first, first.add_days_saturating(days)).unwrap()),
+        ]
+        .boxed()
+    }
+}
+
+/// A window a destination may leave unbounded, as often unbounded as a single day or a few
+pub fn window() -> impl Strategy<Value = Option<DateWindow>> {
+    option::weighted(2.0 / 3.0, any::<DateWindow>())
+}
+
+impl Arbitrary for DateRestrictions {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<DateRestrictions>;
+
+    /// The minimum stay is never longer than the maximum, as [DestinationQuery::validate] rejects those
+    ///
+    /// [DestinationQuery::validate]: crate::queries::DestinationQuery::validate
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (option::of(0..7i64), option::of(0..14i64))
+            .prop_map(|(min, extra)| DateRestrictions {
+                min_days: min.map(Duration::days),
+                max_days: extra.map(|extra| Duration::days(min.unwrap_or(0) + extra)),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Destination {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Destination>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let event = option::weighted(0.2, (date(), 0..4i64)).prop_map(|event| {
+            event.map(|(first_day, days)| EventDates {
+                first_day,
+                last_day: first_day.add_days_saturating(days),
+            })
+        });
+        (
+            airport(),
+            window(),
+            window(),
+            any::<DateRestrictions>(),
+            event,
+            option::weighted(0.2, 50.0..2000.0f32),
+        )
+            .prop_map(|(iata, arrival_window, departure_window, restrictions, event, max_leg_price)| Destination {
+                iata,
+                dates: DateConstraints {
+                    arrival_window,
+                    departure_window,
+                    date_restrictions: restrictions,
+                    event,
+                },
+                max_leg_price,
+            })
+            .boxed()
+    }
+}